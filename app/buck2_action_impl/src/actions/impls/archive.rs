@@ -0,0 +1,696 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+use std::borrow::Cow;
+use std::collections::btree_map;
+use std::collections::BTreeMap;
+use std::fs::Metadata;
+use std::io;
+use std::io::BufWriter;
+use std::io::Seek;
+use std::io::Write;
+use std::time::Instant;
+
+use allocative::Allocative;
+use anyhow::Context as _;
+use async_trait::async_trait;
+use buck2_artifact::artifact::build_artifact::BuildArtifact;
+use buck2_build_api::actions::box_slice_set::BoxSliceSet;
+use buck2_build_api::actions::execute::action_executor::ActionExecutionKind;
+use buck2_build_api::actions::execute::action_executor::ActionExecutionMetadata;
+use buck2_build_api::actions::execute::action_executor::ActionOutputs;
+use buck2_build_api::actions::Action;
+use buck2_build_api::actions::ActionExecutable;
+use buck2_build_api::actions::ActionExecutionCtx;
+use buck2_build_api::actions::IncrementalActionExecutable;
+use buck2_build_api::actions::UnregisteredAction;
+use buck2_build_api::artifact_groups::ArtifactGroup;
+use buck2_build_api::interpreter::rule_defs::artifact::associated::AssociatedArtifacts;
+use buck2_common::file_ops::FileDigestConfig;
+use buck2_core::category::Category;
+use buck2_core::fs::fs_util;
+use buck2_core::fs::paths::abs_norm_path::AbsNormPath;
+use buck2_core::fs::paths::abs_norm_path::AbsNormPathBuf;
+use buck2_core::fs::paths::file_name::FileName;
+use buck2_core::fs::paths::forward_rel_path::ForwardRelativePath;
+use buck2_core::fs::paths::forward_rel_path::ForwardRelativePathBuf;
+use buck2_core::fs::project_rel_path::ProjectRelativePathBuf;
+use buck2_execute::artifact::artifact_dyn::ArtifactDyn;
+use buck2_execute::artifact::fs::ExecutorFs;
+use buck2_execute::artifact_value::ArtifactValue;
+use buck2_execute::directory::INTERNER;
+use buck2_execute::entry::build_entry_from_disk;
+use buck2_execute::execute::command_executor::ActionExecutionTimingData;
+use dupe::Dupe;
+use gazebo::prelude::*;
+use indexmap::indexmap;
+use indexmap::IndexMap;
+use indexmap::IndexSet;
+use once_cell::sync::Lazy;
+use starlark::values::OwnedFrozenValue;
+use starlark::values::Value;
+use starlark::values::ValueError;
+use thiserror::Error;
+
+use crate::actions::impls::symlinked_dir::UnregisteredSymlinkedDirAction;
+
+#[derive(Debug, Error)]
+enum ArchiveActionError {
+    #[error("Unknown archive format `{0}`, expected one of `zip`, `tar` or `tar.zst`")]
+    UnknownFormat(String),
+    #[error("Exactly one output file must be specified for an archive action, got {0}")]
+    WrongNumberOfOutputs(usize),
+    #[error("Path `{0}` would be added to the archive more than once")]
+    DuplicatePath(ForwardRelativePathBuf),
+    #[error("Path `{0}` is of an unsupported file type")]
+    UnsupportedFileType(AbsNormPathBuf),
+    #[error("Directory `{0}` contains itself through a symlink")]
+    SymlinkCycle(AbsNormPathBuf),
+}
+
+/// Permissions written for every archive entry, so that the archive does not depend on the umask
+/// of the machine that produced its inputs. Only the executable bit of files is preserved.
+const FILE_MODE: u32 = 0o644;
+const EXECUTABLE_FILE_MODE: u32 = 0o755;
+const DIRECTORY_MODE: u32 = 0o755;
+
+#[derive(Debug, Clone, Copy, Dupe, PartialEq, Eq, Allocative)]
+pub(crate) enum ArchiveFormat {
+    Zip,
+    Tar,
+    TarZst,
+}
+
+impl ArchiveFormat {
+    pub(crate) fn from_name(name: &str) -> anyhow::Result<Self> {
+        match name {
+            "zip" => Ok(Self::Zip),
+            "tar" => Ok(Self::Tar),
+            "tar.zst" => Ok(Self::TarZst),
+            _ => Err(ArchiveActionError::UnknownFormat(name.to_owned()).into()),
+        }
+    }
+
+    /// Guess the format of an archive from its file name.
+    pub(crate) fn from_file_name(file_name: &FileName) -> Option<Self> {
+        let file_name = file_name.as_str();
+        if file_name.ends_with(".zip") {
+            Some(Self::Zip)
+        } else if file_name.ends_with(".tar") {
+            Some(Self::Tar)
+        } else if file_name.ends_with(".tar.zst") || file_name.ends_with(".tzst") {
+            Some(Self::TarZst)
+        } else {
+            None
+        }
+    }
+
+    pub(crate) fn name(self) -> &'static str {
+        match self {
+            Self::Zip => "zip",
+            Self::Tar => "tar",
+            Self::TarZst => "tar.zst",
+        }
+    }
+}
+
+#[derive(Allocative)]
+pub(crate) struct UnregisteredArchiveAction {
+    format: ArchiveFormat,
+    args: Vec<(ArtifactGroup, Box<ForwardRelativePath>)>,
+    // All associated artifacts of inputs unioned together
+    unioned_associated_artifacts: AssociatedArtifacts,
+}
+
+impl UnregisteredArchiveAction {
+    pub(crate) fn new(format: ArchiveFormat, srcs: Value) -> anyhow::Result<Self> {
+        let (args, unioned_associated_artifacts) =
+            UnregisteredSymlinkedDirAction::unpack_args(srcs)
+                .with_context(|| ValueError::IncorrectParameterTypeNamed("srcs".to_owned()))?;
+        Ok(Self {
+            format,
+            args,
+            unioned_associated_artifacts: AssociatedArtifacts::from(unioned_associated_artifacts),
+        })
+    }
+
+    pub(crate) fn inputs(&self) -> IndexSet<ArtifactGroup> {
+        self.args.iter().map(|x| x.0.dupe()).collect()
+    }
+
+    pub(crate) fn unioned_associated_artifacts(&self) -> AssociatedArtifacts {
+        self.unioned_associated_artifacts.dupe()
+    }
+}
+
+impl UnregisteredAction for UnregisteredArchiveAction {
+    fn register(
+        self: Box<Self>,
+        inputs: IndexSet<ArtifactGroup>,
+        outputs: IndexSet<BuildArtifact>,
+        _starlark_data: Option<OwnedFrozenValue>,
+    ) -> anyhow::Result<Box<dyn Action>> {
+        if outputs.len() != 1 {
+            return Err(ArchiveActionError::WrongNumberOfOutputs(outputs.len()).into());
+        }
+        Ok(Box::new(ArchiveAction {
+            format: self.format,
+            args: self.args,
+            inputs: BoxSliceSet::from(inputs),
+            outputs: BoxSliceSet::from(outputs),
+        }))
+    }
+}
+
+#[derive(Debug, Allocative)]
+struct ArchiveAction {
+    format: ArchiveFormat,
+    args: Vec<(ArtifactGroup, Box<ForwardRelativePath>)>,
+    inputs: BoxSliceSet<ArtifactGroup>,
+    outputs: BoxSliceSet<BuildArtifact>,
+}
+
+impl ArchiveAction {
+    fn output(&self) -> &BuildArtifact {
+        self.outputs
+            .iter()
+            .next()
+            .expect("a single artifact by construction")
+    }
+}
+
+#[async_trait]
+impl Action for ArchiveAction {
+    fn kind(&self) -> buck2_data::ActionKind {
+        buck2_data::ActionKind::Archive
+    }
+
+    fn inputs(&self) -> anyhow::Result<Cow<'_, [ArtifactGroup]>> {
+        Ok(Cow::Borrowed(self.inputs.as_slice()))
+    }
+
+    fn outputs(&self) -> anyhow::Result<Cow<'_, [BuildArtifact]>> {
+        Ok(Cow::Borrowed(self.outputs.as_slice()))
+    }
+
+    fn as_executable(&self) -> ActionExecutable<'_> {
+        ActionExecutable::Incremental(self)
+    }
+
+    fn category(&self) -> &Category {
+        static ARCHIVE_CATEGORY: Lazy<Category> =
+            Lazy::new(|| Category::try_from("archive").unwrap());
+
+        &ARCHIVE_CATEGORY
+    }
+
+    fn identifier(&self) -> Option<&str> {
+        Some(self.output().get_path().path().as_str())
+    }
+
+    fn aquery_attributes(&self, _fs: &ExecutorFs) -> IndexMap<String, String> {
+        indexmap! {
+            "format".to_owned() => self.format.name().to_owned(),
+        }
+    }
+}
+
+#[async_trait]
+impl IncrementalActionExecutable for ArchiveAction {
+    async fn execute(
+        &self,
+        ctx: &mut dyn ActionExecutionCtx,
+    ) -> anyhow::Result<(ActionOutputs, ActionExecutionMetadata)> {
+        let mut srcs = Vec::with_capacity(self.args.len());
+        let mut to_materialize = Vec::new();
+        for (group, dest) in &self.args {
+            let (src_artifact, _value) = ctx
+                .artifact_values(group)
+                .iter()
+                .into_singleton()
+                .context("Input did not dereference to exactly one artifact")?;
+            let src = src_artifact.resolve_path(ctx.fs())?;
+            if !src_artifact.is_source() {
+                to_materialize.push(src.clone());
+            }
+            srcs.push((src, dest));
+        }
+
+        ctx.materializer()
+            .ensure_materialized(to_materialize)
+            .await?;
+        ctx.cleanup_outputs().await?;
+
+        let output = ctx.fs().resolve_build(self.output().get_path());
+        let execution_start = Instant::now();
+
+        ctx.blocking_executor()
+            .execute_io_inline(|| {
+                let fs = ctx.fs().fs();
+                let mut entries = BTreeMap::new();
+                for (src, dest) in &srcs {
+                    collect_entries(&mut entries, fs.resolve(src), dest.to_buf())?;
+                }
+                write_archive(self.format, &entries, &fs.resolve(&output))
+            })
+            .await?;
+
+        let wall_time = execution_start.elapsed();
+        let value = declare_output_from_disk(ctx, output).await?;
+
+        Ok((
+            ActionOutputs::from_single(self.output().get_path().dupe(), value),
+            ActionExecutionMetadata {
+                execution_kind: ActionExecutionKind::Simple,
+                timing: ActionExecutionTimingData { wall_time },
+            },
+        ))
+    }
+}
+
+/// Compute the value of an output that an action wrote to disk itself, and let the materializer
+/// know that it exists.
+pub(crate) async fn declare_output_from_disk(
+    ctx: &mut dyn ActionExecutionCtx,
+    output: ProjectRelativePathBuf,
+) -> anyhow::Result<ArtifactValue> {
+    let entry = ctx
+        .blocking_executor()
+        .execute_io_inline(|| {
+            build_entry_from_disk(
+                ctx.fs().fs().resolve(&output),
+                FileDigestConfig::build(ctx.digest_config().cas_digest_config()),
+            )
+        })
+        .await?
+        .with_context(|| format!("Action did not produce its output: `{}`", output))?
+        .map_dir(|dir| {
+            dir.fingerprint(ctx.digest_config().as_directory_serializer())
+                .shared(&*INTERNER)
+        });
+    let value = ArtifactValue::from(entry);
+
+    ctx.materializer()
+        .declare_existing(vec![(output, value.dupe())])
+        .await?;
+
+    Ok(value)
+}
+
+enum ArchiveEntry {
+    Directory,
+    File {
+        path: AbsNormPathBuf,
+        size: u64,
+        is_executable: bool,
+    },
+}
+
+/// Add the file or directory at `disk_path` to the archive as `archive_path`. Symlinks are
+/// followed, so the archive contains whatever the input resolves to.
+///
+/// Entries are keyed by their path in the archive, which gives them a stable order regardless of
+/// the order of the inputs or of the filesystem.
+fn collect_entries(
+    entries: &mut BTreeMap<ForwardRelativePathBuf, ArchiveEntry>,
+    disk_path: AbsNormPathBuf,
+    archive_path: ForwardRelativePathBuf,
+) -> anyhow::Result<()> {
+    collect_entries_impl(entries, disk_path, archive_path, &mut Vec::new())
+}
+
+/// `ancestors` are the canonical paths of the directories being collected, so that a symlink to
+/// one of them is reported rather than followed forever.
+fn collect_entries_impl(
+    entries: &mut BTreeMap<ForwardRelativePathBuf, ArchiveEntry>,
+    disk_path: AbsNormPathBuf,
+    archive_path: ForwardRelativePathBuf,
+    ancestors: &mut Vec<AbsNormPathBuf>,
+) -> anyhow::Result<()> {
+    let metadata = fs_util::metadata(&disk_path)?;
+
+    if metadata.is_dir() {
+        let canonical = fs_util::canonicalize(&disk_path)?;
+        if ancestors.contains(&canonical) {
+            return Err(ArchiveActionError::SymlinkCycle(disk_path).into());
+        }
+
+        if !archive_path.is_empty() {
+            insert_directory(entries, &archive_path)?;
+        }
+        ancestors.push(canonical);
+        for child in fs_util::read_dir(&disk_path)? {
+            let child = child?.file_name();
+            let child = child
+                .to_str()
+                .context("Filename is not UTF-8")
+                .and_then(FileName::new)
+                .with_context(|| format!("Invalid filename in `{}`", disk_path.display()))?;
+            collect_entries_impl(
+                entries,
+                disk_path.join(child),
+                archive_path.join(child),
+                ancestors,
+            )?;
+        }
+        ancestors.pop();
+    } else if metadata.is_file() {
+        if let Some(parent) = archive_path.parent() {
+            insert_directory(entries, parent)?;
+        }
+        let entry = ArchiveEntry::File {
+            size: metadata.len(),
+            is_executable: is_executable(&metadata),
+            path: disk_path,
+        };
+        match entries.entry(archive_path) {
+            btree_map::Entry::Vacant(e) => {
+                e.insert(entry);
+            }
+            btree_map::Entry::Occupied(e) => {
+                return Err(ArchiveActionError::DuplicatePath(e.key().clone()).into());
+            }
+        }
+    } else {
+        return Err(ArchiveActionError::UnsupportedFileType(disk_path).into());
+    }
+
+    Ok(())
+}
+
+/// Insert a directory and all its parents. Directories may be added more than once, since several
+/// inputs can share a parent.
+fn insert_directory(
+    entries: &mut BTreeMap<ForwardRelativePathBuf, ArchiveEntry>,
+    path: &ForwardRelativePath,
+) -> anyhow::Result<()> {
+    let mut path = Some(path);
+    while let Some(p) = path {
+        if p.is_empty() {
+            break;
+        }
+        match entries.get(p) {
+            Some(ArchiveEntry::Directory) => break,
+            Some(ArchiveEntry::File { .. }) => {
+                return Err(ArchiveActionError::DuplicatePath(p.to_buf()).into());
+            }
+            None => {
+                entries.insert(p.to_buf(), ArchiveEntry::Directory);
+            }
+        }
+        path = p.parent();
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
+fn is_executable(metadata: &Metadata) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+
+    metadata.permissions().mode() & 0o111 != 0
+}
+
+#[cfg(not(unix))]
+fn is_executable(_metadata: &Metadata) -> bool {
+    false
+}
+
+fn write_archive(
+    format: ArchiveFormat,
+    entries: &BTreeMap<ForwardRelativePathBuf, ArchiveEntry>,
+    dest: &AbsNormPath,
+) -> anyhow::Result<()> {
+    if let Some(parent) = dest.parent() {
+        fs_util::create_dir_all(parent)?;
+    }
+
+    match format {
+        ArchiveFormat::Zip => {
+            // The zip writer seeks back to fill in each entry's header once its data is written,
+            // which the output file supports.
+            let out = BufWriter::new(fs_util::create_file(dest)?);
+            write_zip(entries, out)?.flush()?;
+        }
+        ArchiveFormat::Tar => {
+            let mut out = BufWriter::new(fs_util::create_file(dest)?);
+            write_tar(entries, &mut out)?;
+            out.flush()?;
+        }
+        ArchiveFormat::TarZst => {
+            let out = BufWriter::new(fs_util::create_file(dest)?);
+            let mut encoder = zstd::stream::write::Encoder::new(out, 0)?;
+            write_tar(entries, &mut encoder)?;
+            encoder.finish()?.flush()?;
+        }
+    }
+
+    Ok(())
+}
+
+fn write_tar<W: Write>(
+    entries: &BTreeMap<ForwardRelativePathBuf, ArchiveEntry>,
+    out: W,
+) -> anyhow::Result<()> {
+    let mut builder = tar::Builder::new(out);
+
+    for (path, entry) in entries {
+        // Ownership is left as zero, and the mtime is the epoch.
+        let mut header = tar::Header::new_gnu();
+        header.set_mtime(0);
+        match entry {
+            ArchiveEntry::Directory => {
+                header.set_entry_type(tar::EntryType::Directory);
+                header.set_mode(DIRECTORY_MODE);
+                header.set_size(0);
+                builder.append_data(&mut header, path.as_str(), io::empty())?;
+            }
+            ArchiveEntry::File {
+                path: src,
+                size,
+                is_executable,
+            } => {
+                header.set_entry_type(tar::EntryType::Regular);
+                header.set_mode(if *is_executable {
+                    EXECUTABLE_FILE_MODE
+                } else {
+                    FILE_MODE
+                });
+                header.set_size(*size);
+                builder
+                    .append_data(&mut header, path.as_str(), fs_util::open_file(src)?)
+                    .with_context(|| format!("Error adding `{}` to archive", src.display()))?;
+            }
+        }
+    }
+
+    // Writes the end-of-archive marker.
+    builder.into_inner()?;
+    Ok(())
+}
+
+fn write_zip<W: Write + Seek>(
+    entries: &BTreeMap<ForwardRelativePathBuf, ArchiveEntry>,
+    out: W,
+) -> anyhow::Result<W> {
+    let mut zip = zip::ZipWriter::new(out);
+
+    // The default timestamp is the earliest one zip can represent, 1980-01-01.
+    let options = zip::write::FileOptions::default()
+        .compression_method(zip::CompressionMethod::Deflated)
+        .last_modified_time(zip::DateTime::default());
+
+    for (path, entry) in entries {
+        match entry {
+            ArchiveEntry::Directory => {
+                zip.add_directory(path.as_str(), options.unix_permissions(DIRECTORY_MODE))?;
+            }
+            ArchiveEntry::File {
+                path: src,
+                size: _,
+                is_executable,
+            } => {
+                zip.start_file(
+                    path.as_str(),
+                    options.unix_permissions(if *is_executable {
+                        EXECUTABLE_FILE_MODE
+                    } else {
+                        FILE_MODE
+                    }),
+                )?;
+                io::copy(&mut fs_util::open_file(src)?, &mut zip)
+                    .with_context(|| format!("Error adding `{}` to archive", src.display()))?;
+            }
+        }
+    }
+
+    Ok(zip.finish()?)
+}
+
+#[cfg(test)]
+mod tests {
+    use buck2_core::fs::project::ProjectRootTemp;
+
+    use super::*;
+    use crate::actions::impls::extract::extract_archive;
+
+    const FORMATS: [ArchiveFormat; 3] = [
+        ArchiveFormat::Zip,
+        ArchiveFormat::Tar,
+        ArchiveFormat::TarZst,
+    ];
+
+    fn path(temp: &ProjectRootTemp, path: &str) -> anyhow::Result<AbsNormPathBuf> {
+        Ok(temp.path().root().join(ForwardRelativePath::new(path)?))
+    }
+
+    /// Write `files` (path, contents, is_executable) under `dir`, in the given order.
+    fn write_files(
+        temp: &ProjectRootTemp,
+        dir: &str,
+        files: &[(&str, &str, bool)],
+    ) -> anyhow::Result<()> {
+        for (name, contents, is_executable) in files {
+            let file = path(temp, &format!("{}/{}", dir, name))?;
+            fs_util::create_dir_all(file.parent().unwrap())?;
+            fs_util::write(&file, contents)?;
+            if *is_executable {
+                fs_util::set_executable(&file)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn archive_dir(
+        temp: &ProjectRootTemp,
+        dir: &str,
+        format: ArchiveFormat,
+        dest: &str,
+    ) -> anyhow::Result<AbsNormPathBuf> {
+        let mut entries = BTreeMap::new();
+        collect_entries(
+            &mut entries,
+            path(temp, dir)?,
+            ForwardRelativePathBuf::try_from("root".to_owned())?,
+        )?;
+        let dest = path(temp, dest)?;
+        write_archive(format, &entries, &dest)?;
+        Ok(dest)
+    }
+
+    #[test]
+    fn test_format_from_file_name() -> anyhow::Result<()> {
+        let format = |name: &str| -> anyhow::Result<Option<ArchiveFormat>> {
+            Ok(ArchiveFormat::from_file_name(FileName::new(name)?))
+        };
+
+        assert_eq!(Some(ArchiveFormat::Zip), format("foo.zip")?);
+        assert_eq!(Some(ArchiveFormat::Tar), format("foo.tar")?);
+        assert_eq!(Some(ArchiveFormat::TarZst), format("foo.tar.zst")?);
+        assert_eq!(Some(ArchiveFormat::TarZst), format("foo.tzst")?);
+        assert_eq!(None, format("foo.tar.gz")?);
+        assert_eq!(None, format("zip")?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_insert_directory_adds_parents() -> anyhow::Result<()> {
+        let mut entries = BTreeMap::new();
+        insert_directory(&mut entries, ForwardRelativePath::new("a/b/c")?)?;
+        insert_directory(&mut entries, ForwardRelativePath::new("a/d")?)?;
+
+        let paths = entries.keys().map(|p| p.as_str()).collect::<Vec<_>>();
+        assert_eq!(vec!["a", "a/b", "a/b/c", "a/d"], paths);
+        Ok(())
+    }
+
+    #[test]
+    fn test_archive_is_reproducible() -> anyhow::Result<()> {
+        let temp = ProjectRootTemp::new()?;
+        let files = [
+            ("a.txt", "a", false),
+            ("bin/run", "#!/bin/sh", true),
+            ("sub/dir/b.txt", "b", false),
+        ];
+        write_files(&temp, "first", &files)?;
+        // Written in a different order, and later, so directory order and mtimes differ.
+        let mut reversed = files;
+        reversed.reverse();
+        write_files(&temp, "second", &reversed)?;
+
+        for format in FORMATS {
+            let first = archive_dir(&temp, "first", format, &format!("first.{}", format.name()))?;
+            let second = archive_dir(
+                &temp,
+                "second",
+                format,
+                &format!("second.{}", format.name()),
+            )?;
+            assert!(
+                fs_util::read(&first)? == fs_util::read(&second)?,
+                "{} archives differ",
+                format.name()
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_archive_extract_round_trip() -> anyhow::Result<()> {
+        let temp = ProjectRootTemp::new()?;
+        let files = [
+            ("a.txt", "a", false),
+            ("bin/run", "#!/bin/sh", true),
+            ("sub/dir/b.txt", "b", false),
+        ];
+        write_files(&temp, "src", &files)?;
+        fs_util::create_dir_all(path(&temp, "src/empty")?)?;
+
+        for format in FORMATS {
+            let archive = archive_dir(&temp, "src", format, &format!("out.{}", format.name()))?;
+            let dest = path(&temp, &format!("extracted-{}", format.name()))?;
+            extract_archive(format, &archive, &dest)?;
+
+            for (name, contents, executable) in files {
+                let file = dest.join(ForwardRelativePath::new(&format!("root/{}", name))?);
+                assert_eq!(contents.as_bytes(), fs_util::read(&file)?);
+                if cfg!(unix) {
+                    assert_eq!(executable, is_executable(&fs_util::metadata(&file)?));
+                }
+            }
+            assert!(
+                fs_util::metadata(dest.join(ForwardRelativePath::new("root/empty")?))?.is_dir()
+            );
+        }
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_collect_entries_symlink_cycle() -> anyhow::Result<()> {
+        let temp = ProjectRootTemp::new()?;
+        write_files(&temp, "src", &[("a.txt", "a", false)])?;
+        fs_util::create_dir_all(path(&temp, "src/sub")?)?;
+        fs_util::symlink("..", path(&temp, "src/sub/loop")?)?;
+
+        let mut entries = BTreeMap::new();
+        let err = collect_entries(
+            &mut entries,
+            path(&temp, "src")?,
+            ForwardRelativePathBuf::try_from("root".to_owned())?,
+        )
+        .unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("contains itself through a symlink"),
+            "{:#}",
+            err
+        );
+        Ok(())
+    }
+}
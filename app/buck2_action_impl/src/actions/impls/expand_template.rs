@@ -0,0 +1,402 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::time::Instant;
+
+use allocative::Allocative;
+use anyhow::Context as _;
+use async_trait::async_trait;
+use buck2_artifact::artifact::artifact_type::Artifact;
+use buck2_artifact::artifact::build_artifact::BuildArtifact;
+use buck2_build_api::actions::box_slice_set::BoxSliceSet;
+use buck2_build_api::actions::execute::action_executor::ActionExecutionKind;
+use buck2_build_api::actions::execute::action_executor::ActionExecutionMetadata;
+use buck2_build_api::actions::execute::action_executor::ActionOutputs;
+use buck2_build_api::actions::Action;
+use buck2_build_api::actions::ActionExecutable;
+use buck2_build_api::actions::ActionExecutionCtx;
+use buck2_build_api::actions::IncrementalActionExecutable;
+use buck2_build_api::actions::UnregisteredAction;
+use buck2_build_api::artifact_groups::ArtifactGroup;
+use buck2_build_api::interpreter::rule_defs::cmd_args::value_as::ValueAsCommandLineLike;
+use buck2_build_api::interpreter::rule_defs::cmd_args::DefaultCommandLineContext;
+use buck2_core::category::Category;
+use buck2_core::fs::fs_util;
+use buck2_execute::artifact::artifact_dyn::ArtifactDyn;
+use buck2_execute::artifact::fs::ExecutorFs;
+use buck2_execute::execute::command_executor::ActionExecutionTimingData;
+use buck2_execute::materialize::materializer::WriteRequest;
+use dupe::Dupe;
+use gazebo::prelude::*;
+use indexmap::indexmap;
+use indexmap::IndexMap;
+use indexmap::IndexSet;
+use once_cell::sync::Lazy;
+use starlark::values::dict::DictRef;
+use starlark::values::OwnedFrozenValue;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+enum ExpandTemplateActionValidationError {
+    #[error("Exactly one template must be specified for an expand template action, got {0}")]
+    WrongNumberOfInputs(usize),
+    #[error("Only artifact templates are supported in expand template actions, got {0}")]
+    UnsupportedInput(ArtifactGroup),
+    #[error("Exactly one output file must be specified for an expand template action, got {0}")]
+    WrongNumberOfOutputs(usize),
+    #[error("Expected a dict of strings to command line values, got {0}")]
+    SubstitutionsNotCommandLineValues(String),
+}
+
+#[derive(Allocative)]
+pub(crate) struct UnregisteredExpandTemplateAction {
+    is_executable: bool,
+    macro_files: Option<IndexSet<Artifact>>,
+}
+
+impl UnregisteredExpandTemplateAction {
+    pub(crate) fn new(is_executable: bool, macro_files: Option<IndexSet<Artifact>>) -> Self {
+        Self {
+            is_executable,
+            macro_files,
+        }
+    }
+}
+
+impl UnregisteredAction for UnregisteredExpandTemplateAction {
+    fn register(
+        self: Box<Self>,
+        inputs: IndexSet<ArtifactGroup>,
+        outputs: IndexSet<BuildArtifact>,
+        starlark_data: Option<OwnedFrozenValue>,
+    ) -> anyhow::Result<Box<dyn Action>> {
+        let substitutions = starlark_data.expect("module data to be present");
+
+        Ok(Box::new(ExpandTemplateAction::new(
+            substitutions,
+            self.is_executable,
+            inputs,
+            self.macro_files,
+            outputs,
+        )?))
+    }
+}
+
+#[derive(Debug, Allocative)]
+struct ExpandTemplateAction {
+    substitutions: OwnedFrozenValue, // Dict of str to command line
+    is_executable: bool,
+    macro_files: Option<IndexSet<Artifact>>,
+    inputs: BoxSliceSet<ArtifactGroup>,
+    outputs: BoxSliceSet<BuildArtifact>,
+}
+
+impl ExpandTemplateAction {
+    fn new(
+        substitutions: OwnedFrozenValue,
+        is_executable: bool,
+        inputs: IndexSet<ArtifactGroup>,
+        macro_files: Option<IndexSet<Artifact>>,
+        outputs: IndexSet<BuildArtifact>,
+    ) -> anyhow::Result<Self> {
+        match inputs.iter().into_singleton() {
+            Some(ArtifactGroup::Artifact(..)) => {}
+            Some(other) => {
+                return Err(
+                    ExpandTemplateActionValidationError::UnsupportedInput(other.dupe()).into(),
+                );
+            }
+            None => {
+                return Err(
+                    ExpandTemplateActionValidationError::WrongNumberOfInputs(inputs.len()).into(),
+                );
+            }
+        };
+
+        if outputs.len() != 1 {
+            return Err(
+                ExpandTemplateActionValidationError::WrongNumberOfOutputs(outputs.len()).into(),
+            );
+        }
+
+        let valid = DictRef::from_value(substitutions.value()).map_or(false, |d| {
+            d.iter()
+                .all(|(k, v)| k.unpack_str().is_some() && v.as_command_line().is_some())
+        });
+        if !valid {
+            return Err(
+                ExpandTemplateActionValidationError::SubstitutionsNotCommandLineValues(
+                    substitutions.value().to_repr(),
+                )
+                .into(),
+            );
+        }
+
+        Ok(ExpandTemplateAction {
+            substitutions,
+            is_executable,
+            macro_files,
+            inputs: BoxSliceSet::from(inputs),
+            outputs: BoxSliceSet::from(outputs),
+        })
+    }
+
+    fn template(&self) -> &ArtifactGroup {
+        self.inputs
+            .iter()
+            .next()
+            .expect("a single input by construction")
+    }
+
+    fn output(&self) -> &BuildArtifact {
+        self.outputs
+            .iter()
+            .next()
+            .expect("a single artifact by construction")
+    }
+
+    /// Render every substitution value the same way `run` renders environment variables. All the
+    /// values share a context so that write-to-file macros are numbered the same way as when
+    /// `expand_template` declared their files.
+    fn get_substitutions(&self, fs: &ExecutorFs) -> anyhow::Result<Vec<(String, String)>> {
+        let substitutions =
+            DictRef::from_value(self.substitutions.value()).expect("checked by construction");
+
+        let mut ctx = if let Some(macro_files) = &self.macro_files {
+            DefaultCommandLineContext::new_with_write_to_file_macros_support(fs, macro_files)
+        } else {
+            DefaultCommandLineContext::new(fs)
+        };
+
+        let mut res = Vec::with_capacity(substitutions.len());
+        for (k, v) in substitutions.iter() {
+            let mut rendered = Vec::<String>::new();
+            v.as_command_line()
+                .expect("checked by construction")
+                .add_to_command_line(&mut rendered, &mut ctx)?;
+            res.push((
+                k.unpack_str().expect("checked by construction").to_owned(),
+                rendered.join(" "),
+            ));
+        }
+        Ok(res)
+    }
+}
+
+#[async_trait]
+impl Action for ExpandTemplateAction {
+    fn kind(&self) -> buck2_data::ActionKind {
+        buck2_data::ActionKind::ExpandTemplate
+    }
+
+    fn inputs(&self) -> anyhow::Result<Cow<'_, [ArtifactGroup]>> {
+        Ok(Cow::Borrowed(self.inputs.as_slice()))
+    }
+
+    fn outputs(&self) -> anyhow::Result<Cow<'_, [BuildArtifact]>> {
+        Ok(Cow::Borrowed(self.outputs.as_slice()))
+    }
+
+    fn as_executable(&self) -> ActionExecutable<'_> {
+        ActionExecutable::Incremental(self)
+    }
+
+    fn category(&self) -> &Category {
+        static EXPAND_TEMPLATE_CATEGORY: Lazy<Category> =
+            Lazy::new(|| Category::try_from("expand_template").unwrap());
+
+        &EXPAND_TEMPLATE_CATEGORY
+    }
+
+    fn identifier(&self) -> Option<&str> {
+        Some(self.output().get_path().path().as_str())
+    }
+
+    fn aquery_attributes(&self, fs: &ExecutorFs) -> IndexMap<String, String> {
+        indexmap! {
+            "substitutions".to_owned() => match self.get_substitutions(fs) {
+                Ok(v) => v
+                    .into_iter()
+                    .map(|(k, v)| format!("{} => {}", k, v))
+                    .collect::<Vec<_>>()
+                    .join("\n"),
+                Err(e) => format!("ERROR: constructing substitutions ({})", e)
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl IncrementalActionExecutable for ExpandTemplateAction {
+    async fn execute(
+        &self,
+        ctx: &mut dyn ActionExecutionCtx,
+    ) -> anyhow::Result<(ActionOutputs, ActionExecutionMetadata)> {
+        let (input, _value) = ctx
+            .artifact_values(self.template())
+            .iter()
+            .into_singleton()
+            .context("Input did not dereference to exactly one artifact")?;
+        let template = input.resolve_path(ctx.fs())?;
+        if !input.is_source() {
+            ctx.materializer()
+                .ensure_materialized(vec![template.clone()])
+                .await?;
+        }
+
+        let fs = ctx.fs();
+        let mut execution_start = None;
+
+        let value = ctx
+            .materializer()
+            .declare_write(Box::new(|| {
+                execution_start = Some(Instant::now());
+                let contents = fs_util::read(fs.fs().resolve(&template))
+                    .with_context(|| format!("Error reading template `{}`", template))?;
+                let substitutions = self.get_substitutions(&ctx.executor_fs())?;
+                Ok(vec![WriteRequest {
+                    path: fs.resolve_build(self.output().get_path()),
+                    content: expand(&contents, &substitutions),
+                    is_executable: self.is_executable,
+                }])
+            }))
+            .await?
+            .into_iter()
+            .next()
+            .context("Write did not execute")?;
+
+        let wall_time = execution_start
+            .context("Action did not set execution_start")?
+            .elapsed();
+
+        Ok((
+            ActionOutputs::new(indexmap![self.output().get_path().dupe() => value]),
+            ActionExecutionMetadata {
+                execution_kind: ActionExecutionKind::Simple,
+                timing: ActionExecutionTimingData { wall_time },
+            },
+        ))
+    }
+}
+
+/// A trie over the bytes of the substitution keys.
+#[derive(Default)]
+struct TrieNode<'a> {
+    children: HashMap<u8, usize>,
+    /// The replacement if a key ends at this node.
+    value: Option<&'a [u8]>,
+}
+
+/// Replace every occurrence of a key of `substitutions` in `template` by its value, in a single
+/// pass: replaced text is never substituted again. When several keys match at the same position,
+/// the longest one wins.
+///
+/// Each position of the template is matched against a trie of the keys, so this takes time
+/// proportional to the length of the template times that of the longest key.
+fn expand(template: &[u8], substitutions: &[(String, String)]) -> Vec<u8> {
+    let mut trie = vec![TrieNode::default()];
+    for (k, v) in substitutions {
+        if k.is_empty() {
+            continue;
+        }
+        let mut node = 0;
+        for b in k.bytes() {
+            let next = trie.len();
+            node = *trie[node].children.entry(b).or_insert(next);
+            if node == next {
+                trie.push(TrieNode::default());
+            }
+        }
+        trie[node].value = Some(v.as_bytes());
+    }
+
+    let mut res = Vec::with_capacity(template.len());
+    let mut i = 0;
+    while i < template.len() {
+        let mut node = 0;
+        let mut longest = None;
+        for (len, b) in template[i..].iter().enumerate() {
+            match trie[node].children.get(b) {
+                Some(child) => node = *child,
+                None => break,
+            }
+            if let Some(value) = trie[node].value {
+                longest = Some((len + 1, value));
+            }
+        }
+
+        match longest {
+            Some((len, value)) => {
+                res.extend_from_slice(value);
+                i += len;
+            }
+            None => {
+                res.push(template[i]);
+                i += 1;
+            }
+        }
+    }
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn substitutions(xs: &[(&str, &str)]) -> Vec<(String, String)> {
+        xs.map(|(k, v)| ((*k).to_owned(), (*v).to_owned()))
+    }
+
+    fn expand_str(template: &str, subs: &[(String, String)]) -> String {
+        String::from_utf8(expand(template.as_bytes(), subs)).unwrap()
+    }
+
+    #[test]
+    fn test_expand() {
+        let subs = substitutions(&[("{NAME}", "world"), ("{PATH}", "buck-out/v2/gen/foo")]);
+        assert_eq!(
+            "hello world, see buck-out/v2/gen/foo and buck-out/v2/gen/foo",
+            expand_str("hello {NAME}, see {PATH} and {PATH}", &subs)
+        );
+        assert_eq!("no placeholders", expand_str("no placeholders", &subs));
+        assert_eq!("", expand_str("", &subs));
+        assert_eq!("{NAM world", expand_str("{NAM {NAME}", &subs));
+    }
+
+    #[test]
+    fn test_expand_does_not_recurse() {
+        let subs = substitutions(&[("A", "B"), ("B", "A")]);
+        assert_eq!("BA", expand_str("AB", &subs));
+    }
+
+    #[test]
+    fn test_expand_prefers_longest_match() {
+        let subs = substitutions(&[("$X", "short"), ("$XY", "long"), ("$XYZW", "longest")]);
+        assert_eq!("long short longZ", expand_str("$XY $X $XYZ", &subs));
+        assert_eq!("longest", expand_str("$XYZW", &subs));
+    }
+
+    #[test]
+    fn test_expand_bytes() {
+        let subs = substitutions(&[("@X@", "\u{e9}")]);
+        assert_eq!(
+            b"\xff\x00\xc3\xa9\xfe".to_vec(),
+            expand(b"\xff\x00@X@\xfe", &subs)
+        );
+    }
+
+    #[test]
+    fn test_expand_many_occurrences() {
+        let subs = substitutions(&[("{A}", "a"), ("{B}", "bb")]);
+        let template = "{A}{B}.".repeat(10000);
+        assert_eq!("abb.".repeat(10000), expand_str(&template, &subs));
+    }
+}
@@ -0,0 +1,541 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::collections::VecDeque;
+use std::io;
+use std::io::BufReader;
+use std::io::Read;
+use std::io::Seek;
+use std::time::Instant;
+
+use allocative::Allocative;
+use anyhow::Context as _;
+use async_trait::async_trait;
+use buck2_artifact::artifact::build_artifact::BuildArtifact;
+use buck2_build_api::actions::box_slice_set::BoxSliceSet;
+use buck2_build_api::actions::execute::action_executor::ActionExecutionKind;
+use buck2_build_api::actions::execute::action_executor::ActionExecutionMetadata;
+use buck2_build_api::actions::execute::action_executor::ActionOutputs;
+use buck2_build_api::actions::Action;
+use buck2_build_api::actions::ActionExecutable;
+use buck2_build_api::actions::ActionExecutionCtx;
+use buck2_build_api::actions::IncrementalActionExecutable;
+use buck2_build_api::actions::UnregisteredAction;
+use buck2_build_api::artifact_groups::ArtifactGroup;
+use buck2_core::category::Category;
+use buck2_core::fs::fs_util;
+use buck2_core::fs::paths::abs_norm_path::AbsNormPath;
+use buck2_core::fs::paths::forward_rel_path::ForwardRelativePath;
+use buck2_core::fs::paths::forward_rel_path::ForwardRelativePathBuf;
+use buck2_execute::artifact::artifact_dyn::ArtifactDyn;
+use buck2_execute::artifact::fs::ExecutorFs;
+use buck2_execute::execute::command_executor::ActionExecutionTimingData;
+use dupe::Dupe;
+use gazebo::prelude::*;
+use indexmap::indexmap;
+use indexmap::IndexMap;
+use indexmap::IndexSet;
+use once_cell::sync::Lazy;
+use starlark::values::OwnedFrozenValue;
+use thiserror::Error;
+
+use crate::actions::impls::archive::declare_output_from_disk;
+use crate::actions::impls::archive::ArchiveFormat;
+
+#[derive(Debug, Error)]
+enum ExtractActionError {
+    #[error("Exactly one input file must be specified for an extract action, got {0}")]
+    WrongNumberOfInputs(usize),
+    #[error("Exactly one output directory must be specified for an extract action, got {0}")]
+    WrongNumberOfOutputs(usize),
+    #[error("Only artifact inputs are supported in extract actions, got {0}")]
+    UnsupportedInput(ArtifactGroup),
+    #[error("Archive entry `{0}` does not have a valid relative path")]
+    InvalidPath(String),
+    #[error("Archive entry `{0}` is a symlink to `{1}`, which is outside of the output directory")]
+    SymlinkOutsideOutput(ForwardRelativePathBuf, String),
+    #[error("Archive entry `{0}` is of unsupported type `{1}`")]
+    UnsupportedEntryType(String, String),
+}
+
+#[derive(Allocative)]
+pub(crate) struct UnregisteredExtractAction {
+    format: ArchiveFormat,
+}
+
+impl UnregisteredExtractAction {
+    pub(crate) fn new(format: ArchiveFormat) -> Self {
+        Self { format }
+    }
+}
+
+impl UnregisteredAction for UnregisteredExtractAction {
+    fn register(
+        self: Box<Self>,
+        inputs: IndexSet<ArtifactGroup>,
+        outputs: IndexSet<BuildArtifact>,
+        _starlark_data: Option<OwnedFrozenValue>,
+    ) -> anyhow::Result<Box<dyn Action>> {
+        Ok(Box::new(ExtractAction::new(self.format, inputs, outputs)?))
+    }
+}
+
+#[derive(Debug, Allocative)]
+struct ExtractAction {
+    format: ArchiveFormat,
+    inputs: BoxSliceSet<ArtifactGroup>,
+    outputs: BoxSliceSet<BuildArtifact>,
+}
+
+impl ExtractAction {
+    fn new(
+        format: ArchiveFormat,
+        inputs: IndexSet<ArtifactGroup>,
+        outputs: IndexSet<BuildArtifact>,
+    ) -> anyhow::Result<Self> {
+        match inputs.iter().into_singleton() {
+            Some(ArtifactGroup::Artifact(..)) => {}
+            Some(other) => {
+                return Err(ExtractActionError::UnsupportedInput(other.dupe()).into());
+            }
+            None => return Err(ExtractActionError::WrongNumberOfInputs(inputs.len()).into()),
+        };
+
+        if outputs.len() != 1 {
+            return Err(ExtractActionError::WrongNumberOfOutputs(outputs.len()).into());
+        }
+
+        Ok(ExtractAction {
+            format,
+            inputs: BoxSliceSet::from(inputs),
+            outputs: BoxSliceSet::from(outputs),
+        })
+    }
+
+    fn input(&self) -> &ArtifactGroup {
+        self.inputs
+            .iter()
+            .next()
+            .expect("a single input by construction")
+    }
+
+    fn output(&self) -> &BuildArtifact {
+        self.outputs
+            .iter()
+            .next()
+            .expect("a single artifact by construction")
+    }
+}
+
+#[async_trait]
+impl Action for ExtractAction {
+    fn kind(&self) -> buck2_data::ActionKind {
+        buck2_data::ActionKind::Extract
+    }
+
+    fn inputs(&self) -> anyhow::Result<Cow<'_, [ArtifactGroup]>> {
+        Ok(Cow::Borrowed(self.inputs.as_slice()))
+    }
+
+    fn outputs(&self) -> anyhow::Result<Cow<'_, [BuildArtifact]>> {
+        Ok(Cow::Borrowed(self.outputs.as_slice()))
+    }
+
+    fn as_executable(&self) -> ActionExecutable<'_> {
+        ActionExecutable::Incremental(self)
+    }
+
+    fn category(&self) -> &Category {
+        static EXTRACT_CATEGORY: Lazy<Category> =
+            Lazy::new(|| Category::try_from("extract").unwrap());
+
+        &EXTRACT_CATEGORY
+    }
+
+    fn identifier(&self) -> Option<&str> {
+        Some(self.output().get_path().path().as_str())
+    }
+
+    fn aquery_attributes(&self, _fs: &ExecutorFs) -> IndexMap<String, String> {
+        indexmap! {
+            "format".to_owned() => self.format.name().to_owned(),
+        }
+    }
+}
+
+#[async_trait]
+impl IncrementalActionExecutable for ExtractAction {
+    async fn execute(
+        &self,
+        ctx: &mut dyn ActionExecutionCtx,
+    ) -> anyhow::Result<(ActionOutputs, ActionExecutionMetadata)> {
+        let (input, _value) = ctx
+            .artifact_values(self.input())
+            .iter()
+            .into_singleton()
+            .context("Input did not dereference to exactly one artifact")?;
+        let archive = input.resolve_path(ctx.fs())?;
+        if !input.is_source() {
+            ctx.materializer()
+                .ensure_materialized(vec![archive.clone()])
+                .await?;
+        }
+        ctx.cleanup_outputs().await?;
+
+        let output = ctx.fs().resolve_build(self.output().get_path());
+        let execution_start = Instant::now();
+
+        ctx.blocking_executor()
+            .execute_io_inline(|| {
+                let fs = ctx.fs().fs();
+                extract_archive(self.format, &fs.resolve(&archive), &fs.resolve(&output))
+                    .with_context(|| format!("Error extracting `{}`", archive))
+            })
+            .await?;
+
+        let wall_time = execution_start.elapsed();
+        let value = declare_output_from_disk(ctx, output).await?;
+
+        Ok((
+            ActionOutputs::from_single(self.output().get_path().dupe(), value),
+            ActionExecutionMetadata {
+                execution_kind: ActionExecutionKind::Simple,
+                timing: ActionExecutionTimingData { wall_time },
+            },
+        ))
+    }
+}
+
+pub(crate) fn extract_archive(
+    format: ArchiveFormat,
+    archive: &AbsNormPath,
+    dest: &AbsNormPath,
+) -> anyhow::Result<()> {
+    fs_util::create_dir_all(dest)?;
+    let mut extractor = Extractor {
+        root: dest,
+        symlinks: BTreeMap::new(),
+    };
+
+    match format {
+        ArchiveFormat::Zip => {
+            // The zip reader seeks to the central directory at the end of the archive, and then
+            // back to each entry, which the input file supports.
+            extract_zip(BufReader::new(fs_util::open_file(archive)?), &mut extractor)?;
+        }
+        ArchiveFormat::Tar => {
+            extract_tar(BufReader::new(fs_util::open_file(archive)?), &mut extractor)?;
+        }
+        ArchiveFormat::TarZst => {
+            let decoder = zstd::stream::read::Decoder::new(fs_util::open_file(archive)?)?;
+            extract_tar(decoder, &mut extractor)?;
+        }
+    }
+
+    extractor.finish()
+}
+
+fn extract_tar<R: Read>(reader: R, extractor: &mut Extractor) -> anyhow::Result<()> {
+    let mut archive = tar::Archive::new(reader);
+
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        let entry_type = entry.header().entry_type();
+        if entry_type == tar::EntryType::XGlobalHeader {
+            // Metadata about the archive as a whole, which we don't use.
+            continue;
+        }
+
+        let name = entry
+            .path()?
+            .to_str()
+            .context("Archive entry path is not UTF-8")?
+            .to_owned();
+        let path = match entry_path(&name)? {
+            Some(path) => path,
+            None => continue,
+        };
+
+        match entry_type {
+            tar::EntryType::Regular | tar::EntryType::Continuous => {
+                let is_executable = entry.header().mode()? & 0o111 != 0;
+                extractor.file(&path, is_executable, &mut entry)?;
+            }
+            tar::EntryType::Directory => extractor.directory(&path)?,
+            tar::EntryType::Symlink => {
+                let target = entry
+                    .link_name()?
+                    .with_context(|| format!("Symlink `{}` has no target", name))?
+                    .to_str()
+                    .context("Symlink target is not UTF-8")?
+                    .to_owned();
+                extractor.symlink(path, target)?;
+            }
+            other => {
+                return Err(
+                    ExtractActionError::UnsupportedEntryType(name, format!("{:?}", other)).into(),
+                );
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn extract_zip<R: Read + Seek>(reader: R, extractor: &mut Extractor) -> anyhow::Result<()> {
+    // File type bits of a unix mode, and the value they have for symlinks.
+    const S_IFMT: u32 = 0o170000;
+    const S_IFLNK: u32 = 0o120000;
+
+    let mut archive = zip::ZipArchive::new(reader)?;
+
+    for i in 0..archive.len() {
+        let mut file = archive.by_index(i)?;
+        let name = file.name().to_owned();
+        let path = match entry_path(&name)? {
+            Some(path) => path,
+            None => continue,
+        };
+        let mode = file.unix_mode();
+
+        if file.is_dir() {
+            extractor.directory(&path)?;
+        } else if mode.map_or(false, |m| m & S_IFMT == S_IFLNK) {
+            let mut target = String::new();
+            file.read_to_string(&mut target)
+                .with_context(|| format!("Reading target of symlink `{}`", name))?;
+            extractor.symlink(path, target)?;
+        } else {
+            let is_executable = mode.map_or(false, |m| m & 0o111 != 0);
+            extractor.file(&path, is_executable, &mut file)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Convert the name of an archive entry to a path in the output. Archives created with e.g.
+/// `tar -C dir .` prefix every entry with `./`, so that is stripped. Returns `None` for the entry
+/// describing the root directory itself.
+fn entry_path(name: &str) -> anyhow::Result<Option<ForwardRelativePathBuf>> {
+    let mut path = name;
+    while let Some(rest) = path.strip_prefix("./") {
+        path = rest;
+    }
+    let path = path.trim_end_matches('/');
+    if path.is_empty() || path == "." {
+        return Ok(None);
+    }
+
+    let path = ForwardRelativePath::new(path)
+        .with_context(|| ExtractActionError::InvalidPath(name.to_owned()))?;
+    Ok(Some(path.to_buf()))
+}
+
+/// Writes archive entries under `root`. Only the executable bit of files is preserved, so the
+/// output does not depend on the permissions recorded by whoever created the archive.
+struct Extractor<'a> {
+    root: &'a AbsNormPath,
+    /// Symlinks are created once all other entries were written, so that no entry can be written
+    /// through a symlink. They are keyed by their path so that targets can be resolved through
+    /// other symlinks in the archive.
+    symlinks: BTreeMap<ForwardRelativePathBuf, String>,
+}
+
+impl Extractor<'_> {
+    fn directory(&mut self, path: &ForwardRelativePath) -> anyhow::Result<()> {
+        fs_util::create_dir_all(self.root.join(path))
+    }
+
+    fn file(
+        &mut self,
+        path: &ForwardRelativePath,
+        is_executable: bool,
+        contents: &mut dyn Read,
+    ) -> anyhow::Result<()> {
+        let dest = self.root.join(path);
+        if let Some(parent) = dest.parent() {
+            fs_util::create_dir_all(parent)?;
+        }
+
+        let mut file = fs_util::create_file(&dest)?;
+        io::copy(contents, &mut file).with_context(|| format!("Error extracting `{}`", path))?;
+        drop(file);
+
+        if is_executable {
+            fs_util::set_executable(&dest)?;
+        }
+        Ok(())
+    }
+
+    fn symlink(&mut self, path: ForwardRelativePathBuf, target: String) -> anyhow::Result<()> {
+        self.symlinks.insert(path, target);
+        Ok(())
+    }
+
+    fn finish(self) -> anyhow::Result<()> {
+        // Targets are only checked once all symlinks are known, since a target can go through
+        // symlinks that appear later in the archive.
+        for (path, target) in &self.symlinks {
+            if !resolves_inside(&self.symlinks, path, target) {
+                return Err(
+                    ExtractActionError::SymlinkOutsideOutput(path.clone(), target.clone()).into(),
+                );
+            }
+        }
+
+        for (path, target) in &self.symlinks {
+            let dest = self.root.join(path);
+            if let Some(parent) = dest.parent() {
+                fs_util::create_dir_all(parent)?;
+            }
+            fs_util::symlink(target, &dest)?;
+        }
+        Ok(())
+    }
+}
+
+/// Whether the symlink at `path` pointing to `target` resolves to somewhere inside the output
+/// directory, following the other `symlinks` in the archive (e.g. `a -> d/l/..` escapes if
+/// `d/l -> ..`). Symlinks that don't resolve within a bounded number of hops, such as cycles, are
+/// rejected too.
+fn resolves_inside(
+    symlinks: &BTreeMap<ForwardRelativePathBuf, String>,
+    path: &ForwardRelativePath,
+    target: &str,
+) -> bool {
+    const MAX_HOPS: usize = 40;
+
+    if target.starts_with('/') {
+        return false;
+    }
+
+    // The directory we resolved to so far, and the components of the target still to visit.
+    let mut resolved: Vec<&str> = path.parent().map_or_else(Vec::new, |parent| {
+        parent.iter().map(|c| c.as_str()).collect()
+    });
+    let mut remaining: VecDeque<&str> = target.split('/').collect();
+    let mut hops = 0;
+
+    while let Some(component) = remaining.pop_front() {
+        match component {
+            "" | "." => {}
+            ".." => {
+                if resolved.pop().is_none() {
+                    return false;
+                }
+            }
+            name => {
+                resolved.push(name);
+                let current = resolved.join("/");
+                if let Some(link) = ForwardRelativePath::new(&current)
+                    .ok()
+                    .and_then(|current| symlinks.get(current))
+                {
+                    hops += 1;
+                    if hops > MAX_HOPS || link.starts_with('/') {
+                        return false;
+                    }
+                    // The link's target is relative to the directory containing it.
+                    resolved.pop();
+                    for c in link.split('/').rev() {
+                        remaining.push_front(c);
+                    }
+                }
+            }
+        }
+    }
+
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use buck2_core::fs::project::ProjectRootTemp;
+
+    use super::*;
+
+    #[test]
+    fn test_entry_path() -> anyhow::Result<()> {
+        let path = |name: &str| -> anyhow::Result<Option<String>> {
+            Ok(entry_path(name)?.map(|p| p.as_str().to_owned()))
+        };
+
+        assert_eq!(Some("foo/bar".to_owned()), path("foo/bar")?);
+        assert_eq!(Some("foo".to_owned()), path("foo/")?);
+        assert_eq!(Some("foo/bar".to_owned()), path("./foo/bar")?);
+        assert_eq!(None, path("./")?);
+        assert_eq!(None, path(".")?);
+        assert!(path("/foo").is_err());
+        assert!(path("../foo").is_err());
+        assert!(path("foo/../../bar").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_resolves_inside() -> anyhow::Result<()> {
+        let symlinks: BTreeMap<_, _> = [("d/l", ".."), ("a", "b"), ("b", "a")]
+            .into_iter()
+            .map(|(path, target)| {
+                Ok((
+                    ForwardRelativePathBuf::try_from(path.to_owned())?,
+                    target.to_owned(),
+                ))
+            })
+            .collect::<anyhow::Result<_>>()?;
+        let resolves = |path: &str, target: &str| -> anyhow::Result<bool> {
+            Ok(resolves_inside(
+                &symlinks,
+                ForwardRelativePath::new(path)?,
+                target,
+            ))
+        };
+
+        assert!(resolves("d/l", "..")?);
+        assert!(resolves("e", "d/l/x")?);
+        assert!(resolves("e", "x/../y")?);
+        assert!(resolves("x/e", "../y")?);
+        assert!(!resolves("e", "/x")?);
+        assert!(!resolves("e", "../x")?);
+        // `d/l` resolves to the output directory, so going up from it escapes.
+        assert!(!resolves("e", "d/l/..")?);
+        assert!(!resolves("d/m", "l/..")?);
+        // Cycles never resolve.
+        assert!(!resolves("e", "a")?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_extract_rejects_chained_symlink_escape() -> anyhow::Result<()> {
+        let mut builder = tar::Builder::new(Vec::new());
+        for (path, target) in [("d/l", ".."), ("e", "d/l/..")] {
+            let mut header = tar::Header::new_gnu();
+            header.set_entry_type(tar::EntryType::Symlink);
+            header.set_size(0);
+            header.set_link_name(target)?;
+            builder.append_data(&mut header, path, io::empty())?;
+        }
+        let data = builder.into_inner()?;
+
+        let temp = ProjectRootTemp::new()?;
+        let archive = temp.path().root().join(ForwardRelativePath::new("in.tar")?);
+        let dest = temp.path().root().join(ForwardRelativePath::new("out")?);
+        fs_util::write(&archive, data)?;
+
+        assert!(extract_archive(ArchiveFormat::Tar, &archive, &dest).is_err());
+        // Nothing is created when any symlink escapes.
+        assert!(
+            fs_util::symlink_metadata_if_exists(dest.join(ForwardRelativePath::new("d/l")?))?
+                .is_none()
+        );
+        Ok(())
+    }
+}
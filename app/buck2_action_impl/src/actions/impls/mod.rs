@@ -7,9 +7,12 @@
  * of this source tree.
  */
 
+pub(crate) mod archive;
 pub(crate) mod cas_artifact;
 pub(crate) mod copy;
 pub(crate) mod download_file;
+pub(crate) mod expand_template;
+pub(crate) mod extract;
 pub(crate) mod offline;
 pub mod run;
 pub(crate) mod symlinked_dir;
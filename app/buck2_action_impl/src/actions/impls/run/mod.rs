@@ -9,6 +9,7 @@
 
 use std::borrow::Cow;
 use std::fmt::Display;
+use std::time::Duration;
 
 use allocative::Allocative;
 use async_trait::async_trait;
@@ -161,6 +162,8 @@ pub(crate) struct UnregisteredRunAction {
     pub(crate) no_outputs_cleanup: bool,
     pub(crate) allow_cache_upload: bool,
     pub(crate) force_full_hybrid_if_capable: bool,
+    /// Overrides the execution platform's `action_timeout_ms`.
+    pub(crate) timeout: Option<Duration>,
 }
 
 impl UnregisteredAction for UnregisteredRunAction {
@@ -513,6 +516,12 @@ impl Action for RunAction {
             "no_outputs_cleanup".to_owned() => self.inner.no_outputs_cleanup.to_string(),
         }
     }
+
+    fn aquery_command_line(&self, fs: &ExecutorFs) -> anyhow::Result<Option<ExpandedCommandLine>> {
+        let (expanded, _worker) =
+            self.expand_command_line_and_worker(fs, &mut SimpleCommandLineArtifactVisitor::new())?;
+        Ok(Some(expanded))
+    }
 }
 
 #[async_trait]
@@ -597,6 +606,15 @@ impl IncrementalActionExecutable for RunAction {
             .with_force_full_hybrid_if_capable(self.inner.force_full_hybrid_if_capable)
             .with_custom_tmpdir(ctx.target().custom_tmpdir());
 
+        let timeout = self
+            .inner
+            .timeout
+            .or(ctx.target().execution_config().action_timeout);
+        let req = match timeout {
+            Some(timeout) => req.with_explicit_timeout(timeout),
+            None => req,
+        };
+
         let prepared_action = ctx.prepare_action(&req)?;
         let manager = ctx.command_execution_manager();
         let (outputs, meta) = ctx.exec_cmd(manager, &req, &prepared_action).await?;
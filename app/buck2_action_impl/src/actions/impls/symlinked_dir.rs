@@ -94,7 +94,7 @@ impl UnregisteredSymlinkedDirAction {
 
     // Map each artifact into an optional tuple of (artifact, path) and associated_artifacts, then collect
     // them into an optional tuple of vector and an index set respectively
-    fn unpack_args(
+    pub(crate) fn unpack_args(
         srcs: Value,
     ) -> anyhow::Result<(
         Vec<(ArtifactGroup, Box<ForwardRelativePath>)>,
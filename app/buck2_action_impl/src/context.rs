@@ -10,10 +10,13 @@
 use std::collections::hash_map::Entry;
 use std::collections::HashMap;
 use std::sync::Arc;
+use std::time::Duration;
 
 use anyhow::Context;
+use buck2_artifact::artifact::artifact_type::DeclaredArtifact;
 use buck2_artifact::artifact::artifact_type::OutputArtifact;
 use buck2_build_api::actions::impls::json::validate_json;
+use buck2_build_api::analysis::registry::AnalysisRegistry;
 use buck2_build_api::artifact_groups::ArtifactGroup;
 use buck2_build_api::attrs::resolve::attr_type::arg::value::ResolvedMacro;
 use buck2_build_api::interpreter::rule_defs::artifact::associated::AssociatedArtifacts;
@@ -56,7 +59,9 @@ use sha1::Sha1;
 use starlark::environment::MethodsBuilder;
 use starlark::eval::Evaluator;
 use starlark::starlark_module;
+use starlark::values::dict::AllocDict;
 use starlark::values::function::FUNCTION_TYPE;
+use starlark::values::list::AllocList;
 use starlark::values::none::NoneOr;
 use starlark::values::none::NoneType;
 use starlark::values::Heap;
@@ -68,12 +73,16 @@ use starlark::values::ValueTyped;
 use starlark_map::small_map::SmallMap;
 use starlark_map::small_set::SmallSet;
 
+use crate::actions::impls::archive::ArchiveFormat;
+use crate::actions::impls::archive::UnregisteredArchiveAction;
 use crate::actions::impls::cas_artifact::ArtifactKind;
 use crate::actions::impls::cas_artifact::DirectoryKind;
 use crate::actions::impls::cas_artifact::UnregisteredCasArtifactAction;
 use crate::actions::impls::copy::CopyMode;
 use crate::actions::impls::copy::UnregisteredCopyAction;
 use crate::actions::impls::download_file::UnregisteredDownloadFileAction;
+use crate::actions::impls::expand_template::UnregisteredExpandTemplateAction;
+use crate::actions::impls::extract::UnregisteredExtractAction;
 use crate::actions::impls::run::dep_files::RunActionDepFiles;
 use crate::actions::impls::run::new_executor_preference;
 use crate::actions::impls::run::MetadataParameter;
@@ -90,6 +99,18 @@ enum DownloadFileError {
     MissingChecksum,
 }
 
+#[derive(thiserror::Error, Debug)]
+enum ExpandTemplateError {
+    #[error("`substitutions` keys must not be empty")]
+    EmptySubstitutionKey,
+}
+
+#[derive(thiserror::Error, Debug)]
+enum ExtractError {
+    #[error("Cannot infer the format of archive `{0}` from its name, pass `format` explicitly")]
+    UnknownFormat(String),
+}
+
 #[derive(thiserror::Error, Debug)]
 enum DynamicOutputError {
     #[error("Output list may not be empty")]
@@ -116,6 +137,8 @@ enum RunActionError {
     InvalidWeight(i32),
     #[error("`weight` and `weight_percentage` cannot both be passed")]
     DuplicateWeightsSpecified,
+    #[error("`timeout_ms` must be a positive integer, got `{0}`")]
+    InvalidTimeout(i32),
     #[error("`dep_files` values must be artifact tags, got `{}` for key `{}`", .value, .key)]
     InvalidDepFileTag { key: String, value: String },
     #[error("`dep_files` value with key `{}` has an invalid count of associated outputs. Expected 1, got {}.", .key, .count)]
@@ -144,6 +167,87 @@ enum WriteActionError {
     ArgAttrsDetectedButNotAllowed,
 }
 
+fn count_write_to_file_macros(
+    args_allowed: bool,
+    cli: &dyn CommandLineArgLike,
+) -> anyhow::Result<u32> {
+    if !args_allowed && cli.contains_arg_attr() {
+        return Err(anyhow::anyhow!(
+            WriteActionError::ArgAttrsDetectedButNotAllowed
+        ));
+    }
+
+    struct WriteToFileMacrosCounter {
+        count: u32,
+    }
+
+    impl WriteToFileMacroVisitor for WriteToFileMacrosCounter {
+        fn visit_write_to_file_macro(&mut self, _m: &ResolvedMacro) -> anyhow::Result<()> {
+            self.count += 1;
+            Ok(())
+        }
+
+        fn set_current_relative_to_path(
+            &mut self,
+            _gen: &dyn Fn(&dyn CommandLineContext) -> anyhow::Result<Option<RelativePathBuf>>,
+        ) -> anyhow::Result<()> {
+            Ok(())
+        }
+    }
+
+    let mut counter = WriteToFileMacrosCounter { count: 0 };
+    cli.visit_write_to_file_macros(&mut counter)?;
+    Ok(counter.count)
+}
+
+/// Declares one `.macro` file per write-to-file macro in `content_cli`, next to `output`, and
+/// registers the action that writes them.
+fn declare_write_to_file_macros<'v>(
+    this: &mut AnalysisRegistry<'v>,
+    eval: &mut Evaluator<'v, '_>,
+    output_artifact: &OutputArtifact,
+    content_cli: Value<'v>,
+    written_macro_count: u32,
+) -> anyhow::Result<IndexSet<DeclaredArtifact>> {
+    if written_macro_count == 0 {
+        return Ok(indexset![]);
+    }
+
+    let macro_directory_path = {
+        // There might be several write actions at once, use write action output hash to deterministically avoid collisions for .macro files.
+        let digest = output_artifact
+            .get_path()
+            .with_full_path(|path| Sha1::digest(path.as_str().as_bytes()));
+        let sha = hex::encode(digest);
+        format!("__macros/{}", sha)
+    };
+
+    let mut written_macro_files = indexset![];
+    for i in 0..written_macro_count {
+        let macro_file = this.declare_output(
+            None,
+            &format!("{}/{}.macro", &macro_directory_path, i),
+            OutputType::File,
+            eval.call_stack_top_location(),
+        )?;
+        written_macro_files.insert(macro_file);
+    }
+
+    let action = UnregisteredWriteMacrosToFileAction::new(
+        output_artifact
+            .get_path()
+            .with_short_path(|p| p.to_string()),
+    );
+    this.register_action(
+        indexset![],
+        written_macro_files.iter().map(|a| a.as_output()).collect(),
+        action,
+        Some(content_cli),
+    )?;
+
+    Ok(written_macro_files)
+}
+
 fn create_dir_tree<'v>(
     eval: &mut Evaluator<'v, '_>,
     this: &AnalysisActions<'v>,
@@ -311,41 +415,6 @@ fn analysis_actions_methods_actions(builder: &mut MethodsBuilder) {
         #[starlark(require = named, default = false)] with_inputs: bool,
         eval: &mut Evaluator<'v, '_>,
     ) -> anyhow::Result<Value<'v>> {
-        fn count_write_to_file_macros(
-            args_allowed: bool,
-            cli: &dyn CommandLineArgLike,
-        ) -> anyhow::Result<u32> {
-            if !args_allowed && cli.contains_arg_attr() {
-                return Err(anyhow::anyhow!(
-                    WriteActionError::ArgAttrsDetectedButNotAllowed
-                ));
-            }
-
-            struct WriteToFileMacrosCounter {
-                count: u32,
-            }
-
-            impl WriteToFileMacroVisitor for WriteToFileMacrosCounter {
-                fn visit_write_to_file_macro(&mut self, _m: &ResolvedMacro) -> anyhow::Result<()> {
-                    self.count += 1;
-                    Ok(())
-                }
-
-                fn set_current_relative_to_path(
-                    &mut self,
-                    _gen: &dyn Fn(
-                        &dyn CommandLineContext,
-                    ) -> anyhow::Result<Option<RelativePathBuf>>,
-                ) -> anyhow::Result<()> {
-                    Ok(())
-                }
-            }
-
-            let mut counter = WriteToFileMacrosCounter { count: 0 };
-            cli.visit_write_to_file_macros(&mut counter)?;
-            Ok(counter.count)
-        }
-
         fn get_cli_inputs(
             with_inputs: bool,
             cli: &dyn CommandLineArgLike,
@@ -387,44 +456,13 @@ fn analysis_actions_methods_actions(builder: &mut MethodsBuilder) {
                 (eval.heap().alloc(cli), count, cli_inputs)
             };
 
-        let written_macro_files = if written_macro_count > 0 {
-            let macro_directory_path = {
-                // There might be several write actions at once, use write action output hash to deterministically avoid collisions for .macro files.
-                let digest = output_artifact
-                    .get_path()
-                    .with_full_path(|path| Sha1::digest(path.as_str().as_bytes()));
-                let sha = hex::encode(digest);
-                format!("__macros/{}", sha)
-            };
-
-            let mut written_macro_files = indexset![];
-            for i in 0..written_macro_count {
-                let macro_file = this.declare_output(
-                    None,
-                    &format!("{}/{}.macro", &macro_directory_path, i),
-                    OutputType::File,
-                    eval.call_stack_top_location(),
-                )?;
-                written_macro_files.insert(macro_file);
-            }
-
-            let state = &mut *this;
-            let action = UnregisteredWriteMacrosToFileAction::new(
-                output_artifact
-                    .get_path()
-                    .with_short_path(|p| p.to_string()),
-            );
-            state.register_action(
-                indexset![],
-                written_macro_files.iter().map(|a| a.as_output()).collect(),
-                action,
-                Some(eval.heap().alloc(content_cli)),
-            )?;
-
-            written_macro_files
-        } else {
-            indexset![]
-        };
+        let written_macro_files = declare_write_to_file_macros(
+            &mut this,
+            eval,
+            &output_artifact,
+            content_cli,
+            written_macro_count,
+        )?;
 
         let action = {
             let maybe_macro_files = if allow_args {
@@ -468,6 +506,101 @@ fn analysis_actions_methods_actions(builder: &mut MethodsBuilder) {
         }
     }
 
+    /// Returns an `artifact` whose contents are those of `template`, with every occurrence of each key of `substitutions` replaced by its value
+    ///
+    /// * `substitutions`: a dictionary of placeholder to replacement. Replacements can be strings or anything accepted by `cmd_args`; artifacts are written as their paths (relative to the project root, unless `relative_to` is used), and multiple arguments are joined by spaces
+    /// * `is_executable` (optional): indicates whether the resulting file should be marked with executable permissions
+    /// * `allow_args` (optional): must be set to `True` if you want to use parameter arguments in replacements (in particular, macros that write to file)
+    ///     * If it is true, the result will be a pair of the `artifact` containing the expanded template and a list of artifact values that were written by macros, which should be used in hidden fields or similar
+    ///
+    /// Substitutions are made in a single pass over the template, so replacements are never themselves substituted. Where several placeholders match at the same position, the longest one is used. The template does not need to be UTF-8.
+    #[starlark(return_type = "[\"artifact\", (\"artifact\", [\"artifact\"])]")]
+    fn expand_template<'v>(
+        this: &AnalysisActions<'v>,
+        #[starlark(require = pos, type = TYPE_ARTIFACT)] template: Value<'v>,
+        #[starlark(require = pos, type = TYPE_INPUT_ARTIFACT)] output: Value<'v>,
+        #[starlark(require = named)] substitutions: Option<
+            ValueOf<'v, SmallMap<&'v str, Value<'v>>>,
+        >,
+        #[starlark(require = named, default = false)] is_executable: bool,
+        #[starlark(require = named, default = false)] allow_args: bool,
+        eval: &mut Evaluator<'v, '_>,
+    ) -> anyhow::Result<Value<'v>> {
+        let template = template
+            .as_artifact()
+            .ok_or_else(|| ValueError::IncorrectParameterTypeNamed("template".to_owned()))?;
+        let artifact = template.get_bound_artifact()?;
+
+        let (substitutions, replacements) = match substitutions {
+            None => (eval.heap().alloc(AllocDict::EMPTY), Vec::new()),
+            Some(substitutions) => {
+                let mut replacements = Vec::with_capacity(substitutions.typed.len());
+                for (k, v) in substitutions.typed.iter() {
+                    if k.is_empty() {
+                        return Err(ExpandTemplateError::EmptySubstitutionKey.into());
+                    }
+                    v.as_command_line_err()?;
+                    replacements.push(*v);
+                }
+                (substitutions.value, replacements)
+            }
+        };
+
+        // The replacements in the same order the action renders them, so that write-to-file
+        // macros are assigned the same files by both.
+        let replacements_cli =
+            StarlarkCommandLine::try_from_value(eval.heap().alloc(AllocList(replacements)))?;
+        let written_macro_count = count_write_to_file_macros(allow_args, &replacements_cli)?;
+        let replacements_cli = eval.heap().alloc(replacements_cli);
+
+        let mut this = this.state();
+        let (declaration, output_artifact) =
+            this.get_or_declare_output(eval, output, "output", OutputType::File)?;
+
+        let written_macro_files = declare_write_to_file_macros(
+            &mut this,
+            eval,
+            &output_artifact,
+            replacements_cli,
+            written_macro_count,
+        )?;
+        let mut macro_files = indexset![];
+        for a in &written_macro_files {
+            macro_files.insert(a.dupe().ensure_bound()?.into_artifact());
+        }
+
+        this.register_action(
+            indexset![ArtifactGroup::Artifact(artifact)],
+            indexset![output_artifact],
+            UnregisteredExpandTemplateAction::new(
+                is_executable,
+                if allow_args {
+                    Some(macro_files.clone())
+                } else {
+                    None
+                },
+            ),
+            Some(substitutions),
+        )?;
+
+        let associated_artifacts = macro_files
+            .into_iter()
+            .map(ArtifactGroup::Artifact)
+            .collect::<SmallSet<_>>();
+        let value = declaration
+            .into_declared_artifact(AssociatedArtifacts::from(associated_artifacts))
+            .to_value();
+        if allow_args {
+            let macro_files: Vec<StarlarkDeclaredArtifact> = written_macro_files
+                .into_iter()
+                .map(|a| StarlarkDeclaredArtifact::new(None, a, AssociatedArtifacts::new()))
+                .collect();
+            Ok(eval.heap().alloc((value, macro_files)))
+        } else {
+            Ok(value)
+        }
+    }
+
     /// Copies the source `artifact` to the destination (which can be a string representing a filename or an output `artifact`) and returns the output `artifact`.
     /// The copy works for files or directories.
     #[starlark(return_type = TYPE_ARTIFACT)]
@@ -561,6 +694,80 @@ fn analysis_actions_methods_actions(builder: &mut MethodsBuilder) {
         create_dir_tree(eval, this, output, srcs, true)
     }
 
+    /// Returns an `artifact` which is an archive containing the srcs.
+    /// The srcs must be a dictionary of path (as string, relative to the root of the archive) to the bound `artifact`, which will be laid out in the archive.
+    /// Directories are added recursively, and symlinks are followed.
+    ///
+    /// * `format`: one of `"zip"`, `"tar"` or `"tar.zst"`
+    ///
+    /// The archive is built by Buck2 itself and is byte-for-byte reproducible: entries are sorted by path, timestamps are fixed, ownership is cleared, and permissions are `0644`, or `0755` for directories and executable files.
+    #[starlark(return_type = TYPE_ARTIFACT)]
+    fn archive<'v>(
+        this: &AnalysisActions<'v>,
+        #[starlark(require = pos, type = TYPE_INPUT_ARTIFACT)] output: Value<'v>,
+        #[starlark(require = pos, type = "{str.type, \"artifact\"}")] srcs: Value<'v>,
+        #[starlark(require = named, default = "zip")] format: &str,
+        eval: &mut Evaluator<'v, '_>,
+    ) -> anyhow::Result<Value<'v>> {
+        let action = UnregisteredArchiveAction::new(ArchiveFormat::from_name(format)?, srcs)?;
+        let inputs = action.inputs();
+        let unioned_associated_artifacts = action.unioned_associated_artifacts();
+
+        let mut this = this.state();
+        let (declaration, output_artifact) =
+            this.get_or_declare_output(eval, output, "output", OutputType::File)?;
+        this.register_action(inputs, indexset![output_artifact], action, None)?;
+
+        let value = declaration
+            .into_declared_artifact(unioned_associated_artifacts)
+            .to_value();
+        Ok(value)
+    }
+
+    /// Returns an `artifact` which is a directory containing the extracted contents of the archive.
+    ///
+    /// * `format` (optional): one of `"zip"`, `"tar"` or `"tar.zst"`. Defaults to guessing from the extension of the archive
+    ///
+    /// Only the executable bit of extracted files is preserved. Archives containing absolute paths, `..` components, symlinks pointing outside of the output, or hard links are rejected.
+    #[starlark(return_type = TYPE_ARTIFACT)]
+    fn extract<'v>(
+        this: &AnalysisActions<'v>,
+        #[starlark(require = pos, type = TYPE_ARTIFACT)] archive: Value<'v>,
+        #[starlark(require = pos, type = TYPE_INPUT_ARTIFACT)] output: Value<'v>,
+        #[starlark(require = named, default = NoneOr::None)] format: NoneOr<&str>,
+        eval: &mut Evaluator<'v, '_>,
+    ) -> anyhow::Result<Value<'v>> {
+        let archive = archive
+            .as_artifact()
+            .ok_or_else(|| ValueError::IncorrectParameterTypeNamed("archive".to_owned()))?;
+        let artifact = archive.get_bound_artifact()?;
+        let format = match format.into_option() {
+            Some(format) => ArchiveFormat::from_name(format)?,
+            None => artifact
+                .get_path()
+                .with_filename(|f| anyhow::Ok(ArchiveFormat::from_file_name(f?)))?
+                .ok_or_else(|| ExtractError::UnknownFormat(artifact.get_path().to_string()))?,
+        };
+        let associated_artifacts = archive.get_associated_artifacts();
+
+        let mut this = this.state();
+        let (declaration, output_artifact) =
+            this.get_or_declare_output(eval, output, "output", OutputType::Directory)?;
+        this.register_action(
+            indexset![ArtifactGroup::Artifact(artifact)],
+            indexset![output_artifact],
+            UnregisteredExtractAction::new(format),
+            None,
+        )?;
+
+        let value = declaration.into_declared_artifact(
+            associated_artifacts
+                .duped()
+                .unwrap_or_else(AssociatedArtifacts::new),
+        );
+        Ok(value.to_value())
+    }
+
     /// Runs a command
     ///
     /// * `arguments`: must be of type `cmd_args`, or a type convertible to such (such as a list of strings and artifacts) and must contain at least one `.as_output()` artifact
@@ -571,6 +778,7 @@ fn analysis_actions_methods_actions(builder: &mut MethodsBuilder) {
     ///     * `metadata_path`: defines a path relative to the result directory for a file with action metadata, which will be created right before the command will be run.
     ///     * Metadata contains the path relative to the Buck2 project root and hash digest for every action input (this excludes symlinks as they could be resolved by a user script if needed). The resolved path relative to the Buck2 project for the metadata file will be passed to command from arguments, via the environment variable, with its name set by `metadata_env_var`
     ///     * Both `metadata_env_var` and `metadata_path` are useful when making actions behave in an incremental manner (for details, see [Incremental Actions](https://buck2.build/docs/rule_authors/incremental_actions/))
+    /// * `timeout_ms`: if the command runs for longer than this, it is killed and the action fails as timed out. Defaults to the execution platform's `action_timeout_ms`, if any. The timeout applies to remote execution too
     fn run<'v>(
         this: &AnalysisActions<'v>,
         #[starlark(require = pos, type = TYPE_CMD_ARG_LIKE)] arguments: Value<'v>,
@@ -592,6 +800,7 @@ fn analysis_actions_methods_actions(builder: &mut MethodsBuilder) {
         #[starlark(require = named, default = false)] no_outputs_cleanup: bool,
         #[starlark(require = named, default = false)] allow_cache_upload: bool,
         #[starlark(require = named, default = false)] force_full_hybrid_if_capable: bool,
+        #[starlark(require = named)] timeout_ms: Option<i32>,
         #[starlark(require = named)] exe: Option<
             Either<ValueOf<'v, &'v WorkerRunInfo<'v>>, ValueOf<'v, &'v RunInfo<'v>>>,
         >,
@@ -686,6 +895,12 @@ fn analysis_actions_methods_actions(builder: &mut MethodsBuilder) {
             }
         };
 
+        let timeout = match timeout_ms {
+            None => None,
+            Some(v) if v < 1 => return Err(RunActionError::InvalidTimeout(v).into()),
+            Some(v) => Some(Duration::from_millis(v as u64)),
+        };
+
         let starlark_env = match env {
             None => Value::new_none(),
             Some(env) => {
@@ -776,6 +991,7 @@ fn analysis_actions_methods_actions(builder: &mut MethodsBuilder) {
             no_outputs_cleanup,
             allow_cache_upload,
             force_full_hybrid_if_capable,
+            timeout,
         };
         this.state().register_action(
             artifacts.inputs,
@@ -217,3 +217,63 @@ fn declare_output_require_bound() -> anyhow::Result<()> {
         ),
     })
 }
+
+#[test]
+fn run_invalid_timeout() -> anyhow::Result<()> {
+    let content = indoc!(
+        r#"
+         def test(c):
+             out = c.actions.declare_output("out")
+             c.actions.run(["foo", out.as_output()], category = "test_category", timeout_ms = 0)
+         "#
+    );
+
+    let expect = "`timeout_ms` must be a positive integer";
+    run_ctx_test(content, |ret| match ret {
+        Err(e) if e.to_string().contains(expect) => Ok(()),
+        _ => panic!(
+            "Expected a specific failure containing `{}`, got {:?}",
+            expect, ret
+        ),
+    })
+}
+
+#[test]
+fn extract_unknown_format() -> anyhow::Result<()> {
+    let content = indoc!(
+        r#"
+         def test(c):
+             archive = c.actions.write("archive.rar", "")
+             c.actions.extract(archive, "out")
+         "#
+    );
+
+    let expect = "Cannot infer the format of archive";
+    run_ctx_test(content, |ret| match ret {
+        Err(e) if e.to_string().contains(expect) => Ok(()),
+        _ => panic!(
+            "Expected a specific failure containing `{}`, got {:?}",
+            expect, ret
+        ),
+    })
+}
+
+#[test]
+fn expand_template_empty_key() -> anyhow::Result<()> {
+    let content = indoc!(
+        r#"
+         def test(c):
+             template = c.actions.write("template.in", "")
+             c.actions.expand_template(template, "out", substitutions = {"": "x"})
+         "#
+    );
+
+    let expect = "`substitutions` keys must not be empty";
+    run_ctx_test(content, |ret| match ret {
+        Err(e) if e.to_string().contains(expect) => Ok(()),
+        _ => panic!(
+            "Expected a specific failure containing `{}`, got {:?}",
+            expect, ret
+        ),
+    })
+}
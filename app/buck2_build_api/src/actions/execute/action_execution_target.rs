@@ -9,6 +9,7 @@
 
 use std::fmt::Write;
 
+use buck2_common::executor_config::CommandExecutorConfig;
 use buck2_core::base_deferred_key::BaseDeferredKey;
 use buck2_core::category::Category;
 use buck2_core::fs::buck_out_path::BuckOutScratchPath;
@@ -43,6 +44,10 @@ impl<'a> ActionExecutionTarget<'a> {
         self.action.identifier()
     }
 
+    pub fn execution_config(&self) -> &'a CommandExecutorConfig {
+        self.action.execution_config()
+    }
+
     pub fn custom_tmpdir(&self) -> BuckOutScratchPath {
         BuckOutScratchPath::new(
             self.action.owner().dupe(),
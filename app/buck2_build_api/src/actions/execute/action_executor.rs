@@ -63,6 +63,7 @@ use more_futures::cancellation::CancellationContext;
 
 use crate::actions::execute::action_execution_target::ActionExecutionTarget;
 use crate::actions::execute::error::CommandExecutionErrorMarker;
+use crate::actions::execute::error::CommandTimedOutMarker;
 use crate::actions::execute::error::ExecuteError;
 use crate::actions::impls::run_action_knobs::HasRunActionKnobs;
 use crate::actions::impls::run_action_knobs::RunActionKnobs;
@@ -344,6 +345,10 @@ impl BuckActionExecutionContext<'_> {
                 );
                 Ok(result)
             }
+            CommandExecutionStatus::TimedOut { duration, .. } => Err(CommandTimedOutMarker {
+                duration: *duration,
+            }
+            .into()),
             _ => Err(CommandExecutionErrorMarker.into()),
         };
         self.command_reports.extend(rejected_execution.into_iter());
@@ -633,6 +638,7 @@ mod tests {
     use std::sync::atomic::Ordering;
     use std::sync::Arc;
     use std::sync::Mutex;
+    use std::time::Duration;
 
     use allocative::Allocative;
     use async_trait::async_trait;
@@ -674,21 +680,33 @@ mod tests {
     use buck2_events::dispatch::EventDispatcher;
     use buck2_execute::artifact_value::ArtifactValue;
     use buck2_execute::digest_config::DigestConfig;
+    use buck2_execute::execute::action_digest::ActionDigest;
     use buck2_execute::execute::blocking::testing::DummyBlockingExecutor;
     use buck2_execute::execute::clean_output_paths::cleanup_path;
     use buck2_execute::execute::command_executor::ActionExecutionTimingData;
     use buck2_execute::execute::command_executor::CommandExecutor;
+    use buck2_execute::execute::kind::CommandExecutionKind;
+    use buck2_execute::execute::manager::CommandExecutionManager;
+    use buck2_execute::execute::manager::CommandExecutionManagerExt;
     use buck2_execute::execute::prepared::NoOpCommandExecutor;
+    use buck2_execute::execute::prepared::PreparedCommand;
+    use buck2_execute::execute::prepared::PreparedCommandExecutor;
     use buck2_execute::execute::request::CommandExecutionInput;
     use buck2_execute::execute::request::CommandExecutionOutput;
     use buck2_execute::execute::request::CommandExecutionPaths;
     use buck2_execute::execute::request::CommandExecutionRequest;
+    use buck2_execute::execute::request::ExecutorPreference;
     use buck2_execute::execute::request::OutputType;
+    use buck2_execute::execute::result::CommandExecutionMetadata;
+    use buck2_execute::execute::result::CommandExecutionResult;
+    use buck2_execute::execute::result::CommandExecutionStatus;
     use buck2_execute::execute::testing_dry_run::DryRunExecutor;
     use buck2_execute::materialize::nodisk::NoDiskMaterializer;
     use buck2_execute::re::manager::ManagedRemoteExecutionClient;
     use dupe::Dupe;
     use indexmap::indexset;
+    use indexmap::IndexMap;
+    use indexmap::IndexSet;
     use more_futures::cancellation::CancellationContext;
     use once_cell::sync::Lazy;
     use sorted_vector_map::SortedVectorMap;
@@ -699,6 +717,7 @@ mod tests {
     use crate::actions::execute::action_executor::ActionExecutor;
     use crate::actions::execute::action_executor::ActionOutputs;
     use crate::actions::execute::action_executor::BuckActionExecutor;
+    use crate::actions::execute::error::ExecuteError;
     use crate::actions::key::ActionKeyExt;
     use crate::actions::Action;
     use crate::actions::ActionExecutable;
@@ -708,8 +727,9 @@ mod tests {
     use crate::artifact_groups::ArtifactGroup;
     use crate::artifact_groups::ArtifactGroupValues;
 
-    #[tokio::test]
-    async fn can_execute_some_action() {
+    fn testing_executor(
+        executor: impl FnOnce(ArtifactFs) -> Arc<dyn PreparedCommandExecutor>,
+    ) -> (ProjectRootTemp, BuckActionExecutor) {
         let cells = CellResolver::testing_with_name_and_path(
             CellName::testing_new("cell"),
             CellRootPathBuf::new(ProjectRelativePathBuf::unchecked_new("cell_path".into())),
@@ -726,11 +746,9 @@ mod tests {
             project_fs.dupe(),
         );
 
-        let tracker = Arc::new(Mutex::new(Vec::new()));
-
         let executor = BuckActionExecutor::new(
             CommandExecutor::new(
-                Arc::new(DryRunExecutor::new(tracker, artifact_fs.clone())),
+                executor(artifact_fs.clone()),
                 Arc::new(NoOpCommandExecutor {}),
                 artifact_fs,
                 CommandGenerationOptions {
@@ -755,112 +773,121 @@ mod tests {
             Arc::new(ClientForTest {}),
         );
 
-        #[derive(Debug, Allocative)]
-        struct TestingAction {
-            inputs: BoxSliceSet<ArtifactGroup>,
-            outputs: BoxSliceSet<BuildArtifact>,
-            ran: AtomicBool,
-        }
+        (temp_fs, executor)
+    }
 
-        #[async_trait]
-        impl Action for TestingAction {
-            fn kind(&self) -> buck2_data::ActionKind {
-                buck2_data::ActionKind::NotSet
-            }
+    #[derive(Debug, Allocative)]
+    struct TestingAction {
+        inputs: BoxSliceSet<ArtifactGroup>,
+        outputs: BoxSliceSet<BuildArtifact>,
+        timeout: Option<Duration>,
+        ran: AtomicBool,
+    }
 
-            fn inputs(&self) -> anyhow::Result<Cow<'_, [ArtifactGroup]>> {
-                Ok(Cow::Borrowed(self.inputs.as_slice()))
-            }
+    #[async_trait]
+    impl Action for TestingAction {
+        fn kind(&self) -> buck2_data::ActionKind {
+            buck2_data::ActionKind::NotSet
+        }
 
-            fn outputs(&self) -> anyhow::Result<Cow<'_, [BuildArtifact]>> {
-                Ok(Cow::Borrowed(self.outputs.as_slice()))
-            }
+        fn inputs(&self) -> anyhow::Result<Cow<'_, [ArtifactGroup]>> {
+            Ok(Cow::Borrowed(self.inputs.as_slice()))
+        }
 
-            fn as_executable(&self) -> ActionExecutable<'_> {
-                ActionExecutable::Pristine(self)
-            }
+        fn outputs(&self) -> anyhow::Result<Cow<'_, [BuildArtifact]>> {
+            Ok(Cow::Borrowed(self.outputs.as_slice()))
+        }
 
-            fn category(&self) -> &Category {
-                static TEST_CATEGORY: Lazy<Category> =
-                    Lazy::new(|| Category::try_from("testing").unwrap());
+        fn as_executable(&self) -> ActionExecutable<'_> {
+            ActionExecutable::Pristine(self)
+        }
 
-                &TEST_CATEGORY
-            }
+        fn category(&self) -> &Category {
+            static TEST_CATEGORY: Lazy<Category> =
+                Lazy::new(|| Category::try_from("testing").unwrap());
 
-            fn identifier(&self) -> Option<&str> {
-                None
-            }
+            &TEST_CATEGORY
         }
 
-        #[async_trait]
-        impl PristineActionExecutable for TestingAction {
-            async fn execute(
-                &self,
-                ctx: &mut dyn ActionExecutionCtx,
-            ) -> anyhow::Result<(ActionOutputs, ActionExecutionMetadata)> {
-                self.ran.store(true, Ordering::SeqCst);
-
-                let req = CommandExecutionRequest::new(
-                    vec![],
-                    vec!["foo".to_owned(), "bar".to_owned(), "cmd".to_owned()],
-                    CommandExecutionPaths::new(
-                        self.inputs
-                            .iter()
-                            .map(|x| {
-                                CommandExecutionInput::Artifact(Box::new(
-                                    ArtifactGroupValues::from_artifact(
-                                        x.unpack_artifact().unwrap().dupe(),
-                                        ArtifactValue::file(ctx.digest_config().empty_file()),
-                                    ),
-                                ))
-                            })
-                            .collect(),
-                        self.outputs
-                            .iter()
-                            .map(|b| CommandExecutionOutput::BuildArtifact {
-                                path: b.get_path().dupe(),
-                                output_type: OutputType::FileOrDirectory,
-                            })
-                            .collect(),
-                        ctx.fs(),
-                        ctx.digest_config(),
-                    )?,
-                    SortedVectorMap::new(),
-                );
+        fn identifier(&self) -> Option<&str> {
+            None
+        }
+    }
 
-                // on fake executor, this does nothing
-                let prepared_action = ctx.prepare_action(&req)?;
-                let manager = ctx.command_execution_manager();
-                let res = ctx.exec_cmd(manager, &req, &prepared_action).await;
+    #[async_trait]
+    impl PristineActionExecutable for TestingAction {
+        async fn execute(
+            &self,
+            ctx: &mut dyn ActionExecutionCtx,
+        ) -> anyhow::Result<(ActionOutputs, ActionExecutionMetadata)> {
+            self.ran.store(true, Ordering::SeqCst);
+
+            let req = CommandExecutionRequest::new(
+                vec![],
+                vec!["foo".to_owned(), "bar".to_owned(), "cmd".to_owned()],
+                CommandExecutionPaths::new(
+                    self.inputs
+                        .iter()
+                        .map(|x| {
+                            CommandExecutionInput::Artifact(Box::new(
+                                ArtifactGroupValues::from_artifact(
+                                    x.unpack_artifact().unwrap().dupe(),
+                                    ArtifactValue::file(ctx.digest_config().empty_file()),
+                                ),
+                            ))
+                        })
+                        .collect(),
+                    self.outputs
+                        .iter()
+                        .map(|b| CommandExecutionOutput::BuildArtifact {
+                            path: b.get_path().dupe(),
+                            output_type: OutputType::FileOrDirectory,
+                        })
+                        .collect(),
+                    ctx.fs(),
+                    ctx.digest_config(),
+                )?,
+                SortedVectorMap::new(),
+            );
+            let req = match self.timeout {
+                Some(timeout) => req.with_explicit_timeout(timeout),
+                None => req,
+            };
 
-                // Must write out the things we promised to do
-                for x in &self.outputs {
-                    let dest = x.get_path();
-                    let dest_path = ctx.fs().resolve_build(dest);
-                    ctx.fs().fs().write_file(&dest_path, "", false)?
-                }
+            // on fake executor, this does nothing
+            let prepared_action = ctx.prepare_action(&req)?;
+            let manager = ctx.command_execution_manager();
+            let res = ctx.exec_cmd(manager, &req, &prepared_action).await;
 
-                res?;
-                let outputs = self
-                    .outputs
-                    .iter()
-                    .map(|o| {
-                        (
-                            o.get_path().dupe(),
-                            ArtifactValue::file(ctx.digest_config().empty_file()),
-                        )
-                    })
-                    .collect();
-                Ok((
-                    ActionOutputs::new(outputs),
-                    ActionExecutionMetadata {
-                        execution_kind: ActionExecutionKind::Simple,
-                        timing: ActionExecutionTimingData::default(),
-                    },
-                ))
+            // Must write out the things we promised to do
+            for x in &self.outputs {
+                let dest = x.get_path();
+                let dest_path = ctx.fs().resolve_build(dest);
+                ctx.fs().fs().write_file(&dest_path, "", false)?
             }
+
+            res?;
+            let outputs = self
+                .outputs
+                .iter()
+                .map(|o| {
+                    (
+                        o.get_path().dupe(),
+                        ArtifactValue::file(ctx.digest_config().empty_file()),
+                    )
+                })
+                .collect();
+            Ok((
+                ActionOutputs::new(outputs),
+                ActionExecutionMetadata {
+                    execution_kind: ActionExecutionKind::Simple,
+                    timing: ActionExecutionTimingData::default(),
+                },
+            ))
         }
+    }
 
+    fn testing_action(timeout: Option<Duration>) -> (RegisteredAction, IndexSet<BuildArtifact>) {
         let pkg = PackageLabel::new(
             CellName::testing_new("cell"),
             CellRelativePath::unchecked_new("pkg"),
@@ -888,10 +915,22 @@ mod tests {
             Box::new(TestingAction {
                 inputs: BoxSliceSet::from(inputs),
                 outputs: BoxSliceSet::from(outputs.clone()),
+                timeout,
                 ran: Default::default(),
             }),
             CommandExecutorConfig::testing_local(),
         );
+
+        (action, outputs)
+    }
+
+    #[tokio::test]
+    async fn can_execute_some_action() {
+        let tracker = Arc::new(Mutex::new(Vec::new()));
+        let (_temp_fs, executor) =
+            testing_executor(|artifact_fs| Arc::new(DryRunExecutor::new(tracker, artifact_fs)));
+        let (action, outputs) = testing_action(None);
+
         let res = with_dispatcher_async(
             EventDispatcher::null(),
             executor.execute(Default::default(), &action, CancellationContext::testing()),
@@ -911,6 +950,115 @@ mod tests {
         assert_eq!(res.0, ActionOutputs::new(outputs));
     }
 
+    /// Reports every command that has a timeout as having exceeded it.
+    struct TimingOutExecutor;
+
+    #[async_trait]
+    impl PreparedCommandExecutor for TimingOutExecutor {
+        async fn exec_cmd(
+            &self,
+            command: &PreparedCommand<'_, '_>,
+            manager: CommandExecutionManager,
+            _cancellations: &CancellationContext,
+        ) -> CommandExecutionResult {
+            let manager = manager.claim().await;
+            let exec_kind = CommandExecutionKind::Local {
+                digest: ActionDigest::empty(command.digest_config.cas_digest_config()),
+                command: Default::default(),
+                env: Default::default(),
+            };
+            match command.request.timeout() {
+                Some(timeout) => manager.timeout(
+                    exec_kind,
+                    timeout,
+                    Default::default(),
+                    CommandExecutionMetadata::default(),
+                ),
+                None => manager.success(
+                    exec_kind,
+                    IndexMap::new(),
+                    Default::default(),
+                    CommandExecutionMetadata::default(),
+                ),
+            }
+        }
+
+        fn is_local_execution_possible(&self, _executor_preference: ExecutorPreference) -> bool {
+            false
+        }
+    }
+
+    #[tokio::test]
+    async fn test_action_timed_out() {
+        let (_temp_fs, executor) = testing_executor(|_| Arc::new(TimingOutExecutor));
+        let (action, _outputs) = testing_action(Some(Duration::from_millis(10)));
+
+        let (res, reports) = with_dispatcher_async(
+            EventDispatcher::null(),
+            executor.execute(Default::default(), &action, CancellationContext::testing()),
+        )
+        .await;
+
+        let error = match res {
+            Err(error @ ExecuteError::CommandTimedOut { .. }) => error,
+            Err(error) => panic!("expected a timeout, got {:?}", error),
+            Ok(..) => panic!("expected a timeout, got success"),
+        };
+        match error.as_proto() {
+            buck2_data::action_execution_end::Error::TimedOut(timed_out) => {
+                assert_eq!(timed_out.message, "Command timed out after 0.010s");
+                assert!(timed_out.duration.is_some());
+            }
+            error => panic!("expected a timeout, got {:?}", error),
+        }
+        assert!(matches!(
+            reports.last().map(|report| &report.status),
+            Some(CommandExecutionStatus::TimedOut { duration, .. })
+                if *duration == Duration::from_millis(10)
+        ));
+    }
+
+    #[test]
+    fn test_explicit_timeout_is_sent_to_re() -> anyhow::Result<()> {
+        let (_temp_fs, executor) = testing_executor(|_| Arc::new(TimingOutExecutor));
+        let command_executor = &executor.command_executor;
+        let digest_config = DigestConfig::testing_default();
+
+        let request = || -> anyhow::Result<CommandExecutionRequest> {
+            Ok(CommandExecutionRequest::new(
+                vec![],
+                vec!["true".to_owned()],
+                CommandExecutionPaths::new(
+                    Vec::new(),
+                    IndexSet::new(),
+                    command_executor.fs(),
+                    digest_config,
+                )?,
+                SortedVectorMap::new(),
+            ))
+        };
+        let action_digest = |request: CommandExecutionRequest| -> anyhow::Result<ActionDigest> {
+            Ok(command_executor
+                .prepare_action(&request, digest_config)?
+                .action)
+        };
+
+        let timeout = Duration::from_secs(10);
+        let without_timeout = action_digest(request()?)?;
+        // `buck2.enforce_re_timeouts` isn't set, so other timeouts aren't sent to RE...
+        assert_eq!(
+            without_timeout,
+            action_digest(request()?.with_timeout(timeout))?
+        );
+        // ... but the ones actions ask for are.
+        assert_ne!(
+            without_timeout,
+            action_digest(request()?.with_explicit_timeout(timeout))?
+        );
+
+        Ok(())
+    }
+
     #[test]
     fn test_cleanup_path_missing() -> anyhow::Result<()> {
         let fs = ProjectRootTemp::new()?;
@@ -25,7 +25,8 @@ pub struct RunActionKnobs {
     /// builds.
     pub use_network_action_output_cache: bool,
 
-    /// Whether to enforce timeouts when running things on RE.
+    /// Whether to enforce timeouts when running things on RE. Timeouts that actions set
+    /// explicitly (`timeout_ms`, `action_timeout_ms`) are always enforced.
     pub enforce_re_timeouts: bool,
 }
 
@@ -15,7 +15,9 @@ use std::io::Write;
 use std::pin::Pin;
 use std::sync::Arc;
 
+use allocative::Allocative;
 use buck2_artifact::actions::key::ActionKey;
+use buck2_artifact::artifact::artifact_type::BaseArtifactKind;
 use buck2_core::build_file_path::BuildFilePath;
 use buck2_core::cells::cell_path::CellPath;
 use buck2_core::cells::CellResolver;
@@ -37,8 +39,10 @@ use ref_cast::RefCast;
 use serde::Serialize;
 use serde::Serializer;
 
+use crate::actions::impls::expanded_command_line::ExpandedCommandLine;
 use crate::actions::RegisteredAction;
 use crate::analysis::AnalysisResult;
+use crate::artifact_groups::ResolvedArtifactGroup;
 use crate::artifact_groups::TransitiveSetProjectionKey;
 
 #[derive(Debug, derive_more::Display, RefCast, Serialize)]
@@ -56,7 +60,7 @@ impl ActionAttr {
 pub struct SetProjectionInputsData {
     key: TransitiveSetProjectionKey,
     pub direct: Vec<ActionKey>,
-    pub(crate) children: Vec<SetProjectionInputs>,
+    pub children: Vec<SetProjectionInputs>,
 }
 
 #[derive(Debug, Clone, Eq, PartialEq, Hash)]
@@ -89,15 +93,52 @@ pub enum ActionInput {
     IndirectInputs(SetProjectionInputs),
 }
 
-#[derive(Derivative, Clone, Dupe)]
+impl ActionInput {
+    /// The keys of the actions producing `inputs`, including those reached through transitive set
+    /// projections, in first-seen order and without duplicates.
+    pub fn action_keys(inputs: impl IntoIterator<Item = ActionInput>) -> Vec<ActionKey> {
+        let mut keys = Vec::new();
+        let mut visited = HashSet::new();
+        let mut queue = Vec::new();
+        for input in inputs {
+            match input {
+                ActionInput::ActionKey(key) => keys.push(key),
+                ActionInput::IndirectInputs(projection) => queue.push(projection),
+            }
+        }
+        while let Some(projection) = queue.pop() {
+            if visited.insert(projection.dupe()) {
+                keys.extend(projection.node.direct.iter().cloned());
+                queue.extend(projection.node.children.iter().cloned());
+            }
+        }
+
+        let mut seen = HashSet::new();
+        keys.retain(|key| seen.insert(key.dupe()));
+        keys
+    }
+}
+
+#[derive(Derivative, Clone, Dupe, Allocative)]
 #[derivative(Debug)]
 pub struct ActionQueryNode {
     action: Arc<RegisteredAction>,
+    #[allocative(skip)]
     deps: Arc<Vec<ActionInput>>,
     #[derivative(Debug = "ignore")]
+    #[allocative(skip)]
     fs: Arc<ArtifactFs>,
 }
 
+/// Action nodes are identified by their action key.
+impl PartialEq for ActionQueryNode {
+    fn eq(&self, other: &Self) -> bool {
+        self.action.key() == other.action.key()
+    }
+}
+
+impl Eq for ActionQueryNode {}
+
 impl ActionQueryNode {
     pub fn new(action: Arc<RegisteredAction>, deps: Vec<ActionInput>, fs: Arc<ArtifactFs>) -> Self {
         Self {
@@ -107,11 +148,15 @@ impl ActionQueryNode {
         }
     }
 
-    pub fn attrs(&self) -> IndexMap<String, String> {
-        let mut attrs = self.action.action().aquery_attributes(&ExecutorFs::new(
+    fn executor_fs(&self) -> ExecutorFs {
+        ExecutorFs::new(
             &self.fs,
             self.action.execution_config().options.path_separator,
-        ));
+        )
+    }
+
+    pub fn attrs(&self) -> IndexMap<String, String> {
+        let mut attrs = self.action.action().aquery_attributes(&self.executor_fs());
         attrs.insert(
             "executor_configuration".to_owned(),
             self.action.execution_config().executor.to_string(),
@@ -122,6 +167,32 @@ impl ActionQueryNode {
     pub fn action(&self) -> Arc<RegisteredAction> {
         self.action.dupe()
     }
+
+    /// The command line and environment the action runs, with artifacts resolved to paths, or
+    /// `None` if the action doesn't run a command.
+    pub fn command_line(&self) -> anyhow::Result<Option<ExpandedCommandLine>> {
+        self.action
+            .action()
+            .aquery_command_line(&self.executor_fs())
+    }
+
+    /// The source files (or directories) this action reads directly. Sources that only reach the
+    /// action through a transitive set are not included.
+    pub fn source_inputs(&self) -> anyhow::Result<Vec<CellPath>> {
+        let mut paths = Vec::new();
+        for input in self.action.inputs()?.iter() {
+            if let ResolvedArtifactGroup::Artifact(artifact) = input.assert_resolved() {
+                if let (BaseArtifactKind::Source(source), projection) = artifact.as_parts() {
+                    let path = source.get_path().to_cell_path();
+                    paths.push(match projection {
+                        Some(projection) => path.join(projection),
+                        None => path,
+                    });
+                }
+            }
+        }
+        Ok(paths)
+    }
 }
 
 impl LabeledNode for ActionQueryNode {
@@ -34,8 +34,9 @@ pub static AUDIT_OUTPUT: LateBinding<
         &'v CellResolver,
         &'v DiceComputations,
         global_target_platform: Option<TargetLabel>,
-    )
-        -> Pin<Box<dyn Future<Output = anyhow::Result<Option<AuditOutputResult>>> + 'v>>,
+    ) -> Pin<
+        Box<dyn Future<Output = anyhow::Result<Option<AuditOutputResult>>> + Send + 'v>,
+    >,
 > = LateBinding::new("AUDIT_OUTPUT");
 
 pub async fn audit_output<'v>(
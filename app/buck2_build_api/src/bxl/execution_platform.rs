@@ -32,6 +32,7 @@ pub static EXECUTION_PLATFORM: Lazy<ExecutionPlatformResolution> = Lazy::new(||
                     path_separator: PathSeparatorKind::system_default(),
                     output_paths_behavior: Default::default(),
                 },
+                action_timeout: None,
             }),
             ConfigurationNoExec::unspecified(),
         )),
@@ -21,11 +21,15 @@ use dice::Dice;
 use dice::WhichDice;
 use dice::WhichSpawner;
 
+use crate::subscription_events::SetSubscriptionEvents;
+use crate::subscription_events::SubscriptionEvents;
+
 /// Utility to configure the dice globals.
 /// One place to not forget to initialize something in all places.
 pub async fn configure_dice_for_buck(
     io: Arc<dyn IoProvider>,
     digest_config: DigestConfig,
+    subscription_events: Arc<SubscriptionEvents>,
     root_config: Option<&LegacyBuckConfig>,
     detect_cycles: Option<DetectCycles>,
     which_dice: Option<WhichDice>,
@@ -61,6 +65,7 @@ pub async fn configure_dice_for_buck(
     };
     dice.set_io_provider(io);
     dice.set_digest_config(digest_config);
+    dice.set_subscription_events(subscription_events);
 
     let dice = dice.build_with_which_spawner(detect_cycles, which_spawner);
     let mut dice_ctx = dice.updater();
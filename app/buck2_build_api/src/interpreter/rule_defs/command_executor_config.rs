@@ -8,6 +8,7 @@
  */
 
 use std::sync::Arc;
+use std::time::Duration;
 
 use allocative::Allocative;
 use anyhow::Context as _;
@@ -17,6 +18,7 @@ use buck2_common::executor_config::CommandGenerationOptions;
 use buck2_common::executor_config::Executor;
 use buck2_common::executor_config::HybridExecutionLevel;
 use buck2_common::executor_config::LocalExecutorOptions;
+use buck2_common::executor_config::LocalSandboxOptions;
 use buck2_common::executor_config::PathSeparatorKind;
 use buck2_common::executor_config::RemoteEnabledExecutor;
 use buck2_common::executor_config::RemoteExecutorOptions;
@@ -45,6 +47,10 @@ enum CommandExecutorConfigErrors {
         "executor config must specify at least `local_enabled = True` or `remote_enabled = True`"
     )]
     NoExecutor,
+    #[error(
+        "`local_sandbox` cannot be combined with `use_persistent_workers`, since persistent workers outlive the actions they run"
+    )]
+    SandboxWithPersistentWorkers,
 }
 
 #[derive(Debug, Display, NoSerialize, ProvidesStaticType, Allocative)]
@@ -82,6 +88,9 @@ pub fn register_command_executor_config(builder: &mut GlobalsBuilder) {
     /// * `max_cache_upload_mebibytes`: Maximum size to upload in cache uploads
     /// * `experimental_low_pass_filter`: Whether to use the experimental low pass filter
     /// * `remote_output_paths`: How to express output paths to RE
+    /// * `local_sandbox`: Whether to run local actions in a sandbox that only exposes their declared inputs and outputs (Linux only). Other files in the project are replaced by empty placeholders, and actions that open one fail with an error listing them. Cannot be combined with `use_persistent_workers`
+    /// * `local_sandbox_allow_network`: Whether sandboxed local actions may access the network
+    /// * `action_timeout_ms`: Timeout for actions that don't set `timeout_ms` themselves. Actions that exceed it are killed and fail as timed out, including on remote execution
     #[starlark(dot_type = "command_executor_config")]
     fn CommandExecutorConfig<'v>(
         #[starlark(require = named)] local_enabled: bool,
@@ -105,6 +114,9 @@ pub fn register_command_executor_config(builder: &mut GlobalsBuilder) {
         >,
         #[starlark(default = false, require = named)] experimental_low_pass_filter: bool,
         #[starlark(default = NoneOr::None, require = named)] remote_output_paths: NoneOr<&str>,
+        #[starlark(default = false, require = named)] local_sandbox: bool,
+        #[starlark(default = false, require = named)] local_sandbox_allow_network: bool,
+        #[starlark(default = NoneOr::None, require = named)] action_timeout_ms: NoneOr<i32>,
     ) -> anyhow::Result<StarlarkCommandExecutorConfig> {
         let command_executor_config = {
             let remote_execution_max_input_files_mebibytes =
@@ -144,8 +156,20 @@ pub fn register_command_executor_config(builder: &mut GlobalsBuilder) {
             };
 
             let local_options = if local_enabled {
+                if local_sandbox && use_persistent_workers {
+                    return Err(CommandExecutorConfigErrors::SandboxWithPersistentWorkers.into());
+                }
+
+                let sandbox = if local_sandbox {
+                    Some(LocalSandboxOptions {
+                        allow_network: local_sandbox_allow_network,
+                    })
+                } else {
+                    None
+                };
                 Some(LocalExecutorOptions {
                     use_persistent_workers,
+                    sandbox,
                 })
             } else {
                 None
@@ -257,6 +281,16 @@ pub fn register_command_executor_config(builder: &mut GlobalsBuilder) {
                 }
             };
 
+            let action_timeout = action_timeout_ms
+                .into_option()
+                .map(|t| match u64::try_from(t) {
+                    Ok(t) if t > 0 => Ok(Duration::from_millis(t)),
+                    _ => Err(CommandExecutorConfigErrors::InvalidField(
+                        "action_timeout_ms",
+                    )),
+                })
+                .transpose()?;
+
             let output_paths_behavior = remote_output_paths
                 .into_option()
                 .map(|s| s.parse())
@@ -276,6 +310,7 @@ pub fn register_command_executor_config(builder: &mut GlobalsBuilder) {
                     },
                     output_paths_behavior,
                 },
+                action_timeout,
             }
         };
 
@@ -9,6 +9,7 @@
 
 use std::iter::empty;
 use std::iter::once;
+use std::time::Duration;
 
 use allocative::Allocative;
 use anyhow::Context as _;
@@ -102,6 +103,25 @@ pub struct ExternalRunnerTestInfoGen<V> {
     /// should be ignored when executing tests even if those are passed as required from test runner.
     #[provider(field_type = "DictType<String, Option<FrozenLocalResourceInfo>>")]
     local_resources: V,
+
+    /// RE platform properties to use when running this test remotely. These are merged over the
+    /// properties of the executor the test runs on.
+    /// This is of type {str.type: str.type}
+    #[provider(field_type = "DictType<String, String>")]
+    re_properties: V,
+
+    /// Timeout for each attempt at running this test, in milliseconds. If set, this overrides the
+    /// timeout requested by the test runner.
+    /// This is of type int.type
+    #[provider(field_type = "i32")]
+    timeout_ms: V,
+
+    /// How many times to run this test before giving up on it. Executions that don't exit
+    /// successfully are retried until this many attempts were made. The default is 1, i.e. no
+    /// retries.
+    /// This is of type int.type
+    #[provider(field_type = "i32")]
+    max_attempts: V,
 }
 
 // NOTE: All the methods here unwrap because we validate at freeze time.
@@ -156,6 +176,22 @@ impl FrozenExternalRunnerTestInfo {
         unwrap_all(iter_local_resources(self.local_resources.to_value())).collect()
     }
 
+    pub fn re_properties(&self) -> impl Iterator<Item = (&str, &str)> {
+        unwrap_all(iter_re_properties(self.re_properties.to_value()))
+    }
+
+    pub fn timeout(&self) -> Option<Duration> {
+        unpack_opt_positive_int(self.timeout_ms.to_value(), "timeout_ms")
+            .unwrap()
+            .map(|ms| Duration::from_millis(ms.into()))
+    }
+
+    pub fn max_attempts(&self) -> u32 {
+        unpack_opt_positive_int(self.max_attempts.to_value(), "max_attempts")
+            .unwrap()
+            .unwrap_or(1)
+    }
+
     pub fn visit_artifacts(
         &self,
         visitor: &mut dyn CommandLineArtifactVisitor,
@@ -385,6 +421,63 @@ fn iter_local_resources<'v>(
     }))
 }
 
+fn iter_re_properties<'v>(
+    re_properties: Value<'v>,
+) -> impl Iterator<Item = anyhow::Result<(&'v str, &'v str)>> {
+    if re_properties.is_none() {
+        return Either::Left(Either::Left(empty()));
+    }
+
+    let re_properties = match DictRef::from_value(re_properties) {
+        Some(re_properties) => re_properties,
+        None => {
+            return Either::Left(Either::Right(once(Err(anyhow::anyhow!(
+                "Invalid `re_properties`: Expected a dict, got: `{}`",
+                re_properties
+            )))));
+        }
+    };
+
+    // TODO: In an ideal world this wouldnt be necessary, but re_properties's lifetime is bound by
+    // this function.
+    #[allow(clippy::needless_collect)]
+    let re_properties = re_properties.iter().collect::<Vec<_>>();
+
+    Either::Right(re_properties.into_iter().map(|(key, value)| {
+        let key = key.unpack_str().with_context(|| {
+            format!(
+                "Invalid key in `re_properties`: Expected a str, got: `{}`",
+                key
+            )
+        })?;
+
+        let value = value.unpack_str().with_context(|| {
+            format!(
+                "Invalid value in `re_properties` for key `{}`: Expected a str, got: `{}`",
+                key, value
+            )
+        })?;
+
+        Ok((key, value))
+    }))
+}
+
+fn unpack_opt_positive_int(value: Value, name: &str) -> anyhow::Result<Option<u32>> {
+    let value = NoneOr::<i32>::unpack_value(value)
+        .with_context(|| format!("`{}` must be an int if provided, got: `{}`", name, value))?
+        .into_option();
+
+    match value {
+        Some(value) if value <= 0 => Err(anyhow::anyhow!(
+            "`{}` must be positive, got: `{}`",
+            name,
+            value
+        )),
+        Some(value) => Ok(Some(value as u32)),
+        None => Ok(None),
+    }
+}
+
 fn unpack_opt_executor<'v>(
     executor: Value<'v>,
 ) -> anyhow::Result<Option<&'v StarlarkCommandExecutorConfig>> {
@@ -427,6 +520,9 @@ where
     check_all(iter_opt_str_list(info.contacts.to_value(), "contacts"))?;
     check_all(iter_executor_overrides(info.executor_overrides.to_value()))?;
     check_all(iter_local_resources(info.local_resources.to_value()))?;
+    check_all(iter_re_properties(info.re_properties.to_value()))?;
+    unpack_opt_positive_int(info.timeout_ms.to_value(), "timeout_ms")?;
+    unpack_opt_positive_int(info.max_attempts.to_value(), "max_attempts")?;
     NoneOr::<bool>::unpack_value(info.use_project_relative_paths.to_value())
         .context("`use_project_relative_paths` must be a bool if provided")?;
     NoneOr::<bool>::unpack_value(info.run_from_project_root.to_value())
@@ -453,6 +549,9 @@ fn external_runner_test_info_creator(globals: &mut GlobalsBuilder) {
         #[starlark(default = NoneType)] default_executor: Value<'v>,
         #[starlark(default = NoneType)] executor_overrides: Value<'v>,
         #[starlark(default = NoneType)] local_resources: Value<'v>,
+        #[starlark(default = NoneType)] re_properties: Value<'v>,
+        #[starlark(default = NoneType)] timeout_ms: Value<'v>,
+        #[starlark(default = NoneType)] max_attempts: Value<'v>,
     ) -> anyhow::Result<ExternalRunnerTestInfo<'v>> {
         let res = ExternalRunnerTestInfo {
             test_type: r#type,
@@ -465,6 +564,9 @@ fn external_runner_test_info_creator(globals: &mut GlobalsBuilder) {
             default_executor,
             executor_overrides,
             local_resources,
+            re_properties,
+            timeout_ms,
+            max_attempts,
         };
         validate_external_runner_test_info(&res)?;
         Ok(res)
@@ -43,3 +43,4 @@ pub mod keep_going;
 pub mod nodes;
 pub mod query;
 pub mod spawner;
+pub mod subscription_events;
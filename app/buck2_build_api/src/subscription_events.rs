@@ -0,0 +1,235 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+//! Daemon-wide notifications that are forwarded to `buck2 subscribe` clients.
+//!
+//! The daemon owns a single [`SubscriptionEvents`], which it also installs in DICE, so that it is
+//! told about invalidated configured nodes, and so that commands can publish to it. Producers
+//! publish regardless of which command they are running for, and every live subscription receives
+//! a copy. Producers should check [`SubscriptionEvents::has_subscribers`] before doing any work to
+//! construct an event, since there usually are none.
+
+use std::any::Any;
+use std::collections::HashMap;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::sync::Mutex;
+
+use allocative::Allocative;
+use buck2_core::provider::label::ConfiguredProvidersLabel;
+use buck2_core::target::label::ConfiguredTargetLabel;
+use dice::DiceData;
+use dice::DiceDataBuilder;
+use dice::InvalidationTracker;
+use dupe::Dupe;
+use tokio::sync::mpsc;
+use tokio::sync::mpsc::UnboundedReceiver;
+use tokio::sync::mpsc::UnboundedSender;
+
+use crate::nodes::calculation::ConfiguredTargetNodeKey;
+
+#[derive(Clone, Dupe)]
+pub enum SubscriptionEvent {
+    /// Files that the file watcher reported as changed, and invalidated in DICE.
+    FileChanges(Arc<Vec<buck2_data::FileWatcherEvent>>),
+    /// DICE invalidated the configured node of a target that it had computed, either because the
+    /// node was changed, or because one of its inputs was.
+    ConfiguredNodeInvalidated(ConfiguredTargetLabel),
+    /// DICE dropped all its state (e.g. on a Watchman fresh instance), invalidating every
+    /// configured node. Those are not reported individually.
+    AllInvalidated,
+    /// A build command finished building a target.
+    TargetBuilt(Arc<TargetBuilt>),
+}
+
+pub struct TargetBuilt {
+    pub label: ConfiguredProvidersLabel,
+    /// Project-relative paths of the outputs that were built.
+    pub outputs: Vec<String>,
+    /// Errors encountered building this target. Empty on success.
+    pub errors: Vec<String>,
+}
+
+#[derive(Default)]
+struct Subscribers {
+    next_index: u64,
+    senders: HashMap<u64, UnboundedSender<SubscriptionEvent>>,
+}
+
+#[derive(Allocative, Default)]
+pub struct SubscriptionEvents {
+    #[allocative(skip)]
+    subscribers: Mutex<Subscribers>,
+    /// Mirrors `!subscribers.senders.is_empty()`, so that hot paths can check it without locking.
+    has_subscribers: AtomicBool,
+}
+
+impl SubscriptionEvents {
+    pub fn new() -> Arc<Self> {
+        Arc::new(Self::default())
+    }
+
+    /// Whether anyone is listening. Producers should skip publishing when this returns false.
+    pub fn has_subscribers(&self) -> bool {
+        self.has_subscribers.load(Ordering::Relaxed)
+    }
+
+    /// Send an event to all current subscribers.
+    pub fn publish(&self, event: SubscriptionEvent) {
+        let subscribers = self.subscribers.lock().unwrap();
+        for sender in subscribers.senders.values() {
+            // If the receiver hung up, its handle is being dropped and will unregister itself.
+            let _ignored = sender.send(event.dupe());
+        }
+    }
+
+    /// Start receiving events. Events are delivered until the returned handle is dropped.
+    pub fn subscribe(self: &Arc<Self>) -> SubscriptionEventsHandle {
+        let (sender, receiver) = mpsc::unbounded_channel();
+
+        let mut subscribers = self.subscribers.lock().unwrap();
+        let index = subscribers.next_index;
+        subscribers.next_index += 1;
+        subscribers.senders.insert(index, sender);
+        self.has_subscribers.store(true, Ordering::Relaxed);
+
+        SubscriptionEventsHandle {
+            events: self.dupe(),
+            index,
+            receiver,
+        }
+    }
+}
+
+impl InvalidationTracker for SubscriptionEvents {
+    fn key_invalidated(&self, key: &dyn Any) {
+        if !self.has_subscribers() {
+            return;
+        }
+
+        if let Some(key) = key.downcast_ref::<ConfiguredTargetNodeKey>() {
+            self.publish(SubscriptionEvent::ConfiguredNodeInvalidated(key.0.dupe()));
+        }
+    }
+
+    fn all_invalidated(&self) {
+        if self.has_subscribers() {
+            self.publish(SubscriptionEvent::AllInvalidated);
+        }
+    }
+}
+
+pub struct SubscriptionEventsHandle {
+    events: Arc<SubscriptionEvents>,
+    index: u64,
+    receiver: UnboundedReceiver<SubscriptionEvent>,
+}
+
+impl SubscriptionEventsHandle {
+    pub async fn next_event(&mut self) -> Option<SubscriptionEvent> {
+        self.receiver.recv().await
+    }
+}
+
+impl Drop for SubscriptionEventsHandle {
+    fn drop(&mut self) {
+        let mut subscribers = self.events.subscribers.lock().unwrap();
+        subscribers.senders.remove(&self.index);
+        self.events
+            .has_subscribers
+            .store(!subscribers.senders.is_empty(), Ordering::Relaxed);
+    }
+}
+
+pub trait HasSubscriptionEvents {
+    fn get_subscription_events(&self) -> &Arc<SubscriptionEvents>;
+}
+
+pub trait SetSubscriptionEvents {
+    /// Also registers the events to be notified of invalidations.
+    fn set_subscription_events(&mut self, events: Arc<SubscriptionEvents>);
+}
+
+impl HasSubscriptionEvents for DiceData {
+    fn get_subscription_events(&self) -> &Arc<SubscriptionEvents> {
+        self.get::<Arc<SubscriptionEvents>>()
+            .expect("subscription events should be set")
+    }
+}
+
+impl SetSubscriptionEvents for DiceDataBuilder {
+    fn set_subscription_events(&mut self, events: Arc<SubscriptionEvents>) {
+        self.set_invalidation_tracker(events.dupe());
+        self.set(events)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use buck2_core::configuration::data::ConfigurationData;
+
+    use super::*;
+
+    fn label(name: &str) -> ConfiguredTargetLabel {
+        ConfiguredTargetLabel::testing_parse(
+            &format!("cell//pkg:{}", name),
+            ConfigurationData::testing_new(),
+        )
+    }
+
+    async fn next_label(handle: &mut SubscriptionEventsHandle) -> ConfiguredTargetLabel {
+        match handle.next_event().await {
+            Some(SubscriptionEvent::ConfiguredNodeInvalidated(label)) => label,
+            _ => panic!("expected an invalidated node"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_publish_to_all_subscribers() {
+        let events = SubscriptionEvents::new();
+        let mut a = events.subscribe();
+        let mut b = events.subscribe();
+        assert!(events.has_subscribers());
+
+        events.publish(SubscriptionEvent::ConfiguredNodeInvalidated(label("foo")));
+        assert_eq!(next_label(&mut a).await, label("foo"));
+        assert_eq!(next_label(&mut b).await, label("foo"));
+
+        // Once a subscriber is dropped, the others keep receiving events.
+        drop(b);
+        events.publish(SubscriptionEvent::ConfiguredNodeInvalidated(label("bar")));
+        assert_eq!(next_label(&mut a).await, label("bar"));
+
+        // Dropping the last subscriber unregisters it.
+        drop(a);
+        assert!(events.subscribers.lock().unwrap().senders.is_empty());
+        assert!(!events.has_subscribers());
+    }
+
+    #[tokio::test]
+    async fn test_invalidation_tracker() {
+        let events = SubscriptionEvents::new();
+
+        // Nothing is published without subscribers.
+        events.key_invalidated(&ConfiguredTargetNodeKey(label("foo")));
+        let mut handle = events.subscribe();
+
+        // Only configured nodes are reported.
+        events.key_invalidated(&label("bar"));
+        events.key_invalidated(&ConfiguredTargetNodeKey(label("baz")));
+        assert_eq!(next_label(&mut handle).await, label("baz"));
+
+        events.all_invalidated();
+        assert!(matches!(
+            handle.next_event().await,
+            Some(SubscriptionEvent::AllInvalidated)
+        ));
+    }
+}
@@ -9,5 +9,6 @@
 
 mod calculation;
 mod impls;
+mod query;
 pub(crate) mod registry;
 pub(crate) mod testings;
@@ -0,0 +1,258 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+use std::sync::Arc;
+
+use buck2_artifact::actions::key::ActionKey;
+use buck2_artifact::artifact::artifact_type::testing::BuildArtifactTestingExt;
+use buck2_artifact::artifact::artifact_type::Artifact;
+use buck2_artifact::artifact::build_artifact::BuildArtifact;
+use buck2_artifact::artifact::source_artifact::SourceArtifact;
+use buck2_artifact::deferred::data::DeferredData;
+use buck2_artifact::deferred::id::DeferredId;
+use buck2_artifact::deferred::key::DeferredKey;
+use buck2_build_api::actions::query::ActionInput;
+use buck2_build_api::actions::query::ActionQueryNode;
+use buck2_build_api::actions::query::SetProjectionInputs;
+use buck2_build_api::actions::RegisteredAction;
+use buck2_build_api::artifact_groups::ArtifactGroup;
+use buck2_build_api::artifact_groups::TransitiveSetProjectionKey;
+use buck2_common::executor_config::CommandExecutorConfig;
+use buck2_core::base_deferred_key::BaseDeferredKey;
+use buck2_core::buck_path::path::BuckPath;
+use buck2_core::buck_path::resolver::BuckPathResolver;
+use buck2_core::category::Category;
+use buck2_core::cells::cell_path::CellPath;
+use buck2_core::configuration::data::ConfigurationData;
+use buck2_core::fs::artifact_path_resolver::ArtifactFs;
+use buck2_core::fs::buck_out_path::BuckOutPathResolver;
+use buck2_core::fs::paths::abs_norm_path::AbsNormPathBuf;
+use buck2_core::fs::paths::forward_rel_path::ForwardRelativePathBuf;
+use buck2_core::fs::project::ProjectRoot;
+use buck2_core::fs::project_rel_path::ProjectRelativePathBuf;
+use buck2_core::package::package_relative_path::PackageRelativePathBuf;
+use buck2_core::package::PackageLabel;
+use buck2_core::target::label::ConfiguredTargetLabel;
+use buck2_interpreter_for_build::interpreter::testing::cells;
+use buck2_query::query::environment::LabeledNode;
+use buck2_query::query::environment::QueryTarget;
+use buck2_query::query::syntax::simple::eval::set::TargetSet;
+use buck2_query::query::syntax::simple::eval::set::TargetSetExt;
+use dupe::Dupe;
+use indexmap::indexset;
+use indexmap::IndexSet;
+
+use crate::actions::testings::SimpleAction;
+
+fn target() -> ConfiguredTargetLabel {
+    ConfiguredTargetLabel::testing_parse("cell//pkg:foo", ConfigurationData::testing_new())
+}
+
+fn action_key(id: u32) -> ActionKey {
+    ActionKey::unchecked_new(DeferredKey::Base(
+        BaseDeferredKey::TargetLabel(target()),
+        DeferredId::testing_new(id),
+    ))
+}
+
+fn projection_key(id: u32) -> TransitiveSetProjectionKey {
+    TransitiveSetProjectionKey {
+        key: DeferredData::unchecked_new(DeferredKey::Base(
+            BaseDeferredKey::TargetLabel(target()),
+            DeferredId::testing_new(id),
+        )),
+        projection: 0,
+    }
+}
+
+fn artifact_fs() -> Arc<ArtifactFs> {
+    let cell_info = cells(None).unwrap();
+    Arc::new(ArtifactFs::new(
+        BuckPathResolver::new(cell_info.1),
+        BuckOutPathResolver::new(ProjectRelativePathBuf::unchecked_new(
+            "buck-out/v2".to_owned(),
+        )),
+        ProjectRoot::new(AbsNormPathBuf::try_from(std::env::current_dir().unwrap()).unwrap())
+            .unwrap(),
+    ))
+}
+
+fn node_with_inputs(
+    id: u32,
+    category: &str,
+    identifier: Option<&str>,
+    inputs: IndexSet<ArtifactGroup>,
+    deps: Vec<ActionInput>,
+) -> ActionQueryNode {
+    let output = BuildArtifact::testing_new(
+        target(),
+        ForwardRelativePathBuf::unchecked_new(format!("out{}", id)),
+        DeferredId::testing_new(id),
+    );
+    let action = SimpleAction::new(
+        inputs,
+        indexset![output.dupe()],
+        vec!["true".to_owned()],
+        Category::try_from(category).unwrap(),
+        identifier.map(ToOwned::to_owned),
+    );
+    let action = RegisteredAction::new(
+        output.key().dupe(),
+        Box::new(action),
+        CommandExecutorConfig::testing_local(),
+    );
+    ActionQueryNode::new(Arc::new(action), deps, artifact_fs())
+}
+
+fn node(
+    id: u32,
+    category: &str,
+    identifier: Option<&str>,
+    deps: Vec<ActionInput>,
+) -> ActionQueryNode {
+    node_with_inputs(id, category, identifier, IndexSet::new(), deps)
+}
+
+fn attr(node: &ActionQueryNode, key: &str) -> Option<String> {
+    node.map_attr(key, |attr| attr.map(|attr| attr.to_string()))
+}
+
+#[test]
+fn test_eq_by_action_key() {
+    let a = node(1, "cxx_compile", Some("a.cpp"), Vec::new());
+    let a_with_deps = node(
+        1,
+        "cxx_compile",
+        Some("a.cpp"),
+        vec![ActionInput::ActionKey(action_key(2))],
+    );
+    let b = node(2, "cxx_compile", Some("a.cpp"), Vec::new());
+
+    assert_eq!(a, a_with_deps);
+    assert_ne!(a, b);
+    assert_eq!(a.node_ref(), &action_key(1));
+}
+
+#[test]
+fn test_deps_include_projections() {
+    let leaf = SetProjectionInputs::new(projection_key(10), vec![action_key(4)], Vec::new());
+    let middle =
+        SetProjectionInputs::new(projection_key(11), vec![action_key(3)], vec![leaf.dupe()]);
+    // `leaf` is reachable twice, but its keys are only visited once.
+    let link = node(
+        1,
+        "cxx_link",
+        None,
+        vec![
+            ActionInput::ActionKey(action_key(2)),
+            ActionInput::IndirectInputs(middle),
+            ActionInput::IndirectInputs(leaf),
+        ],
+    );
+
+    let deps: Vec<_> = link.deps().cloned().collect();
+    assert_eq!(deps.len(), 3);
+    for id in [2, 3, 4] {
+        assert!(deps.contains(&action_key(id)), "missing dep {}", id);
+    }
+}
+
+#[test]
+fn test_action_keys_dedups_outputs() {
+    let shared = SetProjectionInputs::new(projection_key(10), vec![action_key(3)], Vec::new());
+    let top = SetProjectionInputs::new(
+        projection_key(11),
+        vec![action_key(2), action_key(1)],
+        vec![shared.dupe()],
+    );
+
+    let keys = ActionInput::action_keys(vec![
+        ActionInput::ActionKey(action_key(1)),
+        ActionInput::ActionKey(action_key(1)),
+        ActionInput::IndirectInputs(top),
+        ActionInput::IndirectInputs(shared),
+    ]);
+
+    assert_eq!(keys[0], action_key(1));
+    assert_eq!(keys.len(), 3);
+    for id in [1, 2, 3] {
+        assert!(keys.contains(&action_key(id)), "missing key {}", id);
+    }
+}
+
+#[test]
+fn test_attrs() {
+    let compile = node(1, "cxx_compile", Some("a.cpp"), Vec::new());
+
+    assert_eq!(compile.rule_type(), "notset");
+    assert_eq!(attr(&compile, "kind").as_deref(), Some("notset"));
+    assert_eq!(attr(&compile, "category").as_deref(), Some("cxx_compile"));
+    assert_eq!(attr(&compile, "identifier").as_deref(), Some("a.cpp"));
+    assert_eq!(
+        attr(&compile, "executor_configuration"),
+        Some(compile.action().execution_config().executor.to_string())
+    );
+    assert_eq!(attr(&compile, "missing"), None);
+
+    let anonymous = node(2, "cxx_compile", None, Vec::new());
+    assert_eq!(attr(&anonymous, "identifier").as_deref(), Some(""));
+}
+
+#[test]
+fn test_source_inputs() {
+    let source = SourceArtifact::new(BuckPath::testing_new(
+        PackageLabel::testing_new("cell", "pkg"),
+        PackageRelativePathBuf::unchecked_new("src.c".to_owned()),
+    ));
+    let output = BuildArtifact::testing_new(
+        target(),
+        ForwardRelativePathBuf::unchecked_new("gen.h".to_owned()),
+        DeferredId::testing_new(2),
+    );
+    let node = node_with_inputs(
+        1,
+        "cxx_compile",
+        None,
+        indexset![
+            ArtifactGroup::Artifact(Artifact::from(source)),
+            ArtifactGroup::Artifact(output.into()),
+        ],
+        Vec::new(),
+    );
+
+    assert_eq!(
+        node.source_inputs().unwrap(),
+        vec![CellPath::testing_new("cell//pkg/src.c")]
+    );
+}
+
+#[test]
+fn test_attrfilter() -> anyhow::Result<()> {
+    let mut set = TargetSet::new();
+    set.insert(node(1, "cxx_compile", Some("a.cpp"), Vec::new()));
+    set.insert(node(2, "cxx_compile", Some("b.cpp"), Vec::new()));
+    set.insert(node(3, "cxx_link", None, Vec::new()));
+    // Same key as an existing node, so it isn't added again.
+    assert!(!set.insert(node(1, "cxx_compile", Some("a.cpp"), Vec::new())));
+
+    let compiles = set.attrfilter("category", &|v| Ok(v == "cxx_compile"))?;
+    assert_eq!(
+        compiles.iter_names().cloned().collect::<Vec<_>>(),
+        vec![action_key(1), action_key(2)]
+    );
+
+    let b = set.attrfilter("identifier", &|v| Ok(v == "b.cpp"))?;
+    assert_eq!(
+        b.iter_names().cloned().collect::<Vec<_>>(),
+        vec![action_key(2)]
+    );
+
+    assert!(set.attrfilter("missing", &|_| Ok(true))?.is_empty());
+    Ok(())
+}
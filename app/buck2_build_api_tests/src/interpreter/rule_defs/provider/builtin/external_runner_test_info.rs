@@ -34,6 +34,8 @@ fn test_construction() -> anyhow::Result<()> {
             ExternalRunnerTestInfo(type = "foo", labels = ("foo",))
             ExternalRunnerTestInfo(type = "foo", use_project_relative_paths = True)
             ExternalRunnerTestInfo(type = "foo", run_from_project_root = True)
+            ExternalRunnerTestInfo(type = "foo", re_properties = {"platform": "large"})
+            ExternalRunnerTestInfo(type = "foo", timeout_ms = 1000, max_attempts = 3)
         "#
     );
     let mut tester = tester();
@@ -184,6 +186,36 @@ fn test_validation() -> anyhow::Result<()> {
         "`executor_overrides`",
     );
 
+    tester.run_starlark_bzl_test_expecting_error(
+        indoc!(
+            r#"
+        def test():
+            ExternalRunnerTestInfo(type = "foo", re_properties = {"foo": 123})
+        "#
+        ),
+        "`re_properties`",
+    );
+
+    tester.run_starlark_bzl_test_expecting_error(
+        indoc!(
+            r#"
+        def test():
+            ExternalRunnerTestInfo(type = "foo", timeout_ms = "foo")
+        "#
+        ),
+        "`timeout_ms`",
+    );
+
+    tester.run_starlark_bzl_test_expecting_error(
+        indoc!(
+            r#"
+        def test():
+            ExternalRunnerTestInfo(type = "foo", max_attempts = 0)
+        "#
+        ),
+        "`max_attempts`",
+    );
+
     Ok(())
 }
 
@@ -7,11 +7,13 @@
  * of this source tree.
  */
 
+use buck2_build_api::actions::query::ActionQueryNode;
 use buck2_node::nodes::configured::ConfiguredTargetNode;
 use buck2_node::nodes::unconfigured::TargetNode;
 use starlark::values::Heap;
 use starlark::values::Value;
 
+use crate::bxl::starlark_defs::nodes::action::StarlarkActionQueryNode;
 use crate::bxl::starlark_defs::nodes::configured::StarlarkConfiguredTargetNode;
 use crate::bxl::starlark_defs::nodes::unconfigured::StarlarkTargetNode;
 
@@ -31,3 +33,9 @@ impl AllocNode for ConfiguredTargetNode {
         heap.alloc(StarlarkConfiguredTargetNode(self))
     }
 }
+
+impl AllocNode for ActionQueryNode {
+    fn alloc(self, heap: &Heap) -> Value {
+        heap.alloc(StarlarkActionQueryNode(self))
+    }
+}
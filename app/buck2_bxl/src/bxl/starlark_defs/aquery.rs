@@ -0,0 +1,304 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+use allocative::Allocative;
+use buck2_build_api::actions::query::ActionQueryNode;
+use buck2_build_api::query::bxl::BxlAqueryFunctions;
+use buck2_build_api::query::bxl::NEW_BXL_AQUERY_FUNCTIONS;
+use buck2_core::target::label::TargetLabel;
+use buck2_node::nodes::configured::ConfiguredTargetNode;
+use buck2_query::query::syntax::simple::eval::set::TargetSet;
+use buck2_query::query::syntax::simple::eval::set::TargetSetExt;
+use buck2_query::query::syntax::simple::functions::helpers::CapturedExpr;
+use derivative::Derivative;
+use derive_more::Display;
+use dupe::Dupe;
+use gazebo::prelude::*;
+use starlark::any::ProvidesStaticType;
+use starlark::environment::Methods;
+use starlark::environment::MethodsBuilder;
+use starlark::environment::MethodsStatic;
+use starlark::eval::Evaluator;
+use starlark::starlark_module;
+use starlark::starlark_type;
+use starlark::values::list::ListRef;
+use starlark::values::none::NoneOr;
+use starlark::values::type_repr::StarlarkTypeRepr;
+use starlark::values::AllocValue;
+use starlark::values::Heap;
+use starlark::values::NoSerialize;
+use starlark::values::StarlarkValue;
+use starlark::values::Trace;
+use starlark::values::UnpackValue;
+use starlark::values::Value;
+use starlark::values::ValueLike;
+use starlark::StarlarkDocs;
+use thiserror::Error;
+
+use crate::bxl::starlark_defs::context::BxlContext;
+use crate::bxl::starlark_defs::nodes::action::StarlarkActionQueryNode;
+use crate::bxl::starlark_defs::target_expr::filter_incompatible;
+use crate::bxl::starlark_defs::target_expr::TargetExpr;
+use crate::bxl::starlark_defs::targetset::StarlarkTargetSet;
+use crate::bxl::value_as_starlark_target_label::ValueAsStarlarkTargetLabel;
+
+#[derive(Debug, Error)]
+enum AqueryError {
+    #[error(
+        "Expected an action query node, a list of them or a target set of them, got `{0}` of type `{1}`"
+    )]
+    NotActionNodes(String, String),
+}
+
+#[derive(
+    ProvidesStaticType,
+    Derivative,
+    Display,
+    Trace,
+    NoSerialize,
+    StarlarkDocs,
+    Allocative
+)]
+#[starlark_docs(directory = "bxl")]
+#[derivative(Debug)]
+#[display(fmt = "{:?}", self)]
+#[allocative(skip)]
+pub struct StarlarkAQueryCtx<'v> {
+    #[trace(unsafe_ignore)]
+    #[derivative(Debug = "ignore")]
+    ctx: &'v BxlContext<'v>,
+    #[derivative(Debug = "ignore")]
+    target_platform: Option<TargetLabel>,
+}
+
+impl<'v> StarlarkValue<'v> for StarlarkAQueryCtx<'v> {
+    starlark_type!("aqueryctx");
+
+    fn get_methods() -> Option<&'static Methods> {
+        static RES: MethodsStatic = MethodsStatic::new();
+        RES.methods(register_aquery)
+    }
+}
+
+impl<'v> AllocValue<'v> for StarlarkAQueryCtx<'v> {
+    fn alloc_value(self, heap: &'v Heap) -> Value<'v> {
+        heap.alloc_complex_no_freeze(self)
+    }
+}
+
+impl<'v> StarlarkTypeRepr for &'v StarlarkAQueryCtx<'v> {
+    fn starlark_type_repr() -> String {
+        StarlarkAQueryCtx::get_type_starlark_repr()
+    }
+}
+
+impl<'v> UnpackValue<'v> for &'v StarlarkAQueryCtx<'v> {
+    fn unpack_value(x: Value<'v>) -> Option<&'v StarlarkAQueryCtx<'v>> {
+        x.downcast_ref()
+    }
+}
+
+async fn get_aquery_env<'v>(
+    ctx: &'v BxlContext<'v>,
+    target_platform: Option<TargetLabel>,
+) -> anyhow::Result<Box<dyn BxlAqueryFunctions<'v> + 'v>> {
+    (NEW_BXL_AQUERY_FUNCTIONS.get()?)(
+        ctx.async_ctx.0,
+        target_platform,
+        ctx.project_root().dupe(),
+        ctx.cell_name,
+    )
+    .await
+}
+
+/// Unpacks a single action node, a list of them or a target set of them.
+fn unpack_action_nodes<'v>(value: Value<'v>) -> anyhow::Result<TargetSet<ActionQueryNode>> {
+    if let Some(set) = <&StarlarkTargetSet<ActionQueryNode>>::unpack_value(value) {
+        return Ok(set.0.clone());
+    }
+
+    let mut set = TargetSet::new();
+    if let Some(node) = StarlarkActionQueryNode::unpack_value(value) {
+        set.insert(node.0);
+        return Ok(set);
+    }
+    if let Some(list) = ListRef::from_value(value) {
+        for item in list.iter() {
+            match StarlarkActionQueryNode::unpack_value(item) {
+                Some(node) => set.insert(node.0),
+                None => return Err(not_action_nodes(item)),
+            };
+        }
+        return Ok(set);
+    }
+    Err(not_action_nodes(value))
+}
+
+fn not_action_nodes(value: Value) -> anyhow::Error {
+    AqueryError::NotActionNodes(value.to_repr(), value.get_type().to_owned()).into()
+}
+
+impl<'v> StarlarkAQueryCtx<'v> {
+    pub async fn new(
+        ctx: &'v BxlContext<'v>,
+        global_target_platform: Value<'v>,
+        default_target_platform: &Option<TargetLabel>,
+    ) -> anyhow::Result<StarlarkAQueryCtx<'v>> {
+        let target_platform = global_target_platform.parse_target_platforms(
+            &ctx.target_alias_resolver,
+            &ctx.cell_resolver,
+            ctx.cell_name,
+            default_target_platform,
+        )?;
+
+        Ok(Self {
+            ctx,
+            target_platform,
+        })
+    }
+
+    async fn configured_targets(
+        &self,
+        targets: Value<'v>,
+        eval: &mut Evaluator<'v, '_>,
+    ) -> anyhow::Result<TargetSet<ConfiguredTargetNode>> {
+        filter_incompatible(
+            TargetExpr::<'v, ConfiguredTargetNode>::unpack(
+                targets,
+                &self.target_platform,
+                self.ctx,
+                eval,
+            )
+            .await?
+            .get(self.ctx.async_ctx.0)
+            .await?
+            .into_iter(),
+            self.ctx,
+        )
+    }
+}
+
+/// The context for performing `aquery` operations in bxl. The functions offered on this ctx are
+/// the same behaviour as the query functions available within aquery command.
+///
+/// Query results are `[StarlarkTargetSet]`s of `[StarlarkActionQueryNode]`s, which supports
+/// iteration, indexing, `len()`, set addition/subtraction, and `equals()`.
+#[starlark_module]
+fn register_aquery(builder: &mut MethodsBuilder) {
+    /// The deps query for finding the transitive closure of the dependencies of actions, i.e. the
+    /// actions producing their inputs. `universe` is an action node, a list of them or a target
+    /// set of them.
+    ///
+    /// Sample usage:
+    /// ```text
+    /// def _impl_deps(ctx):
+    ///     aquery = ctx.aquery()
+    ///     deps = aquery.deps(aquery.all_outputs("//foo:bar"), 1)
+    ///     ctx.output.print(deps)
+    /// ```
+    fn deps<'v>(
+        this: &StarlarkAQueryCtx<'v>,
+        universe: Value<'v>,
+        #[starlark(default = NoneOr::None)] depth: NoneOr<i32>,
+        #[starlark(default = NoneOr::None)] filter: NoneOr<&'v str>,
+    ) -> anyhow::Result<StarlarkTargetSet<ActionQueryNode>> {
+        let universe = unpack_action_nodes(universe)?;
+        this.ctx
+            .async_ctx
+            .via(|| async {
+                let filter = filter
+                    .into_option()
+                    .try_map(buck2_query_parser::parse_expr)?;
+
+                get_aquery_env(this.ctx, this.target_platform.dupe())
+                    .await?
+                    .deps(
+                        &universe,
+                        depth.into_option(),
+                        filter
+                            .as_ref()
+                            .map(|span| CapturedExpr { expr: span })
+                            .as_ref(),
+                    )
+                    .await
+            })
+            .map(StarlarkTargetSet::from)
+    }
+
+    /// All the actions registered by the analysis of the given configured targets.
+    ///
+    /// Sample usage:
+    /// ```text
+    /// def _impl_all_actions(ctx):
+    ///     for action in ctx.aquery().all_actions("//foo:bar"):
+    ///         ctx.output.print(action.category(), action.identifier())
+    /// ```
+    fn all_actions<'v>(
+        this: &StarlarkAQueryCtx<'v>,
+        targets: Value<'v>,
+        eval: &mut Evaluator<'v, '_>,
+    ) -> anyhow::Result<StarlarkTargetSet<ActionQueryNode>> {
+        this.ctx
+            .async_ctx
+            .via(|| async {
+                let targets = this.configured_targets(targets, eval).await?;
+                get_aquery_env(this.ctx, this.target_platform.dupe())
+                    .await?
+                    .all_actions(&targets)
+                    .await
+            })
+            .map(StarlarkTargetSet::from)
+    }
+
+    /// The actions producing the default and other outputs of the given configured targets.
+    ///
+    /// Sample usage:
+    /// ```text
+    /// def _impl_all_outputs(ctx):
+    ///     for action in ctx.aquery().all_outputs("//foo:bar"):
+    ///         ctx.output.print(action.outputs())
+    /// ```
+    fn all_outputs<'v>(
+        this: &StarlarkAQueryCtx<'v>,
+        targets: Value<'v>,
+        eval: &mut Evaluator<'v, '_>,
+    ) -> anyhow::Result<StarlarkTargetSet<ActionQueryNode>> {
+        this.ctx
+            .async_ctx
+            .via(|| async {
+                let targets = this.configured_targets(targets, eval).await?;
+                get_aquery_env(this.ctx, this.target_platform.dupe())
+                    .await?
+                    .all_outputs(&targets)
+                    .await
+            })
+            .map(StarlarkTargetSet::from)
+    }
+
+    /// The attrfilter query for filtering actions by their aquery attributes, e.g. `category`
+    /// or `identifier`. `targets` is an action node, a list of them or a target set of them.
+    ///
+    /// Sample usage:
+    /// ```text
+    /// def _impl_attrfilter(ctx):
+    ///     aquery = ctx.aquery()
+    ///     compiles = aquery.attrfilter("category", "cxx_compile", aquery.all_actions("//foo:bar"))
+    ///     ctx.output.print(compiles)
+    /// ```
+    fn attrfilter<'v>(
+        _this: &StarlarkAQueryCtx<'v>,
+        attr: &str,
+        value: &str,
+        targets: Value<'v>,
+    ) -> anyhow::Result<StarlarkTargetSet<ActionQueryNode>> {
+        unpack_action_nodes(targets)?
+            .attrfilter(attr, &|v| Ok(v == value))
+            .map(StarlarkTargetSet::from)
+    }
+}
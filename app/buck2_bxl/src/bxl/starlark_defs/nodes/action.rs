@@ -10,12 +10,18 @@
 use std::sync::Arc;
 
 use allocative::Allocative;
+use buck2_artifact::artifact::artifact_type::Artifact;
+use buck2_build_api::actions::query::ActionQueryNode;
 use buck2_build_api::actions::RegisteredAction;
+use buck2_build_api::artifact_groups::ResolvedArtifactGroup;
+use buck2_build_api::interpreter::rule_defs::artifact::StarlarkArtifact;
 use buck2_core::base_deferred_key::BaseDeferredKey;
 use buck2_interpreter::types::target_label::StarlarkConfiguredTargetLabel;
+use buck2_query::query::environment::QueryTarget;
 use derive_more::Display;
 use dupe::Dupe;
 use starlark::any::ProvidesStaticType;
+use starlark::collections::SmallMap;
 use starlark::environment::Methods;
 use starlark::environment::MethodsBuilder;
 use starlark::environment::MethodsStatic;
@@ -69,11 +75,139 @@ fn action_methods(builder: &mut MethodsBuilder) {
     ///     ctx.output.print(action.owner())
     /// ```
     fn owner<'v>(this: StarlarkAction) -> anyhow::Result<StarlarkConfiguredTargetLabel> {
-        match this.0.owner() {
-            BaseDeferredKey::TargetLabel(label) => {
-                Ok(StarlarkConfiguredTargetLabel::new(label.dupe()))
+        action_owner(&this.0)
+    }
+
+    /// Gets the category of the action, e.g. `cxx_compile`.
+    fn category<'v>(this: StarlarkAction) -> anyhow::Result<String> {
+        Ok(this.0.category().as_str().to_owned())
+    }
+
+    /// Gets the identifier of the action within its category, if any, e.g. the source file
+    /// for a `cxx_compile` action.
+    fn identifier<'v>(this: StarlarkAction) -> anyhow::Result<Option<String>> {
+        Ok(this.0.identifier().map(|v| v.to_owned()))
+    }
+}
+
+fn action_owner(action: &RegisteredAction) -> anyhow::Result<StarlarkConfiguredTargetLabel> {
+    match action.owner() {
+        BaseDeferredKey::TargetLabel(label) => Ok(StarlarkConfiguredTargetLabel::new(label.dupe())),
+        _ => Err(anyhow::anyhow!("BXL and anon targets not supported.")),
+    }
+}
+
+/// An action node returned by the functions of `ctx.aquery()`.
+#[derive(Debug, Display, ProvidesStaticType, Allocative, StarlarkDocs)]
+#[derive(NoSerialize)]
+#[display(fmt = "action_query_node({})", "self.0.action()")]
+#[starlark_docs(directory = "bxl")]
+pub struct StarlarkActionQueryNode(pub ActionQueryNode);
+
+starlark_simple_value!(StarlarkActionQueryNode);
+
+impl<'v> StarlarkValue<'v> for StarlarkActionQueryNode {
+    starlark_type!("action_query_node");
+
+    fn get_methods() -> Option<&'static Methods> {
+        static RES: MethodsStatic = MethodsStatic::new();
+        RES.methods(action_query_node_methods)
+    }
+}
+
+impl<'a> UnpackValue<'a> for StarlarkActionQueryNode {
+    fn expected() -> String {
+        "action query node".to_owned()
+    }
+
+    fn unpack_value(value: starlark::values::Value<'a>) -> Option<Self> {
+        value
+            .downcast_ref::<Self>()
+            .map(|value| Self(value.0.dupe()))
+    }
+}
+
+/// Methods for an action query node.
+#[starlark_module]
+fn action_query_node_methods(builder: &mut MethodsBuilder) {
+    /// Gets the underlying action.
+    fn action<'v>(this: StarlarkActionQueryNode) -> anyhow::Result<StarlarkAction> {
+        Ok(StarlarkAction(this.0.action()))
+    }
+
+    /// Gets the owning configured target label for the action.
+    fn owner<'v>(this: StarlarkActionQueryNode) -> anyhow::Result<StarlarkConfiguredTargetLabel> {
+        action_owner(&this.0.action())
+    }
+
+    /// Gets the kind of the action, e.g. `run` or `write`.
+    #[starlark(attribute)]
+    fn rule_type<'v>(this: StarlarkActionQueryNode) -> anyhow::Result<String> {
+        Ok(this.0.rule_type().into_owned())
+    }
+
+    /// Gets the category of the action, e.g. `cxx_compile`.
+    fn category<'v>(this: StarlarkActionQueryNode) -> anyhow::Result<String> {
+        Ok(this.0.action().category().as_str().to_owned())
+    }
+
+    /// Gets the identifier of the action within its category, if any.
+    fn identifier<'v>(this: StarlarkActionQueryNode) -> anyhow::Result<Option<String>> {
+        Ok(this.0.action().identifier().map(|v| v.to_owned()))
+    }
+
+    /// Gets the command line (the executable followed by its arguments) that the action runs,
+    /// with artifacts resolved to their paths, or `None` for actions that don't run a command.
+    ///
+    /// Sample usage:
+    /// ```text
+    /// def _impl_compile_commands(ctx):
+    ///     for action in ctx.aquery().all_actions("//foo:bar"):
+    ///         if action.category() == "cxx_compile":
+    ///             ctx.output.print(action.cmd())
+    /// ```
+    fn cmd<'v>(this: StarlarkActionQueryNode) -> anyhow::Result<Option<Vec<String>>> {
+        Ok(this
+            .0
+            .command_line()?
+            .map(|cmd| cmd.exe.into_iter().chain(cmd.args).collect()))
+    }
+
+    /// Gets the environment variables set for the command the action runs, or `None` for actions
+    /// that don't run a command.
+    fn env<'v>(this: StarlarkActionQueryNode) -> anyhow::Result<Option<SmallMap<String, String>>> {
+        Ok(this
+            .0
+            .command_line()?
+            .map(|cmd| cmd.env.into_iter().collect()))
+    }
+
+    /// Gets the artifacts the action reads directly. Inputs that only reach the action through a
+    /// transitive set are not included, use `ctx.aquery().deps()` to find the actions producing them.
+    fn inputs<'v>(this: StarlarkActionQueryNode) -> anyhow::Result<Vec<StarlarkArtifact>> {
+        let action = this.0.action();
+        let mut inputs = Vec::new();
+        for input in action.inputs()?.iter() {
+            if let ResolvedArtifactGroup::Artifact(artifact) = input.assert_resolved() {
+                inputs.push(StarlarkArtifact::new(artifact.dupe()));
             }
-            _ => Err(anyhow::anyhow!("BXL and anon targets not supported.")),
         }
+        Ok(inputs)
+    }
+
+    /// Gets the artifacts the action produces.
+    fn outputs<'v>(this: StarlarkActionQueryNode) -> anyhow::Result<Vec<StarlarkArtifact>> {
+        Ok(this
+            .0
+            .action()
+            .outputs()?
+            .iter()
+            .map(|output| StarlarkArtifact::new(Artifact::from(output.dupe())))
+            .collect())
+    }
+
+    /// Gets the attributes `buck2 aquery` shows for the action, as a dict of strings.
+    fn attrs<'v>(this: StarlarkActionQueryNode) -> anyhow::Result<SmallMap<String, String>> {
+        Ok(this.0.attrs().into_iter().collect())
     }
 }
@@ -240,8 +240,8 @@ impl WhatRanCommandImplementation for WhatFailedImpl {
 
             match data {
                 buck2_data::buck_event::Data::SpanEnd(span) => match &span.data {
-                    Some(buck2_data::span_end_event::Data::ActionExecution(action))
-                        if action.failed =>
+                    Some(buck2_data::span_end_event::Data::ActionExecution(action_end))
+                        if action_end.failed =>
                     {
                         if let Some(entry) = self.known_actions.remove(&event.span_id) {
                             let action = WhatRanRelevantAction::from_buck_data(
@@ -249,13 +249,14 @@ impl WhatRanCommandImplementation for WhatFailedImpl {
                             );
 
                             for repro in entry.reproducers.iter() {
-                                what_ran::emit_reproducer(
+                                what_ran::emit_failed_reproducer(
                                     action,
                                     CommandReproducer::from_buck_data(
                                         repro.data.as_ref().expect("Checked above"),
                                         options,
                                     )
                                     .expect("Checked above"),
+                                    action_end.error.as_ref(),
                                     output,
                                 )?;
                             }
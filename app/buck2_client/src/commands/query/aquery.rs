@@ -36,6 +36,10 @@ use crate::commands::query::common::CommonQueryOptions;
 ///
 /// `buck2 aquery 'kind(run, deps("//java/com/example/app:amazing+more"))' --output-attribute=cmd`
 ///
+/// Print the actions that read a source file, or the action that produces a buck-out path
+///
+/// `buck2 aquery 'owner(java/com/example/app/Main.java)'`
+///
 /// Dynamic outputs (`ctx.actions.dynamic_output`):
 ///
 /// Currently, aquery interacts poorly with dynamic outputs. It may return incorrect results or otherwise
@@ -25,6 +25,9 @@ enum QueryOutputFormatArg {
     Dot,
     Json,
     DotCompact,
+    Ndjson,
+    Csv,
+    Graphml,
 }
 
 /// Args common to all the query commands
@@ -53,9 +56,12 @@ pub(crate) struct CommonQueryOptions {
         long_help = "Output format (default: list). \n
            dot -  dot graph format. \n
            dot_compact - compact alternative to dot format. \n
-           json - JSON format.
+           json - JSON format. \n
+           ndjson - newline-delimited JSON, one target (with its attributes) per line. \n
+           csv - CSV, one target per row with a column for each output attribute. \n
+           graphml - GraphML graph format.
          ",
-        value_name = "dot|dot_compact|json",
+        value_name = "dot|dot_compact|json|ndjson|csv|graphml",
         arg_enum
     )]
     output_format: Option<QueryOutputFormatArg>,
@@ -87,6 +93,9 @@ impl CommonQueryOptions {
             Some(QueryOutputFormatArg::Json) => QueryOutputFormat::Json,
             Some(QueryOutputFormatArg::Dot) => QueryOutputFormat::Dot,
             Some(QueryOutputFormatArg::DotCompact) => QueryOutputFormat::DotCompact,
+            Some(QueryOutputFormatArg::Ndjson) => QueryOutputFormat::Ndjson,
+            Some(QueryOutputFormatArg::Csv) => QueryOutputFormat::Csv,
+            Some(QueryOutputFormatArg::Graphml) => QueryOutputFormat::Graphml,
             None => {
                 if self.json {
                     QueryOutputFormat::Json
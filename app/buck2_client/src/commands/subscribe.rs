@@ -54,6 +54,19 @@ pub struct SubscribeCommand {
     #[clap(long)]
     active_commands: bool,
 
+    /// Whether to request notifications of source file changes picked up by the file watcher.
+    #[clap(long)]
+    file_changes: bool,
+
+    /// Whether to request notifications of configured target nodes being invalidated, either
+    /// because they or their inputs changed.
+    #[clap(long)]
+    invalidated_targets: bool,
+
+    /// Whether to request notifications of targets finishing building.
+    #[clap(long)]
+    build_completions: bool,
+
     /// Whether to get output as JSON. The JSON format is deemed unstable so this should only be
     /// used for debugging.
     #[clap(long)]
@@ -101,15 +114,28 @@ impl StreamingCommand for SubscribeCommand {
             ok: true,
         };
 
-        let stream = if self.active_commands {
-            futures::stream::once(futures::future::ready(SubscriptionRequest {
-                request: Some(buck2_subscription_proto::SubscribeToActiveCommands {}.into()),
-            }))
-            .chain(stream)
-            .left_stream()
-        } else {
-            stream.right_stream()
-        };
+        let mut initial_requests: Vec<buck2_subscription_proto::subscription_request::Request> =
+            Vec::new();
+        if self.active_commands {
+            initial_requests.push(buck2_subscription_proto::SubscribeToActiveCommands {}.into());
+        }
+        if self.file_changes {
+            initial_requests.push(buck2_subscription_proto::SubscribeToFileChanges {}.into());
+        }
+        if self.invalidated_targets {
+            initial_requests
+                .push(buck2_subscription_proto::SubscribeToInvalidatedTargets {}.into());
+        }
+        if self.build_completions {
+            initial_requests.push(buck2_subscription_proto::SubscribeToBuildCompletions {}.into());
+        }
+
+        let stream = futures::stream::iter(initial_requests.into_iter().map(|request| {
+            SubscriptionRequest {
+                request: Some(request),
+            }
+        }))
+        .chain(stream);
 
         let stream = stream.map(|request| buck2_cli_proto::SubscriptionRequestWrapper {
             request: Some(request),
@@ -26,8 +26,11 @@ use buck2_client_ctx::output_destination_arg::OutputDestinationArg;
 use buck2_client_ctx::path_arg::PathArg;
 use buck2_client_ctx::stdio::eprint_line;
 use buck2_client_ctx::streaming::StreamingCommand;
+use buck2_client_ctx::subscribers::subscriber::EventSubscriber;
 use buck2_client_ctx::subscribers::superconsole::test::span_from_build_failure_count;
 use buck2_client_ctx::subscribers::superconsole::test::TestCounterColumn;
+use buck2_client_ctx::subscribers::test_report::TestReportFormat;
+use buck2_client_ctx::subscribers::test_report::TestReportWriter;
 use buck2_core::fs::fs_util;
 use buck2_core::fs::working_dir::WorkingDir;
 use gazebo::prelude::*;
@@ -108,10 +111,21 @@ If include patterns are present, regardless of whether exclude patterns are pres
     #[clap(long = "deep")]
     deep: bool,
 
-    // ignored. only for e2e tests. compatibility with v1.
-    #[clap(long = "xml")]
-    #[allow(unused)] // for v1 compat
-    xml: Option<String>,
+    /// Same as `--report-junit-xml`. Kept for compatibility with Buck1.
+    #[clap(long = "xml", value_name = "PATH")]
+    xml: Option<PathArg>,
+
+    /// Write a JUnit XML report of the test results to the provided path.
+    #[clap(long, value_name = "PATH")]
+    report_junit_xml: Option<PathArg>,
+
+    /// Write a TAP (Test Anything Protocol) report of the test results to the provided path.
+    #[clap(long, value_name = "PATH")]
+    report_tap: Option<PathArg>,
+
+    /// Write a JSON report of the test results to the provided path.
+    #[clap(long, value_name = "PATH")]
+    report_json: Option<PathArg>,
 
     /// Will allow tests that are compatible with RE (setup to run from the repo root and
     /// use relative paths) to run from RE.
@@ -298,4 +312,22 @@ impl StreamingCommand for TestCommand {
     fn common_opts(&self) -> &CommonBuildConfigurationOptions {
         &self.common_opts.config_opts
     }
+
+    fn extra_subscribers(&self, ctx: &ClientCommandContext<'_>) -> Vec<Box<dyn EventSubscriber>> {
+        let reports: Vec<_> = [
+            (TestReportFormat::JunitXml, &self.report_junit_xml),
+            (TestReportFormat::JunitXml, &self.xml),
+            (TestReportFormat::Tap, &self.report_tap),
+            (TestReportFormat::Json, &self.report_json),
+        ]
+        .into_iter()
+        .filter_map(|(format, path)| Some((format, path.as_ref()?.resolve(&ctx.working_dir))))
+        .collect();
+
+        if reports.is_empty() {
+            vec![]
+        } else {
+            vec![Box::new(TestReportWriter::new(reports))]
+        }
+    }
 }
@@ -20,6 +20,7 @@ pub(crate) mod stdout_stderr_forwarder;
 pub mod subscriber;
 pub mod subscriber_unpack;
 pub mod superconsole;
+pub mod test_report;
 
 pub fn should_upload_log() -> anyhow::Result<bool> {
     if buck2_core::is_open_source() {
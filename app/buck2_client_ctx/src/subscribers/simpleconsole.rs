@@ -125,6 +125,12 @@ fn eprint_command_details(
                 remote_command.action_digest
             )?;
         }
+        Some(Command::LocalActionCacheCommand(local_action_cache_command)) => {
+            echo!(
+                "Local action cache hit: {}",
+                local_action_cache_command.action_digest
+            )?;
+        }
         Some(Command::OmittedLocalCommand(..)) | None => {
             // Nothing to show in this case.
         }
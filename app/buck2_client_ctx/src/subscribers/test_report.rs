@@ -0,0 +1,342 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+//! Machine-readable reports for `buck2 test`, built from the `TestResult` events the daemon
+//! sends us.
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::Context;
+use async_trait::async_trait;
+use buck2_core::fs::fs_util;
+use buck2_core::fs::paths::abs_path::AbsPathBuf;
+use buck2_event_observer::display::display_configured_target_label;
+use buck2_event_observer::display::TargetDisplayOptions;
+use buck2_events::BuckEvent;
+use buck2_test_api::data::TestStatus;
+use buck2_util::truncate::truncate;
+use buck2_util::xml::xml_escape;
+use dupe::Dupe;
+use serde::Serialize;
+
+use crate::subscribers::subscriber::EventSubscriber;
+
+/// Test output can be arbitrarily large, so we only include this much of it in reports.
+const MAX_OUTPUT_EXCERPT_BYTES: usize = 64 * 1024;
+
+#[derive(Debug, Clone, Copy, Dupe, Eq, PartialEq)]
+pub enum TestReportFormat {
+    JunitXml,
+    Tap,
+    Json,
+}
+
+/// A test result, as it appears in reports.
+#[derive(Debug, Serialize)]
+struct ReportedTest {
+    target: String,
+    name: String,
+    status: String,
+    #[serde(skip)]
+    outcome: Outcome,
+    duration_ms: Option<u128>,
+    message: Option<String>,
+    output: String,
+}
+
+#[derive(Debug, Clone, Copy, Dupe, Eq, PartialEq)]
+enum Outcome {
+    Pass,
+    Fail,
+    Error,
+    Skip,
+}
+
+impl ReportedTest {
+    /// Returns `None` for results that don't describe the outcome of a test: successful listings,
+    /// and attempts that were retried. Statuses we don't know about (e.g. from a newer test
+    /// runner) are reported as `UNKNOWN`, rather than failing the whole command.
+    fn from_event(result: &buck2_data::TestResult) -> anyhow::Result<Option<Self>> {
+        let status = TestStatus::try_from(result.status).unwrap_or(TestStatus::UNKNOWN);
+        let outcome = match status {
+            TestStatus::PASS => Outcome::Pass,
+            TestStatus::FAIL | TestStatus::TIMEOUT => Outcome::Fail,
+            TestStatus::FATAL | TestStatus::UNKNOWN | TestStatus::LISTING_FAILED => Outcome::Error,
+            TestStatus::SKIP | TestStatus::OMITTED => Outcome::Skip,
+            TestStatus::LISTING_SUCCESS | TestStatus::RERUN => return Ok(None),
+        };
+
+        let target = match &result.target_label {
+            Some(label) => display_configured_target_label(label, TargetDisplayOptions::for_log())?,
+            None => String::new(),
+        };
+
+        let duration = result
+            .duration
+            .clone()
+            .and_then(|d| Duration::try_from(d).ok());
+
+        Ok(Some(Self {
+            target,
+            name: result.name.clone(),
+            status: format!("{:?}", status),
+            outcome,
+            duration_ms: duration.map(|d| d.as_millis()),
+            message: result.msg.as_ref().map(|m| m.msg.clone()),
+            output: truncate(&result.details, MAX_OUTPUT_EXCERPT_BYTES),
+        }))
+    }
+
+    fn duration_secs(&self) -> String {
+        format!("{:.3}", self.duration_ms.unwrap_or(0) as f64 / 1000.0)
+    }
+}
+
+fn render_junit_xml(tests: &[ReportedTest]) -> String {
+    let mut suites: BTreeMap<&str, Vec<&ReportedTest>> = BTreeMap::new();
+    for test in tests {
+        suites.entry(&test.target).or_default().push(test);
+    }
+
+    let count =
+        |tests: &[&ReportedTest], outcome| tests.iter().filter(|t| t.outcome == outcome).count();
+
+    let mut xml = String::new();
+    xml.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    writeln!(
+        xml,
+        "<testsuites tests=\"{}\" failures=\"{}\" errors=\"{}\" skipped=\"{}\">",
+        tests.len(),
+        tests.iter().filter(|t| t.outcome == Outcome::Fail).count(),
+        tests.iter().filter(|t| t.outcome == Outcome::Error).count(),
+        tests.iter().filter(|t| t.outcome == Outcome::Skip).count(),
+    )
+    .unwrap();
+
+    for (target, tests) in &suites {
+        let time = tests.iter().filter_map(|t| t.duration_ms).sum::<u128>() as f64 / 1000.0;
+        writeln!(
+            xml,
+            "  <testsuite name=\"{}\" tests=\"{}\" failures=\"{}\" errors=\"{}\" skipped=\"{}\" time=\"{:.3}\">",
+            xml_escape(target),
+            tests.len(),
+            count(tests, Outcome::Fail),
+            count(tests, Outcome::Error),
+            count(tests, Outcome::Skip),
+            time,
+        )
+        .unwrap();
+
+        for test in tests {
+            writeln!(
+                xml,
+                "    <testcase name=\"{}\" classname=\"{}\" time=\"{}\">",
+                xml_escape(&test.name),
+                xml_escape(target),
+                test.duration_secs(),
+            )
+            .unwrap();
+
+            let message = xml_escape(test.message.as_deref().unwrap_or(&test.status));
+            match test.outcome {
+                Outcome::Pass => {}
+                Outcome::Fail => writeln!(xml, "      <failure message=\"{}\"/>", message).unwrap(),
+                Outcome::Error => writeln!(xml, "      <error message=\"{}\"/>", message).unwrap(),
+                Outcome::Skip => writeln!(xml, "      <skipped message=\"{}\"/>", message).unwrap(),
+            }
+
+            if !test.output.is_empty() {
+                writeln!(
+                    xml,
+                    "      <system-out>{}</system-out>",
+                    xml_escape(&test.output)
+                )
+                .unwrap();
+            }
+
+            xml.push_str("    </testcase>\n");
+        }
+
+        xml.push_str("  </testsuite>\n");
+    }
+
+    xml.push_str("</testsuites>\n");
+    xml
+}
+
+fn render_tap(tests: &[ReportedTest]) -> String {
+    let mut tap = String::new();
+    tap.push_str("TAP version 13\n");
+    writeln!(tap, "1..{}", tests.len()).unwrap();
+
+    for (i, test) in tests.iter().enumerate() {
+        let ok = match test.outcome {
+            Outcome::Pass | Outcome::Skip => "ok",
+            Outcome::Fail | Outcome::Error => "not ok",
+        };
+        // `#` starts a directive in TAP, so it can't appear in the description.
+        let description = format!("{} {}", test.target, test.name).replace('#', "\\#");
+        write!(tap, "{} {} - {}", ok, i + 1, description).unwrap();
+        if test.outcome == Outcome::Skip {
+            tap.push_str(" # SKIP");
+        }
+        tap.push('\n');
+
+        // YAML diagnostics block.
+        tap.push_str("  ---\n");
+        writeln!(tap, "  status: {}", test.status).unwrap();
+        if let Some(duration_ms) = test.duration_ms {
+            writeln!(tap, "  duration_ms: {}", duration_ms).unwrap();
+        }
+        if let Some(message) = &test.message {
+            writeln!(
+                tap,
+                "  message: {}",
+                serde_json::to_string(message).unwrap()
+            )
+            .unwrap();
+        }
+        if !test.output.is_empty() {
+            tap.push_str("  output: |\n");
+            for line in test.output.lines() {
+                writeln!(tap, "    {}", line).unwrap();
+            }
+        }
+        tap.push_str("  ...\n");
+    }
+
+    tap
+}
+
+fn render_json(tests: &[ReportedTest]) -> anyhow::Result<String> {
+    Ok(serde_json::to_string_pretty(tests)?)
+}
+
+/// Collects test results and writes them out in the requested formats when the command finishes.
+pub struct TestReportWriter {
+    reports: Vec<(TestReportFormat, AbsPathBuf)>,
+    tests: Vec<ReportedTest>,
+}
+
+impl TestReportWriter {
+    pub fn new(reports: Vec<(TestReportFormat, AbsPathBuf)>) -> Self {
+        Self {
+            reports,
+            tests: Vec::new(),
+        }
+    }
+}
+
+#[async_trait]
+impl EventSubscriber for TestReportWriter {
+    async fn handle_events(&mut self, events: &[Arc<BuckEvent>]) -> anyhow::Result<()> {
+        for event in events {
+            if let buck2_data::buck_event::Data::Instant(instant) = event.data() {
+                if let Some(buck2_data::instant_event::Data::TestResult(result)) = &instant.data {
+                    if let Some(test) = ReportedTest::from_event(result)? {
+                        self.tests.push(test);
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    async fn exit(&mut self) -> anyhow::Result<()> {
+        for (format, path) in &self.reports {
+            let report = match format {
+                TestReportFormat::JunitXml => render_junit_xml(&self.tests),
+                TestReportFormat::Tap => render_tap(&self.tests),
+                TestReportFormat::Json => render_json(&self.tests)?,
+            };
+            fs_util::write(path, report)
+                .with_context(|| format!("Error writing test report to `{}`", path.display()))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test(target: &str, name: &str, status: &str, outcome: Outcome) -> ReportedTest {
+        ReportedTest {
+            target: target.to_owned(),
+            name: name.to_owned(),
+            status: status.to_owned(),
+            outcome,
+            duration_ms: Some(1500),
+            message: None,
+            output: String::new(),
+        }
+    }
+
+    fn tests() -> Vec<ReportedTest> {
+        let mut failing = test("root//foo:bar", "test_b", "FAIL", Outcome::Fail);
+        failing.message = Some("expected <1> & got \"2\"".to_owned());
+        failing.output = "line 1\nline 2".to_owned();
+        vec![
+            test("root//foo:bar", "test_a", "PASS", Outcome::Pass),
+            failing,
+            test("root//baz:qux", "test # c", "SKIP", Outcome::Skip),
+        ]
+    }
+
+    #[test]
+    fn test_junit_xml() {
+        let xml = render_junit_xml(&tests());
+        assert!(xml.contains("<testsuites tests=\"3\" failures=\"1\" errors=\"0\" skipped=\"1\">"));
+        assert!(xml.contains(
+            "<testsuite name=\"root//foo:bar\" tests=\"2\" failures=\"1\" errors=\"0\" skipped=\"0\" time=\"3.000\">"
+        ));
+        assert!(
+            xml.contains("<testcase name=\"test_b\" classname=\"root//foo:bar\" time=\"1.500\">")
+        );
+        assert!(xml.contains("<failure message=\"expected &lt;1&gt; &amp; got &quot;2&quot;\"/>"));
+        assert!(xml.contains("<system-out>line 1\nline 2</system-out>"));
+        assert!(xml.contains("<skipped message=\"SKIP\"/>"));
+    }
+
+    #[test]
+    fn test_tap() {
+        let tap = render_tap(&tests());
+        assert!(tap.starts_with("TAP version 13\n1..3\n"));
+        assert!(tap.contains("ok 1 - root//foo:bar test_a\n"));
+        assert!(tap.contains("not ok 2 - root//foo:bar test_b\n"));
+        assert!(tap.contains("  output: |\n    line 1\n    line 2\n"));
+        assert!(tap.contains("ok 3 - root//baz:qux test \\# c # SKIP\n"));
+    }
+
+    #[test]
+    fn test_unknown_status() -> anyhow::Result<()> {
+        let result = buck2_data::TestResult {
+            name: "test_a".to_owned(),
+            status: 1000,
+            ..Default::default()
+        };
+        let test = ReportedTest::from_event(&result)?.unwrap();
+        assert_eq!(test.status, "UNKNOWN");
+        assert_eq!(test.outcome, Outcome::Error);
+        Ok(())
+    }
+
+    #[test]
+    fn test_json() -> anyhow::Result<()> {
+        let json: serde_json::Value = serde_json::from_str(&render_json(&tests())?)?;
+        assert_eq!(json[1]["name"], "test_b");
+        assert_eq!(json[1]["status"], "FAIL");
+        assert_eq!(json[1]["duration_ms"], 1500);
+        assert_eq!(json[2]["target"], "root//baz:qux");
+        Ok(())
+    }
+}
@@ -13,6 +13,7 @@ use std::hash::Hash;
 use std::hash::Hasher;
 use std::str::FromStr;
 use std::sync::Arc;
+use std::time::Duration;
 
 use allocative::Allocative;
 use buck2_core::collections::sorted_map::SortedMap;
@@ -25,6 +26,16 @@ use once_cell::sync::Lazy;
 #[derive(Debug, Default, Eq, Hash, PartialEq, Clone, Dupe, Allocative)]
 pub struct LocalExecutorOptions {
     pub use_persistent_workers: bool,
+    /// When set, local commands run in a sandbox that only exposes their declared inputs and
+    /// outputs. Only supported on Linux, and not with persistent workers.
+    pub sandbox: Option<LocalSandboxOptions>,
+}
+
+#[derive(Debug, Default, Eq, Hash, PartialEq, Copy, Clone, Dupe, Allocative)]
+pub struct LocalSandboxOptions {
+    /// Whether sandboxed commands may access the network. When this is false, they run in a
+    /// network namespace that only has a (down) loopback interface.
+    pub allow_network: bool,
 }
 
 #[derive(Debug, Eq, PartialEq, Copy, Clone, Dupe, Display, Allocative)]
@@ -103,8 +114,9 @@ impl Display for Executor {
             Self::Local(options) => {
                 write!(
                     f,
-                    "Local + use persistent workers {}",
-                    options.use_persistent_workers
+                    "Local + use persistent workers {} + sandbox {}",
+                    options.use_persistent_workers,
+                    options.sandbox.is_some()
                 )
             }
             Self::RemoteEnabled {
@@ -203,6 +215,8 @@ pub struct CommandGenerationOptions {
 pub struct CommandExecutorConfig {
     pub executor: Executor,
     pub options: CommandGenerationOptions,
+    /// Timeout for build actions that don't set their own.
+    pub action_timeout: Option<Duration>,
 }
 
 #[derive(Debug, Eq, PartialEq, Clone, Copy, Dupe, Hash, Allocative)]
@@ -229,6 +243,7 @@ impl CommandExecutorConfig {
                 path_separator: PathSeparatorKind::system_default(),
                 output_paths_behavior: Default::default(),
             },
+            action_timeout: None,
         })
     }
 }
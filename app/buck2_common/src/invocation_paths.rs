@@ -45,6 +45,7 @@ pub(crate) fn home_buck_dir() -> anyhow::Result<&'static AbsNormPath> {
 }
 
 const BUCKD_DIR_PREFIX: &FileName = FileName::unchecked_new("buckd");
+const LOCAL_ACTION_CACHE_DIR: &FileName = FileName::unchecked_new("action_cache");
 
 #[derive(Clone, Allocative)]
 pub struct InvocationPaths {
@@ -131,6 +132,12 @@ impl InvocationPaths {
         Ok(home_buck_dir()?.join(BUCKD_DIR_PREFIX))
     }
 
+    /// Default location of the local action cache. This is shared by all the daemons of a given
+    /// user, so that cached outputs can be reused across projects and worktrees.
+    pub fn common_local_action_cache_dir() -> anyhow::Result<AbsNormPathBuf> {
+        Ok(home_buck_dir()?.join(LOCAL_ACTION_CACHE_DIR))
+    }
+
     pub fn cell_root(&self) -> &AbsNormPath {
         &self.roots.cell_root
     }
@@ -204,8 +211,22 @@ impl InvocationPaths {
         FileName::unchecked_new("materializer_state")
     }
 
+    /// Subdirectory of `cache_dir` where file watchers that can't ask the OS what changed while
+    /// the daemon was down store what they saw last.
+    pub fn file_watcher_state_path(&self) -> AbsNormPathBuf {
+        self.cache_dir_path()
+            .join(self.file_watcher_state_dir_name())
+    }
+
+    pub fn file_watcher_state_dir_name(&self) -> &FileName {
+        FileName::unchecked_new("file_watcher_state")
+    }
+
     pub fn valid_cache_dirs(&self) -> Vec<&FileName> {
-        vec![self.materializer_state_dir_name()]
+        vec![
+            self.materializer_state_dir_name(),
+            self.file_watcher_state_dir_name(),
+        ]
     }
 }
 
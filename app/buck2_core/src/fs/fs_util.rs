@@ -14,6 +14,8 @@ use std::fs;
 use std::fs::File;
 use std::io;
 use std::io::Read;
+use std::io::Seek;
+use std::io::SeekFrom;
 use std::io::Write;
 use std::ops::Deref;
 use std::path::Path;
@@ -463,6 +465,12 @@ impl Write for FileWriteGuard {
     }
 }
 
+impl Seek for FileWriteGuard {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        self.file.seek(pos)
+    }
+}
+
 pub fn create_file<P: AsRef<AbsPath>>(path: P) -> anyhow::Result<FileWriteGuard> {
     let guard = IoCounterKey::Write.guard();
     let file = File::create(path.as_ref().as_maybe_relativized())
@@ -484,6 +492,12 @@ impl Read for FileReadGuard {
     }
 }
 
+impl Seek for FileReadGuard {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        self.file.seek(pos)
+    }
+}
+
 pub fn open_file<P: AsRef<AbsPath>>(path: P) -> anyhow::Result<FileReadGuard> {
     let guard = IoCounterKey::Read.guard();
     let file = File::open(path.as_ref().as_maybe_relativized())
@@ -321,6 +321,7 @@ fn display_file_watcher(provider: i32) -> &'static str {
     match buck2_data::FileWatcherProvider::from_i32(provider) {
         Some(buck2_data::FileWatcherProvider::Watchman) => "Watchman",
         Some(buck2_data::FileWatcherProvider::RustNotify) => "notify",
+        Some(buck2_data::FileWatcherProvider::Poll) => "poll",
         None => "unknown mechanism",
     }
 }
@@ -546,6 +547,10 @@ pub fn display_action_error<'a>(
                 None => "Unexpected command status".to_owned(),
             }
         }
+        Error::TimedOut(timed_out) => match action.commands.last() {
+            Some(c) => failure_reason_for_command_execution(c)?,
+            None => timed_out.message.clone(),
+        },
     };
 
     Ok(ActionErrorDisplay {
@@ -578,7 +583,9 @@ fn failure_reason_for_command_execution(
 
     let locality = match command.command {
         Some(Command::RemoteCommand(..)) => "Remote ",
-        Some(Command::LocalCommand(..)) | Some(Command::OmittedLocalCommand(..)) => "Local ",
+        Some(Command::LocalCommand(..))
+        | Some(Command::OmittedLocalCommand(..))
+        | Some(Command::LocalActionCacheCommand(..)) => "Local ",
         None => "",
     };
 
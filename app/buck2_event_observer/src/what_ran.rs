@@ -130,6 +130,30 @@ pub fn emit_reproducer(
     action: Option<WhatRanRelevantAction<'_>>,
     repro: CommandReproducer<'_>,
     output: &mut impl WhatRanOutputWriter,
+) -> anyhow::Result<()> {
+    emit_reproducer_with_reason(action, repro, None, output)
+}
+
+/// Like [`emit_reproducer`], for a command whose action failed with `error`. Actions that timed
+/// out are reported with a distinct `build.timed_out` reason.
+pub fn emit_failed_reproducer(
+    action: Option<WhatRanRelevantAction<'_>>,
+    repro: CommandReproducer<'_>,
+    error: Option<&buck2_data::action_execution_end::Error>,
+    output: &mut impl WhatRanOutputWriter,
+) -> anyhow::Result<()> {
+    let reason = match error {
+        Some(buck2_data::action_execution_end::Error::TimedOut(..)) => Some("build.timed_out"),
+        _ => None,
+    };
+    emit_reproducer_with_reason(action, repro, reason, output)
+}
+
+fn emit_reproducer_with_reason(
+    action: Option<WhatRanRelevantAction<'_>>,
+    repro: CommandReproducer<'_>,
+    reason_override: Option<&'static str>,
+    output: &mut impl WhatRanOutputWriter,
 ) -> anyhow::Result<()> {
     let (reason, identity, extra) = match action {
         Some(WhatRanRelevantAction::ActionExecution(action)) => (
@@ -158,7 +182,7 @@ pub fn emit_reproducer(
     };
 
     output.emit_command(WhatRanOutputCommand {
-        reason,
+        reason: reason_override.unwrap_or(reason),
         identity: &identity,
         repro,
         extra,
@@ -378,4 +402,48 @@ mod tests {
         let result = executor_with_platform(&execute);
         assert_eq!(result, "re".to_owned());
     }
+
+    struct Reasons(Vec<String>);
+
+    impl WhatRanOutputWriter for Reasons {
+        fn emit_command(&mut self, command: WhatRanOutputCommand<'_>) -> anyhow::Result<()> {
+            self.0.push(command.reason().to_owned());
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_failed_reproducer_reason() -> anyhow::Result<()> {
+        let start = buck2_data::ActionExecutionStart {
+            key: Some(buck2_data::ActionKey {
+                owner: Some(buck2_data::action_key::Owner::BxlKey(
+                    buck2_data::BxlFunctionKey {
+                        label: Some(buck2_data::BxlFunctionLabel {
+                            bxl_path: "cell//foo.bxl".to_owned(),
+                            name: "main".to_owned(),
+                        }),
+                    },
+                )),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let action = Some(WhatRanRelevantAction::ActionExecution(&start));
+        let execute = buck2_data::LocalExecute::default();
+        let repro = CommandReproducer::LocalExecute(&execute);
+
+        let timed_out = buck2_data::action_execution_end::Error::TimedOut(
+            buck2_data::CommandTimedOut::default(),
+        );
+        let failed = buck2_data::action_execution_end::Error::CommandExecutionError(
+            buck2_data::CommandExecutionError::default(),
+        );
+
+        let mut output = Reasons(Vec::new());
+        emit_failed_reproducer(action, repro, Some(&timed_out), &mut output)?;
+        emit_failed_reproducer(action, repro, Some(&failed), &mut output)?;
+        emit_failed_reproducer(action, repro, None, &mut output)?;
+        assert_eq!(output.0, vec!["build.timed_out", "build", "build"]);
+        Ok(())
+    }
 }
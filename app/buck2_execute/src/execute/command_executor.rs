@@ -157,7 +157,7 @@ impl CommandExecutor {
                 request.env(),
                 input_digest,
                 action_metadata_blobs,
-                if self.0.enforce_re_timeouts {
+                if self.0.enforce_re_timeouts || request.explicit_timeout() {
                     request.timeout()
                 } else {
                     None
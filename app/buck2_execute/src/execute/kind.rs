@@ -29,6 +29,9 @@ pub enum CommandExecutionKind {
     /// This action was served by the action cache and not executed.
     #[display(fmt = "action_cache")]
     ActionCache { digest: ActionDigest },
+    /// This action was served by the local on-disk action cache and not executed.
+    #[display(fmt = "local_action_cache")]
+    LocalActionCache { digest: ActionDigest },
 }
 
 impl CommandExecutionKind {
@@ -37,6 +40,7 @@ impl CommandExecutionKind {
             Self::Local { .. } => buck2_data::ActionExecutionKind::Local,
             Self::Remote { .. } => buck2_data::ActionExecutionKind::Remote,
             Self::ActionCache { .. } => buck2_data::ActionExecutionKind::ActionCache,
+            Self::LocalActionCache { .. } => buck2_data::ActionExecutionKind::LocalActionCache,
         }
     }
 }
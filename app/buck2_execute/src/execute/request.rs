@@ -247,6 +247,9 @@ pub struct CommandExecutionRequest {
     paths: CommandExecutionPaths,
     env: SortedVectorMap<String, String>,
     timeout: Option<Duration>,
+    /// Whether the action asked for `timeout` itself, in which case it is enforced on RE even if
+    /// `buck2.enforce_re_timeouts` isn't set.
+    explicit_timeout: bool,
     executor_preference: ExecutorPreference,
     // Run with a custom $TMPDIR, or just the standard system one
     custom_tmpdir: Option<BuckOutScratchPath>,
@@ -286,6 +289,7 @@ impl CommandExecutionRequest {
             paths,
             env,
             timeout: None,
+            explicit_timeout: false,
             executor_preference: ExecutorPreference::Default,
             custom_tmpdir: None,
             host_sharing_requirements: HostSharingRequirements::default(),
@@ -310,6 +314,13 @@ impl CommandExecutionRequest {
         self
     }
 
+    /// Like `with_timeout`, for a timeout the action asked for, which is always enforced.
+    pub fn with_explicit_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self.explicit_timeout = true;
+        self
+    }
+
     pub fn with_executor_preference(mut self, executor_preference: ExecutorPreference) -> Self {
         self.executor_preference = executor_preference;
         self
@@ -396,6 +407,10 @@ impl CommandExecutionRequest {
         self.timeout
     }
 
+    pub fn explicit_timeout(&self) -> bool {
+        self.explicit_timeout
+    }
+
     pub fn executor_preference(&self) -> ExecutorPreference {
         self.executor_preference
     }
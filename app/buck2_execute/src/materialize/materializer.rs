@@ -19,6 +19,7 @@ use buck2_core::base_deferred_key::BaseDeferredKey;
 use buck2_core::directory::DirectoryEntry;
 use buck2_core::fs::project_rel_path::ProjectRelativePathBuf;
 use buck2_events::dispatch::EventDispatcher;
+use buck2_wrapper_common::invocation_id::TraceId;
 use chrono::DateTime;
 use chrono::Duration;
 use chrono::Utc;
@@ -263,6 +264,10 @@ pub trait Materializer: Allocative + Send + Sync + 'static {
 
     /// Currently no-op for all materializers except deferred materializer
     fn log_materializer_state(&self, _events: &EventDispatcher) {}
+
+    /// Called when the command identified by `trace_id` finishes, so that the artifacts it
+    /// materialized are no longer kept from being garbage collected.
+    fn command_finished(&self, _trace_id: &TraceId) {}
 }
 
 #[derive(Copy, Clone, Dupe, Debug)]
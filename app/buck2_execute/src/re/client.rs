@@ -104,6 +104,18 @@ pub struct RemoteExecutionClientStats {
     pub materializes: RemoteExecutionClientOpStats,
     pub write_action_results: RemoteExecutionClientOpStats,
     pub get_digest_expirations: RemoteExecutionClientOpStats,
+    pub retries: RemoteExecutionClientRetryStats,
+}
+
+/// How many times requests were retried after a transient failure. Only the OSS client reports
+/// those: downloads done to materialize outputs are counted in `downloads`.
+#[derive(Default)]
+pub struct RemoteExecutionClientRetryStats {
+    pub uploads: u32,
+    pub downloads: u32,
+    pub action_cache: u32,
+    pub executes: u32,
+    pub get_digest_expirations: u32,
 }
 
 #[derive(Clone, Dupe, Allocative)]
@@ -450,6 +462,22 @@ impl RemoteExecutionClient {
             .checked_sub(self.data.initial_network_stats.downloaded)
             .and_then(|d| u64::try_from(d).ok())
             .context("Overflow calculating downloaded bytes")?;
+
+        #[cfg(fbcode_build)]
+        let retries = RemoteExecutionClientRetryStats::default();
+        #[cfg(not(fbcode_build))]
+        let retries = {
+            let retries = self.data.client.client().get_retry_stats();
+            let count = |n: u64| u32::try_from(n).unwrap_or(u32::MAX);
+            RemoteExecutionClientRetryStats {
+                uploads: count(retries.uploads),
+                downloads: count(retries.downloads),
+                action_cache: count(retries.action_cache),
+                executes: count(retries.executes),
+                get_digest_expirations: count(retries.find_missing_blobs),
+            }
+        };
+
         Ok(RemoteExecutionClientStats {
             uploaded,
             downloaded,
@@ -464,6 +492,7 @@ impl RemoteExecutionClient {
             get_digest_expirations: RemoteExecutionClientOpStats::from(
                 &self.data.get_digest_expirations,
             ),
+            retries,
         })
     }
 }
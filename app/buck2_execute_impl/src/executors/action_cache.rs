@@ -26,6 +26,8 @@ use dupe::Dupe;
 use more_futures::cancellation::CancellationContext;
 use tracing::info;
 
+use crate::executors::caching::try_local_action_cache_fetch;
+use crate::local_action_cache::LocalActionCache;
 use crate::re::download::download_action_results;
 use crate::re::download::DownloadResult;
 
@@ -35,6 +37,10 @@ pub struct ActionCacheChecker {
     pub re_client: ManagedRemoteExecutionClient,
     pub re_use_case: RemoteExecutorUseCase,
     pub upload_all_actions: bool,
+    /// Whether to use the RE action cache. When this is disabled, we only use the local action
+    /// cache (if any).
+    pub remote_cache_enabled: bool,
+    pub local_action_cache: Option<Arc<LocalActionCache>>,
 }
 
 #[async_trait]
@@ -45,6 +51,25 @@ impl PreparedCommandOptionalExecutor for ActionCacheChecker {
         manager: CommandExecutionManager,
         cancellations: &CancellationContext,
     ) -> ControlFlow<CommandExecutionResult, CommandExecutionManager> {
+        let manager = match &self.local_action_cache {
+            Some(local_action_cache) => {
+                try_local_action_cache_fetch(
+                    local_action_cache,
+                    &self.artifact_fs,
+                    &*self.materializer,
+                    manager,
+                    command,
+                    cancellations,
+                )
+                .await?
+            }
+            None => manager,
+        };
+
+        if !self.remote_cache_enabled {
+            return ControlFlow::Continue(manager);
+        }
+
         let request = command.request;
         let action_digest = &command.prepared_action.action;
         let action_blobs = &command.prepared_action.blobs;
@@ -9,6 +9,7 @@
 
 use std::ops::ControlFlow;
 use std::sync::Arc;
+use std::time::Instant;
 use std::time::SystemTime;
 
 use anyhow::Context as _;
@@ -34,6 +35,7 @@ use buck2_execute::execute::prepared::PreparedCommand;
 use buck2_execute::execute::prepared::PreparedCommandExecutor;
 use buck2_execute::execute::request::CommandExecutionRequest;
 use buck2_execute::execute::request::ExecutorPreference;
+use buck2_execute::execute::result::CommandExecutionMetadata;
 use buck2_execute::execute::result::CommandExecutionResult;
 use buck2_execute::execute::result::CommandExecutionStatus;
 use buck2_execute::execute::target::CommandExecutionTarget;
@@ -57,6 +59,7 @@ use remote_execution::TStatus;
 use remote_execution::TTimestamp;
 use tracing::info;
 
+use crate::local_action_cache::LocalActionCache;
 use crate::re::download::download_action_results;
 use crate::re::download::DownloadResult;
 
@@ -73,6 +76,10 @@ pub struct CachingExecutor {
     pub upload_all_actions: bool,
     pub knobs: ExecutorGlobalKnobs,
     pub cache_upload_behavior: CacheUploadBehavior,
+    /// Whether to use the RE action cache. When this is disabled, we only use the local action
+    /// cache (if any).
+    pub remote_cache_enabled: bool,
+    pub local_action_cache: Option<Arc<LocalActionCache>>,
 }
 
 impl CachingExecutor {
@@ -155,6 +162,41 @@ impl CachingExecutor {
         ControlFlow::Break(res)
     }
 
+    /// Store an action result in the local action cache. Like for RE uploads, we only do this for
+    /// successful actions that ran locally.
+    async fn maybe_store_in_local_action_cache(
+        &self,
+        local_action_cache: &LocalActionCache,
+        request: &CommandExecutionRequest,
+        digest: &ActionDigest,
+        result: &CommandExecutionResult,
+        digest_config: DigestConfig,
+    ) {
+        if !request.allow_cache_upload() {
+            return;
+        }
+
+        match &result.report.status {
+            CommandExecutionStatus::Success {
+                execution_kind: CommandExecutionKind::Local { .. },
+            } => {}
+            _ => return,
+        }
+
+        match local_action_cache
+            .store(&self.artifact_fs, digest, result, digest_config)
+            .await
+        {
+            Ok(true) => tracing::debug!("Stored `{}` in the local action cache", digest),
+            Ok(false) => tracing::debug!("Local action cache cannot store `{}`", digest),
+            Err(e) => tracing::warn!(
+                "Storing `{}` in the local action cache failed: {:#}",
+                digest,
+                e
+            ),
+        }
+    }
+
     /// Upload an action result to the RE action cache, assuming conditions for the upload are met:
     /// the action must have been successful and must have run locally (not much point in caching
     /// something that ran on RE and is already cached), and cache uploads must be enabled, both
@@ -437,8 +479,23 @@ impl PreparedCommandExecutor for CachingExecutor {
             Err(e) => return manager.error("cache_upload", e),
         };
 
-        let manager = self
-            .try_action_cache_fetch(
+        let manager = match &self.local_action_cache {
+            Some(local_action_cache) => {
+                try_local_action_cache_fetch(
+                    local_action_cache,
+                    &self.artifact_fs,
+                    &*self.materializer,
+                    manager,
+                    command,
+                    cancellations,
+                )
+                .await?
+            }
+            None => manager,
+        };
+
+        let manager = if self.remote_cache_enabled {
+            self.try_action_cache_fetch(
                 manager,
                 command.request,
                 &command.prepared_action.action,
@@ -446,20 +503,37 @@ impl PreparedCommandExecutor for CachingExecutor {
                 command.digest_config,
                 cancellations,
             )
-            .await?;
+            .await?
+        } else {
+            manager
+        };
 
         let mut res = self.inner.exec_cmd(command, manager, cancellations).await;
 
+        if let Some(local_action_cache) = &self.local_action_cache {
+            self.maybe_store_in_local_action_cache(
+                local_action_cache,
+                command.request,
+                &command.prepared_action.action,
+                &res,
+                command.digest_config,
+            )
+            .await;
+        }
+
         // TODO(bobyf, torozco) should these be critical sections?
-        let upload_res = self
-            .maybe_perform_cache_upload(
+        let upload_res = if self.remote_cache_enabled {
+            self.maybe_perform_cache_upload(
                 command.request,
                 command.target.dupe(),
                 &command.prepared_action.action,
                 &res,
                 command.digest_config,
             )
-            .await;
+            .await
+        } else {
+            Ok(None)
+        };
 
         match upload_res {
             Ok(Some(CacheUploadOutcome::Success)) => {
@@ -506,6 +580,75 @@ impl PreparedCommandExecutor for CachingExecutor {
     }
 }
 
+/// Check the local action cache, and materialize the outputs of the action if we find it there.
+pub(crate) async fn try_local_action_cache_fetch(
+    local_action_cache: &LocalActionCache,
+    artifact_fs: &ArtifactFs,
+    materializer: &dyn Materializer,
+    manager: CommandExecutionManager,
+    command: &PreparedCommand<'_, '_>,
+    cancellations: &CancellationContext,
+) -> ControlFlow<CommandExecutionResult, CommandExecutionManager> {
+    let request = command.request;
+    let action_digest = &command.prepared_action.action;
+    let start_time = SystemTime::now();
+    let start = Instant::now();
+
+    let hit = local_action_cache
+        .lookup(
+            action_digest,
+            request.paths(),
+            request.outputs(),
+            command.digest_config,
+        )
+        .await;
+
+    let hit = match hit {
+        Ok(Some(hit)) => hit,
+        Ok(None) => return ControlFlow::Continue(manager),
+        Err(e) => {
+            tracing::warn!(
+                "Local action cache lookup for `{}` failed: {:#}",
+                action_digest,
+                e
+            );
+            return ControlFlow::Continue(manager);
+        }
+    };
+
+    info!(
+        "Action result is cached locally, skipping execution of:\n```\n$ {}\n```\n for action `{}`",
+        request.all_args_str(),
+        action_digest,
+    );
+
+    let manager = manager.claim().await;
+
+    if let Err(e) = local_action_cache
+        .materialize(artifact_fs, materializer, hit.to_declare, cancellations)
+        .await
+    {
+        return ControlFlow::Break(manager.error(
+            "local_action_cache",
+            e.context(format!("action_digest={}", action_digest)),
+        ));
+    }
+
+    ControlFlow::Break(manager.success(
+        CommandExecutionKind::LocalActionCache {
+            digest: action_digest.dupe(),
+        },
+        hit.outputs,
+        hit.std_streams,
+        CommandExecutionMetadata {
+            wall_time: start.elapsed(),
+            execution_time: hit.execution_time,
+            start_time,
+            ..Default::default()
+        },
+    ))
+}
+
 /// Whether we completed a cache upload.
 #[derive(Copy, Clone, Dupe, Debug)]
 enum CacheUploadOutcome {
@@ -19,6 +19,7 @@ use std::time::SystemTime;
 
 use anyhow::Context as _;
 use async_trait::async_trait;
+use buck2_common::executor_config::LocalSandboxOptions;
 use buck2_common::file_ops::FileDigestConfig;
 use buck2_common::liveliness_observer::LivelinessObserver;
 use buck2_common::liveliness_observer::LivelinessObserverExt;
@@ -77,6 +78,7 @@ use more_futures::cancellation::CancellationContext;
 use thiserror::Error;
 use tracing::info;
 
+use crate::executors::sandbox::configure_sandbox;
 use crate::executors::worker::WorkerPool;
 
 #[derive(Debug, Error)]
@@ -86,6 +88,15 @@ enum LocalExecutionError {
 
     #[error("Trying to execute a remote-only action on a local executor")]
     RemoteOnlyAction,
+
+    #[error("Sandboxing local actions requires the forkserver (`buck2.forkserver`)")]
+    SandboxRequiresForkserver,
+
+    #[error(
+        "Action ran in a sandbox and accessed files that are not declared as inputs:\n  {}",
+        .0.join("\n  ")
+    )]
+    UndeclaredInputs(Vec<String>),
 }
 
 #[derive(Clone)]
@@ -101,6 +112,7 @@ pub struct LocalExecutor {
     knobs: ExecutorGlobalKnobs,
     #[allow(unused)]
     worker_pool: Option<Arc<WorkerPool>>,
+    sandbox: Option<LocalSandboxOptions>,
 }
 
 impl LocalExecutor {
@@ -113,6 +125,7 @@ impl LocalExecutor {
         forkserver: Option<ForkserverClient>,
         knobs: ExecutorGlobalKnobs,
         worker_pool: Option<Arc<WorkerPool>>,
+        sandbox: Option<LocalSandboxOptions>,
     ) -> Self {
         Self {
             artifact_fs,
@@ -123,6 +136,7 @@ impl LocalExecutor {
             forkserver,
             knobs,
             worker_pool,
+            sandbox,
         }
     }
 
@@ -138,6 +152,7 @@ impl LocalExecutor {
         env_inheritance: Option<&'a EnvironmentInheritance>,
         liveliness_observer: impl LivelinessObserver + 'static,
         disable_miniperf: bool,
+        sandbox: Option<buck2_forkserver_proto::SandboxConfig>,
     ) -> impl futures::future::Future<
         Output = anyhow::Result<(GatherOutputStatus, Vec<u8>, Vec<u8>)>,
     > + Send
@@ -164,18 +179,23 @@ impl LocalExecutor {
                             env_inheritance,
                             liveliness_observer,
                             self.knobs.enable_miniperf && !disable_miniperf,
+                            sandbox,
                         )
                         .await
                     }
 
                     #[cfg(not(unix))]
                     {
-                        let _unused = (forkserver, disable_miniperf);
+                        let _unused = (forkserver, disable_miniperf, sandbox);
                         Err(anyhow::anyhow!("Forkserver is not supported off-UNIX"))
                     }
                 }
 
                 None => {
+                    if sandbox.is_some() {
+                        return Err(LocalExecutionError::SandboxRequiresForkserver.into());
+                    }
+
                     let mut cmd = background_command(exe);
                     cmd.current_dir(working_directory);
                     cmd.args(args);
@@ -260,6 +280,17 @@ impl LocalExecutor {
 
         let scratch_dir = &scratch_dir; // So it doesn't move in the block below.
 
+        let sandbox_config = match self.sandbox {
+            Some(options) => {
+                match configure_sandbox(&self.artifact_fs, request, scratch_dir.as_deref(), options)
+                {
+                    Ok(config) => Some(config),
+                    Err(e) => return manager.error("sandbox_setup_failed", e),
+                }
+            }
+            None => None,
+        };
+
         if let Err(e) = executor_stage_async(
             buck2_data::LocalStage {
                 stage: Some(buck2_data::LocalPrepareOutputDirs {}.into()),
@@ -384,7 +415,13 @@ impl LocalExecutor {
                 let execution_start = Instant::now();
                 let start_time = SystemTime::now();
 
-                let worker = request.worker();
+                // Persistent workers outlive the actions they run, so they can't be sandboxed. The
+                // executor config rejects enabling both, so this is only a safeguard.
+                let worker = if self.sandbox.is_some() {
+                    None
+                } else {
+                    request.worker()
+                };
                 #[cfg(unix)]
                 let worker_pool = self.worker_pool.dupe();
                 #[cfg(not(unix))]
@@ -397,8 +434,15 @@ impl LocalExecutor {
                     unreachable!("Workers should be disabled off unix");
                     // TODO(ctolliday) spawn workers the same way test resources are acquired
                     #[cfg(unix)]
-                    unix::exec_via_worker(worker_pool, worker, request.args(), env, &self.root)
-                        .await
+                    unix::exec_via_worker(
+                        worker_pool,
+                        worker,
+                        request.args(),
+                        env,
+                        &self.root,
+                        request.timeout(),
+                    )
+                    .await
                 } else {
                     self.exec(
                         &args[0],
@@ -409,6 +453,7 @@ impl LocalExecutor {
                         request.local_environment_inheritance(),
                         liveliness_observer,
                         request.disable_miniperf(),
+                        sandbox_config,
                     )
                     .await
                 };
@@ -445,7 +490,17 @@ impl LocalExecutor {
             GatherOutputStatus::Finished {
                 exit_code,
                 execution_stats,
+                undeclared_inputs,
             } => {
+                // Accessing undeclared inputs is an error even if the command succeeded, since
+                // it would not see them on RE.
+                if !undeclared_inputs.is_empty() {
+                    return manager.error(
+                        "undeclared_inputs",
+                        LocalExecutionError::UndeclaredInputs(undeclared_inputs),
+                    );
+                }
+
                 let outputs = match self
                     .calculate_and_declare_output_values(request, digest_config)
                     .await
@@ -911,6 +966,7 @@ mod unix {
         env_inheritance: Option<&EnvironmentInheritance>,
         liveliness_observer: impl LivelinessObserver + 'static,
         enable_miniperf: bool,
+        sandbox: Option<buck2_forkserver_proto::SandboxConfig>,
     ) -> anyhow::Result<(GatherOutputStatus, Vec<u8>, Vec<u8>)> {
         let exe = exe.as_ref();
 
@@ -926,6 +982,7 @@ mod unix {
             env: vec![],
             timeout: command_timeout.try_map(|d| d.try_into())?,
             enable_miniperf,
+            sandbox,
         };
         apply_local_execution_environment(&mut req, working_directory, env, env_inheritance);
         forkserver
@@ -982,6 +1039,7 @@ mod unix {
         args: &[String],
         env: impl IntoIterator<Item = (impl AsRef<OsStr> + Clone, impl AsRef<OsStr> + Clone)>,
         root: &AbsNormPathBuf,
+        timeout: Option<Duration>,
     ) -> anyhow::Result<(GatherOutputStatus, Vec<u8>, Vec<u8>)> {
         let env: Vec<(OsString, OsString)> = env
             .into_iter()
@@ -990,7 +1048,7 @@ mod unix {
         let worker = worker_pool
             .get_or_create_worker(worker, env.clone(), root)
             .await?;
-        Ok(worker.exec_cmd(args, env).await)
+        Ok(worker.exec_cmd(args, env, timeout).await)
     }
 }
 
@@ -1128,6 +1186,7 @@ mod tests {
             None,
             ExecutorGlobalKnobs::default(),
             None,
+            None,
         );
 
         Ok((executor, temp.path().root().to_buf(), temp))
@@ -1148,6 +1207,7 @@ mod tests {
                 None,
                 NoopLivelinessObserver::create(),
                 false,
+                None,
             )
             .await?;
         assert!(matches!(status, GatherOutputStatus::Finished { exit_code, .. } if exit_code == 0));
@@ -1184,6 +1244,7 @@ mod tests {
                 Some(&EnvironmentInheritance::empty()),
                 NoopLivelinessObserver::create(),
                 false,
+                None,
             )
             .await?;
         assert!(matches!(status, GatherOutputStatus::Finished { exit_code, .. } if exit_code == 0));
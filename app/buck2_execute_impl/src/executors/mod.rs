@@ -12,6 +12,7 @@ pub mod caching;
 pub mod hybrid;
 pub mod local;
 pub mod re;
+pub(crate) mod sandbox;
 #[cfg(unix)]
 pub mod worker;
 #[cfg(not(unix))]
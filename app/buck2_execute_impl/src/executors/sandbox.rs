@@ -0,0 +1,91 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+use std::path::Path;
+
+use buck2_common::executor_config::LocalSandboxOptions;
+use buck2_core::fs::artifact_path_resolver::ArtifactFs;
+use buck2_core::fs::project_rel_path::ProjectRelativePath;
+use buck2_execute::execute::request::CommandExecutionInput;
+use buck2_execute::execute::request::CommandExecutionRequest;
+use buck2_forkserver_proto::sandbox_path::Kind;
+use buck2_forkserver_proto::SandboxConfig;
+use buck2_forkserver_proto::SandboxPath;
+
+/// Configure the sandbox a local command runs in: its declared inputs are exposed read-only, and
+/// its outputs (as well as its scratch directory) are writable. Everything else in the project is
+/// unreachable, and opening it makes the command report undeclared inputs.
+pub(crate) fn configure_sandbox(
+    artifact_fs: &ArtifactFs,
+    request: &CommandExecutionRequest,
+    scratch_dir: Option<&ProjectRelativePath>,
+    options: LocalSandboxOptions,
+) -> anyhow::Result<SandboxConfig> {
+    let mut paths = Vec::new();
+
+    for input in request.inputs() {
+        match input {
+            CommandExecutionInput::Artifact(group) => {
+                for (artifact, _) in group.iter() {
+                    paths.push((artifact.resolve_path(artifact_fs)?, Kind::ReadOnly));
+                }
+            }
+            CommandExecutionInput::ActionMetadata(metadata) => {
+                let path = artifact_fs
+                    .buck_out_path_resolver()
+                    .resolve_gen(&metadata.path);
+                paths.push((path, Kind::ReadOnly));
+            }
+        }
+    }
+
+    // Those are the directories that `create_output_dirs` creates, which is where outputs will
+    // go.
+    for output in request.outputs() {
+        let output = output.resolve(artifact_fs);
+        if let Some(path) = output.path_to_create() {
+            paths.push((path.to_owned(), Kind::Writable));
+        }
+    }
+
+    if let Some(scratch_dir) = scratch_dir {
+        paths.push((scratch_dir.to_owned(), Kind::Writable));
+    }
+
+    if let Some(working_directory) = request.working_directory() {
+        if !working_directory.is_empty() {
+            paths.push((working_directory.to_owned(), Kind::EmptyDirectory));
+        }
+    }
+
+    Ok(SandboxConfig {
+        root: path_to_bytes(artifact_fs.fs().root().as_path()),
+        paths: paths
+            .iter()
+            .map(|(path, kind)| SandboxPath {
+                path: path.as_str().as_bytes().to_vec(),
+                kind: *kind as i32,
+            })
+            .collect(),
+        allow_network: options.allow_network,
+    })
+}
+
+fn path_to_bytes(path: &Path) -> Vec<u8> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::ffi::OsStrExt;
+        path.as_os_str().as_bytes().to_vec()
+    }
+
+    #[cfg(not(unix))]
+    {
+        path.to_string_lossy().into_owned().into_bytes()
+    }
+}
@@ -217,6 +217,7 @@ impl WorkerCommandHandle {
         &self,
         args: &[String],
         env: Vec<(OsString, OsString)>,
+        timeout: Option<Duration>,
     ) -> (GatherOutputStatus, Vec<u8>, Vec<u8>) {
         tracing::info!(
             "Sending worker command:\nExecuteCommand {{ argv: {:?}, env: {:?} }}\n",
@@ -233,7 +234,17 @@ impl WorkerCommandHandle {
             .collect();
 
         let request = ExecuteCommand { argv, env };
-        let response = self.client.clone().execute(request).await;
+        let mut client = self.client.clone();
+        let response = client.execute(request);
+        let response = match timeout {
+            Some(timeout) => match tokio::time::timeout(timeout, response).await {
+                Ok(response) => response,
+                // Dropping the request cancels it, but the worker decides what to do with the
+                // command it was running.
+                Err(_) => return (GatherOutputStatus::TimedOut(timeout), vec![], vec![]),
+            },
+            None => response.await,
+        };
 
         match response {
             Ok(response) => {
@@ -243,6 +254,7 @@ impl WorkerCommandHandle {
                     GatherOutputStatus::Finished {
                         exit_code: exec_response.exit_code,
                         execution_stats: None,
+                        undeclared_inputs: Vec::new(),
                     },
                     vec![],
                     exec_response.stderr.into(),
@@ -10,8 +10,10 @@
 #![feature(try_blocks)]
 #![feature(box_patterns)]
 #![feature(try_trait_v2)]
+#![feature(file_set_times)]
 
 pub mod executors;
+pub mod local_action_cache;
 pub mod low_pass_filter;
 pub mod materializers;
 pub mod re;
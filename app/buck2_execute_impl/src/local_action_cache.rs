@@ -0,0 +1,1002 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+//! A directory-backed action cache, used to get cache hits for builds that don't have access to
+//! a remote cache. Since the cache lives outside of buck-out, it survives `buck2 clean` and can
+//! be shared by all the daemons (and worktrees) of a given user.
+//!
+//! The on-disk layout is similar to the one used by Bazel's disk cache:
+//!
+//! - `ac/<prefix>/<action digest>` holds a JSON-encoded `CachedActionResult`.
+//! - `cas/<prefix>/<digest>` holds blobs: output files, output directories (encoded as an
+//!   `RE::Tree`) and std streams.
+//! - `tmp/` is used to stage writes. Those are committed with a rename, so that concurrent readers
+//!   (possibly in other daemons) never observe partially written entries.
+//!
+//! The cache is bounded in size. Once it exceeds its limit, we evict the least recently used
+//! action cache entries, then delete blobs that are no longer referenced by any entry.
+
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::io::Write;
+use std::path::Path;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::time::Duration;
+use std::time::SystemTime;
+
+use anyhow::Context as _;
+use buck2_common::cas_digest::CasDigest;
+use buck2_common::file_ops::FileDigest;
+use buck2_common::file_ops::FileMetadata;
+use buck2_common::file_ops::TrackedFileDigest;
+use buck2_common::invocation_paths::InvocationPaths;
+use buck2_common::legacy_configs::LegacyBuckConfig;
+use buck2_core::directory::unordered_entry_walk;
+use buck2_core::directory::DirectoryEntry;
+use buck2_core::fs::artifact_path_resolver::ArtifactFs;
+use buck2_core::fs::fs_util;
+use buck2_core::fs::paths::abs_norm_path::AbsNormPath;
+use buck2_core::fs::paths::abs_norm_path::AbsNormPathBuf;
+use buck2_core::fs::paths::forward_rel_path::ForwardRelativePath;
+use buck2_core::fs::project::ProjectRoot;
+use buck2_core::fs::project_rel_path::ProjectRelativePath;
+use buck2_core::fs::project_rel_path::ProjectRelativePathBuf;
+use buck2_execute::artifact_value::ArtifactValue;
+use buck2_execute::digest_config::DigestConfig;
+use buck2_execute::directory::directory_to_re_tree;
+use buck2_execute::directory::extract_artifact_value;
+use buck2_execute::directory::re_tree_to_directory;
+use buck2_execute::directory::ActionDirectoryMember;
+use buck2_execute::execute::action_digest::ActionDigest;
+use buck2_execute::execute::blocking::BlockingExecutor;
+use buck2_execute::execute::clean_output_paths::CleanOutputPaths;
+use buck2_execute::execute::output::CommandStdStreams;
+use buck2_execute::execute::request::CommandExecutionOutput;
+use buck2_execute::execute::request::CommandExecutionOutputRef;
+use buck2_execute::execute::request::CommandExecutionPaths;
+use buck2_execute::execute::result::CommandExecutionResult;
+use buck2_execute::materialize::materializer::Materializer;
+use chrono::DateTime;
+use chrono::Utc;
+use dupe::Dupe;
+use indexmap::IndexMap;
+use more_futures::cancellation::CancellationContext;
+use parking_lot::Mutex;
+use prost::Message;
+use remote_execution as RE;
+use serde::Deserialize;
+use serde::Serialize;
+use thiserror::Error;
+
+use crate::materializers::io::materialize_dirs_and_syms;
+
+const SECTION: &str = "buck2_local_action_cache";
+
+/// 10GiB.
+const DEFAULT_MAX_BYTES: u64 = 10 * 1024 * 1024 * 1024;
+
+/// When collecting, we evict down to this fraction of the size limit, so that we don't have to
+/// collect again on the very next write.
+const GC_TARGET_PERCENT: u64 = 80;
+
+/// Files in `tmp/` older than this are left over from writes that were interrupted (e.g. because
+/// the daemon was killed), so they get deleted when collecting.
+const STALE_TMP_AGE: Duration = Duration::from_secs(24 * 60 * 60);
+
+#[derive(Error, Debug)]
+enum LocalActionCacheError {
+    #[error("Invalid digest in local action cache entry: `{0}`")]
+    InvalidDigest(String),
+
+    #[error("Output `{0}` is not a valid path")]
+    InvalidOutputPath(String),
+}
+
+/// Configuration for the local action cache, read from the `[buck2_local_action_cache]` section
+/// of the root cell's buckconfig.
+#[derive(Clone, Debug)]
+pub struct LocalActionCacheConfig {
+    /// Where the cache lives on disk.
+    pub root: AbsNormPathBuf,
+    /// Size the cache is allowed to grow to before we start evicting entries.
+    pub max_bytes: u64,
+}
+
+impl LocalActionCacheConfig {
+    /// Returns `None` if the local action cache is not enabled.
+    pub fn from_config(
+        config: &LegacyBuckConfig,
+        project_root: &ProjectRoot,
+    ) -> anyhow::Result<Option<Self>> {
+        if !config.parse::<bool>(SECTION, "enabled")?.unwrap_or(false) {
+            return Ok(None);
+        }
+
+        let root = match config.get(SECTION, "path") {
+            Some(path) if Path::new(path).is_absolute() => {
+                AbsNormPathBuf::try_from(path.to_owned())
+                    .with_context(|| format!("Invalid `{}.path`: `{}`", SECTION, path))?
+            }
+            Some(path) => project_root.resolve(
+                ProjectRelativePath::new(path)
+                    .with_context(|| format!("Invalid `{}.path`: `{}`", SECTION, path))?,
+            ),
+            None => InvocationPaths::common_local_action_cache_dir()?,
+        };
+
+        let max_bytes = config
+            .parse(SECTION, "max_bytes")?
+            .unwrap_or(DEFAULT_MAX_BYTES);
+
+        Ok(Some(Self { root, max_bytes }))
+    }
+}
+
+/// An output file of a cached action.
+#[derive(Serialize, Deserialize)]
+struct CachedOutputFile {
+    path: String,
+    digest: String,
+    executable: bool,
+}
+
+/// An output directory of a cached action. The directory is stored as a `RE::Tree` in the CAS.
+#[derive(Serialize, Deserialize)]
+struct CachedOutputDirectory {
+    path: String,
+    tree_digest: String,
+}
+
+/// What we store in the action cache for a given action digest.
+#[derive(Serialize, Deserialize)]
+struct CachedActionResult {
+    output_files: Vec<CachedOutputFile>,
+    output_directories: Vec<CachedOutputDirectory>,
+    stdout_digest: String,
+    stderr_digest: String,
+    execution_time_ms: u64,
+    /// All the blobs this entry references (including the files in output directories). We use
+    /// this to check that an entry is complete before using it, and to find unreferenced blobs
+    /// when collecting.
+    blobs: Vec<String>,
+}
+
+/// An action cache hit, ready to be materialized.
+pub struct LocalActionCacheHit {
+    /// The outputs to materialize and declare to the materializer.
+    pub to_declare: Vec<(ProjectRelativePathBuf, ArtifactValue)>,
+    /// The outputs of the action.
+    pub outputs: IndexMap<CommandExecutionOutput, ArtifactValue>,
+    pub std_streams: CommandStdStreams,
+    /// How long the action originally took to execute.
+    pub execution_time: Duration,
+}
+
+pub struct LocalActionCache {
+    store: Arc<DiskStore>,
+    blocking_executor: Arc<dyn BlockingExecutor>,
+}
+
+impl LocalActionCache {
+    pub fn new(
+        config: LocalActionCacheConfig,
+        blocking_executor: Arc<dyn BlockingExecutor>,
+    ) -> Self {
+        Self {
+            store: Arc::new(DiskStore::new(config.root, config.max_bytes)),
+            blocking_executor,
+        }
+    }
+
+    /// Look up an action in the cache. This returns `None` if the action isn't cached, or if some
+    /// of the blobs it references were evicted.
+    pub async fn lookup<'a>(
+        &self,
+        action_digest: &ActionDigest,
+        paths: &CommandExecutionPaths,
+        requested_outputs: impl Iterator<Item = CommandExecutionOutputRef<'a>>,
+        digest_config: DigestConfig,
+    ) -> anyhow::Result<Option<LocalActionCacheHit>> {
+        let store = &self.store;
+        let found = self
+            .blocking_executor
+            .execute_io_inline(|| store.read_entry(action_digest))
+            .await?;
+
+        let found = match found {
+            Some(found) => found,
+            None => return Ok(None),
+        };
+
+        // Those digests are for files that exist on disk, so they don't expire.
+        let expires = DateTime::<Utc>::from(SystemTime::UNIX_EPOCH);
+        let cas_digest_config = digest_config.cas_digest_config();
+        let parse_digest = |digest: &str| {
+            FileDigest::parse_digest(digest, cas_digest_config)
+                .map(|(digest, _)| digest)
+                .map_err(|_| LocalActionCacheError::InvalidDigest(digest.to_owned()))
+        };
+
+        let mut builder = paths.input_directory().clone().into_builder();
+
+        for file in &found.entry.output_files {
+            let digest = TrackedFileDigest::new(parse_digest(&file.digest)?, cas_digest_config);
+            let entry = DirectoryEntry::Leaf(ActionDirectoryMember::File(FileMetadata {
+                digest,
+                is_executable: file.executable,
+            }));
+            builder.insert(output_path(&file.path)?, entry)?;
+        }
+
+        for (dir, tree) in found.entry.output_directories.iter().zip(&found.trees) {
+            let entry = re_tree_to_directory(tree, &expires, digest_config)?;
+            builder.insert(output_path(&dir.path)?, DirectoryEntry::Dir(entry))?;
+        }
+
+        let output_paths = paths.output_paths();
+        let mut to_declare = Vec::with_capacity(output_paths.len());
+        let mut outputs = IndexMap::with_capacity(output_paths.len());
+
+        for (requested, (path, _)) in requested_outputs.zip(output_paths.iter()) {
+            let value = extract_artifact_value(&builder, path, digest_config)?;
+            if let Some(value) = value {
+                to_declare.push((path.to_owned(), value.dupe()));
+                outputs.insert(requested.cloned(), value);
+            }
+        }
+
+        Ok(Some(LocalActionCacheHit {
+            to_declare,
+            outputs,
+            std_streams: CommandStdStreams::Local {
+                stdout: found.stdout,
+                stderr: found.stderr,
+            },
+            execution_time: Duration::from_millis(found.entry.execution_time_ms),
+        }))
+    }
+
+    /// Write the outputs of a cache hit to disk, and declare them to the materializer.
+    pub async fn materialize(
+        &self,
+        artifact_fs: &ArtifactFs,
+        materializer: &dyn Materializer,
+        to_declare: Vec<(ProjectRelativePathBuf, ArtifactValue)>,
+        cancellations: &CancellationContext,
+    ) -> anyhow::Result<()> {
+        let paths = to_declare
+            .iter()
+            .map(|(path, _)| path.clone())
+            .collect::<Vec<_>>();
+
+        materializer.invalidate_many(paths.clone()).await?;
+
+        self.blocking_executor
+            .execute_io(Box::new(CleanOutputPaths { paths }), cancellations)
+            .await
+            .context("Failed to cleanup output directory")?;
+
+        let store = &self.store;
+        let fs = artifact_fs.fs();
+        let to_declare = &to_declare;
+        self.blocking_executor
+            .execute_io_inline(|| {
+                for (path, value) in to_declare {
+                    store.materialize(value, &fs.resolve(path))?;
+                }
+                Ok(())
+            })
+            .await?;
+
+        materializer.declare_existing(to_declare.clone()).await
+    }
+
+    /// Store the outputs of a successful command in the cache. Returns `false` if they can't be
+    /// represented in the cache (this happens if an output is a symlink).
+    pub async fn store(
+        &self,
+        artifact_fs: &ArtifactFs,
+        action_digest: &ActionDigest,
+        result: &CommandExecutionResult,
+        digest_config: DigestConfig,
+    ) -> anyhow::Result<bool> {
+        let (stdout, stderr) = match &result.report.std_streams {
+            CommandStdStreams::Local { stdout, stderr } => (stdout.as_slice(), stderr.as_slice()),
+            CommandStdStreams::Empty => (&[][..], &[][..]),
+            // We only store results from local execution.
+            CommandStdStreams::Remote(..) => return Ok(false),
+        };
+
+        let store = &self.store;
+        let stored = self
+            .blocking_executor
+            .execute_io_inline(|| {
+                store.write_entry(
+                    artifact_fs,
+                    action_digest,
+                    result,
+                    stdout,
+                    stderr,
+                    digest_config,
+                )
+            })
+            .await?;
+
+        if self.store.needs_gc() {
+            let store = self.store.dupe();
+            tokio::task::spawn_blocking(move || {
+                if let Err(e) = store.gc() {
+                    tracing::warn!("Local action cache collection failed: {:#}", e);
+                }
+            });
+        }
+
+        Ok(stored)
+    }
+}
+
+/// An action cache entry read from disk, along with the blobs we need to decode it.
+struct FoundEntry {
+    entry: CachedActionResult,
+    trees: Vec<RE::Tree>,
+    stdout: Vec<u8>,
+    stderr: Vec<u8>,
+}
+
+struct DiskStore {
+    root: AbsNormPathBuf,
+    max_bytes: u64,
+    /// Approximate size of the cache on disk. Other daemons might be writing to the cache too, so
+    /// this is only accurate after a collection.
+    size: AtomicU64,
+    /// Whether `size` was initialized.
+    size_known: AtomicBool,
+    gc_running: AtomicBool,
+    tmp_counter: AtomicU64,
+    /// Held while computing the initial size, to avoid scanning the cache repeatedly.
+    size_lock: Mutex<()>,
+}
+
+impl DiskStore {
+    fn new(root: AbsNormPathBuf, max_bytes: u64) -> Self {
+        Self {
+            root,
+            max_bytes,
+            size: AtomicU64::new(0),
+            size_known: AtomicBool::new(false),
+            gc_running: AtomicBool::new(false),
+            tmp_counter: AtomicU64::new(0),
+            size_lock: Mutex::new(()),
+        }
+    }
+
+    fn ac_dir(&self) -> AbsNormPathBuf {
+        self.root.join(ForwardRelativePath::unchecked_new("ac"))
+    }
+
+    fn cas_dir(&self) -> AbsNormPathBuf {
+        self.root.join(ForwardRelativePath::unchecked_new("cas"))
+    }
+
+    fn tmp_dir(&self) -> AbsNormPathBuf {
+        self.root.join(ForwardRelativePath::unchecked_new("tmp"))
+    }
+
+    fn ac_path(&self, digest: &ActionDigest) -> anyhow::Result<AbsNormPathBuf> {
+        blob_path(&self.ac_dir(), &digest_file_name(digest))
+    }
+
+    fn cas_path(&self, digest: &str) -> anyhow::Result<AbsNormPathBuf> {
+        blob_path(&self.cas_dir(), &digest.replace(':', "_"))
+    }
+
+    fn read_entry(&self, action_digest: &ActionDigest) -> anyhow::Result<Option<FoundEntry>> {
+        let ac_path = self.ac_path(action_digest)?;
+        let data = match fs_util::read_to_string_opt(&ac_path)? {
+            Some(data) => data,
+            None => return Ok(None),
+        };
+
+        let entry: CachedActionResult = match serde_json::from_str(&data) {
+            Ok(entry) => entry,
+            Err(e) => {
+                tracing::warn!(
+                    "Ignoring corrupt local action cache entry `{}`: {}",
+                    ac_path,
+                    e
+                );
+                return Ok(None);
+            }
+        };
+
+        for blob in &entry.blobs {
+            if !fs_util::try_exists(self.cas_path(blob)?)? {
+                tracing::debug!(
+                    "Local action cache entry `{}` references missing blob `{}`",
+                    action_digest,
+                    blob
+                );
+                return Ok(None);
+            }
+        }
+
+        let trees = entry
+            .output_directories
+            .iter()
+            .map(|dir| {
+                let data = fs_util::read(self.cas_path(&dir.tree_digest)?)?;
+                RE::Tree::decode(data.as_slice()).context("Invalid tree in local action cache")
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+
+        let stdout = fs_util::read(self.cas_path(&entry.stdout_digest)?)?;
+        let stderr = fs_util::read(self.cas_path(&entry.stderr_digest)?)?;
+
+        // Bump the entry's mtime, which is what we use to evict entries.
+        touch(&ac_path)?;
+
+        Ok(Some(FoundEntry {
+            entry,
+            trees,
+            stdout,
+            stderr,
+        }))
+    }
+
+    fn write_entry(
+        &self,
+        artifact_fs: &ArtifactFs,
+        action_digest: &ActionDigest,
+        result: &CommandExecutionResult,
+        stdout: &[u8],
+        stderr: &[u8],
+        digest_config: DigestConfig,
+    ) -> anyhow::Result<bool> {
+        let fs = artifact_fs.fs();
+        let cas_digest_config = digest_config.cas_digest_config();
+
+        let mut output_files = Vec::new();
+        let mut output_directories = Vec::new();
+        let mut blobs = Vec::new();
+        let mut written_bytes = 0;
+
+        for (output, value) in result.resolve_outputs(artifact_fs) {
+            let abspath = fs.resolve(output.path());
+
+            match value.entry().as_ref() {
+                DirectoryEntry::Leaf(ActionDirectoryMember::File(f)) => {
+                    let digest = f.digest.to_string();
+                    written_bytes += self.write_blob_from_file(&digest, &abspath)?;
+                    output_files.push(CachedOutputFile {
+                        path: output.path().to_string(),
+                        digest: digest.clone(),
+                        executable: f.is_executable,
+                    });
+                    blobs.push(digest);
+                }
+                DirectoryEntry::Dir(d) => {
+                    let mut walk = unordered_entry_walk(value.entry().as_ref());
+                    while let Some((path, entry)) = walk.next() {
+                        if let DirectoryEntry::Leaf(ActionDirectoryMember::File(f)) = entry {
+                            let digest = f.digest.to_string();
+                            written_bytes +=
+                                self.write_blob_from_file(&digest, &abspath.join(path.get()))?;
+                            blobs.push(digest);
+                        }
+                    }
+
+                    let tree = directory_to_re_tree(d).encode_to_vec();
+                    let tree_digest =
+                        FileDigest::from_content(&tree, cas_digest_config).to_string();
+                    written_bytes += self.write_blob(&tree_digest, &tree)?;
+                    output_directories.push(CachedOutputDirectory {
+                        path: output.path().to_string(),
+                        tree_digest: tree_digest.clone(),
+                    });
+                    blobs.push(tree_digest);
+                }
+                DirectoryEntry::Leaf(..) => {
+                    // Like the RE action cache, we can't represent an output that is a symlink.
+                    return Ok(false);
+                }
+            }
+        }
+
+        let mut std_stream_digest = |data: &[u8]| -> anyhow::Result<String> {
+            let digest = FileDigest::from_content(data, cas_digest_config).to_string();
+            written_bytes += self.write_blob(&digest, data)?;
+            blobs.push(digest.clone());
+            Ok(digest)
+        };
+        let stdout_digest = std_stream_digest(stdout)?;
+        let stderr_digest = std_stream_digest(stderr)?;
+
+        blobs.sort();
+        blobs.dedup();
+
+        written_bytes += self.write_action_result(
+            action_digest,
+            &CachedActionResult {
+                output_files,
+                output_directories,
+                stdout_digest,
+                stderr_digest,
+                execution_time_ms: result.report.timing.execution_time.as_millis() as u64,
+                blobs,
+            },
+        )?;
+
+        self.add_size(written_bytes)?;
+
+        Ok(true)
+    }
+
+    /// Write an action cache entry. Returns the number of bytes written.
+    fn write_action_result(
+        &self,
+        action_digest: &ActionDigest,
+        entry: &CachedActionResult,
+    ) -> anyhow::Result<u64> {
+        let entry = serde_json::to_vec(entry)?;
+        self.write_atomic(&self.ac_path(action_digest)?, |file| {
+            Ok(file.write_all(&entry)?)
+        })?;
+        Ok(entry.len() as u64)
+    }
+
+    /// Copy a file into the CAS, unless it's already there. Returns the number of bytes written.
+    fn write_blob_from_file(&self, digest: &str, src: &AbsNormPath) -> anyhow::Result<u64> {
+        let dest = self.cas_path(digest)?;
+        if fs_util::try_exists(&dest)? {
+            return Ok(0);
+        }
+        self.write_atomic(&dest, |file| {
+            Ok(std::io::copy(&mut fs_util::open_file(src)?, file)?)
+        })
+    }
+
+    /// Write a blob into the CAS, unless it's already there. Returns the number of bytes written.
+    fn write_blob(&self, digest: &str, data: &[u8]) -> anyhow::Result<u64> {
+        let dest = self.cas_path(digest)?;
+        if fs_util::try_exists(&dest)? {
+            return Ok(0);
+        }
+        self.write_atomic(&dest, |file| {
+            file.write_all(data)?;
+            Ok(data.len() as u64)
+        })
+    }
+
+    /// Write a file by staging it in our `tmp` directory, then renaming it into place.
+    fn write_atomic<T>(
+        &self,
+        dest: &AbsNormPath,
+        write: impl FnOnce(&mut fs_util::FileWriteGuard) -> anyhow::Result<T>,
+    ) -> anyhow::Result<T> {
+        let tmp_dir = self.tmp_dir();
+        fs_util::create_dir_all(&tmp_dir)?;
+        let tmp = tmp_dir.join(ForwardRelativePath::new(&format!(
+            "{}.{}",
+            std::process::id(),
+            self.tmp_counter.fetch_add(1, Ordering::Relaxed)
+        ))?);
+
+        let res = (|| {
+            let mut file = fs_util::create_file(&tmp)?;
+            let res = write(&mut file)?;
+            drop(file);
+            if let Some(parent) = dest.parent() {
+                fs_util::create_dir_all(parent)?;
+            }
+            fs_util::rename(&tmp, dest)?;
+            Ok(res)
+        })();
+
+        if res.is_err() {
+            let _ignored = fs_util::remove_file(&tmp);
+        }
+
+        res
+    }
+
+    /// Materialize an artifact from the CAS at `dest`.
+    fn materialize(&self, value: &ArtifactValue, dest: &AbsNormPath) -> anyhow::Result<()> {
+        materialize_dirs_and_syms(value.entry().as_ref(), dest)?;
+
+        let mut walk = unordered_entry_walk(value.entry().as_ref());
+        while let Some((path, entry)) = walk.next() {
+            if let DirectoryEntry::Leaf(ActionDirectoryMember::File(f)) = entry {
+                let dest = dest.join(path.get());
+                let src = self.cas_path(&f.digest.to_string())?;
+                std::io::copy(
+                    &mut fs_util::open_file(&src)?,
+                    &mut fs_util::create_file(&dest)?,
+                )
+                .with_context(|| format!("Error copying `{}` to `{}`", src, dest))?;
+                if f.is_executable {
+                    fs_util::set_executable(&dest)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn add_size(&self, bytes: u64) -> anyhow::Result<()> {
+        if !self.size_known.load(Ordering::Acquire) {
+            let _guard = self.size_lock.lock();
+            if !self.size_known.load(Ordering::Acquire) {
+                let size = list_files(&self.ac_dir())?
+                    .into_iter()
+                    .chain(list_files(&self.cas_dir())?)
+                    .map(|f| f.size)
+                    .sum();
+                self.size.store(size, Ordering::Relaxed);
+                self.size_known.store(true, Ordering::Release);
+                return Ok(());
+            }
+        }
+        self.size.fetch_add(bytes, Ordering::Relaxed);
+        Ok(())
+    }
+
+    fn needs_gc(&self) -> bool {
+        self.size.load(Ordering::Relaxed) > self.max_bytes
+            && !self.gc_running.swap(true, Ordering::Relaxed)
+    }
+
+    /// Evict the least recently used entries until we're back under our target size, then delete
+    /// any blobs that are not referenced by the remaining entries.
+    fn gc(&self) -> anyhow::Result<()> {
+        let res = self.gc_impl();
+        self.gc_running.store(false, Ordering::Relaxed);
+        res
+    }
+
+    fn gc_impl(&self) -> anyhow::Result<()> {
+        let start = SystemTime::now();
+        let target = self.max_bytes / 100 * GC_TARGET_PERCENT;
+
+        let mut entries = list_files(&self.ac_dir())?;
+        // Most recently used first.
+        entries.sort_by(|a, b| b.mtime.cmp(&a.mtime));
+
+        let blobs = list_files(&self.cas_dir())?;
+        let blob_sizes = blobs
+            .iter()
+            .map(|b| (b.name.as_str(), b.size))
+            .collect::<HashMap<_, _>>();
+
+        let mut size = 0;
+        let mut live = HashSet::new();
+
+        for entry in entries {
+            let referenced = fs_util::read_to_string_opt(&entry.path)?
+                .and_then(|data| serde_json::from_str::<CachedActionResult>(&data).ok())
+                .map(|e| e.blobs);
+
+            let keep = match &referenced {
+                Some(referenced) => {
+                    let added = referenced
+                        .iter()
+                        .map(|b| b.replace(':', "_"))
+                        .filter(|b| !live.contains(b))
+                        .map(|b| blob_sizes.get(b.as_str()).copied().unwrap_or_default())
+                        .sum::<u64>();
+                    size + entry.size + added <= target
+                }
+                None => false,
+            };
+
+            if keep {
+                let referenced = referenced.unwrap_or_default();
+                size += entry.size;
+                for blob in referenced {
+                    let blob = blob.replace(':', "_");
+                    if !live.contains(&blob) {
+                        size += blob_sizes.get(blob.as_str()).copied().unwrap_or_default();
+                        live.insert(blob);
+                    }
+                }
+            } else {
+                remove_file_if_exists(&entry.path)?;
+            }
+        }
+
+        for blob in blobs {
+            // Blobs written since we started might be referenced by entries we did not see, so
+            // we leave those alone.
+            if blob.mtime >= start || live.contains(&blob.name) {
+                continue;
+            }
+            remove_file_if_exists(&blob.path)?;
+        }
+
+        self.remove_stale_tmp_files(start)?;
+
+        self.size.store(size, Ordering::Relaxed);
+        self.size_known.store(true, Ordering::Release);
+
+        tracing::info!(
+            "Local action cache collection done, size is now {} bytes",
+            size
+        );
+
+        Ok(())
+    }
+
+    /// Delete files in `tmp/` that were staged long enough ago that the write they were staged
+    /// for must have been interrupted.
+    fn remove_stale_tmp_files(&self, now: SystemTime) -> anyhow::Result<()> {
+        let files = match fs_util::read_dir_if_exists(self.tmp_dir())? {
+            Some(files) => files,
+            None => return Ok(()),
+        };
+
+        for file in files {
+            let file = file?;
+            let mtime = match file.metadata().and_then(|m| m.modified()) {
+                Ok(mtime) => mtime,
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+                Err(e) => return Err(e.into()),
+            };
+            let stale = now
+                .duration_since(mtime)
+                .map_or(false, |age| age > STALE_TMP_AGE);
+            if stale {
+                remove_file_if_exists(&file.path())?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Set the mtime of a file to now. It's fine if the file is gone: another daemon might have
+/// evicted it concurrently.
+fn touch(path: &AbsNormPath) -> anyhow::Result<()> {
+    let res = std::fs::OpenOptions::new()
+        .write(true)
+        .open(path)
+        .and_then(|file| file.set_modified(SystemTime::now()));
+    match res {
+        Ok(()) => Ok(()),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+        Err(e) => Err(e).with_context(|| format!("Error updating mtime of `{}`", path)),
+    }
+}
+
+/// Other daemons sharing the cache may be collecting concurrently, so files we're deleting might
+/// already be gone.
+fn remove_file_if_exists(path: &AbsNormPath) -> anyhow::Result<()> {
+    match std::fs::remove_file(path) {
+        Ok(()) => Ok(()),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+        Err(e) => Err(e).with_context(|| format!("Error removing `{}`", path)),
+    }
+}
+
+struct ListedFile {
+    path: AbsNormPathBuf,
+    name: String,
+    size: u64,
+    mtime: SystemTime,
+}
+
+/// List the files stored under `dir` (which is either the AC or the CAS).
+fn list_files(dir: &AbsNormPath) -> anyhow::Result<Vec<ListedFile>> {
+    let mut files = Vec::new();
+    let prefixes = match fs_util::read_dir_if_exists(dir)? {
+        Some(prefixes) => prefixes,
+        None => return Ok(files),
+    };
+
+    for prefix in prefixes {
+        let prefix = prefix?;
+        for file in fs_util::read_dir(prefix.path())? {
+            let file = file?;
+            // Files might get deleted concurrently by other daemons.
+            let metadata = match file.metadata() {
+                Ok(metadata) => metadata,
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+                Err(e) => return Err(e.into()),
+            };
+            files.push(ListedFile {
+                path: file.path(),
+                name: file.file_name().to_string_lossy().into_owned(),
+                size: metadata.len(),
+                mtime: metadata.modified()?,
+            });
+        }
+    }
+
+    Ok(files)
+}
+
+fn digest_file_name<Kind>(digest: &CasDigest<Kind>) -> String {
+    format!("{}_{}", digest.raw_digest(), digest.size())
+}
+
+/// Blobs are sharded by the first 2 characters of their name, to avoid huge directories.
+fn blob_path(dir: &AbsNormPath, name: &str) -> anyhow::Result<AbsNormPathBuf> {
+    let prefix = name.get(..2).unwrap_or(name);
+    Ok(dir
+        .join(ForwardRelativePath::new(prefix)?)
+        .join(ForwardRelativePath::new(name)?))
+}
+
+fn output_path(path: &str) -> anyhow::Result<&ForwardRelativePath> {
+    ForwardRelativePath::new(path)
+        .map_err(|_| LocalActionCacheError::InvalidOutputPath(path.to_owned()).into())
+}
+
+#[cfg(test)]
+mod tests {
+    use buck2_common::cas_digest::CasDigestConfig;
+    use buck2_core::fs::project::ProjectRootTemp;
+
+    use super::*;
+
+    fn set_mtime(path: &AbsNormPath, mtime: SystemTime) -> anyhow::Result<()> {
+        std::fs::OpenOptions::new()
+            .write(true)
+            .open(path)?
+            .set_modified(mtime)?;
+        Ok(())
+    }
+
+    fn mtime(path: &AbsNormPath) -> anyhow::Result<SystemTime> {
+        Ok(fs_util::metadata(path)?.modified()?)
+    }
+
+    fn action_digest(name: &str) -> ActionDigest {
+        ActionDigest::from_content(name.as_bytes(), CasDigestConfig::testing_default())
+    }
+
+    /// Write blobs with the given contents, then an entry for `action` referencing them. Returns
+    /// the digests of the blobs.
+    fn put(store: &DiskStore, action: &str, blobs: &[&[u8]]) -> anyhow::Result<Vec<String>> {
+        let digests = blobs
+            .iter()
+            .map(|data| {
+                let digest =
+                    FileDigest::from_content(data, CasDigestConfig::testing_default()).to_string();
+                store.write_blob(&digest, data)?;
+                Ok(digest)
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+
+        let empty = FileDigest::from_content(b"", CasDigestConfig::testing_default()).to_string();
+        store.write_blob(&empty, b"")?;
+
+        let mut referenced = digests.clone();
+        referenced.push(empty.clone());
+        store.write_action_result(
+            &action_digest(action),
+            &CachedActionResult {
+                output_files: digests
+                    .iter()
+                    .enumerate()
+                    .map(|(i, digest)| CachedOutputFile {
+                        path: format!("out/{}", i),
+                        digest: digest.clone(),
+                        executable: false,
+                    })
+                    .collect(),
+                output_directories: Vec::new(),
+                stdout_digest: empty.clone(),
+                stderr_digest: empty,
+                execution_time_ms: 10,
+                blobs: referenced,
+            },
+        )?;
+
+        Ok(digests)
+    }
+
+    #[test]
+    fn test_store_and_read_entry() -> anyhow::Result<()> {
+        let temp = ProjectRootTemp::new()?;
+        let store = DiskStore::new(temp.path().root().to_buf(), u64::MAX);
+
+        let digests = put(&store, "action", &[b"output"])?;
+
+        let found = store.read_entry(&action_digest("action"))?.unwrap();
+        assert_eq!(found.entry.output_files.len(), 1);
+        assert_eq!(found.entry.output_files[0].digest, digests[0]);
+        assert_eq!(found.entry.execution_time_ms, 10);
+        assert_eq!(found.stdout, b"");
+
+        assert!(store.read_entry(&action_digest("other"))?.is_none());
+
+        // Entries referencing blobs that were evicted are misses.
+        fs_util::remove_file(store.cas_path(&digests[0])?)?;
+        assert!(store.read_entry(&action_digest("action"))?.is_none());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_entry_bumps_mtime() -> anyhow::Result<()> {
+        let temp = ProjectRootTemp::new()?;
+        let store = DiskStore::new(temp.path().root().to_buf(), u64::MAX);
+
+        put(&store, "action", &[b"output"])?;
+        let ac_path = store.ac_path(&action_digest("action"))?;
+        let old = SystemTime::now() - Duration::from_secs(3600);
+        set_mtime(&ac_path, old)?;
+        let data = fs_util::read(&ac_path)?;
+
+        assert!(store.read_entry(&action_digest("action"))?.is_some());
+        assert!(mtime(&ac_path)? > old);
+        assert_eq!(fs_util::read(&ac_path)?, data);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_gc() -> anyhow::Result<()> {
+        let temp = ProjectRootTemp::new()?;
+        let blob_size = 4000;
+        // Room for one entry and its blobs, but not two.
+        let store = DiskStore::new(temp.path().root().to_buf(), 3 * blob_size);
+
+        let shared = vec![b's'; blob_size as usize];
+        let old_blobs = put(&store, "old", &[&vec![b'o'; blob_size as usize], &shared])?;
+        let new_blobs = put(&store, "new", &[&vec![b'n'; blob_size as usize], &shared])?;
+
+        // Blobs written while collecting are kept, so make everything older than the GC.
+        let now = SystemTime::now();
+        for file in list_files(&store.cas_dir())? {
+            set_mtime(&file.path, now - Duration::from_secs(60))?;
+        }
+        set_mtime(
+            &store.ac_path(&action_digest("old"))?,
+            now - Duration::from_secs(120),
+        )?;
+
+        fs_util::create_dir_all(store.tmp_dir())?;
+        let stale_tmp = store.tmp_dir().join(ForwardRelativePath::new("stale")?);
+        let fresh_tmp = store.tmp_dir().join(ForwardRelativePath::new("fresh")?);
+        fs_util::write(&stale_tmp, "")?;
+        fs_util::write(&fresh_tmp, "")?;
+        set_mtime(&stale_tmp, now - STALE_TMP_AGE - Duration::from_secs(60))?;
+
+        store.gc()?;
+
+        assert!(store.read_entry(&action_digest("old"))?.is_none());
+        assert!(store.read_entry(&action_digest("new"))?.is_some());
+        assert!(!fs_util::try_exists(store.cas_path(&old_blobs[0])?)?);
+        assert!(fs_util::try_exists(store.cas_path(&new_blobs[0])?)?);
+        assert!(fs_util::try_exists(store.cas_path(&new_blobs[1])?)?);
+        assert!(!fs_util::try_exists(&stale_tmp)?);
+        assert!(fs_util::try_exists(&fresh_tmp)?);
+        assert!(store.size.load(Ordering::Relaxed) <= store.max_bytes);
+
+        // Collecting again (e.g. concurrently from another daemon) is fine.
+        store.gc()?;
+        assert!(store.read_entry(&action_digest("new"))?.is_some());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_remove_file_if_exists() -> anyhow::Result<()> {
+        let temp = ProjectRootTemp::new()?;
+        let path = temp.path().root().join(ForwardRelativePath::new("file")?);
+        fs_util::write(&path, "")?;
+        remove_file_if_exists(&path)?;
+        assert!(!fs_util::try_exists(&path)?);
+        remove_file_if_exists(&path)?;
+        Ok(())
+    }
+}
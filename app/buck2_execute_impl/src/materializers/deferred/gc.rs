@@ -0,0 +1,279 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+//! Size-budget garbage collection: keeps the artifacts tracked by the materializer under a
+//! configured number of bytes by evicting the least recently accessed ones.
+
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+use std::collections::HashSet;
+
+use buck2_common::result::ToUnsharedResultExt;
+use buck2_core::fs::project_rel_path::ProjectRelativePath;
+use buck2_core::fs::project_rel_path::ProjectRelativePathBuf;
+use buck2_core::soft_error;
+use buck2_wrapper_common::invocation_id::TraceId;
+use dupe::Dupe;
+use futures::future::FutureExt;
+
+use crate::materializers::deferred::clean_path;
+use crate::materializers::deferred::io_handler::IoHandler;
+use crate::materializers::deferred::join_all_existing_futs;
+use crate::materializers::deferred::ArtifactMaterializationData;
+use crate::materializers::deferred::ArtifactMaterializationStage;
+use crate::materializers::deferred::ArtifactTree;
+use crate::materializers::deferred::CleaningFuture;
+use crate::materializers::deferred::DeferredMaterializerCommandProcessor;
+use crate::materializers::deferred::ExistingFutures;
+use crate::materializers::deferred::Processing;
+use crate::materializers::deferred::ProcessingFuture;
+
+/// Paths that running commands passed to `ensure_materialized`. They stay pinned until their
+/// command finishes, and the GC never evicts a pinned path or an artifact that contains one. This
+/// is what protects the artifacts commands are using, since others may be evicted even if they're
+/// active.
+#[derive(Default)]
+pub(super) struct Pins {
+    by_command: HashMap<TraceId, HashSet<ProjectRelativePathBuf>>,
+    /// How many running commands pinned each path.
+    counts: HashMap<ProjectRelativePathBuf, usize>,
+}
+
+impl Pins {
+    pub(super) fn pin(&mut self, trace_id: &TraceId, paths: &[ProjectRelativePathBuf]) {
+        let pinned = self.by_command.entry(trace_id.clone()).or_default();
+        for path in paths {
+            if pinned.insert(path.clone()) {
+                *self.counts.entry(path.clone()).or_default() += 1;
+            }
+        }
+    }
+
+    pub(super) fn release(&mut self, trace_id: &TraceId) {
+        for path in self.by_command.remove(trace_id).into_iter().flatten() {
+            if let Entry::Occupied(mut count) = self.counts.entry(path) {
+                *count.get_mut() -= 1;
+                if *count.get() == 0 {
+                    count.remove();
+                }
+            }
+        }
+    }
+
+    /// The pinned paths and all their ancestors, i.e. every path an artifact containing a pinned
+    /// path may be declared at.
+    fn with_ancestors(&self) -> HashSet<&ProjectRelativePath> {
+        let mut res = HashSet::new();
+        for path in self.counts.keys() {
+            let mut path = Some(&**path);
+            while let Some(p) = path {
+                if !res.insert(p) {
+                    break;
+                }
+                path = p.parent();
+            }
+        }
+        res
+    }
+}
+
+#[derive(Debug, Default, PartialEq, Eq)]
+pub(super) struct Eviction {
+    /// Artifacts to evict, least recently accessed first.
+    pub(super) paths: Vec<ProjectRelativePathBuf>,
+    pub(super) total_bytes: u64,
+    pub(super) evicted_bytes: u64,
+}
+
+/// Pick the artifacts to evict so that the materialized artifacts in `tree` fit in `max_bytes`.
+///
+/// Only artifacts that have no pending operation and aren't pinned by a running command are
+/// eligible. Artifacts declared by this daemon (`active: true`) are only eligible if they can be
+/// downloaded from the CAS again, as DICE still expects to be able to materialize them. If
+/// evicting all of the eligible ones isn't enough to get under budget, they are all evicted
+/// anyway.
+pub(super) fn find_artifacts_to_evict(
+    tree: &ArtifactTree,
+    max_bytes: u64,
+    pins: &Pins,
+) -> Eviction {
+    let pinned = pins.with_ancestors();
+    let mut total_bytes = 0;
+    let mut candidates = Vec::new();
+
+    for (path, data) in tree.iter_with_paths() {
+        if let ArtifactMaterializationStage::Materialized {
+            metadata,
+            last_access_time,
+            active,
+            cas_declaration,
+        } = &data.stage
+        {
+            let size = metadata.size();
+            total_bytes += size;
+
+            let path = ProjectRelativePathBuf::from(path);
+            let can_redeclare = cas_declaration.as_ref().map_or(false, |d| d.can_download());
+            if (!active || can_redeclare)
+                && !pinned.contains(&*path)
+                && matches!(data.processing, Processing::Done(..))
+            {
+                candidates.push((*last_access_time, path, size));
+            }
+        }
+    }
+
+    let mut eviction = Eviction {
+        total_bytes,
+        ..Default::default()
+    };
+
+    if total_bytes <= max_bytes {
+        return eviction;
+    }
+
+    candidates.sort_by(|(t1, p1, _), (t2, p2, _)| t1.cmp(t2).then_with(|| p1.cmp(p2)));
+
+    for (_, path, size) in candidates {
+        if total_bytes - eviction.evicted_bytes <= max_bytes {
+            break;
+        }
+        eviction.evicted_bytes += size;
+        eviction.paths.push(path);
+    }
+
+    eviction
+}
+
+impl<T: IoHandler> DeferredMaterializerCommandProcessor<T> {
+    /// Evict artifacts until the materialized artifacts fit in `max_bytes`. The artifacts are
+    /// removed from the materializer state right away, and deleted from disk in the background.
+    /// Active artifacts are declared again, so they're downloaded the next time they're needed.
+    pub(super) fn collect_garbage(&mut self, max_bytes: u64) {
+        if self.pending_gc_cleaning().is_some() {
+            tracing::debug!("Previous GC is still running, skipping");
+            return;
+        }
+
+        let eviction = find_artifacts_to_evict(&self.tree, max_bytes, &self.pins);
+        if eviction.paths.is_empty() {
+            return;
+        }
+
+        tracing::info!(
+            total_bytes = eviction.total_bytes,
+            evicted_bytes = eviction.evicted_bytes,
+            evicted_artifacts = eviction.paths.len(),
+            "Materializer size budget exceeded, evicting artifacts",
+        );
+
+        let mut redeclare = Vec::new();
+        for path in &eviction.paths {
+            if let Some(ArtifactMaterializationData {
+                deps,
+                stage:
+                    ArtifactMaterializationStage::Materialized {
+                        active: true,
+                        cas_declaration: Some(cas_declaration),
+                        ..
+                    },
+                ..
+            }) = self.tree.prefix_get(&mut path.iter()).map(|data| &**data)
+            {
+                redeclare.push((
+                    path.clone(),
+                    deps.dupe(),
+                    cas_declaration.entry.dupe(),
+                    cas_declaration.method.dupe(),
+                ));
+            }
+        }
+
+        // Always invalidate materializer state before actual deleting from filesystem
+        // so there will never be a moment where artifact is deleted but materializer
+        // thinks it still exists.
+        let existing_futs = match self
+            .tree
+            .invalidate_paths_and_collect_futures(eviction.paths.clone(), self.sqlite_db.as_mut())
+        {
+            Ok(existing_futs) => existing_futs,
+            Err(e) => {
+                soft_error!(
+                    "materializer_gc_error",
+                    e.context(self.log_buffer.clone()),
+                    quiet: true
+                )
+                .unwrap();
+                return;
+            }
+        };
+
+        let io = self.io.dupe();
+        let cancellations = self.cancellations;
+        let remove = self.rt.spawn(async move {
+            join_all_existing_futs(existing_futs)
+                .await
+                .unshared_error()?;
+            io.remove_paths(eviction.paths, cancellations).await
+        });
+
+        // Errors are only logged: artifacts declared at evicted paths wait on this future, and
+        // they'll clean up their own output path anyway.
+        let future: CleaningFuture = async move {
+            match remove.await {
+                Ok(Ok(())) => tracing::debug!("Finished removing evicted artifacts"),
+                Ok(Err(e)) => tracing::warn!("Error removing evicted artifacts: {:#}", e),
+                Err(e) => tracing::warn!("Error removing evicted artifacts: {:#}", e),
+            }
+            Ok(())
+        }
+        .boxed()
+        .shared();
+
+        // Materializing the redeclared artifacts has to wait for the eviction to finish.
+        for (path, deps, entry, method) in redeclare {
+            let version = self.version_tracker.next();
+            let future = ProcessingFuture::Cleaning(clean_path(
+                &self.io,
+                path.clone(),
+                version,
+                self.command_sender.dupe(),
+                ExistingFutures(Ok(vec![(
+                    path.clone(),
+                    ProcessingFuture::Cleaning(future.clone()),
+                )])),
+                &self.rt,
+                self.cancellations,
+            ));
+            self.tree.insert(
+                path.iter().map(|f| f.to_owned()),
+                Box::new(ArtifactMaterializationData {
+                    deps,
+                    stage: ArtifactMaterializationStage::Declared { entry, method },
+                    processing: Processing::Active { future, version },
+                }),
+            );
+        }
+
+        self.gc_cleaning = Some(future);
+    }
+
+    /// The removal of the artifacts evicted by the last GC, if it hasn't finished yet.
+    pub(super) fn pending_gc_cleaning(&mut self) -> Option<CleaningFuture> {
+        // The removal is spawned, so this only checks whether it's done.
+        if self
+            .gc_cleaning
+            .as_ref()
+            .map_or(false, |f| f.clone().now_or_never().is_some())
+        {
+            self.gc_cleaning = None;
+        }
+        self.gc_cleaning.clone()
+    }
+}
@@ -33,6 +33,7 @@ use buck2_execute::directory::ActionSharedDirectory;
 use buck2_execute::execute::blocking::BlockingExecutor;
 use buck2_execute::execute::blocking::IoRequest;
 use buck2_execute::execute::clean_output_paths::cleanup_path;
+use buck2_execute::execute::clean_output_paths::CleanOutputPaths;
 use buck2_execute::materialize::http::http_download;
 use buck2_execute::output_size::OutputSize;
 use buck2_execute::re::manager::ReConnectionManager;
@@ -65,6 +66,7 @@ use crate::materializers::deferred::Version;
 use crate::materializers::deferred::WriteFile;
 use crate::materializers::io::materialize_files;
 use crate::materializers::io::MaterializeTreeStructure;
+use crate::materializers::local_cas::LocalCas;
 
 pub(super) struct DefaultIoHandler {
     pub(super) fs: ProjectRoot,
@@ -74,6 +76,8 @@ pub(super) struct DefaultIoHandler {
     /// Executor for blocking IO operations
     pub(super) io_executor: Arc<dyn BlockingExecutor>,
     pub(super) http_client: Arc<dyn HttpClient>,
+    /// Store shared across buck-outs, used to avoid downloading the same files again.
+    pub(super) local_cas: Option<LocalCas>,
 }
 
 struct MaterializationStat {
@@ -100,6 +104,13 @@ pub(super) trait IoHandler: Sized + Sync + Send + 'static {
         cancellations: &'a CancellationContext,
     ) -> BoxFuture<'a, Result<(), SharedError>>;
 
+    /// Delete paths that are no longer tracked by the materializer.
+    fn remove_paths(
+        self: &Arc<Self>,
+        paths: Vec<ProjectRelativePathBuf>,
+        cancellations: &'static CancellationContext,
+    ) -> BoxFuture<'static, anyhow::Result<()>>;
+
     async fn materialize_entry(
         self: &Arc<Self>,
         path: ProjectRelativePathBuf,
@@ -149,39 +160,52 @@ impl DefaultIoHandler {
 
                     while let Some((entry_path, entry)) = walk.next() {
                         if let DirectoryEntry::Leaf(ActionDirectoryMember::File(f)) = entry {
-                            let name = path.join_normalized(entry_path.get())?;
-                            let digest = maybe_tombstone_digest(f.digest.data())?.to_re();
-
-                            tracing::trace!(name = %name, digest = %digest, "push download");
-                            let name = self
-                                .fs
-                                .resolve(&name)
-                                .as_maybe_relativized_str()?
-                                .to_owned();
-
-                            files.push(NamedDigestWithPermissions {
-                                named_digest: NamedDigest {
-                                    name,
-                                    digest,
-                                    ..Default::default()
-                                },
-                                is_executable: f.is_executable,
-                                ..Default::default()
-                            });
+                            files.push((path.join_normalized(entry_path.get())?, f.dupe()));
                         }
                     }
                 }
                 stat.file_count = files.len().try_into().unwrap_or_default();
-                stat.total_bytes = files
-                    .iter()
-                    .map(|x| u64::try_from(x.named_digest.digest.size_in_bytes).unwrap_or_default())
-                    .sum();
+
+                // Only download the files that aren't in the local CAS.
+                let files = match &self.local_cas {
+                    Some(local_cas) => {
+                        self.io_executor
+                            .execute_io_inline(|| Ok(local_cas.materialize_files(&self.fs, files)))
+                            .await?
+                    }
+                    None => files,
+                };
+
+                if files.is_empty() {
+                    return Ok(());
+                }
+
+                // Only count what is actually downloaded.
+                stat.total_bytes = files.iter().map(|(_, f)| f.digest.size()).sum();
+
+                let mut re_files = Vec::with_capacity(files.len());
+                for (name, f) in &files {
+                    let digest = maybe_tombstone_digest(f.digest.data())?.to_re();
+
+                    tracing::trace!(name = %name, digest = %digest, "push download");
+                    let name = self.fs.resolve(name).as_maybe_relativized_str()?.to_owned();
+
+                    re_files.push(NamedDigestWithPermissions {
+                        named_digest: NamedDigest {
+                            name,
+                            digest,
+                            ..Default::default()
+                        },
+                        is_executable: f.is_executable,
+                        ..Default::default()
+                    });
+                }
 
                 let connection = self.re_client_manager.get_re_connection();
                 let re_client = connection.get_client();
 
                 re_client
-                    .materialize_files(files, info.re_use_case)
+                    .materialize_files(re_files, info.re_use_case)
                     .await
                     .map_err(|e| match e.downcast_ref::<REClientError>() {
                         Some(e) if e.code == TCode::NOT_FOUND => MaterializeEntryError::NotFound {
@@ -195,6 +219,15 @@ impl DefaultIoHandler {
                             )
                         })),
                     })?;
+
+                if let Some(local_cas) = &self.local_cas {
+                    self.io_executor
+                        .execute_io_inline(|| {
+                            local_cas.insert_files(&self.fs, &files);
+                            Ok(())
+                        })
+                        .await?;
+                }
             }
             ArtifactMaterializationMethod::HttpDownload { info } => {
                 async {
@@ -314,6 +347,28 @@ impl IoHandler for DefaultIoHandler {
             .boxed()
     }
 
+    fn remove_paths(
+        self: &Arc<Self>,
+        paths: Vec<ProjectRelativePathBuf>,
+        cancellations: &'static CancellationContext,
+    ) -> BoxFuture<'static, anyhow::Result<()>> {
+        let io_executor = self.io_executor.dupe();
+
+        async move {
+            // Kick off one CleanOutputPaths per path to get parallelism.
+            futures::future::try_join_all(paths.into_iter().map(|path| {
+                io_executor.execute_io(
+                    Box::new(CleanOutputPaths { paths: vec![path] }),
+                    cancellations,
+                )
+            }))
+            .await?;
+
+            Ok(())
+        }
+        .boxed()
+    }
+
     /// Materializes an `entry` at `path`, using the materialization `method`
     #[instrument(level = "debug", skip(self, cancellations), fields(path = %path, method = %method, entry = %entry))]
     async fn materialize_entry(
@@ -10,6 +10,7 @@
 mod clean_stale;
 mod extension;
 mod file_tree;
+mod gc;
 mod io_handler;
 mod subscriptions;
 
@@ -98,11 +99,14 @@ use tracing::instrument;
 
 use crate::materializers::deferred::extension::ExtensionCommand;
 use crate::materializers::deferred::file_tree::FileTree;
+use crate::materializers::deferred::gc::Pins;
 use crate::materializers::deferred::io_handler::DefaultIoHandler;
 use crate::materializers::deferred::io_handler::IoHandler;
 use crate::materializers::deferred::subscriptions::MaterializerSubscriptionOperation;
 use crate::materializers::deferred::subscriptions::MaterializerSubscriptions;
 use crate::materializers::immediate;
+use crate::materializers::local_cas::LocalCas;
+use crate::materializers::local_cas::LocalCasConfig;
 use crate::materializers::sqlite::MaterializerState;
 use crate::materializers::sqlite::MaterializerStateSqliteDb;
 
@@ -162,6 +166,9 @@ pub struct DeferredMaterializerConfigs {
     pub materialize_final_artifacts: bool,
     pub defer_write_actions: bool,
     pub ttl_refresh: TtlRefreshConfiguration,
+    pub gc: GcConfiguration,
+    /// When set, downloaded files are shared with other buck-outs through this store.
+    pub local_cas: Option<LocalCasConfig>,
 }
 
 pub struct TtlRefreshConfiguration {
@@ -170,6 +177,13 @@ pub struct TtlRefreshConfiguration {
     pub enabled: bool,
 }
 
+pub struct GcConfiguration {
+    pub frequency: std::time::Duration,
+    /// Size budget for the artifacts tracked by the materializer. When exceeded, the least
+    /// recently accessed artifacts are evicted. No GC happens if this is unset.
+    pub max_bytes: Option<u64>,
+}
+
 #[derive(Copy, Dupe, Clone)]
 struct MaterializerCounters {
     sent: &'static AtomicUsize,
@@ -261,6 +275,10 @@ struct DeferredMaterializerCommandProcessor<T: 'static> {
     ttl_refresh_history: Vec<TtlRefreshHistoryEntry>,
     /// The current ttl_refresh instance, if any exists.
     ttl_refresh_instance: Option<oneshot::Receiver<(DateTime<Utc>, anyhow::Result<()>)>>,
+    /// Paths that running commands asked to materialize, which the GC never evicts.
+    pins: Pins,
+    /// The removal of the artifacts evicted by the last GC, if it's still running.
+    gc_cleaning: Option<CleaningFuture>,
     cancellations: &'static CancellationContext,
 }
 
@@ -360,6 +378,9 @@ enum MaterializerCommand<T: 'static> {
         oneshot::Sender<BoxStream<'static, Result<(), MaterializationError>>>,
     ),
 
+    /// Releases the paths pinned by the `Ensure` commands of a command that has finished.
+    ReleasePins(TraceId),
+
     Subscription(MaterializerSubscriptionOperation<T>),
 
     Extension(Box<dyn ExtensionCommand<T>>),
@@ -388,6 +409,7 @@ impl<T> std::fmt::Debug for MaterializerCommand<T> {
                 write!(f, "InvalidateFilePaths({:?})", paths)
             }
             MaterializerCommand::Ensure(paths, _, _) => write!(f, "Ensure({:?}, _)", paths,),
+            MaterializerCommand::ReleasePins(trace_id) => write!(f, "ReleasePins({})", trace_id),
             MaterializerCommand::Subscription(op) => write!(f, "Subscription({:?})", op,),
             MaterializerCommand::Extension(ext) => write!(f, "Extension({:?})", ext),
         }
@@ -567,11 +589,45 @@ enum ArtifactMaterializationStage {
         last_access_time: DateTime<Utc>,
         /// Artifact declared by running daemon.
         /// Should not be deleted without invalidating DICE nodes, which currently
-        /// means killing the daemon.
+        /// means killing the daemon, unless it can be declared again from `cas_declaration`.
         active: bool,
+        /// How this active artifact was declared, if it was downloaded from the CAS. This lets
+        /// the GC evict it and declare it again, so it's downloaded the next time it's needed.
+        cas_declaration: Option<Box<CasDeclaration>>,
     },
 }
 
+/// The declaration of an artifact that is downloaded from the CAS.
+struct CasDeclaration {
+    entry: ActionDirectoryEntry<ActionSharedDirectory>,
+    method: Arc<ArtifactMaterializationMethod>,
+}
+
+impl CasDeclaration {
+    fn new(
+        entry: &ActionDirectoryEntry<ActionSharedDirectory>,
+        method: &Arc<ArtifactMaterializationMethod>,
+    ) -> Option<Box<Self>> {
+        match &**method {
+            ArtifactMaterializationMethod::CasDownload { .. } => Some(Box::new(Self {
+                entry: entry.dupe(),
+                method: method.dupe(),
+            })),
+            _ => None,
+        }
+    }
+
+    /// Whether the CAS is still guaranteed to have the artifact.
+    fn can_download(&self) -> bool {
+        match &*self.method {
+            ArtifactMaterializationMethod::CasDownload { info } => {
+                info.origin.guaranteed_by_action_cache()
+            }
+            _ => false,
+        }
+    }
+}
+
 /// Different ways to materialize the files of an artifact. Some artifacts need
 /// to be fetched from the CAS, others copied locally.
 #[derive(Debug, Display)]
@@ -863,6 +919,13 @@ impl Materializer for DeferredMaterializer {
     fn log_materializer_state(&self, events: &EventDispatcher) {
         events.instant_event(self.materializer_state_info.clone())
     }
+
+    fn command_finished(&self, trace_id: &TraceId) {
+        // The materializer may already be shutting down, in which case there is nothing to do.
+        let _ignored = self
+            .command_sender
+            .send(MaterializerCommand::ReleasePins(trace_id.clone()));
+    }
 }
 
 impl DeferredMaterializer {
@@ -913,6 +976,7 @@ impl DeferredMaterializer {
                             metadata,
                             last_access_time,
                             active: false,
+                            cas_declaration: None,
                         },
                         processing: Processing::Done(Version(0)),
                     }),
@@ -920,6 +984,10 @@ impl DeferredMaterializer {
             }
         }
 
+        let local_cas = configs
+            .local_cas
+            .map(|config| LocalCas::new(config, digest_config));
+
         let command_processor = {
             let command_sender = command_sender.dupe();
             let io_executor = io_executor.dupe();
@@ -933,6 +1001,7 @@ impl DeferredMaterializer {
                     re_client_manager,
                     io_executor,
                     http_client,
+                    local_cas,
                 }),
                 digest_config,
                 sqlite_db,
@@ -945,6 +1014,8 @@ impl DeferredMaterializer {
                 subscriptions: MaterializerSubscriptions::new(),
                 ttl_refresh_history: Vec::new(),
                 ttl_refresh_instance: None,
+                pins: Pins::default(),
+                gc_cleaning: None,
                 cancellations,
             }
         };
@@ -960,9 +1031,11 @@ impl DeferredMaterializer {
 
                     let cancellations = CancellationContext::never_cancelled();
 
-                    rt.block_on(
-                        command_processor(cancellations).run(command_receiver, configs.ttl_refresh),
-                    );
+                    rt.block_on(command_processor(cancellations).run(
+                        command_receiver,
+                        configs.ttl_refresh,
+                        configs.gc,
+                    ));
                 }
             })
             .context("Cannot start materializer thread")?;
@@ -1014,12 +1087,14 @@ struct CommandStream<T: 'static> {
     high_priority: UnboundedReceiver<MaterializerCommand<T>>,
     low_priority: UnboundedReceiver<LowPriorityMaterializerCommand>,
     refresh_ttl_ticker: Option<Interval>,
+    gc_ticker: Option<Interval>,
 }
 
 enum Op<T: 'static> {
     Command(MaterializerCommand<T>),
     LowPriorityCommand(LowPriorityMaterializerCommand),
     RefreshTtls,
+    CollectGarbage,
 }
 
 impl<T: 'static> Stream for CommandStream<T> {
@@ -1042,6 +1117,12 @@ impl<T: 'static> Stream for CommandStream<T> {
             }
         }
 
+        if let Some(ticker) = this.gc_ticker.as_mut() {
+            if let Poll::Ready(..) = ticker.poll_tick(cx) {
+                return Poll::Ready(Some(Op::CollectGarbage));
+            }
+        }
+
         // We can never be done because we never drop the senders, so let's not bother.
 
         Poll::Pending
@@ -1056,6 +1137,7 @@ impl<T: IoHandler> DeferredMaterializerCommandProcessor<T> {
         mut self,
         commands: MaterializerReceiver<T>,
         ttl_refresh: TtlRefreshConfiguration,
+        gc: GcConfiguration,
     ) {
         let MaterializerReceiver {
             high_priority,
@@ -1072,10 +1154,15 @@ impl<T: IoHandler> DeferredMaterializerCommandProcessor<T> {
             None
         };
 
+        let gc_ticker = gc.max_bytes.map(|_| {
+            tokio::time::interval_at(tokio::time::Instant::now() + gc.frequency, gc.frequency)
+        });
+
         let mut stream = CommandStream {
             high_priority,
             low_priority,
             refresh_ttl_ticker,
+            gc_ticker,
         };
 
         while let Some(op) = stream.next().await {
@@ -1127,6 +1214,11 @@ impl<T: IoHandler> DeferredMaterializerCommandProcessor<T> {
                         }
                     }
                 }
+                Op::CollectGarbage => {
+                    if let Some(max_bytes) = gc.max_bytes {
+                        self.collect_garbage(max_bytes);
+                    }
+                }
             }
         }
     }
@@ -1183,10 +1275,12 @@ impl<T: IoHandler> DeferredMaterializerCommandProcessor<T> {
             }
             // Entry point for `ensure_materialized` calls
             MaterializerCommand::Ensure(paths, event_dispatcher, fut_sender) => {
+                self.pins.pin(event_dispatcher.trace_id(), &paths);
                 fut_sender
                     .send(self.materialize_many_artifacts(paths, event_dispatcher))
                     .ok();
             }
+            MaterializerCommand::ReleasePins(trace_id) => self.pins.release(&trace_id),
             MaterializerCommand::Subscription(sub) => sub.execute(self),
             MaterializerCommand::Extension(ext) => ext.execute(self),
         }
@@ -1298,6 +1392,7 @@ impl<T: IoHandler> DeferredMaterializerCommandProcessor<T> {
                     metadata,
                     last_access_time: Utc::now(),
                     active: true,
+                    cas_declaration: None,
                 },
                 processing: Processing::Done(self.version_tracker.next()),
             }),
@@ -1345,6 +1440,7 @@ impl<T: IoHandler> DeferredMaterializerCommandProcessor<T> {
                             metadata: metadata.dupe(),
                             last_access_time: *last_access_time,
                             active: true,
+                            cas_declaration: CasDeclaration::new(value.entry(), &Arc::from(method)),
                         };
                         data.deps = deps;
 
@@ -1369,10 +1465,16 @@ impl<T: IoHandler> DeferredMaterializerCommandProcessor<T> {
         // Always invalidate materializer state before actual deleting from filesystem
         // so there will never be a moment where artifact is deleted but materializer
         // thinks it still exists.
-        let existing_futs = self
+        let mut existing_futs = self
             .tree
             .invalidate_paths_and_collect_futures(vec![path.to_owned()], self.sqlite_db.as_mut());
 
+        // Artifacts evicted by the GC are no longer in the tree, but they may still be on disk
+        // where this artifact is about to be materialized.
+        if let (Ok(futs), Some(gc_cleaning)) = (&mut existing_futs, self.pending_gc_cleaning()) {
+            futs.push((path.to_owned(), ProcessingFuture::Cleaning(gc_cleaning)));
+        }
+
         let existing_futs = ExistingFutures(existing_futs);
 
         let method = Arc::from(method);
@@ -1727,10 +1829,7 @@ impl<T: IoHandler> DeferredMaterializerCommandProcessor<T> {
                             tracing::debug!("artifact is already materialized");
                             None
                         }
-                        ArtifactMaterializationStage::Declared {
-                            entry,
-                            method: _method,
-                        } => {
+                        ArtifactMaterializationStage::Declared { entry, method } => {
                             let metadata = ArtifactMetadata::new(entry);
                             // NOTE: We only insert this artifact if there isn't an in-progress cleanup
                             // future on this path.
@@ -1748,6 +1847,7 @@ impl<T: IoHandler> DeferredMaterializerCommandProcessor<T> {
                                 metadata,
                                 last_access_time: timestamp,
                                 active: true,
+                                cas_declaration: CasDeclaration::new(entry, method),
                             })
                         }
                     };
@@ -90,8 +90,10 @@ mod state_machine {
     use std::path::Path;
 
     use assert_matches::assert_matches;
+    use buck2_common::executor_config::RemoteExecutorUseCase;
     use buck2_execute::directory::Symlink;
     use buck2_execute::directory::INTERNER;
+    use buck2_execute::execute::action_digest::TrackedActionDigest;
     use parking_lot::Mutex;
     use tokio::time::sleep;
     use tokio::time::Duration as TokioDuration;
@@ -165,6 +167,17 @@ mod state_machine {
             .boxed()
         }
 
+        fn remove_paths(
+            self: &Arc<Self>,
+            paths: Vec<ProjectRelativePathBuf>,
+            _cancellations: &'static CancellationContext,
+        ) -> BoxFuture<'static, anyhow::Result<()>> {
+            self.log
+                .lock()
+                .extend(paths.into_iter().map(|path| (Op::Clean, path)));
+            futures::future::ready(Ok(())).boxed()
+        }
+
         async fn materialize_entry(
             self: &Arc<Self>,
             path: ProjectRelativePathBuf,
@@ -256,6 +269,8 @@ mod state_machine {
                 subscriptions: MaterializerSubscriptions::new(),
                 ttl_refresh_history: Default::default(),
                 ttl_refresh_instance: Default::default(),
+                pins: Pins::default(),
+                gc_cleaning: None,
                 cancellations: CancellationContext::testing(),
             },
             command_receiver,
@@ -661,4 +676,186 @@ mod state_machine {
 
         Ok(())
     }
+
+    fn insert_materialized(
+        dm: &mut DeferredMaterializerCommandProcessor<StubIoHandler>,
+        path: &ProjectRelativePath,
+        size: usize,
+        last_access_time: DateTime<Utc>,
+        active: bool,
+        cas_declaration: Option<Box<CasDeclaration>>,
+    ) {
+        let metadata = ArtifactMetadata(DirectoryEntry::Leaf(ActionDirectoryMember::File(
+            FileMetadata {
+                digest: TrackedFileDigest::from_content(
+                    &vec![0; size],
+                    dm.digest_config.cas_digest_config(),
+                ),
+                is_executable: false,
+            },
+        )));
+
+        dm.tree.insert(
+            path.iter().map(|f| f.to_owned()),
+            Box::new(ArtifactMaterializationData {
+                deps: None,
+                stage: ArtifactMaterializationStage::Materialized {
+                    metadata,
+                    last_access_time,
+                    active,
+                    cas_declaration,
+                },
+                processing: Processing::Done(Version(0)),
+            }),
+        );
+    }
+
+    #[tokio::test]
+    async fn test_gc() -> anyhow::Result<()> {
+        let digest_config = DigestConfig::testing_default();
+
+        let (mut dm, _) = make_processor(digest_config, Default::default());
+
+        let old = make_path("gc/old");
+        let older = make_path("gc/older");
+        let active = make_path("gc/active");
+        let pinned = make_path("gc/pinned");
+
+        let now = Utc::now();
+        insert_materialized(&mut dm, &old, 10, now - Duration::days(1), false, None);
+        insert_materialized(&mut dm, &older, 10, now - Duration::days(2), false, None);
+        insert_materialized(&mut dm, &active, 10, now - Duration::days(3), true, None);
+        insert_materialized(&mut dm, &pinned, 10, now - Duration::days(4), false, None);
+
+        // Two commands use the pinned artifact, one of them only a path inside it.
+        let first_command = TraceId::new();
+        let second_command = TraceId::new();
+        dm.pins.pin(&first_command, &[make_path("gc/pinned/file")]);
+        dm.pins.pin(&second_command, &[pinned.clone()]);
+
+        // Under budget, nothing happens.
+        dm.collect_garbage(40);
+        assert!(dm.pending_gc_cleaning().is_none());
+        assert_eq!(dm.io.take_log(), &[]);
+
+        // Over budget, the least recently accessed artifacts that aren't in use get evicted.
+        dm.collect_garbage(25);
+        dm.pending_gc_cleaning()
+            .context("Expected a GC")?
+            .await
+            .unshared_error()?;
+        assert_eq!(
+            dm.io.take_log(),
+            &[(Op::Clean, older.clone()), (Op::Clean, old.clone())]
+        );
+        assert!(!dm.is_path_materialized(&old));
+        assert!(!dm.is_path_materialized(&older));
+        assert!(dm.is_path_materialized(&active));
+        assert!(dm.is_path_materialized(&pinned));
+
+        // Artifacts that may be in use are never evicted, even if we can't get under budget.
+        dm.collect_garbage(5);
+        assert!(dm.pending_gc_cleaning().is_none());
+        assert_eq!(dm.io.take_log(), &[]);
+
+        // The artifact stays pinned until both commands finish.
+        dm.pins.release(&first_command);
+        dm.collect_garbage(5);
+        assert!(dm.pending_gc_cleaning().is_none());
+
+        dm.pins.release(&second_command);
+        dm.collect_garbage(5);
+        dm.pending_gc_cleaning()
+            .context("Expected a GC")?
+            .await
+            .unshared_error()?;
+        assert_eq!(dm.io.take_log(), &[(Op::Clean, pinned.clone())]);
+        assert!(dm.is_path_materialized(&active));
+
+        Ok(())
+    }
+
+    /// The declaration of an artifact downloaded from the CAS by an action that ran `ttl` ago.
+    fn cas_declaration(digest_config: DigestConfig, ttl: Duration) -> Option<Box<CasDeclaration>> {
+        let info = CasDownloadInfo::new_execution(
+            TrackedActionDigest::empty(digest_config.cas_digest_config()),
+            RemoteExecutorUseCase::buck2_default(),
+            Utc::now(),
+            ttl,
+        );
+        CasDeclaration::new(
+            ArtifactValue::file(digest_config.empty_file()).entry(),
+            &Arc::new(ArtifactMaterializationMethod::CasDownload {
+                info: Arc::new(info),
+            }),
+        )
+    }
+
+    #[tokio::test]
+    async fn test_gc_redeclares_active_cas_artifacts() -> anyhow::Result<()> {
+        let digest_config = DigestConfig::testing_default();
+
+        let (mut dm, mut channel) = make_processor(digest_config, Default::default());
+
+        let downloaded = make_path("gc/downloaded");
+        let expired = make_path("gc/expired");
+        let built = make_path("gc/built");
+
+        let now = Utc::now();
+        insert_materialized(
+            &mut dm,
+            &downloaded,
+            10,
+            now - Duration::days(1),
+            true,
+            cas_declaration(digest_config, Duration::days(1)),
+        );
+        insert_materialized(
+            &mut dm,
+            &expired,
+            10,
+            now - Duration::days(2),
+            true,
+            cas_declaration(digest_config, Duration::zero()),
+        );
+        insert_materialized(&mut dm, &built, 10, now - Duration::days(3), true, None);
+
+        // Only the artifact that the CAS still has can be evicted.
+        dm.collect_garbage(5);
+        dm.pending_gc_cleaning()
+            .context("Expected a GC")?
+            .await
+            .unshared_error()?;
+        assert!(dm.io.take_log().contains(&(Op::Clean, downloaded.clone())));
+        assert!(!dm.is_path_materialized(&downloaded));
+        assert!(dm.is_path_materialized(&expired));
+        assert!(dm.is_path_materialized(&built));
+
+        // It was declared again, so it's downloaded the next time it's needed.
+        let res = dm
+            .materialize_artifact(&downloaded, EventDispatcher::null())
+            .context("Expected a future")?
+            .await;
+        assert_matches!(res, Ok(()));
+        assert!(
+            dm.io
+                .take_log()
+                .contains(&(Op::Materialize, downloaded.clone()))
+        );
+
+        while let Ok(cmd) = channel.low_priority.try_recv() {
+            dm.process_one_low_priority_command(cmd);
+        }
+        assert!(dm.is_path_materialized(&downloaded));
+
+        // And it can be evicted again.
+        dm.collect_garbage(5);
+        dm.pending_gc_cleaning()
+            .context("Expected a GC")?
+            .await
+            .unshared_error()?;
+        assert!(!dm.is_path_materialized(&downloaded));
+
+        Ok(())
+    }
 }
@@ -0,0 +1,632 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+//! A local content-addressed store of files, shared by all the buck-outs of a user. Files
+//! downloaded from RE are added to it, and later materializations of the same content are served
+//! from it instead of being downloaded again.
+
+use std::fs;
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::SystemTime;
+
+use anyhow::Context;
+use buck2_common::file_ops::FileDigest;
+use buck2_common::file_ops::FileDigestConfig;
+use buck2_common::file_ops::FileMetadata;
+use buck2_core::fs::fs_util;
+use buck2_core::fs::paths::abs_norm_path::AbsNormPath;
+use buck2_core::fs::paths::abs_norm_path::AbsNormPathBuf;
+use buck2_core::fs::paths::forward_rel_path::ForwardRelativePath;
+use buck2_core::fs::project::ProjectRoot;
+use buck2_core::fs::project_rel_path::ProjectRelativePathBuf;
+use buck2_execute::digest_config::DigestConfig;
+use dupe::Dupe;
+use parking_lot::Mutex;
+
+pub struct LocalCasConfig {
+    /// Where the store lives. Pointing several repositories at the same directory lets them share
+    /// their outputs.
+    pub root: AbsNormPathBuf,
+    /// Hash blobs before using them, and discard the ones that don't match their digest.
+    pub verify: bool,
+    /// Hardlink blobs into buck-out when they can't be reflinked. Both the blob and the output are
+    /// made read-only, but that doesn't stop a process running as root from writing to one and
+    /// corrupting the other, so blobs are always verified when this is set.
+    pub hardlinks: bool,
+    /// Evict the least recently used blobs once the store grows past this many bytes.
+    pub max_bytes: Option<u64>,
+}
+
+/// How a file was put in place.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum LinkMethod {
+    Reflink,
+    Hardlink,
+    Copy,
+}
+
+pub(crate) struct LocalCas {
+    config: LocalCasConfig,
+    digest_config: DigestConfig,
+    /// Used to name temporary files, which are renamed into place once complete.
+    tmp_counter: AtomicU64,
+    /// Set when the store has a size budget.
+    gc: Option<Arc<LocalCasGc>>,
+}
+
+impl LocalCas {
+    pub(crate) fn new(config: LocalCasConfig, digest_config: DigestConfig) -> Self {
+        let gc = config.max_bytes.map(|max_bytes| {
+            let gc = Arc::new(LocalCasGc {
+                root: config.root.clone(),
+                max_bytes,
+                total_bytes: AtomicU64::new(0),
+                running: Mutex::new(None),
+            });
+            // Other daemons may have filled the store already, and this is how its size is found.
+            gc.start();
+            gc
+        });
+        Self {
+            config,
+            digest_config,
+            tmp_counter: AtomicU64::new(0),
+            gc,
+        }
+    }
+
+    /// Blobs are keyed by digest and executable bit, since hardlinks share their permissions.
+    fn blob_path(
+        &self,
+        digest: &FileDigest,
+        is_executable: bool,
+    ) -> anyhow::Result<AbsNormPathBuf> {
+        let raw = digest.raw_digest();
+        let hex = raw.to_string();
+        let path = format!(
+            "{}/{}/{}_{}{}",
+            raw.algorithm(),
+            &hex[..2],
+            hex,
+            digest.size(),
+            if is_executable { "_x" } else { "" },
+        );
+        Ok(self.config.root.join(ForwardRelativePath::new(&path)?))
+    }
+
+    fn should_verify(&self) -> bool {
+        self.config.verify || self.config.hardlinks
+    }
+
+    fn verify(&self, path: &AbsNormPath, digest: &FileDigest) -> anyhow::Result<bool> {
+        let actual = FileDigest::from_file_disk(
+            path,
+            FileDigestConfig::build(self.digest_config.cas_digest_config()),
+        )?;
+        Ok(actual == *digest)
+    }
+
+    /// Materialize `meta` at `dest` from the store. Returns false if the store doesn't have it.
+    fn materialize(&self, meta: &FileMetadata, dest: &AbsNormPath) -> anyhow::Result<bool> {
+        let blob = self.blob_path(meta.digest.data(), meta.is_executable)?;
+        if !fs_util::try_exists(&blob)? {
+            return Ok(false);
+        }
+
+        if self.should_verify() && !self.verify(&blob, meta.digest.data())? {
+            tracing::warn!(blob = %blob, "Discarding corrupted blob from the local CAS");
+            // Another process may have discarded it already.
+            fs_util::remove_file(&blob).ok();
+            return Ok(false);
+        }
+
+        if link_or_copy(&blob, dest, self.config.hardlinks)? != LinkMethod::Hardlink {
+            set_permissions(dest, meta.is_executable, false)?;
+        }
+
+        // The modification time of blobs tracks their last use, for garbage collection.
+        touch(&blob)?;
+
+        Ok(true)
+    }
+
+    /// Add `meta`, which was just materialized at `src`, to the store. Returns false if the store
+    /// already had it.
+    fn insert(&self, meta: &FileMetadata, src: &AbsNormPath) -> anyhow::Result<bool> {
+        let blob = self.blob_path(meta.digest.data(), meta.is_executable)?;
+        if fs_util::try_exists(&blob)? {
+            return Ok(false);
+        }
+
+        if self.should_verify() && !self.verify(src, meta.digest.data())? {
+            return Err(anyhow::anyhow!(
+                "`{}` does not match its digest `{}`",
+                src,
+                meta.digest
+            ));
+        }
+
+        let dir = blob.parent().context("Blob has no parent")?;
+        fs_util::create_dir_all(dir)?;
+
+        // Other processes may be using the store concurrently, so only ever rename complete
+        // blobs into place.
+        let tmp = dir.join(ForwardRelativePath::new(&format!(
+            "{}.tmp.{}.{}",
+            blob.file_name()
+                .context("Blob has no file name")?
+                .to_string_lossy(),
+            std::process::id(),
+            self.tmp_counter.fetch_add(1, Ordering::Relaxed),
+        ))?);
+
+        let res = link_or_copy(src, &tmp, self.config.hardlinks)
+            .and_then(|_| set_permissions(&tmp, meta.is_executable, true))
+            .and_then(|_| fs_util::rename(&tmp, &blob));
+
+        if res.is_err() {
+            fs_util::remove_file(&tmp).ok();
+        }
+
+        res.map(|()| true)
+    }
+
+    /// Materialize the `files` that are in the store. Returns the ones that aren't.
+    pub(crate) fn materialize_files(
+        &self,
+        fs: &ProjectRoot,
+        files: Vec<(ProjectRelativePathBuf, FileMetadata)>,
+    ) -> Vec<(ProjectRelativePathBuf, FileMetadata)> {
+        let mut missing = Vec::new();
+
+        for (path, meta) in files {
+            let dest = fs.resolve(&path);
+            match self.materialize(&meta, &dest) {
+                Ok(true) => {
+                    tracing::trace!(path = %path, digest = %meta.digest, "local CAS hit");
+                }
+                Ok(false) => missing.push((path, meta)),
+                Err(e) => {
+                    // The store is only a cache, so fall back to downloading.
+                    tracing::warn!("Error materializing `{}` from the local CAS: {:#}", path, e);
+                    fs_util::remove_file(&dest).ok();
+                    missing.push((path, meta));
+                }
+            }
+        }
+
+        missing
+    }
+
+    /// Add the `files` that were just materialized to the store.
+    pub(crate) fn insert_files(
+        &self,
+        fs: &ProjectRoot,
+        files: &[(ProjectRelativePathBuf, FileMetadata)],
+    ) {
+        let mut inserted_bytes = 0;
+        for (path, meta) in files {
+            match self.insert(meta, &fs.resolve(path)) {
+                Ok(true) => inserted_bytes += meta.digest.size(),
+                Ok(false) => {}
+                Err(e) => tracing::warn!("Error adding `{}` to the local CAS: {:#}", path, e),
+            }
+        }
+
+        if let Some(gc) = &self.gc {
+            if inserted_bytes > 0 {
+                gc.add(inserted_bytes);
+            }
+        }
+    }
+}
+
+/// Keeps the store within its size budget. Listing the store is expensive, so garbage collection
+/// runs on a background thread, and a running total of the store size decides when it's needed.
+struct LocalCasGc {
+    root: AbsNormPathBuf,
+    max_bytes: u64,
+    /// Approximate size of the store. Blobs added or evicted by other processes sharing the store
+    /// are only accounted for by the next garbage collection.
+    total_bytes: AtomicU64,
+    /// The last garbage collection that was started.
+    running: Mutex<Option<JoinHandle<()>>>,
+}
+
+impl LocalCasGc {
+    /// Record that `bytes` were added to the store, and collect garbage if it's over budget.
+    fn add(self: &Arc<Self>, bytes: u64) {
+        let total_bytes = self.total_bytes.fetch_add(bytes, Ordering::Relaxed) + bytes;
+        if total_bytes > self.max_bytes {
+            self.start();
+        }
+    }
+
+    /// Start a garbage collection, unless one is running already.
+    fn start(self: &Arc<Self>) {
+        let mut running = self.running.lock();
+        if running
+            .as_ref()
+            .map_or(false, |handle| !handle.is_finished())
+        {
+            return;
+        }
+
+        let this = self.dupe();
+        let res = std::thread::Builder::new()
+            .name("buck2-local-cas-gc".to_owned())
+            .spawn(move || {
+                if let Err(e) = this.collect_garbage() {
+                    tracing::warn!("Error collecting garbage in the local CAS: {:#}", e);
+                }
+            });
+        match res {
+            Ok(handle) => *running = Some(handle),
+            Err(e) => tracing::warn!("Error starting local CAS garbage collection: {:#}", e),
+        }
+    }
+
+    /// List the store to find its size, and evict the least recently used blobs if it's over
+    /// budget. Eviction leaves some headroom so that the next few inserts don't collect again.
+    fn collect_garbage(&self) -> anyhow::Result<()> {
+        // Blobs inserted while listing are counted twice if the listing sees them, which only
+        // makes the next garbage collection come a little early.
+        let previous_bytes = self.total_bytes.swap(0, Ordering::Relaxed);
+        let mut blobs = Vec::new();
+        if let Err(e) = list_blobs(&self.root, &mut blobs) {
+            self.total_bytes
+                .fetch_add(previous_bytes, Ordering::Relaxed);
+            return Err(e);
+        }
+
+        let mut total_bytes: u64 = blobs.iter().map(|(_, size, _)| size).sum();
+        if total_bytes > self.max_bytes {
+            let target_bytes = self.max_bytes - self.max_bytes / 10;
+            tracing::info!(
+                total_bytes = total_bytes,
+                max_bytes = self.max_bytes,
+                "Local CAS size budget exceeded, evicting blobs",
+            );
+
+            blobs.sort_by_key(|(modified, _, _)| *modified);
+            for (_, size, path) in blobs {
+                if total_bytes <= target_bytes {
+                    break;
+                }
+                // Another process may have evicted it already.
+                fs_util::remove_file(&path).ok();
+                total_bytes -= size;
+            }
+        }
+
+        self.total_bytes.fetch_add(total_bytes, Ordering::Relaxed);
+        Ok(())
+    }
+}
+
+/// Add the blobs under `dir` to `blobs`, with their modification time and size.
+fn list_blobs(
+    dir: &AbsNormPath,
+    blobs: &mut Vec<(SystemTime, u64, AbsNormPathBuf)>,
+) -> anyhow::Result<()> {
+    let entries = match fs_util::read_dir_if_exists(dir)? {
+        Some(entries) => entries,
+        None => return Ok(()),
+    };
+
+    for entry in entries {
+        let entry = entry?;
+        // Other processes may be adding or evicting blobs concurrently.
+        let metadata = match entry.metadata() {
+            Ok(metadata) => metadata,
+            Err(_) => continue,
+        };
+        if metadata.is_dir() {
+            list_blobs(&entry.path(), blobs)?;
+        } else if metadata.is_file() && !entry.file_name().to_string_lossy().contains(".tmp.") {
+            blobs.push((metadata.modified()?, metadata.len(), entry.path()));
+        }
+    }
+
+    Ok(())
+}
+
+/// Put a file with the contents of `src` at `dest`, by reflink if the filesystem supports it, by
+/// hardlink if allowed, and by copy otherwise.
+fn link_or_copy(
+    src: &AbsNormPath,
+    dest: &AbsNormPath,
+    hardlinks: bool,
+) -> anyhow::Result<LinkMethod> {
+    if reflink(src, dest)? {
+        return Ok(LinkMethod::Reflink);
+    }
+
+    // Hardlinks are only used where read-only files can still be deleted.
+    if hardlinks && cfg!(unix) {
+        match fs::hard_link(src.as_maybe_relativized(), dest.as_maybe_relativized()) {
+            Ok(()) => return Ok(LinkMethod::Hardlink),
+            // Typically, `src` and `dest` are on different filesystems.
+            Err(e) => tracing::debug!("Cannot hardlink `{}` to `{}`: {}", src, dest, e),
+        }
+    }
+
+    fs_util::copy(src, dest)?;
+    Ok(LinkMethod::Copy)
+}
+
+/// Clone `src` to a new file at `dest` with `FICLONE`. Returns false if the filesystem doesn't
+/// support it.
+#[cfg(target_os = "linux")]
+fn reflink(src: &AbsNormPath, dest: &AbsNormPath) -> anyhow::Result<bool> {
+    use std::os::unix::io::AsRawFd;
+
+    // `_IOW(0x94, 9, int)`, from `linux/fs.h`.
+    const FICLONE: libc::c_ulong = 0x40049409;
+
+    let src_file =
+        fs::File::open(src.as_maybe_relativized()).with_context(|| format!("open({})", src))?;
+    let dest_file = fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(dest.as_maybe_relativized())
+        .with_context(|| format!("create({})", dest))?;
+
+    // SAFETY: Both file descriptors are valid for the duration of the call.
+    let res = unsafe { libc::ioctl(dest_file.as_raw_fd(), FICLONE as _, src_file.as_raw_fd()) };
+    if res == 0 {
+        return Ok(true);
+    }
+
+    tracing::debug!(
+        "Cannot reflink `{}` to `{}`: {}",
+        src,
+        dest,
+        std::io::Error::last_os_error()
+    );
+    drop(dest_file);
+    fs_util::remove_file(dest)?;
+    Ok(false)
+}
+
+#[cfg(not(target_os = "linux"))]
+fn reflink(_src: &AbsNormPath, _dest: &AbsNormPath) -> anyhow::Result<bool> {
+    Ok(false)
+}
+
+/// Set the modification time of `path` to now.
+#[cfg(unix)]
+fn touch(path: &AbsNormPath) -> anyhow::Result<()> {
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+
+    let c_path = CString::new(path.as_os_str().as_bytes())?;
+    // SAFETY: `c_path` is a valid C string, and null `times` sets both times to now.
+    let res = unsafe { libc::utimensat(libc::AT_FDCWD, c_path.as_ptr(), std::ptr::null(), 0) };
+    if res != 0 {
+        return Err(std::io::Error::last_os_error()).with_context(|| format!("touch({})", path));
+    }
+    Ok(())
+}
+
+/// Blobs are only evicted in the order they were added.
+#[cfg(not(unix))]
+fn touch(_path: &AbsNormPath) -> anyhow::Result<()> {
+    Ok(())
+}
+
+fn set_permissions(path: &AbsNormPath, is_executable: bool, read_only: bool) -> anyhow::Result<()> {
+    #[cfg(unix)]
+    let perms = {
+        use std::os::unix::fs::PermissionsExt;
+
+        let mode = match (read_only, is_executable) {
+            (true, true) => 0o555,
+            (true, false) => 0o444,
+            (false, true) => 0o755,
+            (false, false) => 0o644,
+        };
+        fs::Permissions::from_mode(mode)
+    };
+
+    #[cfg(not(unix))]
+    let perms = {
+        let _ignore = is_executable;
+        let mut perms = fs_util::metadata(path)?.permissions();
+        perms.set_readonly(read_only);
+        perms
+    };
+
+    fs_util::set_permissions(path, perms)
+}
+
+#[cfg(test)]
+mod tests {
+    use buck2_common::file_ops::TrackedFileDigest;
+    use buck2_core::fs::project::ProjectRootTemp;
+
+    use super::*;
+
+    fn file(content: &[u8], is_executable: bool) -> FileMetadata {
+        FileMetadata {
+            digest: TrackedFileDigest::from_content(
+                content,
+                DigestConfig::testing_default().cas_digest_config(),
+            ),
+            is_executable,
+        }
+    }
+
+    #[test]
+    fn test_insert_and_materialize() -> anyhow::Result<()> {
+        let temp = ProjectRootTemp::new()?;
+        let fs = temp.path();
+        let cas = LocalCas::new(
+            LocalCasConfig {
+                root: fs.root().join(ForwardRelativePath::new("cas")?),
+                verify: true,
+                hardlinks: true,
+                max_bytes: None,
+            },
+            DigestConfig::testing_default(),
+        );
+
+        let src = ProjectRelativePathBuf::unchecked_new("src".to_owned());
+        let dest = ProjectRelativePathBuf::unchecked_new("dest".to_owned());
+        let meta = file(b"hello", false);
+        fs.write_file(&src, "hello", false)?;
+
+        // Not in the store yet.
+        let missing = cas.materialize_files(fs, vec![(dest.clone(), meta.dupe())]);
+        assert_eq!(missing, vec![(dest.clone(), meta.dupe())]);
+
+        cas.insert_files(fs, &[(src, meta.dupe())]);
+
+        let missing = cas.materialize_files(fs, vec![(dest.clone(), meta.dupe())]);
+        assert_eq!(missing, vec![]);
+        assert_eq!(fs_util::read_to_string(fs.resolve(&dest))?, "hello");
+
+        // The executable bit is part of the key.
+        let dest_x = ProjectRelativePathBuf::unchecked_new("dest_x".to_owned());
+        let meta_x = file(b"hello", true);
+        let missing = cas.materialize_files(fs, vec![(dest_x.clone(), meta_x.dupe())]);
+        assert_eq!(missing, vec![(dest_x, meta_x)]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_verify() -> anyhow::Result<()> {
+        let temp = ProjectRootTemp::new()?;
+        let fs = temp.path();
+        let cas = LocalCas::new(
+            LocalCasConfig {
+                root: fs.root().join(ForwardRelativePath::new("cas")?),
+                verify: true,
+                hardlinks: false,
+                max_bytes: None,
+            },
+            DigestConfig::testing_default(),
+        );
+
+        let src = ProjectRelativePathBuf::unchecked_new("src".to_owned());
+        let dest = ProjectRelativePathBuf::unchecked_new("dest".to_owned());
+        let meta = file(b"hello", false);
+        fs.write_file(&src, "hello", false)?;
+        cas.insert_files(fs, &[(src, meta.dupe())]);
+
+        // Corrupt the blob.
+        let blob = cas.blob_path(meta.digest.data(), false)?;
+        set_permissions(&blob, false, false)?;
+        fs_util::write(&blob, "corrupted")?;
+
+        let missing = cas.materialize_files(fs, vec![(dest.clone(), meta.dupe())]);
+        assert_eq!(missing, vec![(dest, meta)]);
+        assert!(!fs_util::try_exists(&blob)?);
+
+        // Content that doesn't match its digest is never added.
+        let other = ProjectRelativePathBuf::unchecked_new("other".to_owned());
+        fs.write_file(&other, "not hello", false)?;
+        cas.insert_files(fs, &[(other, file(b"hello", false))]);
+        assert!(!fs_util::try_exists(&blob)?);
+
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    fn set_modified(path: &AbsNormPath, secs: i64) -> anyhow::Result<()> {
+        use std::ffi::CString;
+        use std::os::unix::ffi::OsStrExt;
+
+        let c_path = CString::new(path.as_os_str().as_bytes())?;
+        let time = libc::timespec {
+            tv_sec: secs as _,
+            tv_nsec: 0,
+        };
+        let times = [time, time];
+        // SAFETY: `c_path` is a valid C string, and `times` has two elements.
+        let res = unsafe { libc::utimensat(libc::AT_FDCWD, c_path.as_ptr(), times.as_ptr(), 0) };
+        assert_eq!(res, 0, "{}", std::io::Error::last_os_error());
+        Ok(())
+    }
+
+    /// Wait for the garbage collection started last to finish.
+    fn wait_for_gc(cas: &LocalCas) {
+        let handle = cas.gc.as_ref().unwrap().running.lock().take();
+        if let Some(handle) = handle {
+            handle.join().unwrap();
+        }
+    }
+
+    fn total_bytes(cas: &LocalCas) -> u64 {
+        cas.gc.as_ref().unwrap().total_bytes.load(Ordering::Relaxed)
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_collect_garbage() -> anyhow::Result<()> {
+        let temp = ProjectRootTemp::new()?;
+        let fs = temp.path();
+        let cas = LocalCas::new(
+            LocalCasConfig {
+                root: fs.root().join(ForwardRelativePath::new("cas")?),
+                verify: false,
+                hardlinks: false,
+                max_bytes: Some(12),
+            },
+            DigestConfig::testing_default(),
+        );
+
+        // The store is listed once on startup.
+        wait_for_gc(&cas);
+        assert_eq!(total_bytes(&cas), 0);
+
+        let insert = |name: &str, content: &str| -> anyhow::Result<AbsNormPathBuf> {
+            let path = ProjectRelativePathBuf::unchecked_new(name.to_owned());
+            let meta = file(content.as_bytes(), false);
+            fs.write_file(&path, content, false)?;
+            cas.insert_files(fs, &[(path, meta.dupe())]);
+            cas.blob_path(meta.digest.data(), false)
+        };
+
+        let a = insert("a", "aaaaa")?;
+        let b = insert("b", "bbbbb")?;
+        set_modified(&a, 1000)?;
+        set_modified(&b, 2000)?;
+
+        // Within budget, nothing is collected.
+        assert!(cas.gc.as_ref().unwrap().running.lock().is_none());
+        assert_eq!(total_bytes(&cas), 10);
+
+        // Over budget, the least recently used blob is evicted.
+        let c = insert("c", "ccccc")?;
+        wait_for_gc(&cas);
+        assert_eq!(total_bytes(&cas), 10);
+        assert!(!fs_util::try_exists(&a)?);
+        assert!(fs_util::try_exists(&b)?);
+        assert!(fs_util::try_exists(&c)?);
+
+        // Materializing a blob marks it as used.
+        set_modified(&c, 3000)?;
+        let dest = ProjectRelativePathBuf::unchecked_new("dest".to_owned());
+        let missing = cas.materialize_files(fs, vec![(dest, file(b"bbbbb", false))]);
+        assert_eq!(missing, vec![]);
+
+        let d = insert("d", "ddddd")?;
+        wait_for_gc(&cas);
+        assert!(fs_util::try_exists(&b)?);
+        assert!(!fs_util::try_exists(&c)?);
+        assert!(fs_util::try_exists(&d)?);
+
+        Ok(())
+    }
+}
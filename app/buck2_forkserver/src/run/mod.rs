@@ -43,6 +43,8 @@ pub enum GatherOutputStatus {
     Finished {
         exit_code: i32,
         execution_stats: Option<buck2_data::CommandExecutionStats>,
+        /// Paths of undeclared inputs the command tried to access, if it ran in a sandbox.
+        undeclared_inputs: Vec<String>,
     },
     TimedOut(Duration),
     Cancelled,
@@ -58,6 +60,7 @@ impl From<DecodedStatus> for GatherOutputStatus {
             } => Self::Finished {
                 exit_code,
                 execution_stats,
+                undeclared_inputs: Vec::new(),
             },
             DecodedStatus::SpawnFailed(v) => Self::SpawnFailed(v),
         }
@@ -9,6 +9,8 @@
 
 mod command;
 mod launch;
+#[cfg(target_os = "linux")]
+mod sandbox;
 mod service;
 
 pub use command::run_forkserver;
@@ -0,0 +1,944 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+//! Sandboxing for commands spawned by the forkserver.
+//!
+//! The sandbox relies on user and mount namespaces, so it does not require any privileges. Once
+//! the child has forked (and before it execs), it:
+//!
+//! - Enters a new user namespace (mapping the current user to itself) and a new mount namespace
+//!   (and optionally a new network namespace).
+//! - Bind mounts the sandbox root onto a stash directory, so that it remains reachable.
+//! - Mounts a tmpfs over the sandbox root, hiding everything under it.
+//! - Recreates (via bind mounts from the stash) the paths the command is allowed to see.
+//! - Replaces the other entries of the directories it had to create with empty placeholders, so
+//!   that the contents of undeclared inputs are unreachable, and watches those directories with
+//!   inotify, so that we find out which placeholders the command opens.
+//! - Unmounts the stash.
+//!
+//! Everything the child does after forking must be async-signal-safe, so all the work that
+//! requires allocating (validating paths, deciding what to mount where) is done before spawning,
+//! in [`Sandbox::new`]. The inotify instance is created by the parent and inherited by the child,
+//! so the parent can read the events once the command exits, in [`UndeclaredInputsTracker`].
+//!
+//! Placeholders only exist in the directories we create: paths under an undeclared directory are
+//! missing, and inotify does not report lookups, so accessing them fails without being reported.
+
+use std::collections::BTreeSet;
+use std::collections::HashSet;
+use std::ffi::CString;
+use std::ffi::OsStr;
+use std::io;
+use std::mem;
+use std::os::fd::AsRawFd;
+use std::os::fd::FromRawFd;
+use std::os::fd::OwnedFd;
+use std::os::fd::RawFd;
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::process::CommandExt;
+use std::path::Component;
+use std::path::Path;
+use std::path::PathBuf;
+use std::process::Command;
+use std::ptr;
+
+use anyhow::Context as _;
+use buck2_core::fs::paths::abs_norm_path::AbsNormPath;
+use buck2_forkserver_proto::sandbox_path::Kind;
+use buck2_forkserver_proto::SandboxConfig;
+use futures::stream::Stream;
+use futures::stream::StreamExt;
+use thiserror::Error;
+
+use crate::run::CommandEvent;
+use crate::run::GatherOutputStatus;
+
+#[derive(Debug, Error)]
+enum SandboxError {
+    #[error("Sandbox path is not a normalized relative path: `{}`", .0.display())]
+    InvalidPath(PathBuf),
+
+    #[error("Sandbox root must be an absolute path: `{}`", .0.display())]
+    InvalidRoot(PathBuf),
+
+    #[error(
+        "Sandbox stash `{}` must not be under the sandbox root `{}`",
+        .stash.display(),
+        .root.display()
+    )]
+    StashUnderRoot { stash: PathBuf, root: PathBuf },
+
+    #[error("The sandboxed command accessed too many placeholders to track its undeclared inputs")]
+    TooManyAccesses,
+}
+
+/// Configure `cmd` so that it runs in the sandbox described by `config`. `cwd` is the working
+/// directory the command should have once the sandbox is set up, and `stash` an empty directory
+/// outside of the sandbox root that we can use as a mount point.
+pub(crate) fn apply_sandbox(
+    cmd: &mut Command,
+    config: SandboxConfig,
+    cwd: Option<&OsStr>,
+    stash: &AbsNormPath,
+) -> anyhow::Result<UndeclaredInputsTracker> {
+    // SAFETY: This has no preconditions, and we take ownership of the descriptor it returns.
+    let inotify = unsafe {
+        OwnedFd::from_raw_fd(
+            check(libc::inotify_init1(libc::IN_NONBLOCK | libc::IN_CLOEXEC))
+                .context("Error creating inotify instance")?,
+        )
+    };
+
+    let (sandbox, tracker) = Sandbox::new(config, cwd, stash, inotify)?;
+    // SAFETY: `Sandbox::enter` does not allocate.
+    unsafe {
+        cmd.pre_exec(move || sandbox.enter());
+    }
+    Ok(tracker)
+}
+
+/// Fill in the undeclared inputs that `tracker` observed in the exit event of `stream`.
+pub(crate) fn report_undeclared_inputs(
+    stream: impl Stream<Item = anyhow::Result<CommandEvent>>,
+    tracker: Option<UndeclaredInputsTracker>,
+) -> impl Stream<Item = anyhow::Result<CommandEvent>> {
+    stream.map(move |event| match (event, &tracker) {
+        (
+            Ok(CommandEvent::Exit(GatherOutputStatus::Finished {
+                exit_code,
+                execution_stats,
+                ..
+            })),
+            Some(tracker),
+        ) => Ok(CommandEvent::Exit(GatherOutputStatus::Finished {
+            exit_code,
+            execution_stats,
+            undeclared_inputs: tracker.undeclared_inputs()?,
+        })),
+        (event, _) => event,
+    })
+}
+
+/// Finds out which placeholders a sandboxed command opened. Those are undeclared inputs that the
+/// command tried to access.
+pub(crate) struct UndeclaredInputsTracker {
+    inotify: OwnedFd,
+    /// The directories we watch, relative to the sandbox root, in the order we added watches for
+    /// them.
+    dirs: Vec<PathBuf>,
+    placeholders: HashSet<PathBuf>,
+}
+
+impl UndeclaredInputsTracker {
+    /// The paths of the placeholders that were opened, relative to the sandbox root and sorted.
+    /// This should be called once the command exited.
+    pub(crate) fn undeclared_inputs(&self) -> anyhow::Result<Vec<String>> {
+        let mut accessed = BTreeSet::new();
+        let mut buf = vec![0u8; 64 * 1024];
+
+        loop {
+            // SAFETY: `buf` is valid for writes of its length.
+            let len =
+                unsafe { libc::read(self.inotify.as_raw_fd(), buf.as_mut_ptr().cast(), buf.len()) };
+            if len < 0 {
+                let err = io::Error::last_os_error();
+                if err.kind() == io::ErrorKind::WouldBlock {
+                    break;
+                }
+                return Err(err).context("Error reading inotify events");
+            }
+            if len == 0 {
+                break;
+            }
+
+            let mut events = &buf[..len as usize];
+            while !events.is_empty() {
+                let header = mem::size_of::<libc::inotify_event>();
+                // SAFETY: The kernel only returns whole events.
+                let event: libc::inotify_event =
+                    unsafe { ptr::read_unaligned(events.as_ptr().cast()) };
+                let name = &events[header..header + event.len as usize];
+                events = &events[header + event.len as usize..];
+
+                if event.mask & libc::IN_Q_OVERFLOW != 0 {
+                    return Err(SandboxError::TooManyAccesses.into());
+                }
+
+                // The name is padded with NULs. It's empty when the watched directory itself was
+                // opened, which is fine.
+                let name = name.split(|b| *b == 0).next().unwrap_or_default();
+                if name.is_empty() {
+                    continue;
+                }
+
+                let dir = match usize::try_from(event.wd - 1)
+                    .ok()
+                    .and_then(|i| self.dirs.get(i))
+                {
+                    Some(dir) => dir,
+                    None => continue,
+                };
+
+                let path = dir.join(OsStr::from_bytes(name));
+                if self.placeholders.contains(&path) {
+                    accessed.insert(path.to_string_lossy().into_owned());
+                }
+            }
+        }
+
+        Ok(accessed.into_iter().collect())
+    }
+}
+
+/// An operation to perform to populate the sandbox. Paths are relative to the sandbox root.
+#[derive(Debug, PartialEq, Eq)]
+enum Op {
+    Mkdir(PathBuf),
+    /// Create an empty file, to serve as a mount point.
+    Touch(PathBuf),
+    Symlink {
+        target: PathBuf,
+        path: PathBuf,
+    },
+    Bind {
+        path: PathBuf,
+        read_only: bool,
+    },
+    /// Create an empty, read-only file or directory, in place of an undeclared input.
+    Placeholder {
+        path: PathBuf,
+        is_dir: bool,
+    },
+}
+
+impl Op {
+    fn path(&self) -> &Path {
+        match self {
+            Op::Mkdir(path)
+            | Op::Touch(path)
+            | Op::Symlink { path, .. }
+            | Op::Bind { path, .. }
+            | Op::Placeholder { path, .. } => path,
+        }
+    }
+}
+
+/// What currently exists at a path we want to expose in the sandbox.
+#[derive(Debug)]
+enum Existing {
+    Dir,
+    File,
+    Symlink(PathBuf),
+    Missing,
+}
+
+impl Existing {
+    fn probe(path: &Path) -> anyhow::Result<Self> {
+        let metadata = match std::fs::symlink_metadata(path) {
+            Ok(metadata) => metadata,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Self::Missing),
+            Err(e) => {
+                return Err(e).with_context(|| format!("Error accessing `{}`", path.display()));
+            }
+        };
+
+        let file_type = metadata.file_type();
+        Ok(if file_type.is_symlink() {
+            Self::Symlink(
+                std::fs::read_link(path)
+                    .with_context(|| format!("Error reading link `{}`", path.display()))?,
+            )
+        } else if file_type.is_dir() {
+            Self::Dir
+        } else {
+            Self::File
+        })
+    }
+}
+
+fn validate_relative_path(path: &Path) -> anyhow::Result<()> {
+    if path
+        .components()
+        .all(|c| matches!(c, Component::Normal(..)))
+    {
+        Ok(())
+    } else {
+        Err(SandboxError::InvalidPath(path.to_owned()).into())
+    }
+}
+
+/// Decide how to populate the sandbox. `entries` must be sorted by path and free of duplicates.
+///
+/// Paths that are covered by a directory we already bind mounted are visible already, so they
+/// don't need any work, unless they need to be writable and the mount covering them isn't.
+fn plan(entries: Vec<(PathBuf, Kind, Existing)>) -> Vec<Op> {
+    let mut ops = Vec::new();
+    let mut created = HashSet::new();
+    let mut mounted: Vec<(PathBuf, Kind)> = Vec::new();
+
+    for (path, kind, existing) in entries {
+        let covering = mounted
+            .iter()
+            .rev()
+            .find(|(m, _)| path.starts_with(m))
+            .map(|(_, k)| *k);
+
+        if let Some(covering) = covering {
+            if kind != Kind::Writable || covering == Kind::Writable {
+                continue;
+            }
+
+            // The mount point exists since the path is visible through the covering mount.
+            match existing {
+                Existing::Dir => {
+                    ops.push(Op::Bind {
+                        path: path.clone(),
+                        read_only: false,
+                    });
+                    mounted.push((path, kind));
+                }
+                Existing::File => ops.push(Op::Bind {
+                    path,
+                    read_only: false,
+                }),
+                Existing::Symlink(..) | Existing::Missing => {}
+            }
+            continue;
+        }
+
+        let mut ancestors = path
+            .ancestors()
+            .skip(1)
+            .filter(|a| !a.as_os_str().is_empty())
+            .collect::<Vec<_>>();
+        ancestors.reverse();
+        for ancestor in ancestors {
+            if created.insert(ancestor.to_owned()) {
+                ops.push(Op::Mkdir(ancestor.to_owned()));
+            }
+        }
+
+        let read_only = kind == Kind::ReadOnly;
+
+        match (kind, existing) {
+            (Kind::EmptyDirectory, _) => {
+                if created.insert(path.clone()) {
+                    ops.push(Op::Mkdir(path));
+                }
+            }
+            (_, Existing::Missing) => {}
+            (_, Existing::Symlink(target)) => ops.push(Op::Symlink { target, path }),
+            (_, Existing::Dir) => {
+                if created.insert(path.clone()) {
+                    ops.push(Op::Mkdir(path.clone()));
+                }
+                ops.push(Op::Bind {
+                    path: path.clone(),
+                    read_only,
+                });
+                mounted.push((path, kind));
+            }
+            (_, Existing::File) => {
+                ops.push(Op::Touch(path.clone()));
+                ops.push(Op::Bind { path, read_only });
+            }
+        }
+    }
+
+    ops
+}
+
+/// The sandbox root (which is a tmpfs), and the directories that `ops` create in it rather than
+/// bind mount. Their entries that `ops` don't populate are undeclared inputs.
+fn created_directories(ops: &[Op]) -> Vec<PathBuf> {
+    let mounted = ops
+        .iter()
+        .filter_map(|op| match op {
+            Op::Bind { path, .. } => Some(path),
+            _ => None,
+        })
+        .collect::<HashSet<_>>();
+
+    let created = ops.iter().filter_map(|op| match op {
+        Op::Mkdir(path) if !mounted.contains(path) => Some(path.clone()),
+        _ => None,
+    });
+
+    std::iter::once(PathBuf::new()).chain(created).collect()
+}
+
+/// Plan placeholders for the entries of `dirs` that `ops` don't populate. `list` returns the
+/// names of the entries of a directory (relative to the sandbox root), and whether each of them
+/// is a directory.
+fn plan_placeholders(
+    ops: &[Op],
+    dirs: &[PathBuf],
+    mut list: impl FnMut(&Path) -> anyhow::Result<Vec<(PathBuf, bool)>>,
+) -> anyhow::Result<Vec<Op>> {
+    let populated = ops.iter().map(|op| op.path()).collect::<HashSet<_>>();
+    let mut placeholders = Vec::new();
+
+    for dir in dirs {
+        for (name, is_dir) in list(dir)? {
+            let path = dir.join(name);
+            if !populated.contains(path.as_path()) {
+                placeholders.push(Op::Placeholder { path, is_dir });
+            }
+        }
+    }
+
+    Ok(placeholders)
+}
+
+/// List a directory for [`plan_placeholders`], in a stable order. Directories that don't exist
+/// have no entries.
+fn list_directory(path: &Path) -> anyhow::Result<Vec<(PathBuf, bool)>> {
+    let entries = match std::fs::read_dir(path) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => {
+            return Err(e).with_context(|| format!("Error listing `{}`", path.display()));
+        }
+    };
+
+    let mut res = Vec::new();
+    for entry in entries {
+        let entry = entry.with_context(|| format!("Error listing `{}`", path.display()))?;
+        let file_type = entry
+            .file_type()
+            .with_context(|| format!("Error accessing `{}`", entry.path().display()))?;
+        res.push((PathBuf::from(entry.file_name()), file_type.is_dir()));
+    }
+    res.sort();
+
+    Ok(res)
+}
+
+/// Collect the entries to expose in the sandbox, sorted by path. When the same path is requested
+/// more than once, the most permissive kind wins.
+fn collect_entries(config: &SandboxConfig) -> anyhow::Result<Vec<(PathBuf, Kind)>> {
+    let mut entries = Vec::with_capacity(config.paths.len());
+
+    for entry in &config.paths {
+        let path = Path::new(OsStr::from_bytes(&entry.path)).to_owned();
+        validate_relative_path(&path)?;
+        let kind = Kind::from_i32(entry.kind)
+            .with_context(|| format!("Invalid sandbox path kind: `{}`", entry.kind))?;
+        entries.push((path, kind));
+    }
+
+    fn rank(kind: Kind) -> u8 {
+        match kind {
+            Kind::Writable => 0,
+            Kind::ReadOnly => 1,
+            Kind::EmptyDirectory => 2,
+        }
+    }
+
+    entries.sort_by(|(p1, k1), (p2, k2)| p1.cmp(p2).then(rank(*k1).cmp(&rank(*k2))));
+    entries.dedup_by(|(p1, _), (p2, _)| p1 == p2);
+
+    Ok(entries)
+}
+
+/// An [`Op`], resolved to absolute paths that the child can use without allocating.
+enum ResolvedOp {
+    Mkdir(CString),
+    Touch(CString),
+    Symlink {
+        target: CString,
+        path: CString,
+    },
+    Bind {
+        src: CString,
+        dst: CString,
+        read_only: bool,
+    },
+    Placeholder {
+        path: CString,
+        is_dir: bool,
+    },
+}
+
+struct Sandbox {
+    root: CString,
+    stash: CString,
+    cwd: Option<CString>,
+    allow_network: bool,
+    uid_map: Vec<u8>,
+    gid_map: Vec<u8>,
+    ops: Vec<ResolvedOp>,
+    /// Owned by the [`UndeclaredInputsTracker`].
+    inotify: RawFd,
+    /// The directories to watch.
+    watched: Vec<CString>,
+}
+
+fn cstr(path: &Path) -> anyhow::Result<CString> {
+    Ok(CString::new(path.as_os_str().as_bytes())?)
+}
+
+impl Sandbox {
+    fn new(
+        config: SandboxConfig,
+        cwd: Option<&OsStr>,
+        stash: &AbsNormPath,
+        inotify: OwnedFd,
+    ) -> anyhow::Result<(Self, UndeclaredInputsTracker)> {
+        let root = Path::new(OsStr::from_bytes(&config.root));
+        if !root.is_absolute() {
+            return Err(SandboxError::InvalidRoot(root.to_owned()).into());
+        }
+        let stash = stash.as_path();
+        if stash.starts_with(root) {
+            return Err(SandboxError::StashUnderRoot {
+                stash: stash.to_owned(),
+                root: root.to_owned(),
+            }
+            .into());
+        }
+
+        let entries = collect_entries(&config)?
+            .into_iter()
+            .map(|(path, kind)| {
+                let existing = Existing::probe(&root.join(&path))?;
+                anyhow::Ok((path, kind, existing))
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+
+        let mut ops = plan(entries);
+        let dirs = created_directories(&ops);
+        let placeholder_ops =
+            plan_placeholders(&ops, &dirs, |dir| list_directory(&root.join(dir)))?;
+        let placeholders = placeholder_ops
+            .iter()
+            .map(|op| op.path().to_owned())
+            .collect();
+        ops.extend(placeholder_ops);
+
+        let ops = ops
+            .into_iter()
+            .map(|op| {
+                anyhow::Ok(match op {
+                    Op::Mkdir(path) => ResolvedOp::Mkdir(cstr(&root.join(path))?),
+                    Op::Touch(path) => ResolvedOp::Touch(cstr(&root.join(path))?),
+                    Op::Symlink { target, path } => ResolvedOp::Symlink {
+                        target: cstr(&target)?,
+                        path: cstr(&root.join(path))?,
+                    },
+                    Op::Bind { path, read_only } => ResolvedOp::Bind {
+                        src: cstr(&stash.join(&path))?,
+                        dst: cstr(&root.join(&path))?,
+                        read_only,
+                    },
+                    Op::Placeholder { path, is_dir } => ResolvedOp::Placeholder {
+                        path: cstr(&root.join(path))?,
+                        is_dir,
+                    },
+                })
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+
+        let watched = dirs
+            .iter()
+            .map(|dir| cstr(&root.join(dir)))
+            .collect::<anyhow::Result<Vec<_>>>()?;
+
+        // The child has the same credentials as we do, so we can compute those now.
+        // SAFETY: Those never fail.
+        let (uid, gid) = unsafe { (libc::getuid(), libc::getgid()) };
+
+        let sandbox = Self {
+            root: cstr(root)?,
+            stash: cstr(stash)?,
+            cwd: cwd.map(|cwd| cstr(Path::new(cwd))).transpose()?,
+            allow_network: config.allow_network,
+            uid_map: format!("{} {} 1\n", uid, uid).into_bytes(),
+            gid_map: format!("{} {} 1\n", gid, gid).into_bytes(),
+            ops,
+            inotify: inotify.as_raw_fd(),
+            watched,
+        };
+
+        let tracker = UndeclaredInputsTracker {
+            inotify,
+            dirs,
+            placeholders,
+        };
+
+        Ok((sandbox, tracker))
+    }
+
+    /// Set up the sandbox. This runs in the child after fork, so it must not allocate.
+    fn enter(&self) -> io::Result<()> {
+        unsafe {
+            let mut flags = libc::CLONE_NEWUSER | libc::CLONE_NEWNS;
+            if !self.allow_network {
+                flags |= libc::CLONE_NEWNET;
+            }
+            check(libc::unshare(flags))?;
+
+            write_file(b"/proc/self/setgroups\0", b"deny")?;
+            write_file(b"/proc/self/uid_map\0", &self.uid_map)?;
+            write_file(b"/proc/self/gid_map\0", &self.gid_map)?;
+
+            // Make sure nothing we do here propagates back to the parent namespace.
+            check(libc::mount(
+                ptr::null(),
+                b"/\0".as_ptr().cast(),
+                ptr::null(),
+                libc::MS_REC | libc::MS_PRIVATE,
+                ptr::null(),
+            ))?;
+
+            check(libc::mount(
+                self.root.as_ptr(),
+                self.stash.as_ptr(),
+                ptr::null(),
+                libc::MS_BIND | libc::MS_REC,
+                ptr::null(),
+            ))?;
+
+            check(libc::mount(
+                b"tmpfs\0".as_ptr().cast(),
+                self.root.as_ptr(),
+                b"tmpfs\0".as_ptr().cast(),
+                libc::MS_NOSUID | libc::MS_NODEV,
+                b"mode=0755\0".as_ptr().cast(),
+            ))?;
+
+            for op in &self.ops {
+                match op {
+                    ResolvedOp::Mkdir(path) => {
+                        if libc::mkdir(path.as_ptr(), 0o755) != 0 {
+                            let err = io::Error::last_os_error();
+                            if err.raw_os_error() != Some(libc::EEXIST) {
+                                return Err(err);
+                            }
+                        }
+                    }
+                    ResolvedOp::Touch(path) => {
+                        let fd = check(libc::open(
+                            path.as_ptr(),
+                            libc::O_RDONLY | libc::O_CREAT | libc::O_CLOEXEC,
+                            0o644 as libc::c_uint,
+                        ))?;
+                        libc::close(fd);
+                    }
+                    ResolvedOp::Symlink { target, path } => {
+                        check(libc::symlink(target.as_ptr(), path.as_ptr()))?;
+                    }
+                    ResolvedOp::Bind {
+                        src,
+                        dst,
+                        read_only,
+                    } => {
+                        check(libc::mount(
+                            src.as_ptr(),
+                            dst.as_ptr(),
+                            ptr::null(),
+                            libc::MS_BIND | libc::MS_REC,
+                            ptr::null(),
+                        ))?;
+                        if *read_only {
+                            remount_read_only(dst)?;
+                        }
+                    }
+                    ResolvedOp::Placeholder { path, is_dir } => {
+                        if *is_dir {
+                            check(libc::mkdir(path.as_ptr(), 0o555))?;
+                        } else {
+                            let fd = check(libc::open(
+                                path.as_ptr(),
+                                libc::O_RDONLY | libc::O_CREAT | libc::O_CLOEXEC,
+                                0o444 as libc::c_uint,
+                            ))?;
+                            libc::close(fd);
+                        }
+                    }
+                }
+            }
+
+            // Watch the directories we created, now that they are populated, so that we find out
+            // when placeholders in them get opened. A new inotify instance allocates watch
+            // descriptors sequentially from 1, which is how the tracker maps them back to
+            // directories, so check that this holds.
+            for (i, dir) in self.watched.iter().enumerate() {
+                let wd = check(libc::inotify_add_watch(
+                    self.inotify,
+                    dir.as_ptr(),
+                    libc::IN_OPEN | libc::IN_ONLYDIR,
+                ))?;
+                if wd as usize != i + 1 {
+                    return Err(io::ErrorKind::InvalidData.into());
+                }
+            }
+
+            check(libc::umount2(self.stash.as_ptr(), libc::MNT_DETACH))?;
+
+            // Our working directory was set before we got here, so it still points at the
+            // directory we just hid. Enter it again to get its sandboxed version.
+            if let Some(cwd) = &self.cwd {
+                check(libc::chdir(cwd.as_ptr()))?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn check(ret: libc::c_int) -> io::Result<libc::c_int> {
+    if ret < 0 {
+        Err(io::Error::last_os_error())
+    } else {
+        Ok(ret)
+    }
+}
+
+/// Write `data` to the file at `path`, which must be NUL-terminated.
+unsafe fn write_file(path: &[u8], data: &[u8]) -> io::Result<()> {
+    let fd = check(libc::open(
+        path.as_ptr().cast(),
+        libc::O_WRONLY | libc::O_CLOEXEC,
+    ))?;
+    let res = libc::write(fd, data.as_ptr().cast(), data.len());
+    let err = io::Error::last_os_error();
+    libc::close(fd);
+    if res < 0 {
+        return Err(err);
+    }
+    if res as usize != data.len() {
+        return Err(io::ErrorKind::WriteZero.into());
+    }
+    Ok(())
+}
+
+/// Remount a bind mount as read-only. In a user namespace, this must preserve the flags the
+/// mount inherited from the parent namespace, since those are locked.
+unsafe fn remount_read_only(path: &CString) -> io::Result<()> {
+    const LOCKED_FLAGS: &[(libc::c_ulong, libc::c_ulong)] = &[
+        (libc::ST_NOSUID, libc::MS_NOSUID),
+        (libc::ST_NODEV, libc::MS_NODEV),
+        (libc::ST_NOEXEC, libc::MS_NOEXEC),
+        (libc::ST_NOATIME, libc::MS_NOATIME),
+        (libc::ST_NODIRATIME, libc::MS_NODIRATIME),
+        (libc::ST_RELATIME, libc::MS_RELATIME),
+    ];
+
+    let mut stat: libc::statvfs = std::mem::zeroed();
+    check(libc::statvfs(path.as_ptr(), &mut stat))?;
+
+    let mut flags = libc::MS_BIND | libc::MS_REMOUNT | libc::MS_RDONLY;
+    for (st_flag, ms_flag) in LOCKED_FLAGS {
+        if stat.f_flag & st_flag != 0 {
+            flags |= ms_flag;
+        }
+    }
+
+    check(libc::mount(
+        ptr::null(),
+        path.as_ptr(),
+        ptr::null(),
+        flags,
+        ptr::null(),
+    ))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn p(s: &str) -> PathBuf {
+        PathBuf::from(s)
+    }
+
+    #[test]
+    fn test_plan() {
+        let ops = plan(vec![
+            (p("a/b"), Kind::ReadOnly, Existing::Dir),
+            (p("a/b/c"), Kind::ReadOnly, Existing::File),
+            (p("a/b/d"), Kind::Writable, Existing::Dir),
+            (p("cell"), Kind::EmptyDirectory, Existing::Dir),
+            (p("cell/file"), Kind::ReadOnly, Existing::File),
+            (p("cell/link"), Kind::ReadOnly, Existing::Symlink(p("file"))),
+            (p("cell/missing"), Kind::ReadOnly, Existing::Missing),
+            (p("out"), Kind::Writable, Existing::Dir),
+        ]);
+
+        assert_eq!(
+            ops,
+            vec![
+                Op::Mkdir(p("a")),
+                Op::Mkdir(p("a/b")),
+                Op::Bind {
+                    path: p("a/b"),
+                    read_only: true
+                },
+                Op::Bind {
+                    path: p("a/b/d"),
+                    read_only: false
+                },
+                Op::Mkdir(p("cell")),
+                Op::Touch(p("cell/file")),
+                Op::Bind {
+                    path: p("cell/file"),
+                    read_only: true
+                },
+                Op::Symlink {
+                    target: p("file"),
+                    path: p("cell/link")
+                },
+                Op::Mkdir(p("out")),
+                Op::Bind {
+                    path: p("out"),
+                    read_only: false
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_plan_placeholders() -> anyhow::Result<()> {
+        let ops = plan(vec![
+            (p("a/b"), Kind::ReadOnly, Existing::Dir),
+            (p("cell"), Kind::EmptyDirectory, Existing::Dir),
+            (p("cell/file"), Kind::ReadOnly, Existing::File),
+        ]);
+
+        let dirs = created_directories(&ops);
+        assert_eq!(dirs, vec![p(""), p("a"), p("cell")]);
+
+        let placeholders = plan_placeholders(&ops, &dirs, |dir| {
+            Ok(match dir.to_str().unwrap() {
+                "" => vec![(p("a"), true), (p("cell"), true), (p("root"), false)],
+                "a" => vec![(p("b"), true), (p("c"), false)],
+                "cell" => vec![(p("dir"), true), (p("file"), false)],
+                _ => unreachable!(),
+            })
+        })?;
+
+        assert_eq!(
+            placeholders,
+            vec![
+                Op::Placeholder {
+                    path: p("root"),
+                    is_dir: false
+                },
+                Op::Placeholder {
+                    path: p("a/c"),
+                    is_dir: false
+                },
+                Op::Placeholder {
+                    path: p("cell/dir"),
+                    is_dir: true
+                },
+            ]
+        );
+
+        Ok(())
+    }
+
+    /// Run a command in a sandbox exposing `src/input` read-only and `out` writable.
+    #[test]
+    fn test_sandboxed_command() -> anyhow::Result<()> {
+        let root = tempfile::tempdir()?;
+        let stash = tempfile::tempdir()?;
+
+        std::fs::create_dir_all(root.path().join("src/dir"))?;
+        std::fs::create_dir_all(root.path().join("out"))?;
+        std::fs::write(root.path().join("src/input"), "input")?;
+        std::fs::write(root.path().join("src/hidden"), "hidden")?;
+        std::fs::write(root.path().join("src/dir/nested"), "nested")?;
+
+        let path = |path: &str, kind: Kind| buck2_forkserver_proto::SandboxPath {
+            path: path.as_bytes().to_vec(),
+            kind: kind as i32,
+        };
+        let config = SandboxConfig {
+            root: root.path().as_os_str().as_bytes().to_vec(),
+            paths: vec![
+                path("src/input", Kind::ReadOnly),
+                path("out", Kind::Writable),
+            ],
+            allow_network: false,
+        };
+
+        let mut cmd = Command::new("/bin/sh");
+        cmd.arg("-c").arg(
+            "cat src/input > out/output && \
+             test -z \"$(cat src/hidden)\" && \
+             test ! -e src/dir/nested && \
+             ! touch src/input 2>/dev/null",
+        );
+        cmd.current_dir(root.path());
+        let tracker = apply_sandbox(
+            &mut cmd,
+            config,
+            Some(root.path().as_os_str()),
+            AbsNormPath::new(stash.path())?,
+        )?;
+
+        let status = match cmd.status() {
+            Ok(status) => status,
+            // Unprivileged user namespaces are disabled on this host, so there is nothing to test.
+            Err(e) if e.raw_os_error() == Some(libc::EPERM) => return Ok(()),
+            Err(e) => return Err(e.into()),
+        };
+        assert!(status.success(), "{}", status);
+
+        // The command read the placeholder for `src/hidden`. It only looked up `src/dir/nested`,
+        // which isn't reported.
+        assert_eq!(tracker.undeclared_inputs()?, vec!["src/hidden".to_owned()]);
+
+        // Writes to writable paths are visible outside of the sandbox, and nothing else changed.
+        assert_eq!(
+            std::fs::read_to_string(root.path().join("out/output"))?,
+            "input"
+        );
+        assert_eq!(
+            std::fs::read_to_string(root.path().join("src/hidden"))?,
+            "hidden"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_collect_entries() -> anyhow::Result<()> {
+        let path = |path: &str, kind: Kind| buck2_forkserver_proto::SandboxPath {
+            path: path.as_bytes().to_vec(),
+            kind: kind as i32,
+        };
+
+        let config = SandboxConfig {
+            root: b"/root".to_vec(),
+            paths: vec![
+                path("b", Kind::ReadOnly),
+                path("a", Kind::ReadOnly),
+                path("b", Kind::Writable),
+            ],
+            allow_network: false,
+        };
+
+        assert_eq!(
+            collect_entries(&config)?,
+            vec![(p("a"), Kind::ReadOnly), (p("b"), Kind::Writable)]
+        );
+
+        let config = SandboxConfig {
+            root: b"/root".to_vec(),
+            paths: vec![path("../a", Kind::ReadOnly)],
+            allow_network: false,
+        };
+        assert!(collect_entries(&config).is_err());
+
+        Ok(())
+    }
+}
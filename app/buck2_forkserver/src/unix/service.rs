@@ -57,6 +57,10 @@ pub struct UnixForkserverService {
 
     /// State for Miniperf.
     miniperf: Option<MiniperfContainer>,
+
+    /// Empty directory used as a mount point when setting up sandboxes. It must be outside of the
+    /// project (which sandboxes hide), so it's a temporary directory owned by this forkserver.
+    sandbox_stash: tempfile::TempDir,
 }
 
 impl UnixForkserverService {
@@ -66,9 +70,15 @@ impl UnixForkserverService {
     ) -> anyhow::Result<Self> {
         let miniperf = MiniperfContainer::new(state_dir)?;
 
+        let sandbox_stash = tempfile::Builder::new()
+            .prefix("buck2-sandbox-")
+            .tempdir()
+            .context("Error creating sandbox stash directory")?;
+
         Ok(Self {
             log_reload_handle,
             miniperf,
+            sandbox_stash,
         })
     }
 }
@@ -109,6 +119,7 @@ impl Forkserver for UnixForkserverService {
                 cwd,
                 timeout,
                 enable_miniperf,
+                sandbox,
             } = msg;
 
             let exe = OsStr::from_bytes(&exe);
@@ -135,6 +146,27 @@ impl Forkserver for UnixForkserverService {
             }
             cmd.args(argv);
 
+            #[cfg(target_os = "linux")]
+            let mut undeclared_inputs_tracker = None;
+
+            if let Some(sandbox) = sandbox {
+                #[cfg(target_os = "linux")]
+                {
+                    undeclared_inputs_tracker = Some(super::sandbox::apply_sandbox(
+                        &mut cmd,
+                        sandbox,
+                        cwd,
+                        AbsNormPath::new(self.sandbox_stash.path())?,
+                    )?);
+                }
+
+                #[cfg(not(target_os = "linux"))]
+                {
+                    let _unused = (sandbox, &self.sandbox_stash);
+                    return Err(anyhow::anyhow!("Sandboxing is only supported on Linux"));
+                }
+            }
+
             {
                 use buck2_forkserver_proto::env_directive::Data;
 
@@ -177,6 +209,10 @@ impl Forkserver for UnixForkserverService {
                 .right_stream(),
             };
 
+            #[cfg(target_os = "linux")]
+            let stream =
+                super::sandbox::report_undeclared_inputs(stream, undeclared_inputs_tracker);
+
             let stream = encode_event_stream(stream);
             Ok(Box::pin(stream) as _)
         })
@@ -9,31 +9,105 @@
 
 //! Implementation of the cli and query_* attr query language.
 
+use std::sync::Arc;
+
+use anyhow::Context;
 use buck2_query_parser::parse_expr;
 use buck2_query_parser::spanned::Spanned;
 use buck2_query_parser::Expr;
+use buck2_query_parser::SpannedExpr;
 use futures::FutureExt;
 use gazebo::prelude::*;
 use gazebo::variants::VariantName;
 
 use crate::__derive_refs::indexmap::IndexSet;
 use crate::query::environment::QueryEnvironment;
+use crate::query::environment::QueryTarget;
 use crate::query::syntax::simple::eval::error::QueryError;
 use crate::query::syntax::simple::eval::file_set::FileNode;
 use crate::query::syntax::simple::eval::file_set::FileSet;
+use crate::query::syntax::simple::eval::macros::QueryMacro;
+use crate::query::syntax::simple::eval::macros::QueryMacros;
 use crate::query::syntax::simple::eval::set::TargetSet;
 use crate::query::syntax::simple::eval::values::QueryEvaluationValue;
 use crate::query::syntax::simple::eval::values::QueryResult;
 use crate::query::syntax::simple::eval::values::QueryValue;
 use crate::query::syntax::simple::functions::QueryFunctions;
+
+/// A value bound by `let` or by a macro parameter. Scopes are linked lists so that nested
+/// evaluators can share their parent's bindings.
+struct Binding<T: QueryTarget> {
+    name: String,
+    value: QueryValue<T>,
+    parent: Option<Arc<Binding<T>>>,
+}
+
 pub struct QueryEvaluator<'e, Env: QueryEnvironment> {
     env: &'e Env,
     functions: &'e dyn QueryFunctions<Env = Env>,
+    macros: Option<&'e QueryMacros>,
+    scope: Option<Arc<Binding<Env::Target>>>,
+    /// Macros whose bodies we're currently evaluating, used to reject recursive macros.
+    expanding_macros: Vec<String>,
 }
 
 impl<'e, Env: QueryEnvironment> QueryEvaluator<'e, Env> {
     pub fn new(env: &'e Env, functions: &'e dyn QueryFunctions<Env = Env>) -> Self {
-        Self { env, functions }
+        Self {
+            env,
+            functions,
+            macros: None,
+            scope: None,
+            expanding_macros: Vec::new(),
+        }
+    }
+
+    pub fn with_macros(self, macros: &'e QueryMacros) -> Self {
+        Self {
+            macros: Some(macros),
+            ..self
+        }
+    }
+
+    /// Returns an evaluator using different functions, but which otherwise sees the same
+    /// bindings and macros as this one (for example, to evaluate an expression captured by a
+    /// function in a function-specific context).
+    pub fn with_functions<'f>(
+        &'f self,
+        functions: &'f dyn QueryFunctions<Env = Env>,
+    ) -> QueryEvaluator<'f, Env> {
+        QueryEvaluator {
+            env: self.env,
+            functions,
+            macros: self.macros,
+            scope: self.scope.clone(),
+            expanding_macros: self.expanding_macros.clone(),
+        }
+    }
+
+    fn with_binding(&self, name: &str, value: QueryValue<Env::Target>) -> Self {
+        Self {
+            env: self.env,
+            functions: self.functions,
+            macros: self.macros,
+            scope: Some(Arc::new(Binding {
+                name: name.to_owned(),
+                value,
+                parent: self.scope.clone(),
+            })),
+            expanding_macros: self.expanding_macros.clone(),
+        }
+    }
+
+    fn lookup(&self, name: &str) -> Option<&QueryValue<Env::Target>> {
+        let mut scope = self.scope.as_deref();
+        while let Some(binding) = scope {
+            if binding.name == name {
+                return Some(&binding.value);
+            }
+            scope = binding.parent.as_deref();
+        }
+        None
     }
 
     pub fn env(&self) -> &Env {
@@ -61,9 +135,12 @@ impl<'e, Env: QueryEnvironment> QueryEvaluator<'e, Env> {
                 args,
             } => match self.functions.get(function_name) {
                 Some(func) => func.invoke(self, args).await,
-                None => Err(QueryError::UnknownFunction(
-                    (*function_name.fragment()).to_owned(),
-                )),
+                None => match self.macros.and_then(|m| m.get(function_name)) {
+                    Some(query_macro) => self.eval_macro(function_name, query_macro, args).await,
+                    None => Err(QueryError::UnknownFunction(
+                        (*function_name.fragment()).to_owned(),
+                    )),
+                },
             },
             Expr::BinaryOpSequence(left, exprs) => {
                 let (left, rights) = futures::future::try_join(
@@ -105,6 +182,65 @@ impl<'e, Env: QueryEnvironment> QueryEvaluator<'e, Env> {
 
                 Ok(files.into())
             }
+            Expr::Let { name, value, body } => {
+                // The bound value is evaluated once, however many times the body refers to it.
+                let value = self.eval(value).await?.value;
+                let evaluator = self.with_binding(name.fragment(), value);
+                Ok(evaluator.eval(body).await?.value)
+            }
+            Expr::Variable(name) => match self.lookup(name.fragment()) {
+                Some(value) => Ok(value.clone()),
+                None => Err(QueryError::UnboundVariable((*name.fragment()).to_owned())),
+            },
+        }
+    }
+
+    async fn eval_macro(
+        &self,
+        name: &str,
+        query_macro: &QueryMacro,
+        args: &[SpannedExpr<'_>],
+    ) -> Result<QueryValue<Env::Target>, QueryError> {
+        if args.len() != query_macro.params().len() {
+            return Err(QueryError::MacroArgCount {
+                name: name.to_owned(),
+                expected: query_macro.params().len(),
+                actual: args.len(),
+            });
+        }
+        if self.expanding_macros.iter().any(|m| m == name) {
+            return Err(QueryError::RecursiveMacro(name.to_owned()));
+        }
+
+        // Arguments are evaluated in the caller's scope, and only once.
+        let args = futures::future::try_join_all(args.iter().map(|arg| self.eval(arg))).await?;
+
+        // The body only sees the macro's parameters, not the caller's bindings.
+        let mut scope = None;
+        for (param, arg) in query_macro.params().iter().zip(args) {
+            scope = Some(Arc::new(Binding {
+                name: param.clone(),
+                value: arg.value,
+                parent: scope,
+            }));
+        }
+        let mut expanding_macros = self.expanding_macros.clone();
+        expanding_macros.push(name.to_owned());
+        let evaluator = QueryEvaluator {
+            env: self.env,
+            functions: self.functions,
+            macros: self.macros,
+            scope,
+            expanding_macros,
+        };
+
+        let body = parse_expr(query_macro.body())
+            .with_context(|| format!("Error parsing macro `{}`", name))?;
+        match evaluator.eval(&body).await {
+            Ok(v) => Ok(v.value),
+            Err(e) => Err(QueryError::convert_error(e, query_macro.body())
+                .context(format!("Error evaluating macro `{}`", name))
+                .into()),
         }
     }
 
@@ -13,6 +13,7 @@ use buck2_query_parser::parse_expr;
 use buck2_query_parser::placeholder::QUERY_PERCENT_S_PLACEHOLDER;
 use starlark_map::small_set::SmallSet;
 
+use crate::query::syntax::simple::eval::macros::QueryMacros;
 use crate::query::syntax::simple::eval::values::QueryResultExt;
 use crate::query::syntax::simple::functions::QueryFunctions;
 use crate::query::syntax::simple::functions::QueryFunctionsVisitLiterals;
@@ -22,6 +23,7 @@ use crate::query::syntax::simple::functions::QueryLiteralVisitor;
 /// Adds those that are found to `result` set.
 pub fn extract_target_literals<F: QueryFunctions>(
     functions: &F,
+    macros: Option<&QueryMacros>,
     query: &str,
     result: &mut SmallSet<String>,
 ) -> anyhow::Result<()> {
@@ -39,7 +41,7 @@ pub fn extract_target_literals<F: QueryFunctions>(
     }
     let mut visitor = LiteralExtractor { literals: result };
     functions
-        .visit_literals(&mut visitor, &parsed)
+        .visit_literals_with_macros(&mut visitor, macros, &parsed)
         .into_anyhow(query)?;
     Ok(())
 }
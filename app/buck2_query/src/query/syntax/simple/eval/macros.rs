@@ -0,0 +1,124 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+//! User-defined query macros.
+
+use std::collections::HashMap;
+
+use anyhow::Context;
+use buck2_query_parser::parse_macro_definitions;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+enum QueryMacroError {
+    #[error("Macro `{0}` has more than one parameter named `{1}`")]
+    DuplicateParam(String, String),
+}
+
+/// A named, parameterized query expression. Within the body, parameters are referred to as `$name`.
+#[derive(Debug)]
+pub struct QueryMacro {
+    params: Vec<String>,
+    body: String,
+}
+
+impl QueryMacro {
+    pub fn params(&self) -> &[String] {
+        &self.params
+    }
+
+    pub fn body(&self) -> &str {
+        &self.body
+    }
+}
+
+/// The set of macros available to a query. Macros are called like functions, and builtin functions
+/// take precedence over macros with the same name.
+#[derive(Debug, Default)]
+pub struct QueryMacros {
+    macros: HashMap<String, QueryMacro>,
+}
+
+impl QueryMacros {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds the macros defined in `definitions` (see `parse_macro_definitions` for the syntax).
+    /// A definition replaces any earlier macro of the same name.
+    pub fn add_definitions(&mut self, definitions: &str) -> anyhow::Result<()> {
+        for definition in parse_macro_definitions(definitions)? {
+            let name = (*definition.name.fragment()).to_owned();
+            let mut params: Vec<String> = Vec::with_capacity(definition.params.len());
+            for param in &definition.params {
+                if params.iter().any(|p| p == *param.fragment()) {
+                    return Err(QueryMacroError::DuplicateParam(
+                        name,
+                        (*param.fragment()).to_owned(),
+                    )
+                    .into());
+                }
+                params.push((*param.fragment()).to_owned());
+            }
+            let body = definitions[definition.body.position.clone()].to_owned();
+            self.macros.insert(name, QueryMacro { params, body });
+        }
+        Ok(())
+    }
+
+    /// Adds macros from buckconfig-style entries, where the key is the macro's signature and the
+    /// value its body, e.g. `direct_deps(x) = deps($x, 1)`.
+    pub fn add_config_entries<'a>(
+        &mut self,
+        entries: impl IntoIterator<Item = (&'a str, &'a str)>,
+    ) -> anyhow::Result<()> {
+        for (signature, body) in entries {
+            self.add_definitions(&format!("{} = {}", signature, body))
+                .with_context(|| format!("Invalid query macro `{}`", signature))?;
+        }
+        Ok(())
+    }
+
+    pub fn get(&self, name: &str) -> Option<&QueryMacro> {
+        self.macros.get(name)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.macros.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_add_definitions() -> anyhow::Result<()> {
+        let mut macros = QueryMacros::new();
+        macros.add_definitions("direct_deps(x) = deps($x, 1)\nlibs(x) = kind(library, $x)")?;
+        macros.add_config_entries([(
+            "direct_deps(target)",
+            "deps($target, 1, first_order_deps())",
+        )])?;
+
+        let direct_deps = macros.get("direct_deps").unwrap();
+        assert_eq!(direct_deps.params(), &["target".to_owned()]);
+        assert_eq!(direct_deps.body(), "deps($target, 1, first_order_deps())");
+        assert_eq!(macros.get("libs").unwrap().body(), "kind(library, $x)");
+        assert!(macros.get("deps").is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn test_duplicate_param() {
+        let mut macros = QueryMacros::new();
+        assert!(macros.add_definitions("f(x, x) = $x").is_err());
+        assert!(macros.add_config_entries([("f", "$x")]).is_err());
+    }
+}
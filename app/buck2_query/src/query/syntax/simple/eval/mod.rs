@@ -12,6 +12,7 @@ pub mod evaluator;
 pub mod file_set;
 pub mod label_indexed;
 pub mod literals;
+pub mod macros;
 pub mod multi_query;
 pub mod set;
 pub mod tests;
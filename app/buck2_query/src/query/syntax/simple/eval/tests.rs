@@ -23,13 +23,17 @@ use derive_more::Display;
 use dupe::Dupe;
 use serde::Serialize;
 use serde::Serializer;
+use starlark_map::small_set::SmallSet;
 
 use crate::query::environment::QueryEnvironment;
 use crate::query::environment::QueryTarget;
 use crate::query::syntax::simple::eval::error::QueryError;
 use crate::query::syntax::simple::eval::evaluator::QueryEvaluator;
 use crate::query::syntax::simple::eval::file_set::FileSet;
+use crate::query::syntax::simple::eval::literals::extract_target_literals;
+use crate::query::syntax::simple::eval::macros::QueryMacros;
 use crate::query::syntax::simple::eval::set::TargetSet;
+use crate::query::syntax::simple::eval::values::QueryValue;
 use crate::query::syntax::simple::functions::DefaultQueryFunctionsModule;
 use crate::query::traversal::AsyncTraversalDelegate;
 
@@ -185,3 +189,85 @@ pub async fn test_missing_arg() -> anyhow::Result<()> {
     }
     Ok(())
 }
+
+async fn eval_with_macros(input: &str, macros: &QueryMacros) -> anyhow::Result<QueryValue<Target>> {
+    let parsed = parse_expr(input)?;
+    let functions = DefaultQueryFunctionsModule::new();
+    match QueryEvaluator::new(&Env, &functions)
+        .with_macros(macros)
+        .eval(&parsed)
+        .await
+    {
+        Ok(v) => Ok(v.value),
+        Err(e) => Err(QueryError::convert_error(e, input)),
+    }
+}
+
+#[tokio::test]
+async fn test_let() -> anyhow::Result<()> {
+    let macros = QueryMacros::new();
+    assert_eq!(
+        eval_with_macros("let x = foo in let y = $x in $y", &macros).await?,
+        QueryValue::String("foo".to_owned())
+    );
+    // Inner bindings shadow outer ones.
+    assert_eq!(
+        eval_with_macros("let x = 1 in let x = 2 in $x", &macros).await?,
+        QueryValue::Integer(2)
+    );
+
+    let err = eval_with_macros("let x = foo in $y", &macros)
+        .await
+        .unwrap_err();
+    assert!(format!("{:#}", err).contains("unbound variable `$y`"));
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_macros() -> anyhow::Result<()> {
+    let mut macros = QueryMacros::new();
+    macros.add_definitions("id(x) = $x; second(x, y) = id($y); rec(x) = rec($x); leak() = $z")?;
+
+    assert_eq!(
+        eval_with_macros("second(a, let z = 3 in $z)", &macros).await?,
+        QueryValue::Integer(3)
+    );
+
+    for (query, expected) in [
+        ("id(a, b)", "macro `id` expects 1 args, got 2"),
+        ("rec(a)", "macro `rec` is recursive"),
+        // Macro bodies don't see the caller's bindings.
+        ("let z = 1 in leak()", "unbound variable `$z`"),
+    ] {
+        let err = eval_with_macros(query, &macros).await.unwrap_err();
+        let msg = format!("{:#}", err);
+        assert!(
+            msg.contains(expected),
+            "expected `{}` in error for `{}`, got `{}`",
+            expected,
+            query,
+            msg
+        );
+    }
+    Ok(())
+}
+
+#[test]
+fn test_extract_literals_through_bindings_and_macros() -> anyhow::Result<()> {
+    let mut macros = QueryMacros::new();
+    macros.add_definitions("direct_deps(x) = deps($x, 1)")?;
+    let functions = DefaultQueryFunctionsModule::<Env>::new();
+
+    let mut literals = SmallSet::new();
+    extract_target_literals(
+        &functions,
+        Some(&macros),
+        "let r = '.*_test' in let t = //foo:bar in filter($r, deps($t) + direct_deps(//baz:qux))",
+        &mut literals,
+    )?;
+    assert_eq!(
+        literals.into_iter().collect::<Vec<_>>(),
+        vec!["//foo:bar".to_owned(), "//baz:qux".to_owned()]
+    );
+    Ok(())
+}
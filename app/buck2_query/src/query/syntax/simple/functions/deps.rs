@@ -72,8 +72,7 @@ pub(crate) struct DepsFunction<Env: QueryEnvironment> {
 impl<Env: QueryEnvironment> DepsFunction<Env> {
     pub(crate) async fn invoke_deps(
         &self,
-        env: &Env,
-        functions: &dyn QueryFunctions<Env = Env>,
+        evaluator: &QueryEvaluator<'_, Env>,
         targets: &TargetSet<Env::Target>,
         depth: Option<i32>,
         captured_expr: Option<&CapturedExpr<'_>>,
@@ -81,8 +80,7 @@ impl<Env: QueryEnvironment> DepsFunction<Env> {
         let filter = match captured_expr {
             Some(expr) => {
                 struct Filter<'a, Env: QueryEnvironment> {
-                    inner_env: &'a Env,
-                    functions: &'a dyn QueryFunctions<Env = Env>,
+                    evaluator: &'a QueryEvaluator<'a, Env>,
                     expr: &'a CapturedExpr<'a>,
                 }
 
@@ -90,10 +88,11 @@ impl<Env: QueryEnvironment> DepsFunction<Env> {
                 impl<'a, T: QueryTarget, Env: QueryEnvironment<Target = T>> TraversalFilter<T> for Filter<'a, Env> {
                     async fn get_children(&self, target: &T) -> anyhow::Result<TargetSet<T>> {
                         let augmented_functions = AugmentedQueryFunctions::augment(
-                            self.functions,
+                            self.evaluator.functions(),
                             Box::new(DepsContextFunctions { target }),
                         );
-                        let evaluator = QueryEvaluator::new(self.inner_env, &augmented_functions);
+                        // The filter sees the same `let` bindings and macros as the `deps` call.
+                        let evaluator = self.evaluator.with_functions(&augmented_functions);
                         match evaluator.eval_parsed_query(self.expr.expr).await {
                             Ok(v) => match v.value {
                                 QueryEvaluationValue::TargetSet(v) => Ok(v),
@@ -108,11 +107,7 @@ impl<Env: QueryEnvironment> DepsFunction<Env> {
                     }
                 }
 
-                Some(Filter {
-                    inner_env: env,
-                    functions,
-                    expr,
-                })
+                Some(Filter { evaluator, expr })
             }
             None => None,
         };
@@ -121,6 +116,6 @@ impl<Env: QueryEnvironment> DepsFunction<Env> {
             .as_ref()
             .map(|v| v as &dyn TraversalFilter<Env::Target>);
 
-        env.deps(targets, depth, filter_ref).await
+        evaluator.env().deps(targets, depth, filter_ref).await
     }
 }
@@ -12,9 +12,11 @@ use std::fmt::Debug;
 use std::marker::PhantomData;
 
 use allocative::Allocative;
+use anyhow::Context;
 use async_trait::async_trait;
 use buck2_core::configuration::compatibility::MaybeCompatible;
 use buck2_query_derive::query_module;
+use buck2_query_parser::parse_expr;
 use buck2_query_parser::spanned::Spanned;
 use buck2_query_parser::BinaryOp;
 use buck2_query_parser::Expr;
@@ -24,6 +26,8 @@ use crate::query::environment::QueryEnvironment;
 use crate::query::syntax::simple::eval::error::QueryError;
 use crate::query::syntax::simple::eval::evaluator::QueryEvaluator;
 use crate::query::syntax::simple::eval::file_set::FileSet;
+use crate::query::syntax::simple::eval::macros::QueryMacro;
+use crate::query::syntax::simple::eval::macros::QueryMacros;
 use crate::query::syntax::simple::eval::set::TargetSet;
 use crate::query::syntax::simple::eval::set::TargetSetExt;
 use crate::query::syntax::simple::eval::values::QueryResult;
@@ -62,18 +66,57 @@ pub trait QueryFunctionsVisitLiterals: Debug + Send + Sync {
         &self,
         visitor: &mut dyn QueryLiteralVisitor,
         expr: &Spanned<Expr>,
+    ) -> QueryResult<()> {
+        self.visit_literals_with_macros(visitor, None, expr)
+    }
+
+    /// Like `visit_literals`, but also visits the literals in the bodies of any macros called.
+    fn visit_literals_with_macros(
+        &self,
+        visitor: &mut dyn QueryLiteralVisitor,
+        macros: Option<&QueryMacros>,
+        expr: &Spanned<Expr>,
     ) -> QueryResult<()>;
 }
 
+/// State for visiting literals: the variables in scope, and the macros being expanded.
+struct VisitLiteralsScope {
+    /// For each variable in scope, the literal bound to it, if it was bound to a literal.
+    /// Later entries shadow earlier ones.
+    variables: Vec<(String, Option<String>)>,
+    expanding_macros: Vec<String>,
+}
+
+impl VisitLiteralsScope {
+    fn lookup(&self, name: &str) -> Result<Option<&str>, QueryError> {
+        match self.variables.iter().rev().find(|(n, _)| n == name) {
+            Some((_, literal)) => Ok(literal.as_deref()),
+            None => Err(QueryError::UnboundVariable(name.to_owned())),
+        }
+    }
+
+    /// The literal an expression evaluates to, if it is a literal or a variable bound to one.
+    fn literal_value(&self, expr: &Expr) -> Result<Option<String>, QueryError> {
+        match expr {
+            Expr::String(val) => Ok(Some((*val).to_owned())),
+            Expr::Variable(name) => Ok(self.lookup(name.fragment())?.map(str::to_owned)),
+            _ => Ok(None),
+        }
+    }
+}
+
 impl<F: QueryFunctions> QueryFunctionsVisitLiterals for F {
-    fn visit_literals(
+    fn visit_literals_with_macros(
         &self,
         visitor: &mut dyn QueryLiteralVisitor,
+        macros: Option<&QueryMacros>,
         expr: &Spanned<Expr>,
     ) -> QueryResult<()> {
         fn visit_literals_recurse<F: QueryFunctions>(
             this: &F,
             visitor: &mut dyn QueryLiteralVisitor,
+            macros: Option<&QueryMacros>,
+            scope: &mut VisitLiteralsScope,
             expr: &Expr,
         ) -> Result<(), QueryError> {
             match expr {
@@ -86,6 +129,8 @@ impl<F: QueryFunctions> QueryFunctionsVisitLiterals for F {
                             visit_literals_item(
                                 this,
                                 visitor,
+                                macros,
+                                scope,
                                 arg,
                                 matches!(
                                     func.arg_type(i)?,
@@ -97,15 +142,26 @@ impl<F: QueryFunctions> QueryFunctionsVisitLiterals for F {
                         }
                         Ok(())
                     }
-                    None => Err(QueryError::UnknownFunction(
-                        (*function_name.fragment()).to_owned(),
-                    )),
+                    None => match macros.and_then(|m| m.get(function_name)) {
+                        Some(query_macro) => visit_macro_literals(
+                            this,
+                            visitor,
+                            macros,
+                            scope,
+                            function_name,
+                            query_macro,
+                            args,
+                        ),
+                        None => Err(QueryError::UnknownFunction(
+                            (*function_name.fragment()).to_owned(),
+                        )),
+                    },
                 },
                 Expr::BinaryOpSequence(left, exprs) => {
-                    visit_literals_item(this, visitor, left, true)?;
+                    visit_literals_item(this, visitor, macros, scope, left, true)?;
                     // All binary ops are on targetsets currently.
                     for (_, right) in exprs {
-                        visit_literals_item(this, visitor, right, true)?;
+                        visit_literals_item(this, visitor, macros, scope, right, true)?;
                     }
                     Ok(())
                 }
@@ -116,7 +172,20 @@ impl<F: QueryFunctions> QueryFunctionsVisitLiterals for F {
                     Ok(())
                 }
                 Expr::FileSet(_args) => Ok(()),
-                Expr::String(..) | Expr::Integer(..) => {
+                Expr::Let { name, value, body } => {
+                    // We don't know yet how the value will be used, so literals are only
+                    // visited where the variable is used as a target expression.
+                    visit_literals_item(this, visitor, macros, scope, value, false)?;
+                    let literal = scope.literal_value(&value.value)?;
+                    scope
+                        .variables
+                        .push(((*name.fragment()).to_owned(), literal));
+                    let res = visit_literals_item(this, visitor, macros, scope, body, true);
+                    scope.variables.pop();
+                    res?;
+                    Ok(())
+                }
+                Expr::String(..) | Expr::Integer(..) | Expr::Variable(..) => {
                     panic!(
                         "This shouldn't be called with literals, they should be handled in the caller"
                     )
@@ -124,9 +193,53 @@ impl<F: QueryFunctions> QueryFunctionsVisitLiterals for F {
             }
         }
 
+        fn visit_macro_literals<F: QueryFunctions>(
+            this: &F,
+            visitor: &mut dyn QueryLiteralVisitor,
+            macros: Option<&QueryMacros>,
+            scope: &mut VisitLiteralsScope,
+            name: &str,
+            query_macro: &QueryMacro,
+            args: &[Spanned<Expr>],
+        ) -> Result<(), QueryError> {
+            if args.len() != query_macro.params().len() {
+                return Err(QueryError::MacroArgCount {
+                    name: name.to_owned(),
+                    expected: query_macro.params().len(),
+                    actual: args.len(),
+                });
+            }
+            if scope.expanding_macros.iter().any(|m| m == name) {
+                return Err(QueryError::RecursiveMacro(name.to_owned()));
+            }
+
+            let mut variables = Vec::with_capacity(args.len());
+            for (param, arg) in query_macro.params().iter().zip(args) {
+                visit_literals_item(this, visitor, macros, scope, arg, false)?;
+                variables.push((param.clone(), scope.literal_value(&arg.value)?));
+            }
+
+            let body = parse_expr(query_macro.body())
+                .with_context(|| format!("Error parsing macro `{}`", name))?;
+            let mut expanding_macros = scope.expanding_macros.clone();
+            expanding_macros.push(name.to_owned());
+            let mut macro_scope = VisitLiteralsScope {
+                variables,
+                expanding_macros,
+            };
+            match visit_literals_item(this, visitor, macros, &mut macro_scope, &body, true) {
+                Ok(_) => Ok(()),
+                Err(e) => Err(QueryError::convert_error(e, query_macro.body())
+                    .context(format!("Error evaluating macro `{}`", name))
+                    .into()),
+            }
+        }
+
         fn visit_literals_item<F: QueryFunctions>(
             this: &F,
             visitor: &mut dyn QueryLiteralVisitor,
+            macros: Option<&QueryMacros>,
+            scope: &mut VisitLiteralsScope,
             expr: &Spanned<Expr>,
             is_target_expr: bool,
         ) -> QueryResult<()> {
@@ -137,16 +250,26 @@ impl<F: QueryFunctions> QueryFunctionsVisitLiterals for F {
                             visitor.target_pattern(val)?;
                         }
                     }
+                    Expr::Variable(name) => {
+                        let literal = scope.lookup(name.fragment())?;
+                        if let (true, Some(literal)) = (is_target_expr, literal) {
+                            visitor.target_pattern(literal)?;
+                        }
+                    }
                     Expr::Integer(..) => {
                         // ignored
                     }
-                    _ => visit_literals_recurse(this, visitor, value)?,
+                    _ => visit_literals_recurse(this, visitor, macros, scope, value)?,
                 }
                 Ok(())
             })
         }
 
-        visit_literals_item(self, visitor, expr, true)
+        let mut scope = VisitLiteralsScope {
+            variables: Vec::new(),
+            expanding_macros: Vec::new(),
+        };
+        visit_literals_item(self, visitor, macros, &mut scope, expr, true)
     }
 }
 
@@ -299,17 +422,17 @@ impl<Env: QueryEnvironment> DefaultQueryFunctionsModule<Env> {
         depth: Option<u64>,
         captured_expr: Option<CapturedExpr<'_>>,
     ) -> QueryFuncResult<Env> {
-        Ok(self
-            .implementation
-            .deps(
-                evaluator.env(),
-                evaluator.functions(),
-                &targets,
-                depth.map(|v| v as i32),
-                captured_expr.as_ref(),
-            )
-            .await?
-            .into())
+        Ok(DepsFunction::<Env> {
+            _marker: PhantomData,
+        }
+        .invoke_deps(
+            evaluator,
+            &targets,
+            depth.map(|v| v as i32),
+            captured_expr.as_ref(),
+        )
+        .await?
+        .into())
     }
 
     async fn filter(&self, regex: String, set: QueryValueSet<Env::Target>) -> QueryFuncResult<Env> {
@@ -494,7 +617,12 @@ impl<Env: QueryEnvironment> DefaultQueryFunctions<Env> {
         DepsFunction::<Env> {
             _marker: PhantomData,
         }
-        .invoke_deps(env, functions, targets, depth, captured_expr)
+        .invoke_deps(
+            &QueryEvaluator::new(env, functions),
+            targets,
+            depth,
+            captured_expr,
+        )
         .await
     }
 
@@ -12,6 +12,7 @@
 use buck2_query::query::environment::QueryEnvironment;
 use buck2_query::query::syntax::simple::eval::evaluator::QueryEvaluator;
 use buck2_query::query::syntax::simple::eval::literals::extract_target_literals;
+use buck2_query::query::syntax::simple::eval::macros::QueryMacros;
 use buck2_query::query::syntax::simple::eval::multi_query::process_multi_query;
 use buck2_query::query::syntax::simple::eval::values::QueryEvaluationResult;
 use buck2_query::query::syntax::simple::functions::DefaultQueryFunctionsModule;
@@ -35,6 +36,7 @@ pub async fn eval_query<
     A: AsRef<str>,
 >(
     functions: &DefaultQueryFunctionsModule<Env>,
+    macros: &QueryMacros,
     query: &str,
     query_args: &[A],
     environment: impl FnOnce(Vec<String>) -> Fut,
@@ -53,13 +55,14 @@ pub async fn eval_query<
             }
             extract_target_literals(
                 functions,
+                Some(macros),
                 &query.replace(QUERY_PERCENT_S_PLACEHOLDER, q),
                 &mut literals,
             )?;
         }
         let env = environment(literals.into_iter().collect()).await?;
         let results = process_multi_query(query, query_args, |input, query| {
-            let evaluator = QueryEvaluator::new(&env, functions);
+            let evaluator = QueryEvaluator::new(&env, functions).with_macros(macros);
             async move { (input, evaluator.eval_query(&query).await) }
         })
         .await;
@@ -70,10 +73,11 @@ pub async fn eval_query<
                 .into(),
         )
     } else {
-        extract_target_literals(functions, query, &mut literals)?;
+        extract_target_literals(functions, Some(macros), query, &mut literals)?;
         let env = environment(literals.into_iter().collect()).await?;
         Ok(QueryEvaluationResult::Single(
             QueryEvaluator::new(&env, functions)
+                .with_macros(macros)
                 .eval_query(query)
                 .await?,
         ))
@@ -0,0 +1,113 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use buck2_build_api::actions::query::ActionQueryNode;
+use buck2_build_api::query::bxl::BxlAqueryFunctions;
+use buck2_build_api::query::bxl::NEW_BXL_AQUERY_FUNCTIONS;
+use buck2_common::dice::cells::HasCellResolver;
+use buck2_core::fs::project_rel_path::ProjectRelativePathBuf;
+use buck2_core::target::label::TargetLabel;
+use buck2_node::nodes::configured::ConfiguredTargetNode;
+use buck2_query::query::syntax::simple::eval::set::TargetSet;
+use buck2_query::query::syntax::simple::functions::helpers::CapturedExpr;
+use buck2_query::query::syntax::simple::functions::DefaultQueryFunctions;
+use buck2_query::query::syntax::simple::functions::DefaultQueryFunctionsModule;
+use dice::DiceComputations;
+use dupe::Dupe;
+
+use crate::aquery::environment::AqueryDelegate;
+use crate::aquery::environment::AqueryEnvironment;
+use crate::aquery::evaluator::get_dice_aquery_delegate;
+use crate::dice::aquery::DiceAqueryDelegate;
+
+fn aquery_functions<'v>() -> DefaultQueryFunctions<AqueryEnvironment<'v>> {
+    DefaultQueryFunctions::new()
+}
+
+struct BxlAqueryFunctionsImpl<'c> {
+    ctx: &'c DiceComputations,
+    target_platform: Option<TargetLabel>,
+    working_dir: ProjectRelativePathBuf,
+}
+
+impl<'c> BxlAqueryFunctionsImpl<'c> {
+    async fn aquery_delegate(&self) -> anyhow::Result<Arc<DiceAqueryDelegate<'c>>> {
+        get_dice_aquery_delegate(self.ctx, &self.working_dir, self.target_platform.dupe()).await
+    }
+
+    async fn aquery_env(&self) -> anyhow::Result<AqueryEnvironment<'c>> {
+        let delegate = self.aquery_delegate().await?;
+        Ok(AqueryEnvironment::new(delegate.dupe(), delegate))
+    }
+}
+
+#[async_trait]
+impl<'c> BxlAqueryFunctions<'c> for BxlAqueryFunctionsImpl<'c> {
+    async fn deps(
+        &self,
+        targets: &TargetSet<ActionQueryNode>,
+        deps: Option<i32>,
+        captured_expr: Option<&CapturedExpr>,
+    ) -> anyhow::Result<TargetSet<ActionQueryNode>> {
+        Ok(aquery_functions()
+            .deps(
+                &self.aquery_env().await?,
+                &DefaultQueryFunctionsModule::new(),
+                targets,
+                deps,
+                captured_expr,
+            )
+            .await?)
+    }
+
+    async fn all_actions(
+        &self,
+        targets: &TargetSet<ConfiguredTargetNode>,
+    ) -> anyhow::Result<TargetSet<ActionQueryNode>> {
+        let delegate = self.aquery_delegate().await?;
+        let mut result = TargetSet::new();
+        for target in targets.iter() {
+            result.extend(delegate.get_target_actions(target.label()).await?);
+        }
+        Ok(result)
+    }
+
+    async fn all_outputs(
+        &self,
+        targets: &TargetSet<ConfiguredTargetNode>,
+    ) -> anyhow::Result<TargetSet<ActionQueryNode>> {
+        let delegate = self.aquery_delegate().await?;
+        let mut result = TargetSet::new();
+        for target in targets.iter() {
+            result.extend(delegate.get_target_output_actions(target.label()).await?);
+        }
+        Ok(result)
+    }
+}
+
+pub(crate) fn init_new_bxl_aquery_functions() {
+    NEW_BXL_AQUERY_FUNCTIONS.init(|ctx, target_platform, _project_root, cell_name| {
+        Box::pin(async move {
+            let cell_resolver = ctx.get_cell_resolver().await?;
+            let cell = cell_resolver.get(cell_name)?;
+            // TODO(nga): working as as cell root is not right.
+            //   Should be either the project root or user's current working directory.
+            let working_dir = cell.path().as_project_relative_path().to_buf();
+
+            Result::<Box<dyn BxlAqueryFunctions>, _>::Ok(Box::new(BxlAqueryFunctionsImpl {
+                ctx,
+                target_platform,
+                working_dir,
+            }))
+        })
+    })
+}
@@ -12,7 +12,9 @@ use std::sync::Arc;
 use async_trait::async_trait;
 use buck2_artifact::actions::key::ActionKey;
 use buck2_build_api::actions::query::ActionQueryNode;
+use buck2_core::cells::cell_path::CellPath;
 use buck2_core::configuration::compatibility::MaybeCompatible;
+use buck2_core::target::label::ConfiguredTargetLabel;
 use buck2_query::query::environment::QueryEnvironment;
 use buck2_query::query::syntax::simple::eval::error::QueryError;
 use buck2_query::query::syntax::simple::eval::file_set::FileSet;
@@ -21,7 +23,9 @@ use buck2_query::query::traversal::async_depth_first_postorder_traversal;
 use buck2_query::query::traversal::async_depth_limited_traversal;
 use buck2_query::query::traversal::AsyncNodeLookup;
 use buck2_query::query::traversal::AsyncTraversalDelegate;
+use tracing::warn;
 
+use crate::cquery::environment::find_owners;
 use crate::cquery::environment::CqueryDelegate;
 use crate::uquery::environment::QueryLiterals;
 
@@ -31,6 +35,26 @@ pub trait AqueryDelegate: Send + Sync {
     fn cquery_delegate(&self) -> &dyn CqueryDelegate;
 
     async fn get_node(&self, key: &ActionKey) -> anyhow::Result<ActionQueryNode>;
+
+    /// The actions registered by the analysis of `target`.
+    async fn get_target_actions(
+        &self,
+        target: &ConfiguredTargetLabel,
+    ) -> anyhow::Result<Vec<ActionQueryNode>>;
+
+    /// The actions producing the buck-out path `path`, or `None` if `path` isn't in buck-out.
+    async fn get_output_producers(
+        &self,
+        path: &CellPath,
+    ) -> anyhow::Result<Option<Vec<ActionQueryNode>>>;
+}
+
+/// Whether an action with the given source inputs reads `path`, either directly or through an
+/// input directory containing it.
+fn reads_source(path: &CellPath, source_inputs: &[CellPath]) -> bool {
+    source_inputs
+        .iter()
+        .any(|input| path.starts_with(input.as_ref()))
 }
 
 pub struct AqueryEnvironment<'c> {
@@ -49,6 +73,24 @@ impl<'c> AqueryEnvironment<'c> {
     async fn get_node(&self, label: &ActionKey) -> anyhow::Result<ActionQueryNode> {
         self.delegate.get_node(label).await
     }
+
+    /// For a buck-out path, the action that produces it. For a source file, the actions of the
+    /// targets owning the file that read it directly.
+    async fn owner_actions(&self, path: &CellPath) -> anyhow::Result<Vec<ActionQueryNode>> {
+        if let Some(producers) = self.delegate.get_output_producers(path).await? {
+            return Ok(producers);
+        }
+
+        let mut result = Vec::new();
+        for target in find_owners(self.delegate.cquery_delegate(), path).await? {
+            for action in self.delegate.get_target_actions(target.label()).await? {
+                if reads_source(path, &action.source_inputs()?) {
+                    result.push(action);
+                }
+            }
+        }
+        Ok(result)
+    }
 }
 
 #[async_trait]
@@ -112,7 +154,45 @@ impl<'c> QueryEnvironment for AqueryEnvironment<'c> {
         async_depth_limited_traversal(self, root.iter_names(), delegate, depth).await
     }
 
-    async fn owner(&self, _paths: &FileSet) -> anyhow::Result<TargetSet<Self::Target>> {
-        Err(QueryError::NotAvailableInContext("owner").into())
+    async fn owner(&self, paths: &FileSet) -> anyhow::Result<TargetSet<Self::Target>> {
+        let mut result = TargetSet::new();
+        for path in paths.iter() {
+            let owners = self.owner_actions(path).await?;
+            if owners.is_empty() {
+                warn!("No owner was found for {}", path);
+            }
+            result.extend(owners);
+        }
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn reads(path: &str, source_inputs: &[&str]) -> bool {
+        let source_inputs: Vec<CellPath> = source_inputs
+            .iter()
+            .map(|input| CellPath::testing_new(input))
+            .collect();
+        reads_source(&CellPath::testing_new(path), &source_inputs)
+    }
+
+    #[test]
+    fn test_reads_source() {
+        assert!(reads(
+            "cell//pkg/foo.c",
+            &["cell//pkg/bar.c", "cell//pkg/foo.c"]
+        ));
+        // Files inside a source directory are read by the action taking the directory.
+        assert!(reads("cell//pkg/dir/foo.c", &["cell//pkg/dir"]));
+        assert!(!reads("cell//pkg/foo.c", &["cell//pkg/bar.c"]));
+        assert!(!reads("cell//pkg/foo.c", &[]));
+        // Only whole path components match.
+        assert!(!reads("cell//pkg/foo.cc", &["cell//pkg/foo.c"]));
+        assert!(!reads("other//pkg/foo.c", &["cell//pkg/foo.c"]));
+        // An action reading a single file doesn't own the directory containing it.
+        assert!(!reads("cell//pkg", &["cell//pkg/foo.c"]));
     }
 }
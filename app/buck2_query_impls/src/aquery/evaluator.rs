@@ -13,6 +13,7 @@ use std::sync::Arc;
 use buck2_build_api::actions::query::ActionQueryNode;
 use buck2_core::fs::project_rel_path::ProjectRelativePath;
 use buck2_core::target::label::TargetLabel;
+use buck2_query::query::syntax::simple::eval::macros::QueryMacros;
 use buck2_query::query::syntax::simple::eval::values::QueryEvaluationResult;
 use buck2_query::query::syntax::simple::functions::DefaultQueryFunctionsModule;
 use dice::DiceComputations;
@@ -22,11 +23,13 @@ use crate::analysis::evaluator::eval_query;
 use crate::aquery::environment::AqueryEnvironment;
 use crate::dice::aquery::DiceAqueryDelegate;
 use crate::dice::get_dice_query_delegate;
+use crate::dice::get_query_macros;
 use crate::uquery::environment::PreresolvedQueryLiterals;
 
 pub(crate) struct AqueryEvaluator<'c> {
     dice_query_delegate: Arc<DiceAqueryDelegate<'c>>,
     functions: DefaultQueryFunctionsModule<AqueryEnvironment<'c>>,
+    macros: QueryMacros,
 }
 
 impl AqueryEvaluator<'_> {
@@ -35,14 +38,21 @@ impl AqueryEvaluator<'_> {
         query: &str,
         query_args: &[String],
     ) -> anyhow::Result<QueryEvaluationResult<ActionQueryNode>> {
-        eval_query(&self.functions, query, query_args, async move |literals| {
-            let resolved_literals =
-                PreresolvedQueryLiterals::pre_resolve(&*self.dice_query_delegate, &literals).await;
-            Ok(AqueryEnvironment::new(
-                self.dice_query_delegate.dupe(),
-                Arc::new(resolved_literals),
-            ))
-        })
+        eval_query(
+            &self.functions,
+            &self.macros,
+            query,
+            query_args,
+            async move |literals| {
+                let resolved_literals =
+                    PreresolvedQueryLiterals::pre_resolve(&*self.dice_query_delegate, &literals)
+                        .await;
+                Ok(AqueryEnvironment::new(
+                    self.dice_query_delegate.dupe(),
+                    Arc::new(resolved_literals),
+                ))
+            },
+        )
         .await
     }
 }
@@ -57,9 +67,11 @@ pub(crate) async fn get_aquery_evaluator<'a, 'c: 'a>(
     let dice_query_delegate =
         get_dice_aquery_delegate(ctx, working_dir, global_target_platform).await?;
     let functions = DefaultQueryFunctionsModule::new();
+    let macros = get_query_macros(ctx).await?;
     Ok(AqueryEvaluator {
         dice_query_delegate,
         functions,
+        macros,
     })
 }
 
@@ -71,6 +83,7 @@ pub(crate) async fn get_dice_aquery_delegate<'a, 'c: 'a>(
 ) -> anyhow::Result<Arc<DiceAqueryDelegate<'c>>> {
     let dice_query_delegate =
         get_dice_query_delegate(ctx, working_dir, global_target_platform).await?;
-    let dice_query_delegate = Arc::new(DiceAqueryDelegate::new(dice_query_delegate).await?);
+    let dice_query_delegate =
+        Arc::new(DiceAqueryDelegate::new(dice_query_delegate, working_dir).await?);
     Ok(dice_query_delegate)
 }
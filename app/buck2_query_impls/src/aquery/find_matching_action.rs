@@ -8,6 +8,7 @@
  */
 
 use std::any;
+use std::collections::HashSet;
 
 use buck2_artifact::actions::key::ActionKey;
 use buck2_artifact::artifact::build_artifact::BuildArtifact;
@@ -19,9 +20,11 @@ use buck2_core::fs::paths::forward_rel_path::ForwardRelativePathBuf;
 use buck2_core::fs::project_rel_path::ProjectRelativePath;
 use buck2_core::target::label::TargetLabel;
 use dice::DiceComputations;
+use dupe::Dupe;
 use tracing::debug;
 
 use crate::aquery::evaluator::get_dice_aquery_delegate;
+use crate::dice::aquery::DiceAqueryDelegate;
 
 fn check_output_path<'v>(
     build_artifact: &'v BuildArtifact,
@@ -34,13 +37,44 @@ fn check_output_path<'v>(
         path, path_to_check
     );
 
-    if path_to_check.starts_with(path_to_check) {
+    if path_to_check.starts_with(path) {
         Ok(Some(build_artifact.key()))
     } else {
         Ok(None)
     }
 }
 
+/// The outputs of all the actions registered by an analysis.
+fn analysis_outputs(analysis: &AnalysisResult) -> anyhow::Result<Vec<BuildArtifact>> {
+    let mut result = Vec::new();
+    for entry in analysis.iter_deferreds() {
+        match any::request_value::<ProvideOutputs>(entry.as_complex()) {
+            Some(outputs) => result.extend(outputs.0?),
+            None => debug!("Could not extract outputs from deferred table entry"),
+        }
+    }
+    Ok(result)
+}
+
+/// All the actions registered by an analysis that produce at least one output.
+pub(crate) async fn find_analysis_actions(
+    dice_aquery_delegate: &DiceAqueryDelegate<'_>,
+    analysis: &AnalysisResult,
+) -> anyhow::Result<Vec<ActionQueryNode>> {
+    let mut seen = HashSet::new();
+    let mut actions = Vec::new();
+    for build_artifact in analysis_outputs(analysis)? {
+        if seen.insert(build_artifact.key().dupe()) {
+            actions.push(
+                dice_aquery_delegate
+                    .get_action_node(build_artifact.key())
+                    .await?,
+            );
+        }
+    }
+    Ok(actions)
+}
+
 async fn find_matching_action(
     ctx: &DiceComputations,
     working_dir: &ProjectRelativePath,
@@ -51,22 +85,14 @@ async fn find_matching_action(
     let dice_aquery_delegate =
         get_dice_aquery_delegate(ctx, working_dir, global_target_platform.clone()).await?;
 
-    for entry in analysis.iter_deferreds() {
-        match any::request_value::<ProvideOutputs>(entry.as_complex()) {
-            Some(outputs) => {
-                let outputs = outputs.0?;
-                for build_artifact in &outputs {
-                    match check_output_path(build_artifact, &path_after_target_name)? {
-                        Some(action_key) => {
-                            return Ok(Some(
-                                dice_aquery_delegate.get_action_node(action_key).await?,
-                            ));
-                        }
-                        None => (),
-                    }
-                }
+    for build_artifact in &analysis_outputs(analysis)? {
+        match check_output_path(build_artifact, &path_after_target_name)? {
+            Some(action_key) => {
+                return Ok(Some(
+                    dice_aquery_delegate.get_action_node(action_key).await?,
+                ));
             }
-            None => debug!("Could not extract outputs from deferred table entry"),
+            None => (),
         }
     }
     Ok(None)
@@ -85,3 +111,45 @@ pub(crate) fn init_find_matching_action() {
         },
     );
 }
+
+#[cfg(test)]
+mod tests {
+    use buck2_artifact::artifact::artifact_type::testing::BuildArtifactTestingExt;
+    use buck2_artifact::deferred::id::DeferredId;
+    use buck2_core::configuration::data::ConfigurationData;
+    use buck2_core::package::PackageLabel;
+    use buck2_core::target::name::TargetNameRef;
+
+    use super::*;
+
+    fn build_artifact(path: &str) -> BuildArtifact {
+        BuildArtifact::testing_new(
+            TargetLabel::new(
+                PackageLabel::testing_new("cell", "pkg"),
+                TargetNameRef::unchecked_new("foo"),
+            )
+            .configure(ConfigurationData::testing_new()),
+            ForwardRelativePathBuf::unchecked_new(path.to_owned()),
+            DeferredId::testing_new(0),
+        )
+    }
+
+    fn matches(artifact_path: &str, path_to_check: &str) -> anyhow::Result<bool> {
+        Ok(check_output_path(
+            &build_artifact(artifact_path),
+            &ForwardRelativePathBuf::unchecked_new(path_to_check.to_owned()),
+        )?
+        .is_some())
+    }
+
+    #[test]
+    fn test_check_output_path() -> anyhow::Result<()> {
+        assert!(matches("bar.out", "bar.out")?);
+        // Paths inside a directory output belong to the action producing the directory.
+        assert!(matches("dir", "dir/bar.out")?);
+        assert!(!matches("bar.out", "baz.out")?);
+        assert!(!matches("bar", "bar.out")?);
+        assert!(!matches("dir/bar.out", "dir")?);
+        Ok(())
+    }
+}
@@ -7,6 +7,7 @@
  * of this source tree.
  */
 
+pub(crate) mod bxl;
 pub mod environment;
 pub mod evaluator;
 pub(crate) mod find_matching_action;
@@ -123,69 +123,75 @@ impl<'c> CqueryEnvironment<'c> {
     /// Deprecated `owner` function implementation.
     /// See [this post](https://fburl.com/0xv7u4bz) for details.
     async fn owner_deprecated(&self, path: &CellPath) -> anyhow::Result<Vec<ConfiguredTargetNode>> {
-        // need to explicitly track this rather than checking for changes to result set since the owner might
-        // already be in the set.
-        let mut owners = Vec::new();
-        match self
-            .delegate
-            .uquery_delegate()
-            .get_enclosing_packages(path)
-            .await
-        {
-            Ok(packages) => {
-                let package_futs = packages.iter().map(|package| async move {
-                    let mut result: Vec<ConfiguredTargetNode> = Vec::new();
-
-                    // TODO(cjhopman): We should make sure that the file exists.
-                    let targets = self
-                        .delegate
-                        .uquery_delegate()
-                        .eval_build_file(package.dupe())
-                        .await?;
-
-                    for node in targets.targets().values() {
-                        match self.delegate.get_node_for_target(node.label()).await? {
-                            MaybeCompatible::Compatible(node) => {
-                                for input in node.inputs() {
-                                    if &input == path {
-                                        result.push(node.dupe());
-                                        // this intentionally only breaks out of the inner loop. We don't need to look at the
-                                        // other inputs of this target, but it's possible for a single file to be owned by
-                                        // multiple targets.
-                                        break;
-                                    }
+        find_owners(&*self.delegate, path).await
+    }
+
+    fn owner_correct(&self, path: &CellPath) -> anyhow::Result<Vec<ConfiguredTargetNode>> {
+        let universe = self.universe.as_ref().context(CqueryError::NoUniverse)?;
+        Ok(universe.owners(path))
+    }
+}
+
+/// Finds the configured targets in the enclosing packages of `path` that have it as an input.
+pub(crate) async fn find_owners(
+    delegate: &dyn CqueryDelegate,
+    path: &CellPath,
+) -> anyhow::Result<Vec<ConfiguredTargetNode>> {
+    // need to explicitly track this rather than checking for changes to result set since the owner might
+    // already be in the set.
+    let mut owners = Vec::new();
+    match delegate
+        .uquery_delegate()
+        .get_enclosing_packages(path)
+        .await
+    {
+        Ok(packages) => {
+            let package_futs = packages.iter().map(|package| async move {
+                let mut result: Vec<ConfiguredTargetNode> = Vec::new();
+
+                // TODO(cjhopman): We should make sure that the file exists.
+                let targets = delegate
+                    .uquery_delegate()
+                    .eval_build_file(package.dupe())
+                    .await?;
+
+                for node in targets.targets().values() {
+                    match delegate.get_node_for_target(node.label()).await? {
+                        MaybeCompatible::Compatible(node) => {
+                            for input in node.inputs() {
+                                if &input == path {
+                                    result.push(node.dupe());
+                                    // this intentionally only breaks out of the inner loop. We don't need to look at the
+                                    // other inputs of this target, but it's possible for a single file to be owned by
+                                    // multiple targets.
+                                    break;
                                 }
                             }
-                            MaybeCompatible::Incompatible(reason) => {
-                                // TODO(scottcao): Add event for incompatible target skipping
-                                console_message(reason.skipping_message(
-                                    &self.delegate.get_configured_target(node.label()).await?,
-                                ));
-                            }
+                        }
+                        MaybeCompatible::Incompatible(reason) => {
+                            // TODO(scottcao): Add event for incompatible target skipping
+                            console_message(reason.skipping_message(
+                                &delegate.get_configured_target(node.label()).await?,
+                            ));
                         }
                     }
+                }
 
-                    anyhow::Ok(result)
-                });
+                anyhow::Ok(result)
+            });
 
-                for nodes in futures::future::join_all(package_futs).await.into_iter() {
-                    for node in nodes?.into_iter() {
-                        owners.push(node);
-                    }
+            for nodes in futures::future::join_all(package_futs).await.into_iter() {
+                for node in nodes?.into_iter() {
+                    owners.push(node);
                 }
             }
-            Err(_) => {
-                // we don't consider this an error, it's usually the case that the user
-                // just wants to know the target owning the file if it exists.
-            }
-        };
-        Ok(owners)
-    }
-
-    fn owner_correct(&self, path: &CellPath) -> anyhow::Result<Vec<ConfiguredTargetNode>> {
-        let universe = self.universe.as_ref().context(CqueryError::NoUniverse)?;
-        Ok(universe.owners(path))
-    }
+        }
+        Err(_) => {
+            // we don't consider this an error, it's usually the case that the user
+            // just wants to know the target owning the file if it exists.
+        }
+    };
+    Ok(owners)
 }
 
 #[async_trait]
@@ -18,6 +18,7 @@ use buck2_core::target::label::TargetLabel;
 use buck2_events::dispatch::console_message;
 use buck2_node::configured_universe::CqueryUniverse;
 use buck2_node::nodes::configured::ConfiguredTargetNode;
+use buck2_query::query::syntax::simple::eval::macros::QueryMacros;
 use buck2_query::query::syntax::simple::eval::values::QueryEvaluationResult;
 use buck2_query::query::syntax::simple::functions::DefaultQueryFunctionsModule;
 use dice::DiceComputations;
@@ -29,6 +30,7 @@ use gazebo::prelude::*;
 use crate::analysis::evaluator::eval_query;
 use crate::cquery::environment::CqueryEnvironment;
 use crate::dice::get_dice_query_delegate;
+use crate::dice::get_query_macros;
 use crate::dice::DiceQueryDelegate;
 use crate::uquery::environment::PreresolvedQueryLiterals;
 use crate::uquery::environment::QueryLiterals;
@@ -37,6 +39,7 @@ use crate::uquery::environment::UqueryDelegate;
 pub struct CqueryEvaluator<'c> {
     dice_query_delegate: Arc<DiceQueryDelegate<'c>>,
     functions: DefaultQueryFunctionsModule<CqueryEnvironment<'c>>,
+    macros: QueryMacros,
     owner_behavior: CqueryOwnerBehavior,
 }
 
@@ -47,7 +50,12 @@ impl CqueryEvaluator<'_> {
         query_args: &[A],
         target_universe: Option<&[U]>,
     ) -> anyhow::Result<QueryEvaluationResult<ConfiguredTargetNode>> {
-        eval_query(&self.functions, query, query_args, async move |literals| {
+        eval_query(
+            &self.functions,
+            &self.macros,
+            query,
+            query_args,
+            async move |literals| {
             let (universe, resolved_literals) = match target_universe {
                 None => {
                     if literals.is_empty() {
@@ -102,9 +110,11 @@ pub async fn get_cquery_evaluator<'a, 'c: 'a>(
     let dice_query_delegate =
         Arc::new(get_dice_query_delegate(ctx, working_dir, global_target_platform).await?);
     let functions = DefaultQueryFunctionsModule::new();
+    let macros = get_query_macros(ctx).await?;
     Ok(CqueryEvaluator {
         dice_query_delegate,
         functions,
+        macros,
         owner_behavior,
     })
 }
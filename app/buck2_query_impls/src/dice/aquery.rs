@@ -21,12 +21,20 @@ use buck2_build_api::analysis::calculation::RuleAnalysisCalculation;
 use buck2_build_api::artifact_groups::ArtifactGroup;
 use buck2_build_api::artifact_groups::ResolvedArtifactGroup;
 use buck2_build_api::artifact_groups::TransitiveSetProjectionKey;
+use buck2_build_api::audit_output::audit_output;
+use buck2_build_api::audit_output::AuditOutputResult;
 use buck2_build_api::calculation::Calculation;
 use buck2_build_api::deferred::calculation::DeferredCalculation;
 use buck2_common::result::SharedResult;
+use buck2_core::cells::cell_path::CellPath;
 use buck2_core::configuration::compatibility::MaybeCompatible;
 use buck2_core::fs::artifact_path_resolver::ArtifactFs;
+use buck2_core::fs::project_rel_path::ProjectRelativePath;
+use buck2_core::fs::project_rel_path::ProjectRelativePathBuf;
 use buck2_core::pattern::ParsedPattern;
+use buck2_core::provider::label::ConfiguredProvidersLabel;
+use buck2_core::provider::label::ProvidersName;
+use buck2_core::target::label::ConfiguredTargetLabel;
 use buck2_query::query::syntax::simple::eval::set::TargetSet;
 use dashmap::DashMap;
 use dice::DiceComputations;
@@ -41,8 +49,10 @@ use gazebo::prelude::*;
 use itertools::Either;
 use itertools::Itertools;
 use thiserror::Error;
+use tracing::warn;
 
 use crate::aquery::environment::AqueryDelegate;
+use crate::aquery::find_matching_action::find_analysis_actions;
 use crate::cquery::environment::CqueryDelegate;
 use crate::dice::DiceQueryDelegate;
 use crate::uquery::environment::QueryLiterals;
@@ -108,6 +118,7 @@ pub(crate) struct DiceAqueryDelegate<'c> {
     base_delegate: DiceQueryDelegate<'c>,
     nodes_cache: DiceAqueryNodesCache,
     artifact_fs: Arc<ArtifactFs>,
+    working_dir: ProjectRelativePathBuf,
 }
 
 /// Converts artifact inputs into aquery's ActionInput. This is mostly a matter of resolving the indirect
@@ -223,12 +234,14 @@ async fn get_action_node(
 impl<'c> DiceAqueryDelegate<'c> {
     pub(crate) async fn new<'a>(
         base_delegate: DiceQueryDelegate<'a>,
+        working_dir: &ProjectRelativePath,
     ) -> anyhow::Result<DiceAqueryDelegate<'a>> {
         let artifact_fs = Arc::new(base_delegate.ctx().get_artifact_fs().await?);
         Ok(DiceAqueryDelegate {
             base_delegate,
             nodes_cache: DiceAqueryNodesCache::new(),
             artifact_fs,
+            working_dir: working_dir.to_buf(),
         })
     }
 
@@ -241,6 +254,40 @@ impl<'c> DiceAqueryDelegate<'c> {
         )
         .await
     }
+
+    /// The actions producing the default and other outputs of `target`, including those reached
+    /// through transitive set projections.
+    pub(crate) async fn get_target_output_actions(
+        &self,
+        target: &ConfiguredTargetLabel,
+    ) -> anyhow::Result<Vec<ActionQueryNode>> {
+        let label = ConfiguredProvidersLabel::new(target.dupe(), ProvidersName::Default);
+        let providers = match self.base_delegate.ctx().get_providers(&label).await? {
+            MaybeCompatible::Incompatible(_) => return Ok(Vec::new()),
+            MaybeCompatible::Compatible(providers) => providers,
+        };
+        let mut outputs = Vec::new();
+        providers
+            .provider_collection()
+            .default_info()
+            .for_each_output(&mut |output| {
+                outputs.push(output);
+                Ok(())
+            })?;
+
+        let inputs = convert_inputs(
+            self.base_delegate.ctx(),
+            self.nodes_cache.dupe(),
+            outputs.iter(),
+        )
+        .await?;
+
+        let mut actions = Vec::new();
+        for key in ActionInput::action_keys(inputs) {
+            actions.push(self.get_action_node(&key).await?);
+        }
+        Ok(actions)
+    }
 }
 
 #[async_trait]
@@ -252,6 +299,46 @@ impl<'c> AqueryDelegate for DiceAqueryDelegate<'c> {
     async fn get_node(&self, key: &ActionKey) -> anyhow::Result<ActionQueryNode> {
         self.get_action_node(key).await
     }
+
+    async fn get_target_actions(
+        &self,
+        target: &ConfiguredTargetLabel,
+    ) -> anyhow::Result<Vec<ActionQueryNode>> {
+        match self.base_delegate.ctx().get_analysis_result(target).await? {
+            MaybeCompatible::Incompatible(_) => Ok(Vec::new()),
+            MaybeCompatible::Compatible(analysis) => find_analysis_actions(self, &analysis).await,
+        }
+    }
+
+    async fn get_output_producers(
+        &self,
+        path: &CellPath,
+    ) -> anyhow::Result<Option<Vec<ActionQueryNode>>> {
+        let project_path = self.artifact_fs.resolve_cell_path(path.as_ref())?;
+        if !project_path.starts_with(self.artifact_fs.buck_out_path_resolver().root()) {
+            return Ok(None);
+        }
+
+        let result = audit_output(
+            project_path.as_str(),
+            &self.working_dir,
+            self.base_delegate.cell_resolver(),
+            self.base_delegate.ctx(),
+            self.base_delegate.global_target_platform().cloned(),
+        )
+        .await?;
+        Ok(Some(match result {
+            Some(AuditOutputResult::Match(node)) => vec![node],
+            Some(AuditOutputResult::MaybeRelevant(target)) => {
+                warn!(
+                    "`{}` was built with a different configuration than `{}` is configured with in this query",
+                    path, target
+                );
+                Vec::new()
+            }
+            None => Vec::new(),
+        }))
+    }
 }
 
 #[async_trait]
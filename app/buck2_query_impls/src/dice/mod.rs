@@ -10,6 +10,7 @@
 use std::collections::HashMap;
 use std::sync::Arc;
 
+use anyhow::Context;
 use async_trait::async_trait;
 use buck2_build_api::calculation::Calculation;
 use buck2_build_api::configure_targets::load_compatible_patterns;
@@ -17,6 +18,8 @@ use buck2_build_api::nodes::calculation::NodeCalculation;
 use buck2_common::dice::cells::HasCellResolver;
 use buck2_common::dice::data::HasIoProvider;
 use buck2_common::dice::file_ops::HasFileOps;
+use buck2_common::file_ops::FileOps;
+use buck2_common::legacy_configs::dice::HasLegacyConfigs;
 use buck2_common::package_boundary::HasPackageBoundaryExceptions;
 use buck2_common::package_boundary::PackageBoundaryExceptions;
 use buck2_common::package_listing::dice::HasPackageListingResolver;
@@ -53,6 +56,7 @@ use buck2_node::nodes::frontend::TargetGraphCalculation;
 use buck2_node::nodes::unconfigured::TargetNode;
 use buck2_query::query::syntax::simple::eval::file_set::FileNode;
 use buck2_query::query::syntax::simple::eval::file_set::FileSet;
+use buck2_query::query::syntax::simple::eval::macros::QueryMacros;
 use buck2_query::query::syntax::simple::eval::set::TargetSet;
 use dice::DiceComputations;
 use dupe::Dupe;
@@ -180,6 +184,10 @@ impl<'c> DiceQueryDelegate<'c> {
         self.ctx
     }
 
+    pub(crate) fn cell_resolver(&self) -> &CellResolver {
+        &self.cell_resolver
+    }
+
     pub(crate) fn literal_parser(&self) -> &LiteralParser {
         &self.literal_parser
     }
@@ -355,3 +363,29 @@ pub(crate) async fn get_dice_query_delegate<'a, 'c: 'a>(
         target_alias_resolver,
     )
 }
+
+/// Loads the query macros configured in the root cell's buckconfig: the files listed in
+/// `query.macro_files` (project-relative paths), followed by the entries of the `query_macros`
+/// section.
+pub(crate) async fn get_query_macros(ctx: &DiceComputations) -> anyhow::Result<QueryMacros> {
+    let cell_resolver = ctx.get_cell_resolver().await?;
+    let root_config = ctx
+        .get_legacy_config_for_cell(cell_resolver.root_cell())
+        .await?;
+
+    let mut macros = QueryMacros::new();
+    if let Some(files) = root_config.parse_list::<String>("query", "macro_files")? {
+        let file_ops = ctx.file_ops();
+        for file in files {
+            let path = cell_resolver.get_cell_path(ProjectRelativePath::new(&file)?)?;
+            let definitions = <dyn FileOps>::read_file(&file_ops, path.as_ref()).await?;
+            macros
+                .add_definitions(&definitions)
+                .with_context(|| format!("Error loading query macros from `{}`", file))?;
+        }
+    }
+    if let Some(section) = root_config.get_section("query_macros") {
+        macros.add_config_entries(section.iter().map(|(k, v)| (k, v.as_str())))?;
+    }
+    Ok(macros)
+}
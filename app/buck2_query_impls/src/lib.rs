@@ -26,6 +26,7 @@ pub fn init_late_bindings() {
         analysis::environment::init_classpath_for_targets();
         analysis::environment::init_query_functions();
         analysis::eval::init_eval_analysis_query();
+        aquery::bxl::init_new_bxl_aquery_functions();
         aquery::find_matching_action::init_find_matching_action();
         frontend::init_query_frontend();
         cquery::bxl::init_new_bxl_cquery_functions();
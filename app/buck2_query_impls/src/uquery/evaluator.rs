@@ -13,6 +13,7 @@ use std::sync::Arc;
 use buck2_core::fs::project_rel_path::ProjectRelativePath;
 use buck2_core::target::label::TargetLabel;
 use buck2_node::nodes::unconfigured::TargetNode;
+use buck2_query::query::syntax::simple::eval::macros::QueryMacros;
 use buck2_query::query::syntax::simple::eval::values::QueryEvaluationResult;
 use buck2_query::query::syntax::simple::functions::DefaultQueryFunctionsModule;
 use dice::DiceComputations;
@@ -20,6 +21,7 @@ use dupe::Dupe;
 
 use crate::analysis::evaluator::eval_query;
 use crate::dice::get_dice_query_delegate;
+use crate::dice::get_query_macros;
 use crate::dice::DiceQueryDelegate;
 use crate::uquery::environment::PreresolvedQueryLiterals;
 use crate::uquery::environment::UqueryEnvironment;
@@ -27,6 +29,7 @@ use crate::uquery::environment::UqueryEnvironment;
 pub struct UqueryEvaluator<'c> {
     dice_query_delegate: Arc<DiceQueryDelegate<'c>>,
     functions: DefaultQueryFunctionsModule<UqueryEnvironment<'c>>,
+    macros: QueryMacros,
 }
 
 impl UqueryEvaluator<'_> {
@@ -35,14 +38,21 @@ impl UqueryEvaluator<'_> {
         query: &str,
         query_args: &[String],
     ) -> anyhow::Result<QueryEvaluationResult<TargetNode>> {
-        eval_query(&self.functions, query, query_args, async move |literals| {
-            let resolved_literals =
-                PreresolvedQueryLiterals::pre_resolve(&*self.dice_query_delegate, &literals).await;
-            Ok(UqueryEnvironment::new(
-                self.dice_query_delegate.dupe(),
-                Arc::new(resolved_literals),
-            ))
-        })
+        eval_query(
+            &self.functions,
+            &self.macros,
+            query,
+            query_args,
+            async move |literals| {
+                let resolved_literals =
+                    PreresolvedQueryLiterals::pre_resolve(&*self.dice_query_delegate, &literals)
+                        .await;
+                Ok(UqueryEnvironment::new(
+                    self.dice_query_delegate.dupe(),
+                    Arc::new(resolved_literals),
+                ))
+            },
+        )
         .await
     }
 }
@@ -57,9 +67,11 @@ pub async fn get_uquery_evaluator<'a, 'c: 'a>(
     let dice_query_delegate =
         Arc::new(get_dice_query_delegate(ctx, working_dir, global_target_platform).await?);
     let functions = DefaultQueryFunctionsModule::new();
+    let macros = get_query_macros(ctx).await?;
 
     Ok(UqueryEvaluator {
         dice_query_delegate,
         functions,
+        macros,
     })
 }
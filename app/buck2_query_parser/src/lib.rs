@@ -30,6 +30,8 @@
 //!        | EXPR ' + ' EXPR
 //!        | EXPR ' except ' EXPR
 //!        | EXPR ' - ' EXPR
+//!        | 'let' IDENTIFIER '=' EXPR 'in' EXPR
+//!        | '$' IDENTIFIER
 //!
 //! # word is much broader than a normal identifier-like thing would allow since we don't want to require
 //! # quoting targets "@fbcode//some:target" or common regexes ".*" or filenames "Foo.java".
@@ -40,9 +42,17 @@
 //!
 //! INTEGER ::= "0" | ("1-9" "0-9"*)
 //!
-//! FUNCTION_NAME ::= "a-zA-Z_" "a-zA-Z0-9_" *
+//! FUNCTION_NAME ::= IDENTIFIER
+//!
+//! IDENTIFIER ::= "a-zA-Z_" "a-zA-Z0-9_" *
 //!
 //! ```
+//!
+//! A `$` followed by an identifier refers to a variable bound by an enclosing `let` (or to a macro
+//! parameter). Anything else starting with `$` is still parsed as a `WORD`, so regexes like `foo$`
+//! keep working.
+//!
+//! Query macros are defined separately from queries, see [`parse_macro_definitions`].
 
 pub mod placeholder;
 pub mod span;
@@ -84,8 +94,6 @@ use thiserror::Error;
 use crate::span::Span;
 use crate::spanned::Spanned;
 
-// TODO(cjhopman): Add `LET WORD = expr IN expr`
-
 // TODO(cjhopman): We should switch to our own error type here. VerboseError doesn't even allow us to construct
 // our own error messages (so, for example, we can't have a good error message for too large integers) and doesn't
 // support propagating anyhow or std errors (and since we can't do a custom message, we can't even capture them as a string).
@@ -123,6 +131,22 @@ pub enum Expr<'a> {
     BinaryOpSequence(Box<SpannedExpr<'a>>, Vec<(BinaryOp, SpannedExpr<'a>)>),
     Set(Vec<Span<'a>>),
     FileSet(Vec<Span<'a>>),
+    /// `let name = value in body`.
+    Let {
+        name: Span<'a>,
+        value: Box<SpannedExpr<'a>>,
+        body: Box<SpannedExpr<'a>>,
+    },
+    /// `$name`, a reference to a `let` binding or a macro parameter.
+    Variable(Span<'a>),
+}
+
+/// A query macro definition, `NAME(PARAM, ...) = EXPR`.
+#[derive(Debug)]
+pub struct MacroDefinition<'a> {
+    pub name: Span<'a>,
+    pub params: Vec<Span<'a>>,
+    pub body: SpannedExpr<'a>,
 }
 
 impl Display for Expr<'_> {
@@ -179,6 +203,10 @@ impl Display for Expr<'_> {
                 }
                 f.write_str(")")?;
             }
+            Expr::Let { name, value, body } => {
+                write!(f, "let {} = {} in {}", name.fragment(), value, body)?;
+            }
+            Expr::Variable(name) => write!(f, "${}", name.fragment())?,
         }
         Ok(())
     }
@@ -236,13 +264,30 @@ fn convert_to_str_error(err: VerboseError<Span>) -> VerboseError<&str> {
 
 /// Parses a query string into a SpannedExpr. Requires that the entire input is consumed.
 pub fn parse_expr(input: &str) -> anyhow::Result<SpannedExpr> {
+    parse_all(input, expr, expr)
+}
+
+/// Parses a sequence of query macro definitions of the form `NAME(PARAM, ...) = EXPR`.
+///
+/// Definitions may optionally be separated by `;`, and lines starting with `#` are comments. The
+/// text of a macro's body is `&input[definition.body.position]`.
+pub fn parse_macro_definitions(input: &str) -> anyhow::Result<Vec<MacroDefinition>> {
+    parse_all(input, macro_definitions, macro_definitions)
+}
+
+/// Runs a parser over the entire input. The parser is passed twice so that it can be instantiated
+/// with both a fast error type (`()`) and `VerboseError`: we parse with the former first, and on
+/// error reparse again with the latter to get detailed errors.
+fn parse_all<'a, O>(
+    input: &'a str,
+    fast: impl FnMut(Span<'a>) -> NomResult<'a, O, ()>,
+    verbose: impl FnMut(Span<'a>) -> NomResult<'a, O, VerboseError<Span<'a>>>,
+) -> anyhow::Result<O> {
     let span = Span::new(input);
-    // Parse with fast error (`()`) first,
-    // and on error reparse again with `VerboseError` to get detailed errors.
-    match all_consuming(expr)(span) {
+    match all_consuming(fast)(span) {
         Ok((_, value)) => Ok(value),
         Err(nom::Err::Failure(())) | Err(nom::Err::Error(())) => {
-            match all_consuming(expr)(span) {
+            match all_consuming(verbose)(span) {
                 Ok(..) => unreachable!(
                     "if fast parse didn't succeed, slow parse should not succeed as well"
                 ),
@@ -276,8 +321,10 @@ fn single_expr<'a, E: NomParseError<'a>>(input: Span<'a>) -> NomResult<'a, Spann
         preceded(char('('), cut(terminated(expr, char(')')))),
         expr_set,
         expr_fileset,
+        expr_let,
         expr_function,
         expr_int,
+        expr_variable,
         expr_word,
     ))(input)?;
 
@@ -351,9 +398,32 @@ fn expr_int<'a, E: NomParseError<'a>>(input: Span<'a>) -> NomResult<'a, SpannedE
     })(input)
 }
 
+/// Tries to parse an Expr::Variable. Fails recoverably if the `$name` is followed by other word
+/// characters, in which case it will be parsed as a word instead.
+fn expr_variable<'a, E: NomParseError<'a>>(input: Span<'a>) -> NomResult<'a, SpannedExpr<'a>, E> {
+    spanned(|input| {
+        let (remaining, name) = preceded(char('$'), identifier)(input)?;
+        if remaining
+            .fragment()
+            .starts_with(|c: char| c.is_alphanumeric() || NON_ALPHANUMERIC_WORD_CHARS.contains(c))
+        {
+            return Err(nom::Err::Error(nom::error::ParseError::from_error_kind(
+                input,
+                ErrorKind::Char,
+            )));
+        }
+        Ok((remaining, Expr::Variable(name)))
+    })(input)
+}
+
+const NON_ALPHANUMERIC_WORD_CHARS: &str = "*/@.-_:$#%";
+
 fn word<'a, E: NomParseError<'a>>(input: Span<'a>) -> NomResult<'a, Span<'a>, E> {
     fn non_quoted_word<'a, E: NomParseError<'a>>(input: Span<'a>) -> NomResult<'a, Span<'a>, E> {
-        recognize(many1(alt((alphanumeric1, is_a("*/@.-_:$#%")))))(input)
+        recognize(many1(alt((
+            alphanumeric1,
+            is_a(NON_ALPHANUMERIC_WORD_CHARS),
+        ))))(input)
     }
 
     alt((
@@ -437,10 +507,7 @@ fn expr_function<'a, E: NomParseError<'a>>(input: Span<'a>) -> NomResult<'a, Spa
     }
 
     spanned(|input| {
-        let (input, function_name) = recognize(pair(
-            alt((alpha1, tag("_"))),
-            many0(alt((alphanumeric1, tag("_")))),
-        ))(input)?;
+        let (input, function_name) = identifier(input)?;
         let (input, _) = char('(')(input)?;
         cut(move |input| {
             let (input, args) = terminated(function_args, char(')'))(input)?;
@@ -455,6 +522,66 @@ fn expr_function<'a, E: NomParseError<'a>>(input: Span<'a>) -> NomResult<'a, Spa
     })(input)
 }
 
+fn identifier<'a, E: NomParseError<'a>>(input: Span<'a>) -> NomResult<'a, Span<'a>, E> {
+    recognize(pair(
+        alt((alpha1, tag("_"))),
+        many0(alt((alphanumeric1, tag("_")))),
+    ))(input)
+}
+
+/// Tries to parse an Expr::Let. Will fail if it detects an unfinished "let name ="
+fn expr_let<'a, E: NomParseError<'a>>(input: Span<'a>) -> NomResult<'a, SpannedExpr<'a>, E> {
+    spanned(|input| {
+        let (input, _) = terminated(tag("let"), multispace1)(input)?;
+        let (input, name) =
+            terminated(identifier, delimited(multispace0, char('='), multispace0))(input)?;
+        cut(move |input| {
+            let (input, value) = expr(input)?;
+            let (input, _) = terminated(tag("in"), multispace1)(input)?;
+            let (input, body) = expr(input)?;
+            Ok((
+                input,
+                Expr::Let {
+                    name,
+                    value: Box::new(value),
+                    body: Box::new(body),
+                },
+            ))
+        })(input)
+    })(input)
+}
+
+/// Parses a single macro definition. Will fail if it detects an unfinished "name("
+fn macro_definition<'a, E: NomParseError<'a>>(
+    input: Span<'a>,
+) -> NomResult<'a, MacroDefinition<'a>, E> {
+    let (input, name) = terminated(identifier, char('('))(input)?;
+    cut(move |input| {
+        let (input, params) =
+            separated_list0(char(','), delimited(multispace0, identifier, multispace0))(input)?;
+        let (input, _) = terminated(char(')'), multispace0)(input)?;
+        let (input, _) = char('=')(input)?;
+        let (input, body) = expr(input)?;
+        Ok((input, MacroDefinition { name, params, body }))
+    })(input)
+}
+
+fn macro_definitions<'a, E: NomParseError<'a>>(
+    input: Span<'a>,
+) -> NomResult<'a, Vec<MacroDefinition<'a>>, E> {
+    // Whitespace, `;` separators and `#` comments between definitions.
+    fn separators<'a, E: NomParseError<'a>>(input: Span<'a>) -> NomResult<'a, (), E> {
+        let (input, _) = many0(alt((
+            multispace1,
+            tag(";"),
+            recognize(pair(char('#'), take_till(|c| c == '\n'))),
+        )))(input)?;
+        Ok((input, ()))
+    }
+
+    preceded(separators, many0(terminated(macro_definition, separators)))(input)
+}
+
 #[cfg(test)]
 mod tests {
     use std::fmt::Debug;
@@ -646,4 +773,108 @@ mod tests {
         );
         Ok(())
     }
+
+    #[test]
+    fn test_let() -> anyhow::Result<()> {
+        run_tests(
+            expr_let,
+            &[
+                "let x = a in $x",
+                "let x=a in $x",
+                "let x = deps(a) + b in let y = $x in kind(lib, $y)",
+                "let x = a in\n  $x",
+            ],
+            // As long as we don't match "let name =", it should be recoverable
+            &["let", "letter", "let(a)", "let x", "let x y", "a"],
+            // An error after "let name =" is non-recoverable
+            &["let x = ", "let x = a", "let x = a in", "let x = a b"],
+        );
+
+        match parse_expr("let x = a in $x ^ b") {
+            Ok(Spanned {
+                value: Expr::Let { name, body, .. },
+                ..
+            }) => {
+                assert_eq!(*name.fragment(), "x");
+                assert!(matches!(body.value, Expr::BinaryOpSequence(..)));
+            }
+            v => panic!("expected let expr, got `{:?}`", v),
+        }
+
+        // `let` on its own is still a word.
+        match parse_expr("let") {
+            Ok(Spanned {
+                value: Expr::String("let"),
+                ..
+            }) => {}
+            v => panic!("expected 'let', got `{:?}`", v),
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_variable() -> anyhow::Result<()> {
+        run_tests(
+            expr_variable,
+            &["$x", "$_foo1"],
+            &["x", "$", "$1", "$x.*", "$x$", "regex$"],
+            &[],
+        );
+
+        match parse_expr("filter('foo$', $x)") {
+            Ok(Spanned {
+                value: Expr::Function { args, .. },
+                ..
+            }) => {
+                assert!(matches!(args[0].value, Expr::String("foo$")));
+                assert!(matches!(args[1].value, Expr::Variable(name) if *name.fragment() == "x"));
+            }
+            v => panic!("expected function expr, got `{:?}`", v),
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_display_let() -> anyhow::Result<()> {
+        assert_eq!(
+            parse_expr("let  x=deps(a)   in $x")?.to_string(),
+            "let x = deps('a') in $x"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_macro_definitions() -> anyhow::Result<()> {
+        let input = r#"
+# Direct deps of a target.
+direct_deps(x) = deps($x, 1);
+libs_in(x, y) =
+    kind(library, $x ^ $y)
+
+nothing() = set()
+"#;
+        let definitions = parse_macro_definitions(input)?;
+        assert_eq!(
+            definitions
+                .iter()
+                .map(|d| (
+                    *d.name.fragment(),
+                    d.params.map(|p| *p.fragment()),
+                    &input[d.body.position.clone()],
+                ))
+                .collect::<Vec<_>>(),
+            vec![
+                ("direct_deps", vec!["x"], "deps($x, 1)"),
+                ("libs_in", vec!["x", "y"], "kind(library, $x ^ $y)"),
+                ("nothing", vec![], "set()"),
+            ]
+        );
+
+        assert!(parse_macro_definitions("")?.is_empty());
+        assert!(parse_macro_definitions("foo(x) =").is_err());
+        assert!(parse_macro_definitions("foo = bar").is_err());
+        Ok(())
+    }
 }
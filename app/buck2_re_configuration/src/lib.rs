@@ -133,12 +133,26 @@ mod not_fbcode {
 #[derive(Clone, Debug, Default, Allocative)]
 pub struct Buck2OssReConfiguration {
     /// Address for RBE Content Addresable Storage service (including bytestream uploads service).
+    ///
+    /// Addresses can either be a host and port (optionally prefixed with `grpc://`), or a Unix
+    /// domain socket, as `unix:relative/path` or `unix:///absolute/path`.
     pub cas_address: Option<String>,
     /// Address for RBE Engine service (including capabilities service).
     pub engine_address: Option<String>,
     /// Address for RBE Action Cache service.
     pub action_cache_address: Option<String>,
-    /// Whether to use TLS to interact with remote execution.
+    /// Base URL of a cache implementing the Bazel HTTP caching protocol (`/ac/` and `/cas/`).
+    /// When set, this is used instead of the gRPC services above, and remote execution is not
+    /// available (only remote caching is).
+    pub http_cache_address: Option<String>,
+    /// TLS settings for the CAS service, overriding the global ones.
+    pub cas_tls: Buck2OssReServiceTlsConfiguration,
+    /// TLS settings for the Engine service, overriding the global ones.
+    pub engine_tls: Buck2OssReServiceTlsConfiguration,
+    /// TLS settings for the Action Cache service, overriding the global ones.
+    pub action_cache_tls: Buck2OssReServiceTlsConfiguration,
+    /// Whether to use TLS to interact with remote execution. This does not apply to services
+    /// reached over a Unix domain socket, which never use TLS.
     pub tls: bool,
     /// Path to a CA certificates bundle. This must be PEM-encoded. If none is set, a default
     /// bundle will be used.
@@ -162,6 +176,50 @@ pub struct Buck2OssReConfiguration {
     pub capabilities: Option<bool>,
     /// The instance name to use in requests.
     pub instance_name: Option<String>,
+    /// Whether to compress blobs transferred to and from the CAS. If unset, compression is used
+    /// when the server advertises support for a compressor we support (zstd is preferred). If
+    /// set to `true`, compression is used even if the server does not advertise it, and if set
+    /// to `false`, blobs are always transferred uncompressed.
+    pub compression: Option<bool>,
+    /// How many times to attempt an RPC to RE before giving up, including the first attempt. Only
+    /// transient failures (`UNAVAILABLE`, `RESOURCE_EXHAUSTED` and `DEADLINE_EXCEEDED`) are
+    /// retried. Set this to 1 to disable retries.
+    pub retry_max_attempts: Option<usize>,
+    /// How long to wait before the first retry. This doubles for every subsequent retry, up to
+    /// `retry_max_backoff_ms`, and some random jitter is applied to it.
+    pub retry_initial_backoff_ms: Option<u64>,
+    /// The maximum time to wait between two retries.
+    pub retry_max_backoff_ms: Option<u64>,
+    /// A deadline for every attempt of a unary RPC to RE. This does not apply to streaming RPCs
+    /// (`Execute` and ByteStream reads and writes), which take as long as the action or the
+    /// transfer does. If unset, RPCs have no deadline.
+    pub rpc_timeout_ms: Option<u64>,
+}
+
+/// TLS settings for a single RE service. Those are configured as `<service>_tls`,
+/// `<service>_tls_ca_certs` and `<service>_tls_client_cert`, and default to the global `tls`,
+/// `tls_ca_certs` and `tls_client_cert` settings when unset.
+#[derive(Clone, Debug, Default, Allocative)]
+pub struct Buck2OssReServiceTlsConfiguration {
+    pub tls: Option<bool>,
+    pub tls_ca_certs: Option<String>,
+    pub tls_client_cert: Option<String>,
+}
+
+impl Buck2OssReServiceTlsConfiguration {
+    fn from_legacy_config(legacy_config: &LegacyBuckConfig, service: &str) -> anyhow::Result<Self> {
+        Ok(Self {
+            tls: legacy_config.parse(BUCK2_RE_CLIENT_CFG_SECTION, &format!("{}_tls", service))?,
+            tls_ca_certs: legacy_config.parse(
+                BUCK2_RE_CLIENT_CFG_SECTION,
+                &format!("{}_tls_ca_certs", service),
+            )?,
+            tls_client_cert: legacy_config.parse(
+                BUCK2_RE_CLIENT_CFG_SECTION,
+                &format!("{}_tls_client_cert", service),
+            )?,
+        })
+    }
 }
 
 #[derive(Clone, Debug, Default, Allocative)]
@@ -205,6 +263,17 @@ impl Buck2OssReConfiguration {
             action_cache_address: legacy_config
                 .parse(BUCK2_RE_CLIENT_CFG_SECTION, "action_cache_address")?
                 .or(default_address),
+            http_cache_address: legacy_config
+                .parse(BUCK2_RE_CLIENT_CFG_SECTION, "http_cache_address")?,
+            cas_tls: Buck2OssReServiceTlsConfiguration::from_legacy_config(legacy_config, "cas")?,
+            engine_tls: Buck2OssReServiceTlsConfiguration::from_legacy_config(
+                legacy_config,
+                "engine",
+            )?,
+            action_cache_tls: Buck2OssReServiceTlsConfiguration::from_legacy_config(
+                legacy_config,
+                "action_cache",
+            )?,
             tls: legacy_config
                 .parse(BUCK2_RE_CLIENT_CFG_SECTION, "tls")?
                 .unwrap_or(true),
@@ -215,6 +284,14 @@ impl Buck2OssReConfiguration {
                 .unwrap_or_default(), // Empty list is as good None.
             capabilities: legacy_config.parse(BUCK2_RE_CLIENT_CFG_SECTION, "capabilities")?,
             instance_name: legacy_config.parse(BUCK2_RE_CLIENT_CFG_SECTION, "instance_name")?,
+            compression: legacy_config.parse(BUCK2_RE_CLIENT_CFG_SECTION, "compression")?,
+            retry_max_attempts: legacy_config
+                .parse(BUCK2_RE_CLIENT_CFG_SECTION, "retry_max_attempts")?,
+            retry_initial_backoff_ms: legacy_config
+                .parse(BUCK2_RE_CLIENT_CFG_SECTION, "retry_initial_backoff_ms")?,
+            retry_max_backoff_ms: legacy_config
+                .parse(BUCK2_RE_CLIENT_CFG_SECTION, "retry_max_backoff_ms")?,
+            rpc_timeout_ms: legacy_config.parse(BUCK2_RE_CLIENT_CFG_SECTION, "rpc_timeout_ms")?,
         })
     }
 }
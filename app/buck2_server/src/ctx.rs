@@ -31,6 +31,7 @@ use buck2_build_api::interpreter::context::configure_package_file_globals;
 use buck2_build_api::interpreter::context::prelude_path;
 use buck2_build_api::keep_going::HasKeepGoing;
 use buck2_build_api::spawner::BuckSpawner;
+use buck2_build_api::subscription_events::SubscriptionEvents;
 use buck2_cli_proto::client_context::HostArchOverride;
 use buck2_cli_proto::client_context::HostPlatformOverride;
 use buck2_cli_proto::common_build_options::ExecutionStrategy;
@@ -80,6 +81,7 @@ use buck2_execute::re::manager::ReConnectionHandle;
 use buck2_execute::re::manager::ReConnectionManager;
 use buck2_execute::re::manager::ReConnectionObserver;
 use buck2_execute_impl::executors::worker::WorkerPool;
+use buck2_execute_impl::local_action_cache::LocalActionCache;
 use buck2_execute_impl::low_pass_filter::LowPassFilter;
 use buck2_forkserver::client::ForkserverClient;
 use buck2_interpreter::bxl::CONFIGURE_BXL_FILE_GLOBALS;
@@ -170,6 +172,10 @@ pub struct BaseServerCommandContext {
     pub create_unhashed_outputs_lock: Arc<Mutex<()>>,
     /// Http client used during run actions; shared with materializer.
     pub http_client: Arc<dyn HttpClient>,
+    /// The local on-disk action cache, if enabled.
+    pub local_action_cache: Option<Arc<LocalActionCache>>,
+    /// Notifications for `buck2 subscribe` clients.
+    pub subscription_events: Arc<SubscriptionEvents>,
 }
 
 /// ServerCommandContext provides access to the global daemon state and information about the calling client for
@@ -415,6 +421,7 @@ impl<'a> ServerCommandContext<'a> {
                 .as_ref()
                 .map_or(false, |opts| opts.keep_going),
             http_client: self.base_context.http_client.dupe(),
+            local_action_cache: self.base_context.local_action_cache.dupe(),
         }
     }
 
@@ -514,6 +521,7 @@ struct DiceCommandDataProvider {
     starlark_debugger: Option<BuckStarlarkDebuggerHandle>,
     keep_going: bool,
     http_client: Arc<dyn HttpClient>,
+    local_action_cache: Option<Arc<LocalActionCache>>,
 }
 
 #[async_trait]
@@ -606,6 +614,7 @@ impl DiceDataProvider for DiceCommandDataProvider {
             self.forkserver.dupe(),
             self.skip_cache_read,
             self.skip_cache_write,
+            self.local_action_cache.dupe(),
             ctx.global_data()
                 .get_io_provider()
                 .project_root()
@@ -699,6 +708,9 @@ impl<'a> Drop for ServerCommandContext<'a> {
     fn drop(&mut self) {
         // Ensure we cancel the heartbeat guard first.
         std::mem::drop(self.heartbeat_guard_handle.take());
+        self.base_context
+            .materializer
+            .command_finished(self.base_context.events.trace_id());
     }
 }
 
@@ -31,6 +31,7 @@ use buck2_execute::execute::dice_data::CommandExecutorResponse;
 use buck2_execute::execute::dice_data::HasCommandExecutor;
 use buck2_execute::execute::prepared::NoOpCommandExecutor;
 use buck2_execute::execute::prepared::PreparedCommandExecutor;
+use buck2_execute::execute::prepared::PreparedCommandOptionalExecutor;
 use buck2_execute::execute::request::ExecutorPreference;
 use buck2_execute::knobs::ExecutorGlobalKnobs;
 use buck2_execute::materialize::materializer::Materializer;
@@ -41,6 +42,7 @@ use buck2_execute_impl::executors::hybrid::HybridExecutor;
 use buck2_execute_impl::executors::local::LocalExecutor;
 use buck2_execute_impl::executors::re::ReExecutor;
 use buck2_execute_impl::executors::worker::WorkerPool;
+use buck2_execute_impl::local_action_cache::LocalActionCache;
 use buck2_execute_impl::low_pass_filter::LowPassFilter;
 use buck2_forkserver::client::ForkserverClient;
 use dupe::Dupe;
@@ -76,6 +78,7 @@ pub struct CommandExecutorFactory {
     pub forkserver: Option<ForkserverClient>,
     pub skip_cache_read: bool,
     pub skip_cache_write: bool,
+    pub local_action_cache: Option<Arc<LocalActionCache>>,
     project_root: ProjectRoot,
     worker_pool: Arc<WorkerPool>,
 }
@@ -93,6 +96,7 @@ impl CommandExecutorFactory {
         forkserver: Option<ForkserverClient>,
         skip_cache_read: bool,
         skip_cache_write: bool,
+        local_action_cache: Option<Arc<LocalActionCache>>,
         project_root: ProjectRoot,
         worker_pool: Arc<WorkerPool>,
     ) -> Self {
@@ -108,6 +112,7 @@ impl CommandExecutorFactory {
             forkserver,
             skip_cache_read,
             skip_cache_write,
+            local_action_cache,
             project_root,
             worker_pool,
         }
@@ -135,6 +140,7 @@ impl HasCommandExecutor for CommandExecutorFactory {
                 self.forkserver.dupe(),
                 self.executor_global_knobs.dupe(),
                 worker_pool,
+                options.sandbox,
             )
         };
 
@@ -181,15 +187,79 @@ impl HasCommandExecutor for CommandExecutorFactory {
             }
         };
 
+        // NOTE: While we now have a legit flag for this, we keep the env var. This has been used
+        // in remediating prod incidents in the past, and this is the kind of thing that can easily
+        // become tribal knowledge. Keeping this does not hurt us.
+        static DISABLE_CACHING: EnvHelper<bool> = EnvHelper::new("BUCK2_TEST_DISABLE_CACHING");
+
+        let disable_caching = DISABLE_CACHING.get_copied()?;
+
+        // `--no-remote-cache` only applies to the remote cache, so only the env var disables the
+        // local action cache.
+        let local_action_cache = if disable_caching == Some(true) {
+            None
+        } else {
+            self.local_action_cache.dupe()
+        };
+
+        let caching_executor_new = |inner_executor: Option<Arc<dyn PreparedCommandExecutor>>,
+                                    re_use_case: &RemoteExecutorUseCase,
+                                    cache_upload_behavior: &CacheUploadBehavior,
+                                    remote_cache_enabled: bool|
+         -> (
+            Option<Arc<dyn PreparedCommandExecutor>>,
+            Arc<dyn PreparedCommandOptionalExecutor>,
+        ) {
+            let remote_cache_enabled =
+                remote_cache_enabled && !disable_caching.unwrap_or(self.skip_cache_read);
+
+            if !remote_cache_enabled && local_action_cache.is_none() {
+                return (inner_executor, Arc::new(NoOpCommandExecutor {}));
+            }
+
+            (
+                inner_executor.map(|inner_executor| {
+                    Arc::new(CachingExecutor {
+                        inner: inner_executor,
+                        artifact_fs: artifact_fs.clone(),
+                        materializer: self.materializer.dupe(),
+                        re_client: self.re_connection.get_client(),
+                        re_use_case: *re_use_case,
+                        upload_all_actions: self.upload_all_actions,
+                        knobs: self.executor_global_knobs.dupe(),
+                        cache_upload_behavior: *cache_upload_behavior,
+                        remote_cache_enabled,
+                        local_action_cache: local_action_cache.dupe(),
+                    }) as _
+                }),
+                Arc::new(ActionCacheChecker {
+                    artifact_fs: artifact_fs.clone(),
+                    materializer: self.materializer.dupe(),
+                    re_client: self.re_connection.get_client(),
+                    re_use_case: *re_use_case,
+                    upload_all_actions: self.upload_all_actions,
+                    remote_cache_enabled,
+                    local_action_cache: local_action_cache.dupe(),
+                }),
+            )
+        };
+
         let response = match &executor_config.executor {
             Executor::Local(local) => {
                 if self.strategy.ban_local() {
                     None
                 } else {
-                    Some(CommandExecutorResponse {
-                        executor: Arc::new(local_executor_new(local)),
+                    let (executor, cache_checker) = caching_executor_new(
+                        Some(Arc::new(local_executor_new(local))),
+                        &RemoteExecutorUseCase::buck2_default(),
+                        &CacheUploadBehavior::Disabled,
+                        false,
+                    );
+
+                    executor.map(|executor| CommandExecutorResponse {
+                        executor,
                         platform: Default::default(),
-                        cache_checker: Arc::new(NoOpCommandExecutor {}),
+                        cache_checker,
                     })
                 }
             }
@@ -225,41 +295,12 @@ impl HasCommandExecutor for CommandExecutorFactory {
                     _ => None,
                 };
 
-                // NOTE: While we now have a legit flag for this, we keep the env var. This has been used
-                // in remediating prod incidents in the past, and this is the kind of thing that can easily
-                // become tribal knowledge. Keeping this does not hurt us.
-                static DISABLE_CACHING: EnvHelper<bool> =
-                    EnvHelper::new("BUCK2_TEST_DISABLE_CACHING");
-
-                let disable_caching = DISABLE_CACHING
-                    .get_copied()?
-                    .unwrap_or(self.skip_cache_read);
-
-                let (executor, cache_checker) = if disable_caching || !remote_cache_enabled {
-                    (inner_executor, Arc::new(NoOpCommandExecutor {}) as _)
-                } else {
-                    (
-                        inner_executor.map(|inner_executor| {
-                            Arc::new(CachingExecutor {
-                                inner: inner_executor,
-                                artifact_fs: artifact_fs.clone(),
-                                materializer: self.materializer.dupe(),
-                                re_client: self.re_connection.get_client(),
-                                re_use_case: *re_use_case,
-                                upload_all_actions: self.upload_all_actions,
-                                knobs: self.executor_global_knobs.dupe(),
-                                cache_upload_behavior: *cache_upload_behavior,
-                            }) as _
-                        }),
-                        Arc::new(ActionCacheChecker {
-                            artifact_fs: artifact_fs.clone(),
-                            materializer: self.materializer.dupe(),
-                            re_client: self.re_connection.get_client(),
-                            re_use_case: *re_use_case,
-                            upload_all_actions: self.upload_all_actions,
-                        }) as _,
-                    )
-                };
+                let (executor, cache_checker) = caching_executor_new(
+                    inner_executor,
+                    re_use_case,
+                    cache_upload_behavior,
+                    *remote_cache_enabled,
+                );
 
                 let platform = RE::Platform {
                     properties: re_properties
@@ -352,6 +393,7 @@ pub fn get_default_executor_config(host_platform: HostPlatformOverride) -> Comma
             path_separator: PathSeparatorKind::system_default(),
             output_paths_behavior: Default::default(),
         },
+        action_timeout: None,
     }
 }
 
@@ -27,6 +27,7 @@ use async_trait::async_trait;
 use buck2_build_api::build_signals;
 use buck2_build_api::configure_dice::configure_dice_for_buck;
 use buck2_build_api::spawner::BuckSpawner;
+use buck2_build_api::subscription_events::SubscriptionEvents;
 use buck2_cli_proto::daemon_api_server::*;
 use buck2_cli_proto::*;
 use buck2_common::buckd_connection::BUCK_AUTH_TOKEN_HEADER;
@@ -151,10 +152,12 @@ impl BuckdServerInitPreferences {
         io: Arc<dyn IoProvider>,
         digest_config: DigestConfig,
         root_config: &LegacyBuckConfig,
+        subscription_events: Arc<SubscriptionEvents>,
     ) -> anyhow::Result<Arc<Dice>> {
         configure_dice_for_buck(
             io,
             digest_config,
+            subscription_events,
             Some(root_config),
             self.detect_cycles,
             self.which_dice,
@@ -1353,7 +1356,13 @@ impl DaemonApi for BuckdServer {
              partial_result_dispatcher,
              _client_ctx,
              req: StreamingRequestHandler<SubscriptionRequestWrapper>| {
-                run_subscription_server_command(ctx, partial_result_dispatcher, req).boxed()
+                run_subscription_server_command(
+                    ctx,
+                    ctx.base_context.subscription_events.dupe(),
+                    partial_result_dispatcher,
+                    req,
+                )
+                .boxed()
             },
         )
         .await
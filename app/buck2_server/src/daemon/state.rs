@@ -17,6 +17,7 @@ use std::time::Instant;
 use allocative::Allocative;
 use anyhow::Context;
 use buck2_build_api::build_signals::CriticalPathBackendName;
+use buck2_build_api::subscription_events::SubscriptionEvents;
 use buck2_cli_proto::unstable_dice_dump_request::DiceDumpFormat;
 use buck2_common::cas_digest::DigestAlgorithm;
 use buck2_common::cas_digest::DigestAlgorithmKind;
@@ -32,6 +33,7 @@ use buck2_core::cells::name::CellName;
 use buck2_core::env_helper::EnvHelper;
 use buck2_core::facebook_only;
 use buck2_core::fs::fs_util;
+use buck2_core::fs::paths::abs_norm_path::AbsNormPathBuf;
 use buck2_core::fs::project::ProjectRoot;
 use buck2_core::fs::project_rel_path::ProjectRelativePathBuf;
 use buck2_core::rollout_percentage::RolloutPercentage;
@@ -47,10 +49,14 @@ use buck2_execute::execute::blocking::BuckBlockingExecutor;
 use buck2_execute::materialize::materializer::MaterializationMethod;
 use buck2_execute::materialize::materializer::Materializer;
 use buck2_execute::re::manager::ReConnectionManager;
+use buck2_execute_impl::local_action_cache::LocalActionCache;
+use buck2_execute_impl::local_action_cache::LocalActionCacheConfig;
 use buck2_execute_impl::materializers::deferred::DeferredMaterializer;
 use buck2_execute_impl::materializers::deferred::DeferredMaterializerConfigs;
+use buck2_execute_impl::materializers::deferred::GcConfiguration;
 use buck2_execute_impl::materializers::deferred::TtlRefreshConfiguration;
 use buck2_execute_impl::materializers::immediate::ImmediateMaterializer;
+use buck2_execute_impl::materializers::local_cas::LocalCasConfig;
 use buck2_execute_impl::materializers::sqlite::MaterializerState;
 use buck2_execute_impl::materializers::sqlite::MaterializerStateIdentity;
 use buck2_execute_impl::materializers::sqlite::MaterializerStateSqliteDb;
@@ -162,6 +168,14 @@ pub struct DaemonStateData {
 
     /// Are we using buck-out as our cwd?
     pub cwd_buck_out: bool,
+
+    /// The local on-disk action cache, if enabled. This lives for the lifetime of the daemon so
+    /// that we keep track of the cache's size across commands.
+    #[allocative(skip)]
+    pub local_action_cache: Option<Arc<LocalActionCache>>,
+
+    /// Notifications for `buck2 subscribe` clients. DICE reports invalidations to this.
+    pub(crate) subscription_events: Arc<SubscriptionEvents>,
 }
 
 impl DaemonStateData {
@@ -302,6 +316,8 @@ impl DaemonState {
             MaterializationMethod::try_new_from_config(legacy_configs.get(cells.root_cell()).ok())?;
         let disk_state_options = DiskStateOptions::new(root_config, materialization_method.dupe())?;
         let blocking_executor = Arc::new(BuckBlockingExecutor::default_concurrency(fs.dupe())?);
+        let local_action_cache = LocalActionCacheConfig::from_config(root_config, &fs)?
+            .map(|config| Arc::new(LocalActionCache::new(config, blocking_executor.dupe())));
         let cache_dir_path = paths.cache_dir_path();
         let valid_cache_dirs = paths.valid_cache_dirs();
         let fs_duped = fs.dupe();
@@ -327,6 +343,36 @@ impl DaemonState {
                 .unwrap_or_else(RolloutPercentage::never)
                 .roll();
 
+            // When set, artifacts are evicted from buck-out, least recently accessed first, to
+            // keep it under that many bytes.
+            let gc_max_bytes = root_config.parse("buck2", "materializer_gc_max_bytes")?;
+
+            let gc_frequency = root_config
+                .parse("buck2", "materializer_gc_frequency_seconds")?
+                .unwrap_or(300);
+            if gc_frequency == 0 {
+                return Err(anyhow::anyhow!(
+                    "`buck2.materializer_gc_frequency_seconds` must be greater than 0"
+                ));
+            }
+
+            let local_cas = root_config
+                .get("buck2", "local_cas_dir")
+                .map(|dir| {
+                    anyhow::Ok(LocalCasConfig {
+                        root: AbsNormPathBuf::try_from(dir.to_owned())
+                            .context("`buck2.local_cas_dir` must be an absolute path")?,
+                        verify: root_config
+                            .parse("buck2", "local_cas_verify")?
+                            .unwrap_or(false),
+                        hardlinks: root_config
+                            .parse("buck2", "local_cas_hardlinks")?
+                            .unwrap_or(false),
+                        max_bytes: root_config.parse("buck2", "local_cas_max_bytes")?,
+                    })
+                })
+                .transpose()?;
+
             DeferredMaterializerConfigs {
                 materialize_final_artifacts: matches!(
                     materialization_method,
@@ -338,6 +384,11 @@ impl DaemonState {
                     min_ttl: chrono::Duration::seconds(ttl_refresh_min_ttl),
                     enabled: ttl_refresh_enabled,
                 },
+                gc: GcConfiguration {
+                    frequency: std::time::Duration::from_secs(gc_frequency),
+                    max_bytes: gc_max_bytes,
+                },
+                local_cas,
             }
         };
 
@@ -402,8 +453,15 @@ impl DaemonState {
         let forkserver =
             maybe_launch_forkserver(root_config, &paths.forkserver_state_dir()).await?;
 
+        let subscription_events = SubscriptionEvents::new();
+
         let dice = init_ctx
-            .construct_dice(io.dupe(), digest_config, root_config)
+            .construct_dice(
+                io.dupe(),
+                digest_config,
+                root_config,
+                subscription_events.dupe(),
+            )
             .await?;
 
         // TODO(cjhopman): We want to use Expr::True here, but we need to workaround
@@ -411,10 +469,12 @@ impl DaemonState {
         // this list should be safe until we can revert it to Expr::True.
 
         let file_watcher = <dyn FileWatcher>::new(
-            paths.project_root(),
+            paths,
             root_config,
             cells.dupe(),
             ignore_specs,
+            digest_config.cas_digest_config(),
+            subscription_events.dupe(),
         )
         .with_context(|| {
             format!(
@@ -505,6 +565,8 @@ impl DaemonState {
             enable_restarter,
             http_client,
             cwd_buck_out,
+            local_action_cache,
+            subscription_events,
         }))
     }
 
@@ -668,6 +730,7 @@ impl DaemonState {
                 data.disk_state_options.sqlite_materializer_state
             ),
             format!("cwd-buck-out:{}", data.cwd_buck_out),
+            format!("local-action-cache:{}", data.local_action_cache.is_some()),
         ];
 
         dispatcher.instant_event(buck2_data::TagEvent { tags });
@@ -696,6 +759,8 @@ impl DaemonState {
             daemon_start_time: data.start_time,
             create_unhashed_outputs_lock: data.create_unhashed_outputs_lock.dupe(),
             http_client: data.http_client.dupe(),
+            local_action_cache: data.local_action_cache.dupe(),
+            subscription_events: data.subscription_events.dupe(),
         })
     }
 
@@ -13,18 +13,22 @@ use std::sync::Arc;
 use allocative::Allocative;
 use anyhow::Context;
 use async_trait::async_trait;
+use buck2_build_api::subscription_events::SubscriptionEvents;
+use buck2_common::cas_digest::CasDigestConfig;
 use buck2_common::ignores::ignore_set::IgnoreSet;
+use buck2_common::invocation_paths::InvocationPaths;
 use buck2_common::legacy_configs::LegacyBuckConfig;
 use buck2_core::cells::name::CellName;
 use buck2_core::cells::CellResolver;
-use buck2_core::fs::project::ProjectRoot;
 use buck2_core::is_open_source;
 use dice::DiceTransactionUpdater;
 
 use crate::file_watcher::notify::NotifyFileWatcher;
+use crate::file_watcher::poll::PollFileWatcher;
 use crate::file_watcher::watchman::interface::WatchmanFileWatcher;
 
 mod notify;
+mod poll;
 mod stats;
 mod watchman;
 
@@ -37,11 +41,14 @@ impl dyn FileWatcher {
     /// Create a new FileWatcher. Note that this is not async, since it's called during daemon
     /// startup and shouldn't be doing any work that could warrant suspending.
     pub fn new(
-        project_root: &ProjectRoot,
+        paths: &InvocationPaths,
         root_config: &LegacyBuckConfig,
         cells: CellResolver,
         ignore_specs: HashMap<CellName, IgnoreSet>,
+        digest_config: CasDigestConfig,
+        subscription_events: Arc<SubscriptionEvents>,
     ) -> anyhow::Result<Arc<dyn FileWatcher>> {
+        let project_root = paths.project_root();
         let default = if is_open_source() {
             "notify"
         } else {
@@ -50,13 +57,39 @@ impl dyn FileWatcher {
 
         match root_config.get("buck2", "file_watcher").unwrap_or(default) {
             "watchman" => Ok(Arc::new(
-                WatchmanFileWatcher::new(project_root.root(), root_config, cells, ignore_specs)
-                    .context("Creating watchman file watcher")?,
+                WatchmanFileWatcher::new(
+                    project_root.root(),
+                    root_config,
+                    cells,
+                    ignore_specs,
+                    subscription_events,
+                )
+                .context("Creating watchman file watcher")?,
             )),
             "notify" => Ok(Arc::new(
-                NotifyFileWatcher::new(project_root, cells, ignore_specs)
+                NotifyFileWatcher::new(project_root, cells, ignore_specs, subscription_events)
                     .context("Creating notify file watcher")?,
             )),
+            "poll" => {
+                // Hashing file contents avoids spurious invalidations when files are touched
+                // without being modified, at the cost of reading every file when we take the
+                // first snapshot.
+                let digest_config = root_config
+                    .parse::<bool>("buck2", "file_watcher_poll_hash_contents")?
+                    .unwrap_or(false)
+                    .then_some(digest_config);
+                Ok(Arc::new(
+                    PollFileWatcher::new(
+                        project_root,
+                        cells,
+                        ignore_specs,
+                        digest_config,
+                        paths.file_watcher_state_path(),
+                        subscription_events,
+                    )
+                    .context("Creating poll file watcher")?,
+                ))
+            }
             other => Err(anyhow::anyhow!("Invalid buck2.file_watcher: {}", other)),
         }
     }
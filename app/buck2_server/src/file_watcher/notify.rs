@@ -14,6 +14,7 @@ use std::sync::Mutex;
 
 use allocative::Allocative;
 use async_trait::async_trait;
+use buck2_build_api::subscription_events::SubscriptionEvents;
 use buck2_common::dice::file_ops::FileChangeTracker;
 use buck2_common::ignores::ignore_set::IgnoreSet;
 use buck2_common::invocation_paths::InvocationPaths;
@@ -136,7 +137,10 @@ impl NotifyFileData {
         Ok(())
     }
 
-    fn sync(self) -> (buck2_data::FileWatcherStats, FileChangeTracker) {
+    fn sync(
+        self,
+        subscription_events: &Arc<SubscriptionEvents>,
+    ) -> (buck2_data::FileWatcherStats, FileChangeTracker) {
         // The changes that go into the DICE transaction
         let mut changed = FileChangeTracker::new();
         // The files that were changed for accumulating the stats
@@ -158,7 +162,7 @@ impl NotifyFileData {
             changed_paths.insert(cell_path_str);
         }
 
-        let mut stats = FileWatcherStats::new(changed_paths.len(), None, None);
+        let mut stats = FileWatcherStats::new(changed_paths.len(), None, None, subscription_events);
         stats.add_ignored(self.ignored);
         for path in changed_paths {
             // The event type and watcher kind are just made up, but that's not a big deal
@@ -180,6 +184,7 @@ pub struct NotifyFileWatcher {
     #[allocative(skip)]
     watcher: RecommendedWatcher,
     data: Arc<Mutex<anyhow::Result<NotifyFileData>>>,
+    subscription_events: Arc<SubscriptionEvents>,
 }
 
 impl NotifyFileWatcher {
@@ -187,6 +192,7 @@ impl NotifyFileWatcher {
         root: &ProjectRoot,
         cells: CellResolver,
         ignore_specs: HashMap<CellName, IgnoreSet>,
+        subscription_events: Arc<SubscriptionEvents>,
     ) -> anyhow::Result<Self> {
         let data = Arc::new(Mutex::new(Ok(NotifyFileData::new())));
         let data2 = data.dupe();
@@ -200,7 +206,11 @@ impl NotifyFileWatcher {
             }
         })?;
         watcher.watch(root.root().as_path(), notify::RecursiveMode::Recursive)?;
-        Ok(Self { watcher, data })
+        Ok(Self {
+            watcher,
+            data,
+            subscription_events,
+        })
     }
 
     fn sync2(
@@ -209,7 +219,7 @@ impl NotifyFileWatcher {
     ) -> anyhow::Result<(buck2_data::FileWatcherStats, DiceTransactionUpdater)> {
         let mut guard = self.data.lock().unwrap();
         let old = mem::replace(&mut *guard, Ok(NotifyFileData::new()));
-        let (stats, changes) = old?.sync();
+        let (stats, changes) = old?.sync(&self.subscription_events);
         changes.write_to_dice(&mut dice)?;
         Ok((stats, dice))
     }
@@ -0,0 +1,836 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+//! A file watcher that doesn't rely on OS notifications: on every sync, we walk the repo and
+//! compare what we find with a snapshot taken on the previous sync. This is slow on big repos,
+//! but works on file systems where neither Watchman nor inotify are usable (e.g. some network
+//! file systems, or containers with bind mounts).
+//!
+//! The snapshot of each cell is saved under buck-out after every sync that changed it, so that
+//! after a restart we can still report what changed while the daemon was down.
+
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::fs;
+use std::io;
+use std::io::BufReader;
+use std::io::BufWriter;
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::SystemTime;
+
+use allocative::Allocative;
+use anyhow::Context;
+use async_trait::async_trait;
+use buck2_build_api::subscription_events::SubscriptionEvents;
+use buck2_common::cas_digest::CasDigestConfig;
+use buck2_common::dice::file_ops::FileChangeTracker;
+use buck2_common::file_ops::FileDigest;
+use buck2_common::file_ops::FileDigestConfig;
+use buck2_common::ignores::ignore_set::IgnoreSet;
+use buck2_common::invocation_paths::InvocationPaths;
+use buck2_core::cells::cell_path::CellPath;
+use buck2_core::cells::name::CellName;
+use buck2_core::cells::paths::CellRelativePathBuf;
+use buck2_core::cells::CellResolver;
+use buck2_core::fs::fs_util;
+use buck2_core::fs::paths::abs_norm_path::AbsNormPathBuf;
+use buck2_core::fs::paths::file_name::FileName;
+use buck2_core::fs::project::ProjectRoot;
+use buck2_core::fs::project_rel_path::ProjectRelativePath;
+use buck2_events::dispatch::span_async;
+use dice::DiceTransactionUpdater;
+use dupe::Dupe;
+use serde::Deserialize;
+use serde::Serialize;
+use tracing::warn;
+
+use crate::file_watcher::stats::FileWatcherStats;
+use crate::file_watcher::FileWatcher;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+enum EntryKind {
+    File,
+    Dir,
+    Symlink(PathBuf),
+}
+
+impl EntryKind {
+    fn watcher_kind(&self) -> buck2_data::FileWatcherKind {
+        match self {
+            Self::File => buck2_data::FileWatcherKind::File,
+            Self::Dir => buck2_data::FileWatcherKind::Directory,
+            Self::Symlink(_) => buck2_data::FileWatcherKind::Symlink,
+        }
+    }
+}
+
+/// What we remember about a path between two syncs.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Entry {
+    kind: EntryKind,
+    len: u64,
+    mtime: Option<SystemTime>,
+    /// Lets us notice files that were replaced (e.g. by an atomic rename) without their size or
+    /// mtime changing.
+    ino: u64,
+    is_executable: bool,
+    /// Only computed if content hashing is enabled, and only for files.
+    digest: Option<FileDigest>,
+}
+
+impl Entry {
+    fn new(kind: EntryKind, meta: &fs::Metadata) -> Self {
+        #[cfg(unix)]
+        let (ino, is_executable) = {
+            use std::os::unix::fs::MetadataExt;
+            (meta.ino(), meta.mode() & 0o111 != 0)
+        };
+        #[cfg(not(unix))]
+        let (ino, is_executable) = (0, false);
+
+        Self {
+            kind,
+            len: meta.len(),
+            mtime: meta.modified().ok(),
+            ino,
+            is_executable,
+            digest: None,
+        }
+    }
+
+    /// Whether the metadata we got from the file system is the same, regardless of the digest.
+    fn same_metadata(&self, other: &Self) -> bool {
+        self.kind == other.kind
+            && self.len == other.len
+            && self.mtime == other.mtime
+            && self.ino == other.ino
+            && self.is_executable == other.is_executable
+    }
+
+    /// Whether this entry changed in a way that matters to the build, assuming it's the same kind
+    /// of entry as `old`.
+    fn modified_since(&self, old: &Self) -> bool {
+        match (&self.kind, &old.digest, &self.digest) {
+            // Directory metadata changes when entries are added or removed, but we track those
+            // individually.
+            (EntryKind::Dir, _, _) => false,
+            // If we know what's in the file, we don't need to care about e.g. the mtime.
+            (EntryKind::File, Some(old_digest), Some(new_digest)) => {
+                old_digest != new_digest || old.is_executable != self.is_executable
+            }
+            _ => !self.same_metadata(old),
+        }
+    }
+}
+
+type CellSnapshot = HashMap<CellRelativePathBuf, Entry>;
+
+/// The state of all the files we watch at a point in time. A cell is missing if we don't know
+/// what it looked like (e.g. on the first sync, if nothing was saved to disk).
+#[derive(Default)]
+struct Snapshot {
+    cells: HashMap<CellName, CellSnapshot>,
+}
+
+impl Snapshot {
+    fn get(&self, path: &CellPath) -> Option<&Entry> {
+        self.cells.get(&path.cell())?.get(path.path())
+    }
+}
+
+#[derive(Debug, Clone, Copy, Dupe, PartialEq, Eq, PartialOrd, Ord)]
+enum ChangeType {
+    Create,
+    Modify,
+    Delete,
+}
+
+impl ChangeType {
+    fn event_type(self) -> buck2_data::FileWatcherEventType {
+        match self {
+            Self::Create => buck2_data::FileWatcherEventType::Create,
+            Self::Modify => buck2_data::FileWatcherEventType::Modify,
+            Self::Delete => buck2_data::FileWatcherEventType::Delete,
+        }
+    }
+}
+
+/// Walks the repo to produce a `Snapshot`.
+struct Scanner<'a> {
+    root: &'a ProjectRoot,
+    cells: &'a CellResolver,
+    ignore_specs: &'a HashMap<CellName, IgnoreSet>,
+    digest_config: Option<FileDigestConfig>,
+    /// The previous snapshot, so that we only hash files whose metadata changed.
+    previous: Option<&'a Snapshot>,
+}
+
+impl<'a> Scanner<'a> {
+    fn scan(&self) -> anyhow::Result<Snapshot> {
+        let mut cells: HashMap<CellName, CellSnapshot> = self
+            .cells
+            .cells()
+            .map(|(name, _)| (name, HashMap::new()))
+            .collect();
+        let mut queue = vec![ProjectRelativePath::empty().to_buf()];
+
+        while let Some(dir) = queue.pop() {
+            // The directory might have been removed since we listed its parent, in which case
+            // we'll just pick up on it during the next sync.
+            let read_dir = match fs_util::read_dir_if_exists(self.root.resolve(&dir))? {
+                Some(read_dir) => read_dir,
+                None => continue,
+            };
+
+            for dir_entry in read_dir {
+                let dir_entry = dir_entry?;
+                let file_name = dir_entry.file_name();
+                let file_name = match file_name.to_str() {
+                    Some(file_name) => FileName::new(file_name)?,
+                    None => {
+                        warn!(
+                            "FileWatcher: ignoring non-UTF-8 path in `{}`: {:?}",
+                            dir, file_name
+                        );
+                        continue;
+                    }
+                };
+                let path = dir.join(file_name);
+
+                // Like the notify watcher, we never look at buck-out: it's big, and only modified
+                // by us.
+                if path.starts_with(InvocationPaths::buck_out_dir_prefix()) {
+                    continue;
+                }
+
+                let cell_path = self.cells.get_cell_path(&path)?;
+                // If a directory is ignored, then we also don't look at anything it contains.
+                if self
+                    .ignore_specs
+                    .get(&cell_path.cell())
+                    .expect("unexpected cell name mismatch")
+                    .is_match(cell_path.path())
+                {
+                    continue;
+                }
+
+                let abs_path = dir_entry.path();
+                let meta = match fs_util::symlink_metadata_if_exists(&abs_path)? {
+                    Some(meta) => meta,
+                    None => continue,
+                };
+
+                let kind = if meta.is_symlink() {
+                    match fs::read_link(&abs_path) {
+                        Ok(target) => EntryKind::Symlink(target),
+                        Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+                        Err(e) => {
+                            return Err(e).with_context(|| format!("read_link({})", abs_path));
+                        }
+                    }
+                } else if meta.is_dir() {
+                    queue.push(path);
+                    EntryKind::Dir
+                } else {
+                    EntryKind::File
+                };
+
+                let mut entry = Entry::new(kind, &meta);
+                if let (EntryKind::File, Some(digest_config)) = (&entry.kind, self.digest_config) {
+                    entry.digest = match self.previous.and_then(|p| p.get(&cell_path)) {
+                        Some(old) if old.digest.is_some() && old.same_metadata(&entry) => {
+                            old.digest.clone()
+                        }
+                        // If we can't read the file (e.g. because it was just deleted), we'll
+                        // just rely on its metadata.
+                        _ => FileDigest::from_file(&abs_path, digest_config).ok(),
+                    };
+                }
+
+                cells
+                    .entry(cell_path.cell())
+                    .or_default()
+                    .insert(cell_path.path().to_buf(), entry);
+            }
+        }
+
+        Ok(Snapshot { cells })
+    }
+}
+
+/// Compare two snapshots. A path that changed kind (e.g. from a file to a directory) is reported
+/// as deleted and created. Cells that are missing from `old` are not compared.
+fn diff(
+    old: &Snapshot,
+    new: &Snapshot,
+) -> Vec<(CellPath, ChangeType, buck2_data::FileWatcherKind)> {
+    let mut changes = Vec::new();
+    for (cell, new_entries) in &new.cells {
+        if let Some(old_entries) = old.cells.get(cell) {
+            diff_cell(*cell, old_entries, new_entries, &mut changes);
+        }
+    }
+    changes.sort();
+    changes
+}
+
+fn diff_cell(
+    cell: CellName,
+    old: &CellSnapshot,
+    new: &CellSnapshot,
+    changes: &mut Vec<(CellPath, ChangeType, buck2_data::FileWatcherKind)>,
+) {
+    let cell_path = |path: &CellRelativePathBuf| CellPath::new(cell, path.clone());
+
+    for (path, old_entry) in old {
+        let same_kind = match new.get(path) {
+            Some(new_entry) => {
+                std::mem::discriminant(&old_entry.kind) == std::mem::discriminant(&new_entry.kind)
+            }
+            None => false,
+        };
+        if !same_kind {
+            changes.push((
+                cell_path(path),
+                ChangeType::Delete,
+                old_entry.kind.watcher_kind(),
+            ));
+        }
+    }
+
+    for (path, new_entry) in new {
+        match old.get(path) {
+            Some(old_entry)
+                if std::mem::discriminant(&old_entry.kind)
+                    == std::mem::discriminant(&new_entry.kind) =>
+            {
+                if new_entry.modified_since(old_entry) {
+                    changes.push((
+                        cell_path(path),
+                        ChangeType::Modify,
+                        new_entry.kind.watcher_kind(),
+                    ));
+                }
+            }
+            _ => changes.push((
+                cell_path(path),
+                ChangeType::Create,
+                new_entry.kind.watcher_kind(),
+            )),
+        }
+    }
+}
+
+fn process_changes(
+    changes: Vec<(CellPath, ChangeType, buck2_data::FileWatcherKind)>,
+    subscription_events: &Arc<SubscriptionEvents>,
+) -> (buck2_data::FileWatcherStats, FileChangeTracker) {
+    let mut changed = FileChangeTracker::new();
+    let mut stats = FileWatcherStats::new(changes.len(), None, None, subscription_events);
+
+    for (cell_path, change_type, kind) in changes {
+        stats.add(cell_path.to_string(), change_type.event_type(), kind);
+        match (change_type, kind) {
+            (ChangeType::Modify, _) => changed.file_changed(cell_path),
+            (_, buck2_data::FileWatcherKind::Directory) => changed.dir_added_or_removed(cell_path),
+            (_, _) => changed.file_added_or_removed(cell_path),
+        }
+    }
+
+    (stats.finish(), changed)
+}
+
+/// Bump this when changing `PersistedCellSnapshot`, or what we record in it.
+const FORMAT_VERSION: u32 = 1;
+
+#[derive(Serialize, Deserialize)]
+struct PersistedCellSnapshot {
+    version: u32,
+    /// Digests are only comparable if they were computed with the same config.
+    digest_config: Option<String>,
+    entries: Vec<(String, PersistedEntry)>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct PersistedEntry {
+    kind: EntryKind,
+    len: u64,
+    mtime: Option<SystemTime>,
+    ino: u64,
+    is_executable: bool,
+    digest: Option<String>,
+}
+
+/// Saves the snapshot of each cell to its own file in `dir`, and restores them.
+struct SnapshotStore {
+    dir: AbsNormPathBuf,
+    digest_config: Option<FileDigestConfig>,
+}
+
+impl SnapshotStore {
+    fn digest_config_key(&self) -> Option<String> {
+        self.digest_config
+            .map(|config| format!("{:?}", config.as_cas_digest_config()))
+    }
+
+    fn cell_file(&self, cell: CellName, suffix: &str) -> anyhow::Result<AbsNormPathBuf> {
+        Ok(self
+            .dir
+            .join(FileName::new(&format!("{}{}", cell.as_str(), suffix))?))
+    }
+
+    /// Load the snapshots that were saved for the cells in `cells`. Cells whose snapshot is
+    /// missing or unusable are left out, so we won't report changes for them on the next sync.
+    fn load(&self, cells: &CellResolver) -> Snapshot {
+        let mut snapshot = Snapshot::default();
+        for (cell, _) in cells.cells() {
+            match self.load_cell(cell) {
+                Ok(Some(entries)) => {
+                    snapshot.cells.insert(cell, entries);
+                }
+                Ok(None) => {}
+                Err(e) => warn!(
+                    "FileWatcher: ignoring saved snapshot for cell `{}`: {:#}",
+                    cell, e
+                ),
+            }
+        }
+        snapshot
+    }
+
+    fn load_cell(&self, cell: CellName) -> anyhow::Result<Option<CellSnapshot>> {
+        let path = self.cell_file(cell, "")?;
+        let file = match fs::File::open(&path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e).with_context(|| format!("Error opening `{}`", path)),
+        };
+        let persisted: PersistedCellSnapshot = bincode::deserialize_from(BufReader::new(file))
+            .with_context(|| format!("Error reading `{}`", path))?;
+
+        if persisted.version != FORMAT_VERSION {
+            return Err(anyhow::anyhow!(
+                "Snapshot has version {}, expected {}",
+                persisted.version,
+                FORMAT_VERSION
+            ));
+        }
+        if persisted.digest_config != self.digest_config_key() {
+            return Err(anyhow::anyhow!(
+                "Snapshot was taken with a different digest config"
+            ));
+        }
+
+        let mut entries = HashMap::with_capacity(persisted.entries.len());
+        for (path, entry) in persisted.entries {
+            let digest = match (entry.digest, self.digest_config) {
+                (Some(digest), Some(config)) => {
+                    Some(FileDigest::parse_digest(&digest, config.as_cas_digest_config())?.0)
+                }
+                _ => None,
+            };
+            entries.insert(
+                CellRelativePathBuf::try_from(path)?,
+                Entry {
+                    kind: entry.kind,
+                    len: entry.len,
+                    mtime: entry.mtime,
+                    ino: entry.ino,
+                    is_executable: entry.is_executable,
+                    digest,
+                },
+            );
+        }
+        Ok(Some(entries))
+    }
+
+    fn save_cell(&self, cell: CellName, entries: &CellSnapshot) -> anyhow::Result<()> {
+        let persisted = PersistedCellSnapshot {
+            version: FORMAT_VERSION,
+            digest_config: self.digest_config_key(),
+            entries: entries
+                .iter()
+                .map(|(path, entry)| {
+                    (
+                        path.to_string(),
+                        PersistedEntry {
+                            kind: entry.kind.clone(),
+                            len: entry.len,
+                            mtime: entry.mtime,
+                            ino: entry.ino,
+                            is_executable: entry.is_executable,
+                            digest: entry.digest.as_ref().map(|d| d.to_string()),
+                        },
+                    )
+                })
+                .collect(),
+        };
+
+        // Write to a temporary file first so that we never leave a truncated snapshot behind.
+        fs_util::create_dir_all(&self.dir)?;
+        let tmp_path = self.cell_file(cell, ".tmp")?;
+        let mut writer = BufWriter::new(
+            fs::File::create(&tmp_path)
+                .with_context(|| format!("Error creating `{}`", tmp_path))?,
+        );
+        bincode::serialize_into(&mut writer, &persisted)?;
+        writer.flush()?;
+        drop(writer);
+        fs_util::rename(&tmp_path, &self.cell_file(cell, "")?)
+    }
+}
+
+#[derive(Allocative)]
+pub struct PollFileWatcher {
+    root: ProjectRoot,
+    cells: CellResolver,
+    #[allocative(skip)]
+    ignore_specs: Arc<HashMap<CellName, IgnoreSet>>,
+    #[allocative(skip)]
+    digest_config: Option<FileDigestConfig>,
+    /// The snapshot from the last sync. This is `None` until the first sync, which compares
+    /// against the snapshot saved by the previous daemon instead.
+    #[allocative(skip)]
+    snapshot: tokio::sync::Mutex<Option<Arc<Snapshot>>>,
+    #[allocative(skip)]
+    store: Arc<SnapshotStore>,
+    subscription_events: Arc<SubscriptionEvents>,
+}
+
+impl PollFileWatcher {
+    pub fn new(
+        root: &ProjectRoot,
+        cells: CellResolver,
+        ignore_specs: HashMap<CellName, IgnoreSet>,
+        digest_config: Option<CasDigestConfig>,
+        state_dir: AbsNormPathBuf,
+        subscription_events: Arc<SubscriptionEvents>,
+    ) -> anyhow::Result<Self> {
+        let digest_config = digest_config.map(FileDigestConfig::source);
+        Ok(Self {
+            root: root.dupe(),
+            cells,
+            ignore_specs: Arc::new(ignore_specs),
+            digest_config,
+            snapshot: tokio::sync::Mutex::new(None),
+            store: Arc::new(SnapshotStore {
+                dir: state_dir,
+                digest_config,
+            }),
+            subscription_events,
+        })
+    }
+
+    async fn sync2(
+        &self,
+        mut dice: DiceTransactionUpdater,
+    ) -> anyhow::Result<(buck2_data::FileWatcherStats, DiceTransactionUpdater)> {
+        let mut guard = self.snapshot.lock().await;
+        let previous = guard.clone();
+
+        let root = self.root.dupe();
+        let cells = self.cells.dupe();
+        let ignore_specs = self.ignore_specs.dupe();
+        let digest_config = self.digest_config;
+        let store = self.store.dupe();
+        let (snapshot, changes, dirty_cells) = tokio::task::spawn_blocking(move || {
+            let previous = match previous {
+                Some(previous) => previous,
+                None => Arc::new(store.load(&cells)),
+            };
+            let snapshot = Scanner {
+                root: &root,
+                cells: &cells,
+                ignore_specs: &ignore_specs,
+                digest_config,
+                previous: Some(&previous),
+            }
+            .scan()?;
+            let changes = diff(&previous, &snapshot);
+            // The cells we need to save: those that changed, and those we knew nothing about.
+            let dirty_cells: HashSet<CellName> = changes
+                .iter()
+                .map(|(path, _, _)| path.cell())
+                .chain(
+                    snapshot
+                        .cells
+                        .keys()
+                        .filter(|cell| !previous.cells.contains_key(cell))
+                        .copied(),
+                )
+                .collect();
+            anyhow::Ok((Arc::new(snapshot), changes, dirty_cells))
+        })
+        .await
+        .context("Polling file watcher scan panicked")?
+        .context("Error scanning the repository for changes")?;
+
+        let (stats, changed) = process_changes(changes, &self.subscription_events);
+        changed.write_to_dice(&mut dice)?;
+
+        // Only update the snapshot once the changes were recorded, so that if we fail, we'll
+        // report them again on the next sync. Failing to save it only means we'll miss changes
+        // made while the daemon is down, so that's not an error.
+        let store = self.store.dupe();
+        let to_save = snapshot.dupe();
+        tokio::task::spawn_blocking(move || {
+            for cell in dirty_cells {
+                if let Err(e) = store.save_cell(cell, &to_save.cells[&cell]) {
+                    warn!(
+                        "FileWatcher: error saving snapshot for cell `{}`: {:#}",
+                        cell, e
+                    );
+                }
+            }
+        })
+        .await
+        .context("Polling file watcher save panicked")?;
+
+        *guard = Some(snapshot);
+        Ok((stats, dice))
+    }
+}
+
+#[async_trait]
+impl FileWatcher for PollFileWatcher {
+    async fn sync(&self, dice: DiceTransactionUpdater) -> anyhow::Result<DiceTransactionUpdater> {
+        span_async(
+            buck2_data::FileWatcherStart {
+                provider: buck2_data::FileWatcherProvider::Poll as i32,
+            },
+            async {
+                let (stats, res) = match self.sync2(dice).await {
+                    Ok((stats, dice)) => ((Some(stats)), Ok(dice)),
+                    Err(e) => (None, Err(e)),
+                };
+                (res, buck2_data::FileWatcherEnd { stats })
+            },
+        )
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use buck2_core::cells::cell_root_path::CellRootPathBuf;
+    use buck2_core::fs::project::ProjectRootTemp;
+
+    use super::*;
+
+    struct TestRepo {
+        temp: ProjectRootTemp,
+        cells: CellResolver,
+        ignore_specs: HashMap<CellName, IgnoreSet>,
+    }
+
+    impl TestRepo {
+        fn new() -> Self {
+            let root = CellName::testing_new("root");
+            Self {
+                temp: ProjectRootTemp::new().unwrap(),
+                cells: CellResolver::testing_with_name_and_path(
+                    root,
+                    CellRootPathBuf::testing_new(""),
+                ),
+                ignore_specs: HashMap::from_iter([(
+                    root,
+                    IgnoreSet::from_ignore_spec("ignored", true).unwrap(),
+                )]),
+            }
+        }
+
+        fn scan(
+            &self,
+            digest_config: Option<CasDigestConfig>,
+            previous: Option<&Snapshot>,
+        ) -> Snapshot {
+            Scanner {
+                root: self.temp.path(),
+                cells: &self.cells,
+                ignore_specs: &self.ignore_specs,
+                digest_config: digest_config.map(FileDigestConfig::source),
+                previous,
+            }
+            .scan()
+            .unwrap()
+        }
+
+        fn store(&self, digest_config: Option<CasDigestConfig>) -> SnapshotStore {
+            SnapshotStore {
+                dir: self
+                    .temp
+                    .path()
+                    .resolve(ProjectRelativePath::unchecked_new("buck-out/v2/cache/poll")),
+                digest_config: digest_config.map(FileDigestConfig::source),
+            }
+        }
+
+        fn remove(&self, path: &str) {
+            self.temp
+                .path()
+                .remove_path_recursive(ProjectRelativePath::unchecked_new(path))
+                .unwrap();
+        }
+    }
+
+    fn changes(old: &Snapshot, new: &Snapshot) -> Vec<String> {
+        diff(old, new)
+            .into_iter()
+            .map(|(path, change_type, kind)| format!("{:?} {:?} {}", change_type, kind, path))
+            .collect()
+    }
+
+    #[test]
+    fn test_diff() {
+        let repo = TestRepo::new();
+        repo.temp.write_file("a/b.txt", "b");
+        repo.temp.write_file("a/c.txt", "c");
+        repo.temp.write_file("ignored/d.txt", "d");
+        repo.temp.write_file("buck-out/v2/e.txt", "e");
+        let old = repo.scan(None, None);
+        assert_eq!(changes(&old, &repo.scan(None, None)), Vec::<String>::new());
+
+        repo.temp.write_file("a/b.txt", "bb");
+        repo.remove("a/c.txt");
+        repo.temp.write_file("f/g.txt", "g");
+        repo.temp.write_file("ignored/h.txt", "h");
+        repo.temp.write_file("buck-out/v2/i.txt", "i");
+        let new = repo.scan(None, Some(&old));
+
+        assert_eq!(
+            changes(&old, &new),
+            vec![
+                "Modify File root//a/b.txt",
+                "Delete File root//a/c.txt",
+                "Create Directory root//f",
+                "Create File root//f/g.txt",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_diff_kind_change() {
+        let repo = TestRepo::new();
+        repo.temp.write_file("a", "a");
+        let old = repo.scan(None, None);
+
+        repo.remove("a");
+        repo.temp.write_file("a/b.txt", "b");
+        let new = repo.scan(None, Some(&old));
+
+        assert_eq!(
+            changes(&old, &new),
+            vec![
+                "Create Directory root//a",
+                "Delete File root//a",
+                "Create File root//a/b.txt",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_diff_with_digests() {
+        let repo = TestRepo::new();
+        let digest_config = Some(CasDigestConfig::testing_default());
+        repo.temp.write_file("a.txt", "a");
+        repo.temp.write_file("b.txt", "b");
+        let old = repo.scan(digest_config, None);
+        assert!(
+            old.cells
+                .values()
+                .flatten()
+                .all(|(_, e)| e.digest.is_some())
+        );
+
+        // Rewriting a file with the same contents is not a change if we hash contents.
+        repo.remove("a.txt");
+        repo.temp.write_file("a.txt", "a");
+        repo.temp.write_file("b.txt", "bb");
+        let new = repo.scan(digest_config, Some(&old));
+
+        assert_eq!(changes(&old, &new), vec!["Modify File root//b.txt"]);
+    }
+
+    #[test]
+    fn test_store_roundtrip() {
+        let repo = TestRepo::new();
+        let digest_config = Some(CasDigestConfig::testing_default());
+        repo.temp.write_file("a/b.txt", "b");
+        repo.temp.write_file("c.txt", "c");
+        #[cfg(unix)]
+        std::os::unix::fs::symlink(
+            "c.txt",
+            repo.temp
+                .path()
+                .resolve(ProjectRelativePath::unchecked_new("d")),
+        )
+        .unwrap();
+        let snapshot = repo.scan(digest_config, None);
+
+        let store = repo.store(digest_config);
+        let root = CellName::testing_new("root");
+        assert!(store.load(&repo.cells).cells.is_empty());
+        store.save_cell(root, &snapshot.cells[&root]).unwrap();
+        assert_eq!(store.load(&repo.cells).cells, snapshot.cells);
+
+        // Digests computed with another config (or none) can't be compared with ours.
+        assert!(repo.store(None).load(&repo.cells).cells.is_empty());
+    }
+
+    #[test]
+    fn test_diff_after_restart() {
+        let repo = TestRepo::new();
+        repo.temp.write_file("a.txt", "a");
+        repo.temp.write_file("b.txt", "b");
+        let root = CellName::testing_new("root");
+        let old = repo.scan(None, None);
+        repo.store(None).save_cell(root, &old.cells[&root]).unwrap();
+
+        // Changes made while the daemon is down are picked up by comparing with what we saved.
+        repo.temp.write_file("a.txt", "aa");
+        repo.remove("b.txt");
+        let restored = repo.store(None).load(&repo.cells);
+        let new = repo.scan(None, Some(&restored));
+        assert_eq!(
+            changes(&restored, &new),
+            vec!["Modify File root//a.txt", "Delete File root//b.txt"]
+        );
+
+        // Cells we know nothing about aren't reported as changed.
+        assert_eq!(changes(&Snapshot::default(), &new), Vec::<String>::new());
+    }
+
+    #[test]
+    fn test_process_changes() {
+        let path = |p| CellPath::testing_new(p);
+        let (stats, _changed) = process_changes(
+            vec![
+                (
+                    path("root//a"),
+                    ChangeType::Create,
+                    buck2_data::FileWatcherKind::Directory,
+                ),
+                (
+                    path("root//a/b"),
+                    ChangeType::Modify,
+                    buck2_data::FileWatcherKind::File,
+                ),
+            ],
+            &SubscriptionEvents::new(),
+        );
+        assert_eq!(stats.events_processed, 2);
+        assert_eq!(stats.events[0].path, "root//a");
+        assert_eq!(
+            stats.events[1].event,
+            buck2_data::FileWatcherEventType::Modify as i32
+        );
+    }
+}
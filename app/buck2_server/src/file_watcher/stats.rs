@@ -7,7 +7,12 @@
  * of this source tree.
  */
 
+use std::sync::Arc;
+
 use allocative::Allocative;
+use buck2_build_api::subscription_events::SubscriptionEvent;
+use buck2_build_api::subscription_events::SubscriptionEvents;
+use dupe::Dupe;
 
 /// We limit the number of file change records so we don't use too much memory
 /// or too much space in scribe.
@@ -23,6 +28,9 @@ pub(crate) struct FileWatcherStats {
     changes: Vec<buck2_data::FileWatcherEvent>,
     // Did we not insert things into changes
     changes_missed: bool,
+    // Every change, unbounded, if anyone is subscribed to file changes
+    subscribed_changes: Option<Vec<buck2_data::FileWatcherEvent>>,
+    subscription_events: Arc<SubscriptionEvents>,
 }
 
 impl FileWatcherStats {
@@ -30,6 +38,7 @@ impl FileWatcherStats {
         min_count: usize,
         mergebase: Option<&str>,
         watchman_version: Option<String>,
+        subscription_events: &Arc<SubscriptionEvents>,
     ) -> Self {
         let stats = buck2_data::FileWatcherStats {
             branched_from_revision: mergebase.map(ToOwned::to_owned),
@@ -39,10 +48,14 @@ impl FileWatcherStats {
 
         let changes = Vec::with_capacity(std::cmp::min(MAX_FILE_CHANGE_RECORDS, min_count));
 
+        let subscribed_changes = subscription_events.has_subscribers().then(Vec::new);
+
         Self {
             stats,
             changes,
             changes_missed: false,
+            subscribed_changes,
+            subscription_events: subscription_events.dupe(),
         }
     }
 
@@ -61,12 +74,18 @@ impl FileWatcherStats {
         self.stats.events_total += 1;
         self.stats.events_processed += 1;
 
+        let change = buck2_data::FileWatcherEvent {
+            event: event as i32,
+            kind: kind as i32,
+            path,
+        };
+
+        if let Some(subscribed_changes) = &mut self.subscribed_changes {
+            subscribed_changes.push(change.clone());
+        }
+
         if self.changes.len() < MAX_FILE_CHANGE_RECORDS {
-            self.changes.push(buck2_data::FileWatcherEvent {
-                event: event as i32,
-                kind: kind as i32,
-                path,
-            });
+            self.changes.push(change);
         } else {
             self.changes_missed = true;
         }
@@ -77,8 +96,17 @@ impl FileWatcherStats {
             mut stats,
             changes,
             changes_missed,
+            subscribed_changes,
+            subscription_events,
         } = self;
 
+        if let Some(subscribed_changes) = subscribed_changes {
+            if !subscribed_changes.is_empty() {
+                subscription_events
+                    .publish(SubscriptionEvent::FileChanges(Arc::new(subscribed_changes)));
+            }
+        }
+
         stats.events = changes;
         if changes_missed {
             let reason = format!(
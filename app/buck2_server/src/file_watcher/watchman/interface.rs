@@ -9,10 +9,12 @@
 
 use std::collections::HashMap;
 use std::path::Path;
+use std::sync::Arc;
 
 use allocative::Allocative;
 use anyhow::Context as _;
 use async_trait::async_trait;
+use buck2_build_api::subscription_events::SubscriptionEvents;
 use buck2_common::dice::file_ops::FileChangeTracker;
 use buck2_common::ignores::ignore_set::IgnoreSet;
 use buck2_common::legacy_configs::LegacyBuckConfig;
@@ -42,6 +44,7 @@ struct WatchmanQueryProcessor {
     ignore_specs: HashMap<CellName, IgnoreSet>,
     retain_dep_files_on_watchman_fresh_instance: bool,
     last_mergebase: Option<String>,
+    subscription_events: Arc<SubscriptionEvents>,
 }
 
 /// Used in process_one_change
@@ -60,7 +63,12 @@ impl WatchmanQueryProcessor {
         watchman_version: Option<String>,
     ) -> anyhow::Result<(buck2_data::FileWatcherStats, DiceTransactionUpdater)> {
         let mut handler = FileChangeTracker::new();
-        let mut stats = FileWatcherStats::new(events.len(), mergebase.as_deref(), watchman_version);
+        let mut stats = FileWatcherStats::new(
+            events.len(),
+            mergebase.as_deref(),
+            watchman_version,
+            &self.subscription_events,
+        );
 
         for ev in events {
             // If the path is invalid, then walk up all the way until you find a valid dir to
@@ -286,6 +294,7 @@ impl WatchmanFileWatcher {
         root_config: &LegacyBuckConfig,
         cells: CellResolver,
         ignore_specs: HashMap<CellName, IgnoreSet>,
+        subscription_events: Arc<SubscriptionEvents>,
     ) -> anyhow::Result<Self> {
         let watchman_merge_base = root_config
             .get("project", "watchman_merge_base")
@@ -309,6 +318,7 @@ impl WatchmanFileWatcher {
                 ignore_specs,
                 retain_dep_files_on_watchman_fresh_instance,
                 last_mergebase: None,
+                subscription_events,
             }),
             watchman_merge_base,
         )?;
@@ -23,6 +23,7 @@ use buck2_common::result::SharedResult;
 use buck2_core::bzl::ImportPath;
 use buck2_core::cells::build_file_cell::BuildFileCell;
 use buck2_core::cells::cell_path::CellPathRef;
+use buck2_core::cells::paths::CellRelativePath;
 use buck2_core::cells::CellResolver;
 use buck2_core::fs::paths::abs_path::AbsPath;
 use buck2_core::fs::paths::forward_rel_path::ForwardRelativePath;
@@ -37,6 +38,7 @@ use buck2_events::dispatch::with_dispatcher;
 use buck2_events::dispatch::with_dispatcher_async;
 use buck2_interpreter::path::BxlFilePath;
 use buck2_interpreter::path::OwnedStarlarkModulePath;
+use buck2_interpreter::path::PackageFilePath;
 use buck2_interpreter::path::StarlarkPath;
 use buck2_interpreter_for_build::interpreter::dice_calculation_delegate::HasCalculationDelegate;
 use buck2_interpreter_for_build::interpreter::global_interpreter_state::HasGlobalInterpreterState;
@@ -49,6 +51,7 @@ use dice::DiceEquality;
 use dice::DiceTransaction;
 use dupe::Dupe;
 use futures::channel::mpsc::UnboundedSender;
+use futures::future;
 use futures::FutureExt;
 use futures::SinkExt;
 use futures::StreamExt;
@@ -60,6 +63,8 @@ use lsp_types::Range;
 use lsp_types::Url;
 use starlark::docs::Doc;
 use starlark::docs::DocItem;
+use starlark::docs::DocMember;
+use starlark::docs::DocModule;
 use starlark::docs::Identifier;
 use starlark::docs::Location;
 use starlark::errors::EvalMessage;
@@ -69,6 +74,7 @@ use starlark::lsp::server::LspEvalResult;
 use starlark::lsp::server::LspUrl;
 use starlark::lsp::server::StringLiteralResult;
 use starlark::syntax::AstModule;
+use starlark::syntax::FormatMode;
 use tokio::runtime::Handle;
 use tokio::sync::Mutex;
 use tokio::sync::MutexGuard;
@@ -172,6 +178,8 @@ struct DocsCache {
     global_urls: HashMap<String, LspUrl>,
     /// Mapping of starlark: urls to a synthesized starlark representation.
     native_starlark_files: HashMap<LspUrl, String>,
+    /// Documentation for the global functions and properties, e.g. rules and their attributes.
+    environment: DocModule,
 }
 
 #[derive(thiserror::Error, Debug)]
@@ -242,6 +250,7 @@ impl DocsCache {
     ) -> anyhow::Result<Self> {
         let mut global_urls = HashMap::with_capacity(builtin_symbols.len());
         let mut native_starlark_files = HashMap::new();
+        let mut environment = DocModule::default();
         for doc in builtin_symbols {
             let url = match &doc.id.location {
                 Some(l) => location_lookup(l).await?,
@@ -270,10 +279,24 @@ impl DocsCache {
                 }
                 .into());
             }
+            match &doc.item {
+                DocItem::Function(f) => {
+                    environment
+                        .members
+                        .insert(doc.id.name.clone(), DocMember::Function(f.clone()));
+                }
+                DocItem::Property(p) => {
+                    environment
+                        .members
+                        .insert(doc.id.name.clone(), DocMember::Property(p.clone()));
+                }
+                DocItem::Module(_) | DocItem::Object(_) => {}
+            }
         }
         Ok(Self {
             global_urls,
             native_starlark_files,
+            environment,
         })
     }
 
@@ -286,6 +309,10 @@ impl DocsCache {
     fn url_for_symbol(&self, symbol: &str) -> Option<&LspUrl> {
         self.global_urls.get(symbol)
     }
+
+    fn environment(&self) -> &DocModule {
+        &self.environment
+    }
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -463,6 +490,66 @@ impl<'a> BuckLspContext<'a> {
         }
     }
 
+    /// Find the Starlark files in the cell that `path` is in: its build files, and its `.bzl`
+    /// and `.bxl` files.
+    ///
+    /// The directory listings come from DICE, so are only read from disk once, and all the
+    /// directories at the same depth are listed concurrently.
+    async fn cell_starlark_files(&self, path: &Path) -> anyhow::Result<Vec<LspUrl>> {
+        let cell = self.import_path(path).await?.borrow().cell();
+        self.with_dice_ctx(async move |dice_ctx| {
+            let file_ops = dice_ctx.file_ops();
+            let cell_resolver = dice_ctx.get_cell_resolver().await?;
+            let buildfiles = cell_resolver.get(cell)?.buildfiles();
+            let mut dirs = vec![CellPathRef::new(cell, CellRelativePath::empty()).to_owned()];
+            let mut files = Vec::new();
+            while !dirs.is_empty() {
+                let listings =
+                    future::try_join_all(dirs.iter().map(|dir| file_ops.read_dir(dir.as_ref())))
+                        .await?;
+                let mut subdirs = Vec::new();
+                for (dir, listing) in dirs.iter().zip(listings) {
+                    for entry in listing.included.iter() {
+                        let child = dir.join(&entry.file_name);
+                        if entry.file_type.is_dir() {
+                            subdirs.push(child);
+                        } else if matches!(entry.file_name.extension(), Some("bzl" | "bxl"))
+                            || buildfiles.contains(&entry.file_name)
+                        {
+                            let relative_path = cell_resolver.resolve_path(child.as_ref())?;
+                            let abs_path = self.fs.resolve(&relative_path);
+                            files.push(Url::from_file_path(abs_path).unwrap().try_into()?);
+                        }
+                    }
+                }
+                dirs = subdirs;
+            }
+            Ok(files)
+        })
+        .await
+    }
+
+    /// Whether `path` is a build file or a `PACKAGE` file, rather than e.g. a `.bzl` file.
+    async fn is_build_file(&self, path: &Path) -> anyhow::Result<bool> {
+        let relative_path = self.fs.relativize_any(AbsPath::new(path)?)?;
+        let file_name = match relative_path.file_name() {
+            Some(file_name) => file_name,
+            None => return Ok(false),
+        };
+        if file_name == PackageFilePath::PACKAGE_FILE_NAME {
+            return Ok(true);
+        }
+        let cell_resolver = self
+            .with_dice_ctx(|dice_ctx| async move { dice_ctx.get_cell_resolver().await })
+            .await?;
+        let cell = cell_resolver.get_cell_path(&relative_path)?.cell();
+        Ok(cell_resolver
+            .get(cell)?
+            .buildfiles()
+            .iter()
+            .any(|buildfile| buildfile.as_str() == file_name.as_str()))
+    }
+
     async fn parse_file_with_contents(
         &self,
         uri: &LspUrl,
@@ -736,6 +823,50 @@ impl<'a> LspContext for BuckLspContext<'a> {
                 Ok(docs_cache.url_for_symbol(symbol).cloned())
             }))
     }
+
+    fn get_environment(&self, _current_file: &LspUrl) -> anyhow::Result<DocModule> {
+        let dispatcher = self.server_ctx.events().dupe();
+        self.runtime
+            .block_on(with_dispatcher_async(dispatcher, async {
+                let docs_cache = self
+                    .with_dice_ctx(|dice_ctx| async {
+                        self.docs_cache_manager.get_cache(dice_ctx).await
+                    })
+                    .await?;
+                Ok(docs_cache.environment().clone())
+            }))
+    }
+
+    fn get_workspace_files(&self, current_file: &LspUrl) -> anyhow::Result<Vec<LspUrl>> {
+        let dispatcher = self.server_ctx.events().dupe();
+        self.runtime
+            .block_on(with_dispatcher_async(dispatcher, async {
+                match current_file {
+                    LspUrl::File(path) => self.cell_starlark_files(path).await,
+                    // Files that are not on disk are not in any cell.
+                    _ => Ok(Vec::new()),
+                }
+            }))
+    }
+
+    fn format_mode(&self, uri: &LspUrl) -> FormatMode {
+        let dispatcher = self.server_ctx.events().dupe();
+        let is_build_file = self
+            .runtime
+            .block_on(with_dispatcher_async(dispatcher, async {
+                match uri {
+                    LspUrl::File(path) => self.is_build_file(path).await,
+                    _ => Ok(false),
+                }
+            }))
+            // Formatting as an extension file is always safe, it just doesn't sort as much.
+            .unwrap_or(false);
+        if is_build_file {
+            FormatMode::Build
+        } else {
+            FormatMode::Extension
+        }
+    }
 }
 
 pub(crate) async fn run_lsp_server_command(
@@ -964,6 +1095,15 @@ mod test {
             &LspUrl::try_from(Url::parse("file:/usr/local/dir/prelude.bzl")?)?,
             cache.url_for_symbol("prelude_function").unwrap()
         );
+        assert_eq!(
+            vec!["native_function1", "native_function2", "prelude_function"],
+            cache
+                .environment()
+                .members
+                .keys()
+                .map(|k| k.as_str())
+                .collect::<Vec<_>>()
+        );
 
         Ok(())
     }
@@ -151,6 +151,11 @@ impl SnapshotCollector {
                 stats.get_digest_expirations.finished_successfully;
             snapshot.re_get_digest_expirations_finished_with_error =
                 stats.get_digest_expirations.finished_with_error;
+            snapshot.re_uploads_retries = stats.retries.uploads;
+            snapshot.re_downloads_retries = stats.retries.downloads;
+            snapshot.re_action_cache_retries = stats.retries.action_cache;
+            snapshot.re_executes_retries = stats.retries.executes;
+            snapshot.re_get_digest_expirations_retries = stats.retries.get_digest_expirations;
 
             Ok(())
         }
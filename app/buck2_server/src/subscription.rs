@@ -7,9 +7,15 @@
  * of this source tree.
  */
 
+use std::collections::BTreeSet;
+use std::sync::Arc;
 use std::time::Duration;
 
 use anyhow::Context as _;
+use buck2_build_api::subscription_events::SubscriptionEvent;
+use buck2_build_api::subscription_events::SubscriptionEvents;
+use buck2_build_api::subscription_events::SubscriptionEventsHandle;
+use buck2_core::target::label::ConfiguredTargetLabel;
 use buck2_events::dispatch::span_async;
 use buck2_server_ctx::command_end::command_end;
 use buck2_server_ctx::ctx::ServerCommandContextTrait;
@@ -23,6 +29,7 @@ use crate::active_commands;
 
 pub(crate) async fn run_subscription_server_command(
     ctx: &dyn ServerCommandContextTrait,
+    subscription_events: Arc<SubscriptionEvents>,
     mut partial_result_dispatcher: PartialResultDispatcher<
         buck2_cli_proto::SubscriptionResponseWrapper,
     >,
@@ -51,6 +58,16 @@ pub(crate) async fn run_subscription_server_command(
 
             let mut wants_active_commands = false;
 
+            // We only register with subscription_events once the client asks for those events, so
+            // that producers don't do any work for subscriptions that don't want them.
+            let mut events: Option<SubscriptionEventsHandle> = None;
+            let mut wants_file_changes = false;
+            let mut wants_invalidated_targets = false;
+            let mut wants_build_completions = false;
+
+            // Invalidated targets are batched and flushed on every tick.
+            let mut invalidated_targets = InvalidatedTargetsBatch::default();
+
             let mut ticker = tokio::time::interval(Duration::from_millis(100));
             ticker.set_missed_tick_behavior(MissedTickBehavior::Skip);
 
@@ -74,6 +91,55 @@ pub(crate) async fn run_subscription_server_command(
                             Request::SubscribeToActiveCommands(buck2_subscription_proto::SubscribeToActiveCommands {}) => {
                                 wants_active_commands = true;
                             }
+                            Request::SubscribeToFileChanges(buck2_subscription_proto::SubscribeToFileChanges {}) => {
+                                events.get_or_insert_with(|| subscription_events.subscribe());
+                                wants_file_changes = true;
+                            }
+                            Request::SubscribeToInvalidatedTargets(buck2_subscription_proto::SubscribeToInvalidatedTargets {}) => {
+                                events.get_or_insert_with(|| subscription_events.subscribe());
+                                wants_invalidated_targets = true;
+                            }
+                            Request::SubscribeToBuildCompletions(buck2_subscription_proto::SubscribeToBuildCompletions {}) => {
+                                events.get_or_insert_with(|| subscription_events.subscribe());
+                                wants_build_completions = true;
+                            }
+                        }
+                    }
+                    event = next_subscription_event(&mut events).fuse() => {
+                        match event.context("Subscription events hung up")? {
+                            SubscriptionEvent::FileChanges(changes) => {
+                                if wants_file_changes {
+                                    partial_result_dispatcher.emit(buck2_cli_proto::SubscriptionResponseWrapper {
+                                        response: Some(buck2_subscription_proto::SubscriptionResponse {
+                                            response: Some(file_changes(&changes).into())
+                                        })
+                                    });
+                                }
+                            }
+                            SubscriptionEvent::ConfiguredNodeInvalidated(label) => {
+                                if wants_invalidated_targets {
+                                    invalidated_targets.add(label);
+                                }
+                            }
+                            SubscriptionEvent::AllInvalidated => {
+                                if wants_invalidated_targets {
+                                    invalidated_targets.add_all();
+                                }
+                            }
+                            SubscriptionEvent::TargetBuilt(built) => {
+                                if wants_build_completions {
+                                    partial_result_dispatcher.emit(buck2_cli_proto::SubscriptionResponseWrapper {
+                                        response: Some(buck2_subscription_proto::SubscriptionResponse {
+                                            response: Some(buck2_subscription_proto::TargetBuilt {
+                                                target: built.label.unconfigured().to_string(),
+                                                configuration: built.label.cfg().to_string(),
+                                                outputs: built.outputs.clone(),
+                                                errors: built.errors.clone(),
+                                            }.into())
+                                        })
+                                    });
+                                }
+                            }
                         }
                     }
                     path = materializer_subscription.next_materialization().fuse() => {
@@ -85,6 +151,14 @@ pub(crate) async fn run_subscription_server_command(
                         });
                     }
                     _ = ticker.tick().fuse() => {
+                        if let Some(targets) = invalidated_targets.flush() {
+                            partial_result_dispatcher.emit(buck2_cli_proto::SubscriptionResponseWrapper {
+                                response: Some(buck2_subscription_proto::SubscriptionResponse {
+                                    response: Some(targets.into())
+                                })
+                            });
+                        }
+
                         if wants_active_commands {
                             let snapshot = active_commands_snapshot();
                             partial_result_dispatcher.emit(buck2_cli_proto::SubscriptionResponseWrapper {
@@ -115,6 +189,86 @@ pub(crate) async fn run_subscription_server_command(
     .await
 }
 
+/// Wait for the next event, or forever if the client hasn't subscribed to any.
+async fn next_subscription_event(
+    events: &mut Option<SubscriptionEventsHandle>,
+) -> Option<SubscriptionEvent> {
+    match events {
+        Some(events) => events.next_event().await,
+        None => futures::future::pending().await,
+    }
+}
+
+fn file_changes(changes: &[buck2_data::FileWatcherEvent]) -> buck2_subscription_proto::FileChanges {
+    use buck2_subscription_proto::file_change::Change;
+    use buck2_subscription_proto::file_change::Kind;
+
+    let changes = changes
+        .iter()
+        .map(|change| {
+            let kind = match change.kind() {
+                buck2_data::FileWatcherKind::File => Kind::File,
+                buck2_data::FileWatcherKind::Directory => Kind::Directory,
+                buck2_data::FileWatcherKind::Symlink => Kind::Symlink,
+            };
+            let change_type = match change.event() {
+                buck2_data::FileWatcherEventType::Create => Change::Create,
+                buck2_data::FileWatcherEventType::Modify => Change::Modify,
+                buck2_data::FileWatcherEventType::Delete => Change::Delete,
+            };
+
+            buck2_subscription_proto::FileChange {
+                path: change.path.clone(),
+                kind: kind as i32,
+                change: change_type as i32,
+            }
+        })
+        .collect();
+
+    buck2_subscription_proto::FileChanges { changes }
+}
+
+/// Configured targets invalidated since the last flush, deduplicated and in label order.
+#[derive(Default)]
+struct InvalidatedTargetsBatch {
+    targets: BTreeSet<ConfiguredTargetLabel>,
+    /// Everything was invalidated, so there is no point in listing targets.
+    all: bool,
+}
+
+impl InvalidatedTargetsBatch {
+    fn add(&mut self, label: ConfiguredTargetLabel) {
+        if !self.all {
+            self.targets.insert(label);
+        }
+    }
+
+    fn add_all(&mut self) {
+        self.all = true;
+        self.targets.clear();
+    }
+
+    /// Take the targets added so far, or `None` if there were none.
+    fn flush(&mut self) -> Option<buck2_subscription_proto::TargetsInvalidated> {
+        if self.targets.is_empty() && !self.all {
+            return None;
+        }
+
+        let targets = std::mem::take(&mut self.targets)
+            .into_iter()
+            .map(|label| buck2_subscription_proto::ConfiguredTarget {
+                target: label.unconfigured().to_string(),
+                configuration: label.cfg().to_string(),
+            })
+            .collect();
+
+        Some(buck2_subscription_proto::TargetsInvalidated {
+            targets,
+            all: std::mem::take(&mut self.all),
+        })
+    }
+}
+
 fn active_commands_snapshot() -> buck2_subscription_proto::ActiveCommandsSnapshot {
     let active_commands = active_commands::active_commands()
         .iter()
@@ -136,3 +290,59 @@ fn active_commands_snapshot() -> buck2_subscription_proto::ActiveCommandsSnapsho
 
     buck2_subscription_proto::ActiveCommandsSnapshot { active_commands }
 }
+
+#[cfg(test)]
+mod tests {
+    use buck2_core::configuration::data::ConfigurationData;
+
+    use super::*;
+
+    fn label(name: &str) -> ConfiguredTargetLabel {
+        ConfiguredTargetLabel::testing_parse(
+            &format!("cell//pkg:{}", name),
+            ConfigurationData::testing_new(),
+        )
+    }
+
+    #[test]
+    fn test_invalidated_targets_batch() {
+        let mut batch = InvalidatedTargetsBatch::default();
+        assert_eq!(batch.flush(), None);
+
+        batch.add(label("b"));
+        batch.add(label("a"));
+        batch.add(label("b"));
+
+        let targets = batch.flush().unwrap().targets;
+        assert_eq!(
+            targets
+                .iter()
+                .map(|t| t.target.as_str())
+                .collect::<Vec<_>>(),
+            vec!["cell//pkg:a", "cell//pkg:b"]
+        );
+        assert!(
+            targets
+                .iter()
+                .all(|t| t.configuration == ConfigurationData::testing_new().to_string())
+        );
+
+        // A flush empties the batch.
+        assert_eq!(batch.flush(), None);
+        batch.add(label("a"));
+        assert_eq!(batch.flush().unwrap().targets.len(), 1);
+
+        // Once everything is invalidated, targets are no longer listed.
+        batch.add(label("a"));
+        batch.add_all();
+        batch.add(label("b"));
+        assert_eq!(
+            batch.flush(),
+            Some(buck2_subscription_proto::TargetsInvalidated {
+                targets: Vec::new(),
+                all: true,
+            })
+        );
+        assert_eq!(batch.flush(), None);
+    }
+}
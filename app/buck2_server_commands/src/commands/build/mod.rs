@@ -22,6 +22,7 @@ use buck2_build_api::build::MaterializationContext;
 use buck2_build_api::build::ProvidersToBuild;
 use buck2_build_api::calculation::Calculation;
 use buck2_build_api::query::oneshot::QUERY_FRONTEND;
+use buck2_build_api::subscription_events::HasSubscriptionEvents;
 use buck2_cli_proto::build_request::build_providers::Action as BuildProviderAction;
 use buck2_cli_proto::build_request::BuildProviders;
 use buck2_cli_proto::build_request::Materializations;
@@ -69,6 +70,7 @@ use crate::commands::build::results::build_report::BuildReportCollector;
 use crate::commands::build::results::providers::ProvidersPrinter;
 use crate::commands::build::results::result_report::ResultReporter;
 use crate::commands::build::results::result_report::ResultReporterOptions;
+use crate::commands::build::results::subscription::SubscriptionNotifier;
 use crate::commands::build::results::BuildOwner;
 use crate::commands::build::results::BuildResultCollector;
 use crate::commands::build::unhashed_outputs::create_unhashed_outputs;
@@ -210,6 +212,16 @@ async fn build(
         None
     };
 
+    let subscription_events = ctx.global_data().get_subscription_events();
+    let mut subscription_notifier = if subscription_events.has_subscribers() {
+        Some(SubscriptionNotifier::new(
+            &artifact_fs,
+            subscription_events.dupe(),
+        ))
+    } else {
+        None
+    };
+
     let mut result_collectors = vec![
         Some(&mut result_collector as &mut dyn BuildResultCollector),
         build_report_collector
@@ -218,6 +230,9 @@ async fn build(
         providers_printer
             .as_mut()
             .map(|v| v as &mut dyn BuildResultCollector),
+        subscription_notifier
+            .as_mut()
+            .map(|v| v as &mut dyn BuildResultCollector),
     ]
     .into_iter()
     .flatten()
@@ -435,3 +435,64 @@ pub mod providers {
         }
     }
 }
+
+pub mod subscription {
+    use std::sync::Arc;
+
+    use buck2_build_api::build::BuildTargetResult;
+    use buck2_build_api::subscription_events::SubscriptionEvent;
+    use buck2_build_api::subscription_events::SubscriptionEvents;
+    use buck2_build_api::subscription_events::TargetBuilt;
+    use buck2_core::fs::artifact_path_resolver::ArtifactFs;
+    use buck2_execute::artifact::artifact_dyn::ArtifactDyn;
+    use itertools::Itertools;
+
+    use crate::commands::build::results::BuildOwner;
+    use crate::commands::build::results::BuildResultCollector;
+
+    /// Notifies `buck2 subscribe` clients of each target that finished building.
+    pub(crate) struct SubscriptionNotifier<'a> {
+        artifact_fs: &'a ArtifactFs,
+        events: Arc<SubscriptionEvents>,
+    }
+
+    impl<'a> SubscriptionNotifier<'a> {
+        pub(crate) fn new(artifact_fs: &'a ArtifactFs, events: Arc<SubscriptionEvents>) -> Self {
+            Self {
+                artifact_fs,
+                events,
+            }
+        }
+    }
+
+    impl<'a> BuildResultCollector for SubscriptionNotifier<'a> {
+        fn collect_result(&mut self, label: &BuildOwner, result: &BuildTargetResult) {
+            let label = match label {
+                BuildOwner::Target(t) => (*t).clone(),
+            };
+
+            let mut outputs = Vec::new();
+            let mut errors = Vec::new();
+            for output in &result.outputs {
+                match output {
+                    Ok(output) => {
+                        for (artifact, _value) in output.values.iter() {
+                            match artifact.resolve_path(self.artifact_fs) {
+                                Ok(path) => outputs.push(path.to_string()),
+                                Err(e) => errors.push(format!("{:#}", e)),
+                            }
+                        }
+                    }
+                    Err(e) => errors.push(format!("{:#}", e)),
+                }
+            }
+
+            self.events
+                .publish(SubscriptionEvent::TargetBuilt(Arc::new(TargetBuilt {
+                    label,
+                    outputs: outputs.into_iter().unique().collect(),
+                    errors: errors.into_iter().unique().collect(),
+                })));
+        }
+    }
+}
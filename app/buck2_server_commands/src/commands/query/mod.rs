@@ -20,4 +20,6 @@ enum QueryCommandError {
         "query result was a set of files and one or more --output-attribute was requested, but files have not attributes"
     )]
     FileSetHasNoAttributes,
+    #[error("query result was a set of files, which can't be printed as GraphML")]
+    FileSetHasNoGraphml,
 }
@@ -37,8 +37,10 @@ use serde::ser::SerializeMap;
 use serde::ser::SerializeSeq;
 use serde::Serialize;
 use serde::Serializer;
+use starlark_map::small_set::SmallSet;
 
 use crate::commands::query::QueryCommandError;
+use crate::dot::graphml::GraphML;
 use crate::dot::targets::DotTargetGraph;
 use crate::dot::Dot;
 use crate::dot::DotCompact;
@@ -84,6 +86,9 @@ impl<'a, T: QueryTarget> TargetSetJsonPrinter<'a, T> {
     }
 }
 
+/// Name of the field holding the target label in NDJSON and CSV output.
+const TARGET_FIELD: &str = "target";
+
 struct PrintableQueryTarget<'a, T: QueryTarget> {
     value: &'a T,
     attributes: &'a Option<RegexSet>,
@@ -91,12 +96,6 @@ struct PrintableQueryTarget<'a, T: QueryTarget> {
     target_call_stacks: bool,
 }
 
-impl<'a, T: QueryTarget> PrintableQueryTarget<'a, T> {
-    fn label(&self) -> String {
-        self.value.node_ref().to_string()
-    }
-}
-
 impl<'a, T: QueryTarget> Display for PrintableQueryTarget<'a, T> {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         write!(f, "{}", self.value.node_ref())?;
@@ -129,13 +128,12 @@ impl<'a, T: QueryTarget> Display for PrintableQueryTarget<'a, T> {
     }
 }
 
-impl<'a, T: QueryTarget> Serialize for PrintableQueryTarget<'a, T> {
-    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
-    where
-        S: Serializer,
-    {
-        let mut map = serializer.serialize_map(None)?;
+impl<'a, T: QueryTarget> PrintableQueryTarget<'a, T> {
+    fn label(&self) -> String {
+        self.value.node_ref().to_string()
+    }
 
+    fn serialize_entries<M: SerializeMap>(&self, map: &mut M) -> Result<(), M::Error> {
         QueryTargets::for_all_attrs(self.value, |attr_name, attr_value| {
             if let Some(attr_regex) = self.attributes {
                 if attr_regex.is_match(attr_name) {
@@ -173,10 +171,100 @@ impl<'a, T: QueryTarget> Serialize for PrintableQueryTarget<'a, T> {
             map.serialize_entry("buck.providers", providers)?;
         }
 
+        Ok(())
+    }
+
+    /// The columns of this target's CSV row, as `(column name, value)` pairs.
+    fn csv_fields(&self) -> anyhow::Result<Vec<(String, String)>> {
+        let mut fields = vec![(TARGET_FIELD.to_owned(), self.label())];
+
+        QueryTargets::for_all_attrs::<anyhow::Error, _, _>(self.value, |attr_name, attr_value| {
+            if let Some(attr_regex) = self.attributes {
+                if attr_regex.is_match(attr_name) {
+                    fields.push((
+                        attr_name.to_owned(),
+                        self.value.attr_to_string_alternate(attr_value),
+                    ));
+                }
+            }
+            Ok(())
+        })?;
+
+        if self.target_call_stacks {
+            fields.push((
+                "buck.target_call_stack".to_owned(),
+                self.value.call_stack().unwrap_or_default(),
+            ));
+        }
+
+        if let Some(providers) = &self.providers {
+            fields.push((
+                "buck.providers".to_owned(),
+                format!("{}", providers.provider_collection()),
+            ));
+        }
+
+        Ok(fields)
+    }
+}
+
+impl<'a, T: QueryTarget> Serialize for PrintableQueryTarget<'a, T> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let mut map = serializer.serialize_map(None)?;
+        self.serialize_entries(&mut map)?;
         map.end()
     }
 }
 
+/// A single line of NDJSON output: the target's label along with its requested attributes.
+struct NdjsonTargetPrinter<'a, 'b, T: QueryTarget>(&'b PrintableQueryTarget<'a, T>);
+
+impl<'a, 'b, T: QueryTarget> Serialize for NdjsonTargetPrinter<'a, 'b, T> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let mut map = serializer.serialize_map(None)?;
+        map.serialize_entry(TARGET_FIELD, &self.0.label())?;
+        self.0.serialize_entries(&mut map)?;
+        map.end()
+    }
+}
+
+/// Writes targets as CSV with a header row. The columns are the target label followed by every
+/// requested attribute that any of the targets has; targets lacking an attribute get an empty cell.
+fn write_targets_csv<'a, T: QueryTarget, W: std::io::Write>(
+    output: W,
+    targets: &[PrintableQueryTarget<'a, T>],
+) -> anyhow::Result<()> {
+    let rows = targets
+        .iter()
+        .map(|target| target.csv_fields())
+        .collect::<anyhow::Result<Vec<_>>>()?;
+
+    let mut columns: SmallSet<&str> = SmallSet::new();
+    for row in &rows {
+        for (column, _) in row {
+            columns.insert(column.as_str());
+        }
+    }
+
+    let mut writer = csv::Writer::from_writer(output);
+    writer.write_record(columns.iter())?;
+    for row in &rows {
+        writer.write_record(columns.iter().map(|column| {
+            row.iter()
+                .find(|(name, _)| name == column)
+                .map_or("", |(_, value)| value.as_str())
+        }))?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
 impl<'a, T: QueryTarget> Serialize for TargetSetJsonPrinter<'a, T> {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
@@ -368,6 +456,31 @@ impl<'a> QueryResultPrinter<'a> {
                         &mut output,
                     )?;
                 }
+                QueryOutputFormat::Ndjson => {
+                    for target in
+                        printable_targets(&targets, print_providers, &self.attributes, call_stack)
+                            .await?
+                    {
+                        serde_json::to_writer(&mut output, &NdjsonTargetPrinter(&target))?;
+                        writeln!(&mut output)?;
+                    }
+                }
+                QueryOutputFormat::Csv => {
+                    write_targets_csv(
+                        &mut output,
+                        &printable_targets(&targets, print_providers, &self.attributes, call_stack)
+                            .await?,
+                    )?;
+                }
+                QueryOutputFormat::Graphml => {
+                    GraphML::render(
+                        &DotTargetGraph {
+                            targets,
+                            attributes: self.attributes.clone(),
+                        },
+                        &mut output,
+                    )?;
+                }
             },
             QueryEvaluationValue::FileSet(files) => {
                 if self.attributes.is_some() {
@@ -400,6 +513,29 @@ impl<'a> QueryResultPrinter<'a> {
                     QueryOutputFormat::DotCompact => {
                         unimplemented!("dot_compact output for files not implemented yet")
                     }
+                    QueryOutputFormat::Ndjson => {
+                        for file in files.iter() {
+                            serde_json::to_writer(
+                                &mut output,
+                                &self.resolver.resolve_path(file.as_ref())?.to_string(),
+                            )?;
+                            writeln!(&mut output)?;
+                        }
+                    }
+                    QueryOutputFormat::Csv => {
+                        let mut writer = csv::Writer::from_writer(&mut output);
+                        writer.write_record(["path"])?;
+                        for file in files.iter() {
+                            writer.write_record([self
+                                .resolver
+                                .resolve_path(file.as_ref())?
+                                .to_string()])?;
+                        }
+                        writer.flush()?;
+                    }
+                    QueryOutputFormat::Graphml => {
+                        return Err(QueryCommandError::FileSetHasNoGraphml.into());
+                    }
                 }
             }
         }
@@ -479,3 +615,151 @@ pub(crate) fn init_print_action_node() {
         ))
     });
 }
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use buck2_core::bzl::ImportPath;
+    use buck2_core::cells::cell_path::CellPath;
+    use buck2_core::cells::cell_root_path::CellRootPathBuf;
+    use buck2_core::cells::name::CellName;
+    use buck2_core::target::label::TargetLabel;
+    use buck2_node::attrs::attr::Attribute;
+    use buck2_node::attrs::attr_type::string::StringLiteral;
+    use buck2_node::attrs::attr_type::AttrType;
+    use buck2_node::attrs::coerced_attr::CoercedAttr;
+    use buck2_node::nodes::unconfigured::testing::TargetNodeExt;
+    use buck2_node::nodes::unconfigured::TargetNode;
+    use buck2_node::rule_type::RuleType;
+    use buck2_node::rule_type::StarlarkRuleType;
+    use buck2_query::query::syntax::simple::eval::file_set::FileNode;
+
+    use super::*;
+
+    fn resolver() -> CellResolver {
+        CellResolver::testing_with_name_and_path(
+            CellName::testing_new("root"),
+            CellRootPathBuf::testing_new(""),
+        )
+    }
+
+    fn targets() -> TargetSet<TargetNode> {
+        let rule_type = RuleType::Starlark(Arc::new(StarlarkRuleType {
+            import_path: ImportPath::testing_new("root//foo:defs.bzl"),
+            name: "some_rule".to_owned(),
+        }));
+        let mut targets = TargetSet::new();
+        targets.insert(TargetNode::testing_new(
+            TargetLabel::testing_parse("root//foo:a"),
+            rule_type.clone(),
+            vec![(
+                "srcs_note",
+                Attribute::new(None, "", AttrType::string()),
+                CoercedAttr::String(StringLiteral("x, \"y\"".into())),
+            )],
+        ));
+        targets.insert(TargetNode::testing_new(
+            TargetLabel::testing_parse("root//foo:b"),
+            rule_type,
+            Vec::new(),
+        ));
+        targets
+    }
+
+    fn files() -> FileSet {
+        let mut files = FileSet::new(Default::default());
+        files.insert(FileNode(CellPath::testing_new("root//foo/a.txt")));
+        files.insert(FileNode(CellPath::testing_new("root//foo/b,c.txt")));
+        files
+    }
+
+    async fn print(
+        format: QueryOutputFormat,
+        attributes: &[&str],
+        result: QueryEvaluationValue<TargetNode>,
+    ) -> anyhow::Result<String> {
+        let resolver = resolver();
+        let attributes: Vec<String> = attributes.iter().map(|a| (*a).to_owned()).collect();
+        let printer = QueryResultPrinter::from_options(&resolver, &attributes, format)?;
+        let mut output = Vec::new();
+        printer
+            .print_single_output(&mut output, result, false, ShouldPrintProviders::No)
+            .await?;
+        Ok(String::from_utf8(output)?)
+    }
+
+    #[tokio::test]
+    async fn test_csv_targets() -> anyhow::Result<()> {
+        let csv = print(
+            QueryOutputFormat::Csv,
+            &["^srcs_note$"],
+            QueryEvaluationValue::TargetSet(targets()),
+        )
+        .await?;
+        // Targets that don't have an attribute get an empty cell.
+        assert_eq!(
+            csv,
+            "target,srcs_note\nroot//foo:a,\"x, \"\"y\"\"\"\nroot//foo:b,\n"
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_ndjson_targets() -> anyhow::Result<()> {
+        let ndjson = print(
+            QueryOutputFormat::Ndjson,
+            &["^srcs_note$"],
+            QueryEvaluationValue::TargetSet(targets()),
+        )
+        .await?;
+        let lines = ndjson
+            .lines()
+            .map(serde_json::from_str)
+            .collect::<Result<Vec<serde_json::Value>, _>>()?;
+        assert_eq!(
+            lines,
+            vec![
+                serde_json::json!({"target": "root//foo:a", "srcs_note": "x, \"y\""}),
+                serde_json::json!({"target": "root//foo:b"}),
+            ]
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_csv_and_ndjson_files() -> anyhow::Result<()> {
+        assert_eq!(
+            print(
+                QueryOutputFormat::Csv,
+                &[],
+                QueryEvaluationValue::FileSet(files())
+            )
+            .await?,
+            "path\nfoo/a.txt\n\"foo/b,c.txt\"\n"
+        );
+        assert_eq!(
+            print(
+                QueryOutputFormat::Ndjson,
+                &[],
+                QueryEvaluationValue::FileSet(files())
+            )
+            .await?,
+            "\"foo/a.txt\"\n\"foo/b,c.txt\"\n"
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_graphml_files() {
+        assert!(
+            print(
+                QueryOutputFormat::Graphml,
+                &[],
+                QueryEvaluationValue::FileSet(files())
+            )
+            .await
+            .is_err()
+        );
+    }
+}
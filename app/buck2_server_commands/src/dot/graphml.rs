@@ -0,0 +1,189 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+//! Renders a `DotDigraph` as GraphML (see <http://graphml.graphdrawing.org/specification.html>).
+//!
+//! Node attributes become `<data>` elements. GraphML requires every attribute to be declared with
+//! a `<key>` before the graph, so the whole graph is collected before anything is written.
+
+use std::io::Write;
+
+use buck2_util::xml::xml_escape;
+use starlark_map::small_set::SmallSet;
+
+use crate::dot::DotDigraph;
+use crate::dot::DotNode;
+
+pub struct GraphML {}
+
+impl GraphML {
+    pub fn render<'a, T: DotDigraph<'a>, W: Write>(graph: &'a T, mut w: W) -> anyhow::Result<()> {
+        let mut keys: SmallSet<String> = SmallSet::new();
+        let mut nodes: Vec<(String, Vec<(String, String)>)> = Vec::new();
+        let mut edges: Vec<(String, String)> = Vec::new();
+
+        graph.for_each_node(|node| {
+            let attrs = node.attrs()?;
+            // `style` and `color` only describe how dot should draw the node, so are skipped.
+            let data: Vec<(String, String)> = attrs
+                .label
+                .map(|label| ("label".to_owned(), label))
+                .into_iter()
+                .chain(attrs.extra.into_iter())
+                .collect();
+            for (key, _) in &data {
+                if !keys.contains(key) {
+                    keys.insert(key.clone());
+                }
+            }
+            nodes.push((node.id(), data));
+            graph.for_each_edge(node, |edge| {
+                edges.push((edge.from.to_owned(), edge.to.to_owned()));
+                Ok(())
+            })?;
+            Ok(())
+        })?;
+
+        writeln!(w, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+        writeln!(
+            w,
+            r#"<graphml xmlns="http://graphml.graphdrawing.org/xmlns">"#
+        )?;
+        for key in &keys {
+            let key = xml_escape(key);
+            writeln!(
+                w,
+                r#"  <key id="{}" for="node" attr.name="{}" attr.type="string"/>"#,
+                key, key
+            )?;
+        }
+        writeln!(
+            w,
+            r#"  <graph id="{}" edgedefault="directed">"#,
+            xml_escape(graph.name())
+        )?;
+        for (id, data) in &nodes {
+            if data.is_empty() {
+                writeln!(w, r#"    <node id="{}"/>"#, xml_escape(id))?;
+                continue;
+            }
+            writeln!(w, r#"    <node id="{}">"#, xml_escape(id))?;
+            for (key, value) in data {
+                writeln!(
+                    w,
+                    r#"      <data key="{}">{}</data>"#,
+                    xml_escape(key),
+                    xml_escape(value)
+                )?;
+            }
+            writeln!(w, "    </node>")?;
+        }
+        for (from, to) in &edges {
+            writeln!(
+                w,
+                r#"    <edge source="{}" target="{}"/>"#,
+                xml_escape(from),
+                xml_escape(to)
+            )?;
+        }
+        writeln!(w, "  </graph>")?;
+        writeln!(w, "</graphml>")?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use starlark_map::small_map::SmallMap;
+
+    use super::*;
+    use crate::dot::DotEdge;
+    use crate::dot::DotNodeAttrs;
+
+    struct TestNode(&'static str, Vec<&'static str>);
+
+    impl DotNode for TestNode {
+        fn attrs(&self) -> anyhow::Result<DotNodeAttrs> {
+            let mut extra = SmallMap::new();
+            extra.insert("buck_deps".to_owned(), self.1.join(" & "));
+            Ok(DotNodeAttrs {
+                style: Some("filled".to_owned()),
+                extra,
+                ..DotNodeAttrs::default()
+            })
+        }
+
+        fn id(&self) -> String {
+            self.0.to_owned()
+        }
+    }
+
+    struct TestGraph(Vec<TestNode>);
+
+    impl<'a> DotDigraph<'a> for TestGraph {
+        type Node = TestNode;
+
+        fn name(&self) -> &str {
+            "result_graph"
+        }
+
+        fn for_each_node<F: FnMut(&Self::Node) -> anyhow::Result<()>>(
+            &'a self,
+            mut f: F,
+        ) -> anyhow::Result<()> {
+            for node in &self.0 {
+                f(node)?;
+            }
+            Ok(())
+        }
+
+        fn for_each_edge<F: FnMut(&DotEdge) -> anyhow::Result<()>>(
+            &'a self,
+            node: &Self::Node,
+            mut f: F,
+        ) -> anyhow::Result<()> {
+            for dep in &node.1 {
+                f(&DotEdge {
+                    from: node.0,
+                    to: dep,
+                })?;
+            }
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_render() -> anyhow::Result<()> {
+        let graph = TestGraph(vec![
+            TestNode("root//:a", vec!["root//:b", "root//:<c>"]),
+            TestNode("root//:b", vec![]),
+        ]);
+        let mut out = Vec::new();
+        GraphML::render(&graph, &mut out)?;
+        assert_eq!(
+            String::from_utf8(out)?,
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<graphml xmlns="http://graphml.graphdrawing.org/xmlns">
+  <key id="buck_deps" for="node" attr.name="buck_deps" attr.type="string"/>
+  <graph id="result_graph" edgedefault="directed">
+    <node id="root//:a">
+      <data key="buck_deps">root//:b &amp; root//:&lt;c&gt;</data>
+    </node>
+    <node id="root//:b">
+      <data key="buck_deps"></data>
+    </node>
+    <edge source="root//:a" target="root//:b"/>
+    <edge source="root//:a" target="root//:&lt;c&gt;"/>
+  </graph>
+</graphml>
+"#
+        );
+        Ok(())
+    }
+}
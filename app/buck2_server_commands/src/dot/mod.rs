@@ -28,6 +28,7 @@ use once_cell::sync::Lazy;
 use regex::Regex;
 use starlark_map::small_map::SmallMap;
 
+pub mod graphml;
 pub mod targets;
 
 #[derive(Default, Debug)]
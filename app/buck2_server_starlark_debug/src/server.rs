@@ -30,8 +30,12 @@ use starlark::debug::resolve_breakpoints;
 use starlark::debug::DapAdapter;
 use starlark::debug::DapAdapterClient;
 use starlark::debug::DapAdapterEvalHook;
+use starlark::debug::ExceptionBreakpoints;
 use starlark::debug::ResolvedBreakpoints;
 use starlark::debug::StepKind;
+use starlark::debug::StoppedReason;
+use starlark::debug::Variable;
+use starlark::debug::VariablePath;
 use starlark::syntax::AstModule;
 use starlark::syntax::Dialect;
 use starlark::syntax::DialectTypes;
@@ -69,6 +73,8 @@ fn capabilities() -> serde_json::Value {
         "supports_set_variable": true,
         "supports_step_in_targets_request": true,
         "supports_conditional_breakpoints": true,
+        "supports_log_points": true,
+        "exception_breakpoint_filters": ExceptionBreakpoints::dap_filters(),
 
         // This is different from starlark's `dap_capabilities`. The buck starlark debugger treats
         // each ongoing starlark Evaluation as a separate thread and handles requests appropriately.
@@ -203,8 +209,13 @@ impl BuckStarlarkDebuggerServer {
     }
 
     /// Called when a starlark evaluation is paused (e.g. at a breakpoint).
-    pub(crate) fn event_stopped(&self, hook_id: HookId) {
-        self.maybe_to_state(ServerMessage::EvalStopped { hook_id });
+    pub(crate) fn event_stopped(&self, hook_id: HookId, reason: StoppedReason) {
+        self.maybe_to_state(ServerMessage::EvalStopped { hook_id, reason });
+    }
+
+    /// Called when a starlark evaluation hits a logpoint.
+    pub(crate) fn event_output(&self, output: String) {
+        self.maybe_to_state(ServerMessage::EvalOutput { output });
     }
 
     /// Called to forward along requests from the DAP client.
@@ -245,6 +256,10 @@ enum ServerMessage {
     },
     EvalStopped {
         hook_id: HookId,
+        reason: StoppedReason,
+    },
+    EvalOutput {
+        output: String,
     },
     Detach,
 }
@@ -263,6 +278,9 @@ struct ServerState {
     /// The currently set breakpoints. New hooks will be initialized with these.
     set_breakpoints: HashMap<String, ResolvedBreakpoints>,
 
+    /// The currently set exception breakpoints. New hooks will be initialized with these.
+    exception_breakpoints: ExceptionBreakpoints,
+
     /// The project root is used to get the current source code to resolve breakpoints.
     project_root: ProjectRoot,
 
@@ -284,6 +302,8 @@ struct ServerState {
 }
 
 static TOP_FRAME_LOCALS_ID: i64 = 2000;
+/// Variables references above TOP_FRAME_LOCALS_ID refer to `HookState::variable_paths`.
+static MAX_VARIABLES_ID: i64 = 0xFFFF;
 
 impl DebugServer for ServerState {
     fn initialize(
@@ -320,11 +340,14 @@ impl DebugServer for ServerState {
 
     fn set_exception_breakpoints(
         &mut self,
-        _x: dap::SetExceptionBreakpointsArguments,
+        x: dap::SetExceptionBreakpointsArguments,
     ) -> anyhow::Result<()> {
-        // TODO(cjhopman): This may not make sense in starlark and could be a more informative error. Or possibly we
-        // could use it to break on `fail()`.
-        Err(StarlarkDebuggerError::Unimplemented.into())
+        let breakpoints = ExceptionBreakpoints::from_dap(&x)?;
+        for hook_state in self.current_hooks.values() {
+            hook_state.adapter.set_exception_breakpoints(breakpoints)?;
+        }
+        self.exception_breakpoints = breakpoints;
+        Ok(())
     }
 
     fn attach(&mut self, _x: dap::AttachRequestArguments) -> anyhow::Result<()> {
@@ -384,9 +407,8 @@ impl DebugServer for ServerState {
             scopes: vec![dap::Scope {
                 name: "Locals".to_owned(),
                 named_variables: Some(scopes_info.num_locals as i64),
-                // rewrite variables reference to include our threadid. we don't currently send back any
-                // variables other than locals so the TOP_FRAME_LOCALS_ID doesnt really matter (though we
-                // do check against it below).
+                // rewrite variables reference to include our threadid. references for the children of
+                // variables are allocated above TOP_FRAME_LOCALS_ID (see `variables` below).
                 variables_reference: (thread_id << 16) | TOP_FRAME_LOCALS_ID,
                 expensive: false,
                 column: None,
@@ -405,17 +427,20 @@ impl DebugServer for ServerState {
     ) -> anyhow::Result<dap::VariablesResponseBody> {
         let thread_id = x.variables_reference >> 16;
         let variables_id = x.variables_reference & 0xFFFF;
-        // We only understand the TOP_FRAME_LOCALS_ID id
-        if variables_id != TOP_FRAME_LOCALS_ID {
-            return Ok(dap::VariablesResponseBody {
-                variables: Vec::new(),
-            });
-        }
-
-        let hook = self.find_hook_by_pseudo_thread(thread_id)?;
-        let vars_info = hook.adapter.variables()?;
+        let hook = self.find_hook_by_pseudo_thread_mut(thread_id)?;
+        let vars = if variables_id == TOP_FRAME_LOCALS_ID {
+            hook.adapter.variables()?.locals
+        } else {
+            let path = variables_id
+                .checked_sub(TOP_FRAME_LOCALS_ID + 1)
+                .and_then(|i| hook.variable_paths.get(i as usize));
+            match path {
+                Some(path) => hook.adapter.inspect_variable(path.clone())?.sub_values,
+                None => Vec::new(),
+            }
+        };
         Ok(dap::VariablesResponseBody {
-            variables: vars_info.locals.into_map(|var| var.to_dap()),
+            variables: vars.into_map(|var| hook.variable_to_dap(thread_id, var)),
         })
     }
 
@@ -483,6 +508,7 @@ impl ServerState {
             next_pseudo_thread: 0,
             next_hook_id: HookId(0),
             set_breakpoints: HashMap::new(),
+            exception_breakpoints: ExceptionBreakpoints::default(),
         }
     }
 
@@ -583,7 +609,8 @@ impl ServerState {
                 };
                 self.to_client.send(ToClientMessage::Response(response))?;
             }
-            ServerMessage::EvalStopped { hook_id } => self.eval_stopped(hook_id)?,
+            ServerMessage::EvalStopped { hook_id, reason } => self.eval_stopped(hook_id, reason)?,
+            ServerMessage::EvalOutput { output } => self.eval_output(output)?,
             ServerMessage::Detach => {
                 self.detach();
                 return Ok(false);
@@ -625,11 +652,15 @@ impl ServerState {
             pseudo_thread_name: description,
             stopped_at: None,
             handle_id: handle.0.id,
+            variable_paths: Vec::new(),
         };
 
         for (source, breakpoints) in &self.set_breakpoints {
             hook_state.adapter.set_breakpoints(source, breakpoints)?;
         }
+        hook_state
+            .adapter
+            .set_exception_breakpoints(self.exception_breakpoints)?;
         self.current_hooks.insert(hook_id, hook_state);
 
         self.to_client.send(ToClientMessage::Event(dap_event(
@@ -665,7 +696,7 @@ impl ServerState {
         self.current_commands.remove(&handle_id);
     }
 
-    fn eval_stopped(&mut self, hook_id: HookId) -> anyhow::Result<()> {
+    fn eval_stopped(&mut self, hook_id: HookId, reason: StoppedReason) -> anyhow::Result<()> {
         debug!("eval stopped {}", hook_id);
         let mut state = self.current_hooks.get_mut(&hook_id).unwrap();
         let top_frame = state.adapter.top_frame();
@@ -673,16 +704,18 @@ impl ServerState {
             Ok(Some(v)) => describe_frame(v),
             _ => "???".to_owned(),
         };
-        state.stopped_at = Some(description);
+        state.stopped_at = Some(description.clone());
+        // Variable references are only valid until the evaluation resumes.
+        state.variable_paths.clear();
         let thread_id = state.pseudo_thread_id;
 
         let msg = dap::StoppedEventBody {
-            reason: "breakpoint".to_owned(),
+            reason: reason.dap_reason().to_owned(),
             thread_id: Some(thread_id as i64),
-            description: Some("Hello".to_owned()),
+            description: Some(description),
             all_threads_stopped: Some(false),
             preserve_focus_hint: None,
-            text: None,
+            text: reason.text().map(|v| v.to_owned()),
         };
 
         self.to_client
@@ -690,6 +723,22 @@ impl ServerState {
         Ok(())
     }
 
+    fn eval_output(&mut self, output: String) -> anyhow::Result<()> {
+        let msg = dap::OutputEventBody {
+            category: Some("console".to_owned()),
+            output: format!("{}\n", output),
+            variables_reference: None,
+            source: None,
+            line: None,
+            column: None,
+            data: None,
+        };
+
+        self.to_client
+            .send(ToClientMessage::Event(dap_event("output", Some(&msg))))?;
+        Ok(())
+    }
+
     fn detach(&mut self) {
         // Dropping the DapAdapter should make any hooked Evaluator continue freely.
         self.current_hooks.clear();
@@ -705,6 +754,14 @@ impl ServerState {
         Err(anyhow::anyhow!("can't find evaluator thread"))
     }
 
+    fn find_hook_by_pseudo_thread_mut(&mut self, thread_id: i64) -> anyhow::Result<&mut HookState> {
+        let thread_id = thread_id as u32;
+        self.current_hooks
+            .values_mut()
+            .find(|hook_state| hook_state.pseudo_thread_id == thread_id)
+            .ok_or_else(|| anyhow::anyhow!("can't find evaluator thread"))
+    }
+
     fn get_ast(&self, source: &ProjectRelativePath) -> anyhow::Result<AstModule> {
         debug!("tried to get ast `{}`", source);
         let abs_path = self.project_root.resolve(source);
@@ -736,8 +793,12 @@ struct BuckStarlarkDapAdapterClient {
 }
 
 impl DapAdapterClient for BuckStarlarkDapAdapterClient {
-    fn event_stopped(&self) {
-        self.handle.0.server.event_stopped(self.hook_id)
+    fn event_stopped(&self, reason: StoppedReason) {
+        self.handle.0.server.event_stopped(self.hook_id, reason)
+    }
+
+    fn event_output(&self, output: String) {
+        self.handle.0.server.event_output(output)
     }
 }
 
@@ -767,6 +828,24 @@ struct HookState {
     /// The id of the corresponding handle (also used for snapshots so a command can tell if a
     /// stopped evaluation is from itself or another command).
     handle_id: HandleId,
+    /// The variables that the DAP client can expand while the evaluation is stopped. The variables
+    /// reference for the entry at index `i` is `TOP_FRAME_LOCALS_ID + 1 + i`.
+    variable_paths: Vec<VariablePath>,
+}
+
+impl HookState {
+    /// Converts to the DAP variable, allocating a variables reference if it has children.
+    fn variable_to_dap(&mut self, thread_id: i64, var: Variable) -> dap::Variable {
+        let variables_id = TOP_FRAME_LOCALS_ID + 1 + self.variable_paths.len() as i64;
+        match &var.children {
+            Some(path) if variables_id <= MAX_VARIABLES_ID => {
+                self.variable_paths.push(path.clone());
+                var.to_dap((thread_id << 16) | variables_id)
+            }
+            // Either no children or we've run out of ids, either way the client can't expand it.
+            _ => var.to_dap(0),
+        }
+    }
 }
 
 /// Provides a simple description of a stack frame, typically "<file>:<line>".
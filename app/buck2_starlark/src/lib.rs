@@ -28,9 +28,11 @@ use buck2_server_ctx::ctx::ServerCommandContextTrait;
 use buck2_server_ctx::partial_result_dispatcher::PartialResultDispatcher;
 
 use crate::debug::StarlarkDebugAttachCommand;
+use crate::fmt::StarlarkFmtCommand;
 use crate::lint::StarlarkLintCommand;
 
 mod debug;
+mod fmt;
 mod lint;
 pub mod server;
 mod util;
@@ -49,6 +51,7 @@ pub enum StarlarkCommand {
 #[derive(Debug, clap::Subcommand, serde::Serialize, serde::Deserialize)]
 pub enum StarlarkOpaqueCommand {
     Lint(StarlarkLintCommand),
+    Fmt(StarlarkFmtCommand),
 }
 
 #[derive(Debug, clap::Parser, serde::Serialize, serde::Deserialize, Default)]
@@ -89,6 +92,7 @@ impl StarlarkOpaqueCommand {
     fn as_subcommand(&self) -> &dyn StarlarkOpaqueSubcommand {
         match self {
             Self::Lint(cmd) => cmd,
+            Self::Fmt(cmd) => cmd,
         }
     }
 }
@@ -9,8 +9,10 @@
 
 //! Implementation of the `TestOrchestrator` from `buck2_test_api`.
 
+use std::collections::BTreeMap;
 use std::collections::HashMap;
 use std::ffi::OsStr;
+use std::future::Future;
 use std::sync::Arc;
 use std::time::Duration;
 
@@ -224,6 +226,8 @@ impl<'a> TestOrchestrator for BuckTestOrchestrator<'a> {
         let test_executor = self
             .get_test_executor(&test_target, &test_info, executor_override, &fs)
             .await?;
+        // The test's own timeout takes precedence over the one the test runner asked for.
+        let timeout = test_info.timeout().unwrap_or(timeout);
         let test_executable_expanded = self
             .expand_test_executable(
                 &test_target,
@@ -285,9 +289,30 @@ impl<'a> TestOrchestrator for BuckTestOrchestrator<'a> {
             )
             .await?;
 
-        let (stdout, stderr, status, timing, outputs) = self
-            .execute_shared(&test_target, metadata, &test_executor, execution_request)
-            .await?;
+        let ((stdout, stderr, timing, outputs), status, previous_attempts) = {
+            let test_target = &test_target;
+            let test_executor = &test_executor;
+            let execution_request = &execution_request;
+            let metadata = &metadata;
+            retry_until_success(
+                test_info.max_attempts() as usize,
+                move |attempt| async move {
+                    if attempt > 0 {
+                        self.liveliness_observer.require_alive().await?;
+                    }
+                    let (stdout, stderr, status, timing, outputs) = self
+                        .execute_shared(
+                            test_target,
+                            metadata.clone(),
+                            test_executor,
+                            execution_request,
+                        )
+                        .await?;
+                    Ok(((stdout, stderr, timing, outputs), status))
+                },
+            )
+            .await?
+        };
 
         self.liveliness_observer.require_alive().await?;
 
@@ -319,6 +344,7 @@ impl<'a> TestOrchestrator for BuckTestOrchestrator<'a> {
             outputs,
             start_time: timing.start_time,
             execution_time: timing.execution_time,
+            previous_attempts,
         })
     }
 
@@ -474,7 +500,7 @@ impl<'b> BuckTestOrchestrator<'b> {
         test_target: &ConfiguredProvidersLabel,
         metadata: DisplayMetadata,
         executor: &CommandExecutor,
-        request: CommandExecutionRequest,
+        request: &CommandExecutionRequest,
     ) -> anyhow::Result<(
         ExecutionStream,
         ExecutionStream,
@@ -505,10 +531,10 @@ impl<'b> BuckTestOrchestrator<'b> {
 
         // For test execution, we currently do not do any cache queries
 
-        let prepared_action = executor.prepare_action(&request, self.digest_config)?;
+        let prepared_action = executor.prepare_action(request, self.digest_config)?;
         let prepared_command = PreparedCommand {
             target: &test_target as _,
-            request: &request,
+            request,
             prepared_action: &prepared_action,
             digest_config: self.digest_config,
         };
@@ -611,6 +637,7 @@ impl<'b> BuckTestOrchestrator<'b> {
         &self,
         fs: &ArtifactFs,
         test_target_node: &ConfiguredTargetNode,
+        test_info: &FrozenExternalRunnerTestInfo,
         executor_override: Option<&CommandExecutorConfig>,
     ) -> anyhow::Result<CommandExecutor> {
         let executor_config = match executor_override {
@@ -621,6 +648,12 @@ impl<'b> BuckTestOrchestrator<'b> {
                 .context("Error accessing executor config")?,
         };
 
+        let executor_config_with_re_properties =
+            with_test_re_properties(executor_config, test_info.re_properties());
+        let executor_config = executor_config_with_re_properties
+            .as_ref()
+            .unwrap_or(executor_config);
+
         let CommandExecutorResponse {
             executor,
             platform,
@@ -646,6 +679,7 @@ impl<'b> BuckTestOrchestrator<'b> {
                 path_separator: PathSeparatorKind::system_default(),
                 output_paths_behavior: Default::default(),
             },
+            action_timeout: None,
         };
         let CommandExecutorResponse {
             executor,
@@ -714,6 +748,7 @@ impl<'b> BuckTestOrchestrator<'b> {
         self.get_command_executor(
             fs,
             &node,
+            test_info,
             resolved_executor_override.as_ref().map(|a| &***a),
         )
         .context("Error constructing CommandExecutor")
@@ -1176,6 +1211,66 @@ struct ExpandedTestExecutable {
     declared_outputs: IndexMap<BuckOutTestPath, OutputCreationBehavior>,
 }
 
+/// Run `execute` until it exits successfully or `max_attempts` attempts were made. `execute` is
+/// passed the number of attempts made so far. Returns the last attempt along with its status, and
+/// the statuses of the earlier attempts. Errors are not retried.
+async fn retry_until_success<T, Fut>(
+    max_attempts: usize,
+    mut execute: impl FnMut(usize) -> Fut,
+) -> anyhow::Result<(T, ExecutionStatus, Vec<ExecutionStatus>)>
+where
+    Fut: Future<Output = anyhow::Result<(T, ExecutionStatus)>>,
+{
+    let mut previous_attempts = Vec::new();
+    loop {
+        let (res, status) = execute(previous_attempts.len()).await?;
+        let success = matches!(status, ExecutionStatus::Finished { exitcode: 0 });
+        if success || previous_attempts.len() + 1 >= max_attempts {
+            return Ok((res, status, previous_attempts));
+        }
+        previous_attempts.push(status);
+    }
+}
+
+/// Apply the RE properties requested by a test on top of those of the executor it runs on. Returns
+/// `None` if there is nothing to change.
+fn with_test_re_properties<'a>(
+    executor_config: &CommandExecutorConfig,
+    test_re_properties: impl IntoIterator<Item = (&'a str, &'a str)>,
+) -> Option<CommandExecutorConfig> {
+    let mut test_re_properties = test_re_properties.into_iter().peekable();
+    test_re_properties.peek()?;
+
+    match &executor_config.executor {
+        Executor::Local(..) => None,
+        Executor::RemoteEnabled {
+            executor,
+            re_properties,
+            re_use_case,
+            cache_upload_behavior,
+            remote_cache_enabled,
+        } => {
+            let mut merged = re_properties
+                .iter()
+                .map(|(k, v)| (k.clone(), v.clone()))
+                .collect::<BTreeMap<_, _>>();
+            merged.extend(test_re_properties.map(|(k, v)| (k.to_owned(), v.to_owned())));
+
+            Some(CommandExecutorConfig {
+                executor: Executor::RemoteEnabled {
+                    executor: executor.clone(),
+                    re_properties: merged.into_iter().collect(),
+                    re_use_case: *re_use_case,
+                    cache_upload_behavior: *cache_upload_behavior,
+                    remote_cache_enabled: *remote_cache_enabled,
+                },
+                options: executor_config.options,
+                action_timeout: executor_config.action_timeout,
+            })
+        }
+    }
+}
+
 fn create_prepare_for_local_execution_result(
     fs: &ArtifactFs,
     request: CommandExecutionRequest,
@@ -1311,6 +1406,8 @@ mod tests {
     use buck2_build_api::context::SetBuildContextData;
     use buck2_common::dice::cells::SetCellResolver;
     use buck2_common::dice::data::testing::SetTestingIoProvider;
+    use buck2_common::executor_config::RemoteEnabledExecutor;
+    use buck2_common::executor_config::RemoteExecutorUseCase;
     use buck2_common::liveliness_observer::NoopLivelinessObserver;
     use buck2_core::cells::name::CellName;
     use buck2_core::cells::CellResolver;
@@ -1479,4 +1576,112 @@ mod tests {
 
         Ok(())
     }
+
+    async fn run_attempts(
+        max_attempts: usize,
+        statuses: Vec<ExecutionStatus>,
+    ) -> anyhow::Result<(usize, ExecutionStatus, Vec<ExecutionStatus>)> {
+        let mut statuses = statuses.into_iter();
+        let mut calls = 0;
+        let (attempt, status, previous_attempts) = retry_until_success(max_attempts, |attempt| {
+            calls += 1;
+            let status = statuses.next().expect("Too many attempts");
+            future::ready(anyhow::Ok((attempt, status)))
+        })
+        .await?;
+        assert_eq!(calls, attempt + 1);
+        Ok((attempt, status, previous_attempts))
+    }
+
+    #[tokio::test]
+    async fn test_retry_flaky_then_pass() -> anyhow::Result<()> {
+        let fail = ExecutionStatus::Finished { exitcode: 1 };
+        let pass = ExecutionStatus::Finished { exitcode: 0 };
+
+        let (attempt, status, previous_attempts) =
+            run_attempts(3, vec![fail.clone(), pass.clone(), fail.clone()]).await?;
+        assert_eq!(attempt, 1);
+        assert_eq!(status, pass);
+        assert_eq!(previous_attempts, vec![fail]);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_retry_stops_after_max_attempts() -> anyhow::Result<()> {
+        let fail = ExecutionStatus::Finished { exitcode: 1 };
+        let timed_out = ExecutionStatus::TimedOut {
+            duration: Duration::from_secs(1),
+        };
+
+        let (attempt, status, previous_attempts) = run_attempts(
+            3,
+            vec![fail.clone(), timed_out.clone(), fail.clone(), fail.clone()],
+        )
+        .await?;
+        assert_eq!(attempt, 2);
+        assert_eq!(status, fail);
+        assert_eq!(previous_attempts, vec![fail.clone(), timed_out]);
+
+        // Without retries, a failure is final.
+        let (attempt, status, previous_attempts) =
+            run_attempts(1, vec![fail.clone(), fail.clone()]).await?;
+        assert_eq!(attempt, 0);
+        assert_eq!(status, fail);
+        assert_eq!(previous_attempts, vec![]);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_retry_does_not_retry_errors() -> anyhow::Result<()> {
+        let mut calls = 0;
+        let res = retry_until_success::<(), _>(3, |_| {
+            calls += 1;
+            future::ready(Err(anyhow::anyhow!("infra error")))
+        })
+        .await;
+        assert!(res.is_err());
+        assert_eq!(calls, 1);
+
+        Ok(())
+    }
+
+    fn remote_executor_config(re_properties: &[(&str, &str)]) -> CommandExecutorConfig {
+        CommandExecutorConfig {
+            executor: Executor::RemoteEnabled {
+                executor: RemoteEnabledExecutor::Remote(Default::default()),
+                re_properties: re_properties
+                    .iter()
+                    .map(|(k, v)| ((*k).to_owned(), (*v).to_owned()))
+                    .collect(),
+                re_use_case: RemoteExecutorUseCase::buck2_default(),
+                cache_upload_behavior: Default::default(),
+                remote_cache_enabled: true,
+            },
+            options: CommandExecutorConfig::testing_local().options,
+            action_timeout: Some(Duration::from_secs(10)),
+        }
+    }
+
+    #[test]
+    fn test_with_test_re_properties() {
+        let config = remote_executor_config(&[("platform", "linux"), ("size", "small")]);
+
+        assert_eq!(
+            with_test_re_properties(&config, [("size", "large"), ("gpu", "1")]),
+            Some(remote_executor_config(&[
+                ("gpu", "1"),
+                ("platform", "linux"),
+                ("size", "large"),
+            ])),
+        );
+
+        // Nothing to change.
+        assert_eq!(with_test_re_properties(&config, std::iter::empty()), None);
+        assert_eq!(
+            with_test_re_properties(&CommandExecutorConfig::testing_local(), [("size", "large")]),
+            None
+        );
+    }
 }
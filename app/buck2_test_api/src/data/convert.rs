@@ -620,6 +620,11 @@ impl TryInto<buck2_test_proto::ExecutionResult2> for ExecutionResult2 {
                     .try_into()?,
             ),
             execution_time: Some(self.execution_time.try_into()?),
+            previous_attempts: self
+                .previous_attempts
+                .into_iter()
+                .map(|status| status.try_into().context("Invalid `previous_attempts`"))
+                .collect::<Result<_, Self::Error>>()?,
         })
     }
 }
@@ -635,6 +640,7 @@ impl TryFrom<buck2_test_proto::ExecutionResult2> for ExecutionResult2 {
             outputs,
             start_time,
             execution_time,
+            previous_attempts,
         } = s;
         let status = status
             .context("Missing `status`")?
@@ -676,6 +682,11 @@ impl TryFrom<buck2_test_proto::ExecutionResult2> for ExecutionResult2 {
             convert::to_std_duration(execution_time.context("Missing `execution_time`")?)
                 .context("Invalid `execution_time`")?;
 
+        let previous_attempts = previous_attempts
+            .into_iter()
+            .map(|status| status.try_into().context("Invalid `previous_attempts`"))
+            .collect::<Result<_, Self::Error>>()?;
+
         Ok(ExecutionResult2 {
             status,
             stdout,
@@ -683,6 +694,7 @@ impl TryFrom<buck2_test_proto::ExecutionResult2> for ExecutionResult2 {
             outputs,
             start_time,
             execution_time,
+            previous_attempts,
         })
     }
 }
@@ -940,6 +952,12 @@ mod tests {
             .collect(),
             start_time: SystemTime::UNIX_EPOCH + Duration::from_secs(123),
             execution_time: Duration::from_secs(456),
+            previous_attempts: vec![
+                ExecutionStatus::Finished { exitcode: 1 },
+                ExecutionStatus::TimedOut {
+                    duration: Duration::from_secs(789),
+                },
+            ],
         };
         assert_roundtrips::<buck2_test_proto::ExecutionResult2, ExecutionResult2>(&result);
     }
@@ -217,6 +217,16 @@ pub struct ExecutionResult2 {
     pub outputs: HashMap<DeclaredOutput, Output>,
     pub start_time: SystemTime,
     pub execution_time: Duration,
+    /// The statuses of earlier attempts at this execution that were retried, oldest first. The
+    /// fields above describe the last attempt.
+    pub previous_attempts: Vec<ExecutionStatus>,
+}
+
+impl ExecutionResult2 {
+    /// The number of times this was executed.
+    pub fn attempts(&self) -> usize {
+        self.previous_attempts.len() + 1
+    }
 }
 
 #[derive(Clone, Debug, PartialEq)]
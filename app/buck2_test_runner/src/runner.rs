@@ -194,11 +194,19 @@ fn get_test_result(
         },
         ExecutionStatus::TimedOut { .. } => TestStatus::TIMEOUT,
     };
+    // Tests that the orchestrator retried are flaky if they eventually passed.
+    let msg = match execution_result.attempts() {
+        1 => None,
+        attempts if status == TestStatus::PASS => {
+            Some(format!("Flaky: passed after {} attempts", attempts))
+        }
+        attempts => Some(format!("Failed {} attempts", attempts)),
+    };
     TestResult {
         target,
         name,
         status,
-        msg: None,
+        msg,
         duration: Some(execution_result.execution_time),
         details: format!(
             "---- STDOUT ----\n{:?}\n---- STDERR ----\n{:?}\n",
@@ -221,3 +229,62 @@ impl RunVerdict {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+    use std::time::SystemTime;
+
+    use buck2_test_api::data::ExecutionStream;
+
+    use super::*;
+
+    fn execution_result(
+        status: ExecutionStatus,
+        previous_attempts: Vec<ExecutionStatus>,
+    ) -> ExecutionResult2 {
+        ExecutionResult2 {
+            status,
+            stdout: ExecutionStream::Inline(Vec::new()),
+            stderr: ExecutionStream::Inline(Vec::new()),
+            outputs: Default::default(),
+            start_time: SystemTime::UNIX_EPOCH,
+            execution_time: Duration::from_secs(1),
+            previous_attempts,
+        }
+    }
+
+    #[test]
+    fn test_get_test_result_attempts() {
+        let target = ConfiguredTargetHandle::from(0);
+        let pass = ExecutionStatus::Finished { exitcode: 0 };
+        let fail = ExecutionStatus::Finished { exitcode: 1 };
+
+        let result = get_test_result(
+            "test".to_owned(),
+            target,
+            execution_result(pass.clone(), Vec::new()),
+        );
+        assert_eq!(result.status, TestStatus::PASS);
+        assert_eq!(result.msg, None);
+
+        let result = get_test_result(
+            "test".to_owned(),
+            target,
+            execution_result(pass, vec![fail.clone(), fail.clone()]),
+        );
+        assert_eq!(result.status, TestStatus::PASS);
+        assert_eq!(
+            result.msg.as_deref(),
+            Some("Flaky: passed after 3 attempts")
+        );
+
+        let result = get_test_result(
+            "test".to_owned(),
+            target,
+            execution_result(fail.clone(), vec![fail]),
+        );
+        assert_eq!(result.status, TestStatus::FAIL);
+        assert_eq!(result.msg.as_deref(), Some("Failed 2 attempts"));
+    }
+}
@@ -19,3 +19,4 @@ pub mod process_stats;
 pub mod rtabort;
 pub mod thin_box;
 pub mod truncate;
+pub mod xml;
@@ -0,0 +1,45 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+/// Escape a string for use in XML attribute values and text. Characters that XML 1.0 doesn't
+/// allow at all are dropped.
+pub fn xml_escape(s: &str) -> String {
+    let mut res = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => res.push_str("&amp;"),
+            '<' => res.push_str("&lt;"),
+            '>' => res.push_str("&gt;"),
+            '"' => res.push_str("&quot;"),
+            '\'' => res.push_str("&apos;"),
+            '\t' | '\n' | '\r' => res.push(c),
+            c if c < ' ' => {}
+            c => res.push(c),
+        }
+    }
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::xml::xml_escape;
+
+    #[test]
+    fn test_xml_escape() {
+        assert_eq!(
+            xml_escape("<a href=\"x\">b & 'c'</a>"),
+            "&lt;a href=&quot;x&quot;&gt;b &amp; &apos;c&apos;&lt;/a&gt;"
+        );
+    }
+
+    #[test]
+    fn test_xml_escape_drops_invalid_characters() {
+        assert_eq!(xml_escape("a\u{1b}[0mb\n"), "a[0mb\n");
+    }
+}
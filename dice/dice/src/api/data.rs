@@ -59,6 +59,9 @@ use anymap::Map;
 use itertools::Itertools;
 use thiserror::Error;
 
+use crate::api::invalidation_tracker::InvalidationTracker;
+use crate::api::invalidation_tracker::InvalidationTrackerHolder;
+
 #[derive(Error, Debug)]
 #[error(
     "should store a value first before requesting a value for requested data key of type `{0}`. Known types are `{1}`"
@@ -89,4 +92,10 @@ impl DiceData {
             .get::<K>()
             .ok_or_else(|| MissingData(std::any::type_name::<K>(), self.1.iter().join(", ")))
     }
+
+    pub(crate) fn invalidation_tracker(&self) -> Option<&dyn InvalidationTracker> {
+        self.0
+            .get::<InvalidationTrackerHolder>()
+            .map(|holder| &*holder.0)
+    }
 }
@@ -194,6 +194,8 @@ use futures::future::Future;
 use serde::Serializer;
 
 use crate::api::cycles::DetectCycles;
+use crate::api::invalidation_tracker::InvalidationTracker;
+use crate::api::invalidation_tracker::InvalidationTrackerHolder;
 use crate::api::transaction::DiceTransactionUpdater;
 use crate::api::user_data::UserComputationData;
 use crate::api::which::WhichSpawner;
@@ -283,6 +285,11 @@ impl DiceDataBuilder {
         self.0.set(val);
     }
 
+    /// Notify the given tracker whenever cached values are invalidated.
+    pub fn set_invalidation_tracker(&mut self, tracker: Arc<dyn InvalidationTracker>) {
+        self.0.set(InvalidationTrackerHolder(tracker));
+    }
+
     pub fn build(self, detect_cycles: DetectCycles) -> Arc<Dice> {
         self.build_with_which_spawner(detect_cycles, WhichSpawner::ExplicitCancel)
     }
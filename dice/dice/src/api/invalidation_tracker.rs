@@ -0,0 +1,30 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+use std::any::Any;
+use std::sync::Arc;
+
+/// An InvalidationTracker can be used to identify which cached values were invalidated when
+/// committing a transaction, without having to request them again.
+///
+/// Trackers are notified while DICE updates its graph, so they should be cheap, and must not call
+/// back into DICE.
+pub trait InvalidationTracker: Send + Sync + 'static {
+    /// Receives when the cached value of a key was invalidated, either because it was changed, or
+    /// because something it depends on was. Keys that were never computed are not reported. The
+    /// caller will want to downcast the key to types they care about.
+    fn key_invalidated(&self, key: &dyn Any);
+
+    /// Receives when all the state of DICE was dropped, which invalidates every key. Those keys
+    /// are not reported individually.
+    fn all_invalidated(&self);
+}
+
+/// Holds the `InvalidationTracker` in the global `DiceData`.
+pub(crate) struct InvalidationTrackerHolder(pub(crate) Arc<dyn InvalidationTracker>);
@@ -17,6 +17,7 @@ pub mod dice;
 pub mod error;
 pub mod events;
 pub mod injected;
+pub mod invalidation_tracker;
 pub mod key;
 pub mod opaque;
 pub mod projection;
@@ -356,10 +356,22 @@ impl VersionedGraph {
 
     /// Invalidates an entry and its transitive rdeps. Returning true if this caused any type of
     /// change
+    #[cfg(test)]
     pub(crate) fn invalidate(
         &mut self,
         key: VersionedGraphKey,
         invalidate: InvalidateKind,
+    ) -> bool {
+        self.invalidate_reporting(key, invalidate, &mut Vec::new())
+    }
+
+    /// Like `invalidate`, but also appends every key whose computed value was invalidated to
+    /// `invalidated`.
+    pub(crate) fn invalidate_reporting(
+        &mut self,
+        key: VersionedGraphKey,
+        invalidate: InvalidateKind,
+        invalidated: &mut Vec<DiceKey>,
     ) -> bool {
         let rdeps = {
             match invalidate {
@@ -381,6 +393,8 @@ impl VersionedGraph {
 
                         if dirtied {
                             if let Some(e) = e.unpack_occupied() {
+                                invalidated.push(key.k);
+
                                 let queue = {
                                     let metadata = e.metadata();
                                     let rdeps = metadata.rdeps.rdeps();
@@ -423,6 +437,8 @@ impl VersionedGraph {
                         match entry {
                             Some(VersionedGraphNode::Occupied(occ)) => {
                                 if !occ.val().equality(&value) {
+                                    invalidated.push(key.k);
+
                                     occ.metadata()
                                         .rdeps
                                         .rdeps()
@@ -481,7 +497,7 @@ impl VersionedGraph {
             }
         };
 
-        self.invalidate_rdeps(key.v, rdeps);
+        self.invalidate_rdeps(key.v, rdeps, invalidated);
         true
     }
 
@@ -489,6 +505,7 @@ impl VersionedGraph {
         &mut self,
         version: VersionNumber,
         mut queue: Vec<(DiceKey, VersionNumber)>,
+        invalidated: &mut Vec<DiceKey>,
     ) {
         while let Some((rdep, relevant_version)) = queue.pop() {
             if let Some(node) = self.get_internal(VersionedGraphKey::new(relevant_version, rdep)) {
@@ -501,6 +518,8 @@ impl VersionedGraph {
                     // so we skip marking it as dirty, and rely on delayed propagation of dirty
 
                     if let Some(node) = node.unpack_occupied() {
+                        invalidated.push(rdep);
+
                         queue.extend({
                             let rdeps = node.metadata().rdeps.rdeps();
 
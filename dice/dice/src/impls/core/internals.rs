@@ -47,16 +47,18 @@ impl CoreState {
         }
     }
 
+    /// Returns the new version, and the keys whose computed values were invalidated.
     pub(super) fn update_state(
         &mut self,
         updates: impl IntoIterator<Item = (DiceKey, ChangeType)>,
-    ) -> VersionNumber {
+    ) -> (VersionNumber, Vec<DiceKey>) {
         let version_update = self.version_tracker.write();
         let v = version_update.version();
 
         let mut changes_recorded = false;
+        let mut invalidated = Vec::new();
         for (key, change) in updates {
-            changes_recorded |= self.graph.invalidate(
+            changes_recorded |= self.graph.invalidate_reporting(
                 VersionedGraphKey::new(v, key),
                 match change {
                     ChangeType::Invalidate => InvalidateKind::ForceDirty,
@@ -64,13 +66,16 @@ impl CoreState {
                     #[cfg(test)]
                     ChangeType::TestingSoftDirty => InvalidateKind::Invalidate,
                 },
+                &mut invalidated,
             );
         }
-        if changes_recorded {
+        let v = if changes_recorded {
             version_update.commit()
         } else {
             version_update.undo()
-        }
+        };
+
+        (v, invalidated)
     }
 
     pub(super) fn ctx_at_version(&mut self, v: VersionNumber) -> (VersionEpoch, SharedCache) {
@@ -190,12 +195,14 @@ mod tests {
         let mut core = CoreState::new();
 
         assert_eq!(
-            core.update_state([(DiceKey { index: 0 }, ChangeType::Invalidate)]),
+            core.update_state([(DiceKey { index: 0 }, ChangeType::Invalidate)])
+                .0,
             VersionNumber::new(1)
         );
 
         assert_eq!(
-            core.update_state([(DiceKey { index: 1 }, ChangeType::Invalidate)]),
+            core.update_state([(DiceKey { index: 1 }, ChangeType::Invalidate)])
+                .0,
             VersionNumber::new(2)
         );
     }
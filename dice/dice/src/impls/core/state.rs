@@ -38,10 +38,11 @@ use crate::HashMap;
 #[derivative(Debug)]
 pub(crate) enum StateRequest {
     /// Updates the core state with the given set of changes. The new VersionNumber that should be
-    /// used is sent back via the channel provided
+    /// used, and the keys whose computed values were invalidated, are sent back via the channel
+    /// provided
     UpdateState {
         changes: Vec<(DiceKey, ChangeType)>,
-        resp: Sender<VersionNumber>,
+        resp: Sender<(VersionNumber, Vec<DiceKey>)>,
     },
     /// Gets the current version number
     CurrentVersion { resp: Sender<VersionNumber> },
@@ -278,7 +278,7 @@ async fn test_values_gets_reevaluated_when_deps_change() -> anyhow::Result<()> {
         changes: vec![(key.dupe(), ChangeType::TestingSoftDirty)],
         resp: tx,
     });
-    let v = rx.await.unwrap();
+    let (v, _) = rx.await.unwrap();
     drop(guard);
     drop(ctx);
 
@@ -321,7 +321,7 @@ async fn test_values_gets_reevaluated_when_deps_change() -> anyhow::Result<()> {
         changes: vec![(key.dupe(), ChangeType::TestingSoftDirty)],
         resp: tx,
     });
-    let v = rx.await.unwrap();
+    let (v, _) = rx.await.unwrap();
 
     let (ctx, _guard) = dice.testing_shared_ctx(v).await;
     ctx.inject(
@@ -366,7 +366,7 @@ async fn test_values_gets_reevaluated_when_deps_change() -> anyhow::Result<()> {
         changes: vec![(key.dupe(), ChangeType::TestingSoftDirty)],
         resp: tx,
     });
-    let new_v = rx.await.unwrap();
+    let (new_v, _) = rx.await.unwrap();
 
     let (ctx, _guard) = dice.testing_shared_ctx(v).await;
     ctx.inject(
@@ -463,7 +463,7 @@ async fn when_equal_return_same_instance() -> anyhow::Result<()> {
         changes: vec![],
         resp: tx,
     });
-    let v = rx.await.unwrap();
+    let (v, _) = rx.await.unwrap();
 
     let (ctx, _guard) = dice.testing_shared_ctx(v).await;
     let eval = AsyncEvaluator {
@@ -491,7 +491,7 @@ async fn when_equal_return_same_instance() -> anyhow::Result<()> {
         changes: vec![(key.dupe(), ChangeType::Invalidate)],
         resp: tx,
     });
-    let v = rx.await.unwrap();
+    let (v, _) = rx.await.unwrap();
 
     let (ctx, _guard) = dice.testing_shared_ctx(v).await;
     let eval = AsyncEvaluator {
@@ -0,0 +1,179 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+use std::any::Any;
+use std::sync::Arc;
+use std::sync::Mutex;
+
+use allocative::Allocative;
+use async_trait::async_trait;
+use derive_more::Display;
+use dupe::Dupe;
+use more_futures::cancellation::CancellationContext;
+
+use crate::api::computations::DiceComputations;
+use crate::api::cycles::DetectCycles;
+use crate::api::dice::Dice;
+use crate::api::key::Key;
+use crate::DiceDataBuilder;
+use crate::InjectedKey;
+use crate::InvalidationTracker;
+
+#[derive(Default)]
+struct Tracker {
+    /// Keys invalidated, or None when everything was.
+    state: Mutex<Vec<Option<Kind>>>,
+}
+
+impl Tracker {
+    fn take(&self) -> Vec<Option<Kind>> {
+        let mut state = std::mem::take(&mut *self.state.lock().unwrap());
+        state.sort();
+        state
+    }
+}
+
+impl InvalidationTracker for Tracker {
+    fn key_invalidated(&self, key: &dyn Any) {
+        self.state.lock().unwrap().push(Some(Kind::from_any(key)));
+    }
+
+    fn all_invalidated(&self) {
+        self.state.lock().unwrap().push(None);
+    }
+}
+
+#[derive(PartialEq, Eq, PartialOrd, Ord, Debug, Dupe, Clone)]
+enum Kind {
+    Injected,
+    Stage0,
+    Stage1,
+}
+
+impl Kind {
+    fn from_any(key: &dyn Any) -> Self {
+        if key.is::<Injected>() {
+            return Self::Injected;
+        }
+
+        if key.is::<Stage0>() {
+            return Self::Stage0;
+        }
+
+        if key.is::<Stage1>() {
+            return Self::Stage1;
+        }
+
+        panic!("Unexpected key: {:?}", key)
+    }
+}
+
+#[derive(Clone, Dupe, Debug, Display, Eq, Hash, PartialEq, Allocative)]
+#[display(fmt = "{:?}", self)]
+struct Injected;
+
+#[async_trait]
+impl InjectedKey for Injected {
+    type Value = i32;
+
+    fn equality(x: &Self::Value, y: &Self::Value) -> bool {
+        x == y
+    }
+}
+
+#[derive(Clone, Dupe, Debug, Display, PartialEq, Eq, Hash, Allocative)]
+#[display(fmt = "{:?}", self)]
+struct Stage0;
+
+#[async_trait]
+impl Key for Stage0 {
+    type Value = i32;
+
+    async fn compute(
+        &self,
+        ctx: &DiceComputations,
+        _cancellations: &CancellationContext,
+    ) -> Self::Value {
+        ctx.compute(&Injected).await.unwrap()
+    }
+
+    fn equality(x: &Self::Value, y: &Self::Value) -> bool {
+        x == y
+    }
+}
+
+#[derive(Clone, Dupe, Debug, Display, PartialEq, Eq, Hash, Allocative)]
+#[display(fmt = "{:?}", self)]
+struct Stage1;
+
+#[async_trait]
+impl Key for Stage1 {
+    type Value = i32;
+
+    async fn compute(
+        &self,
+        ctx: &DiceComputations,
+        _cancellations: &CancellationContext,
+    ) -> Self::Value {
+        ctx.compute(&Stage0).await.unwrap()
+    }
+
+    fn equality(x: &Self::Value, y: &Self::Value) -> bool {
+        x == y
+    }
+}
+
+async fn test_invalidation_tracker_impl(mut builder: DiceDataBuilder) -> anyhow::Result<()> {
+    let tracker = Arc::new(Tracker::default());
+    builder.set_invalidation_tracker(tracker.dupe());
+    let dice = builder.build(DetectCycles::Enabled);
+
+    // Nothing was computed yet, so nothing is invalidated.
+    let mut updater = dice.updater();
+    updater.changed_to(vec![(Injected, 123)])?;
+    let transaction = updater.commit().await;
+    assert_eq!(transaction.compute(&Stage1).await?, 123);
+    drop(transaction);
+    assert_eq!(tracker.take(), vec![]);
+
+    // Changing a value invalidates it and everything that depends on it.
+    let mut updater = dice.updater();
+    updater.changed_to(vec![(Injected, 456)])?;
+    let transaction = updater.commit().await;
+    assert_eq!(
+        tracker.take(),
+        vec![Some(Kind::Injected), Some(Kind::Stage0), Some(Kind::Stage1)]
+    );
+    assert_eq!(transaction.compute(&Stage1).await?, 456);
+    drop(transaction);
+
+    // Invalidating a computed key invalidates its dependents, but not its dependencies.
+    let mut updater = dice.updater();
+    updater.changed(vec![Stage0])?;
+    let transaction = updater.commit().await;
+    assert_eq!(tracker.take(), vec![Some(Kind::Stage0), Some(Kind::Stage1)]);
+    drop(transaction);
+
+    // Dropping all the state invalidates everything.
+    let updater = dice.updater().unstable_take();
+    drop(updater.commit().await);
+    assert_eq!(tracker.take(), vec![None]);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_invalidation_tracker_legacy() -> anyhow::Result<()> {
+    test_invalidation_tracker_impl(Dice::builder()).await
+}
+
+#[tokio::test]
+async fn test_invalidation_tracker_modern() -> anyhow::Result<()> {
+    test_invalidation_tracker_impl(Dice::modern()).await
+}
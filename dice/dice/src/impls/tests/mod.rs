@@ -11,6 +11,7 @@ mod activation_tracker;
 mod demo;
 mod events;
 mod general;
+mod invalidation_tracker;
 mod keys;
 mod spawner;
 mod transients;
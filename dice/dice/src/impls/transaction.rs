@@ -142,7 +142,11 @@ impl TransactionUpdater {
     pub(crate) fn unstable_take(&self) {
         self.dice
             .state_handle
-            .request(StateRequest::UnstableDropEverything)
+            .request(StateRequest::UnstableDropEverything);
+
+        if let Some(tracker) = self.dice.global_data.invalidation_tracker() {
+            tracker.all_invalidated();
+        }
     }
 
     async fn commit_to_state(self) -> (SharedLiveTransactionCtx, ActiveTransactionGuard) {
@@ -152,7 +156,14 @@ impl TransactionUpdater {
             resp: tx,
         });
 
-        let v = rx.await.unwrap();
+        let (v, invalidated) = rx.await.unwrap();
+
+        if let Some(tracker) = self.dice.global_data.invalidation_tracker() {
+            for key in invalidated {
+                tracker.key_invalidated(self.dice.key_index.get(key).as_any());
+            }
+        }
+
         let guard = ActiveTransactionGuard::new(v, self.dice.state_handle.dupe());
         let (tx, rx) = oneshot::channel();
         self.dice.state_handle.request(StateRequest::CtxAtVersion {
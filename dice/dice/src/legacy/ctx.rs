@@ -260,7 +260,7 @@ impl DiceComputationsImplLegacy {
                 Box::new(move |version| {
                     debug!(msg = "marking value as changed", version = %version, key = %k);
                     let cache = dice.find_cache::<K>();
-                    cache.dirty(k, version, true);
+                    cache.dirty(k, version, true, dice.data.invalidation_tracker());
 
                     true
                 }),
@@ -285,7 +285,7 @@ impl DiceComputationsImplLegacy {
                 Box::new(move |version| {
                     let cache = dice.find_cache::<K>();
                     debug!(msg = "marking value as updated", version = %version, key = %k);
-                    cache.update_injected_value(k, version, v)
+                    cache.update_injected_value(k, version, v, dice.data.invalidation_tracker())
                 }),
             )
         })
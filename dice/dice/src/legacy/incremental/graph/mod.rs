@@ -21,6 +21,7 @@
 pub(crate) mod dependencies;
 pub(crate) mod storage_properties;
 
+use std::any::Any;
 use std::borrow::Borrow;
 use std::collections::Bound;
 use std::fmt::Debug;
@@ -278,6 +279,9 @@ pub(crate) trait GraphNodeDyn: Allocative + Send + Sync + 'static {
 
     fn key(&self) -> AnyKey;
 
+    /// The key as an Any, per `StorageProperties::to_key_any`.
+    fn to_key_any(&self) -> &dyn Any;
+
     fn id(&self) -> usize;
 }
 
@@ -312,6 +316,10 @@ where
         AnyKey::new(self.key.clone())
     }
 
+    fn to_key_any(&self) -> &dyn Any {
+        K::to_key_any(&self.key)
+    }
+
     fn id(&self) -> usize {
         self as *const Self as usize
     }
@@ -548,6 +556,10 @@ where
         AnyKey::new(self.key.clone())
     }
 
+    fn to_key_any(&self) -> &dyn Any {
+        K::to_key_any(&self.key)
+    }
+
     fn id(&self) -> usize {
         self as *const Self as usize
     }
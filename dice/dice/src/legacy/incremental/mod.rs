@@ -49,6 +49,7 @@ use tracing::Span;
 
 use crate::api::error::DiceResult;
 use crate::api::events::DiceEvent;
+use crate::api::invalidation_tracker::InvalidationTracker;
 use crate::api::key::Key;
 use crate::api::projection::DiceProjectionComputations;
 use crate::api::projection::ProjectionKey;
@@ -179,8 +180,14 @@ where
     }
 
     /// Dirties the value at K
-    #[instrument(level = "info", skip(self), fields(k = %k, version = %version))]
-    pub(crate) fn dirty(&self, k: K::Key, version: VersionNumber, force_dirty: bool) {
+    #[instrument(level = "info", skip(self, tracker), fields(k = %k, version = %version))]
+    pub(crate) fn dirty(
+        &self,
+        k: K::Key,
+        version: VersionNumber,
+        force_dirty: bool,
+        tracker: Option<&dyn InvalidationTracker>,
+    ) {
         // It is crucial that we dirty first before updating the rdeps.
         // This is related to the race condition where we invalidate while nodes are being inserted
         // into the graph at the same time:
@@ -205,12 +212,22 @@ where
             // if we actually did something, invalidate the rdeps of occupied entries
             if let Some(node) = node.unpack_occupied() {
                 debug!("dirtying rdeps");
-                Self::invalidate_rdeps(version, GraphNode::occupied(node.dupe()))
+                Self::invalidate_rdeps(version, GraphNode::occupied(node.dupe()), tracker)
             }
         }
     }
 
-    fn invalidate_rdeps(version: VersionNumber, invalidated: GraphNode<K>) {
+    /// Invalidates the rdeps of `invalidated`, reporting it and every rdep that is newly
+    /// invalidated to `tracker`.
+    fn invalidate_rdeps(
+        version: VersionNumber,
+        invalidated: GraphNode<K>,
+        tracker: Option<&dyn InvalidationTracker>,
+    ) {
+        if let Some(tracker) = tracker {
+            tracker.key_invalidated(K::to_key_any(invalidated.key()));
+        }
+
         let mut queue = {
             let metadata = invalidated.read_meta();
             let rdeps = metadata.rdeps.rdeps();
@@ -238,6 +255,10 @@ where
                     // the version it was dirtied at, it may no longer depend on the current node
                     // so we skip marking it as dirty, and rely on delayed propagation of dirty
                     if metadata.hist.mark_invalidated(version) {
+                        if let Some(tracker) = tracker {
+                            tracker.key_invalidated(node.to_key_any());
+                        }
+
                         queue.extend({
                             let rdeps = metadata.rdeps.rdeps();
 
@@ -269,12 +290,13 @@ where
     }
 
     /// Updates the value at K. Returns whether this injected value actually causes a change
-    #[instrument(level = "info", skip(self, res, ), fields(k = %k, version = %version))]
+    #[instrument(level = "info", skip(self, res, tracker), fields(k = %k, version = %version))]
     pub(crate) fn update_injected_value(
         self: &Arc<Self>,
         k: K::Key,
         version: VersionNumber,
         res: K::Value,
+        tracker: Option<&dyn InvalidationTracker>,
     ) -> bool {
         // It is crucial that we `dirty` first before updating the `rdeps`.
         // See `IncrementalEngine::dirty` below for details.
@@ -290,7 +312,7 @@ where
 
         if let Some(invalidated) = invalidated {
             debug!("dirtying rdeps");
-            Self::invalidate_rdeps(version, invalidated)
+            Self::invalidate_rdeps(version, invalidated, tracker)
         }
 
         let is_changed = new.get_history().latest_verified_before(version) == Some(version);
@@ -1582,6 +1604,10 @@ mod tests {
                 unimplemented!()
             }
 
+            fn to_key_any(&self) -> &dyn std::any::Any {
+                unimplemented!()
+            }
+
             fn id(&self) -> usize {
                 self as *const Self as usize
             }
@@ -1840,7 +1866,7 @@ mod tests {
         ));
 
         eval_result.store(10, Ordering::SeqCst);
-        assert!(engine.update_injected_value(1, VersionNumber::new(1), 100));
+        assert!(engine.update_injected_value(1, VersionNumber::new(1), 100, None));
         *dep.lock() = Some(ComputedDep::testing_new(
             Arc::downgrade(&engine.dupe()),
             VersionNumber::new(1),
@@ -1897,10 +1923,10 @@ mod tests {
         );
 
         // now force the dependency to have version numbers [1, 2]
-        assert!(!engine.update_injected_value(1, VersionNumber::new(2), 100));
+        assert!(!engine.update_injected_value(1, VersionNumber::new(2), 100, None));
         // also force dirty the root node so we actually check its deps since the above would
         // short circuit dirtying due to the dep value actually being equal.
-        engine.dirty(10, VersionNumber::new(2), false);
+        engine.dirty(10, VersionNumber::new(2), false, None);
         is_ran.store(false, Ordering::SeqCst);
         *dep.lock() = Some(ComputedDep::testing_new(
             Arc::downgrade(&engine.dupe()),
@@ -1941,7 +1967,7 @@ mod tests {
         );
 
         // now force the dependency to be different and have versions [3]
-        assert!(engine.update_injected_value(1, VersionNumber::new(3), 200));
+        assert!(engine.update_injected_value(1, VersionNumber::new(3), 200, None));
         eval_result.store(20, Ordering::SeqCst);
         *dep.lock() = Some(ComputedDep::testing_new(
             Arc::downgrade(&engine.dupe()),
@@ -2072,7 +2098,7 @@ mod tests {
         let _node3 = engine3
             .eval_entry_versioned(&3, &ctx, ComputationData::testing_new())
             .await;
-        engine0.dirty(0, VersionNumber::new(2), false);
+        engine0.dirty(0, VersionNumber::new(2), false, None);
 
         engine0
             .versioned_cache
@@ -2387,9 +2413,9 @@ mod tests {
             &0
         );
 
-        engine.dirty(1, VersionNumber::new(1), true);
-        engine.dirty(1, VersionNumber::new(2), true);
-        engine.dirty(1, VersionNumber::new(3), true);
+        engine.dirty(1, VersionNumber::new(1), true, None);
+        engine.dirty(1, VersionNumber::new(2), true, None);
+        engine.dirty(1, VersionNumber::new(3), true, None);
 
         let ctx = Arc::new(TransactionCtx::testing_new(VersionNumber::new(2)));
         assert_eq!(
@@ -2449,7 +2475,7 @@ mod tests {
             .val()
             .dupe();
 
-        engine.dirty(1, VersionNumber::new(1), false);
+        engine.dirty(1, VersionNumber::new(1), false, None);
 
         let ctx = Arc::new(TransactionCtx::testing_new(VersionNumber::new(1)));
         let second_node = engine
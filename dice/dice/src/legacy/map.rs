@@ -131,7 +131,7 @@ mod tests {
         let dice = DiceLegacy::builder().build(DetectCycles::Enabled, WhichSpawner::ExplicitCancel);
         let cache = map.find_cache(|| IncrementalEngine::new(StoragePropertiesForKey::new(&dice)));
         {
-            cache.update_injected_value(MyKey, VersionNumber::new(0), Bar);
+            cache.update_injected_value(MyKey, VersionNumber::new(0), Bar, None);
             assert_eq!(
                 cache
                     .get_cached(MyKey, VersionNumber::new(0), MinorVersion::testing_new(0))
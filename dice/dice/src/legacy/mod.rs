@@ -210,6 +210,9 @@ impl DiceLegacy {
     pub(crate) fn unstable_take(self: &Arc<DiceLegacy>) -> DiceMap {
         debug!(msg = "clearing all Dice state");
         let mut map = self.map.write();
+        if let Some(tracker) = self.data.invalidation_tracker() {
+            tracker.all_invalidated();
+        }
         std::mem::replace(&mut map, DiceMap::new())
     }
 
@@ -251,6 +251,7 @@ pub use crate::api::error::DiceResult;
 pub use crate::api::events::DiceEvent;
 pub use crate::api::events::DiceEventListener;
 pub use crate::api::injected::InjectedKey;
+pub use crate::api::invalidation_tracker::InvalidationTracker;
 pub use crate::api::key::Key;
 pub use crate::api::opaque::OpaqueValue;
 pub use crate::api::projection::DiceProjectionComputations;
@@ -9,12 +9,15 @@
 
 use std::collections::HashMap;
 use std::env::VarError;
+use std::path::PathBuf;
 use std::pin::Pin;
+use std::sync::atomic::Ordering;
 use std::sync::Arc;
 use std::sync::Mutex;
 
 use anyhow::Context;
 use buck2_re_configuration::Buck2OssReConfiguration;
+use buck2_re_configuration::Buck2OssReServiceTlsConfiguration;
 use buck2_re_configuration::HttpHeader;
 use dupe::Dupe;
 use futures::future::BoxFuture;
@@ -42,10 +45,13 @@ use re_grpc_proto::build::bazel::remote::execution::v2::Digest;
 use re_grpc_proto::build::bazel::remote::execution::v2::ExecuteOperationMetadata;
 use re_grpc_proto::build::bazel::remote::execution::v2::ExecuteRequest as GExecuteRequest;
 use re_grpc_proto::build::bazel::remote::execution::v2::ExecuteResponse as GExecuteResponse;
+use re_grpc_proto::build::bazel::remote::execution::v2::ExecutedActionMetadata;
 use re_grpc_proto::build::bazel::remote::execution::v2::FindMissingBlobsRequest;
 use re_grpc_proto::build::bazel::remote::execution::v2::FindMissingBlobsResponse;
 use re_grpc_proto::build::bazel::remote::execution::v2::GetActionResultRequest;
 use re_grpc_proto::build::bazel::remote::execution::v2::GetCapabilitiesRequest;
+use re_grpc_proto::build::bazel::remote::execution::v2::OutputDirectory;
+use re_grpc_proto::build::bazel::remote::execution::v2::OutputFile;
 use re_grpc_proto::build::bazel::remote::execution::v2::ResultsCachePolicy;
 use re_grpc_proto::google::bytestream::byte_stream_client::ByteStreamClient;
 use re_grpc_proto::google::bytestream::ReadRequest;
@@ -67,13 +73,20 @@ use tonic::service::Interceptor;
 use tonic::transport::channel::ClientTlsConfig;
 use tonic::transport::Certificate;
 use tonic::transport::Channel;
+use tonic::transport::Endpoint;
 use tonic::transport::Identity;
 use tonic::transport::Uri;
 
+use crate::compression::negotiate_compressor;
+use crate::compression::Compressor;
+use crate::compression::ReadDecoder;
 use crate::error::*;
+use crate::http_cache::HttpCacheClient;
 use crate::metadata::*;
 use crate::request::*;
 use crate::response::*;
+use crate::retry::RetryCounters;
+use crate::retry::RetryPolicy;
 
 // RBE Services (e.g. Buildbarn) may not be robust against having too many files open at
 // once. Limit to an arbitrary reasonable number since this information is not expressed
@@ -116,6 +129,13 @@ fn check_status(status: Status) -> Result<(), REClientError> {
     })
 }
 
+fn ttimestamp_to(ts: TTimestamp) -> Option<::prost_types::Timestamp> {
+    Some(::prost_types::Timestamp {
+        seconds: ts.seconds,
+        nanos: ts.nanos,
+    })
+}
+
 fn ttimestamp_from(ts: Option<::prost_types::Timestamp>) -> TTimestamp {
     match ts {
         Some(timestamp) => TTimestamp {
@@ -127,10 +147,38 @@ fn ttimestamp_from(ts: Option<::prost_types::Timestamp>) -> TTimestamp {
     }
 }
 
-async fn create_tls_config(opts: &Buck2OssReConfiguration) -> anyhow::Result<ClientTlsConfig> {
+/// The TLS settings for one of the RE services. Those can be set per-service, and otherwise
+/// default to the global settings.
+struct ServiceTlsOptions<'a> {
+    /// Whether TLS was explicitly enabled or disabled for this service.
+    tls: Option<bool>,
+    tls_ca_certs: Option<&'a str>,
+    tls_client_cert: Option<&'a str>,
+}
+
+impl<'a> ServiceTlsOptions<'a> {
+    fn new(
+        opts: &'a Buck2OssReConfiguration,
+        service: &'a Buck2OssReServiceTlsConfiguration,
+    ) -> Self {
+        Self {
+            tls: service.tls,
+            tls_ca_certs: service
+                .tls_ca_certs
+                .as_deref()
+                .or(opts.tls_ca_certs.as_deref()),
+            tls_client_cert: service
+                .tls_client_cert
+                .as_deref()
+                .or(opts.tls_client_cert.as_deref()),
+        }
+    }
+}
+
+async fn create_tls_config(opts: &ServiceTlsOptions<'_>) -> anyhow::Result<ClientTlsConfig> {
     let config = ClientTlsConfig::new();
 
-    let config = match opts.tls_ca_certs.as_ref() {
+    let config = match opts.tls_ca_certs {
         Some(tls_ca_certs) => {
             let tls_ca_certs =
                 substitute_env_vars(tls_ca_certs).context("Invalid `tls_ca_certs`")?;
@@ -145,7 +193,7 @@ async fn create_tls_config(opts: &Buck2OssReConfiguration) -> anyhow::Result<Cli
         }
     };
 
-    let config = match opts.tls_client_cert.as_ref() {
+    let config = match opts.tls_client_cert {
         Some(tls_client_cert) => {
             let tls_client_cert =
                 substitute_env_vars(tls_client_cert).context("Invalid `tls_client_cert`")?;
@@ -160,6 +208,49 @@ async fn create_tls_config(opts: &Buck2OssReConfiguration) -> anyhow::Result<Cli
     Ok(config)
 }
 
+/// The address of one of the RE services.
+#[derive(Debug, PartialEq, Eq)]
+enum ServiceAddress {
+    Uri(Uri),
+    Unix(PathBuf),
+}
+
+impl ServiceAddress {
+    fn parse(address: &str) -> anyhow::Result<Self> {
+        // This follows the GRPC spec for naming (see below): `unix:path` or
+        // `unix://absolute_path`.
+        if let Some(path) = address.strip_prefix("unix:") {
+            let path = path.strip_prefix("//").unwrap_or(path);
+            if path.is_empty() {
+                return Err(anyhow::anyhow!("Missing Unix domain socket path"));
+            }
+            return Ok(Self::Unix(PathBuf::from(path)));
+        }
+
+        Ok(Self::Uri(address.parse().context("Invalid address")?))
+    }
+}
+
+#[cfg(unix)]
+async fn connect_unix(path: PathBuf) -> anyhow::Result<Channel> {
+    use tower::service_fn;
+
+    // This URI is not used to connect (the connector below takes care of that), but Tonic
+    // requires one.
+    Ok(Endpoint::from_static("http://[::]:50051")
+        .connect_with_connector(service_fn(move |_: Uri| {
+            tokio::net::UnixStream::connect(path.clone())
+        }))
+        .await?)
+}
+
+#[cfg(not(unix))]
+async fn connect_unix(_path: PathBuf) -> anyhow::Result<Channel> {
+    Err(anyhow::anyhow!(
+        "Unix domain sockets are not supported on this platform"
+    ))
+}
+
 fn prepare_uri(uri: Uri, tls: bool) -> anyhow::Result<Uri> {
     // Now do some awkward things with the protocol. Why do we do all this? The reason is
     // because we'd like our configuration to not be super confusing. We don't want to e.g.
@@ -205,6 +296,10 @@ pub struct RECapabilities {
     max_msg_size: usize,
     /// Does the remote server support execution.
     exec_enabled: bool,
+    /// Compressor to use for ByteStream transfers and batch reads, if any.
+    compressor: Option<Compressor>,
+    /// Compressor to use for batch updates, if any.
+    batch_update_compressor: Option<Compressor>,
 }
 
 struct InstanceName(Option<String>);
@@ -225,42 +320,87 @@ impl InstanceName {
     }
 }
 
-pub struct REClientBuilder;
-
-impl REClientBuilder {
-    pub async fn build_and_connect(opts: &Buck2OssReConfiguration) -> anyhow::Result<REClient> {
-        // We just always create this just in case, so that we implicitly validate it if set.
-        let tls_config = create_tls_config(opts)
-            .await
-            .context("Invalid TLS config")?;
+/// Connect to one of the RE services. `service` is the prefix of its configuration keys.
+async fn connect_service(
+    opts: &Buck2OssReConfiguration,
+    address: Option<&str>,
+    service: &str,
+    service_tls: &Buck2OssReServiceTlsConfiguration,
+) -> anyhow::Result<Channel> {
+    let tls_opts = ServiceTlsOptions::new(opts, service_tls);
+
+    // We just always create this just in case, so that we implicitly validate it if set.
+    let tls_config = create_tls_config(&tls_opts)
+        .await
+        .with_context(|| format!("Invalid TLS config for `{}`", service))?;
 
-        let tls_config = &tls_config;
+    let address = address.context("No address")?;
+    let address = substitute_env_vars(address).context("Invalid address")?;
 
-        let create_channel = |address: Option<String>| async move {
-            let address = address.as_ref().context("No address")?;
-            let address = substitute_env_vars(address).context("Invalid address")?;
-            let uri = address.parse().context("Invalid address")?;
-            let uri = prepare_uri(uri, opts.tls).context("Invalid URI")?;
+    let channel = match ServiceAddress::parse(&address)? {
+        ServiceAddress::Unix(path) => {
+            if tls_opts.tls == Some(true) {
+                return Err(anyhow::anyhow!(
+                    "`{}_tls` is set, but TLS is not supported over Unix domain sockets (`{}`)",
+                    service,
+                    address
+                ));
+            }
+            connect_unix(path).await
+        }
+        ServiceAddress::Uri(uri) => {
+            let tls = tls_opts.tls.unwrap_or(opts.tls);
+            let uri = prepare_uri(uri, tls).context("Invalid URI")?;
 
             let mut channel = Channel::builder(uri);
-            if opts.tls {
-                channel = channel.tls_config(tls_config.clone())?;
+            if tls {
+                channel = channel.tls_config(tls_config)?;
             }
 
-            anyhow::Ok(
-                channel
-                    .connect()
-                    .await
-                    .with_context(|| format!("Error connecting to `{}`", address))?,
-            )
-        };
+            channel.connect().await.map_err(anyhow::Error::from)
+        }
+    };
+
+    channel.with_context(|| format!("Error connecting to `{}`", address))
+}
+
+pub struct REClientBuilder;
+
+impl REClientBuilder {
+    pub async fn build_and_connect(opts: &Buck2OssReConfiguration) -> anyhow::Result<REClient> {
+        let instance_name = InstanceName(opts.instance_name.clone());
+
+        if let Some(http_cache_address) = &opts.http_cache_address {
+            let http_cache = HttpCacheClient::new(http_cache_address, &opts.http_headers)
+                .context("Error creating HTTP cache client")?;
+            return Ok(REClient::new(
+                REBackend::HttpCache(http_cache),
+                instance_name,
+                RetryPolicy::from_config(opts),
+            ));
+        }
 
         let (cas, execution, action_cache, bytestream, capabilities) = futures::future::join5(
-            create_channel(opts.cas_address.clone()),
-            create_channel(opts.engine_address.clone()),
-            create_channel(opts.action_cache_address.clone()),
-            create_channel(opts.cas_address.clone()),
-            create_channel(opts.engine_address.clone()),
+            connect_service(opts, opts.cas_address.as_deref(), "cas", &opts.cas_tls),
+            connect_service(
+                opts,
+                opts.engine_address.as_deref(),
+                "engine",
+                &opts.engine_tls,
+            ),
+            connect_service(
+                opts,
+                opts.action_cache_address.as_deref(),
+                "action_cache",
+                &opts.action_cache_tls,
+            ),
+            connect_service(opts, opts.cas_address.as_deref(), "cas", &opts.cas_tls),
+            connect_service(
+                opts,
+                opts.engine_address.as_deref(),
+                "engine",
+                &opts.engine_tls,
+            ),
         )
         .await;
 
@@ -289,14 +429,16 @@ impl REClientBuilder {
             ),
         };
 
-        let instance_name = InstanceName(opts.instance_name.clone());
-
         let capabilities = if opts.capabilities.unwrap_or(true) {
-            Self::fetch_rbe_capabilities(&mut grpc_clients, &instance_name).await?
+            Self::fetch_rbe_capabilities(&mut grpc_clients, &instance_name, opts.compression)
+                .await?
         } else {
+            let compressor = negotiate_compressor(opts.compression, &[]);
             RECapabilities {
                 exec_enabled: true,
                 max_msg_size: DEFAULT_MAX_MSG_SIZE,
+                compressor,
+                batch_update_compressor: compressor,
             }
         };
 
@@ -304,12 +446,20 @@ impl REClientBuilder {
             return Err(anyhow::anyhow!("Server has remote execution disabled."));
         }
 
-        Ok(REClient::new(grpc_clients, capabilities, instance_name))
+        Ok(REClient::new(
+            REBackend::Grpc(GrpcBackend {
+                grpc_clients,
+                capabilities,
+            }),
+            instance_name,
+            RetryPolicy::from_config(opts),
+        ))
     }
 
     async fn fetch_rbe_capabilities(
         clients: &mut GRPCClients,
         instance_name: &InstanceName,
+        compression: Option<bool>,
     ) -> anyhow::Result<RECapabilities> {
         // TODO use more of the capabilities of the remote build executor
 
@@ -325,6 +475,8 @@ impl REClientBuilder {
         // with enough room for headers.
         let mut max_msg_size = DEFAULT_MAX_MSG_SIZE;
         let mut exec_enabled = true;
+        let mut compressor = negotiate_compressor(compression, &[]);
+        let mut batch_update_compressor = compressor;
 
         if let Some(cache_cap) = resp.cache_capabilities {
             let size = cache_cap.max_batch_total_size_bytes as usize;
@@ -332,6 +484,10 @@ impl REClientBuilder {
             if size != 0 {
                 max_msg_size = size;
             }
+
+            compressor = negotiate_compressor(compression, &cache_cap.supported_compressors);
+            batch_update_compressor =
+                negotiate_compressor(compression, &cache_cap.supported_batch_update_compressors);
         }
 
         if let Some(exec_cap) = resp.execution_capabilities {
@@ -341,6 +497,8 @@ impl REClientBuilder {
         Ok(RECapabilities {
             max_msg_size,
             exec_enabled,
+            compressor,
+            batch_update_compressor,
         })
     }
 }
@@ -406,10 +564,24 @@ pub struct REState {
     network_downloaded: i64, // in bytes
 }
 
-pub struct REClient {
+/// The protocol used to talk to RE.
+enum REBackend {
+    Grpc(GrpcBackend),
+    /// A cache using the Bazel HTTP caching protocol, which supports neither remote execution
+    /// nor instance names.
+    HttpCache(HttpCacheClient),
+}
+
+struct GrpcBackend {
     grpc_clients: GRPCClients,
     capabilities: RECapabilities,
+}
+
+pub struct REClient {
+    backend: REBackend,
     instance_name: InstanceName,
+    retry_policy: RetryPolicy,
+    retries: RetryCounters,
     state: Mutex<REState>,
 }
 
@@ -469,15 +641,12 @@ impl BatchUploadReqAggregator {
 }
 
 impl REClient {
-    fn new(
-        grpc_clients: GRPCClients,
-        capabilities: RECapabilities,
-        instance_name: InstanceName,
-    ) -> Self {
+    fn new(backend: REBackend, instance_name: InstanceName, retry_policy: RetryPolicy) -> Self {
         REClient {
-            grpc_clients,
-            capabilities,
+            backend,
             instance_name,
+            retry_policy,
+            retries: RetryCounters::default(),
             state: Mutex::new(REState::default()),
         }
     }
@@ -487,21 +656,30 @@ impl REClient {
         metadata: RemoteExecutionMetadata,
         request: ActionResultRequest,
     ) -> anyhow::Result<ActionResultResponse> {
-        let mut client = self.grpc_clients.action_cache_client.clone();
+        let grpc = match &self.backend {
+            REBackend::Grpc(grpc) => grpc,
+            REBackend::HttpCache(http_cache) => return http_cache.get_action_result(request).await,
+        };
 
-        let res = client
-            .get_action_result(with_internal_metadata(
-                GetActionResultRequest {
-                    instance_name: self.instance_name.as_str().to_owned(),
-                    action_digest: Some(tdigest_to(request.digest)),
-                    ..Default::default()
-                },
-                metadata,
-            ))
+        let request = GetActionResultRequest {
+            instance_name: self.instance_name.as_str().to_owned(),
+            action_digest: Some(tdigest_to(request.digest)),
+            ..Default::default()
+        };
+
+        let res = self
+            .retry_policy
+            .retry_unary(&self.retries.action_cache, || async {
+                let mut client = grpc.grpc_clients.action_cache_client.clone();
+                Ok(client
+                    .get_action_result(with_internal_metadata(request.clone(), metadata.clone()))
+                    .await?
+                    .into_inner())
+            })
             .await?;
 
         Ok(ActionResultResponse {
-            action_result: convert_action_result(res.into_inner())?,
+            action_result: convert_action_result(res)?,
             ttl: 0,
         })
     }
@@ -509,9 +687,12 @@ impl REClient {
     pub async fn write_action_result(
         &self,
         _metadata: RemoteExecutionMetadata,
-        _request: WriteActionResultRequest,
+        request: WriteActionResultRequest,
     ) -> anyhow::Result<WriteActionResultResponse> {
-        Err(anyhow::anyhow!("Not supported"))
+        match &self.backend {
+            REBackend::Grpc(..) => Err(anyhow::anyhow!("Not supported")),
+            REBackend::HttpCache(http_cache) => http_cache.write_action_result(request).await,
+        }
     }
 
     pub async fn execute_with_progress(
@@ -522,7 +703,14 @@ impl REClient {
         // TODO(aloiscochard): Map those properly in the request
         // use crate::proto::build::bazel::remote::execution::v2::ExecutionPolicy;
 
-        let mut client = self.grpc_clients.execution_client.clone();
+        let grpc = match &self.backend {
+            REBackend::Grpc(grpc) => grpc,
+            REBackend::HttpCache(..) => {
+                return Err(anyhow::anyhow!(
+                    "Remote execution is not supported when using an HTTP cache (`http_cache_address`)"
+                ));
+            }
+        };
 
         let action_digest = tdigest_to(execute_request.action_digest.clone());
 
@@ -534,10 +722,18 @@ impl REClient {
             action_digest: Some(action_digest.clone()),
         };
 
-        let stream = client
-            .execute(with_internal_metadata(request, metadata))
-            .await?
-            .into_inner();
+        // We only retry establishing the stream: once the server has accepted the action, it is
+        // executing it, and retrying would start another execution.
+        let stream = self
+            .retry_policy
+            .retry(&self.retries.executes, || async {
+                let mut client = grpc.grpc_clients.execution_client.clone();
+                Ok(client
+                    .execute(with_internal_metadata(request.clone(), metadata.clone()))
+                    .await?
+                    .into_inner())
+            })
+            .await?;
 
         let stream = futures::stream::try_unfold(stream, move |mut stream| async {
             let msg = match stream.try_next().await.context("RE channel error")? {
@@ -636,27 +832,43 @@ impl REClient {
         metadata: RemoteExecutionMetadata,
         request: UploadRequest,
     ) -> anyhow::Result<UploadResponse> {
+        let grpc = match &self.backend {
+            REBackend::Grpc(grpc) => grpc,
+            REBackend::HttpCache(http_cache) => return http_cache.upload(request).await,
+        };
+
+        let metadata = &metadata;
         upload_impl(
             &self.instance_name,
             request,
-            self.capabilities.max_msg_size,
-            |re_request| async {
-                let metadata = metadata.clone();
-                let mut cas_client = self.grpc_clients.cas_client.clone();
-                let resp = cas_client
-                    .batch_update_blobs(with_internal_metadata(re_request, metadata))
-                    .await?;
-                Ok(resp.into_inner())
+            grpc.capabilities.max_msg_size,
+            grpc.capabilities.compressor,
+            grpc.capabilities.batch_update_compressor,
+            |re_request| async move {
+                self.retry_policy
+                    .retry_unary(&self.retries.uploads, || async {
+                        let mut cas_client = grpc.grpc_clients.cas_client.clone();
+                        let resp = cas_client
+                            .batch_update_blobs(with_internal_metadata(
+                                re_request.clone(),
+                                metadata.clone(),
+                            ))
+                            .await?;
+                        Ok(resp.into_inner())
+                    })
+                    .await
             },
-            |segments| async {
-                let metadata = metadata.clone();
-                let mut bytestream_client = self.grpc_clients.bytestream_client.clone();
-                let requests = futures::stream::iter(segments);
-                let resp = bytestream_client
-                    .write(with_internal_metadata(requests, metadata))
-                    .await?;
-
-                Ok(resp.into_inner())
+            |segments| async move {
+                self.retry_policy
+                    .retry(&self.retries.uploads, || async {
+                        let mut bytestream_client = grpc.grpc_clients.bytestream_client.clone();
+                        let requests = futures::stream::iter(segments.clone());
+                        let resp = bytestream_client
+                            .write(with_internal_metadata(requests, metadata.clone()))
+                            .await?;
+                        Ok(resp.into_inner())
+                    })
+                    .await
             },
         )
         .await
@@ -676,26 +888,48 @@ impl REClient {
         metadata: RemoteExecutionMetadata,
         request: DownloadRequest,
     ) -> anyhow::Result<DownloadResponse> {
+        let grpc = match &self.backend {
+            REBackend::Grpc(grpc) => grpc,
+            REBackend::HttpCache(http_cache) => return http_cache.download(request).await,
+        };
+
+        let metadata = &metadata;
         download_impl(
             &self.instance_name,
             request,
-            self.capabilities.max_msg_size,
-            |re_request| async {
-                let metadata = metadata.clone();
-                let mut client = self.grpc_clients.cas_client.clone();
-                Ok(client
-                    .batch_read_blobs(with_internal_metadata(re_request, metadata))
-                    .await?
-                    .into_inner())
+            grpc.capabilities.max_msg_size,
+            grpc.capabilities.compressor,
+            |re_request| async move {
+                self.retry_policy
+                    .retry_unary(&self.retries.downloads, || async {
+                        let mut client = grpc.grpc_clients.cas_client.clone();
+                        Ok(client
+                            .batch_read_blobs(with_internal_metadata(
+                                re_request.clone(),
+                                metadata.clone(),
+                            ))
+                            .await?
+                            .into_inner())
+                    })
+                    .await
             },
             |read_request| {
                 let metadata = metadata.clone();
                 async move {
-                    let mut client = self.grpc_clients.bytestream_client.clone();
-                    let response = client
-                        .read(with_internal_metadata(read_request, metadata))
-                        .await?
-                        .into_inner();
+                    // We only retry opening the stream, not failures in the middle of it.
+                    let response = self
+                        .retry_policy
+                        .retry(&self.retries.downloads, || async {
+                            let mut client = grpc.grpc_clients.bytestream_client.clone();
+                            Ok(client
+                                .read(with_internal_metadata(
+                                    read_request.clone(),
+                                    metadata.clone(),
+                                ))
+                                .await?
+                                .into_inner())
+                        })
+                        .await?;
                     Ok(Box::pin(response.into_stream()))
                 }
             },
@@ -708,7 +942,11 @@ impl REClient {
         metadata: RemoteExecutionMetadata,
         request: GetDigestsTtlRequest,
     ) -> anyhow::Result<GetDigestsTtlResponse> {
-        let mut cas_client = self.grpc_clients.cas_client.clone();
+        let grpc = match &self.backend {
+            REBackend::Grpc(grpc) => grpc,
+            REBackend::HttpCache(http_cache) => return http_cache.get_digests_ttl(request).await,
+        };
+
         let mut remote_ttl: HashMap<TDigest, DigestWithTtl> = HashMap::new();
 
         for digest_chunk in request.digests.chunks(100) {
@@ -725,17 +963,24 @@ impl REClient {
                     },
                 );
             }
-            let missing_blobs = cas_client
-                .find_missing_blobs(with_internal_metadata(
-                    FindMissingBlobsRequest {
-                        instance_name: self.instance_name.as_str().to_owned(),
-                        blob_digests: digest_chunk.map(|b| tdigest_to(b.clone())),
-                    },
-                    metadata.clone(),
-                ))
+            let request = FindMissingBlobsRequest {
+                instance_name: self.instance_name.as_str().to_owned(),
+                blob_digests: digest_chunk.map(|b| tdigest_to(b.clone())),
+            };
+            let resp: FindMissingBlobsResponse = self
+                .retry_policy
+                .retry_unary(&self.retries.find_missing_blobs, || async {
+                    let mut cas_client = grpc.grpc_clients.cas_client.clone();
+                    Ok(cas_client
+                        .find_missing_blobs(with_internal_metadata(
+                            request.clone(),
+                            metadata.clone(),
+                        ))
+                        .await?
+                        .into_inner())
+                })
                 .await
                 .context("Failed to request what blobs are not present on remote")?;
-            let resp: FindMissingBlobsResponse = missing_blobs.into_inner();
             for digest in &resp.missing_blob_digests.map(|d| tdigest_from(d.clone())) {
                 // If it's present in the MissingBlobsResponse, it's expired on the remote and
                 // needs to be refetched.
@@ -784,12 +1029,22 @@ impl REClient {
         })
     }
 
+    pub fn get_retry_stats(&self) -> RetryStatisticsResponse {
+        RetryStatisticsResponse {
+            uploads: self.retries.uploads.load(Ordering::Relaxed),
+            downloads: self.retries.downloads.load(Ordering::Relaxed),
+            action_cache: self.retries.action_cache.load(Ordering::Relaxed),
+            executes: self.retries.executes.load(Ordering::Relaxed),
+            find_missing_blobs: self.retries.find_missing_blobs.load(Ordering::Relaxed),
+        }
+    }
+
     pub fn get_experiment_name(&self) -> anyhow::Result<Option<String>> {
         Ok(None)
     }
 }
 
-fn convert_action_result(action_result: ActionResult) -> anyhow::Result<TActionResult2> {
+pub(crate) fn convert_action_result(action_result: ActionResult) -> anyhow::Result<TActionResult2> {
     let execution_metadata = action_result
         .execution_metadata
         .with_context(|| "The execution metadata are not defined.")?;
@@ -874,10 +1129,65 @@ fn convert_action_result(action_result: ActionResult) -> anyhow::Result<TActionR
     Ok(action_result)
 }
 
+/// The inverse of `convert_action_result`.
+pub(crate) fn convert_t_action_result2(action_result: TActionResult2) -> ActionResult {
+    let execution_metadata = action_result.execution_metadata;
+
+    ActionResult {
+        output_files: action_result
+            .output_files
+            .into_map(|output_file| OutputFile {
+                path: output_file.name,
+                digest: Some(tdigest_to(output_file.digest.digest)),
+                is_executable: output_file.executable,
+                ..Default::default()
+            }),
+        output_directories: action_result
+            .output_directories
+            .into_map(|output_directory| OutputDirectory {
+                path: output_directory.path,
+                tree_digest: Some(tdigest_to(output_directory.tree_digest)),
+                ..Default::default()
+            }),
+        exit_code: action_result.exit_code,
+        stdout_raw: action_result.stdout_raw.unwrap_or_default(),
+        stdout_digest: action_result.stdout_digest.map(tdigest_to),
+        stderr_raw: action_result.stderr_raw.unwrap_or_default(),
+        stderr_digest: action_result.stderr_digest.map(tdigest_to),
+        execution_metadata: Some(ExecutedActionMetadata {
+            worker: execution_metadata.worker,
+            queued_timestamp: ttimestamp_to(execution_metadata.queued_timestamp),
+            worker_start_timestamp: ttimestamp_to(execution_metadata.worker_start_timestamp),
+            worker_completed_timestamp: ttimestamp_to(
+                execution_metadata.worker_completed_timestamp,
+            ),
+            input_fetch_start_timestamp: ttimestamp_to(
+                execution_metadata.input_fetch_start_timestamp,
+            ),
+            input_fetch_completed_timestamp: ttimestamp_to(
+                execution_metadata.input_fetch_completed_timestamp,
+            ),
+            execution_start_timestamp: ttimestamp_to(execution_metadata.execution_start_timestamp),
+            execution_completed_timestamp: ttimestamp_to(
+                execution_metadata.execution_completed_timestamp,
+            ),
+            output_upload_start_timestamp: ttimestamp_to(
+                execution_metadata.output_upload_start_timestamp,
+            ),
+            output_upload_completed_timestamp: ttimestamp_to(
+                execution_metadata.output_upload_completed_timestamp,
+            ),
+            ..Default::default()
+        }),
+        ..Default::default()
+    }
+}
+
 async fn download_impl<Byt, BytRet, Cas>(
     instance_name: &InstanceName,
     request: DownloadRequest,
     max_msg_size: usize,
+    compressor: Option<Compressor>,
     cas_f: impl Fn(BatchReadBlobsRequest) -> Cas,
     bystream_fut: impl Fn(ReadRequest) -> Byt + Sync + Send + Copy,
 ) -> anyhow::Result<DownloadResponse>
@@ -890,12 +1200,21 @@ where
         let hash = digest.hash;
         let size_in_bytes = digest.size_in_bytes;
 
-        let resource_name = format!(
-            "{}blobs/{}/{}",
-            instance_name.as_resource_prefix(),
-            hash,
-            size_in_bytes
-        );
+        let resource_name = match compressor {
+            Some(compressor) => format!(
+                "{}compressed-blobs/{}/{}/{}",
+                instance_name.as_resource_prefix(),
+                compressor.resource_name(),
+                hash,
+                size_in_bytes
+            ),
+            None => format!(
+                "{}blobs/{}/{}",
+                instance_name.as_resource_prefix(),
+                hash,
+                size_in_bytes
+            ),
+        };
 
         bystream_fut(ReadRequest {
             resource_name: resource_name.clone(),
@@ -909,6 +1228,11 @@ where
     let inlined_digests = request.inlined_digests.unwrap_or_default();
     let file_digests = request.file_digests.unwrap_or_default();
 
+    let mut acceptable_compressors = vec![compressor::Value::Identity as i32];
+    if compressor.is_some() {
+        acceptable_compressors.push(Compressor::to_proto(compressor));
+    }
+
     let mut curr_size = 0;
     let mut requests = vec![];
     let mut curr_digests = vec![];
@@ -929,7 +1253,7 @@ where
             let read_blob_req = BatchReadBlobsRequest {
                 instance_name: instance_name.as_str().to_owned(),
                 digests: std::mem::take(&mut curr_digests),
-                acceptable_compressors: vec![compressor::Value::Identity as i32],
+                acceptable_compressors: acceptable_compressors.clone(),
             };
             requests.push(read_blob_req);
         }
@@ -940,7 +1264,7 @@ where
         let read_blob_req = BatchReadBlobsRequest {
             instance_name: instance_name.as_str().to_owned(),
             digests: std::mem::take(&mut curr_digests),
-            acceptable_compressors: vec![compressor::Value::Identity as i32],
+            acceptable_compressors,
         };
         requests.push(read_blob_req);
     }
@@ -953,7 +1277,13 @@ where
         for r in resp.responses.into_iter() {
             let digest = tdigest_from(r.digest.context("Response digest not found.")?);
             check_status(r.status.unwrap_or_default())?;
-            batched_blobs_response.insert(digest, r.data);
+            let data = match Compressor::from_proto(r.compressor)? {
+                Some(compressor) => compressor
+                    .decompress(&r.data, digest.size_in_bytes)
+                    .with_context(|| format!("Error decompressing digest `{}`", digest))?,
+                None => r.data,
+            };
+            batched_blobs_response.insert(digest, data);
         }
     }
 
@@ -972,13 +1302,15 @@ where
     for digest in inlined_digests {
         let data = if digest.size_in_bytes as usize >= max_msg_size {
             let mut accum = vec![];
+            let mut decoder = ReadDecoder::new(compressor, digest.size_in_bytes)?;
             let mut responses = bystream_fut(digest.clone()).await?;
             while let Some(resp) = responses.next().await {
                 let data = resp
                     .with_context(|| format!("Failed to fetch inline digest: {digest}"))?
                     .data;
-                accum.extend_from_slice(&data);
+                accum.extend_from_slice(&decoder.push(data)?);
             }
+            accum.extend_from_slice(&decoder.finish()?);
             accum
         } else {
             get(&digest)?
@@ -1017,15 +1349,22 @@ where
                     .await
                     .with_context(|| format!("Error writing: {}", req.named_digest.digest))?;
             } else {
+                let mut decoder =
+                    ReadDecoder::new(compressor, req.named_digest.digest.size_in_bytes)?;
                 let mut responses = bystream_fut(req.named_digest.digest.clone()).await?;
                 while let Some(resp) = responses.next().await {
                     let data = resp
                         .with_context(|| format!("Failed to fetch file: {:?}", file))?
                         .data;
-                    file.write_all(&data).await.with_context(|| {
-                        format!("Error writing chunk of: {}", req.named_digest.digest)
-                    })?;
+                    file.write_all(&decoder.push(data)?)
+                        .await
+                        .with_context(|| {
+                            format!("Error writing chunk of: {}", req.named_digest.digest)
+                        })?;
                 }
+                file.write_all(&decoder.finish()?).await.with_context(|| {
+                    format!("Error writing chunk of: {}", req.named_digest.digest)
+                })?;
             }
             file.flush().await.context("Error flushing")?;
             anyhow::Ok(())
@@ -1050,6 +1389,8 @@ async fn upload_impl<Byt, Cas>(
     instance_name: &InstanceName,
     request: UploadRequest,
     max_msg_size: usize,
+    compressor: Option<Compressor>,
+    batch_compressor: Option<Compressor>,
     cas_f: impl Fn(BatchUpdateBlobsRequest) -> Cas + Sync + Send + Copy,
     bystream_fut: impl Fn(Vec<WriteRequest>) -> Byt + Sync + Send + Copy,
 ) -> anyhow::Result<UploadResponse>
@@ -1074,16 +1415,13 @@ where
             continue;
         }
 
-        let data = blob.blob;
-        let client_uuid = uuid::Uuid::new_v4().to_string();
-        let resource_name = format!(
-            "{}uploads/{}/blobs/{}/{}",
-            instance_name.as_resource_prefix(),
-            client_uuid,
-            hash,
-            size
-        );
+        let resource_name = upload_resource_name(instance_name, compressor, &hash, size);
         let fut = async move {
+            let data = match compressor {
+                Some(compressor) => compressor.compress(&blob.blob)?,
+                None => blob.blob,
+            };
+
             // Number of complete (non-partial) messages
             let mut upload_segments = vec![];
             for (i, chunk) in data.chunks(max_msg_size).enumerate() {
@@ -1097,7 +1435,7 @@ where
             upload_segments.last_mut().unwrap().finish_write = true;
 
             let resp = bystream_fut(upload_segments).await?;
-            if resp.committed_size != size {
+            if !is_committed(resp.committed_size, compressor, size, data.len()) {
                 return Err(anyhow::anyhow!(
                     "Failed to upload inline blob: invalid committed_size from WriteResponse"
                 ));
@@ -1117,37 +1455,54 @@ where
             batched_blob_updates.push(BatchUploadRequest::File(file));
             continue;
         }
-        let client_uuid = uuid::Uuid::new_v4().to_string();
-        let resource_name = format!(
-            "{}uploads/{}/blobs/{}/{}",
-            instance_name.as_resource_prefix(),
-            client_uuid,
-            hash.clone(),
-            size
-        );
+        let resource_name = upload_resource_name(instance_name, compressor, &hash, size);
         let fut = async move {
             let mut file = tokio::fs::File::open(&name)
                 .await
                 .with_context(|| format!("Opening `{name}` for reading failed"))?;
-            let mut data = vec![0; max_msg_size];
 
             let mut write_offset = 0;
             let mut upload_segments = Vec::new();
-            loop {
-                let length = file
-                    .read(&mut data)
-                    .await
-                    .with_context(|| format!("Error reading from {name}"))?;
-                if length == 0 {
-                    break;
+            match compressor {
+                Some(compressor) => {
+                    // We need the whole file to compress it, and all the segments are kept in
+                    // memory anyway.
+                    let mut data = Vec::with_capacity(size as usize);
+                    file.read_to_end(&mut data)
+                        .await
+                        .with_context(|| format!("Error reading from {name}"))?;
+                    let data = compressor
+                        .compress(&data)
+                        .with_context(|| format!("Error compressing {name}"))?;
+                    for chunk in data.chunks(max_msg_size) {
+                        upload_segments.push(WriteRequest {
+                            resource_name: resource_name.to_owned(),
+                            write_offset,
+                            finish_write: false,
+                            data: chunk.to_owned(),
+                        });
+                        write_offset += chunk.len() as i64;
+                    }
+                }
+                None => {
+                    let mut data = vec![0; max_msg_size];
+                    loop {
+                        let length = file
+                            .read(&mut data)
+                            .await
+                            .with_context(|| format!("Error reading from {name}"))?;
+                        if length == 0 {
+                            break;
+                        }
+                        upload_segments.push(WriteRequest {
+                            resource_name: resource_name.to_owned(),
+                            write_offset,
+                            finish_write: false,
+                            data: data[..length].to_owned(),
+                        });
+                        write_offset += length as i64;
+                    }
                 }
-                upload_segments.push(WriteRequest {
-                    resource_name: resource_name.to_owned(),
-                    write_offset,
-                    finish_write: false,
-                    data: data[..length].to_owned(),
-                });
-                write_offset += length as i64;
             }
             upload_segments
                 .last_mut()
@@ -1155,7 +1510,7 @@ where
                 .finish_write = true;
 
             let resp = bystream_fut(upload_segments).await?;
-            if resp.committed_size != size {
+            if !is_committed(resp.committed_size, compressor, size, write_offset as usize) {
                 return Err(anyhow::anyhow!(
                     "Failed to upload `{name}`: invalid committed_size from WriteResponse"
                 ));
@@ -1177,10 +1532,14 @@ where
             for blob in batch {
                 match blob {
                     BatchUploadRequest::Blob(blob) => {
+                        let data = match batch_compressor {
+                            Some(compressor) => compressor.compress(&blob.blob)?,
+                            None => blob.blob,
+                        };
                         re_request.requests.push(Request {
-                            digest: Some(tdigest_to(blob.digest.clone())),
-                            data: blob.blob.clone(),
-                            compressor: compressor::Value::Identity as i32,
+                            digest: Some(tdigest_to(blob.digest)),
+                            data,
+                            compressor: Compressor::to_proto(batch_compressor),
                         });
                     }
                     BatchUploadRequest::File(file) => {
@@ -1190,11 +1549,17 @@ where
                             .with_context(|| format!("Opening {} for writing failed", file.name))?;
                         let mut data = vec![];
                         fin.read_to_end(&mut data).await?;
+                        let data = match batch_compressor {
+                            Some(compressor) => compressor
+                                .compress(&data)
+                                .with_context(|| format!("Error compressing {}", file.name))?,
+                            None => data,
+                        };
 
                         re_request.requests.push(Request {
                             digest: Some(tdigest_to(file.digest.clone())),
                             data,
-                            compressor: compressor::Value::Identity as i32,
+                            compressor: Compressor::to_proto(batch_compressor),
                         });
                     }
                 }
@@ -1241,6 +1606,46 @@ where
     Ok(UploadResponse {})
 }
 
+fn upload_resource_name(
+    instance_name: &InstanceName,
+    compressor: Option<Compressor>,
+    hash: &str,
+    size: i64,
+) -> String {
+    let client_uuid = uuid::Uuid::new_v4().to_string();
+    match compressor {
+        Some(compressor) => format!(
+            "{}uploads/{}/compressed-blobs/{}/{}/{}",
+            instance_name.as_resource_prefix(),
+            client_uuid,
+            compressor.resource_name(),
+            hash,
+            size
+        ),
+        None => format!(
+            "{}uploads/{}/blobs/{}/{}",
+            instance_name.as_resource_prefix(),
+            client_uuid,
+            hash,
+            size
+        ),
+    }
+}
+
+/// Check the `committed_size` of a ByteStream write. For compressed uploads, that's the size of
+/// the compressed data we sent, or -1 if the blob was already uploaded by someone else.
+fn is_committed(
+    committed_size: i64,
+    compressor: Option<Compressor>,
+    size: i64,
+    sent_size: usize,
+) -> bool {
+    match compressor {
+        Some(_) => committed_size == -1 || committed_size == sent_size as i64,
+        None => committed_size == size,
+    }
+}
+
 fn with_internal_metadata<T>(t: T, metadata: RemoteExecutionMetadata) -> tonic::Request<T> {
     // This is pretty ugly, but the protobuf spec that defines this is internal, so considering
     // field numbers need to be stable anyway (= low risk), and this is not used in prod (= low
@@ -1273,7 +1678,7 @@ fn with_internal_metadata<T>(t: T, metadata: RemoteExecutionMetadata) -> tonic::
 }
 
 /// Replace occurrences of $FOO in a string with the value of the env var $FOO.
-fn substitute_env_vars(s: &str) -> anyhow::Result<String> {
+pub(crate) fn substitute_env_vars(s: &str) -> anyhow::Result<String> {
     substitute_env_vars_impl(s, |v| std::env::var(v))
 }
 
@@ -1310,6 +1715,86 @@ mod tests {
     use crate::NamedDigest;
     use crate::NamedDigestWithPermissions;
 
+    #[test]
+    fn test_convert_t_action_result2_roundtrip() -> anyhow::Result<()> {
+        let digest = |hash: &str| TDigest {
+            hash: hash.to_owned(),
+            size_in_bytes: 3,
+            ..Default::default()
+        };
+
+        let action_result = TActionResult2 {
+            output_files: vec![TFile {
+                digest: DigestWithStatus {
+                    digest: digest("aa"),
+                    ..Default::default()
+                },
+                name: "out/file".to_owned(),
+                executable: true,
+                ..Default::default()
+            }],
+            output_directories: vec![TDirectory2 {
+                path: "out/dir".to_owned(),
+                tree_digest: digest("bb"),
+                root_directory_digest: digest("bb"),
+                ..Default::default()
+            }],
+            exit_code: 0,
+            stdout_raw: Some(b"hello".to_vec()),
+            stderr_digest: Some(digest("cc")),
+            execution_metadata: TExecutedActionMetadata {
+                worker: "worker".to_owned(),
+                execution_start_timestamp: TTimestamp {
+                    seconds: 10,
+                    nanos: 20,
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+
+        let converted = convert_action_result(convert_t_action_result2(action_result))?;
+
+        assert_eq!(converted.output_files.len(), 1);
+        assert_eq!(converted.output_files[0].name, "out/file");
+        assert_eq!(converted.output_files[0].digest.digest, digest("aa"));
+        assert!(converted.output_files[0].executable);
+        assert_eq!(converted.output_directories.len(), 1);
+        assert_eq!(converted.output_directories[0].path, "out/dir");
+        assert_eq!(converted.output_directories[0].tree_digest, digest("bb"));
+        assert_eq!(converted.stdout_raw, Some(b"hello".to_vec()));
+        assert_eq!(converted.stderr_digest, Some(digest("cc")));
+        assert_eq!(converted.execution_metadata.worker, "worker");
+        assert_eq!(
+            converted
+                .execution_metadata
+                .execution_start_timestamp
+                .seconds,
+            10
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_service_address() -> anyhow::Result<()> {
+        assert_eq!(
+            ServiceAddress::parse("unix:///tmp/re.sock")?,
+            ServiceAddress::Unix(PathBuf::from("/tmp/re.sock"))
+        );
+        assert_eq!(
+            ServiceAddress::parse("unix:re.sock")?,
+            ServiceAddress::Unix(PathBuf::from("re.sock"))
+        );
+        assert!(ServiceAddress::parse("unix:").is_err());
+        assert_eq!(
+            ServiceAddress::parse("grpc://localhost:8980")?,
+            ServiceAddress::Uri(Uri::from_static("grpc://localhost:8980"))
+        );
+        Ok(())
+    }
+
     #[tokio::test]
     async fn test_download_named() -> anyhow::Result<()> {
         let work = tempfile::tempdir()?;
@@ -1376,6 +1861,7 @@ mod tests {
             &InstanceName(None),
             req,
             10000,
+            None,
             |req| {
                 let res = res.clone();
                 let digest1 = digest1.clone();
@@ -1482,6 +1968,7 @@ mod tests {
             &InstanceName(None),
             req,
             10, // kept small to simulate a large file download
+            None,
             |req| {
                 let res = res.clone();
                 let digest1 = digest1.clone();
@@ -1563,6 +2050,7 @@ mod tests {
             &InstanceName(None),
             req,
             100000,
+            None,
             |req| {
                 let res = res.clone();
                 let digest1 = digest1.clone();
@@ -1636,6 +2124,7 @@ mod tests {
             &InstanceName(None),
             req,
             10, // intentionally small value to keep data in the test blobs small
+            None,
             |req| {
                 let res = res.clone();
                 let digest1 = digest1.clone();
@@ -1691,6 +2180,7 @@ mod tests {
             &InstanceName(None),
             req,
             100000,
+            None,
             |req| {
                 let res = res.clone();
                 async move {
@@ -1729,6 +2219,7 @@ mod tests {
             &InstanceName(Some("instance".to_owned())),
             req,
             0,
+            None,
             |_req| async { panic!("not called") },
             |req| async move {
                 assert_eq!(req.resource_name, "instance/blobs/aa/0");
@@ -1798,6 +2289,8 @@ mod tests {
             &InstanceName(None),
             req,
             10000,
+            None,
+            None,
             |req| {
                 let res = res.clone();
                 let digest1 = digest1.clone();
@@ -1880,6 +2373,8 @@ mod tests {
             &InstanceName(None),
             req,
             10, // kept small to simulate a large file upload
+            None,
+            None,
             |req| {
                 let res = res.clone();
                 let digest1 = digest1.clone();
@@ -1953,6 +2448,8 @@ mod tests {
             &InstanceName(None),
             req,
             10, // kept small to simulate a large inlined upload
+            None,
+            None,
             |req| {
                 let res = res.clone();
                 let digest1 = digest1.clone();
@@ -2073,6 +2570,8 @@ mod tests {
             &InstanceName(None),
             req,
             3,
+            None,
+            None,
             |_req| async move {
                 panic!("Not called");
             },
@@ -2113,6 +2612,8 @@ mod tests {
             &InstanceName(None),
             req,
             0,
+            None,
+            None,
             |_req| async move {
                 panic!("Not called");
             },
@@ -2158,6 +2659,8 @@ mod tests {
             &InstanceName(Some("instance".to_owned())),
             req,
             1,
+            None,
+            None,
             |_req| async move {
                 panic!("Not called");
             },
@@ -2172,6 +2675,169 @@ mod tests {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn test_download_compressed() -> anyhow::Result<()> {
+        let digest1 = &TDigest {
+            hash: "aa".to_owned(),
+            size_in_bytes: 3,
+            ..Default::default()
+        };
+
+        let blob_data = vec![
+            1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16, 17, 18,
+        ];
+
+        let digest2 = &TDigest {
+            hash: "xl".to_owned(),
+            size_in_bytes: 18,
+            ..Default::default()
+        };
+
+        let req = DownloadRequest {
+            inlined_digests: Some(vec![digest1.clone(), digest2.clone()]),
+            ..Default::default()
+        };
+
+        let res = BatchReadBlobsResponse {
+            responses: vec![batch_read_blobs_response::Response {
+                digest: Some(tdigest_to(digest1.clone())),
+                data: Compressor::Zstd.compress(&[1, 2, 3])?,
+                compressor: compressor::Value::Zstd as i32,
+                ..Default::default()
+            }],
+        };
+
+        let compressed = Compressor::Zstd.compress(&blob_data)?;
+
+        let res = download_impl(
+            &InstanceName(None),
+            req,
+            10,
+            Some(Compressor::Zstd),
+            |req| {
+                let res = res.clone();
+                async move {
+                    assert_eq!(
+                        req.acceptable_compressors,
+                        vec![
+                            compressor::Value::Identity as i32,
+                            compressor::Value::Zstd as i32
+                        ]
+                    );
+                    Ok(res)
+                }
+            },
+            |req| {
+                let compressed = compressed.clone();
+                async move {
+                    assert_eq!(req.resource_name, "compressed-blobs/zstd/xl/18");
+                    anyhow::Ok(Box::pin(futures::stream::iter(
+                        compressed
+                            .chunks(5)
+                            .map(|data| {
+                                Ok(ReadResponse {
+                                    data: data.to_vec(),
+                                })
+                            })
+                            .collect::<Vec<_>>(),
+                    )))
+                }
+            },
+        )
+        .await?;
+
+        let inlined_blobs = res.inlined_blobs.unwrap();
+
+        assert_eq!(inlined_blobs.len(), 2);
+        assert_eq!(inlined_blobs[0].blob, vec![1, 2, 3]);
+        assert_eq!(inlined_blobs[1].blob, blob_data);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_upload_compressed() -> anyhow::Result<()> {
+        let digest1 = TDigest {
+            hash: "aa".to_owned(),
+            size_in_bytes: 3,
+            ..Default::default()
+        };
+        let blob_data1 = b"aaa".to_vec();
+
+        let digest2 = TDigest {
+            hash: "xl".to_owned(),
+            size_in_bytes: 18,
+            ..Default::default()
+        };
+        let blob_data2 = vec![0; 18];
+
+        let req = UploadRequest {
+            inlined_blobs_with_digest: Some(vec![
+                InlinedBlobWithDigest {
+                    blob: blob_data2.clone(),
+                    digest: digest2.clone(),
+                    ..Default::default()
+                },
+                InlinedBlobWithDigest {
+                    blob: blob_data1.clone(),
+                    digest: digest1.clone(),
+                    ..Default::default()
+                },
+            ]),
+            ..Default::default()
+        };
+
+        let res = BatchUpdateBlobsResponse {
+            responses: vec![batch_update_blobs_response::Response {
+                digest: Some(tdigest_to(digest1.clone())),
+                status: Some(Status::default()),
+            }],
+        };
+
+        upload_impl(
+            &InstanceName(None),
+            req,
+            10,
+            Some(Compressor::Zstd),
+            Some(Compressor::Deflate),
+            |req| {
+                let res = res.clone();
+                let blob_data1 = blob_data1.clone();
+                async move {
+                    assert_eq!(req.requests.len(), 1);
+                    assert_eq!(
+                        req.requests[0].compressor,
+                        compressor::Value::Deflate as i32
+                    );
+                    assert_eq!(
+                        Compressor::Deflate.decompress(&req.requests[0].data, 3)?,
+                        blob_data1
+                    );
+                    Ok(res)
+                }
+            },
+            |write_reqs| {
+                let blob_data2 = blob_data2.clone();
+                async move {
+                    assert!(
+                        write_reqs[0]
+                            .resource_name
+                            .ends_with("/compressed-blobs/zstd/xl/18")
+                    );
+                    assert!(write_reqs.last().unwrap().finish_write);
+                    let data = write_reqs
+                        .iter()
+                        .flat_map(|r| r.data.iter().copied())
+                        .collect::<Vec<_>>();
+                    assert_eq!(Compressor::Zstd.decompress(&data, 18)?, blob_data2);
+                    anyhow::Ok(WriteResponse { committed_size: -1 })
+                }
+            },
+        )
+        .await?;
+        Ok(())
+    }
+
     #[test]
     fn test_substitute_env_vars() {
         let getter = |s: &str| match s {
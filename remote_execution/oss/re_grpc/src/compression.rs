@@ -0,0 +1,262 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+use std::io::Write;
+
+use anyhow::Context;
+use dupe::Dupe;
+use re_grpc_proto::build::bazel::remote::execution::v2::compressor;
+
+/// Compression level used for zstd. Level 1 is the one recommended for network transfers: the
+/// ratio is only marginally worse than the default, but it is much faster.
+const ZSTD_LEVEL: i32 = 1;
+
+/// A compressor supported by both this client and the RE server, used to transfer blobs from and
+/// to the CAS.
+#[derive(Copy, Clone, Dupe, Debug, PartialEq, Eq)]
+pub(crate) enum Compressor {
+    Zstd,
+    Deflate,
+}
+
+impl Compressor {
+    /// Compressors we support, in order of preference.
+    const PREFERRED: &'static [Compressor] = &[Compressor::Zstd, Compressor::Deflate];
+
+    pub(crate) fn from_proto(value: i32) -> anyhow::Result<Option<Self>> {
+        match compressor::Value::from_i32(value) {
+            Some(compressor::Value::Identity) => Ok(None),
+            Some(compressor::Value::Zstd) => Ok(Some(Self::Zstd)),
+            Some(compressor::Value::Deflate) => Ok(Some(Self::Deflate)),
+            None => Err(anyhow::anyhow!("Unsupported compressor: `{}`", value)),
+        }
+    }
+
+    pub(crate) fn to_proto(compressor: Option<Self>) -> i32 {
+        let value = match compressor {
+            None => compressor::Value::Identity,
+            Some(Self::Zstd) => compressor::Value::Zstd,
+            Some(Self::Deflate) => compressor::Value::Deflate,
+        };
+        value as i32
+    }
+
+    /// The name of this compressor in `compressed-blobs/{compressor}/...` ByteStream resources.
+    pub(crate) fn resource_name(self) -> &'static str {
+        match self {
+            Self::Zstd => "zstd",
+            Self::Deflate => "deflate",
+        }
+    }
+
+    pub(crate) fn compress(self, data: &[u8]) -> anyhow::Result<Vec<u8>> {
+        match self {
+            Self::Zstd => {
+                zstd::bulk::compress(data, ZSTD_LEVEL).context("Error compressing (zstd)")
+            }
+            Self::Deflate => {
+                let mut encoder =
+                    flate2::write::DeflateEncoder::new(Vec::new(), flate2::Compression::default());
+                encoder
+                    .write_all(data)
+                    .and_then(|()| encoder.finish())
+                    .context("Error compressing (deflate)")
+            }
+        }
+    }
+
+    /// Decompress a blob, checking that it has the expected (uncompressed) size.
+    pub(crate) fn decompress(self, data: &[u8], expected_size: i64) -> anyhow::Result<Vec<u8>> {
+        let mut decoder = self.decoder()?;
+        let mut out = decoder.push(data)?;
+        out.extend(decoder.finish()?);
+        check_decompressed_size(out.len() as i64, expected_size)?;
+        Ok(out)
+    }
+
+    /// Create a streaming decoder, used to decompress ByteStream reads as chunks arrive.
+    pub(crate) fn decoder(self) -> anyhow::Result<Decoder> {
+        Ok(match self {
+            Self::Zstd => Decoder::Zstd(
+                zstd::stream::write::Decoder::new(Vec::new())
+                    .context("Error creating zstd decoder")?,
+            ),
+            Self::Deflate => Decoder::Deflate(flate2::write::DeflateDecoder::new(Vec::new())),
+        })
+    }
+}
+
+fn check_decompressed_size(actual: i64, expected: i64) -> anyhow::Result<()> {
+    if actual != expected {
+        return Err(anyhow::anyhow!(
+            "Decompressed blob has size `{}`, expected `{}`",
+            actual,
+            expected
+        ));
+    }
+    Ok(())
+}
+
+/// Pick the compressor to use given the user's configuration and the compressors the server
+/// advertises. When compression is forced on but the server advertises nothing we support, we
+/// assume it supports zstd (this is mostly useful when querying capabilities is disabled).
+pub(crate) fn negotiate_compressor(config: Option<bool>, supported: &[i32]) -> Option<Compressor> {
+    let advertised = Compressor::PREFERRED
+        .iter()
+        .copied()
+        .find(|c| supported.contains(&Compressor::to_proto(Some(*c))));
+
+    match config {
+        Some(false) => None,
+        Some(true) => Some(advertised.unwrap_or(Compressor::Zstd)),
+        None => advertised,
+    }
+}
+
+/// Streaming decoder for a compressed blob. Compressed data is pushed in and whatever
+/// decompressed output is available gets returned.
+pub(crate) enum Decoder {
+    Zstd(zstd::stream::write::Decoder<'static, Vec<u8>>),
+    Deflate(flate2::write::DeflateDecoder<Vec<u8>>),
+}
+
+impl Decoder {
+    pub(crate) fn push(&mut self, data: &[u8]) -> anyhow::Result<Vec<u8>> {
+        match self {
+            Self::Zstd(d) => {
+                d.write_all(data).context("Error decompressing (zstd)")?;
+                d.flush().context("Error decompressing (zstd)")?;
+                Ok(std::mem::take(d.get_mut()))
+            }
+            Self::Deflate(d) => {
+                d.write_all(data).context("Error decompressing (deflate)")?;
+                Ok(std::mem::take(d.get_mut()))
+            }
+        }
+    }
+
+    pub(crate) fn finish(self) -> anyhow::Result<Vec<u8>> {
+        match self {
+            Self::Zstd(mut d) => {
+                d.flush().context("Error decompressing (zstd)")?;
+                Ok(d.into_inner())
+            }
+            Self::Deflate(d) => d.finish().context("Error decompressing (deflate)"),
+        }
+    }
+}
+
+/// Decoder for ByteStream reads. When no compressor is in use, data is passed through as-is.
+/// Otherwise, it's decompressed and its final size is checked against the expected digest size.
+pub(crate) struct ReadDecoder {
+    decoder: Option<Decoder>,
+    expected_size: i64,
+    size: i64,
+}
+
+impl ReadDecoder {
+    pub(crate) fn new(compressor: Option<Compressor>, expected_size: i64) -> anyhow::Result<Self> {
+        Ok(Self {
+            decoder: compressor.map(|c| c.decoder()).transpose()?,
+            expected_size,
+            size: 0,
+        })
+    }
+
+    pub(crate) fn push(&mut self, data: Vec<u8>) -> anyhow::Result<Vec<u8>> {
+        match &mut self.decoder {
+            Some(decoder) => {
+                let data = decoder.push(&data)?;
+                self.size += data.len() as i64;
+                Ok(data)
+            }
+            None => Ok(data),
+        }
+    }
+
+    pub(crate) fn finish(self) -> anyhow::Result<Vec<u8>> {
+        match self.decoder {
+            Some(decoder) => {
+                let data = decoder.finish()?;
+                check_decompressed_size(self.size + data.len() as i64, self.expected_size)?;
+                Ok(data)
+            }
+            None => Ok(Vec::new()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_roundtrip() -> anyhow::Result<()> {
+        let data =
+            b"aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaabbbbbbbbbbbbbbbbbb".to_vec();
+
+        for compressor in Compressor::PREFERRED {
+            let compressed = compressor.compress(&data)?;
+            assert!(compressed.len() < data.len());
+            assert_eq!(compressor.decompress(&compressed, data.len() as i64)?, data);
+            assert!(
+                compressor
+                    .decompress(&compressed, data.len() as i64 + 1)
+                    .is_err()
+            );
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_streaming_decoder() -> anyhow::Result<()> {
+        let data = (0..10000u32)
+            .flat_map(|i| i.to_le_bytes())
+            .collect::<Vec<_>>();
+
+        for compressor in Compressor::PREFERRED {
+            let compressed = compressor.compress(&data)?;
+            let mut decoder = compressor.decoder()?;
+            let mut out = Vec::new();
+            for chunk in compressed.chunks(7) {
+                out.extend(decoder.push(chunk)?);
+            }
+            out.extend(decoder.finish()?);
+            assert_eq!(out, data);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_negotiate_compressor() {
+        let zstd = compressor::Value::Zstd as i32;
+        let deflate = compressor::Value::Deflate as i32;
+
+        assert_eq!(negotiate_compressor(None, &[]), None);
+        assert_eq!(
+            negotiate_compressor(None, &[deflate, zstd]),
+            Some(Compressor::Zstd)
+        );
+        assert_eq!(
+            negotiate_compressor(None, &[deflate]),
+            Some(Compressor::Deflate)
+        );
+        assert_eq!(negotiate_compressor(Some(false), &[zstd]), None);
+        assert_eq!(
+            negotiate_compressor(Some(true), &[]),
+            Some(Compressor::Zstd)
+        );
+        assert_eq!(
+            negotiate_compressor(Some(true), &[deflate]),
+            Some(Compressor::Deflate)
+        );
+    }
+}
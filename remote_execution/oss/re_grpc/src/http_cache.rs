@@ -0,0 +1,543 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+//! A client for caches that implement the Bazel HTTP caching protocol: action results are stored
+//! under `/ac/<action digest hash>` and blobs under `/cas/<blob digest hash>`, and both are read
+//! with `GET` and written with `PUT`. This lets us use caches like `bazel-remote` (or a plain
+//! WebDAV server) without a gRPC server. Remote execution is not supported by this protocol.
+
+use std::sync::Arc;
+
+use anyhow::Context;
+use buck2_common::http::http_client_for_oss;
+use buck2_common::http::HttpClient;
+use buck2_common::http::HttpError;
+use buck2_re_configuration::HttpHeader;
+use bytes::Bytes;
+use futures::stream;
+use futures::StreamExt;
+use futures::TryStreamExt;
+use gazebo::prelude::*;
+use http::Method;
+use http::StatusCode;
+use hyper::body::HttpBody;
+use hyper::Body;
+use hyper::Request;
+use hyper::Response;
+use prost::Message;
+use re_grpc_proto::build::bazel::remote::execution::v2::ActionResult;
+use tokio::fs::OpenOptions;
+use tokio::io::AsyncWriteExt;
+
+use crate::client::convert_action_result;
+use crate::client::convert_t_action_result2;
+use crate::client::substitute_env_vars;
+use crate::error::*;
+use crate::request::*;
+use crate::response::*;
+
+/// How many requests to the cache we issue concurrently for a single operation.
+const CONCURRENT_REQUESTS: usize = 32;
+
+pub(crate) struct HttpCacheClient {
+    client: Arc<dyn HttpClient>,
+    /// The base URL of the cache, without a trailing slash.
+    address: String,
+    headers: Vec<(String, String)>,
+}
+
+impl HttpCacheClient {
+    pub(crate) fn new(address: &str, headers: &[HttpHeader]) -> anyhow::Result<Self> {
+        let address = substitute_env_vars(address).context("Invalid address")?;
+        let address = address.trim_end_matches('/').to_owned();
+        match address.parse::<http::Uri>()?.scheme_str() {
+            Some("http") | Some("https") => {}
+            _ => {
+                return Err(anyhow::anyhow!(
+                    "Invalid HTTP cache address: `{}` (expected `http://` or `https://`)",
+                    address
+                ));
+            }
+        }
+
+        // Those support env vars for the same reasons as the headers we send to gRPC servers.
+        let headers = headers.try_map(|h| {
+            anyhow::Ok((substitute_env_vars(&h.key)?, substitute_env_vars(&h.value)?))
+        })?;
+
+        Ok(Self {
+            client: http_client_for_oss()?,
+            address,
+            headers,
+        })
+    }
+
+    fn uri(&self, kind: &str, digest: &TDigest) -> String {
+        format!("{}/{}/{}", self.address, kind, digest.hash)
+    }
+
+    async fn request(
+        &self,
+        method: Method,
+        uri: &str,
+        body: Bytes,
+    ) -> Result<Response<Body>, HttpError> {
+        let mut req = Request::builder().uri(uri).method(method);
+        for (name, value) in &self.headers {
+            req = req.header(name, value);
+        }
+        let req = req.body(body).map_err(HttpError::BuildRequest)?;
+        self.client.request(req).await
+    }
+
+    /// Returns `None` if the cache does not have this entry.
+    async fn get(&self, uri: &str) -> anyhow::Result<Option<Body>> {
+        match self.request(Method::GET, uri, Bytes::new()).await {
+            Ok(resp) => Ok(Some(resp.into_body())),
+            Err(HttpError::Status {
+                status: StatusCode::NOT_FOUND,
+                ..
+            }) => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    async fn put(&self, uri: &str, body: Bytes) -> anyhow::Result<()> {
+        self.request(Method::PUT, uri, body).await?;
+        Ok(())
+    }
+
+    async fn contains(&self, uri: &str) -> anyhow::Result<bool> {
+        match self.request(Method::HEAD, uri, Bytes::new()).await {
+            Ok(_) => Ok(true),
+            Err(HttpError::Status {
+                status: StatusCode::NOT_FOUND,
+                ..
+            }) => Ok(false),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    async fn get_blob(&self, digest: &TDigest) -> anyhow::Result<Body> {
+        if digest.size_in_bytes == 0 {
+            return Ok(Body::empty());
+        }
+
+        self.get(&self.uri("cas", digest))
+            .await?
+            .with_context(|| format!("Blob `{}` is missing from the cache", digest))
+    }
+
+    pub(crate) async fn get_action_result(
+        &self,
+        request: ActionResultRequest,
+    ) -> anyhow::Result<ActionResultResponse> {
+        let body = match self.get(&self.uri("ac", &request.digest)).await? {
+            Some(body) => body,
+            None => {
+                return Err(REClientError {
+                    code: TCode::NOT_FOUND,
+                    message: format!("Action `{}` is not in the cache", request.digest),
+                }
+                .into());
+            }
+        };
+
+        let data = hyper::body::to_bytes(body)
+            .await
+            .context("Error reading action result")?;
+        let action_result =
+            ActionResult::decode(data).context("Error decoding action result from the cache")?;
+
+        Ok(ActionResultResponse {
+            action_result: convert_action_result(action_result)?,
+            ttl: 0,
+        })
+    }
+
+    pub(crate) async fn write_action_result(
+        &self,
+        request: WriteActionResultRequest,
+    ) -> anyhow::Result<WriteActionResultResponse> {
+        let action_result = convert_t_action_result2(request.action_result);
+        self.put(
+            &self.uri("ac", &request.action_digest),
+            action_result.encode_to_vec().into(),
+        )
+        .await
+        .with_context(|| format!("Error writing action result `{}`", request.action_digest))?;
+        Ok(WriteActionResultResponse {})
+    }
+
+    pub(crate) async fn upload(&self, request: UploadRequest) -> anyhow::Result<UploadResponse> {
+        let blobs = request
+            .inlined_blobs_with_digest
+            .unwrap_or_default()
+            .into_iter()
+            .map(|blob| async move {
+                self.put(&self.uri("cas", &blob.digest), blob.blob.into())
+                    .await
+                    .with_context(|| format!("Error uploading blob `{}`", blob.digest))
+            })
+            .map(futures::future::Either::Left);
+
+        let files = request
+            .files_with_digest
+            .unwrap_or_default()
+            .into_iter()
+            .map(|file| async move {
+                let fut = async {
+                    let data = tokio::fs::read(&file.name)
+                        .await
+                        .with_context(|| format!("Error reading `{}`", file.name))?;
+                    self.put(&self.uri("cas", &file.digest), data.into()).await
+                };
+                fut.await.with_context(|| {
+                    format!("Error uploading `{}` as `{}`", file.name, file.digest)
+                })
+            })
+            .map(futures::future::Either::Right);
+
+        stream::iter(blobs.chain(files))
+            .buffer_unordered(CONCURRENT_REQUESTS)
+            .try_collect::<Vec<()>>()
+            .await?;
+
+        Ok(UploadResponse {})
+    }
+
+    pub(crate) async fn download(
+        &self,
+        request: DownloadRequest,
+    ) -> anyhow::Result<DownloadResponse> {
+        let inlined_blobs = stream::iter(request.inlined_digests.unwrap_or_default())
+            .map(|digest| async move {
+                let body = self.get_blob(&digest).await?;
+                let blob = hyper::body::to_bytes(body)
+                    .await
+                    .with_context(|| format!("Error downloading blob `{}`", digest))?;
+                anyhow::Ok(InlinedDigestWithStatus {
+                    digest,
+                    status: TStatus::default(),
+                    blob: blob.to_vec(),
+                })
+            })
+            .buffered(CONCURRENT_REQUESTS)
+            .try_collect::<Vec<_>>()
+            .await?;
+
+        stream::iter(request.file_digests.unwrap_or_default())
+            .map(|req| async move {
+                let fut = async {
+                    let mut opts = OpenOptions::new();
+                    opts.write(true).create_new(true);
+                    #[cfg(unix)]
+                    {
+                        opts.mode(if req.is_executable { 0o755 } else { 0o644 });
+                    }
+
+                    let mut body = self.get_blob(&req.named_digest.digest).await?;
+                    let mut file = opts
+                        .open(&req.named_digest.name)
+                        .await
+                        .context("Error opening")?;
+                    while let Some(chunk) = body.data().await {
+                        file.write_all(&chunk.context("Error downloading")?)
+                            .await
+                            .context("Error writing")?;
+                    }
+                    file.flush().await.context("Error flushing")?;
+                    anyhow::Ok(())
+                };
+                fut.await.with_context(|| {
+                    format!(
+                        "Error downloading digest `{}` to `{}`",
+                        req.named_digest.digest, req.named_digest.name,
+                    )
+                })
+            })
+            .buffer_unordered(CONCURRENT_REQUESTS)
+            .try_collect::<Vec<()>>()
+            .await?;
+
+        Ok(DownloadResponse {
+            inlined_blobs: Some(inlined_blobs),
+            directories: None,
+        })
+    }
+
+    pub(crate) async fn get_digests_ttl(
+        &self,
+        request: GetDigestsTtlRequest,
+    ) -> anyhow::Result<GetDigestsTtlResponse> {
+        let digests_with_ttl = stream::iter(request.digests)
+            .map(|digest| async move {
+                let present = digest.size_in_bytes == 0
+                    || self
+                        .contains(&self.uri("cas", &digest))
+                        .await
+                        .with_context(|| format!("Error querying blob `{}`", digest))?;
+                anyhow::Ok(DigestWithTtl {
+                    digest,
+                    // NOTE: HTTP caches don't tell us how long they'll keep blobs, so like for
+                    // RBE, this is arbitrary.
+                    ttl: if present { 60 } else { 0 },
+                })
+            })
+            .buffer_unordered(CONCURRENT_REQUESTS)
+            .try_collect()
+            .await?;
+
+        Ok(GetDigestsTtlResponse { digests_with_ttl })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use httptest::matchers::*;
+    use httptest::responders;
+    use httptest::Expectation;
+    use re_grpc_proto::build::bazel::remote::execution::v2::ExecutedActionMetadata;
+
+    use super::*;
+
+    fn digest(hash: &str, size_in_bytes: i64) -> TDigest {
+        TDigest {
+            hash: hash.to_owned(),
+            size_in_bytes,
+            ..Default::default()
+        }
+    }
+
+    fn client(server: &httptest::Server) -> anyhow::Result<HttpCacheClient> {
+        HttpCacheClient::new(
+            &server.url_str("/cache/"),
+            &[HttpHeader {
+                key: "x-token".to_owned(),
+                value: "secret".to_owned(),
+            }],
+        )
+    }
+
+    #[test]
+    fn test_invalid_address() {
+        assert!(HttpCacheClient::new("grpc://localhost:1234", &[]).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_get_action_result_hit() -> anyhow::Result<()> {
+        let server = httptest::Server::run();
+        let action_result = ActionResult {
+            exit_code: 3,
+            execution_metadata: Some(ExecutedActionMetadata::default()),
+            ..Default::default()
+        };
+        server.expect(
+            Expectation::matching(all_of![
+                request::method_path("GET", "/cache/ac/abc"),
+                request::headers(contains(("x-token", "secret"))),
+            ])
+            .respond_with(responders::status_code(200).body(action_result.encode_to_vec())),
+        );
+
+        let res = client(&server)?
+            .get_action_result(ActionResultRequest {
+                digest: digest("abc", 10),
+                ..Default::default()
+            })
+            .await?;
+        assert_eq!(res.action_result.exit_code, 3);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_get_action_result_miss() -> anyhow::Result<()> {
+        let server = httptest::Server::run();
+        server.expect(
+            Expectation::matching(request::method_path("GET", "/cache/ac/abc"))
+                .respond_with(responders::status_code(404)),
+        );
+
+        let err = client(&server)?
+            .get_action_result(ActionResultRequest {
+                digest: digest("abc", 10),
+                ..Default::default()
+            })
+            .await
+            .err()
+            .unwrap();
+        assert_eq!(
+            err.downcast_ref::<REClientError>().map(|e| e.code.clone()),
+            Some(TCode::NOT_FOUND)
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_get_action_result_error() -> anyhow::Result<()> {
+        let server = httptest::Server::run();
+        server.expect(
+            Expectation::matching(request::method_path("GET", "/cache/ac/abc"))
+                .respond_with(responders::status_code(500)),
+        );
+
+        let err = client(&server)?
+            .get_action_result(ActionResultRequest {
+                digest: digest("abc", 10),
+                ..Default::default()
+            })
+            .await
+            .err()
+            .unwrap();
+        // Server errors aren't cache misses.
+        assert!(err.downcast_ref::<REClientError>().is_none());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_download_inlined() -> anyhow::Result<()> {
+        let server = httptest::Server::run();
+        server.expect(
+            Expectation::matching(request::method_path("GET", "/cache/cas/abc"))
+                .respond_with(responders::status_code(200).body("hello")),
+        );
+
+        let res = client(&server)?
+            .download(DownloadRequest {
+                // Empty blobs are never requested.
+                inlined_digests: Some(vec![digest("abc", 5), digest("empty", 0)]),
+                ..Default::default()
+            })
+            .await?;
+        let blobs = res.inlined_blobs.unwrap();
+        assert_eq!(blobs.len(), 2);
+        assert_eq!(blobs[0].blob, b"hello");
+        assert_eq!(blobs[1].blob, b"");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_download_missing_blob() -> anyhow::Result<()> {
+        let server = httptest::Server::run();
+        server.expect(
+            Expectation::matching(request::method_path("GET", "/cache/cas/abc"))
+                .respond_with(responders::status_code(404)),
+        );
+
+        let err = client(&server)?
+            .download(DownloadRequest {
+                inlined_digests: Some(vec![digest("abc", 5)]),
+                ..Default::default()
+            })
+            .await
+            .err()
+            .unwrap();
+        assert!(format!("{:#}", err).contains("missing from the cache"));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_upload() -> anyhow::Result<()> {
+        let server = httptest::Server::run();
+        server.expect(
+            Expectation::matching(all_of![
+                request::method_path("PUT", "/cache/cas/abc"),
+                request::body("hello"),
+            ])
+            .respond_with(responders::status_code(200)),
+        );
+        server.expect(
+            Expectation::matching(request::method_path("PUT", "/cache/cas/def"))
+                .respond_with(responders::status_code(403)),
+        );
+
+        let client = client(&server)?;
+        client
+            .upload(UploadRequest {
+                inlined_blobs_with_digest: Some(vec![InlinedBlobWithDigest {
+                    blob: b"hello".to_vec(),
+                    digest: digest("abc", 5),
+                    ..Default::default()
+                }]),
+                ..Default::default()
+            })
+            .await?;
+
+        let res = client
+            .upload(UploadRequest {
+                inlined_blobs_with_digest: Some(vec![InlinedBlobWithDigest {
+                    blob: b"world".to_vec(),
+                    digest: digest("def", 5),
+                    ..Default::default()
+                }]),
+                ..Default::default()
+            })
+            .await;
+        assert!(res.is_err());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_get_digests_ttl() -> anyhow::Result<()> {
+        let server = httptest::Server::run();
+        server.expect(
+            Expectation::matching(request::method_path("HEAD", "/cache/cas/present"))
+                .respond_with(responders::status_code(200)),
+        );
+        server.expect(
+            Expectation::matching(request::method_path("HEAD", "/cache/cas/absent"))
+                .respond_with(responders::status_code(404)),
+        );
+        server.expect(
+            Expectation::matching(request::method_path("HEAD", "/cache/cas/broken"))
+                .respond_with(responders::status_code(503)),
+        );
+
+        let client = client(&server)?;
+        let mut res = client
+            .get_digests_ttl(GetDigestsTtlRequest {
+                digests: vec![
+                    digest("present", 5),
+                    digest("absent", 5),
+                    digest("empty", 0),
+                ],
+                ..Default::default()
+            })
+            .await?
+            .digests_with_ttl
+            .into_iter()
+            .map(|d| (d.digest.hash, d.ttl))
+            .collect::<Vec<_>>();
+        res.sort();
+        assert_eq!(
+            res,
+            vec![
+                ("absent".to_owned(), 0),
+                ("empty".to_owned(), 60),
+                ("present".to_owned(), 60),
+            ]
+        );
+
+        let res = client
+            .get_digests_ttl(GetDigestsTtlRequest {
+                digests: vec![digest("broken", 5)],
+                ..Default::default()
+            })
+            .await;
+        assert!(res.is_err());
+
+        Ok(())
+    }
+}
@@ -13,12 +13,15 @@
 #![cfg_attr(feature = "gazebo_lint", plugin(gazebo_lint))]
 
 mod client;
+mod compression;
 mod digest;
 mod error;
 mod grpc;
+mod http_cache;
 mod metadata;
 mod request;
 mod response;
+mod retry;
 pub use client::*;
 pub use digest::*;
 pub use error::*;
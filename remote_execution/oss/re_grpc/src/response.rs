@@ -69,6 +69,17 @@ pub struct TExecutedActionMetadata {
     pub _dot_dot_default: (),
 }
 
+/// How many times RPCs to RE were retried after a transient failure. This is specific to the OSS
+/// client.
+#[derive(Clone, Default)]
+pub struct RetryStatisticsResponse {
+    pub uploads: u64,
+    pub downloads: u64,
+    pub action_cache: u64,
+    pub executes: u64,
+    pub find_missing_blobs: u64,
+}
+
 #[derive(Clone, Default)]
 pub struct TPerfCount {
     pub kernel_events: TSubsysPerfCount,
@@ -0,0 +1,346 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+
+use buck2_re_configuration::Buck2OssReConfiguration;
+use futures::future::Future;
+use rand::Rng;
+
+use crate::error::REClientError;
+
+const DEFAULT_MAX_ATTEMPTS: usize = 4;
+const DEFAULT_INITIAL_BACKOFF: Duration = Duration::from_millis(200);
+const DEFAULT_MAX_BACKOFF: Duration = Duration::from_secs(10);
+
+/// How RPCs to RE are retried when they fail with a transient error.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub(crate) struct RetryPolicy {
+    /// Always at least 1.
+    max_attempts: usize,
+    initial_backoff: Duration,
+    max_backoff: Duration,
+    /// Deadline for individual unary RPCs, if any.
+    pub(crate) rpc_timeout: Option<Duration>,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: DEFAULT_MAX_ATTEMPTS,
+            initial_backoff: DEFAULT_INITIAL_BACKOFF,
+            max_backoff: DEFAULT_MAX_BACKOFF,
+            rpc_timeout: None,
+        }
+    }
+}
+
+impl RetryPolicy {
+    pub(crate) fn from_config(opts: &Buck2OssReConfiguration) -> Self {
+        let default = Self::default();
+        Self {
+            max_attempts: opts
+                .retry_max_attempts
+                .unwrap_or(default.max_attempts)
+                .max(1),
+            initial_backoff: opts
+                .retry_initial_backoff_ms
+                .map_or(default.initial_backoff, Duration::from_millis),
+            max_backoff: opts
+                .retry_max_backoff_ms
+                .map_or(default.max_backoff, Duration::from_millis),
+            rpc_timeout: opts.rpc_timeout_ms.map(Duration::from_millis),
+        }
+    }
+
+    /// The maximum backoff before retry number `retry` (starting at zero): this grows
+    /// exponentially up to `max_backoff`.
+    fn max_backoff_for(&self, retry: u32) -> Duration {
+        self.initial_backoff
+            .saturating_mul(2u32.saturating_pow(retry))
+            .min(self.max_backoff)
+    }
+
+    /// The backoff before retry number `retry`. We use "equal jitter": we wait for at least half
+    /// of the exponential backoff, and a random amount of time for the other half, so that
+    /// clients that failed at the same time don't all retry at the same time.
+    fn backoff(&self, retry: u32) -> Duration {
+        let half = self.max_backoff_for(retry) / 2;
+        half + half.mul_f64(rand::thread_rng().gen::<f64>())
+    }
+
+    /// Run `f` until it succeeds, fails with an error that isn't transient, or we run out of
+    /// attempts. Every retry is counted in `retries`. Attempts have no deadline, so this is what
+    /// streaming RPCs use: those take as long as the data takes to transfer.
+    pub(crate) async fn retry<T, F, Fut>(&self, retries: &AtomicU64, f: F) -> anyhow::Result<T>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = anyhow::Result<T>>,
+    {
+        self.retry_with_deadline(retries, None, f).await
+    }
+
+    /// Like `retry`, for unary RPCs: every attempt is bounded by `rpc_timeout`, and an attempt
+    /// that exceeds it fails with `DEADLINE_EXCEEDED` (and is retried).
+    pub(crate) async fn retry_unary<T, F, Fut>(
+        &self,
+        retries: &AtomicU64,
+        f: F,
+    ) -> anyhow::Result<T>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = anyhow::Result<T>>,
+    {
+        self.retry_with_deadline(retries, self.rpc_timeout, f).await
+    }
+
+    async fn retry_with_deadline<T, F, Fut>(
+        &self,
+        retries: &AtomicU64,
+        deadline: Option<Duration>,
+        mut f: F,
+    ) -> anyhow::Result<T>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = anyhow::Result<T>>,
+    {
+        let mut attempt = 1;
+        loop {
+            let res = match deadline {
+                Some(deadline) => match tokio::time::timeout(deadline, f()).await {
+                    Ok(res) => res,
+                    Err(_) => Err(tonic::Status::deadline_exceeded(format!(
+                        "RE request timed out after {:?}",
+                        deadline
+                    ))
+                    .into()),
+                },
+                None => f().await,
+            };
+            match res {
+                Ok(v) => return Ok(v),
+                Err(e) if attempt < self.max_attempts && is_retryable(&e) => {
+                    let backoff = self.backoff((attempt - 1) as u32);
+                    tracing::debug!(
+                        "RE request failed (attempt {}/{}), retrying in {:?}: {:#}",
+                        attempt,
+                        self.max_attempts,
+                        backoff,
+                        e
+                    );
+                    retries.fetch_add(1, Ordering::Relaxed);
+                    tokio::time::sleep(backoff).await;
+                    attempt += 1;
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+}
+
+fn is_retryable(e: &anyhow::Error) -> bool {
+    let code = if let Some(status) = e.downcast_ref::<tonic::Status>() {
+        status.code()
+    } else if let Some(e) = e.downcast_ref::<REClientError>() {
+        tonic::Code::from_i32(e.code.0)
+    } else {
+        return false;
+    };
+
+    match code {
+        tonic::Code::Unavailable
+        | tonic::Code::ResourceExhausted
+        | tonic::Code::DeadlineExceeded => true,
+        _ => false,
+    }
+}
+
+/// Count of retries per kind of RPC.
+#[derive(Default)]
+pub(crate) struct RetryCounters {
+    pub(crate) uploads: AtomicU64,
+    pub(crate) downloads: AtomicU64,
+    pub(crate) action_cache: AtomicU64,
+    pub(crate) executes: AtomicU64,
+    pub(crate) find_missing_blobs: AtomicU64,
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::AtomicUsize;
+
+    use super::*;
+    use crate::error::TCode;
+
+    fn policy(max_attempts: usize) -> RetryPolicy {
+        RetryPolicy {
+            max_attempts,
+            initial_backoff: Duration::from_millis(1),
+            max_backoff: Duration::from_millis(4),
+            rpc_timeout: None,
+        }
+    }
+
+    #[test]
+    fn test_from_config() {
+        assert_eq!(
+            RetryPolicy::from_config(&Buck2OssReConfiguration::default()),
+            RetryPolicy::default()
+        );
+
+        let policy = RetryPolicy::from_config(&Buck2OssReConfiguration {
+            retry_max_attempts: Some(0),
+            retry_initial_backoff_ms: Some(10),
+            retry_max_backoff_ms: Some(100),
+            rpc_timeout_ms: Some(1000),
+            ..Default::default()
+        });
+        assert_eq!(
+            policy,
+            RetryPolicy {
+                max_attempts: 1,
+                initial_backoff: Duration::from_millis(10),
+                max_backoff: Duration::from_millis(100),
+                rpc_timeout: Some(Duration::from_millis(1000)),
+            }
+        );
+    }
+
+    #[test]
+    fn test_backoff() {
+        let policy = RetryPolicy {
+            max_attempts: 10,
+            initial_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_millis(1000),
+            rpc_timeout: None,
+        };
+
+        assert_eq!(policy.max_backoff_for(0), Duration::from_millis(100));
+        assert_eq!(policy.max_backoff_for(1), Duration::from_millis(200));
+        assert_eq!(policy.max_backoff_for(3), Duration::from_millis(800));
+        assert_eq!(policy.max_backoff_for(4), Duration::from_millis(1000));
+        assert_eq!(policy.max_backoff_for(100), Duration::from_millis(1000));
+
+        for retry in 0..10 {
+            let backoff = policy.backoff(retry);
+            let max = policy.max_backoff_for(retry);
+            assert!(backoff >= max / 2);
+            assert!(backoff <= max);
+        }
+    }
+
+    #[test]
+    fn test_is_retryable() {
+        assert!(is_retryable(&tonic::Status::unavailable("").into()));
+        assert!(is_retryable(&tonic::Status::resource_exhausted("").into()));
+        assert!(is_retryable(
+            &anyhow::Error::from(tonic::Status::deadline_exceeded("")).context("Some context")
+        ));
+        assert!(is_retryable(
+            &REClientError {
+                code: TCode(tonic::Code::Unavailable as i32),
+                message: String::new(),
+            }
+            .into()
+        ));
+
+        assert!(!is_retryable(&tonic::Status::not_found("").into()));
+        assert!(!is_retryable(&tonic::Status::cancelled("").into()));
+        assert!(!is_retryable(&tonic::Status::invalid_argument("").into()));
+        assert!(!is_retryable(&anyhow::anyhow!("Some error")));
+    }
+
+    #[tokio::test]
+    async fn test_retry() -> anyhow::Result<()> {
+        let retries = AtomicU64::new(0);
+        let calls = AtomicUsize::new(0);
+
+        let res = policy(3)
+            .retry(&retries, || async {
+                match calls.fetch_add(1, Ordering::Relaxed) {
+                    0 => Err(tonic::Status::unavailable("").into()),
+                    _ => Ok(42),
+                }
+            })
+            .await?;
+        assert_eq!(res, 42);
+        assert_eq!(calls.load(Ordering::Relaxed), 2);
+        assert_eq!(retries.load(Ordering::Relaxed), 1);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_retry_gives_up() {
+        let retries = AtomicU64::new(0);
+        let calls = AtomicUsize::new(0);
+
+        let res: anyhow::Result<()> = policy(3)
+            .retry(&retries, || async {
+                calls.fetch_add(1, Ordering::Relaxed);
+                Err(tonic::Status::unavailable("").into())
+            })
+            .await;
+        assert!(res.is_err());
+        assert_eq!(calls.load(Ordering::Relaxed), 3);
+        assert_eq!(retries.load(Ordering::Relaxed), 2);
+    }
+
+    #[tokio::test]
+    async fn test_retry_unary_timeout() -> anyhow::Result<()> {
+        let retries = AtomicU64::new(0);
+        let calls = AtomicUsize::new(0);
+
+        let policy = RetryPolicy {
+            rpc_timeout: Some(Duration::from_millis(10)),
+            ..policy(3)
+        };
+        let res = policy
+            .retry_unary(&retries, || async {
+                if calls.fetch_add(1, Ordering::Relaxed) == 0 {
+                    tokio::time::sleep(Duration::from_secs(60)).await;
+                }
+                Ok(42)
+            })
+            .await?;
+        assert_eq!(res, 42);
+        assert_eq!(calls.load(Ordering::Relaxed), 2);
+        assert_eq!(retries.load(Ordering::Relaxed), 1);
+
+        // Without a deadline, the attempt runs to completion.
+        let res = policy
+            .retry(&retries, || async {
+                tokio::time::sleep(Duration::from_millis(50)).await;
+                Ok(43)
+            })
+            .await?;
+        assert_eq!(res, 43);
+        assert_eq!(retries.load(Ordering::Relaxed), 1);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_retry_permanent_error() {
+        let retries = AtomicU64::new(0);
+        let calls = AtomicUsize::new(0);
+
+        let res: anyhow::Result<()> = policy(3)
+            .retry(&retries, || async {
+                calls.fetch_add(1, Ordering::Relaxed);
+                Err(tonic::Status::not_found("").into())
+            })
+            .await;
+        assert!(res.is_err());
+        assert_eq!(calls.load(Ordering::Relaxed), 1);
+        assert_eq!(retries.load(Ordering::Relaxed), 0);
+    }
+}
@@ -30,6 +30,7 @@ use starlark::docs::get_registered_starlark_docs;
 use starlark::docs::render_docs_as_code;
 use starlark::docs::Doc;
 use starlark::docs::DocItem;
+use starlark::docs::DocModule;
 use starlark::environment::FrozenModule;
 use starlark::environment::Globals;
 use starlark::environment::Module;
@@ -345,6 +346,13 @@ impl LspContext for Context {
     ) -> anyhow::Result<Option<LspUrl>> {
         Ok(self.builtin_symbols.get(symbol).cloned())
     }
+
+    fn get_environment(&self, _current_file: &LspUrl) -> anyhow::Result<DocModule> {
+        Ok(match globals().documentation() {
+            DocItem::Module(module) => module,
+            _ => DocModule::default(),
+        })
+    }
 }
 
 pub(crate) fn globals() -> Globals {
@@ -33,6 +33,7 @@ mod flow;
 mod incompatible;
 mod names;
 mod performance;
+pub(crate) mod references;
 mod types;
 mod underscore;
 
@@ -0,0 +1,365 @@
+/*
+ * Copyright 2019 The Starlark in Rust Authors.
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Find all of the places in a module that refer to a given variable.
+
+use crate::analysis::bind::scope;
+use crate::analysis::bind::Assigner;
+use crate::analysis::bind::Bind;
+use crate::analysis::bind::Scope;
+use crate::analysis::definition::LspModule;
+use crate::codemap::Pos;
+use crate::codemap::ResolvedSpan;
+use crate::codemap::Span;
+use crate::syntax::ast::StmtP;
+
+/// A variable, as far as finding the places that refer to it is concerned.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum Symbol {
+    /// A variable that is bound within a function, lambda or comprehension, so can only be
+    /// referred to from within this module. The span is the first place it is bound.
+    Local(ResolvedSpan),
+    /// A variable bound at the top level of this module, which other modules may load.
+    TopLevel(String),
+    /// A variable that is loaded from another module. `path` is the path in the `load()`
+    /// statement, and `name` is the name of the symbol in that module.
+    Loaded { path: String, name: String },
+    /// A variable that is not bound anywhere in this module.
+    Global(String),
+}
+
+/// How a [`Reference`] refers to its [`Symbol`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum ReferenceKind {
+    /// An identifier with the same name as the symbol.
+    Identifier,
+    /// The string in a `load()` statement that names the symbol in the loaded module. The span
+    /// of the reference does not include the quotes.
+    LoadedName,
+    /// An identifier that refers to a loaded symbol by a different name, e.g. `bar` after
+    /// `load("foo.star", bar = "foo")`.
+    Alias,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Reference {
+    pub(crate) span: ResolvedSpan,
+    pub(crate) kind: ReferenceKind,
+}
+
+/// Where an identifier is bound.
+#[derive(Debug, Clone, Copy)]
+enum Binding<'a> {
+    /// At the top level of the module, by the given assigner.
+    TopLevel(&'a Assigner),
+    /// Within a function, lambda or comprehension, at the given span.
+    Local(Span),
+    /// Nowhere in the module.
+    Unbound,
+}
+
+/// An identifier in the module that either binds or refers to a variable.
+#[derive(Debug, Clone, Copy)]
+struct Occurrence<'a> {
+    span: Span,
+    name: &'a str,
+    binding: Binding<'a>,
+}
+
+/// Find every identifier in the module that binds or refers to a variable, along with where
+/// the variable is bound.
+fn occurrences(scope: &Scope) -> Vec<Occurrence<'_>> {
+    fn resolve<'a>(scopes: &[&'a Scope], name: &str) -> Binding<'a> {
+        for (depth, scope) in scopes.iter().copied().enumerate().rev() {
+            if let Some((assigner, span)) = scope.bound.get(name) {
+                return if depth == 0 {
+                    Binding::TopLevel(assigner)
+                } else {
+                    Binding::Local(*span)
+                };
+            }
+        }
+        Binding::Unbound
+    }
+
+    fn walk<'a>(scope: &'a Scope, scopes: &mut Vec<&'a Scope>, res: &mut Vec<Occurrence<'a>>) {
+        scopes.push(scope);
+        for bind in &scope.inner {
+            let (span, name) = match bind {
+                Bind::Set(_, x) => (x.span, x.0.as_str()),
+                Bind::Get(x) => (x.span, x.node.as_str()),
+                Bind::GetDotted(x) => (x.variable.span, x.variable.node.as_str()),
+                Bind::Scope(inner) => {
+                    walk(inner, scopes, res);
+                    continue;
+                }
+                Bind::Flow => continue,
+            };
+            res.push(Occurrence {
+                span,
+                name,
+                binding: resolve(scopes, name),
+            });
+        }
+        scopes.pop();
+    }
+
+    let mut res = Vec::new();
+    walk(scope, &mut Vec::new(), &mut res);
+    res
+}
+
+impl LspModule {
+    /// The strings naming the symbols in `load()` statements, with the path of the module they
+    /// are loaded from and the name of the symbol in that module.
+    fn loaded_names(&self) -> Vec<(&str, &str, Span)> {
+        self.ast
+            .top_level_statements()
+            .into_iter()
+            .filter_map(|stmt| match &stmt.node {
+                StmtP::Load(load) => Some(load),
+                _ => None,
+            })
+            .flat_map(|load| {
+                load.args
+                    .iter()
+                    .map(|(_, name)| (load.module.node.as_str(), name.node.as_str(), name.span))
+            })
+            .collect()
+    }
+
+    /// Resolve the span of a string literal, without its quotes.
+    fn resolve_string_contents(&self, span: Span) -> ResolvedSpan {
+        let mut resolved = self.ast.codemap.resolve_span(span);
+        if resolved.begin_line == resolved.end_line && span.len() >= 2 {
+            resolved.begin_column += 1;
+            resolved.end_column -= 1;
+        }
+        resolved
+    }
+
+    /// Find the variable that the identifier at the given position binds or refers to.
+    ///
+    /// `line` and `col` are zero based. Unlike [`LspModule::find_definition`], this works on
+    /// the places variables are bound (e.g. the name in a `def`) as well as where they are used.
+    pub(crate) fn find_symbol(&self, line: u32, col: u32) -> Option<Symbol> {
+        let line_span = self.ast.codemap.line_span_opt(line as usize)?;
+        let pos: Pos = std::cmp::min(line_span.begin() + col, line_span.end());
+
+        if let Some((path, name, _)) = self
+            .loaded_names()
+            .into_iter()
+            .find(|(_, _, span)| span.contains(pos))
+        {
+            return Some(Symbol::Loaded {
+                path: path.to_owned(),
+                name: name.to_owned(),
+            });
+        }
+
+        let scope = scope(&self.ast);
+        let occurrence = occurrences(&scope)
+            .into_iter()
+            .find(|occurrence| occurrence.span.contains(pos))?;
+        Some(match occurrence.binding {
+            Binding::TopLevel(Assigner::Load { path, name }) => Symbol::Loaded {
+                path: path.node.clone(),
+                name: name.node.clone(),
+            },
+            Binding::TopLevel(_) => Symbol::TopLevel(occurrence.name.to_owned()),
+            Binding::Local(span) => Symbol::Local(self.ast.codemap.resolve_span(span)),
+            Binding::Unbound => Symbol::Global(occurrence.name.to_owned()),
+        })
+    }
+
+    /// Find all of the places in this module that bind or refer to `symbol`.
+    pub(crate) fn find_references(&self, symbol: &Symbol) -> Vec<Reference> {
+        let loaded_names = self.loaded_names();
+        let mut res = Vec::new();
+        if let Symbol::Loaded { path, name } = symbol {
+            res.extend(
+                loaded_names
+                    .iter()
+                    .filter(|(p, n, _)| *p == path.as_str() && *n == name.as_str())
+                    .map(|(_, _, span)| Reference {
+                        span: self.resolve_string_contents(*span),
+                        kind: ReferenceKind::LoadedName,
+                    }),
+            );
+        }
+
+        let scope = scope(&self.ast);
+        for occurrence in occurrences(&scope) {
+            let kind = match (symbol, occurrence.binding) {
+                (Symbol::Local(span), Binding::Local(binding))
+                    if self.ast.codemap.resolve_span(binding) == *span =>
+                {
+                    ReferenceKind::Identifier
+                }
+                (Symbol::TopLevel(name), Binding::TopLevel(assigner))
+                    if occurrence.name == name.as_str()
+                        && !matches!(assigner, Assigner::Load { .. }) =>
+                {
+                    ReferenceKind::Identifier
+                }
+                (
+                    Symbol::Loaded { path, name },
+                    Binding::TopLevel(Assigner::Load {
+                        path: loaded_path,
+                        name: loaded_name,
+                    }),
+                ) if &loaded_path.node == path && &loaded_name.node == name => {
+                    // Without an alias, the binding in the `load()` is the string naming the
+                    // symbol, which has already been found.
+                    if loaded_names
+                        .iter()
+                        .any(|(_, _, span)| *span == occurrence.span)
+                    {
+                        continue;
+                    }
+                    if occurrence.name == name.as_str() {
+                        ReferenceKind::Identifier
+                    } else {
+                        ReferenceKind::Alias
+                    }
+                }
+                (Symbol::Global(name), Binding::Unbound) if occurrence.name == name.as_str() => {
+                    ReferenceKind::Identifier
+                }
+                _ => continue,
+            };
+            res.push(Reference {
+                span: self.ast.codemap.resolve_span(occurrence.span),
+                kind,
+            });
+        }
+        res
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use textwrap::dedent;
+
+    use super::*;
+    use crate::analysis::definition::helpers::FixtureWithRanges;
+
+    fn reference(fixture: &FixtureWithRanges, id: &str, kind: ReferenceKind) -> Reference {
+        Reference {
+            span: fixture.span(id),
+            kind,
+        }
+    }
+
+    #[test]
+    fn finds_symbols() -> anyhow::Result<()> {
+        let contents = dedent(
+            r#"
+            load("bar.star", <bar_load>"bar"</bar_load>, <baz_alias>baz</baz_alias> = "quz")
+            def <foo>foo</foo>(<x>x</x>):
+                return <bar>bar</bar>(<x_get>x</x_get>) + <baz>baz</baz> + <glob>glob</glob>
+            "#,
+        )
+        .trim()
+        .to_owned();
+        let fixture = FixtureWithRanges::from_fixture("foo.star", &contents)?;
+        let module = fixture.module()?;
+
+        let find = |id: &str| module.find_symbol(fixture.begin_line(id), fixture.begin_column(id));
+        let loaded = |name: &str| {
+            Some(Symbol::Loaded {
+                path: "bar.star".to_owned(),
+                name: name.to_owned(),
+            })
+        };
+        assert_eq!(loaded("bar"), find("bar_load"));
+        assert_eq!(loaded("bar"), find("bar"));
+        assert_eq!(loaded("quz"), find("baz_alias"));
+        assert_eq!(loaded("quz"), find("baz"));
+        assert_eq!(Some(Symbol::TopLevel("foo".to_owned())), find("foo"));
+        assert_eq!(Some(Symbol::Local(fixture.span("x"))), find("x"));
+        assert_eq!(Some(Symbol::Local(fixture.span("x"))), find("x_get"));
+        assert_eq!(Some(Symbol::Global("glob".to_owned())), find("glob"));
+        Ok(())
+    }
+
+    #[test]
+    fn finds_references() -> anyhow::Result<()> {
+        let contents = dedent(
+            r#"
+            load("bar.star", "<bar_load>bar</bar_load>", <baz_alias>baz</baz_alias> = "<quz>quz</quz>")
+            <x1>x</x1> = 1
+            def <foo>foo</foo>(<x2>x</x2>):
+                <x3>x</x3> = <x4>x</x4> + 1
+                return [<x5>x</x5> for x in <bar1>bar</bar1>.y]
+            def quz(bar):
+                return <baz>baz</baz>(<x6>x</x6>, bar) + <foo_get>foo</foo_get>()
+            <x7>x</x7> += <glob1>glob</glob1>
+            <glob2>glob</glob2>()
+            "#,
+        )
+        .trim()
+        .to_owned();
+        let fixture = FixtureWithRanges::from_fixture("foo.star", &contents)?;
+        let module = fixture.module()?;
+
+        let identifiers = |ids: &[&str]| {
+            ids.iter()
+                .map(|id| reference(&fixture, id, ReferenceKind::Identifier))
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            identifiers(&["x1", "x6", "x7", "x7"]),
+            module.find_references(&Symbol::TopLevel("x".to_owned()))
+        );
+        assert_eq!(
+            identifiers(&["x2", "x4", "x3"]),
+            module.find_references(&Symbol::Local(fixture.span("x2")))
+        );
+        assert_eq!(
+            identifiers(&["foo", "foo_get"]),
+            module.find_references(&Symbol::TopLevel("foo".to_owned()))
+        );
+        assert_eq!(
+            vec![
+                reference(&fixture, "bar_load", ReferenceKind::LoadedName),
+                reference(&fixture, "bar1", ReferenceKind::Identifier),
+            ],
+            module.find_references(&Symbol::Loaded {
+                path: "bar.star".to_owned(),
+                name: "bar".to_owned(),
+            })
+        );
+        assert_eq!(
+            vec![
+                reference(&fixture, "quz", ReferenceKind::LoadedName),
+                reference(&fixture, "baz_alias", ReferenceKind::Alias),
+                reference(&fixture, "baz", ReferenceKind::Alias),
+            ],
+            module.find_references(&Symbol::Loaded {
+                path: "bar.star".to_owned(),
+                name: "quz".to_owned(),
+            })
+        );
+        assert_eq!(
+            identifiers(&["glob1", "glob2"]),
+            module.find_references(&Symbol::Global("glob".to_owned()))
+        );
+        Ok(())
+    }
+}
@@ -32,7 +32,12 @@ use crate::codemap::FileSpan;
 use crate::codemap::FileSpanRef;
 use crate::codemap::Span;
 use crate::debug::adapter::Breakpoint;
+use crate::debug::adapter::ExceptionBreakpoints;
+use crate::debug::adapter::InspectVariableInfo;
+use crate::debug::adapter::PathSegment;
 use crate::debug::adapter::ResolvedBreakpoints;
+use crate::debug::adapter::StoppedReason;
+use crate::debug::adapter::VariablePath;
 use crate::debug::DapAdapter;
 use crate::debug::DapAdapterClient;
 use crate::debug::DapAdapterEvalHook;
@@ -40,11 +45,19 @@ use crate::debug::ScopesInfo;
 use crate::debug::StepKind;
 use crate::debug::Variable;
 use crate::debug::VariablesInfo;
+use crate::errors::Diagnostic;
 use crate::eval::BeforeStmtFuncDyn;
 use crate::eval::Evaluator;
 use crate::slice_vec_ext::SliceExt;
+use crate::stdlib::funcs::FailError;
 use crate::syntax::AstModule;
 use crate::syntax::Dialect;
+use crate::values::dict::DictRef;
+use crate::values::function::FUNCTION_TYPE;
+use crate::values::list::ListRef;
+use crate::values::set::SetRef;
+use crate::values::tuple::TupleRef;
+use crate::values::Heap;
 use crate::values::Value;
 
 pub(crate) fn prepare_dap_adapter(
@@ -54,6 +67,7 @@ pub(crate) fn prepare_dap_adapter(
     let state = Arc::new(SharedAdapterState {
         client,
         breakpoints: Arc::new(Mutex::new(BreakpointConfig::new())),
+        exception_breakpoints: Mutex::new(ExceptionBreakpoints::default()),
         disable_breakpoints: Arc::new(0usize.into()),
     });
 
@@ -96,14 +110,45 @@ fn evaluate_expr<'v>(
     res
 }
 
+/// Interpolate the `{expr}` parts of a logpoint message.
+fn interpolate_log_message(
+    state: &SharedAdapterState,
+    eval: &mut Evaluator,
+    message: &str,
+) -> String {
+    let mut res = String::new();
+    let mut rest = message;
+    while let Some(start) = rest.find('{') {
+        let Some(len) = rest[start..].find('}') else {
+            break;
+        };
+        res.push_str(&rest[..start]);
+        let expr = &rest[start + 1..start + len];
+        match evaluate_expr(state, eval, expr.to_owned()) {
+            Ok(v) => res.push_str(&v.to_str()),
+            Err(e) => res.push_str(&format!("<{:#}>", e)),
+        }
+        rest = &rest[start + len + 1..];
+    }
+    res.push_str(rest);
+    res
+}
+
+/// Is this error a call to `fail()`.
+fn is_fail_error(error: &anyhow::Error) -> bool {
+    match error.downcast_ref::<Diagnostic>() {
+        Some(d) => d.message.is::<FailError>(),
+        None => error.is::<FailError>(),
+    }
+}
+
 impl<'a> BeforeStmtFuncDyn<'a> for DapAdapterEvalHookImpl {
     fn call<'v>(&mut self, span_loc: FileSpanRef, eval: &mut Evaluator<'v, 'a>) {
         let stop = if self.state.disable_breakpoints.load(Ordering::SeqCst) > 0 {
             false
         } else {
-            let breaks = self.state.breakpoints.lock().unwrap();
-            let breakpoint = breaks.at(span_loc);
-            match breakpoint {
+            let breakpoint = self.state.breakpoints.lock().unwrap().at(span_loc).cloned();
+            let hit = match &breakpoint {
                 Some(Breakpoint {
                     condition: Some(condition),
                     ..
@@ -113,6 +158,18 @@ impl<'a> BeforeStmtFuncDyn<'a> for DapAdapterEvalHookImpl {
                 },
                 Some(..) => true,
                 None => false,
+            };
+            match breakpoint {
+                Some(Breakpoint {
+                    log_message: Some(message),
+                    ..
+                }) if hit => {
+                    // Logpoints never stop the evaluation.
+                    let output = interpolate_log_message(&self.state, eval, &message);
+                    self.state.client.event_output(output);
+                    false
+                }
+                _ => hit,
             }
         };
 
@@ -126,31 +183,30 @@ impl<'a> BeforeStmtFuncDyn<'a> for DapAdapterEvalHookImpl {
             Some((StepKind::Out, stack_size)) => eval.call_stack_count() < stack_size,
         };
 
-        if stop || step_stop {
-            self.step = None;
-            self.state.client.event_stopped();
-            loop {
-                let msg = self.receiver.recv();
-                match msg.map(|msg| msg(span_loc, eval)) {
-                    Ok(Next::Continue) => break,
-                    Ok(Next::Step(kind)) => {
-                        self.step = Some((kind, eval.call_stack_count()));
-                        break;
-                    }
-                    Ok(Next::RemainPaused) => continue,
-                    Err(..) => {
-                        // DapAdapter has been dropped so we'll continue.
-                        break;
-                    }
-                }
-            }
+        if stop {
+            self.pause(StoppedReason::Breakpoint, span_loc, eval);
+        } else if step_stop {
+            self.pause(StoppedReason::Step, span_loc, eval);
         }
     }
-}
 
-impl Debug for DapAdapterEvalHookImpl {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        f.debug_struct("DapAdapterEvaluationWrapper").finish()
+    fn on_error<'v>(
+        &mut self,
+        span_loc: FileSpanRef,
+        error: &anyhow::Error,
+        eval: &mut Evaluator<'v, 'a>,
+    ) {
+        if self.state.disable_breakpoints.load(Ordering::SeqCst) > 0 {
+            return;
+        }
+        let exception_breakpoints = *self.state.exception_breakpoints.lock().unwrap();
+        if exception_breakpoints.error || (exception_breakpoints.fail && is_fail_error(error)) {
+            self.pause(
+                StoppedReason::Exception(format!("{:#}", error)),
+                span_loc,
+                eval,
+            );
+        }
     }
 }
 
@@ -162,6 +218,33 @@ impl DapAdapterEvalHookImpl {
             step: None,
         }
     }
+
+    /// Notify the client and handle its requests until it tells us to continue or step.
+    fn pause(&mut self, reason: StoppedReason, span_loc: FileSpanRef, eval: &mut Evaluator) {
+        self.step = None;
+        self.state.client.event_stopped(reason);
+        loop {
+            let msg = self.receiver.recv();
+            match msg.map(|msg| msg(span_loc, eval)) {
+                Ok(Next::Continue) => break,
+                Ok(Next::Step(kind)) => {
+                    self.step = Some((kind, eval.call_stack_count()));
+                    break;
+                }
+                Ok(Next::RemainPaused) => continue,
+                Err(..) => {
+                    // DapAdapter has been dropped so we'll continue.
+                    break;
+                }
+            }
+        }
+    }
+}
+
+impl Debug for DapAdapterEvalHookImpl {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("DapAdapterEvaluationWrapper").finish()
+    }
 }
 
 impl DapAdapterEvalHook for DapAdapterEvalHookImpl {
@@ -217,6 +300,8 @@ struct SharedAdapterState {
     // These breakpoints must all match statements as per before_stmt.
     // Those values for which we abort the execution.
     breakpoints: Arc<Mutex<BreakpointConfig>>,
+    // Which errors we stop on.
+    exception_breakpoints: Mutex<ExceptionBreakpoints>,
     // Set while we are doing evaluate calls (>= 1 means disable)
     disable_breakpoints: Arc<AtomicUsize>,
 }
@@ -228,6 +313,76 @@ enum Next {
     Step(StepKind),
 }
 
+/// The values nested in `value` that the debugger can expand into, along with their
+/// display name and how to reach them from `value`.
+fn value_children<'v>(value: Value<'v>, heap: &'v Heap) -> Vec<(String, PathSegment, Value<'v>)> {
+    let indexed = |xs: Vec<Value<'v>>| {
+        xs.into_iter()
+            .enumerate()
+            .map(|(i, x)| (i.to_string(), PathSegment::Index(i), x))
+            .collect()
+    };
+    if let Some(xs) = ListRef::from_value(value) {
+        indexed(xs.content().to_vec())
+    } else if let Some(xs) = TupleRef::from_value(value) {
+        indexed(xs.content().to_vec())
+    } else if let Some(xs) = SetRef::from_value(value) {
+        indexed(xs.iter().collect())
+    } else if let Some(xs) = DictRef::from_value(value) {
+        xs.iter()
+            .enumerate()
+            .map(|(i, (k, v))| (k.to_repr(), PathSegment::Index(i), v))
+            .collect()
+    } else {
+        // Structs, providers, records and the like: show the attributes which aren't methods.
+        value
+            .dir_attr()
+            .into_iter()
+            .filter_map(|attr| match value.get_attr(&attr, heap) {
+                Ok(Some(v)) if v.get_type() != FUNCTION_TYPE => {
+                    Some((attr.clone(), PathSegment::Attr(attr), v))
+                }
+                _ => None,
+            })
+            .collect()
+    }
+}
+
+/// Follow a single step of a [`VariablePath`].
+fn value_child<'v>(
+    value: Value<'v>,
+    segment: &PathSegment,
+    heap: &'v Heap,
+) -> anyhow::Result<Value<'v>> {
+    let child = match segment {
+        PathSegment::Attr(attr) => value.get_attr(attr, heap)?,
+        PathSegment::Index(i) => value_children(value, heap)
+            .into_iter()
+            .nth(*i)
+            .map(|(_, _, v)| v),
+    };
+    child.ok_or_else(|| {
+        anyhow::anyhow!(
+            "Value of type `{}` has no child `{:?}`",
+            value.get_type(),
+            segment
+        )
+    })
+}
+
+fn to_variable<'v>(name: String, value: Value<'v>, path: VariablePath, heap: &'v Heap) -> Variable {
+    Variable {
+        name,
+        value: value.to_string(),
+        type_: value.get_type().to_owned(),
+        children: if value_children(value, heap).is_empty() {
+            None
+        } else {
+            Some(path)
+        },
+    }
+}
+
 fn convert_frame(id: usize, name: String, location: Option<FileSpan>) -> StackFrame {
     let mut s = StackFrame {
         id: id as i64,
@@ -307,19 +462,50 @@ impl DapAdapter for DapAdapterImpl {
     fn variables(&self) -> anyhow::Result<VariablesInfo> {
         self.with_ctx(Box::new(|_, eval| {
             let vars = eval.local_variables();
+            let heap = eval.heap();
             Ok(VariablesInfo {
                 locals: vars
                     .into_iter()
-                    .map(|(name, value)| Variable {
-                        name,
-                        value: value.to_string(),
-                        type_: value.get_type().to_owned(),
+                    .map(|(name, value)| {
+                        let path = VariablePath {
+                            name: name.clone(),
+                            access: Vec::new(),
+                        };
+                        to_variable(name, value, path, heap)
+                    })
+                    .collect(),
+            })
+        }))
+    }
+
+    fn inspect_variable(&self, path: VariablePath) -> anyhow::Result<InspectVariableInfo> {
+        self.with_ctx(Box::new(move |_, eval| {
+            let heap = eval.heap();
+            let mut value = *eval
+                .local_variables()
+                .get(&path.name)
+                .ok_or_else(|| anyhow::anyhow!("Unknown variable `{}`", path.name))?;
+            for segment in &path.access {
+                value = value_child(value, segment, heap)?;
+            }
+            Ok(InspectVariableInfo {
+                sub_values: value_children(value, heap)
+                    .into_iter()
+                    .map(|(name, segment, child)| {
+                        let mut child_path = path.clone();
+                        child_path.access.push(segment);
+                        to_variable(name, child, child_path, heap)
                     })
                     .collect(),
             })
         }))
     }
 
+    fn set_exception_breakpoints(&self, breakpoints: ExceptionBreakpoints) -> anyhow::Result<()> {
+        *self.state.exception_breakpoints.lock().unwrap() = breakpoints;
+        Ok(())
+    }
+
     fn continue_(&self) -> anyhow::Result<()> {
         self.inject_next(Next::Continue);
         Ok(())
@@ -409,6 +595,7 @@ pub(crate) fn resolve_breakpoints(
                 poss.get(&(x.line as usize - 1)).map(|span| Breakpoint {
                     span: span.clone(),
                     condition: x.condition.clone(),
+                    log_message: x.log_message.clone(),
                 })
             })
         },
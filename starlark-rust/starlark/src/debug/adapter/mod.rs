@@ -33,8 +33,85 @@ mod tests;
 
 /// The DapAdapterClient is implemented by the user and provides functionality required by the DapAdapter.
 pub trait DapAdapterClient: Debug + Send + Sync + 'static {
-    /// Indicates that the evaluation stopped at a breakpoint.
-    fn event_stopped(&self);
+    /// Indicates that the evaluation stopped, at a breakpoint, after a step or on an exception.
+    fn event_stopped(&self, reason: StoppedReason);
+
+    /// Indicates that a logpoint was hit and produced this output.
+    fn event_output(&self, output: String);
+}
+
+/// Why the evaluation stopped.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StoppedReason {
+    /// Stopped at a line breakpoint.
+    Breakpoint,
+    /// Stopped after a `step` request.
+    Step,
+    /// Stopped when an error was raised, before unwinding. Contains the error message.
+    Exception(String),
+}
+
+impl StoppedReason {
+    /// The `reason` of the DAP `stopped` event.
+    pub fn dap_reason(&self) -> &'static str {
+        match self {
+            StoppedReason::Breakpoint => "breakpoint",
+            StoppedReason::Step => "step",
+            StoppedReason::Exception(_) => "exception",
+        }
+    }
+
+    /// The error message, if stopped on an exception.
+    pub fn text(&self) -> Option<&str> {
+        match self {
+            StoppedReason::Exception(message) => Some(message),
+            _ => None,
+        }
+    }
+}
+
+/// The errors that should stop the evaluation, see
+/// <https://microsoft.github.io/debug-adapter-protocol/specification#Requests_SetExceptionBreakpoints>
+#[derive(Debug, Clone, Copy, Dupe, Default, PartialEq, Eq)]
+pub struct ExceptionBreakpoints {
+    /// Stop when `fail()` is called.
+    pub fail: bool,
+    /// Stop on any error, including `fail()`.
+    pub error: bool,
+}
+
+impl ExceptionBreakpoints {
+    const FAIL_FILTER: &'static str = "fail";
+    const ERROR_FILTER: &'static str = "error";
+
+    /// The filters to advertise in the `exceptionBreakpointFilters` capability.
+    pub fn dap_filters() -> Vec<ExceptionBreakpointsFilter> {
+        vec![
+            ExceptionBreakpointsFilter {
+                filter: Self::FAIL_FILTER.to_owned(),
+                label: "fail() calls".to_owned(),
+                default: Some(false),
+            },
+            ExceptionBreakpointsFilter {
+                filter: Self::ERROR_FILTER.to_owned(),
+                label: "All errors".to_owned(),
+                default: Some(false),
+            },
+        ]
+    }
+
+    /// Parses the filters from a `setExceptionBreakpoints` request.
+    pub fn from_dap(args: &SetExceptionBreakpointsArguments) -> anyhow::Result<Self> {
+        let mut res = Self::default();
+        for filter in &args.filters {
+            match filter.as_str() {
+                Self::FAIL_FILTER => res.fail = true,
+                Self::ERROR_FILTER => res.error = true,
+                _ => return Err(anyhow::anyhow!("Unknown exception filter `{}`", filter)),
+            }
+        }
+        Ok(res)
+    }
 }
 
 /// Information about the variables scopes
@@ -51,11 +128,15 @@ pub struct Variable {
     pub value: String,
     /// The variables type.
     pub type_: String,
+    /// The path to pass to [`DapAdapter::inspect_variable`] to get the children of
+    /// this variable, or [`None`] if it has no children.
+    pub children: Option<VariablePath>,
 }
 
 impl Variable {
-    /// Helper to convert to the DAP Variable type.
-    pub fn to_dap(self) -> debugserver_types::Variable {
+    /// Helper to convert to the DAP Variable type, where `variables_reference` identifies
+    /// the children of the variable (or is 0 if there are none).
+    pub fn to_dap(self, variables_reference: i64) -> debugserver_types::Variable {
         debugserver_types::Variable {
             name: self.name,
             value: self.value,
@@ -64,11 +145,35 @@ impl Variable {
             indexed_variables: None,
             named_variables: None,
             presentation_hint: None,
-            variables_reference: 0,
+            variables_reference,
         }
     }
 }
 
+/// One step from a value to a value nested within it.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum PathSegment {
+    /// The n-th element of a list, tuple or set, or the value of the n-th entry of a dict.
+    Index(usize),
+    /// A named attribute, such as a field of a struct or provider.
+    Attr(String),
+}
+
+/// The path from a variable in the top frame to a value nested within it.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct VariablePath {
+    /// Name of the local (or module) variable.
+    pub name: String,
+    /// Steps from the variable to the value, outermost first.
+    pub access: Vec<PathSegment>,
+}
+
+/// The children of a variable.
+pub struct InspectVariableInfo {
+    /// The elements, entries or attributes of the variable.
+    pub sub_values: Vec<Variable>,
+}
+
 /// The kind of debugger step, used for next/stepin/stepout requests.
 #[derive(Debug, Clone, Dupe, Copy)]
 pub enum StepKind {
@@ -113,11 +218,21 @@ pub trait DapAdapter: Debug + Send + 'static {
     /// See <https://microsoft.github.io/debug-adapter-protocol/specification#Requests_Scopes>
     fn scopes(&self) -> anyhow::Result<ScopesInfo>;
 
-    /// Gets child variables for a variable reference.
+    /// Gets the variables in the top frame.
     ///
     /// See <https://microsoft.github.io/debug-adapter-protocol/specification#Requests_Variables>
     fn variables(&self) -> anyhow::Result<VariablesInfo>;
 
+    /// Gets child variables of a variable in the top frame, for a variable reference.
+    ///
+    /// See <https://microsoft.github.io/debug-adapter-protocol/specification#Requests_Variables>
+    fn inspect_variable(&self, path: VariablePath) -> anyhow::Result<InspectVariableInfo>;
+
+    /// Sets which errors stop the evaluation (and clears the existing ones).
+    ///
+    /// See <https://microsoft.github.io/debug-adapter-protocol/specification#Requests_SetExceptionBreakpoints>
+    fn set_exception_breakpoints(&self, breakpoints: ExceptionBreakpoints) -> anyhow::Result<()>;
+
     /// Resumes execution.
     ///
     /// See <https://microsoft.github.io/debug-adapter-protocol/specification#Requests_Continue>
@@ -139,6 +254,8 @@ pub trait DapAdapter: Debug + Send + 'static {
 pub struct Breakpoint {
     span: FileSpan,
     condition: Option<String>,
+    /// For logpoints, the message to log (with `{expr}` interpolated) instead of stopping.
+    log_message: Option<String>,
 }
 
 /// Breakpoints resolved to their spans.
@@ -176,6 +293,8 @@ pub fn dap_capabilities() -> Capabilities {
         supports_set_variable: Some(true),
         supports_step_in_targets_request: Some(true),
         supports_conditional_breakpoints: Some(true),
+        supports_log_points: Some(true),
+        exception_breakpoint_filters: Some(ExceptionBreakpoints::dap_filters()),
         ..Capabilities::default()
     }
 }
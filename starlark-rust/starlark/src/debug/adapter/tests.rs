@@ -21,6 +21,7 @@ mod t {
     use std::sync::atomic::AtomicUsize;
     use std::sync::atomic::Ordering;
     use std::sync::Arc;
+    use std::sync::Mutex;
     use std::thread::ScopedJoinHandle;
     use std::time::Duration;
     use std::time::Instant;
@@ -34,7 +35,11 @@ mod t {
     use crate::debug::DapAdapter;
     use crate::debug::DapAdapterClient;
     use crate::debug::DapAdapterEvalHook;
+    use crate::debug::ExceptionBreakpoints;
+    use crate::debug::PathSegment;
     use crate::debug::StepKind;
+    use crate::debug::StoppedReason;
+    use crate::debug::VariablePath;
     use crate::environment::GlobalsBuilder;
     use crate::environment::Module;
     use crate::eval::Evaluator;
@@ -47,34 +52,51 @@ mod t {
     #[derive(Debug)]
     struct Client {
         breakpoints_hit: Arc<AtomicUsize>,
-    }
-
-    impl Client {
-        pub fn new(breakpoints_hit: Arc<AtomicUsize>) -> Self {
-            Self { breakpoints_hit }
-        }
+        last_stopped: Arc<Mutex<Option<StoppedReason>>>,
+        output: Arc<Mutex<Vec<String>>>,
     }
 
     impl DapAdapterClient for Client {
-        fn event_stopped(&self) {
+        fn event_stopped(&self, reason: StoppedReason) {
             println!("stopped!");
+            *self.last_stopped.lock().unwrap() = Some(reason);
             self.breakpoints_hit.fetch_add(1, Ordering::SeqCst);
         }
+
+        fn event_output(&self, output: String) {
+            self.output.lock().unwrap().push(output);
+        }
     }
 
     struct BreakpointController {
         breakpoints_hit: Arc<AtomicUsize>,
+        last_stopped: Arc<Mutex<Option<StoppedReason>>>,
+        output: Arc<Mutex<Vec<String>>>,
     }
 
     impl BreakpointController {
         fn new() -> Self {
             Self {
                 breakpoints_hit: Arc::new(AtomicUsize::new(0)),
+                last_stopped: Arc::new(Mutex::new(None)),
+                output: Arc::new(Mutex::new(Vec::new())),
             }
         }
 
         fn get_client(&self) -> Box<dyn DapAdapterClient> {
-            Box::new(Client::new(self.breakpoints_hit.dupe()))
+            Box::new(Client {
+                breakpoints_hit: self.breakpoints_hit.dupe(),
+                last_stopped: self.last_stopped.dupe(),
+                output: self.output.dupe(),
+            })
+        }
+
+        fn last_stopped(&self) -> Option<StoppedReason> {
+            self.last_stopped.lock().unwrap().clone()
+        }
+
+        fn output(&self) -> Vec<String> {
+            self.output.lock().unwrap().clone()
         }
 
         fn wait_for_eval_stopped(&self, breakpoint_count: usize, timeout: Duration) {
@@ -403,4 +425,158 @@ print(x)
             Ok(())
         })
     }
+
+    #[test]
+    fn test_exception_breakpoint_on_fail() -> anyhow::Result<()> {
+        if is_wasm() {
+            return Ok(());
+        }
+
+        let controller = BreakpointController::new();
+        let (adapter, eval_hook) = prepare_dap_adapter(controller.get_client());
+        let file_contents = "
+def check(y):
+    if y > 1:
+        fail(\"too big: \" + str(y))
+for x in [1, 2]:
+    check(x)
+        ";
+        std::thread::scope(|s| {
+            let ast = AstModule::parse("test.bzl", file_contents.to_owned(), &Dialect::Extended)?;
+            adapter.set_exception_breakpoints(ExceptionBreakpoints {
+                fail: true,
+                error: false,
+            })?;
+            let eval_result =
+                s.spawn(move || -> anyhow::Result<_> { eval_with_hook(ast, eval_hook) });
+            controller.wait_for_eval_stopped(1, TIMEOUT);
+            match controller.last_stopped() {
+                Some(StoppedReason::Exception(message)) => {
+                    assert!(message.contains("too big: 2"), "{}", message)
+                }
+                reason => panic!("unexpected stop: {:?}", reason),
+            }
+            // We are still in the frame which called `fail`.
+            assert_eq!("2", adapter.evaluate("y")?.result);
+            adapter.continue_()?;
+
+            assert!(join_timeout(eval_result, TIMEOUT).is_err());
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn test_exception_breakpoint_filters() -> anyhow::Result<()> {
+        if is_wasm() {
+            return Ok(());
+        }
+
+        let controller = BreakpointController::new();
+        let (adapter, eval_hook) = prepare_dap_adapter(controller.get_client());
+        let file_contents = "
+x = [1, 2, 3]
+y = x[5]
+        ";
+        std::thread::scope(|s| {
+            let ast = AstModule::parse("test.bzl", file_contents.to_owned(), &Dialect::Extended)?;
+            // Only `fail()` calls stop, so the index error doesn't.
+            adapter.set_exception_breakpoints(ExceptionBreakpoints {
+                fail: true,
+                error: false,
+            })?;
+            let eval_result =
+                s.spawn(move || -> anyhow::Result<_> { eval_with_hook(ast, eval_hook) });
+            assert!(join_timeout(eval_result, TIMEOUT).is_err());
+            assert_eq!(0, controller.breakpoints_hit.load(Ordering::SeqCst));
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn test_logpoint() -> anyhow::Result<()> {
+        if is_wasm() {
+            return Ok(());
+        }
+
+        let controller = BreakpointController::new();
+        let (adapter, eval_hook) = prepare_dap_adapter(controller.get_client());
+        let file_contents = "
+def adjust(y):
+    y[0] += 1
+    return y
+x = [1, 2, 3]
+adjust(x)
+adjust(x)
+        ";
+        std::thread::scope(|s| {
+            let ast = AstModule::parse("test.bzl", file_contents.to_owned(), &Dialect::Extended)?;
+            let mut args = breakpoints_args("test.bzl", &[(4, Some("y[0] > 2"))]);
+            args.breakpoints.as_mut().unwrap()[0].log_message =
+                Some("y is {y}, first is {y[0]}".to_owned());
+            let breakpoints = resolve_breakpoints(&args, &ast)?;
+            adapter.set_breakpoints("test.bzl", &breakpoints)?;
+            let eval_result =
+                s.spawn(move || -> anyhow::Result<_> { eval_with_hook(ast, eval_hook) });
+            join_timeout(eval_result, TIMEOUT)?;
+            assert_eq!(0, controller.breakpoints_hit.load(Ordering::SeqCst));
+            assert_eq!(vec!["y is [3, 2, 3], first is 3"], controller.output());
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn test_inspect_variable() -> anyhow::Result<()> {
+        if is_wasm() {
+            return Ok(());
+        }
+
+        let controller = BreakpointController::new();
+        let (adapter, eval_hook) = prepare_dap_adapter(controller.get_client());
+        let file_contents = "
+def f(x):
+    return len(x)
+f({\"a\": [1, 2], \"b\": struct(c = 3)})
+        ";
+        std::thread::scope(|s| {
+            let ast = AstModule::parse("test.bzl", file_contents.to_owned(), &Dialect::Extended)?;
+            let breakpoints =
+                resolve_breakpoints(&breakpoints_args("test.bzl", &[(3, None)]), &ast)?;
+            adapter.set_breakpoints("test.bzl", &breakpoints)?;
+            let eval_result =
+                s.spawn(move || -> anyhow::Result<_> { eval_with_hook(ast, eval_hook) });
+            controller.wait_for_eval_stopped(1, TIMEOUT);
+
+            let locals = adapter.variables()?.locals;
+            assert_eq!(1, locals.len());
+            let x = locals[0].children.clone().unwrap();
+
+            let entries = adapter.inspect_variable(x.clone())?.sub_values;
+            assert_eq!(
+                vec!["\"a\"", "\"b\""],
+                entries.iter().map(|v| v.name.as_str()).collect::<Vec<_>>()
+            );
+
+            let list = adapter.inspect_variable(entries[0].children.clone().unwrap())?;
+            assert_eq!(
+                vec![("0", "1"), ("1", "2")],
+                list.sub_values
+                    .iter()
+                    .map(|v| (v.name.as_str(), v.value.as_str()))
+                    .collect::<Vec<_>>()
+            );
+            assert!(list.sub_values.iter().all(|v| v.children.is_none()));
+
+            let fields = adapter.inspect_variable(VariablePath {
+                name: "x".to_owned(),
+                access: vec![PathSegment::Index(1)],
+            })?;
+            assert_eq!(1, fields.sub_values.len());
+            assert_eq!("c", fields.sub_values[0].name);
+            assert_eq!("3", fields.sub_values[0].value);
+
+            adapter.continue_()?;
+            join_timeout(eval_result, TIMEOUT)?;
+            Ok(())
+        })
+    }
 }
@@ -94,12 +94,21 @@ fn escape_name(name: &str) -> String {
     name.replace('_', "\\_")
 }
 
-fn render_property(name: &str, property: &DocProperty) -> String {
+/// The header for a function or property. The LSP shows the name elsewhere, so only the
+/// prototype is included there.
+fn render_header(name: &str, prototype: String, flavor: MarkdownFlavor) -> String {
+    match flavor {
+        MarkdownFlavor::DocFile => format!("## {}\n\n{prototype}", escape_name(name)),
+        MarkdownFlavor::LspSummary => prototype,
+    }
+}
+
+fn render_property(name: &str, property: &DocProperty, flavor: MarkdownFlavor) -> String {
     let prototype = render_code_block(&format!(
         "{name}: {}",
-        TypeRenderer::Type(&property.typ).render_markdown(MarkdownFlavor::DocFile)
+        TypeRenderer::Type(&property.typ).render_markdown(flavor)
     ));
-    let header = format!("## {}\n\n{prototype}", escape_name(name));
+    let header = render_header(name, prototype, flavor);
     let summary = render_doc_string(DSOpts::Summary, &property.docs);
     let details = render_doc_string(DSOpts::Details, &property.docs);
 
@@ -149,15 +158,15 @@ fn render_function_parameters(params: &[DocParam]) -> Option<String> {
     Some(param_list)
 }
 
-fn render_function(name: &str, function: &DocFunction) -> String {
+fn render_function(name: &str, function: &DocFunction, flavor: MarkdownFlavor) -> String {
     let prototype = render_code_block(
         &(TypeRenderer::Function {
             function_name: name,
             f: function,
         }
-        .render_markdown(MarkdownFlavor::DocFile)),
+        .render_markdown(flavor)),
     );
-    let header = format!("## {}\n\n{prototype}", escape_name(name));
+    let header = render_header(name, prototype, flavor);
     let summary = render_doc_string(DSOpts::Summary, &function.docs);
     let details = render_doc_string(DSOpts::Details, &function.docs);
 
@@ -238,8 +247,8 @@ fn render_doc_item(name: &str, item: &DocItem) -> String {
     match &item {
         DocItem::Module(m) => render_module(name, m),
         DocItem::Object(o) => render_object(name, o),
-        DocItem::Function(f) => render_function(name, f),
-        DocItem::Property(p) => render_property(name, p),
+        DocItem::Function(f) => render_function(name, f, MarkdownFlavor::DocFile),
+        DocItem::Property(p) => render_property(name, p, MarkdownFlavor::DocFile),
     }
 }
 
@@ -247,15 +256,23 @@ impl RenderMarkdown for Doc {
     fn render_markdown_opt(&self, flavor: MarkdownFlavor) -> Option<String> {
         match flavor {
             MarkdownFlavor::DocFile => Some(render_doc_item(&self.id.name, &self.item)),
-            MarkdownFlavor::LspSummary => None,
+            MarkdownFlavor::LspSummary => match &self.item {
+                // Modules and objects are too large to show in full, so just show their docstring.
+                DocItem::Module(DocModule { docs, .. })
+                | DocItem::Object(DocObject { docs, .. }) => {
+                    render_doc_string(DSOpts::Combined, docs)
+                }
+                DocItem::Function(f) => Some(render_function(&self.id.name, f, flavor)),
+                DocItem::Property(p) => Some(render_property(&self.id.name, p, flavor)),
+            },
         }
     }
 }
 
 fn render_member(name: &str, member: &DocMember) -> String {
     match member {
-        DocMember::Property(p) => render_property(name, p),
-        DocMember::Function(f) => render_function(name, f),
+        DocMember::Property(p) => render_property(name, p, MarkdownFlavor::DocFile),
+        DocMember::Function(f) => render_function(name, f, MarkdownFlavor::DocFile),
     }
 }
 
@@ -297,8 +314,9 @@ impl<'a> RenderMarkdown for TypeRenderer<'a> {
             }
         }
 
+        // Prototypes are rendered the same way for both flavors.
         match flavor {
-            MarkdownFlavor::DocFile => match self {
+            MarkdownFlavor::DocFile | MarkdownFlavor::LspSummary => match self {
                 TypeRenderer::Type(t) => Some(raw_type(t)),
                 TypeRenderer::Function { function_name, f } => {
                     let mut params = f.params.iter().map(|p| match p {
@@ -341,7 +359,6 @@ impl<'a> RenderMarkdown for TypeRenderer<'a> {
                     }
                 }
             },
-            MarkdownFlavor::LspSummary => None,
         }
     }
 }
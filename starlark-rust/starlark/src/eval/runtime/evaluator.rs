@@ -761,6 +761,9 @@ impl<'v, 'a> Evaluator<'v, 'a> {
 
 pub(crate) trait EvaluationCallbacks {
     fn before_instr(&mut self, _eval: &mut Evaluator, _ip: BcPtrAddr, _opcode: BcOpcode);
+
+    /// Called when the instruction at `ip` fails, before the frame is unwound.
+    fn on_error(&mut self, _eval: &mut Evaluator, _ip: BcPtrAddr, _error: &anyhow::Error) {}
 }
 
 pub(crate) struct EvalCallbacksDisabled;
@@ -796,6 +799,17 @@ impl<'a> EvaluationCallbacks for EvalCallbacksEnabled<'a> {
             self.before_stmt(eval, ip);
         }
     }
+
+    fn on_error(&mut self, eval: &mut Evaluator, ip: BcPtrAddr, error: &anyhow::Error) {
+        // Errors from a callee already have a span, and were reported in the callee's frame.
+        let raised_here = match error.downcast_ref::<Diagnostic>() {
+            Some(d) => d.span.is_none(),
+            None => true,
+        };
+        if self.before_stmt && raised_here {
+            on_error(Bc::slow_arg_at_ptr(ip).span, error, eval);
+        }
+    }
 }
 
 // This function should be called before every meaningful statement.
@@ -817,3 +831,16 @@ pub(crate) fn before_stmt(span: FrameSpan, eval: &mut Evaluator) {
         "`before_stmt` cannot be modified during evaluation"
     );
 }
+
+// Called when an error is raised, while the frame which raised it is still current.
+fn on_error(span: FrameSpan, error: &anyhow::Error, eval: &mut Evaluator) {
+    let mut fs = mem::take(&mut eval.eval_instrumentation.before_stmt.before_stmt);
+    for f in &mut fs {
+        f.on_error(span.span.file_span_ref(), error, eval)
+    }
+    let added = mem::replace(&mut eval.eval_instrumentation.before_stmt.before_stmt, fs);
+    assert!(
+        added.is_empty(),
+        "`before_stmt` cannot be modified during evaluation"
+    );
+}
@@ -0,0 +1,271 @@
+/*
+ * Copyright 2019 The Starlark in Rust Authors.
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Work out what the cursor is in the middle of, for completion and signature help.
+//!
+//! While a user is typing, the file usually does not parse, so this looks at the raw text
+//! before the cursor rather than at the AST.
+
+/// The innermost function call that the cursor is within the arguments of.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct CallContext {
+    /// The function being called, split on `.`. e.g. `["foo", "bar"]` for `foo.bar(`.
+    pub(crate) function: Vec<String>,
+    /// The zero based index of the argument the cursor is in.
+    pub(crate) argument_index: usize,
+    /// The name of the argument the cursor is in, if it is a named argument.
+    pub(crate) argument_name: Option<String>,
+    /// The names of the named arguments before the one the cursor is in.
+    pub(crate) named_arguments: Vec<String>,
+    /// Whether the cursor is directly in the argument list (as opposed to within e.g. a list
+    /// literal that is an argument), and before any `=`, so a parameter name could go here.
+    pub(crate) at_argument_name: bool,
+}
+
+/// An open bracket in the text before the cursor.
+struct OpenBracket {
+    bracket: char,
+    function: Option<Vec<String>>,
+    argument_index: usize,
+    argument_name: Option<String>,
+    named_arguments: Vec<String>,
+    /// Whether anything but an identifier has been written in the current argument.
+    argument_has_value: bool,
+}
+
+fn is_identifier_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+/// Get the dotted identifier (e.g. `foo.bar`) that ends at the end of `text`, split on `.`.
+/// Returns an empty list if `text` does not end in an identifier.
+fn trailing_dotted_identifier(text: &str) -> Vec<String> {
+    let text = text.trim_end();
+    let start = text
+        .char_indices()
+        .rev()
+        .take_while(|(_, c)| is_identifier_char(*c) || *c == '.')
+        .last()
+        .map_or(text.len(), |(i, _)| i);
+    let dotted = &text[start..];
+    if dotted.is_empty()
+        || dotted.starts_with(|c: char| c == '.' || c.is_ascii_digit())
+        || dotted.split('.').any(str::is_empty)
+    {
+        return Vec::new();
+    }
+    dotted.split('.').map(str::to_owned).collect()
+}
+
+/// Find the innermost call that `text` (the contents of the file up to the cursor) is in the
+/// arguments of.
+pub(crate) fn find_call_context(text: &str) -> Option<CallContext> {
+    let mut stack: Vec<OpenBracket> = Vec::new();
+    let mut chars = text.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '#' => {
+                for (_, c) in chars.by_ref() {
+                    if c == '\n' {
+                        break;
+                    }
+                }
+            }
+            '"' | '\'' => {
+                let triple = text[i..].starts_with(&c.to_string().repeat(3));
+                if triple {
+                    chars.next();
+                    chars.next();
+                }
+                let mut escaped = false;
+                while let Some((j, s)) = chars.next() {
+                    if escaped {
+                        escaped = false;
+                    } else if s == '\\' {
+                        escaped = true;
+                    } else if s == c && (!triple || text[j..].starts_with(&c.to_string().repeat(3)))
+                    {
+                        if triple {
+                            chars.next();
+                            chars.next();
+                        }
+                        break;
+                    }
+                }
+                if let Some(top) = stack.last_mut() {
+                    top.argument_has_value = true;
+                }
+            }
+            '(' | '[' | '{' => {
+                let function = if c == '(' {
+                    Some(trailing_dotted_identifier(&text[..i])).filter(|f| !f.is_empty())
+                } else {
+                    None
+                };
+                if let Some(top) = stack.last_mut() {
+                    top.argument_has_value = true;
+                }
+                stack.push(OpenBracket {
+                    bracket: c,
+                    function,
+                    argument_index: 0,
+                    argument_name: None,
+                    named_arguments: Vec::new(),
+                    argument_has_value: false,
+                });
+            }
+            ')' | ']' | '}' => {
+                stack.pop();
+            }
+            ',' => {
+                if let Some(top) = stack.last_mut() {
+                    if let Some(name) = top.argument_name.take() {
+                        top.named_arguments.push(name);
+                    }
+                    top.argument_index += 1;
+                    top.argument_has_value = false;
+                }
+            }
+            '=' => {
+                let previous = text[..i].chars().next_back();
+                let comparison = matches!(previous, Some('=' | '!' | '<' | '>'))
+                    || chars.peek().map(|(_, c)| *c) == Some('=');
+                if comparison && chars.peek().map(|(_, c)| *c) == Some('=') {
+                    chars.next();
+                }
+                if let Some(top) = stack.last_mut() {
+                    if !comparison && top.bracket == '(' && top.argument_name.is_none() {
+                        let name = trailing_dotted_identifier(&text[..i]);
+                        if let [name] = name.as_slice() {
+                            top.argument_name = Some(name.clone());
+                        }
+                    }
+                    top.argument_has_value = true;
+                }
+            }
+            c if c.is_whitespace() || is_identifier_char(c) => {}
+            _ => {
+                if let Some(top) = stack.last_mut() {
+                    top.argument_has_value = true;
+                }
+            }
+        }
+    }
+
+    let innermost = stack.len().checked_sub(1)?;
+    let (index, call) = stack
+        .iter()
+        .enumerate()
+        .rev()
+        .find(|(_, b)| b.function.is_some())?;
+    Some(CallContext {
+        function: call.function.clone()?,
+        argument_index: call.argument_index,
+        argument_name: call.argument_name.clone(),
+        named_arguments: call.named_arguments.clone(),
+        at_argument_name: index == innermost
+            && call.argument_name.is_none()
+            && !call.argument_has_value,
+    })
+}
+
+/// If `text` (the contents of the file up to the cursor) ends in a member access, e.g. `foo.ba`,
+/// get the object being accessed (split on `.`) and the partial member name.
+pub(crate) fn find_member_access(text: &str) -> Option<(Vec<String>, String)> {
+    let prefix_start = text
+        .char_indices()
+        .rev()
+        .take_while(|(_, c)| is_identifier_char(*c))
+        .last()
+        .map_or(text.len(), |(i, _)| i);
+    let object = text[..prefix_start].strip_suffix('.')?;
+    let object = trailing_dotted_identifier(object);
+    if object.is_empty() {
+        None
+    } else {
+        Some((object, text[prefix_start..].to_owned()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn call(
+        function: &[&str],
+        argument_index: usize,
+        argument_name: Option<&str>,
+        named_arguments: &[&str],
+        at_argument_name: bool,
+    ) -> Option<CallContext> {
+        Some(CallContext {
+            function: function.iter().map(|s| (*s).to_owned()).collect(),
+            argument_index,
+            argument_name: argument_name.map(str::to_owned),
+            named_arguments: named_arguments.iter().map(|s| (*s).to_owned()).collect(),
+            at_argument_name,
+        })
+    }
+
+    #[test]
+    fn test_find_call_context() {
+        assert_eq!(None, find_call_context("x = foo"));
+        assert_eq!(None, find_call_context("foo(1)\n"));
+        assert_eq!(
+            call(&["foo"], 0, None, &[], true),
+            find_call_context("foo(")
+        );
+        assert_eq!(
+            call(&["native", "rule"], 2, None, &["name"], true),
+            find_call_context("native.rule(\n    name = \"x\",\n    1,\n    ")
+        );
+        assert_eq!(
+            call(&["foo"], 1, Some("srcs"), &[], false),
+            find_call_context("foo(a, srcs = [\"a.c\", ")
+        );
+        assert_eq!(
+            call(&["foo"], 1, None, &[], false),
+            find_call_context("foo(a, b == c")
+        );
+        assert_eq!(
+            call(&["foo"], 1, None, &[], false),
+            find_call_context("foo(\"(\", # )\n bar(1)")
+        );
+        assert_eq!(
+            call(&["foo"], 1, None, &[], true),
+            find_call_context("foo('''a, \"b\" ''', ")
+        );
+        assert_eq!(
+            call(&["foo"], 0, Some("x"), &[], false),
+            find_call_context("foo(x = (1 + ")
+        );
+    }
+
+    #[test]
+    fn test_find_member_access() {
+        assert_eq!(None, find_member_access("foo"));
+        assert_eq!(None, find_member_access("1."));
+        assert_eq!(
+            Some((vec!["foo".to_owned()], String::new())),
+            find_member_access("x = foo.")
+        );
+        assert_eq!(
+            Some((vec!["foo".to_owned(), "bar".to_owned()], "ba".to_owned())),
+            find_member_access("foo.bar.ba")
+        );
+    }
+}
@@ -0,0 +1,244 @@
+/*
+ * Copyright 2019 The Starlark in Rust Authors.
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Extract documentation for the symbols defined in a module from its AST, so that it can be
+//! shown without evaluating the module.
+
+use std::collections::HashMap;
+
+use crate::analysis::definition::LspModule;
+use crate::codemap::ResolvedSpan;
+use crate::codemap::Spanned;
+use crate::docs::Doc;
+use crate::docs::DocFunction;
+use crate::docs::DocMember;
+use crate::docs::DocParam;
+use crate::docs::DocProperty;
+use crate::docs::DocString;
+use crate::docs::DocStringKind;
+use crate::docs::DocType;
+use crate::docs::Identifier;
+use crate::docs::MarkdownFlavor;
+use crate::docs::RenderMarkdown;
+use crate::syntax::ast::ArgumentP;
+use crate::syntax::ast::AssignP;
+use crate::syntax::ast::AstExpr;
+use crate::syntax::ast::AstLiteral;
+use crate::syntax::ast::AstNoPayload;
+use crate::syntax::ast::AstParameter;
+use crate::syntax::ast::AstStmt;
+use crate::syntax::ast::DefP;
+use crate::syntax::ast::Expr;
+use crate::syntax::ast::ExprP;
+use crate::syntax::ast::ParameterP;
+use crate::syntax::ast::StmtP;
+
+/// Get the docstring of a function body: the string literal that is the first statement, if any.
+fn get_docstring(body: &AstStmt) -> Option<&str> {
+    let first = match &body.node {
+        StmtP::Statements(stmts) => stmts.first()?,
+        _ => body,
+    };
+    match &first.node {
+        StmtP::Expression(Spanned {
+            node: Expr::Literal(AstLiteral::String(s)),
+            ..
+        }) => Some(s.node.as_str()),
+        _ => None,
+    }
+}
+
+fn get_doc_type(typ: Option<&AstExpr>) -> Option<DocType> {
+    typ.map(|typ| DocType {
+        raw_type: typ.node.to_string(),
+    })
+}
+
+fn get_doc_param(param: &AstParameter) -> DocParam {
+    match &param.node {
+        ParameterP::Normal(name, typ) => DocParam::Arg {
+            name: name.0.clone(),
+            docs: None,
+            typ: get_doc_type(typ.as_deref()),
+            default_value: None,
+        },
+        ParameterP::WithDefaultValue(name, typ, default) => DocParam::Arg {
+            name: name.0.clone(),
+            docs: None,
+            typ: get_doc_type(typ.as_deref()),
+            default_value: Some(default.node.to_string()),
+        },
+        ParameterP::NoArgs => DocParam::NoArgs,
+        ParameterP::Args(name, typ) => DocParam::Args {
+            name: format!("*{}", name.0),
+            docs: None,
+            typ: get_doc_type(typ.as_deref()),
+        },
+        ParameterP::KwArgs(name, typ) => DocParam::Kwargs {
+            name: format!("**{}", name.0),
+            docs: None,
+            typ: get_doc_type(typ.as_deref()),
+        },
+    }
+}
+
+fn get_function_docs(def: &DefP<AstNoPayload>) -> DocFunction {
+    DocFunction::from_docstring(
+        DocStringKind::Starlark,
+        def.params.iter().map(get_doc_param).collect(),
+        get_doc_type(def.return_type.as_deref()),
+        get_docstring(&def.body),
+    )
+}
+
+impl LspModule {
+    /// The documentation for each symbol defined at the top level of this module, along with
+    /// the location of its name in its definition.
+    fn top_level_symbol_docs(&self) -> impl Iterator<Item = (&str, ResolvedSpan, DocMember)> {
+        self.ast
+            .top_level_statements()
+            .into_iter()
+            .filter_map(move |stmt| match &stmt.node {
+                StmtP::Def(def) => Some((
+                    def.name.0.as_str(),
+                    self.ast.codemap.resolve_span(def.name.span),
+                    DocMember::Function(get_function_docs(def)),
+                )),
+                StmtP::Assign(lhs, rhs) => match &lhs.node {
+                    AssignP::Identifier(ident) => Some((
+                        ident.0.as_str(),
+                        self.ast.codemap.resolve_span(ident.span),
+                        DocMember::Property(DocProperty {
+                            docs: None,
+                            typ: get_doc_type(rhs.0.as_ref()),
+                        }),
+                    )),
+                    _ => None,
+                },
+                _ => None,
+            })
+    }
+
+    /// Get the documentation for a symbol defined at the top level of this module. Unlike
+    /// [`LspModule::find_exported_symbol`], this includes symbols that start with an underscore.
+    pub(crate) fn get_symbol_docs(&self, name: &str) -> Option<DocMember> {
+        self.top_level_symbol_docs()
+            .find(|(symbol, _, _)| *symbol == name)
+            .map(|(_, _, docs)| docs)
+    }
+
+    /// Get the name and documentation of the top level symbol whose name is at `span` in its
+    /// definition.
+    pub(crate) fn get_symbol_docs_at(&self, span: ResolvedSpan) -> Option<(&str, DocMember)> {
+        self.top_level_symbol_docs()
+            .find(|(_, symbol_span, _)| *symbol_span == span)
+            .map(|(symbol, _, docs)| (symbol, docs))
+    }
+
+    /// If `name` is a top level symbol that is assigned a `struct(...)` call, get the names of
+    /// that struct's members, mapped to the name of the top level symbol each member is set to
+    /// (if the member is set directly to an identifier).
+    pub(crate) fn get_struct_members(&self, name: &str) -> Option<Vec<(String, Option<String>)>> {
+        self.ast
+            .top_level_statements()
+            .into_iter()
+            .find_map(|stmt| match &stmt.node {
+                StmtP::Assign(lhs, rhs) => match (&lhs.node, &rhs.1.node) {
+                    (AssignP::Identifier(ident), ExprP::Call(function, args))
+                        if ident.0 == name =>
+                    {
+                        match &function.node {
+                            ExprP::Identifier(function, _) if function.node == "struct" => {}
+                            _ => return None,
+                        }
+                        Some(
+                            args.iter()
+                                .filter_map(|arg| match &arg.node {
+                                    ArgumentP::Named(member, value) => Some((
+                                        member.node.clone(),
+                                        match &value.node {
+                                            ExprP::Identifier(value, _) => Some(value.node.clone()),
+                                            _ => None,
+                                        },
+                                    )),
+                                    _ => None,
+                                })
+                                .collect(),
+                        )
+                    }
+                    _ => None,
+                },
+                _ => None,
+            })
+    }
+
+    /// Get the documentation for a member of a struct defined at the top level of this module,
+    /// following the member to the symbol it is set to if possible.
+    pub(crate) fn get_struct_member_docs(&self, name: &str, member: &str) -> Option<DocMember> {
+        let (_, value) = self
+            .get_struct_members(name)?
+            .into_iter()
+            .find(|(m, _)| m == member)?;
+        Some(
+            value
+                .and_then(|value| self.get_symbol_docs(&value))
+                .unwrap_or_else(|| DocMember::Property(DocProperty::default())),
+        )
+    }
+
+    /// Map from the names of symbols brought in with `load()` to the module they are loaded
+    /// from and their name in that module.
+    pub(crate) fn get_loaded_symbols(&self) -> HashMap<&str, (&str, &str)> {
+        self.ast
+            .top_level_statements()
+            .into_iter()
+            .filter_map(|stmt| match &stmt.node {
+                StmtP::Load(load) => Some(load),
+                _ => None,
+            })
+            .flat_map(|load| {
+                load.args.iter().map(|(local, their)| {
+                    (
+                        local.0.as_str(),
+                        (load.module.node.as_str(), their.node.as_str()),
+                    )
+                })
+            })
+            .collect()
+    }
+}
+
+/// Render a docstring as markdown, with the details after the summary.
+pub(crate) fn render_doc_string(docs: &DocString) -> String {
+    match &docs.details {
+        Some(details) => format!("{}\n\n{}", docs.summary, details),
+        None => docs.summary.clone(),
+    }
+}
+
+/// Render the documentation for a symbol as markdown to show to users, e.g. on hover.
+pub(crate) fn render_doc_member(name: &str, member: &DocMember) -> String {
+    Doc {
+        id: Identifier {
+            name: name.to_owned(),
+            location: None,
+        },
+        item: member.clone().to_doc_item(),
+        custom_attrs: HashMap::new(),
+    }
+    .render_markdown(MarkdownFlavor::LspSummary)
+}
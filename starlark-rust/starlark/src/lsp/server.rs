@@ -18,6 +18,7 @@
 //! Based on the reference lsp-server example at <https://github.com/rust-analyzer/lsp-server/blob/master/examples/goto_def.rs>.
 
 use std::collections::HashMap;
+use std::collections::HashSet;
 use std::fmt::Debug;
 use std::path::Path;
 use std::path::PathBuf;
@@ -40,26 +41,57 @@ use lsp_types::notification::DidCloseTextDocument;
 use lsp_types::notification::DidOpenTextDocument;
 use lsp_types::notification::LogMessage;
 use lsp_types::notification::PublishDiagnostics;
+use lsp_types::request::Completion;
+use lsp_types::request::Formatting;
 use lsp_types::request::GotoDefinition;
+use lsp_types::request::HoverRequest;
+use lsp_types::request::References;
+use lsp_types::request::Rename;
+use lsp_types::request::SignatureHelpRequest;
+use lsp_types::CompletionItem;
+use lsp_types::CompletionItemKind;
+use lsp_types::CompletionOptions;
+use lsp_types::CompletionParams;
+use lsp_types::CompletionResponse;
 use lsp_types::DefinitionOptions;
 use lsp_types::Diagnostic;
 use lsp_types::DidChangeTextDocumentParams;
 use lsp_types::DidCloseTextDocumentParams;
 use lsp_types::DidOpenTextDocumentParams;
+use lsp_types::DocumentFormattingParams;
+use lsp_types::Documentation;
 use lsp_types::GotoDefinitionParams;
 use lsp_types::GotoDefinitionResponse;
+use lsp_types::Hover;
+use lsp_types::HoverContents;
+use lsp_types::HoverParams;
+use lsp_types::HoverProviderCapability;
 use lsp_types::InitializeParams;
+use lsp_types::Location;
 use lsp_types::LocationLink;
 use lsp_types::LogMessageParams;
+use lsp_types::MarkupContent;
+use lsp_types::MarkupKind;
 use lsp_types::MessageType;
 use lsp_types::OneOf;
+use lsp_types::ParameterInformation;
+use lsp_types::ParameterLabel;
+use lsp_types::Position;
 use lsp_types::PublishDiagnosticsParams;
 use lsp_types::Range;
+use lsp_types::ReferenceParams;
+use lsp_types::RenameParams;
 use lsp_types::ServerCapabilities;
+use lsp_types::SignatureHelp;
+use lsp_types::SignatureHelpOptions;
+use lsp_types::SignatureHelpParams;
+use lsp_types::SignatureInformation;
 use lsp_types::TextDocumentSyncCapability;
 use lsp_types::TextDocumentSyncKind;
+use lsp_types::TextEdit;
 use lsp_types::Url;
 use lsp_types::WorkDoneProgressOptions;
+use lsp_types::WorkspaceEdit;
 use serde::de::DeserializeOwned;
 use serde::Deserialize;
 use serde::Deserializer;
@@ -70,9 +102,27 @@ use crate::analysis::definition::Definition;
 use crate::analysis::definition::DottedDefinition;
 use crate::analysis::definition::IdentifierDefinition;
 use crate::analysis::definition::LspModule;
+use crate::analysis::exported::SymbolKind;
+use crate::analysis::references::Reference;
+use crate::analysis::references::ReferenceKind;
+use crate::analysis::references::Symbol;
+use crate::codemap::LineCol;
 use crate::codemap::ResolvedSpan;
+use crate::docs::DocFunction;
+use crate::docs::DocMember;
+use crate::docs::DocModule;
+use crate::docs::DocParam;
+use crate::lsp::cursor::find_call_context;
+use crate::lsp::cursor::find_member_access;
+use crate::lsp::cursor::CallContext;
+use crate::lsp::docs::render_doc_member;
+use crate::lsp::docs::render_doc_string;
 use crate::lsp::server::LoadContentsError::WrongScheme;
+use crate::lsp::symbols::find_symbols_at_position;
+use crate::slice_vec_ext::VecExt;
 use crate::syntax::AstModule;
+use crate::syntax::Dialect;
+use crate::syntax::FormatMode;
 
 /// The request to get the file contents for a starlark: URI
 struct StarlarkFileContentsRequest {}
@@ -293,6 +343,31 @@ pub trait LspContext {
         current_file: &LspUrl,
         symbol: &str,
     ) -> anyhow::Result<Option<LspUrl>>;
+
+    /// Get the documentation for the global symbols that are available in a given file. These
+    /// are used for hover, completion and signature help.
+    ///
+    /// The current file is provided in case different files have different global symbols
+    /// defined.
+    fn get_environment(&self, _current_file: &LspUrl) -> anyhow::Result<DocModule> {
+        Ok(DocModule::default())
+    }
+
+    /// Get the files to search when finding references to, or renaming, a symbol that other
+    /// files can load. Open files are always searched, so need not be included. Only the files
+    /// whose contents mention the symbol are parsed.
+    ///
+    /// The current file is provided so that the files can be limited to e.g. the project that
+    /// the current file is in.
+    fn get_workspace_files(&self, _current_file: &LspUrl) -> anyhow::Result<Vec<LspUrl>> {
+        Ok(Vec::new())
+    }
+
+    /// How a file should be formatted, e.g. whether it is a build file whose rule arguments
+    /// can be sorted.
+    fn format_mode(&self, _uri: &LspUrl) -> FormatMode {
+        FormatMode::Extension
+    }
 }
 
 /// Errors when [`LspContext::resolve_load()`] cannot resolve a given path.
@@ -311,12 +386,58 @@ pub(crate) enum LoadContentsError {
     WrongScheme(String, LspUrl),
 }
 
+/// Errors when renaming a symbol.
+#[derive(thiserror::Error, Debug)]
+enum RenameError {
+    /// The new name is not a valid identifier.
+    #[error("`{}` is not a valid identifier", .0)]
+    InvalidName(String),
+    /// The symbol is a builtin, or is defined in a file that cannot be edited.
+    #[error("Only symbols that are defined in files in the workspace can be renamed")]
+    NotInWorkspace,
+}
+
+/// The places that refer to a symbol, grouped by file.
+struct SymbolReferences {
+    /// The file that defines the symbol, or `None` if it is a builtin.
+    definition: Option<LspUrl>,
+    references: Vec<(LspUrl, Vec<Reference>)>,
+}
+
 struct Backend<T: LspContext> {
     connection: Connection,
     context: T,
     /// The `AstModule` from the last time that a file was opened / changed and parsed successfully.
     /// Entries are evicted when the file is closed.
     last_valid_parse: RwLock<HashMap<LspUrl, Arc<LspModule>>>,
+    /// The latest contents of each open file, even if they do not parse. Used to work out what
+    /// is being typed for completion and signature help. Entries are evicted when the file is
+    /// closed.
+    open_files: RwLock<HashMap<LspUrl, String>>,
+}
+
+/// Get the part of `text` that is before `position`.
+fn text_before_position(text: &str, position: Position) -> &str {
+    let mut offset = 0;
+    for (i, line) in text.split_inclusive('\n').enumerate() {
+        if i == position.line as usize {
+            let line = line.strip_suffix('\n').unwrap_or(line);
+            let column = line
+                .char_indices()
+                .nth(position.character as usize)
+                .map_or(line.len(), |(column, _)| column);
+            return &text[..offset + column];
+        }
+        offset += line.len();
+    }
+    text
+}
+
+fn markdown(value: String) -> MarkupContent {
+    MarkupContent {
+        kind: MarkupKind::Markdown,
+        value,
+    }
 }
 
 /// The logic implementations of stuff
@@ -332,6 +453,18 @@ impl<T: LspContext> Backend<T> {
         ServerCapabilities {
             text_document_sync: Some(TextDocumentSyncCapability::Kind(TextDocumentSyncKind::FULL)),
             definition_provider,
+            hover_provider: Some(HoverProviderCapability::Simple(true)),
+            completion_provider: Some(CompletionOptions {
+                trigger_characters: Some(vec![".".to_owned()]),
+                ..CompletionOptions::default()
+            }),
+            signature_help_provider: Some(SignatureHelpOptions {
+                trigger_characters: Some(vec!["(".to_owned(), ",".to_owned()]),
+                ..SignatureHelpOptions::default()
+            }),
+            references_provider: Some(OneOf::Left(true)),
+            rename_provider: Some(OneOf::Left(true)),
+            document_formatting_provider: Some(OneOf::Left(true)),
             ..ServerCapabilities::default()
         }
     }
@@ -352,8 +485,34 @@ impl<T: LspContext> Backend<T> {
         Ok(module)
     }
 
+    /// Like [`Backend::get_ast_or_load_from_disk`], but files that are not open are only parsed
+    /// if their text contains `name`, since any reference to a symbol has to spell it out.
+    /// This keeps searching the workspace cheap, as most files are never parsed.
+    fn get_ast_or_parse_if_mentions(
+        &self,
+        uri: &LspUrl,
+        name: &str,
+    ) -> anyhow::Result<Option<Arc<LspModule>>> {
+        if let Some(ast) = self.get_ast(uri) {
+            return Ok(Some(ast));
+        }
+        let contents = match self.context.get_load_contents(uri)? {
+            Some(contents) if contents.contains(name) => contents,
+            _ => return Ok(None),
+        };
+        Ok(self
+            .context
+            .parse_file_with_contents(uri, contents)
+            .ast
+            .map(|ast| Arc::new(LspModule::new(ast))))
+    }
+
     fn validate(&self, uri: Url, version: Option<i64>, text: String) -> anyhow::Result<()> {
-        let uri = uri.try_into()?;
+        let uri: LspUrl = uri.try_into()?;
+        self.open_files
+            .write()
+            .unwrap()
+            .insert(uri.clone(), text.clone());
         let eval_result = self.context.parse_file_with_contents(&uri, text);
         if let Some(ast) = eval_result.ast {
             let module = Arc::new(LspModule::new(ast));
@@ -384,8 +543,11 @@ impl<T: LspContext> Backend<T> {
 
     fn did_close(&self, params: DidCloseTextDocumentParams) -> anyhow::Result<()> {
         {
+            let uri = params.text_document.uri.clone().try_into()?;
             let mut last_valid_parse = self.last_valid_parse.write().unwrap();
-            last_valid_parse.remove(&params.text_document.uri.clone().try_into()?);
+            last_valid_parse.remove(&uri);
+            let mut open_files = self.open_files.write().unwrap();
+            open_files.remove(&uri);
         }
         self.publish_diagnostics(params.text_document.uri, Vec::new(), None);
         Ok(())
@@ -587,6 +749,629 @@ impl<T: LspContext> Backend<T> {
         };
         Ok(GotoDefinitionResponse::Link(response))
     }
+
+    /// Show the documentation for the symbol at the current cursor.
+    ///
+    /// NOTE: Like goto definition, this uses the last valid parse of a file.
+    fn hover(&self, id: RequestId, params: HoverParams) {
+        self.send_response(new_response(id, self.find_hover(params)));
+    }
+
+    /// Suggest symbols, struct members or parameter names for what is being typed.
+    fn completion(&self, id: RequestId, params: CompletionParams) {
+        self.send_response(new_response(id, self.find_completions(params)));
+    }
+
+    /// Show the signature of the function call that the cursor is in the arguments of.
+    fn signature_help(&self, id: RequestId, params: SignatureHelpParams) {
+        self.send_response(new_response(id, self.find_signature_help(params)));
+    }
+
+    /// Find all of the places that refer to the symbol at the cursor. If other files can load
+    /// the symbol, this follows `load()` statements through the open files and the files that
+    /// the context says are in the workspace.
+    ///
+    /// NOTE: Like goto definition, this uses the last valid parse of a file.
+    fn references(&self, id: RequestId, params: ReferenceParams) {
+        self.send_response(new_response(id, self.find_references(params)));
+    }
+
+    /// Rename the symbol at the cursor everywhere that [`Backend::references`] finds it.
+    fn rename(&self, id: RequestId, params: RenameParams) {
+        self.send_response(new_response(id, self.find_rename_edits(params)));
+    }
+
+    /// Format a whole file, using the latest text if it is open. Files that don't parse are
+    /// left alone.
+    fn formatting(&self, id: RequestId, params: DocumentFormattingParams) {
+        self.send_response(new_response(id, self.find_formatting_edits(params)));
+    }
+
+    /// Get the latest text of an open file before `position`.
+    fn get_text_before_position(&self, uri: &LspUrl, position: Position) -> Option<String> {
+        let open_files = self.open_files.read().unwrap();
+        open_files
+            .get(uri)
+            .map(|text| text_before_position(text, position).to_owned())
+    }
+
+    /// Find the module that defines a top level symbol of `ast`, either because the symbol is
+    /// loaded from that module or because `ast` defines it itself, along with the name of the
+    /// symbol in that module.
+    fn find_local_module(
+        &self,
+        ast: &Arc<LspModule>,
+        name: &str,
+        uri: &LspUrl,
+    ) -> anyhow::Result<Option<(Arc<LspModule>, String)>> {
+        if let Some((path, loaded_name)) = ast.get_loaded_symbols().get(name) {
+            let load_uri = self.resolve_load_path(path, uri)?;
+            return Ok(self
+                .get_ast_or_load_from_disk(&load_uri)?
+                .map(|module| (module, (*loaded_name).to_owned())));
+        }
+        Ok(ast
+            .get_symbol_docs(name)
+            .map(|_| (ast.dupe(), name.to_owned())))
+    }
+
+    /// Find the module that defines a global symbol, if the context knows of one.
+    fn find_global_module(
+        &self,
+        name: &str,
+        uri: &LspUrl,
+    ) -> anyhow::Result<Option<(Arc<LspModule>, String)>> {
+        match self.context.get_url_for_global_symbol(uri, name)? {
+            Some(global_uri) => Ok(self
+                .get_ast_or_load_from_disk(&global_uri)?
+                .map(|module| (module, name.to_owned()))),
+            None => Ok(None),
+        }
+    }
+
+    /// Find the module that defines a symbol used at the top level of `ast`.
+    fn find_defining_module(
+        &self,
+        ast: Option<&Arc<LspModule>>,
+        name: &str,
+        uri: &LspUrl,
+    ) -> anyhow::Result<Option<(Arc<LspModule>, String)>> {
+        if let Some(ast) = ast {
+            if let Some(module) = self.find_local_module(ast, name, uri)? {
+                return Ok(Some(module));
+            }
+        }
+        self.find_global_module(name, uri)
+    }
+
+    /// Get the documentation for a global symbol, preferring the docs from the context's
+    /// environment over those parsed from the symbol's file.
+    fn find_global_docs(&self, name: &str, uri: &LspUrl) -> anyhow::Result<Option<DocMember>> {
+        if let Some(docs) = self.context.get_environment(uri)?.members.get(name) {
+            return Ok(Some(docs.clone()));
+        }
+        Ok(self
+            .find_global_module(name, uri)?
+            .and_then(|(module, name)| module.get_symbol_docs(&name)))
+    }
+
+    /// Get the documentation for a symbol used at the top level of `ast`.
+    fn find_symbol_docs(
+        &self,
+        ast: Option<&Arc<LspModule>>,
+        name: &str,
+        uri: &LspUrl,
+    ) -> anyhow::Result<Option<DocMember>> {
+        if let Some(ast) = ast {
+            if let Some((module, name)) = self.find_local_module(ast, name, uri)? {
+                return Ok(module.get_symbol_docs(&name));
+            }
+        }
+        self.find_global_docs(name, uri)
+    }
+
+    /// Get the documentation for a function being called, e.g. `foo` or `foo.bar`.
+    fn find_function_docs(
+        &self,
+        ast: Option<&Arc<LspModule>>,
+        function: &[String],
+        uri: &LspUrl,
+    ) -> anyhow::Result<Option<DocFunction>> {
+        let docs = match function {
+            [name] => self.find_symbol_docs(ast, name, uri)?,
+            [name, member] => self
+                .find_defining_module(ast, name, uri)?
+                .and_then(|(module, name)| module.get_struct_member_docs(&name, member)),
+            _ => None,
+        };
+        match docs {
+            Some(DocMember::Function(function)) => Ok(Some(function)),
+            _ => Ok(None),
+        }
+    }
+
+    /// Get the documentation for an identifier that the cursor is on.
+    fn find_identifier_docs(
+        &self,
+        ast: &LspModule,
+        definition: IdentifierDefinition,
+        uri: &LspUrl,
+    ) -> anyhow::Result<Option<(String, DocMember)>> {
+        let docs = match definition {
+            IdentifierDefinition::Location { destination, .. } => ast
+                .get_symbol_docs_at(destination)
+                .map(|(name, docs)| (name.to_owned(), docs)),
+            IdentifierDefinition::LoadedLocation { path, name, .. } => {
+                let load_uri = self.resolve_load_path(&path, uri)?;
+                self.get_ast_or_load_from_disk(&load_uri)?
+                    .and_then(|module| module.get_symbol_docs(&name))
+                    .map(|docs| (name, docs))
+            }
+            IdentifierDefinition::Unresolved { name, .. } => {
+                self.find_global_docs(&name, uri)?.map(|docs| (name, docs))
+            }
+            IdentifierDefinition::LoadPath { .. }
+            | IdentifierDefinition::StringLiteral { .. }
+            | IdentifierDefinition::NotFound => None,
+        };
+        Ok(docs)
+    }
+
+    /// Get the documentation for a struct member that the cursor is on, e.g. `bar` in `foo.bar`.
+    fn find_member_docs(
+        &self,
+        ast: &Arc<LspModule>,
+        root_definition: IdentifierDefinition,
+        member: &str,
+        uri: &LspUrl,
+    ) -> anyhow::Result<Option<DocMember>> {
+        let module = match root_definition {
+            IdentifierDefinition::Location { destination, .. } => ast
+                .get_symbol_docs_at(destination)
+                .map(|(name, _)| (ast.dupe(), name.to_owned())),
+            IdentifierDefinition::LoadedLocation { path, name, .. } => {
+                let load_uri = self.resolve_load_path(&path, uri)?;
+                self.get_ast_or_load_from_disk(&load_uri)?
+                    .map(|module| (module, name))
+            }
+            IdentifierDefinition::Unresolved { name, .. } => self.find_global_module(&name, uri)?,
+            IdentifierDefinition::LoadPath { .. }
+            | IdentifierDefinition::StringLiteral { .. }
+            | IdentifierDefinition::NotFound => None,
+        };
+        Ok(module.and_then(|(module, name)| module.get_struct_member_docs(&name, member)))
+    }
+
+    fn find_hover(&self, params: HoverParams) -> anyhow::Result<Option<Hover>> {
+        let uri = params
+            .text_document_position_params
+            .text_document
+            .uri
+            .try_into()?;
+        let position = params.text_document_position_params.position;
+
+        let ast = match self.get_ast(&uri) {
+            Some(ast) => ast,
+            None => return Ok(None),
+        };
+        let definition = ast.find_definition(position.line, position.character);
+        let source = match definition.source() {
+            Some(source) => source,
+            None => return Ok(None),
+        };
+        let docs = match definition {
+            Definition::Identifier(definition) => {
+                self.find_identifier_docs(&ast, definition, &uri)?
+            }
+            Definition::Dotted(DottedDefinition {
+                root_definition_location,
+                segments,
+                ..
+            }) => match segments.as_slice() {
+                [_, member] => self
+                    .find_member_docs(&ast, root_definition_location, member, &uri)?
+                    .map(|docs| (segments.join("."), docs)),
+                _ => None,
+            },
+        };
+        Ok(docs.map(|(name, docs)| Hover {
+            contents: HoverContents::Markup(markdown(render_doc_member(&name, &docs))),
+            range: Some(source.into()),
+        }))
+    }
+
+    fn find_completions(&self, params: CompletionParams) -> anyhow::Result<CompletionResponse> {
+        let uri = params.text_document_position.text_document.uri.try_into()?;
+        let position = params.text_document_position.position;
+
+        let text = match self.get_text_before_position(&uri, position) {
+            Some(text) => text,
+            None => return Ok(CompletionResponse::Array(Vec::new())),
+        };
+        let ast = self.get_ast(&uri);
+        let ast = ast.as_ref();
+
+        // The client filters the items by what has been typed so far, so return everything
+        // that could go here.
+        if let Some((object, _)) = find_member_access(&text) {
+            let module = match object.as_slice() {
+                [name] => self.find_defining_module(ast, name, &uri)?,
+                _ => None,
+            };
+            let members = module
+                .and_then(|(module, name)| {
+                    let members = module.get_struct_members(&name)?;
+                    Some(members.into_map(|(member, _)| {
+                        let docs = module.get_struct_member_docs(&name, &member);
+                        completion_item(member, docs.as_ref())
+                    }))
+                })
+                .unwrap_or_default();
+            return Ok(CompletionResponse::Array(members));
+        }
+
+        let mut items = Vec::new();
+        if let Some(call) = find_call_context(&text).filter(|call| call.at_argument_name) {
+            if let Some(function) = self.find_function_docs(ast, &call.function, &uri)? {
+                items.extend(function.params.iter().filter_map(|param| match param {
+                    DocParam::Arg { name, docs, .. } if !call.named_arguments.contains(name) => {
+                        Some(CompletionItem {
+                            label: name.clone(),
+                            kind: Some(CompletionItemKind::PROPERTY),
+                            insert_text: Some(format!("{} = ", name)),
+                            documentation: docs.as_ref().map(|docs| {
+                                Documentation::MarkupContent(markdown(render_doc_string(docs)))
+                            }),
+                            ..CompletionItem::default()
+                        })
+                    }
+                    _ => None,
+                }));
+            }
+        }
+        if let Some(ast) = ast {
+            let position = LineCol {
+                line: position.line as usize,
+                column: position.character as usize,
+            };
+            items.extend(
+                find_symbols_at_position(&ast.ast, position)
+                    .into_iter()
+                    .map(|symbol| CompletionItem {
+                        label: symbol.name.to_owned(),
+                        kind: Some(match symbol.kind {
+                            SymbolKind::Function => CompletionItemKind::FUNCTION,
+                            SymbolKind::Any => CompletionItemKind::VARIABLE,
+                        }),
+                        detail: symbol
+                            .loaded_from
+                            .map(|path| format!("Loaded from {}", path)),
+                        ..CompletionItem::default()
+                    }),
+            );
+        }
+        let globals: Vec<_> = self
+            .context
+            .get_environment(&uri)?
+            .members
+            .into_iter()
+            .filter(|(name, _)| !items.iter().any(|item| &item.label == name))
+            .map(|(name, docs)| completion_item(name, Some(&docs)))
+            .collect();
+        items.extend(globals);
+        Ok(CompletionResponse::Array(items))
+    }
+
+    fn find_signature_help(
+        &self,
+        params: SignatureHelpParams,
+    ) -> anyhow::Result<Option<SignatureHelp>> {
+        let uri = params
+            .text_document_position_params
+            .text_document
+            .uri
+            .try_into()?;
+        let position = params.text_document_position_params.position;
+
+        let call = match self
+            .get_text_before_position(&uri, position)
+            .and_then(|text| find_call_context(&text))
+        {
+            Some(call) => call,
+            None => return Ok(None),
+        };
+        let ast = self.get_ast(&uri);
+        let function = match self.find_function_docs(ast.as_ref(), &call.function, &uri)? {
+            Some(function) => function,
+            None => return Ok(None),
+        };
+        Ok(Some(signature_help(&call, &function)))
+    }
+
+    /// Find the symbol at `position` in `uri`, and all of the places that refer to it.
+    fn find_symbol_references(
+        &self,
+        uri: &LspUrl,
+        position: Position,
+    ) -> anyhow::Result<Option<SymbolReferences>> {
+        let ast = match self.get_ast(uri) {
+            Some(ast) => ast,
+            None => return Ok(None),
+        };
+        let symbol = match ast.find_symbol(position.line, position.character) {
+            Some(symbol) => symbol,
+            None => return Ok(None),
+        };
+        let (definition, name) = match &symbol {
+            Symbol::Local(_) => (uri.clone(), None),
+            Symbol::TopLevel(name) => (uri.clone(), Some(name)),
+            Symbol::Loaded { path, name } => (self.resolve_load_path(path, uri)?, Some(name)),
+            Symbol::Global(name) => match self.context.get_url_for_global_symbol(uri, name)? {
+                Some(definition) => (definition, Some(name)),
+                None => {
+                    return Ok(Some(SymbolReferences {
+                        definition: None,
+                        references: vec![(uri.clone(), ast.find_references(&symbol))],
+                    }));
+                }
+            },
+        };
+        let references = match name {
+            Some(name) => self.find_exported_references(uri, &definition, name)?,
+            None => vec![(uri.clone(), ast.find_references(&symbol))],
+        };
+        Ok(Some(SymbolReferences {
+            definition: Some(definition),
+            references,
+        }))
+    }
+
+    /// Find the references to the top level symbol `name` of the module at `definition` in that
+    /// module, the open files, and the workspace files of `current_uri`.
+    fn find_exported_references(
+        &self,
+        current_uri: &LspUrl,
+        definition: &LspUrl,
+        name: &str,
+    ) -> anyhow::Result<Vec<(LspUrl, Vec<Reference>)>> {
+        let mut files = vec![definition.clone(), current_uri.clone()];
+        files.extend(self.last_valid_parse.read().unwrap().keys().cloned());
+        files.extend(self.context.get_workspace_files(current_uri)?);
+
+        let mut seen = HashSet::new();
+        let mut res = Vec::new();
+        for uri in files {
+            if !seen.insert(uri.clone()) {
+                continue;
+            }
+            // Files that cannot be read or parsed cannot refer to the symbol.
+            let ast = match self.get_ast_or_parse_if_mentions(&uri, name) {
+                Ok(Some(ast)) => ast,
+                _ => continue,
+            };
+            let references = self.find_module_references(&ast, &uri, definition, name);
+            if !references.is_empty() {
+                res.push((uri, references));
+            }
+        }
+        Ok(res)
+    }
+
+    /// Find the references in `ast` to the top level symbol `name` of the module at `definition`.
+    fn find_module_references(
+        &self,
+        ast: &LspModule,
+        uri: &LspUrl,
+        definition: &LspUrl,
+        name: &str,
+    ) -> Vec<Reference> {
+        let mut symbols = Vec::new();
+        if uri == definition {
+            symbols.push(Symbol::TopLevel(name.to_owned()));
+        }
+        let mut paths: Vec<&str> = ast
+            .get_loaded_symbols()
+            .into_values()
+            .filter(|(_, loaded_name)| *loaded_name == name)
+            .map(|(path, _)| path)
+            .collect();
+        paths.sort_unstable();
+        paths.dedup();
+        symbols.extend(
+            paths
+                .into_iter()
+                .filter(|path| self.resolve_load_path(path, uri).ok().as_ref() == Some(definition))
+                .map(|path| Symbol::Loaded {
+                    path: path.to_owned(),
+                    name: name.to_owned(),
+                }),
+        );
+        if self
+            .context
+            .get_url_for_global_symbol(uri, name)
+            .ok()
+            .flatten()
+            .as_ref()
+            == Some(definition)
+        {
+            symbols.push(Symbol::Global(name.to_owned()));
+        }
+        symbols
+            .iter()
+            .flat_map(|symbol| ast.find_references(symbol))
+            .collect()
+    }
+
+    fn find_references(&self, params: ReferenceParams) -> anyhow::Result<Option<Vec<Location>>> {
+        let uri = params.text_document_position.text_document.uri.try_into()?;
+        let position = params.text_document_position.position;
+        let references = match self.find_symbol_references(&uri, position)? {
+            Some(references) => references,
+            None => return Ok(None),
+        };
+        let mut locations = Vec::new();
+        for (uri, references) in references.references {
+            let uri: Url = uri.try_into()?;
+            locations.extend(references.into_iter().map(|reference| Location {
+                uri: uri.clone(),
+                range: reference.span.into(),
+            }));
+        }
+        Ok(Some(locations))
+    }
+
+    fn find_rename_edits(&self, params: RenameParams) -> anyhow::Result<Option<WorkspaceEdit>> {
+        let uri = params.text_document_position.text_document.uri.try_into()?;
+        let position = params.text_document_position.position;
+        let new_name = params.new_name;
+        if !is_valid_identifier(&new_name) {
+            return Err(RenameError::InvalidName(new_name).into());
+        }
+        let references = match self.find_symbol_references(&uri, position)? {
+            Some(references) => references,
+            None => return Ok(None),
+        };
+        if !matches!(references.definition, Some(LspUrl::File(_))) {
+            return Err(RenameError::NotInWorkspace.into());
+        }
+
+        let mut changes = HashMap::new();
+        for (uri, references) in references.references {
+            // Files without a path on disk (e.g. `starlark:` files) cannot be edited.
+            if !matches!(uri, LspUrl::File(_)) {
+                continue;
+            }
+            // A loaded symbol that is given a different name by the `load()` keeps that name.
+            let edits = references
+                .into_iter()
+                .filter(|reference| reference.kind != ReferenceKind::Alias)
+                .map(|reference| TextEdit {
+                    range: reference.span.into(),
+                    new_text: new_name.clone(),
+                })
+                .collect();
+            changes.insert(uri.try_into()?, edits);
+        }
+        Ok(Some(WorkspaceEdit {
+            changes: Some(changes),
+            ..WorkspaceEdit::default()
+        }))
+    }
+
+    fn find_formatting_edits(
+        &self,
+        params: DocumentFormattingParams,
+    ) -> anyhow::Result<Option<Vec<TextEdit>>> {
+        let uri = params.text_document.uri.try_into()?;
+        let open_text = self.open_files.read().unwrap().get(&uri).cloned();
+        let text = match open_text {
+            Some(text) => text,
+            None => match self.context.get_load_contents(&uri)? {
+                Some(text) => text,
+                None => return Ok(None),
+            },
+        };
+        let ast = match self.context.parse_file_with_contents(&uri, text).ast {
+            Some(ast) => ast,
+            None => return Ok(None),
+        };
+        let formatted = ast.format(self.context.format_mode(&uri));
+        if formatted == ast.codemap.source() {
+            return Ok(Some(Vec::new()));
+        }
+        Ok(Some(vec![TextEdit {
+            range: ast.codemap.resolve_span(ast.codemap.full_span()).into(),
+            new_text: formatted,
+        }]))
+    }
+}
+
+/// Create a completion item for a symbol or member, with its documentation if available.
+fn completion_item(label: String, docs: Option<&DocMember>) -> CompletionItem {
+    let kind = match docs {
+        Some(DocMember::Function(_)) => CompletionItemKind::FUNCTION,
+        _ => CompletionItemKind::PROPERTY,
+    };
+    let documentation =
+        docs.map(|docs| Documentation::MarkupContent(markdown(render_doc_member(&label, docs))));
+    CompletionItem {
+        label,
+        kind: Some(kind),
+        documentation,
+        ..CompletionItem::default()
+    }
+}
+
+/// Work out which parameter of `params` the argument the cursor is in is passed to.
+fn active_parameter(call: &CallContext, params: &[DocParam]) -> Option<usize> {
+    match &call.argument_name {
+        Some(argument_name) => params
+            .iter()
+            .position(|param| matches!(param, DocParam::Arg { name, .. } if name == argument_name))
+            .or_else(|| {
+                params
+                    .iter()
+                    .position(|param| matches!(param, DocParam::Kwargs { .. }))
+            }),
+        None => {
+            // Positional arguments fill the parameters before any `*` or `*args`, and then
+            // any more go into `*args`.
+            let positional = params
+                .iter()
+                .position(|param| matches!(param, DocParam::NoArgs | DocParam::Args { .. }))
+                .unwrap_or(params.len());
+            if call.argument_index < positional {
+                Some(call.argument_index)
+            } else {
+                params
+                    .iter()
+                    .position(|param| matches!(param, DocParam::Args { .. }))
+            }
+        }
+    }
+}
+
+fn signature_help(call: &CallContext, function: &DocFunction) -> SignatureHelp {
+    let (labels, parameters): (Vec<_>, Vec<_>) = function
+        .params
+        .iter()
+        .map(|param| {
+            let (label, docs) = match param {
+                DocParam::Arg { name, docs, .. }
+                | DocParam::Args { name, docs, .. }
+                | DocParam::Kwargs { name, docs, .. } => (name.clone(), docs.as_ref()),
+                DocParam::NoArgs => ("*".to_owned(), None),
+            };
+            let parameter = ParameterInformation {
+                label: ParameterLabel::Simple(label.clone()),
+                documentation: docs
+                    .map(|docs| Documentation::MarkupContent(markdown(render_doc_string(docs)))),
+            };
+            (label, parameter)
+        })
+        .unzip();
+    let active_parameter = active_parameter(call, &function.params).map(|i| i as u32);
+    SignatureHelp {
+        signatures: vec![SignatureInformation {
+            label: format!("{}({})", call.function.join("."), labels.join(", ")),
+            documentation: function
+                .docs
+                .as_ref()
+                .map(|docs| Documentation::MarkupContent(markdown(render_doc_string(docs)))),
+            parameters: Some(parameters),
+            active_parameter,
+        }],
+        active_signature: Some(0),
+        active_parameter,
+    }
+}
+
+/// Whether `name` can be used as the name of a variable.
+fn is_valid_identifier(name: &str) -> bool {
+    name.starts_with(|c: char| c.is_alphabetic() || c == '_')
+        && name.chars().all(|c| c.is_alphanumeric() || c == '_')
+        // Rejects keywords, which would otherwise look like identifiers.
+        && AstModule::parse("rename.star", format!("{} = None", name), &Dialect::Extended).is_ok()
 }
 
 /// The library style pieces
@@ -624,6 +1409,18 @@ impl<T: LspContext> Backend<T> {
                     //            be handled client side.
                     if let Some(params) = as_request::<GotoDefinition>(&req) {
                         self.goto_definition(req.id, params);
+                    } else if let Some(params) = as_request::<HoverRequest>(&req) {
+                        self.hover(req.id, params);
+                    } else if let Some(params) = as_request::<Completion>(&req) {
+                        self.completion(req.id, params);
+                    } else if let Some(params) = as_request::<SignatureHelpRequest>(&req) {
+                        self.signature_help(req.id, params);
+                    } else if let Some(params) = as_request::<References>(&req) {
+                        self.references(req.id, params);
+                    } else if let Some(params) = as_request::<Rename>(&req) {
+                        self.rename(req.id, params);
+                    } else if let Some(params) = as_request::<Formatting>(&req) {
+                        self.formatting(req.id, params);
                     } else if let Some(params) = as_request::<StarlarkFileContentsRequest>(&req) {
                         self.get_starlark_file_contents(req.id, params);
                     } else if self.connection.handle_shutdown(&req)? {
@@ -689,6 +1486,7 @@ pub fn server_with_connection<T: LspContext>(
         connection,
         context,
         last_valid_parse: RwLock::default(),
+        open_files: RwLock::default(),
     }
     .main_loop(initialization_params)?;
 
@@ -768,21 +1566,50 @@ where
 //            some paths. Revisit later.
 #[cfg(all(test, not(windows)))]
 mod test {
+    use std::collections::HashMap;
     use std::path::Path;
     use std::path::PathBuf;
 
     use anyhow::Context;
     use lsp_server::Request;
     use lsp_server::RequestId;
+    use lsp_types::request::Completion;
+    use lsp_types::request::Formatting;
     use lsp_types::request::GotoDefinition;
+    use lsp_types::request::HoverRequest;
+    use lsp_types::request::References;
+    use lsp_types::request::Rename;
+    use lsp_types::request::SignatureHelpRequest;
+    use lsp_types::CompletionParams;
+    use lsp_types::CompletionResponse;
+    use lsp_types::DocumentFormattingParams;
+    use lsp_types::Documentation;
+    use lsp_types::FormattingOptions;
     use lsp_types::GotoDefinitionParams;
     use lsp_types::GotoDefinitionResponse;
+    use lsp_types::Hover;
+    use lsp_types::HoverContents;
+    use lsp_types::HoverParams;
+    use lsp_types::HoverProviderCapability;
     use lsp_types::LocationLink;
+    use lsp_types::MarkupContent;
+    use lsp_types::MarkupKind;
+    use lsp_types::OneOf;
+    use lsp_types::ParameterInformation;
+    use lsp_types::ParameterLabel;
     use lsp_types::Position;
     use lsp_types::Range;
+    use lsp_types::ReferenceContext;
+    use lsp_types::ReferenceParams;
+    use lsp_types::RenameParams;
+    use lsp_types::SignatureHelp;
+    use lsp_types::SignatureHelpParams;
+    use lsp_types::SignatureInformation;
     use lsp_types::TextDocumentIdentifier;
     use lsp_types::TextDocumentPositionParams;
+    use lsp_types::TextEdit;
     use lsp_types::Url;
+    use lsp_types::WorkspaceEdit;
     use textwrap::dedent;
 
     use crate::analysis::definition::helpers::FixtureWithRanges;
@@ -793,6 +1620,7 @@ mod test {
     use crate::lsp::server::StarlarkFileContentsRequest;
     use crate::lsp::server::StarlarkFileContentsResponse;
     use crate::lsp::test::TestServer;
+    use crate::slice_vec_ext::VecExt;
 
     fn goto_definition_request(
         server: &mut TestServer,
@@ -1809,4 +2637,550 @@ mod test {
         }
         Ok(())
     }
+
+    fn hover_request(server: &mut TestServer, uri: Url, line: u32, character: u32) -> Request {
+        server.new_request::<HoverRequest>(HoverParams {
+            text_document_position_params: TextDocumentPositionParams {
+                text_document: TextDocumentIdentifier { uri },
+                position: Position { line, character },
+            },
+            work_done_progress_params: Default::default(),
+        })
+    }
+
+    fn hover_contents(server: &mut TestServer, request: Request) -> anyhow::Result<String> {
+        let request_id = server.send_request(request)?;
+        match server.get_response::<Option<Hover>>(request_id)? {
+            Some(Hover {
+                contents: HoverContents::Markup(contents),
+                ..
+            }) => Ok(contents.value),
+            response => Err(anyhow::anyhow!(
+                "Expected hover contents, got `{:?}`",
+                response
+            )),
+        }
+    }
+
+    fn completion_labels(
+        server: &mut TestServer,
+        uri: Url,
+        line: u32,
+        character: u32,
+    ) -> anyhow::Result<Vec<String>> {
+        let request = server.new_request::<Completion>(CompletionParams {
+            text_document_position: TextDocumentPositionParams {
+                text_document: TextDocumentIdentifier { uri },
+                position: Position { line, character },
+            },
+            work_done_progress_params: Default::default(),
+            partial_result_params: Default::default(),
+            context: None,
+        });
+        let request_id = server.send_request(request)?;
+        match server.get_response::<CompletionResponse>(request_id)? {
+            CompletionResponse::Array(items) => Ok(items.into_map(|item| item.label)),
+            response => Err(anyhow::anyhow!("Got invalid message type: {:?}", response)),
+        }
+    }
+
+    fn signature_help(
+        server: &mut TestServer,
+        uri: Url,
+        line: u32,
+        character: u32,
+    ) -> anyhow::Result<Option<SignatureHelp>> {
+        let request = server.new_request::<SignatureHelpRequest>(SignatureHelpParams {
+            context: None,
+            text_document_position_params: TextDocumentPositionParams {
+                text_document: TextDocumentIdentifier { uri },
+                position: Position { line, character },
+            },
+            work_done_progress_params: Default::default(),
+        });
+        let request_id = server.send_request(request)?;
+        server.get_response(request_id)
+    }
+
+    #[test]
+    fn advertises_hover_completion_and_signature_help() -> anyhow::Result<()> {
+        let server = TestServer::new()?;
+        let capabilities = server
+            .initialization_result()
+            .context("initialization result")?
+            .capabilities;
+        assert_eq!(
+            Some(HoverProviderCapability::Simple(true)),
+            capabilities.hover_provider
+        );
+        assert!(capabilities.completion_provider.is_some());
+        assert!(capabilities.signature_help_provider.is_some());
+        Ok(())
+    }
+
+    #[test]
+    fn shows_docs_on_hover() -> anyhow::Result<()> {
+        let foo_uri = temp_file_uri("foo.star");
+        let bar_uri = temp_file_uri("bar.star");
+
+        let foo_contents = dedent(
+            r#"
+            load("bar.star", "bar", "bar_struct")
+            def baz(x, y = 1):
+                """Does baz.
+
+                Args:
+                    x: The x.
+                """
+                return x + y
+            bar()
+            baz(1)
+            bar_struct.qux
+            native_function1()
+            z = 1
+            print(z)
+            "#,
+        )
+        .trim()
+        .to_owned();
+        let bar_contents = dedent(
+            r#"
+            def bar(a):
+                """Does bar."""
+                pass
+            def _qux():
+                """Does qux."""
+                pass
+            bar_struct = struct(qux = _qux)
+            "#,
+        )
+        .trim()
+        .to_owned();
+
+        let mut server = TestServer::new()?;
+        server.open_file(foo_uri.clone(), foo_contents)?;
+        server.set_file_contents(PathBuf::from(bar_uri.path()), bar_contents)?;
+
+        let bar = hover_request(&mut server, foo_uri.clone(), 8, 1);
+        let bar = hover_contents(&mut server, bar)?;
+        assert!(bar.contains("def bar(a)"), "{}", bar);
+        assert!(bar.contains("Does bar."), "{}", bar);
+
+        let baz = hover_request(&mut server, foo_uri.clone(), 9, 1);
+        let baz = hover_contents(&mut server, baz)?;
+        assert!(baz.contains("def baz(x, y = 1)"), "{}", baz);
+        assert!(baz.contains("Does baz."), "{}", baz);
+        assert!(baz.contains("* `x`: The x."), "{}", baz);
+
+        let qux = hover_request(&mut server, foo_uri.clone(), 10, 12);
+        let qux = hover_contents(&mut server, qux)?;
+        assert!(qux.contains("def bar_struct.qux()"), "{}", qux);
+        assert!(qux.contains("Does qux."), "{}", qux);
+
+        let native = hover_request(&mut server, foo_uri.clone(), 11, 1);
+        let native = hover_contents(&mut server, native)?;
+        assert!(native.contains("def native_function1()"), "{}", native);
+
+        let z = hover_request(&mut server, foo_uri.clone(), 13, 6);
+        let z = hover_contents(&mut server, z)?;
+        assert!(z.contains("z: \"\""), "{}", z);
+
+        let nothing = hover_request(&mut server, foo_uri, 2, 4);
+        let request_id = server.send_request(nothing)?;
+        assert_eq!(None, server.get_response::<Option<Hover>>(request_id)?);
+        Ok(())
+    }
+
+    #[test]
+    fn completes_symbols_members_and_parameters() -> anyhow::Result<()> {
+        let foo_uri = temp_file_uri("foo.star");
+        let bar_uri = temp_file_uri("bar.star");
+
+        let bar_contents = dedent(
+            r#"
+            def bar(name, srcs = [], *args, **kwargs):
+                pass
+            def _qux():
+                pass
+            bar_struct = struct(qux = _qux, quux = 1)
+            "#,
+        )
+        .trim()
+        .to_owned();
+        let foo_contents = dedent(
+            r#"
+            load("bar.star", "bar", "bar_struct")
+            def baz(x):
+                return bar(x), bar_struct
+            "#,
+        )
+        .trim()
+        .to_owned();
+
+        let mut server = TestServer::new()?;
+        server.set_file_contents(PathBuf::from(bar_uri.path()), bar_contents)?;
+        server.open_file(foo_uri.clone(), foo_contents.clone())?;
+
+        let globals = completion_labels(&mut server, foo_uri.clone(), 3, 0)?;
+        for expected in [
+            "bar",
+            "bar_struct",
+            "baz",
+            "native_function1",
+            "prelude_function",
+        ] {
+            assert!(
+                globals.contains(&expected.to_owned()),
+                "Missing `{}` in {:?}",
+                expected,
+                globals
+            );
+        }
+
+        // The completions are based on the latest text, even if it does not parse.
+        server.change_file(foo_uri.clone(), format!("{}\nbar_struct.q", foo_contents))?;
+        let mut members = completion_labels(&mut server, foo_uri.clone(), 3, 12)?;
+        members.sort();
+        assert_eq!(vec!["quux".to_owned(), "qux".to_owned()], members);
+
+        server.change_file(
+            foo_uri.clone(),
+            format!("{}\nbar(name = \"x\", ", foo_contents),
+        )?;
+        let parameters = completion_labels(&mut server, foo_uri, 3, 16)?;
+        assert!(parameters.contains(&"srcs".to_owned()), "{:?}", parameters);
+        assert!(!parameters.contains(&"name".to_owned()), "{:?}", parameters);
+        assert!(
+            !parameters.contains(&"*args".to_owned()),
+            "{:?}",
+            parameters
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn shows_signature_help() -> anyhow::Result<()> {
+        let foo_uri = temp_file_uri("foo.star");
+        let foo_contents = dedent(
+            r#"
+            def baz(x, y = 1, *args, z = 2):
+                """Does baz."""
+                return (x, y, args, z)
+            baz(1, 2, 3, z = 4)
+            "#,
+        )
+        .trim_start()
+        .to_owned();
+
+        let mut server = TestServer::new()?;
+        server.open_file(foo_uri.clone(), foo_contents)?;
+
+        let expected_parameters = ["x", "y", "*args", "z"].map(|label| ParameterInformation {
+            label: ParameterLabel::Simple(label.to_owned()),
+            documentation: None,
+        });
+        let cases = [
+            (4, Some(0)),
+            (7, Some(1)),
+            (10, Some(2)),
+            (13, Some(2)),
+            (17, Some(3)),
+        ];
+        for (character, active_parameter) in cases {
+            let help = signature_help(&mut server, foo_uri.clone(), 3, character)?
+                .context("expected signature help")?;
+            assert_eq!(
+                SignatureHelp {
+                    signatures: vec![SignatureInformation {
+                        label: "baz(x, y, *args, z)".to_owned(),
+                        documentation: Some(Documentation::MarkupContent(MarkupContent {
+                            kind: MarkupKind::Markdown,
+                            value: "Does baz.".to_owned(),
+                        })),
+                        parameters: Some(expected_parameters.to_vec()),
+                        active_parameter,
+                    }],
+                    active_signature: Some(0),
+                    active_parameter,
+                },
+                help,
+                "at character {}",
+                character
+            );
+        }
+
+        assert_eq!(None, signature_help(&mut server, foo_uri, 2, 4)?);
+        Ok(())
+    }
+
+    fn references(
+        server: &mut TestServer,
+        uri: Url,
+        line: u32,
+        character: u32,
+    ) -> anyhow::Result<HashMap<Url, Vec<Range>>> {
+        let request = server.new_request::<References>(ReferenceParams {
+            text_document_position: TextDocumentPositionParams {
+                text_document: TextDocumentIdentifier { uri },
+                position: Position { line, character },
+            },
+            work_done_progress_params: Default::default(),
+            partial_result_params: Default::default(),
+            context: ReferenceContext {
+                include_declaration: true,
+            },
+        });
+        let request_id = server.send_request(request)?;
+        let mut res: HashMap<Url, Vec<Range>> = HashMap::new();
+        for location in server
+            .get_response::<Option<Vec<lsp_types::Location>>>(request_id)?
+            .unwrap_or_default()
+        {
+            res.entry(location.uri).or_default().push(location.range);
+        }
+        Ok(res)
+    }
+
+    fn rename(
+        server: &mut TestServer,
+        uri: Url,
+        line: u32,
+        character: u32,
+        new_name: &str,
+    ) -> anyhow::Result<Option<WorkspaceEdit>> {
+        let request = server.new_request::<Rename>(RenameParams {
+            text_document_position: TextDocumentPositionParams {
+                text_document: TextDocumentIdentifier { uri },
+                position: Position { line, character },
+            },
+            new_name: new_name.to_owned(),
+            work_done_progress_params: Default::default(),
+        });
+        let request_id = server.send_request(request)?;
+        server.get_response(request_id)
+    }
+
+    /// Fixtures for a symbol `bar` that is defined in `bar.star`, loaded by `foo.star`, and
+    /// loaded under a different name by `qux.star`.
+    fn loaded_symbol_fixtures() -> anyhow::Result<[(Url, FixtureWithRanges); 4]> {
+        let bar_contents = dedent(
+            r#"
+            def <bar>bar</bar>():
+                pass
+            <bar_call>bar</bar_call>()
+            "#,
+        )
+        .trim()
+        .to_owned();
+        let foo_contents = dedent(
+            r#"
+            load("bar.star", "<bar_load>bar</bar_load>")
+            <bar_call>bar</bar_call>()
+            "#,
+        )
+        .trim()
+        .to_owned();
+        let qux_contents = dedent(
+            r#"
+            load("bar.star", <baz>baz</baz> = "<bar_load>bar</bar_load>")
+            <baz_call>baz</baz_call>()
+            def quz(bar):
+                return bar
+            "#,
+        )
+        .trim()
+        .to_owned();
+        let unrelated_contents = dedent(
+            r#"
+            def bar():
+                pass
+            bar()
+            "#,
+        )
+        .trim()
+        .to_owned();
+
+        let fixture = |name: &str, contents: String| -> anyhow::Result<_> {
+            Ok((
+                temp_file_uri(name),
+                FixtureWithRanges::from_fixture(name, &contents)?,
+            ))
+        };
+        Ok([
+            fixture("bar.star", bar_contents)?,
+            fixture("foo.star", foo_contents)?,
+            fixture("qux.star", qux_contents)?,
+            fixture("unrelated.star", unrelated_contents)?,
+        ])
+    }
+
+    #[test]
+    fn advertises_references_and_rename() -> anyhow::Result<()> {
+        let server = TestServer::new()?;
+        let capabilities = server
+            .initialization_result()
+            .context("initialization result")?
+            .capabilities;
+        assert_eq!(Some(OneOf::Left(true)), capabilities.references_provider);
+        assert_eq!(Some(OneOf::Left(true)), capabilities.rename_provider);
+        Ok(())
+    }
+
+    #[test]
+    fn finds_references_across_loaded_files() -> anyhow::Result<()> {
+        let [
+            (bar_uri, bar),
+            (foo_uri, foo),
+            (qux_uri, qux),
+            (unrelated_uri, unrelated),
+        ] = loaded_symbol_fixtures()?;
+
+        let mut server = TestServer::new()?;
+        server.open_file(foo_uri.clone(), foo.program())?;
+        for (uri, fixture) in [
+            (&bar_uri, &bar),
+            (&qux_uri, &qux),
+            (&unrelated_uri, &unrelated),
+        ] {
+            server.set_file_contents(PathBuf::from(uri.path()), fixture.program())?;
+        }
+
+        let expected: HashMap<Url, Vec<Range>> = HashMap::from([
+            (
+                bar_uri,
+                vec![bar.span("bar").into(), bar.span("bar_call").into()],
+            ),
+            (
+                foo_uri.clone(),
+                vec![foo.span("bar_load").into(), foo.span("bar_call").into()],
+            ),
+            (
+                qux_uri,
+                vec![
+                    qux.span("bar_load").into(),
+                    qux.span("baz").into(),
+                    qux.span("baz_call").into(),
+                ],
+            ),
+        ]);
+        for id in ["bar_load", "bar_call"] {
+            assert_eq!(
+                expected,
+                references(
+                    &mut server,
+                    foo_uri.clone(),
+                    foo.begin_line(id),
+                    foo.begin_column(id)
+                )?,
+                "references from `{}`",
+                id
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn renames_across_loaded_files() -> anyhow::Result<()> {
+        let [
+            (bar_uri, bar),
+            (foo_uri, foo),
+            (qux_uri, qux),
+            (unrelated_uri, unrelated),
+        ] = loaded_symbol_fixtures()?;
+
+        let mut server = TestServer::new()?;
+        server.open_file(bar_uri.clone(), bar.program())?;
+        server.open_file(foo_uri.clone(), foo.program())?;
+        for (uri, fixture) in [(&qux_uri, &qux), (&unrelated_uri, &unrelated)] {
+            server.set_file_contents(PathBuf::from(uri.path()), fixture.program())?;
+        }
+
+        let edits = |fixture: &FixtureWithRanges, ids: &[&str]| {
+            ids.iter()
+                .map(|id| TextEdit {
+                    range: fixture.span(id).into(),
+                    new_text: "renamed".to_owned(),
+                })
+                .collect::<Vec<_>>()
+        };
+        let expected = WorkspaceEdit {
+            changes: Some(HashMap::from([
+                (bar_uri.clone(), edits(&bar, &["bar", "bar_call"])),
+                (foo_uri.clone(), edits(&foo, &["bar_load", "bar_call"])),
+                (qux_uri, edits(&qux, &["bar_load"])),
+            ])),
+            ..WorkspaceEdit::default()
+        };
+        assert_eq!(
+            Some(expected),
+            rename(
+                &mut server,
+                bar_uri.clone(),
+                bar.begin_line("bar"),
+                bar.begin_column("bar"),
+                "renamed"
+            )?
+        );
+
+        // Keywords are not valid names.
+        assert!(
+            rename(
+                &mut server,
+                bar_uri,
+                bar.begin_line("bar"),
+                bar.begin_column("bar"),
+                "def"
+            )
+            .is_err()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn does_not_rename_builtins() -> anyhow::Result<()> {
+        let foo_uri = temp_file_uri("foo.star");
+        let mut server = TestServer::new()?;
+        server.open_file(foo_uri.clone(), "native_function1()\n".to_owned())?;
+        assert!(rename(&mut server, foo_uri, 0, 1, "renamed").is_err());
+        Ok(())
+    }
+
+    fn format(server: &mut TestServer, uri: Url) -> anyhow::Result<Option<Vec<TextEdit>>> {
+        let request = server.new_request::<Formatting>(DocumentFormattingParams {
+            text_document: TextDocumentIdentifier { uri },
+            options: FormattingOptions::default(),
+            work_done_progress_params: Default::default(),
+        });
+        let request_id = server.send_request(request)?;
+        server.get_response(request_id)
+    }
+
+    #[test]
+    fn formats_open_files() -> anyhow::Result<()> {
+        let foo_uri = temp_file_uri("foo.star");
+        let mut server = TestServer::new()?;
+        let capabilities = server
+            .initialization_result()
+            .context("initialization result")?
+            .capabilities;
+        assert_eq!(
+            Some(OneOf::Left(true)),
+            capabilities.document_formatting_provider
+        );
+
+        server.open_file(foo_uri.clone(), "x = [1,2]\ny = x # y\n".to_owned())?;
+        let expected = TextEdit {
+            range: Range::new(Position::new(0, 0), Position::new(2, 0)),
+            new_text: "x = [1, 2]\ny = x  # y\n".to_owned(),
+        };
+        assert_eq!(Some(vec![expected]), format(&mut server, foo_uri.clone())?);
+
+        server.change_file(foo_uri.clone(), "x = [1, 2]\n".to_owned())?;
+        assert_eq!(Some(Vec::new()), format(&mut server, foo_uri.clone())?);
+
+        // Files that don't parse are not formatted.
+        server.change_file(foo_uri.clone(), "x = [1, 2\n".to_owned())?;
+        assert_eq!(None, format(&mut server, foo_uri)?);
+        Ok(())
+    }
 }
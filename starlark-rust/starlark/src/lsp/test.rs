@@ -61,6 +61,8 @@ use crate::docs::render_docs_as_code;
 use crate::docs::Doc;
 use crate::docs::DocFunction;
 use crate::docs::DocItem;
+use crate::docs::DocMember;
+use crate::docs::DocModule;
 use crate::docs::Identifier;
 use crate::docs::Location;
 use crate::errors::EvalMessage;
@@ -120,6 +122,7 @@ struct TestServerContext {
     dirs: Arc<RwLock<HashSet<PathBuf>>>,
     builtin_docs: Arc<HashMap<LspUrl, String>>,
     builtin_symbols: Arc<HashMap<String, LspUrl>>,
+    builtin_environment: Arc<DocModule>,
 }
 
 impl LspContext for TestServerContext {
@@ -227,6 +230,20 @@ impl LspContext for TestServerContext {
     ) -> anyhow::Result<Option<LspUrl>> {
         Ok(self.builtin_symbols.get(symbol).cloned())
     }
+
+    fn get_environment(&self, _current_file: &LspUrl) -> anyhow::Result<DocModule> {
+        Ok((*self.builtin_environment).clone())
+    }
+
+    fn get_workspace_files(&self, _current_file: &LspUrl) -> anyhow::Result<Vec<LspUrl>> {
+        Ok(self
+            .file_contents
+            .read()
+            .unwrap()
+            .keys()
+            .map(|path| LspUrl::File(path.clone()))
+            .collect())
+    }
 }
 
 /// A server for use in testing that provides helpers for sending requests, correlating
@@ -357,10 +374,21 @@ impl TestServer {
         let builtin = Self::testing_builtins(&std::env::current_dir()?)?;
         let mut builtin_docs = HashMap::with_capacity(builtin.len());
         let mut builtin_symbols = HashMap::new();
+        let mut builtin_environment = DocModule::default();
 
         for (u, ds) in builtin {
             builtin_docs.insert(u.clone(), render_docs_as_code(&ds));
             for d in ds {
+                let member = match d.item {
+                    DocItem::Function(f) => Some(DocMember::Function(f)),
+                    DocItem::Property(p) => Some(DocMember::Property(p)),
+                    DocItem::Module(_) | DocItem::Object(_) => None,
+                };
+                if let Some(member) = member {
+                    builtin_environment
+                        .members
+                        .insert(d.id.name.clone(), member);
+                }
                 builtin_symbols.insert(d.id.name, u.clone());
             }
         }
@@ -382,6 +410,7 @@ impl TestServer {
             dirs: dirs.dupe(),
             builtin_docs: builtin_docs.dupe(),
             builtin_symbols,
+            builtin_environment: Arc::new(builtin_environment),
         };
 
         let server_thread = std::thread::spawn(|| {
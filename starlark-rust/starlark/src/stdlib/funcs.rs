@@ -52,6 +52,11 @@ use crate::values::Value;
 use crate::values::ValueError;
 use crate::values::ValueLike;
 
+/// The error raised by `fail()`.
+#[derive(Debug, thiserror::Error)]
+#[error("fail:{0}")]
+pub(crate) struct FailError(String);
+
 fn unpack_pair<'v>(pair: Value<'v>, heap: &'v Heap) -> anyhow::Result<(Value<'v>, Value<'v>)> {
     let mut it = pair.iterate(heap)?;
     if let Some(first) = it.next() {
@@ -156,7 +161,7 @@ pub(crate) fn global_functions(builder: &mut GlobalsBuilder) {
                 None => x.collect_repr(&mut s),
             }
         }
-        Err(anyhow::anyhow!("fail:{}", s))
+        Err(FailError(s).into())
     }
 
     /// [any](
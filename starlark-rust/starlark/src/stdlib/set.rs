@@ -0,0 +1,212 @@
+/*
+ * Copyright 2018 The Starlark in Rust Authors.
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Implementation of the `set` function and the methods of the `set` type.
+
+use starlark_derive::starlark_module;
+
+use crate as starlark;
+use crate::collections::SmallSet;
+use crate::environment::GlobalsBuilder;
+use crate::environment::MethodsBuilder;
+use crate::values::none::NoneType;
+use crate::values::set::Set;
+use crate::values::set::SetMut;
+use crate::values::set::SetRef;
+use crate::values::Heap;
+use crate::values::Value;
+use crate::values::ValueError;
+
+/// Collect the elements of an iterable into a new set, failing if any are unhashable.
+fn collect_set<'v>(xs: Value<'v>, heap: &'v Heap) -> anyhow::Result<Set<'v>> {
+    if let Some(xs) = SetRef::from_value(xs) {
+        return Ok(xs.clone());
+    }
+    let it = xs.iterate(heap)?;
+    let mut content = SmallSet::with_capacity(it.size_hint().0);
+    for x in it {
+        content.insert_hashed(x.get_hashed()?);
+    }
+    Ok(Set::new(content))
+}
+
+#[starlark_module]
+pub fn global(builder: &mut GlobalsBuilder) {
+    /// Creates a set, a mutable collection of unique values which iterates in insertion order.
+    ///
+    /// `set()` returns a new empty set, while `set(x)` returns a set of the elements of the
+    /// iterable `x`, keeping the first occurrence of any duplicates. The elements must be
+    /// hashable.
+    ///
+    /// Sets support `len`, `in`, iteration and the operators `|` (union), `&` (intersection),
+    /// `-` (difference) and `^` (symmetric difference), all of which preserve the order of
+    /// the left operand followed by the right. Two sets are equal if they have the same
+    /// elements, regardless of order. Sets cannot be encoded as JSON.
+    ///
+    /// ```
+    /// # starlark::assert::is_true(r#"
+    /// s = set([3, 1, 3, 2])
+    /// # (
+    /// list(s) == [3, 1, 2]
+    /// # and
+    /// list(s | set([4, 1])) == [3, 1, 2, 4]
+    /// # and
+    /// list(s & set([2, 3])) == [3, 2]
+    /// # and
+    /// list(s - set([1])) == [3, 2]
+    /// # and
+    /// list(s ^ set([1, 5])) == [3, 2, 5]
+    /// # and
+    /// s == set([1, 2, 3])
+    /// # )"#);
+    /// ```
+    #[starlark(dot_type = Set::TYPE, speculative_exec_safe)]
+    fn set<'v>(
+        #[starlark(require = pos, type = "iter(\"\")")] a: Option<Value<'v>>,
+        heap: &'v Heap,
+    ) -> anyhow::Result<Set<'v>> {
+        match a {
+            None => Ok(Set::default()),
+            Some(a) => collect_set(a, heap),
+        }
+    }
+}
+
+#[starlark_module]
+pub(crate) fn set_methods(registry: &mut MethodsBuilder) {
+    /// `S.add(x)` adds the value `x` to the set `S` if it is not already present,
+    /// and returns `None`.
+    ///
+    /// It fails if `x` is unhashable, if the set is frozen or if there are active iterators.
+    ///
+    /// ```
+    /// # starlark::assert::is_true(r#"
+    /// x = set([1, 2])
+    /// x.add(3)
+    /// x.add(1)
+    /// list(x) == [1, 2, 3]
+    /// # "#);
+    /// ```
+    fn add<'v>(
+        this: Value<'v>,
+        #[starlark(require = pos)] value: Value<'v>,
+    ) -> anyhow::Result<NoneType> {
+        let mut this = SetMut::from_value(this)?;
+        this.insert_hashed(value.get_hashed()?);
+        Ok(NoneType)
+    }
+
+    /// `S.remove(x)` removes the value `x` from the set `S`, and returns `None`.
+    ///
+    /// It fails if `x` is not in the set, if the set is frozen or if there are
+    /// active iterators.
+    ///
+    /// ```
+    /// # starlark::assert::is_true(r#"
+    /// x = set([1, 2, 3])
+    /// x.remove(2)
+    /// list(x) == [1, 3]
+    /// # "#);
+    /// ```
+    ///
+    /// ```
+    /// # starlark::assert::fail(r#"
+    /// x = set([1, 2, 3])
+    /// x.remove(4) # error: not found
+    /// # "#, "not found");
+    /// ```
+    fn remove<'v>(
+        this: Value<'v>,
+        #[starlark(require = pos)] value: Value<'v>,
+    ) -> anyhow::Result<NoneType> {
+        let mut this = SetMut::from_value(this)?;
+        if this.remove_hashed(value.get_hashed()?) {
+            Ok(NoneType)
+        } else {
+            Err(ValueError::KeyNotFound(value.to_repr()).into())
+        }
+    }
+
+    /// `S.discard(x)` removes the value `x` from the set `S` if it is present,
+    /// and returns `None`.
+    ///
+    /// It fails if the set is frozen or if there are active iterators.
+    ///
+    /// ```
+    /// # starlark::assert::is_true(r#"
+    /// x = set([1, 2, 3])
+    /// x.discard(2)
+    /// x.discard(4)
+    /// list(x) == [1, 3]
+    /// # "#);
+    /// ```
+    fn discard<'v>(
+        this: Value<'v>,
+        #[starlark(require = pos)] value: Value<'v>,
+    ) -> anyhow::Result<NoneType> {
+        let mut this = SetMut::from_value(this)?;
+        this.remove_hashed(value.get_hashed()?);
+        Ok(NoneType)
+    }
+
+    /// `S.union(*xs)` returns a new set containing the elements of `S` followed by
+    /// those of each iterable in `xs` that were not already present.
+    ///
+    /// ```
+    /// # starlark::assert::is_true(r#"
+    /// x = set([1, 2])
+    /// list(x.union([2, 3], set([4]))) == [1, 2, 3, 4]
+    /// # "#);
+    /// ```
+    #[starlark(speculative_exec_safe)]
+    fn union<'v>(
+        this: SetRef<'v>,
+        #[starlark(args)] others: Vec<Value<'v>>,
+        heap: &'v Heap,
+    ) -> anyhow::Result<Set<'v>> {
+        let mut res = this.clone();
+        for other in others {
+            for x in other.iterate(heap)? {
+                res.insert_hashed(x.get_hashed()?);
+            }
+        }
+        Ok(res)
+    }
+
+    /// `S.issubset(x)` returns `True` if every element of `S` is also in the iterable `x`.
+    ///
+    /// ```
+    /// # starlark::assert::is_true(r#"
+    /// x = set([1, 2])
+    /// # (
+    /// x.issubset([3, 2, 1])
+    /// # and
+    /// not x.issubset(set([1]))
+    /// # )"#);
+    /// ```
+    #[starlark(speculative_exec_safe)]
+    fn issubset<'v>(
+        this: SetRef<'v>,
+        #[starlark(require = pos, type = "iter(\"\")")] other: Value<'v>,
+        heap: &'v Heap,
+    ) -> anyhow::Result<bool> {
+        match SetRef::from_value(other) {
+            Some(other) => Ok(this.is_subset(&other)),
+            None => Ok(this.is_subset(&collect_set(other, heap)?)),
+        }
+    }
+}
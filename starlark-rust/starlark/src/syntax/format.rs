@@ -0,0 +1,1680 @@
+/*
+ * Copyright 2019 The Starlark in Rust Authors.
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Pretty print a parsed module in a canonical layout.
+//!
+//! The layout is produced from the AST, so none of the original whitespace survives, except
+//! that blank lines between statements and between the items of a bracket are kept (collapsed
+//! to one). Comments are not part of the AST, so they are found by scanning the source, and
+//! written before the code that follows them, or at the end of the line they were on.
+//!
+//! Expressions are written on one line if they fit in [`MAX_WIDTH`], and otherwise their
+//! outermost brackets are split with one item per line and a trailing comma. As with `black`,
+//! a trailing comma in the source keeps a bracket split, which makes formatting idempotent.
+
+use std::mem;
+
+use dupe::Dupe;
+
+use crate::codemap::Span;
+use crate::syntax::ast::ArgumentP;
+use crate::syntax::ast::AssignP;
+use crate::syntax::ast::AstArgument;
+use crate::syntax::ast::AstAssign;
+use crate::syntax::ast::AstAssignIdent;
+use crate::syntax::ast::AstExpr;
+use crate::syntax::ast::AstLiteral;
+use crate::syntax::ast::AstNoPayload;
+use crate::syntax::ast::AstParameter;
+use crate::syntax::ast::AstStmt;
+use crate::syntax::ast::AstString;
+use crate::syntax::ast::BinOp;
+use crate::syntax::ast::Clause;
+use crate::syntax::ast::ClauseP;
+use crate::syntax::ast::DefP;
+use crate::syntax::ast::ExprP;
+use crate::syntax::ast::ForClause;
+use crate::syntax::ast::Load;
+use crate::syntax::ast::ParameterP;
+use crate::syntax::ast::StmtP;
+use crate::syntax::AstModule;
+
+/// What kind of file is being formatted, which decides how much it may be rearranged.
+#[derive(Debug, Clone, Copy, Dupe, PartialEq, Eq)]
+pub enum FormatMode {
+    /// A build file, e.g. `BUCK`. Top-level rule calls are split one argument per line, their
+    /// arguments are sorted (`name` first, `deps` and `visibility` last), and their lists of
+    /// sources, dependencies and visibility are sorted if they only contain string literals.
+    Build,
+    /// Any other Starlark file, e.g. a `.bzl` file. Only the symbols of `load` statements are
+    /// reordered.
+    Extension,
+}
+
+/// The width that lines are kept within, where possible.
+const MAX_WIDTH: usize = 100;
+
+const INDENT: &str = "    ";
+
+/// Arguments of rules in build files that come first, in this order.
+const LEADING_RULE_ARGUMENTS: &[&str] = &[
+    "name",
+    "out",
+    "outs",
+    "src",
+    "srcs",
+    "headers",
+    "exported_headers",
+];
+
+/// Arguments of rules in build files that come last, in this order.
+const TRAILING_RULE_ARGUMENTS: &[&str] = &["deps", "exported_deps", "visibility"];
+
+/// Arguments of rules in build files whose lists of string literals are sorted.
+const SORTED_RULE_ARGUMENTS: &[&str] = &[
+    "srcs",
+    "headers",
+    "exported_headers",
+    "deps",
+    "exported_deps",
+    "resources",
+    "visibility",
+];
+
+/// The precedence of primary expressions (identifiers, literals, calls etc.), which never need
+/// parentheses.
+const PRIMARY: u8 = 13;
+
+/// Items within a sorted bracket are ordered by this key.
+type SortKey = (u8, usize, String);
+
+impl AstModule {
+    /// Pretty print the module in a canonical layout, keeping its comments.
+    ///
+    /// Formatting the result again gives the same text.
+    pub fn format(&self, mode: FormatMode) -> String {
+        let source = self.codemap.source();
+        let mut formatter = Formatter {
+            source,
+            mode,
+            comments: find_comments(source),
+            next_comment: 0,
+            anchor: 0,
+            out: String::new(),
+        };
+        formatter.write_block(&block_statements(&self.statement), 0, true);
+        formatter.flush_comments(source.len(), 0, true);
+
+        let mut out = formatter.out;
+        out.truncate(out.trim_end().len());
+        if !out.is_empty() {
+            out.push('\n');
+        }
+        out
+    }
+}
+
+#[derive(Clone, Copy)]
+struct Comment<'a> {
+    /// The byte offset of the `#`.
+    pos: usize,
+    /// The byte offset of the end of the comment.
+    end: usize,
+    /// The column the comment starts at.
+    column: usize,
+    text: &'a str,
+    /// Whether the comment is on a line of its own, rather than after some code.
+    own_line: bool,
+}
+
+/// Find all the comments in `source`, which has already been parsed successfully.
+fn find_comments(source: &str) -> Vec<Comment<'_>> {
+    let bytes = source.as_bytes();
+    let mut comments = Vec::new();
+    let mut line_start = 0;
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'\n' => {
+                i += 1;
+                line_start = i;
+            }
+            b'#' => {
+                let end = source[i..].find('\n').map_or(source.len(), |n| i + n);
+                comments.push(Comment {
+                    pos: i,
+                    end,
+                    column: i - line_start,
+                    text: source[i..end].trim_end(),
+                    own_line: source[line_start..i].trim().is_empty(),
+                });
+                i = end;
+            }
+            quote @ (b'"' | b'\'') => {
+                let delimiter = if bytes[i..].starts_with(&[quote; 3]) {
+                    &[quote; 3][..]
+                } else {
+                    &[quote][..]
+                };
+                i += delimiter.len();
+                while i < bytes.len() {
+                    if bytes[i..].starts_with(delimiter) {
+                        i += delimiter.len();
+                        break;
+                    }
+                    if bytes[i] == b'\n' {
+                        line_start = i + 1;
+                    }
+                    i += if bytes[i] == b'\\' { 2 } else { 1 };
+                }
+            }
+            _ => i += 1,
+        }
+    }
+    comments
+}
+
+/// The statements of a block, with the `Statements` nodes flattened.
+fn block_statements(stmt: &AstStmt) -> Vec<&AstStmt> {
+    fn collect<'a>(stmt: &'a AstStmt, res: &mut Vec<&'a AstStmt>) {
+        match &stmt.node {
+            StmtP::Statements(xs) => {
+                for x in xs {
+                    collect(x, res);
+                }
+            }
+            _ => res.push(stmt),
+        }
+    }
+
+    let mut res = Vec::new();
+    collect(stmt, &mut res);
+    res
+}
+
+fn begin(span: Span) -> usize {
+    span.begin().get() as usize
+}
+
+fn end(span: Span) -> usize {
+    span.end().get() as usize
+}
+
+fn is_def(stmt: &AstStmt) -> bool {
+    matches!(stmt.node, StmtP::Def(_))
+}
+
+/// The precedence of an expression, as written by the formatter, from the grammar.
+fn precedence(e: &AstExpr) -> u8 {
+    match &e.node {
+        ExprP::Lambda(_) => 0,
+        ExprP::If(_) => 1,
+        ExprP::Op(_, op, _) => binop_precedence(*op),
+        ExprP::Not(_) => 4,
+        ExprP::Minus(_) | ExprP::Plus(_) | ExprP::BitNot(_) => 12,
+        _ => PRIMARY,
+    }
+}
+
+fn binop_precedence(op: BinOp) -> u8 {
+    match op {
+        BinOp::Or => 2,
+        BinOp::And => 3,
+        BinOp::Equal
+        | BinOp::NotEqual
+        | BinOp::Less
+        | BinOp::Greater
+        | BinOp::LessOrEqual
+        | BinOp::GreaterOrEqual
+        | BinOp::In
+        | BinOp::NotIn => 5,
+        BinOp::BitOr => 6,
+        BinOp::BitXor => 7,
+        BinOp::BitAnd => 8,
+        BinOp::LeftShift | BinOp::RightShift => 9,
+        BinOp::Add | BinOp::Subtract => 10,
+        BinOp::Multiply | BinOp::Percent | BinOp::Divide | BinOp::FloorDivide => 11,
+    }
+}
+
+/// The precedence required of the left and right operands of a binary operator.
+fn operand_precedences(op: BinOp) -> (u8, u8) {
+    match binop_precedence(op) {
+        // Comparisons don't chain.
+        5 => (6, 6),
+        p => (p, p + 1),
+    }
+}
+
+/// Write a string literal with double quotes, if that doesn't need any escaping to change.
+fn string_literal(text: &str) -> String {
+    let (prefix, quoted) = match text.strip_prefix('r') {
+        Some(quoted) => ("r", quoted),
+        None => ("", text),
+    };
+    for (quote, replacement) in [("'''", "\"\"\""), ("'", "\"")] {
+        if quoted.len() < 2 * quote.len() {
+            continue;
+        }
+        if let Some(body) = quoted
+            .strip_prefix(quote)
+            .and_then(|x| x.strip_suffix(quote))
+        {
+            if !body.contains(&['"', '\\'][..]) {
+                return format!("{}{}{}{}", prefix, replacement, body, replacement);
+            }
+            break;
+        }
+    }
+    text.to_owned()
+}
+
+fn rule_argument_key(arg: &AstArgument) -> SortKey {
+    match &arg.node {
+        ArgumentP::Named(name, _) => {
+            let name = name.node.as_str();
+            if let Some(i) = LEADING_RULE_ARGUMENTS.iter().position(|x| *x == name) {
+                (1, i, String::new())
+            } else if let Some(i) = TRAILING_RULE_ARGUMENTS.iter().position(|x| *x == name) {
+                (3, i, String::new())
+            } else {
+                (2, 0, name.to_owned())
+            }
+        }
+        _ => (0, 0, String::new()),
+    }
+}
+
+/// Sort local targets first, then files, then targets in this cell, then other cells.
+fn label_key(label: &str) -> SortKey {
+    let rank = if label.starts_with(':') {
+        0
+    } else if label.starts_with("//") {
+        2
+    } else if label.contains("//") {
+        3
+    } else {
+        1
+    };
+    (rank, 0, label.to_owned())
+}
+
+/// Something separated by commas within brackets.
+#[derive(Clone, Copy)]
+enum Item<'a> {
+    Expr(&'a AstExpr),
+    String(&'a AstString),
+    Entry(&'a AstExpr, &'a AstExpr),
+    Argument(&'a AstArgument),
+    /// An argument to a top-level rule call in a build file.
+    RuleArgument(&'a AstArgument),
+    Parameter(&'a AstParameter),
+    Load(&'a AstAssignIdent, &'a AstString),
+}
+
+impl<'a> Item<'a> {
+    fn span(self) -> Span {
+        match self {
+            Item::Expr(x) => x.span,
+            Item::String(x) => x.span,
+            Item::Entry(k, v) => k.span.merge(v.span),
+            Item::Argument(x) | Item::RuleArgument(x) => x.span,
+            Item::Parameter(x) => x.span,
+            Item::Load(local, name) => local.span.merge(name.span),
+        }
+    }
+}
+
+struct Bracket<'a> {
+    open: &'static str,
+    close: &'static str,
+    items: Vec<Item<'a>>,
+    /// The order to write the items in, in groups that are separated by blank lines.
+    groups: Vec<Vec<usize>>,
+    /// Comments from here until `end` are within the bracket.
+    begin: usize,
+    end: usize,
+    /// Whether a single item needs a trailing comma.
+    tuple: bool,
+}
+
+struct Formatter<'a> {
+    source: &'a str,
+    mode: FormatMode,
+    comments: Vec<Comment<'a>>,
+    /// The first comment that hasn't been written yet.
+    next_comment: usize,
+    /// The end of the last code or comment that was written, to find blank lines after it.
+    anchor: usize,
+    out: String,
+}
+
+impl<'a> Formatter<'a> {
+    fn text(&self, span: Span) -> &'a str {
+        &self.source[begin(span)..end(span)]
+    }
+
+    fn line_end(&self, pos: usize) -> usize {
+        self.source[pos..]
+            .find('\n')
+            .map_or(self.source.len(), |n| pos + n)
+    }
+
+    fn column_of(&self, pos: usize) -> usize {
+        pos - self.source[..pos].rfind('\n').map_or(0, |n| n + 1)
+    }
+
+    /// Skip past whitespace and comments.
+    fn skip_trivia(&self, mut pos: usize) -> usize {
+        let bytes = self.source.as_bytes();
+        loop {
+            match bytes.get(pos) {
+                Some(b' ' | b'\t' | b'\r' | b'\n' | b'\\') => pos += 1,
+                Some(b'#') => pos = self.line_end(pos),
+                _ => return pos,
+            }
+        }
+    }
+
+    fn has_trailing_comma(&self, after: usize) -> bool {
+        self.source[self.skip_trivia(after)..].starts_with(',')
+    }
+
+    /// Find the `)` after the last item of a bracket.
+    fn closing_paren(&self, after: usize) -> Option<usize> {
+        let mut pos = self.skip_trivia(after);
+        if self.source[pos..].starts_with(',') {
+            pos = self.skip_trivia(pos + 1);
+        }
+        self.source[pos..].starts_with(')').then_some(pos)
+    }
+
+    fn has_comment(&self, begin: usize, end: usize) -> bool {
+        let i = self.comments.partition_point(|c| c.pos < begin);
+        matches!(self.comments.get(i), Some(c) if c.pos < end)
+    }
+
+    /// Whether there is a blank line between two positions.
+    fn has_blank_line(&self, from: usize, to: usize) -> bool {
+        if from >= to {
+            return false;
+        }
+        let lines: Vec<&str> = self.source[from..to].split('\n').collect();
+        lines.len() > 2
+            && lines[1..lines.len() - 1]
+                .iter()
+                .any(|x| x.trim().is_empty())
+    }
+
+    /// Whether there is a blank line between the end of one item and the start of the next,
+    /// or of the comments before it.
+    fn blank_before(&self, prev_end: usize, begin: usize) -> bool {
+        let i = self.comments.partition_point(|c| c.pos < prev_end);
+        let first = self.comments[i..]
+            .iter()
+            .take_while(|c| c.pos < begin)
+            .find(|c| c.own_line)
+            .map_or(begin, |c| c.pos);
+        self.has_blank_line(prev_end, first)
+    }
+
+    fn write(&mut self, text: &str) {
+        self.out.push_str(text);
+    }
+
+    fn indent(&mut self, indent: usize) {
+        for _ in 0..indent {
+            self.out.push_str(INDENT);
+        }
+    }
+
+    fn blank_line(&mut self) {
+        if !self.out.is_empty() && !self.out.ends_with("\n\n") {
+            self.out.push('\n');
+        }
+    }
+
+    /// Whether `text` fits on the current line.
+    fn fits(&self, text: &str) -> bool {
+        let line_start = self.out.rfind('\n').map_or(0, |n| n + 1);
+        let column = self.out[line_start..].chars().count();
+        let width = text.split('\n').next().unwrap_or_default().chars().count();
+        column + width <= MAX_WIDTH
+    }
+
+    /// Finish the current line, with any comments before `limit` that were after code.
+    fn end_line(&mut self, limit: usize) {
+        while let Some(c) = self.comments.get(self.next_comment).copied() {
+            if c.pos >= limit || c.own_line {
+                break;
+            }
+            self.out.push_str("  ");
+            self.out.push_str(c.text);
+            self.anchor = self.anchor.max(c.end);
+            self.next_comment += 1;
+        }
+        self.out.push('\n');
+    }
+
+    /// Write the comments before `limit` on lines of their own.
+    fn flush_comments(&mut self, limit: usize, indent: usize, mut allow_blank: bool) -> bool {
+        while let Some(c) = self.comments.get(self.next_comment).copied() {
+            if c.pos >= limit {
+                break;
+            }
+            if allow_blank && self.has_blank_line(self.anchor, c.pos) {
+                self.blank_line();
+            }
+            self.indent(indent);
+            self.write(c.text);
+            self.out.push('\n');
+            self.anchor = self.anchor.max(c.end);
+            self.next_comment += 1;
+            allow_blank = true;
+        }
+        allow_blank
+    }
+
+    /// Write the comments before the code at `limit`, and a blank line if there was one.
+    fn flush_leading(&mut self, limit: usize, indent: usize, allow_blank: bool) {
+        if self.flush_comments(limit, indent, allow_blank)
+            && self.has_blank_line(self.anchor, limit)
+        {
+            self.blank_line();
+        }
+    }
+
+    /// Write the comments at the end of a block, i.e. those at least as indented as the block,
+    /// up until the next code.
+    fn flush_trailing(&mut self, column: usize, indent: usize) {
+        while let Some(c) = self.comments.get(self.next_comment).copied() {
+            let inside = c.pos < self.anchor;
+            if !inside {
+                let only_trivia = self.source[self.anchor..c.pos]
+                    .trim_matches(|x: char| x.is_whitespace() || x == ';' || x == ')')
+                    .is_empty();
+                if !c.own_line || c.column < column || !only_trivia {
+                    break;
+                }
+                if self.has_blank_line(self.anchor, c.pos) {
+                    self.blank_line();
+                }
+            }
+            self.indent(indent);
+            self.write(c.text);
+            self.out.push('\n');
+            self.anchor = self.anchor.max(c.end);
+            self.next_comment += 1;
+        }
+    }
+
+    /// End the current line and start the next, with any comments before `limit`.
+    fn next_line(&mut self, limit: usize, indent: usize) {
+        self.end_line(limit);
+        self.flush_comments(limit, indent, false);
+        self.indent(indent);
+    }
+
+    fn write_block(&mut self, stmts: &[&'a AstStmt], indent: usize, top_level: bool) {
+        let column = match stmts.first() {
+            Some(first) => self.column_of(begin(first.span)),
+            None => return,
+        };
+        for (i, stmt) in stmts.iter().enumerate() {
+            if top_level && i > 0 && (is_def(stmt) || is_def(stmts[i - 1])) {
+                self.blank_line();
+            }
+            self.flush_leading(begin(stmt.span), indent, i > 0);
+            let mut limit = self.line_end(end(stmt.span));
+            if let Some(next) = stmts.get(i + 1) {
+                limit = limit.min(begin(next.span));
+            }
+            self.write_stmt(stmt, indent, top_level, limit);
+        }
+        if !top_level {
+            self.flush_trailing(column, indent);
+        }
+    }
+
+    fn write_stmt(&mut self, stmt: &'a AstStmt, indent: usize, top_level: bool, limit: usize) {
+        self.indent(indent);
+        match &stmt.node {
+            StmtP::Break => self.write("break"),
+            StmtP::Continue => self.write("continue"),
+            StmtP::Pass => self.write("pass"),
+            StmtP::Return(None) => self.write("return"),
+            StmtP::Return(Some(x)) => {
+                self.write("return ");
+                self.write_statement_expr(x, indent);
+            }
+            StmtP::Expression(x) => match &x.node {
+                ExprP::Call(f, args) if top_level && self.mode == FormatMode::Build => {
+                    self.write_rule_call(x, f, args, indent)
+                }
+                _ => self.write_statement_expr(x, indent),
+            },
+            StmtP::Assign(lhs, ty_rhs) => {
+                let (ty, rhs) = &**ty_rhs;
+                self.write_assign(lhs, true, indent);
+                if let Some(ty) = ty {
+                    self.write(": ");
+                    self.write_expr(ty, 0, indent);
+                }
+                self.write(" = ");
+                self.write_statement_expr(rhs, indent);
+            }
+            StmtP::AssignModify(lhs, op, rhs) => {
+                self.write_assign(lhs, true, indent);
+                self.write(&op.to_string());
+                self.write_statement_expr(rhs, indent);
+            }
+            StmtP::Load(load) => self.write_load(stmt, load, indent),
+            StmtP::Def(def) => return self.write_def(def, indent),
+            StmtP::If(cond, then) => return self.write_if("if", cond, then, None, indent),
+            StmtP::IfElse(cond, then_else) => {
+                let (then, otherwise) = &**then_else;
+                return self.write_if("if", cond, then, Some(otherwise), indent);
+            }
+            StmtP::For(var, over_body) => {
+                let (over, body) = &**over_body;
+                self.write("for ");
+                self.write_assign(var, true, indent);
+                self.write(" in ");
+                self.write_expr(over, 0, indent);
+                self.write(":");
+                self.end_line(begin(body.span));
+                return self.write_block(&block_statements(body), indent + 1, false);
+            }
+            StmtP::Statements(_) => unreachable!("Statements are flattened by block_statements"),
+        }
+        self.anchor = self.anchor.max(end(stmt.span));
+        self.end_line(limit);
+    }
+
+    fn write_def(&mut self, def: &'a DefP<AstNoPayload>, indent: usize) {
+        self.write("def ");
+        self.write(&def.name.node.0);
+        let after_params = match def.params.last() {
+            Some(last) => end(last.span),
+            None => self.skip_trivia(end(def.name.span)) + 1,
+        };
+        let close = self
+            .closing_paren(after_params)
+            .unwrap_or_else(|| begin(def.body.span));
+        let params = self.bracket(
+            "(",
+            ")",
+            def.params.iter().map(Item::Parameter).collect(),
+            end(def.name.span),
+            close,
+            false,
+            None,
+        );
+        let flat = self.flat_bracket(&params).and_then(|params| {
+            Some(match &def.return_type {
+                Some(ret) => format!("{} -> {}:", params, self.flat_expr(ret, 0)?),
+                None => format!("{}:", params),
+            })
+        });
+        match flat {
+            Some(flat) if self.fits(&flat) => self.write(&flat),
+            _ => {
+                self.write_bracket(&params, indent);
+                if let Some(ret) = &def.return_type {
+                    self.write(" -> ");
+                    self.write_expr(ret, 0, indent);
+                }
+                self.write(":");
+            }
+        }
+        self.end_line(begin(def.body.span));
+        self.write_block(&block_statements(&def.body), indent + 1, false);
+    }
+
+    fn write_if(
+        &mut self,
+        keyword: &str,
+        cond: &'a AstExpr,
+        then: &'a AstStmt,
+        otherwise: Option<&'a AstStmt>,
+        indent: usize,
+    ) {
+        self.write(keyword);
+        self.write(" ");
+        self.write_expr(cond, 0, indent);
+        self.write(":");
+        self.end_line(begin(then.span));
+        self.write_block(&block_statements(then), indent + 1, false);
+
+        let otherwise = match otherwise {
+            Some(otherwise) => otherwise,
+            None => return,
+        };
+        let keyword = self.skip_trivia(self.anchor);
+        self.flush_comments(keyword, indent, false);
+        self.indent(indent);
+        if self.source[keyword..].starts_with("elif") {
+            match &otherwise.node {
+                StmtP::If(cond, then) => return self.write_if("elif", cond, then, None, indent),
+                StmtP::IfElse(cond, then_else) => {
+                    let (then, otherwise) = &**then_else;
+                    return self.write_if("elif", cond, then, Some(otherwise), indent);
+                }
+                _ => {}
+            }
+        }
+        self.write("else:");
+        self.end_line(begin(otherwise.span));
+        self.write_block(&block_statements(otherwise), indent + 1, false);
+    }
+
+    fn write_load(&mut self, stmt: &'a AstStmt, load: &'a Load, indent: usize) {
+        let items: Vec<Item> = std::iter::once(Item::String(&load.module))
+            .chain(
+                load.args
+                    .iter()
+                    .map(|(local, name)| Item::Load(local, name)),
+            )
+            .collect();
+        let keys = items
+            .iter()
+            .map(|x| match x {
+                Item::Load(local, _) => (1, 0, local.node.0.clone()),
+                _ => (0, 0, String::new()),
+            })
+            .collect();
+        let bracket = self.bracket(
+            "(",
+            ")",
+            items,
+            begin(stmt.span),
+            end(stmt.span),
+            false,
+            Some(keys),
+        );
+        self.write("load");
+        self.write_brackets(&bracket, indent);
+    }
+
+    /// Write a top-level call in a build file, which is usually a rule.
+    fn write_rule_call(
+        &mut self,
+        call: &'a AstExpr,
+        f: &'a AstExpr,
+        args: &'a [AstArgument],
+        indent: usize,
+    ) {
+        let keys = args
+            .iter()
+            .all(|x| matches!(x.node, ArgumentP::Positional(_) | ArgumentP::Named(..)))
+            .then(|| args.iter().map(rule_argument_key).collect());
+        let bracket = self.bracket(
+            "(",
+            ")",
+            args.iter().map(Item::RuleArgument).collect(),
+            end(f.span),
+            end(call.span),
+            false,
+            keys,
+        );
+        self.write_expr(f, PRIMARY, indent);
+        if args.len() > 1 && args.iter().any(|x| matches!(x.node, ArgumentP::Named(..))) {
+            self.write_bracket(&bracket, indent);
+        } else {
+            self.write_brackets(&bracket, indent);
+        }
+    }
+
+    /// Write an expression at the top of a statement, where a tuple doesn't need brackets.
+    fn write_statement_expr(&mut self, x: &'a AstExpr, indent: usize) {
+        match &x.node {
+            ExprP::Tuple(xs) if xs.len() > 1 => {
+                let bracket = self.tuple_bracket(x, xs);
+                match self.flat_bracket(&bracket) {
+                    Some(flat) if self.fits(&flat[1..flat.len() - 1]) => {
+                        self.write(&flat[1..flat.len() - 1])
+                    }
+                    _ => self.write_bracket(&bracket, indent),
+                }
+            }
+            _ => self.write_expr(x, 0, indent),
+        }
+    }
+
+    fn bracket(
+        &self,
+        open: &'static str,
+        close: &'static str,
+        items: Vec<Item<'a>>,
+        begin: usize,
+        end: usize,
+        tuple: bool,
+        keys: Option<Vec<SortKey>>,
+    ) -> Bracket<'a> {
+        let mut groups: Vec<Vec<usize>> = Vec::new();
+        for (i, item) in items.iter().enumerate() {
+            let blank = i > 0
+                && self.blank_before(self::end(items[i - 1].span()), self::begin(item.span()));
+            match groups.last_mut() {
+                Some(group) if !blank => group.push(i),
+                _ => groups.push(vec![i]),
+            }
+        }
+        if let Some(keys) = keys {
+            for group in &mut groups {
+                group.sort_by(|a, b| keys[*a].cmp(&keys[*b]));
+            }
+        }
+        Bracket {
+            open,
+            close,
+            items,
+            groups,
+            begin,
+            end,
+            tuple,
+        }
+    }
+
+    fn tuple_bracket(&self, x: &'a AstExpr, xs: &'a [AstExpr]) -> Bracket<'a> {
+        let close = xs
+            .last()
+            .and_then(|last| self.closing_paren(end(last.span)))
+            .unwrap_or_else(|| end(x.span));
+        self.bracket(
+            "(",
+            ")",
+            xs.iter().map(Item::Expr).collect(),
+            begin(x.span),
+            close,
+            true,
+            None,
+        )
+    }
+
+    fn list_bracket(&self, x: &'a AstExpr, xs: &'a [AstExpr], sort: bool) -> Bracket<'a> {
+        let keys = if sort {
+            xs.iter()
+                .map(|x| match &x.node {
+                    ExprP::Literal(AstLiteral::String(s)) => Some(label_key(&s.node)),
+                    _ => None,
+                })
+                .collect()
+        } else {
+            None
+        };
+        self.bracket(
+            "[",
+            "]",
+            xs.iter().map(Item::Expr).collect(),
+            begin(x.span),
+            end(x.span),
+            false,
+            keys,
+        )
+    }
+
+    fn call_bracket(
+        &self,
+        call: &'a AstExpr,
+        f: &'a AstExpr,
+        args: &'a [AstArgument],
+    ) -> Bracket<'a> {
+        self.bracket(
+            "(",
+            ")",
+            args.iter().map(Item::Argument).collect(),
+            end(f.span),
+            end(call.span),
+            false,
+            None,
+        )
+    }
+
+    /// The sorted list that is the value of a rule argument, if it should be sorted.
+    fn rule_list(&self, name: &AstString, value: &'a AstExpr) -> Option<Bracket<'a>> {
+        match &value.node {
+            ExprP::List(xs) if SORTED_RULE_ARGUMENTS.contains(&name.node.as_str()) => {
+                Some(self.list_bracket(value, xs, true))
+            }
+            _ => None,
+        }
+    }
+
+    /// Whether a bracket has a trailing comma in the source, so should stay split.
+    fn magic_comma(&self, bracket: &Bracket) -> bool {
+        match bracket.items.last() {
+            Some(last) => {
+                !(bracket.tuple && bracket.items.len() == 1)
+                    && self.has_trailing_comma(end(last.span()))
+            }
+            None => false,
+        }
+    }
+
+    fn flat_bracket(&self, bracket: &Bracket<'a>) -> Option<String> {
+        if self.has_comment(bracket.begin, bracket.end) || self.magic_comma(bracket) {
+            return None;
+        }
+        let items = bracket
+            .groups
+            .iter()
+            .flatten()
+            .map(|i| self.flat_item(bracket.items[*i]))
+            .collect::<Option<Vec<_>>>()?;
+        let comma = if bracket.tuple && items.len() == 1 {
+            ","
+        } else {
+            ""
+        };
+        Some(format!(
+            "{}{}{}{}",
+            bracket.open,
+            items.join(", "),
+            comma,
+            bracket.close
+        ))
+    }
+
+    /// Write a bracket on one line if it fits, otherwise split.
+    fn write_brackets(&mut self, bracket: &Bracket<'a>, indent: usize) {
+        match self.flat_bracket(bracket) {
+            // Splitting an empty bracket doesn't make anything shorter.
+            Some(flat) if self.fits(&flat) || bracket.items.is_empty() => self.write(&flat),
+            _ => self.write_bracket(bracket, indent),
+        }
+    }
+
+    /// Write a bracket split with one item per line.
+    fn write_bracket(&mut self, bracket: &Bracket<'a>, indent: usize) {
+        self.write(bracket.open);
+        self.end_line(
+            bracket
+                .items
+                .first()
+                .map_or(bracket.end, |x| begin(x.span())),
+        );
+
+        // Items are written in source order, so the comments come out in order, but each
+        // into its own buffer, so sorting moves the comments along with their item.
+        let outer = mem::take(&mut self.out);
+        let mut chunks = Vec::with_capacity(bracket.items.len());
+        for (i, item) in bracket.items.iter().enumerate() {
+            self.flush_leading(begin(item.span()), indent + 1, true);
+            self.indent(indent + 1);
+            self.write_item(*item, indent + 1);
+            self.write(",");
+            self.anchor = self.anchor.max(end(item.span()));
+            self.end_line(
+                bracket
+                    .items
+                    .get(i + 1)
+                    .map_or(bracket.end, |x| begin(x.span())),
+            );
+            chunks.push(mem::take(&mut self.out));
+        }
+        self.out = outer;
+        for (i, group) in bracket.groups.iter().enumerate() {
+            if i > 0 {
+                self.out.push('\n');
+            }
+            for item in group {
+                self.out.push_str(&chunks[*item]);
+            }
+        }
+
+        self.flush_comments(bracket.end, indent + 1, !bracket.items.is_empty());
+        self.indent(indent);
+        self.write(bracket.close);
+    }
+
+    fn flat_item(&self, item: Item<'a>) -> Option<String> {
+        Some(match item {
+            Item::Expr(x) => self.flat_expr(x, 0)?,
+            Item::String(x) => string_literal(self.text(x.span)),
+            Item::Entry(k, v) => format!("{}: {}", self.flat_expr(k, 0)?, self.flat_expr(v, 0)?),
+            Item::Argument(x) => self.flat_argument(x, false)?,
+            Item::RuleArgument(x) => self.flat_argument(x, true)?,
+            Item::Parameter(x) => self.flat_parameter(x)?,
+            Item::Load(local, name) => self.load_symbol(local, name),
+        })
+    }
+
+    fn write_item(&mut self, item: Item<'a>, indent: usize) {
+        match item {
+            Item::Expr(x) => self.write_expr(x, 0, indent),
+            Item::Entry(k, v) => {
+                self.write_expr(k, 0, indent);
+                self.write(": ");
+                self.write_expr(v, 0, indent);
+            }
+            Item::Argument(x) => self.write_argument(x, false, indent),
+            Item::RuleArgument(x) => self.write_argument(x, true, indent),
+            Item::Parameter(x) => self.write_parameter(x, indent),
+            Item::String(_) | Item::Load(..) => {
+                let flat = self.flat_item(item).unwrap_or_default();
+                self.write(&flat);
+            }
+        }
+    }
+
+    fn load_symbol(&self, local: &AstAssignIdent, name: &AstString) -> String {
+        let name_literal = string_literal(self.text(name.span));
+        if local.node.0 == name.node {
+            name_literal
+        } else {
+            format!("{} = {}", local.node.0, name_literal)
+        }
+    }
+
+    fn flat_argument(&self, arg: &'a AstArgument, rule: bool) -> Option<String> {
+        Some(match &arg.node {
+            ArgumentP::Positional(x) => self.flat_expr(x, 0)?,
+            ArgumentP::Named(name, x) => {
+                let value = match self.rule_list(name, x).filter(|_| rule) {
+                    Some(list) => self.flat_bracket(&list)?,
+                    None => self.flat_expr(x, 0)?,
+                };
+                format!("{} = {}", name.node, value)
+            }
+            ArgumentP::Args(x) => format!("*{}", self.flat_expr(x, 0)?),
+            ArgumentP::KwArgs(x) => format!("**{}", self.flat_expr(x, 0)?),
+        })
+    }
+
+    fn write_argument(&mut self, arg: &'a AstArgument, rule: bool, indent: usize) {
+        match &arg.node {
+            ArgumentP::Positional(x) => self.write_expr(x, 0, indent),
+            ArgumentP::Named(name, x) => {
+                self.write(&name.node);
+                self.write(" = ");
+                match self.rule_list(name, x).filter(|_| rule) {
+                    Some(list) => self.write_brackets(&list, indent),
+                    None => self.write_expr(x, 0, indent),
+                }
+            }
+            ArgumentP::Args(x) => {
+                self.write("*");
+                self.write_expr(x, 0, indent);
+            }
+            ArgumentP::KwArgs(x) => {
+                self.write("**");
+                self.write_expr(x, 0, indent);
+            }
+        }
+    }
+
+    fn flat_parameter(&self, param: &'a AstParameter) -> Option<String> {
+        let (prefix, name, ty, default) = match &param.node {
+            ParameterP::Normal(name, ty) => ("", name, ty, None),
+            ParameterP::WithDefaultValue(name, ty, default) => ("", name, ty, Some(default)),
+            ParameterP::NoArgs => return Some("*".to_owned()),
+            ParameterP::Args(name, ty) => ("*", name, ty, None),
+            ParameterP::KwArgs(name, ty) => ("**", name, ty, None),
+        };
+        let mut res = format!("{}{}", prefix, name.node.0);
+        if let Some(ty) = ty {
+            res.push_str(": ");
+            res.push_str(&self.flat_expr(ty, 0)?);
+        }
+        if let Some(default) = default {
+            res.push_str(" = ");
+            res.push_str(&self.flat_expr(default, 0)?);
+        }
+        Some(res)
+    }
+
+    fn write_parameter(&mut self, param: &'a AstParameter, indent: usize) {
+        let (prefix, name, ty, default) = match &param.node {
+            ParameterP::Normal(name, ty) => ("", name, ty, None),
+            ParameterP::WithDefaultValue(name, ty, default) => ("", name, ty, Some(default)),
+            ParameterP::NoArgs => return self.write("*"),
+            ParameterP::Args(name, ty) => ("*", name, ty, None),
+            ParameterP::KwArgs(name, ty) => ("**", name, ty, None),
+        };
+        self.write(prefix);
+        self.write(&name.node.0);
+        if let Some(ty) = ty {
+            self.write(": ");
+            self.write_expr(ty, 0, indent);
+        }
+        if let Some(default) = default {
+            self.write(" = ");
+            self.write_expr(default, 0, indent);
+        }
+    }
+
+    fn flat_assign(&self, assign: &'a AstAssign, top: bool) -> Option<String> {
+        Some(match &assign.node {
+            AssignP::Tuple(xs) => {
+                let xs = xs
+                    .iter()
+                    .map(|x| self.flat_assign(x, false))
+                    .collect::<Option<Vec<_>>>()?;
+                match xs.as_slice() {
+                    [x] => format!("({},)", x),
+                    _ if top && !xs.is_empty() => xs.join(", "),
+                    _ => format!("({})", xs.join(", ")),
+                }
+            }
+            AssignP::ArrayIndirection(x_i) => format!(
+                "{}[{}]",
+                self.flat_expr(&x_i.0, PRIMARY)?,
+                self.flat_expr(&x_i.1, 0)?
+            ),
+            AssignP::Dot(x, name) => format!("{}.{}", self.flat_expr(x, PRIMARY)?, name.node),
+            AssignP::Identifier(x) => x.node.0.clone(),
+        })
+    }
+
+    fn write_assign(&mut self, assign: &'a AstAssign, top: bool, indent: usize) {
+        match &assign.node {
+            AssignP::Tuple(xs) => {
+                let parens = !top || xs.len() < 2;
+                if parens {
+                    self.write("(");
+                }
+                for (i, x) in xs.iter().enumerate() {
+                    if i > 0 {
+                        self.write(", ");
+                    }
+                    self.write_assign(x, false, indent);
+                }
+                if xs.len() == 1 {
+                    self.write(",");
+                }
+                if parens {
+                    self.write(")");
+                }
+            }
+            AssignP::ArrayIndirection(x_i) => {
+                self.write_expr(&x_i.0, PRIMARY, indent);
+                self.write("[");
+                self.write_expr(&x_i.1, 0, indent);
+                self.write("]");
+            }
+            AssignP::Dot(x, name) => {
+                self.write_expr(x, PRIMARY, indent);
+                self.write(".");
+                self.write(&name.node);
+            }
+            AssignP::Identifier(x) => self.write(&x.node.0),
+        }
+    }
+
+    fn literal(&self, x: &AstLiteral) -> String {
+        match x {
+            AstLiteral::Int(x) => self.text(x.span).to_owned(),
+            AstLiteral::Float(x) => self.text(x.span).to_owned(),
+            AstLiteral::String(x) => string_literal(self.text(x.span)),
+        }
+    }
+
+    /// The expression on a single line, in parentheses if its precedence is below `prec`, or
+    /// `None` if it can't be written on one line.
+    fn flat_expr(&self, x: &'a AstExpr, prec: u8) -> Option<String> {
+        let res = self.flat_expr_unparenthesized(x)?;
+        if precedence(x) < prec {
+            Some(format!("({})", res))
+        } else {
+            Some(res)
+        }
+    }
+
+    fn flat_expr_unparenthesized(&self, x: &'a AstExpr) -> Option<String> {
+        if self.has_comment(begin(x.span), end(x.span)) {
+            return None;
+        }
+        Some(match &x.node {
+            ExprP::Tuple(xs) => self.flat_bracket(&self.tuple_bracket(x, xs))?,
+            ExprP::Dot(object, name) => {
+                format!("{}.{}", self.flat_expr(object, PRIMARY)?, name.node)
+            }
+            ExprP::Call(f, args) => format!(
+                "{}{}",
+                self.flat_expr(f, PRIMARY)?,
+                self.flat_bracket(&self.call_bracket(x, f, args))?
+            ),
+            ExprP::ArrayIndirection(x_i) => format!(
+                "{}[{}]",
+                self.flat_expr(&x_i.0, PRIMARY)?,
+                self.flat_expr(&x_i.1, 0)?
+            ),
+            ExprP::Slice(object, start, stop, stride) => {
+                let mut res = format!("{}[", self.flat_expr(object, PRIMARY)?);
+                if let Some(start) = start {
+                    res.push_str(&self.flat_expr(start, 0)?);
+                }
+                res.push(':');
+                if let Some(stop) = stop {
+                    res.push_str(&self.flat_expr(stop, 0)?);
+                }
+                if let Some(stride) = stride {
+                    res.push(':');
+                    res.push_str(&self.flat_expr(stride, 0)?);
+                }
+                res.push(']');
+                res
+            }
+            ExprP::Identifier(name, _) => name.node.clone(),
+            ExprP::Lambda(lambda) => {
+                let params = lambda
+                    .params
+                    .iter()
+                    .map(|x| self.flat_parameter(x))
+                    .collect::<Option<Vec<_>>>()?;
+                format!(
+                    "lambda{}{}: {}",
+                    if params.is_empty() { "" } else { " " },
+                    params.join(", "),
+                    self.flat_expr(&lambda.body, 0)?
+                )
+            }
+            ExprP::Literal(literal) => self.literal(literal),
+            ExprP::Not(x) => format!("not {}", self.flat_expr(x, 4)?),
+            ExprP::Minus(x) => format!("-{}", self.flat_expr(x, 12)?),
+            ExprP::Plus(x) => format!("+{}", self.flat_expr(x, 12)?),
+            ExprP::BitNot(x) => format!("~{}", self.flat_expr(x, 12)?),
+            ExprP::Op(lhs, op, rhs) => {
+                let (lhs_prec, rhs_prec) = operand_precedences(*op);
+                format!(
+                    "{}{}{}",
+                    self.flat_expr(lhs, lhs_prec)?,
+                    op,
+                    self.flat_expr(rhs, rhs_prec)?
+                )
+            }
+            ExprP::If(cond_then_else) => {
+                let (cond, then, otherwise) = &**cond_then_else;
+                format!(
+                    "{} if {} else {}",
+                    self.flat_expr(then, 2)?,
+                    self.flat_expr(cond, 2)?,
+                    self.flat_expr(otherwise, 0)?
+                )
+            }
+            ExprP::List(xs) => self.flat_bracket(&self.list_bracket(x, xs, false))?,
+            ExprP::Dict(xs) => self.flat_bracket(&self.bracket(
+                "{",
+                "}",
+                xs.iter().map(|(k, v)| Item::Entry(k, v)).collect(),
+                begin(x.span),
+                end(x.span),
+                false,
+                None,
+            ))?,
+            ExprP::ListComprehension(item, first, clauses) => format!(
+                "[{}{}]",
+                self.flat_expr(item, 0)?,
+                self.flat_clauses(first, clauses)?
+            ),
+            ExprP::DictComprehension(k_v, first, clauses) => format!(
+                "{{{}: {}{}}}",
+                self.flat_expr(&k_v.0, 0)?,
+                self.flat_expr(&k_v.1, 0)?,
+                self.flat_clauses(first, clauses)?
+            ),
+        })
+    }
+
+    fn flat_clauses(&self, first: &'a ForClause, clauses: &'a [Clause]) -> Option<String> {
+        let mut res = self.flat_for_clause(first)?;
+        for clause in clauses {
+            match clause {
+                ClauseP::For(x) => res.push_str(&self.flat_for_clause(x)?),
+                ClauseP::If(x) => {
+                    res.push_str(" if ");
+                    res.push_str(&self.flat_expr(x, 2)?);
+                }
+            }
+        }
+        Some(res)
+    }
+
+    fn flat_for_clause(&self, clause: &'a ForClause) -> Option<String> {
+        Some(format!(
+            " for {} in {}",
+            self.flat_assign(&clause.var, true)?,
+            self.flat_expr(&clause.over, 2)?
+        ))
+    }
+
+    /// Write an expression on one line if it fits, otherwise split its brackets.
+    fn write_expr(&mut self, x: &'a AstExpr, prec: u8, indent: usize) {
+        if let Some(flat) = self.flat_expr(x, prec) {
+            if self.fits(&flat) {
+                return self.write(&flat);
+            }
+        }
+        if let ExprP::Op(..) = &x.node {
+            return self.write_op_chain(x, indent);
+        }
+        let parens = precedence(x) < prec;
+        if parens {
+            self.write("(");
+        }
+        self.write_split_expr(x, indent);
+        if parens {
+            self.write(")");
+        }
+    }
+
+    fn write_split_expr(&mut self, x: &'a AstExpr, indent: usize) {
+        match &x.node {
+            ExprP::Tuple(xs) => {
+                let bracket = self.tuple_bracket(x, xs);
+                self.write_bracket(&bracket, indent);
+            }
+            ExprP::Dot(object, name) => {
+                self.write_expr(object, PRIMARY, indent);
+                self.write(".");
+                self.write(&name.node);
+            }
+            ExprP::Call(f, args) => {
+                self.write_expr(f, PRIMARY, indent);
+                let bracket = self.call_bracket(x, f, args);
+                self.write_brackets(&bracket, indent);
+            }
+            ExprP::ArrayIndirection(x_i) => {
+                self.write_expr(&x_i.0, PRIMARY, indent);
+                self.write("[");
+                self.write_expr(&x_i.1, 0, indent);
+                self.write("]");
+            }
+            ExprP::Slice(object, start, stop, stride) => {
+                self.write_expr(object, PRIMARY, indent);
+                self.write("[");
+                if let Some(start) = start {
+                    self.write_expr(start, 0, indent);
+                }
+                self.write(":");
+                if let Some(stop) = stop {
+                    self.write_expr(stop, 0, indent);
+                }
+                if let Some(stride) = stride {
+                    self.write(":");
+                    self.write_expr(stride, 0, indent);
+                }
+                self.write("]");
+            }
+            ExprP::Identifier(name, _) => self.write(&name.node),
+            ExprP::Lambda(lambda) => {
+                self.write("lambda");
+                for (i, param) in lambda.params.iter().enumerate() {
+                    self.write(if i == 0 { " " } else { ", " });
+                    self.write_parameter(param, indent);
+                }
+                self.write(": ");
+                self.write_expr(&lambda.body, 0, indent);
+            }
+            ExprP::Literal(literal) => {
+                let literal = self.literal(literal);
+                self.write(&literal);
+            }
+            ExprP::Not(x) => {
+                self.write("not ");
+                self.write_expr(x, 4, indent);
+            }
+            ExprP::Minus(x) => {
+                self.write("-");
+                self.write_expr(x, 12, indent);
+            }
+            ExprP::Plus(x) => {
+                self.write("+");
+                self.write_expr(x, 12, indent);
+            }
+            ExprP::BitNot(x) => {
+                self.write("~");
+                self.write_expr(x, 12, indent);
+            }
+            ExprP::Op(..) => self.write_op_chain(x, indent),
+            ExprP::If(cond_then_else) => {
+                let (cond, then, otherwise) = &**cond_then_else;
+                self.write_expr(then, 2, indent);
+                self.write(" if ");
+                self.write_expr(cond, 2, indent);
+                self.write(" else ");
+                self.write_expr(otherwise, 0, indent);
+            }
+            ExprP::List(xs) => {
+                let bracket = self.list_bracket(x, xs, false);
+                self.write_bracket(&bracket, indent);
+            }
+            ExprP::Dict(xs) => {
+                let bracket = self.bracket(
+                    "{",
+                    "}",
+                    xs.iter().map(|(k, v)| Item::Entry(k, v)).collect(),
+                    begin(x.span),
+                    end(x.span),
+                    false,
+                    None,
+                );
+                self.write_bracket(&bracket, indent);
+            }
+            ExprP::ListComprehension(item, first, clauses) => {
+                self.write_comprehension(x, "[", Item::Expr(item), first, clauses, "]", indent)
+            }
+            ExprP::DictComprehension(k_v, first, clauses) => self.write_comprehension(
+                x,
+                "{",
+                Item::Entry(&k_v.0, &k_v.1),
+                first,
+                clauses,
+                "}",
+                indent,
+            ),
+        }
+    }
+
+    /// Write a chain of binary operators of the same precedence in parentheses, with each
+    /// operand on its own line.
+    fn write_op_chain(&mut self, x: &'a AstExpr, indent: usize) {
+        let mut operands = Vec::new();
+        let mut ops = Vec::new();
+        let mut lhs = x;
+        while let ExprP::Op(l, op, r) = &lhs.node {
+            if !ops.is_empty() && binop_precedence(*op) != binop_precedence(ops[0]) {
+                break;
+            }
+            operands.push(&**r);
+            ops.push(*op);
+            lhs = l;
+            if operand_precedences(*op).0 != binop_precedence(*op) {
+                // Comparisons don't chain.
+                break;
+            }
+        }
+        operands.push(lhs);
+        operands.reverse();
+        ops.reverse();
+
+        let (lhs_prec, rhs_prec) = operand_precedences(ops[0]);
+        self.write("(");
+        for (i, operand) in operands.iter().enumerate() {
+            if i > 0 {
+                self.write(ops[i - 1].to_string().trim_end());
+            }
+            self.next_line(begin(operand.span), indent + 1);
+            self.write_expr(
+                operand,
+                if i == 0 { lhs_prec } else { rhs_prec },
+                indent + 1,
+            );
+        }
+        self.end_line(end(x.span));
+        self.flush_comments(end(x.span), indent + 1, false);
+        self.indent(indent);
+        self.write(")");
+    }
+
+    /// Write a comprehension split with its item and each clause on a separate line.
+    fn write_comprehension(
+        &mut self,
+        x: &'a AstExpr,
+        open: &str,
+        item: Item<'a>,
+        first: &'a ForClause,
+        clauses: &'a [Clause],
+        close: &str,
+        indent: usize,
+    ) {
+        self.write(open);
+        self.next_line(begin(item.span()), indent + 1);
+        self.write_item(item, indent + 1);
+        self.write_for_clause(first, indent + 1);
+        for clause in clauses {
+            match clause {
+                ClauseP::For(x) => self.write_for_clause(x, indent + 1),
+                ClauseP::If(x) => {
+                    self.next_line(begin(x.span), indent + 1);
+                    self.write("if ");
+                    self.write_expr(x, 2, indent + 1);
+                }
+            }
+        }
+        self.end_line(end(x.span));
+        self.flush_comments(end(x.span), indent + 1, false);
+        self.indent(indent);
+        self.write(close);
+    }
+
+    fn write_for_clause(&mut self, clause: &'a ForClause, indent: usize) {
+        self.next_line(begin(clause.var.span), indent);
+        self.write("for ");
+        self.write_assign(&clause.var, true, indent);
+        self.write(" in ");
+        self.write_expr(&clause.over, 2, indent);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use textwrap::dedent;
+
+    use super::*;
+    use crate::syntax::Dialect;
+
+    fn format(mode: FormatMode, source: &str) -> String {
+        let source = dedent(source).trim_start().to_owned();
+        let ast = AstModule::parse("test.star", source, &Dialect::Extended).unwrap();
+        let formatted = ast.format(mode);
+        let again = AstModule::parse("test.star", formatted.clone(), &Dialect::Extended)
+            .unwrap_or_else(|e| panic!("Formatted code does not parse: {}\n{}", e, formatted));
+        assert_eq!(
+            formatted,
+            again.format(mode),
+            "Formatting is not idempotent"
+        );
+        formatted
+    }
+
+    fn assert_format(mode: FormatMode, source: &str, expected: &str) {
+        assert_eq!(dedent(expected).trim_start(), format(mode, source));
+    }
+
+    #[test]
+    fn test_format_preserves_meaning() {
+        let source = r#"
+            x = 1+2*3 if a else -(b or c) and not d
+            y = (a, b)[0] , (1,)
+            z = {'a': [1, 2], "b": (lambda x, *args, **kwargs: x + 1)}
+            def f(a, b: int=1, *, c=None, **kw) -> str:
+                for (i, j) in enumerate(a): b += [j for j in range(i) if j % 2 for k in [j]]
+                if a: return b
+                elif b:
+                    pass
+                else:
+                    return {k: v for k, v in kw.items()}
+                return a[1:2], a[::2], a.b.c(*c)
+            "#;
+        let formatted = format(FormatMode::Extension, source);
+        let original = AstModule::parse("test.star", dedent(source), &Dialect::Extended).unwrap();
+        let reparsed = AstModule::parse("test.star", formatted, &Dialect::Extended).unwrap();
+        assert_eq!(
+            original.statement.node.to_string(),
+            reparsed.statement.node.to_string()
+        );
+    }
+
+    #[test]
+    fn test_format_layout() {
+        assert_format(
+            FormatMode::Extension,
+            r#"
+            x=[1,2 , 3]; y = 'hello'
+            z = 'it"s'
+
+
+            def  f( a,b = 2 ):
+                if a : return a
+                elif b: pass
+                else:
+                    return (a, b)
+            w = f(1,)
+            "#,
+            r#"
+            x = [1, 2, 3]
+            y = "hello"
+            z = 'it"s'
+
+            def f(a, b = 2):
+                if a:
+                    return a
+                elif b:
+                    pass
+                else:
+                    return a, b
+
+            w = f(
+                1,
+            )
+            "#,
+        );
+    }
+
+    #[test]
+    fn test_format_long_lines() {
+        assert_format(
+            FormatMode::Extension,
+            r#"
+            def some_function_name(first_argument, second_argument, third_argument, fourth_argument, fifth_argument):
+                return some_function_name(first_argument, [second_argument, third_argument], fourth_argument, fifth_argument)
+            "#,
+            r#"
+            def some_function_name(
+                first_argument,
+                second_argument,
+                third_argument,
+                fourth_argument,
+                fifth_argument,
+            ):
+                return some_function_name(
+                    first_argument,
+                    [second_argument, third_argument],
+                    fourth_argument,
+                    fifth_argument,
+                )
+            "#,
+        );
+    }
+
+    #[test]
+    fn test_format_comments() {
+        assert_format(
+            FormatMode::Extension,
+            r#"
+            # Leading comment.
+
+            x = [  # After the bracket.
+                # Before the item.
+                1,  # After the item.
+                2,
+                # Before the bracket.
+            ]
+            def f():
+                # In the body.
+                pass  # After pass.
+                # End of the body.
+
+            # End of the file.
+            "#,
+            r#"
+            # Leading comment.
+
+            x = [  # After the bracket.
+                # Before the item.
+                1,  # After the item.
+                2,
+                # Before the bracket.
+            ]
+
+            def f():
+                # In the body.
+                pass  # After pass.
+                # End of the body.
+
+            # End of the file.
+            "#,
+        );
+    }
+
+    #[test]
+    fn test_format_load() {
+        assert_format(
+            FormatMode::Extension,
+            r#"
+            load(":b.bzl", 'z', y = "x", a = "a")
+            "#,
+            r#"
+            load(":b.bzl", "a", y = "x", "z")
+            "#,
+        );
+    }
+
+    #[test]
+    fn test_format_build_file() {
+        assert_format(
+            FormatMode::Build,
+            r#"
+            cxx_library(deps = ["//foo:bar", ":baz", "cell//x:y"], visibility = ["PUBLIC"], name = "lib", srcs = ["b.cpp", "a.cpp"], compiler_flags = ["-b", "-a"])
+            export_file(name = "x")
+            "#,
+            r#"
+            cxx_library(
+                name = "lib",
+                srcs = ["a.cpp", "b.cpp"],
+                compiler_flags = ["-b", "-a"],
+                deps = [":baz", "//foo:bar", "cell//x:y"],
+                visibility = ["PUBLIC"],
+            )
+            export_file(name = "x")
+            "#,
+        );
+    }
+
+    #[test]
+    fn test_format_build_file_comments_move_with_items() {
+        assert_format(
+            FormatMode::Build,
+            r#"
+            rust_library(
+                deps = [
+                    # Needed for foo.
+                    ":foo",
+                    ":bar",  # Needed for bar.
+
+                    ":aaa",
+                ],
+                name = "lib",
+            )
+            "#,
+            r#"
+            rust_library(
+                name = "lib",
+                deps = [
+                    ":bar",  # Needed for bar.
+                    # Needed for foo.
+                    ":foo",
+
+                    ":aaa",
+                ],
+            )
+            "#,
+        );
+    }
+}
@@ -20,6 +20,7 @@
 pub use ast::AstModule;
 pub use dialect::Dialect;
 pub use dialect::DialectTypes;
+pub use format::FormatMode;
 pub use parser::AstLoad;
 
 #[cfg(test)]
@@ -32,6 +33,7 @@ mod testcases;
 pub(crate) mod ast;
 pub(crate) mod cursors;
 mod dialect;
+mod format;
 pub(crate) mod lexer;
 pub(crate) mod payload_map;
 pub(crate) mod validate;
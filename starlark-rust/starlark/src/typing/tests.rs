@@ -152,3 +152,19 @@ foo(**{1: "x"})
         r#"Expected type `{"string": ""}` but got `{"int": "string"}`, at filename:4:7-15"#
     );
 }
+
+#[test]
+fn test_set() {
+    let (errs, _, interface, approx) = typecheck(
+        r#"
+s = set([1, 2])
+s.add(3)
+b = s.issubset([1, 2, 3])
+   "#,
+        &HashMap::new(),
+    );
+    assert!(approx.is_empty());
+    assert!(errs.is_empty());
+    assert_eq!(interface.get("s").unwrap(), &Ty::name("set"));
+    assert_eq!(interface.get("b").unwrap(), &Ty::bool());
+}
@@ -32,6 +32,7 @@ pub mod none;
 pub mod range;
 pub mod record;
 pub mod regex;
+pub mod set;
 pub mod string;
 pub mod structs;
 pub mod tuple;
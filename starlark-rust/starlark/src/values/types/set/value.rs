@@ -0,0 +1,477 @@
+/*
+ * Copyright 2018 The Starlark in Rust Authors.
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::cell::Ref;
+use std::cell::RefCell;
+use std::fmt;
+use std::fmt::Debug;
+use std::fmt::Display;
+use std::mem;
+use std::ops::Deref;
+
+use allocative::Allocative;
+use display_container::fmt_container;
+use serde::ser::Error;
+use serde::Serialize;
+use starlark_derive::StarlarkDocs;
+
+use crate as starlark;
+use crate::any::ProvidesStaticType;
+use crate::coerce::coerce;
+use crate::coerce::Coerce;
+use crate::collections::Hashed;
+use crate::collections::SmallSet;
+use crate::environment::Methods;
+use crate::environment::MethodsStatic;
+use crate::starlark_type;
+use crate::values::dict::refcell::unleak_borrow;
+use crate::values::error::ValueError;
+use crate::values::set::SetRef;
+use crate::values::type_repr::StarlarkTypeRepr;
+use crate::values::AllocFrozenValue;
+use crate::values::AllocValue;
+use crate::values::Freeze;
+use crate::values::Freezer;
+use crate::values::FrozenHeap;
+use crate::values::FrozenStringValue;
+use crate::values::FrozenValue;
+use crate::values::Heap;
+use crate::values::StarlarkValue;
+use crate::values::Trace;
+use crate::values::Value;
+
+#[derive(
+    Clone,
+    Default,
+    Trace,
+    Debug,
+    ProvidesStaticType,
+    StarlarkDocs,
+    Allocative
+)]
+#[starlark_docs(builtin = "extension")]
+pub(crate) struct SetGen<T>(pub(crate) T);
+
+impl<'v, T: SetLike<'v>> Display for SetGen<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        display_set(&self.0.content().content, f)
+    }
+}
+
+impl<'v> Display for Set<'v> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        display_set(&self.content, f)
+    }
+}
+
+fn display_set(xs: &SmallSet<Value>, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    if xs.is_empty() {
+        write!(f, "{}()", Set::TYPE)
+    } else {
+        fmt_container(f, "set([", "])", xs.iter())
+    }
+}
+
+/// Define the set type.
+#[derive(Clone, Default, Trace, Debug, ProvidesStaticType, Allocative)]
+#[repr(transparent)]
+pub struct Set<'v> {
+    /// The data stored by the set. The values must all be hashable.
+    content: SmallSet<Value<'v>>,
+}
+
+impl<'v> StarlarkTypeRepr for Set<'v> {
+    fn starlark_type_repr() -> String {
+        SetGen::<FrozenSetData>::get_type_starlark_repr()
+    }
+}
+
+#[derive(Clone, Default, Debug, ProvidesStaticType, Allocative)]
+#[repr(transparent)]
+pub(crate) struct FrozenSetData {
+    /// The data stored by the set. The values must all be hashable.
+    content: SmallSet<FrozenValue>,
+}
+
+/// Alias is used in `StarlarkDocs` derive.
+type FrozenSet = SetGen<FrozenSetData>;
+
+unsafe impl<'v> Coerce<Set<'v>> for FrozenSetData {}
+
+impl<'v> AllocValue<'v> for Set<'v> {
+    fn alloc_value(self, heap: &'v Heap) -> Value<'v> {
+        heap.alloc_complex(SetGen(RefCell::new(self)))
+    }
+}
+
+impl AllocFrozenValue for FrozenSetData {
+    fn alloc_frozen_value(self, heap: &FrozenHeap) -> FrozenValue {
+        heap.alloc_simple(SetGen(self))
+    }
+}
+
+impl<'v> Set<'v> {
+    /// The result of calling `type()` on sets.
+    pub const TYPE: &'static str = "set";
+
+    /// Set type string as Starlark frozen string value.
+    pub fn get_type_value_static() -> FrozenStringValue {
+        SetGen::<FrozenSetData>::get_type_value_static()
+    }
+
+    /// Create a new [`Set`].
+    pub fn new(content: SmallSet<Value<'v>>) -> Self {
+        Self { content }
+    }
+
+    /// Number of elements in the set.
+    pub fn len(&self) -> usize {
+        self.content.len()
+    }
+
+    /// Is the set empty?
+    pub fn is_empty(&self) -> bool {
+        self.content.is_empty()
+    }
+
+    /// Iterate through the values in the set.
+    pub fn iter<'a>(&'a self) -> impl ExactSizeIterator<Item = Value<'v>> + 'a {
+        self.content.iter().copied()
+    }
+
+    /// Iterate through the values in the set, but retaining their hashes.
+    pub fn iter_hashed<'a>(&'a self) -> impl Iterator<Item = Hashed<Value<'v>>> + 'a
+    where
+        'v: 'a,
+    {
+        self.content.iter_hashed().map(|x| x.copied())
+    }
+
+    /// Is the given prehashed value in the set?
+    pub fn contains_hashed(&self, value: Hashed<Value<'v>>) -> bool {
+        self.content.contains_hashed(value.as_ref())
+    }
+
+    /// Insert a value into the set, returning `true` if it was not already present.
+    pub fn insert_hashed(&mut self, value: Hashed<Value<'v>>) -> bool {
+        self.content.insert_hashed(value)
+    }
+
+    /// Remove a value from the set, returning `true` if it was present.
+    pub fn remove_hashed(&mut self, value: Hashed<Value<'v>>) -> bool {
+        self.content.remove_hashed(value.as_ref())
+    }
+
+    /// Remove all elements from the set.
+    pub fn clear(&mut self) {
+        self.content.clear();
+    }
+
+    /// Is every element of this set also in `other`?
+    pub fn is_subset(&self, other: &Set<'v>) -> bool {
+        self.len() <= other.len() && self.iter_hashed().all(|x| other.contains_hashed(x))
+    }
+
+    /// The elements of this set followed by those of `other` which are not in this set.
+    pub fn union(&self, other: &Set<'v>) -> Set<'v> {
+        let mut content = self.content.clone();
+        for x in other.iter_hashed() {
+            content.insert_hashed(x);
+        }
+        Set::new(content)
+    }
+
+    /// The elements of this set which are also in `other`, in the order of this set.
+    pub fn intersection(&self, other: &Set<'v>) -> Set<'v> {
+        self.filter(|x| other.contains_hashed(x))
+    }
+
+    /// The elements of this set which are not in `other`, in the order of this set.
+    pub fn difference(&self, other: &Set<'v>) -> Set<'v> {
+        self.filter(|x| !other.contains_hashed(x))
+    }
+
+    /// The elements which are in exactly one of this set and `other`,
+    /// those of this set first.
+    pub fn symmetric_difference(&self, other: &Set<'v>) -> Set<'v> {
+        let mut res = self.difference(other);
+        for x in other.iter_hashed() {
+            if !self.contains_hashed(x) {
+                res.content.insert_hashed_unique_unchecked(x);
+            }
+        }
+        res
+    }
+
+    fn filter(&self, mut f: impl FnMut(Hashed<Value<'v>>) -> bool) -> Set<'v> {
+        let mut content = SmallSet::new();
+        for x in self.iter_hashed() {
+            if f(x) {
+                content.insert_hashed_unique_unchecked(x);
+            }
+        }
+        Set::new(content)
+    }
+}
+
+impl<'v> Freeze for SetGen<RefCell<Set<'v>>> {
+    type Frozen = SetGen<FrozenSetData>;
+    fn freeze(self, freezer: &Freezer) -> anyhow::Result<Self::Frozen> {
+        let content = self.0.into_inner().content.freeze(freezer)?;
+        Ok(SetGen(FrozenSetData { content }))
+    }
+}
+
+pub(crate) trait SetLike<'v>: Debug + Allocative {
+    type ContentRef<'a>: Deref<Target = Set<'v>>
+    where
+        Self: 'a,
+        'v: 'a;
+    fn content<'a>(&'a self) -> Self::ContentRef<'a>;
+    // These functions are unsafe for the same reason
+    // `StarlarkValue` iterator functions are unsafe.
+    unsafe fn iter_start(&self);
+    unsafe fn content_unchecked(&self) -> &Set<'v>;
+    unsafe fn iter_stop(&self);
+}
+
+impl<'v> SetLike<'v> for RefCell<Set<'v>> {
+    type ContentRef<'a> = Ref<'a, Set<'v>> where Self: 'a, 'v: 'a;
+
+    fn content<'a>(&'a self) -> Ref<'a, Set<'v>> {
+        self.borrow()
+    }
+
+    #[inline]
+    unsafe fn iter_start(&self) {
+        mem::forget(self.borrow());
+    }
+
+    #[inline]
+    unsafe fn iter_stop(&self) {
+        unleak_borrow(self);
+    }
+
+    #[inline]
+    unsafe fn content_unchecked(&self) -> &Set<'v> {
+        // SAFETY: this function contract is, caller must ensure that the value is borrowed.
+        self.try_borrow_unguarded().ok().unwrap_unchecked()
+    }
+}
+
+impl<'v> SetLike<'v> for FrozenSetData {
+    type ContentRef<'a> = &'a Set<'v> where Self: 'a, 'v: 'a;
+
+    fn content<'a>(&'a self) -> &'a Set<'v> {
+        coerce(self)
+    }
+
+    unsafe fn iter_start(&self) {}
+
+    unsafe fn iter_stop(&self) {}
+
+    unsafe fn content_unchecked(&self) -> &Set<'v> {
+        coerce(self)
+    }
+}
+
+pub(crate) fn set_methods() -> Option<&'static Methods> {
+    static RES: MethodsStatic = MethodsStatic::new();
+    RES.methods(crate::stdlib::set::set_methods)
+}
+
+impl<'v, T: SetLike<'v> + 'v> SetGen<T>
+where
+    Self: ProvidesStaticType,
+{
+    fn binop(
+        &self,
+        op: &str,
+        rhs: Value<'v>,
+        heap: &'v Heap,
+        f: impl FnOnce(&Set<'v>, &Set<'v>) -> Set<'v>,
+    ) -> anyhow::Result<Value<'v>> {
+        let rhs = SetRef::from_value(rhs)
+            .map_or_else(|| ValueError::unsupported_with(self, op, rhs), Ok)?;
+        Ok(heap.alloc(f(&self.0.content(), &rhs)))
+    }
+}
+
+impl<'v, T: SetLike<'v> + 'v> StarlarkValue<'v> for SetGen<T>
+where
+    Self: ProvidesStaticType,
+{
+    starlark_type!(Set::TYPE);
+
+    fn get_methods() -> Option<&'static Methods> {
+        set_methods()
+    }
+
+    fn collect_repr_cycle(&self, collector: &mut String) {
+        collector.push_str("set([...])");
+    }
+
+    fn to_bool(&self) -> bool {
+        !self.0.content().is_empty()
+    }
+
+    fn equals(&self, other: Value<'v>) -> anyhow::Result<bool> {
+        match SetRef::from_value(other) {
+            None => Ok(false),
+            Some(other) => {
+                let this = self.0.content();
+                Ok(this.len() == other.len() && this.is_subset(&other))
+            }
+        }
+    }
+
+    fn length(&self) -> anyhow::Result<i32> {
+        Ok(self.0.content().len() as i32)
+    }
+
+    fn is_in(&self, other: Value<'v>) -> anyhow::Result<bool> {
+        Ok(self.0.content().contains_hashed(other.get_hashed()?))
+    }
+
+    unsafe fn iterate(&self, me: Value<'v>, _heap: &'v Heap) -> anyhow::Result<Value<'v>> {
+        self.0.iter_start();
+        Ok(me)
+    }
+
+    unsafe fn iter_size_hint(&self, index: usize) -> (usize, Option<usize>) {
+        debug_assert!(index <= self.0.content().len());
+        let rem = self.0.content().len() - index;
+        (rem, Some(rem))
+    }
+
+    unsafe fn iter_next(&self, index: usize, _heap: &'v Heap) -> Option<Value<'v>> {
+        self.0.content_unchecked().content.get_index(index).copied()
+    }
+
+    unsafe fn iter_stop(&self) {
+        self.0.iter_stop();
+    }
+
+    fn bit_or(&self, rhs: Value<'v>, heap: &'v Heap) -> anyhow::Result<Value<'v>> {
+        self.binop("|", rhs, heap, Set::union)
+    }
+
+    fn bit_and(&self, rhs: Value<'v>, heap: &'v Heap) -> anyhow::Result<Value<'v>> {
+        self.binop("&", rhs, heap, Set::intersection)
+    }
+
+    fn sub(&self, rhs: Value<'v>, heap: &'v Heap) -> anyhow::Result<Value<'v>> {
+        self.binop("-", rhs, heap, Set::difference)
+    }
+
+    fn bit_xor(&self, rhs: Value<'v>, heap: &'v Heap) -> anyhow::Result<Value<'v>> {
+        self.binop("^", rhs, heap, Set::symmetric_difference)
+    }
+}
+
+impl<'v, T: SetLike<'v>> Serialize for SetGen<T> {
+    fn serialize<S>(&self, _serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        // Sets have no JSON equivalent, and silently producing a list would
+        // lose the distinction on a round trip.
+        Err(S::Error::custom(
+            "Value of type `set` cannot be serialized, convert it to a list first",
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::assert;
+    use crate::assert::Assert;
+
+    #[test]
+    fn test_set_repr() {
+        assert::eq("repr(set())", "'set()'");
+        assert::eq("repr(set([2, 'x', 2]))", "'set([2, \"x\"])'");
+        assert::eq("type(set())", "'set'");
+    }
+
+    #[test]
+    fn test_set_operators() {
+        assert::all_true(
+            r#"
+list(set([1, 2]) | set([3, 1])) == [1, 2, 3]
+list(set([1, 2, 3]) & set([3, 1])) == [1, 3]
+list(set([1, 2, 3]) - set([2])) == [1, 3]
+list(set([1, 2, 3]) ^ set([4, 2])) == [1, 3, 4]
+set([1, 2]) == set([2, 1])
+set([1, 2]) != set([1])
+set([1]) != [1]
+2 in set([1, 2])
+not set()
+"#,
+        );
+        assert::fail("set([1]) | [2]", "not supported");
+    }
+
+    #[test]
+    fn test_set_methods() {
+        assert::is_true(
+            r#"
+x = set()
+x.add(1)
+x.add(2)
+x.add(1)
+x.discard(3)
+x.remove(1)
+y = x.union([4], set([2, 5]))
+list(x) == [2] and list(y) == [2, 4, 5] and x.issubset(y) and not y.issubset([2])
+"#,
+        );
+        assert::fail("set([1]).remove(2)", "not found");
+        assert::fail("set([[1]])", "not hashable");
+    }
+
+    #[test]
+    fn test_set_mutation_during_iteration() {
+        assert::fail(
+            r#"
+x = set([1, 2])
+for v in x:
+    x.add(v + 10)
+"#,
+            "mutate an iterable",
+        );
+    }
+
+    #[test]
+    fn test_set_frozen() {
+        let mut a = Assert::new();
+        a.module("m", "s = set([1, 2])");
+        a.is_true("load('m', 's'); list(s | set([3])) == [1, 2, 3] and 2 in s");
+        a.fail("load('m', 's'); s.add(3)", "Immutable");
+    }
+
+    #[test]
+    fn test_set_bound_methods() {
+        assert::is_true("f = ''.count; len(set([f, f])) == 1");
+    }
+
+    #[test]
+    fn test_set_json() {
+        assert::fail("json.encode(set([1]))", "cannot be serialized");
+        assert::eq("json.encode(list(set([1])))", "'[1]'");
+    }
+}
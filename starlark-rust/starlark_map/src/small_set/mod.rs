@@ -244,6 +244,17 @@ impl<T> SmallSet<T> {
         self.0.remove(key).is_some()
     }
 
+    /// Remove the element from the set by a prehashed value.
+    ///
+    /// Time complexity of this operation is *O(N)* where *N* is the number of entries in the set.
+    #[inline]
+    pub fn remove_hashed<Q>(&mut self, key: Hashed<&Q>) -> bool
+    where
+        Q: ?Sized + Equivalent<T>,
+    {
+        self.0.remove_hashed(key).is_some()
+    }
+
     /// Insert entry if it doesn't exist.
     ///
     /// Return the resulting entry in the map.